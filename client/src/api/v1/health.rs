@@ -0,0 +1,20 @@
+use crate::{CouicClient, CouicError};
+use common::{Health, ServerVersion};
+
+pub struct HealthApi<'a> {
+    client: &'a CouicClient,
+}
+
+impl<'a> HealthApi<'a> {
+    pub(crate) const fn new(client: &'a CouicClient) -> Self {
+        Self { client }
+    }
+
+    pub fn get(&self) -> Result<Health, CouicError> {
+        self.client.get("/v1/health")
+    }
+
+    pub fn version(&self) -> Result<ServerVersion, CouicError> {
+        self.client.get("/v1/version")
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{CouicClient, CouicError};
+use common::{Lockdown, LockdownInput};
+
+pub struct LockdownApi<'a> {
+    client: &'a CouicClient,
+}
+
+impl<'a> LockdownApi<'a> {
+    pub(crate) const fn new(client: &'a CouicClient) -> Self {
+        Self { client }
+    }
+
+    /// Whether the daemon drops every packet not matched by an ignore entry
+    pub fn status(&self) -> Result<Lockdown, CouicError> {
+        self.client.get::<Lockdown>("/v1/lockdown")
+    }
+
+    /// Enable or disable lockdown. Enabling it while a management range of
+    /// the daemon is not ignored fails unless `force` is set.
+    pub fn set(&self, enabled: bool, force: bool) -> Result<Lockdown, CouicError> {
+        self.client.post::<Lockdown, LockdownInput>(
+            "/v1/lockdown",
+            Some(&LockdownInput { enabled, force }),
+        )
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{BufRead, BufReader};
+
+use crate::{CouicClient, CouicError};
+use common::{LogFilter, LogLevel, LogLevelChange, LogTail};
+
+pub struct LoggingApi<'a> {
+    client: &'a CouicClient,
+}
+
+impl<'a> LoggingApi<'a> {
+    pub(crate) const fn new(client: &'a CouicClient) -> Self {
+        Self { client }
+    }
+
+    /// Fetch the log filter in effect on the daemon
+    pub fn level(&self) -> Result<LogFilter, CouicError> {
+        self.client.get("/v1/log-level")
+    }
+
+    /// Change the log level of the daemon until the next change or restart
+    pub fn set_level(&self, level: LogLevel) -> Result<LogFilter, CouicError> {
+        self.client
+            .post("/v1/log-level", Some(&LogLevelChange { level }))
+    }
+
+    /// Restore the log filter the daemon started with
+    pub fn reset_level(&self) -> Result<LogFilter, CouicError> {
+        self.client.delete_with_body("/v1/log-level")
+    }
+
+    /// Fetch the last `lines` lines of the daemon log file
+    pub fn tail(&self, lines: usize) -> Result<LogTail, CouicError> {
+        self.client.get(&format!("/v1/logs/tail?lines={lines}"))
+    }
+
+    /// Pass the last `lines` lines of the daemon log file to `on_line`, then
+    /// each line appended to it, until the daemon closes the stream or the
+    /// client timeout, if any, expires
+    pub fn follow(&self, lines: usize, mut on_line: impl FnMut(&str)) -> Result<(), CouicError> {
+        let response = self
+            .client
+            .get_stream(&format!("/v1/logs/tail?lines={lines}&follow=true"))?;
+        // Server-sent events, one per line; comments keep the stream alive
+        for event_line in BufReader::new(response).lines() {
+            let event_line = event_line?;
+            if let Some(data) = event_line.strip_prefix("data:") {
+                on_line(data.strip_prefix(' ').unwrap_or(data));
+            }
+        }
+        Ok(())
+    }
+}
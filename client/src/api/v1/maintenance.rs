@@ -0,0 +1,18 @@
+use crate::{CouicClient, CouicError};
+use common::Compaction;
+
+pub struct MaintenanceApi<'a> {
+    client: &'a CouicClient,
+}
+
+impl<'a> MaintenanceApi<'a> {
+    pub(crate) const fn new(client: &'a CouicClient) -> Self {
+        Self { client }
+    }
+
+    /// Shrink the userspace maps of the daemon to their entries
+    pub fn compact(&self) -> Result<Compaction, CouicError> {
+        self.client
+            .post::<Compaction, ()>("/v1/maintenance/compact", None)
+    }
+}
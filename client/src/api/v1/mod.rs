@@ -1,11 +1,19 @@
 mod clients;
+mod health;
+mod lockdown;
+mod logging;
+mod maintenance;
 mod peer;
 mod policy;
 mod sets;
 mod stats;
 
 pub use clients::ClientsApi;
+pub use health::HealthApi;
+pub use lockdown::LockdownApi;
+pub use logging::LoggingApi;
+pub use maintenance::MaintenanceApi;
 pub use peer::PeerApi;
-pub use policy::PolicyApi;
+pub use policy::{BatchProgress, BatchSummary, PolicyApi};
 pub use sets::SetsApi;
 pub use stats::StatsApi;
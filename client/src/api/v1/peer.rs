@@ -1,5 +1,5 @@
 use crate::{CouicClient, CouicError};
-use common::PeerJob;
+use common::{PeerJob, PeerJobResult, PeerResponse};
 
 pub struct PeerApi<'a> {
     client: &'a CouicClient,
@@ -10,7 +10,15 @@ impl<'a> PeerApi<'a> {
         Self { client }
     }
 
-    pub fn drop(&self, jobs: &[PeerJob]) -> Result<Vec<PeerJob>, CouicError> {
-        self.client.post("/v1/drop/peer", Some(jobs))
+    /// Sends drop jobs to a peer, returning the outcome of each job
+    pub fn drop(&self, jobs: &[PeerJob]) -> Result<Vec<PeerJobResult>, CouicError> {
+        let response: PeerResponse = self.client.post("/v1/drop/peer", Some(jobs))?;
+        Ok(response.into_results())
+    }
+
+    /// Same as `drop`, with the request body compressed with gzip
+    pub fn drop_compressed(&self, jobs: &[PeerJob]) -> Result<Vec<PeerJobResult>, CouicError> {
+        let response: PeerResponse = self.client.post_gzip("/v1/drop/peer", jobs)?;
+        Ok(response.into_results())
     }
 }
@@ -1,5 +1,32 @@
-use crate::{CouicClient, CouicError};
-use common::{Entry, Policy, RawEntry};
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::{CouicClient, CouicError, WriteOptions};
+use common::{
+    Classification, Entry, EntryHits, NormalizedCidr, Policy, RawEntries, RawEntry,
+    RecentlyExpired, ReplaceSummary,
+};
+
+/// Progress of `add_batch`, reported after each entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchProgress {
+    /// Entries sent so far, failed ones included
+    pub done: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+/// Outcome of `add_batch`
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub added: Vec<Entry>,
+    /// Entries refused by the daemon, rate limited included, or whose
+    /// request failed
+    pub failed: Vec<(NormalizedCidr, CouicError)>,
+    /// Entries not sent, the daemon being unreachable
+    pub skipped: usize,
+}
 
 pub struct PolicyApi<'a> {
     client: &'a CouicClient,
@@ -14,15 +41,145 @@ impl<'a> PolicyApi<'a> {
         self.client.get(&format!("/v1/{policy}/{cidr}"))
     }
 
+    /// Packets matched by an entry since it was created
+    pub fn hits(&self, policy: Policy, cidr: &str) -> Result<EntryHits, CouicError> {
+        self.client.get(&format!("/v1/{policy}/{cidr}/hits"))
+    }
+
+    /// Lists the entries of `policy`, revalidating the previous listing of
+    /// this client rather than downloading it again when unchanged
     pub fn list(&self, policy: Policy) -> Result<Vec<Entry>, CouicError> {
-        self.client.get(&format!("/v1/{policy}"))
+        self.client.get_cached(&format!("/v1/{policy}"))
+    }
+
+    /// Iterates over the entries of `policy` as the daemon streams them, when
+    /// it supports `feature::ENTRIES_STREAM`, so that large policies are
+    /// processed with bounded memory. The whole stream must be read within
+    /// the client timeout, if any.
+    pub fn list_stream(
+        &self,
+        policy: Policy,
+    ) -> Result<impl Iterator<Item = Result<Entry, CouicError>> + use<>, CouicError> {
+        let response = self.client.get_stream(&format!("/v1/{policy}/stream"))?;
+        Ok(BufReader::new(response)
+            .lines()
+            .map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+
+    /// Lists the entries of `policy` expiring within `within`, or expired,
+    /// when the daemon supports `feature::EXPIRING_FILTER`
+    pub fn list_expiring(
+        &self,
+        policy: Policy,
+        within: Duration,
+    ) -> Result<Vec<Entry>, CouicError> {
+        self.client.get(&format!(
+            "/v1/{policy}?expiring_within={}",
+            within.as_secs()
+        ))
     }
 
     pub fn add(&self, policy: Policy, entry: &RawEntry) -> Result<Entry, CouicError> {
         self.client.post(&format!("/v1/{policy}"), Some(entry))
     }
 
+    /// Adds an entry, overlapping a protected range or broader than the
+    /// minimum prefix length being allowed when `force` is set
+    pub fn add_with_force(
+        &self,
+        policy: Policy,
+        entry: &RawEntry,
+        force: bool,
+    ) -> Result<Entry, CouicError> {
+        self.add_with_options(
+            policy,
+            entry,
+            WriteOptions {
+                force,
+                ..WriteOptions::default()
+            },
+        )
+    }
+
+    /// Adds an entry with the flags of `options`
+    pub fn add_with_options(
+        &self,
+        policy: Policy,
+        entry: &RawEntry,
+        options: WriteOptions,
+    ) -> Result<Entry, CouicError> {
+        self.client
+            .post_with_options(&format!("/v1/{policy}"), Some(entry), options)
+    }
+
+    /// Adds many entries with the flags of `options`, one request per entry
+    /// as the daemon has no batch endpoint, calling `progress` after each.
+    /// An entry refused by the daemon does not stop the others, rate limited
+    /// ones included once the client gave up waiting, but a request without
+    /// answer skips the remaining entries.
+    pub fn add_batch(
+        &self,
+        policy: Policy,
+        entries: &[RawEntry],
+        options: WriteOptions,
+        mut progress: impl FnMut(BatchProgress),
+    ) -> BatchSummary {
+        let mut summary = BatchSummary::default();
+        for (done, entry) in (1..).zip(entries) {
+            match self.add_with_options(policy, entry, options) {
+                Ok(added) => summary.added.push(added),
+                Err(e @ (CouicError::ApiError { .. } | CouicError::RateLimited { .. })) => {
+                    summary.failed.push((entry.cidr, e));
+                }
+                Err(e) => {
+                    summary.failed.push((entry.cidr, e));
+                    summary.skipped = entries.len().saturating_sub(done);
+                    return summary;
+                }
+            }
+            progress(BatchProgress {
+                done,
+                failed: summary.failed.len(),
+                total: entries.len(),
+            });
+        }
+        summary
+    }
+
+    /// Replaces the manual entries of a policy with `entries`, entries
+    /// loaded from sets being kept
+    pub fn replace(
+        &self,
+        policy: Policy,
+        entries: &RawEntries,
+        options: WriteOptions,
+    ) -> Result<ReplaceSummary, CouicError> {
+        self.client
+            .put_with_options(&format!("/v1/{policy}"), Some(entries), options)
+    }
+
+    /// Longest matching entries of an address and the resulting decision
+    pub fn classify(&self, ip: IpAddr) -> Result<Classification, CouicError> {
+        self.client.get(&format!("/v1/classify/{ip}"))
+    }
+
+    /// Last entries removed once expired, the most recent first
+    pub fn recently_expired(&self) -> Result<Vec<RecentlyExpired>, CouicError> {
+        self.client.get("/v1/recently-expired")
+    }
+
     pub fn delete(&self, policy: Policy, cidr: &str) -> Result<(), CouicError> {
         self.client.delete(&format!("/v1/{policy}/{cidr}"))
     }
+
+    /// Deletes an entry with the flags of `options`
+    pub fn delete_with_options(
+        &self,
+        policy: Policy,
+        cidr: &str,
+        options: WriteOptions,
+    ) -> Result<(), CouicError> {
+        self.client
+            .delete_with_options(&format!("/v1/{policy}/{cidr}"), options)
+    }
 }
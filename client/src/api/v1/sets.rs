@@ -1,5 +1,8 @@
-use crate::{CouicClient, CouicError};
-use common::{Policy, Set, SetName, SetSummary};
+use crate::{CouicClient, CouicError, WriteOptions};
+use common::{
+    Policy, Set, SetActivation, SetName, SetPatch, SetPatchResult, SetSummary, SetsReload,
+    SetsReloadResult, SetsReloadStatus, SetsStatus, SetsSummary,
+};
 
 pub struct SetsApi<'a> {
     client: &'a CouicClient,
@@ -14,10 +17,25 @@ impl<'a> SetsApi<'a> {
         self.client.get(&format!("/v1/sets/{policy}"))
     }
 
+    /// Counts the entries of all the sets of a policy, overlaps once
+    pub fn summary(&self, policy: Policy) -> Result<SetsSummary, CouicError> {
+        self.client.get(&format!("/v1/sets/{policy}/summary"))
+    }
+
     pub fn get(&self, policy: Policy, name: &SetName) -> Result<Set, CouicError> {
         self.client.get(&format!("/v1/sets/{policy}/{name}"))
     }
 
+    /// Gets a set along with its `ETag`, to update it with `update_if_match`
+    pub fn get_with_etag(
+        &self,
+        policy: Policy,
+        name: &SetName,
+    ) -> Result<(Set, Option<String>), CouicError> {
+        self.client
+            .get_with_etag(&format!("/v1/sets/{policy}/{name}"))
+    }
+
     pub fn create(&self, policy: Policy, request: &Set) -> Result<Set, CouicError> {
         self.client
             .post(&format!("/v1/sets/{policy}"), Some(request))
@@ -28,11 +46,92 @@ impl<'a> SetsApi<'a> {
             .put(&format!("/v1/sets/{policy}/{name}"), Some(set))
     }
 
+    /// Updates a set only if it still has the `etag` read before, failing
+    /// with a 412 API error otherwise. Returns the new `ETag` of the set.
+    pub fn update_if_match(
+        &self,
+        policy: Policy,
+        name: &SetName,
+        set: &Set,
+        etag: &str,
+    ) -> Result<(Set, Option<String>), CouicError> {
+        self.client
+            .put_if_match(&format!("/v1/sets/{policy}/{name}"), set, etag)
+    }
+
+    /// Adds and removes entries of a set without resending the others
+    pub fn patch(
+        &self,
+        policy: Policy,
+        name: &SetName,
+        patch: &SetPatch,
+    ) -> Result<SetPatchResult, CouicError> {
+        self.client
+            .patch(&format!("/v1/sets/{policy}/{name}"), patch)
+    }
+
     pub fn delete(&self, policy: Policy, name: &SetName) -> Result<(), CouicError> {
         self.client.delete(&format!("/v1/sets/{policy}/{name}"))
     }
 
-    pub fn reload(&self) -> Result<(), CouicError> {
-        self.client.post_empty("/v1/sets/reload")
+    /// Disables a set, its entries being removed on the next reload while
+    /// its file is kept
+    pub fn disable(&self, policy: Policy, name: &SetName) -> Result<SetActivation, CouicError> {
+        self.client
+            .post::<SetActivation, ()>(&format!("/v1/sets/{policy}/{name}/disable"), None)
+    }
+
+    /// Enables a disabled set, its entries being loaded on the next reload
+    pub fn enable(&self, policy: Policy, name: &SetName) -> Result<SetActivation, CouicError> {
+        self.client
+            .post::<SetActivation, ()>(&format!("/v1/sets/{policy}/{name}/enable"), None)
+    }
+
+    /// Reloads sets, returning the changes applied to each policy
+    pub fn reload(&self) -> Result<Vec<SetsReload>, CouicError> {
+        self.reload_with_force(false)
+    }
+
+    /// Reloads sets, drop sets overlapping a protected range or broader than
+    /// the minimum prefix length being allowed when `force` is set
+    pub fn reload_with_force(&self, force: bool) -> Result<Vec<SetsReload>, CouicError> {
+        let result: SetsReloadResult = self.client.post_with_options(
+            "/v1/sets/reload",
+            None::<&()>,
+            WriteOptions {
+                force,
+                ..WriteOptions::default()
+            },
+        )?;
+        Ok(result.reloads)
+    }
+
+    /// Reloads a single set, the other sets being left as loaded. Drop sets
+    /// overlapping a protected range or broader than the minimum prefix
+    /// length are allowed when `force` is set.
+    pub fn reload_set(
+        &self,
+        policy: Policy,
+        name: &SetName,
+        force: bool,
+    ) -> Result<Vec<SetsReload>, CouicError> {
+        let result: SetsReloadResult = self.client.post_with_options(
+            &format!("/v1/sets/{policy}/{name}/reload"),
+            None::<&()>,
+            WriteOptions {
+                force,
+                ..WriteOptions::default()
+            },
+        )?;
+        Ok(result.reloads)
+    }
+
+    pub fn reload_status(&self) -> Result<SetsReloadStatus, CouicError> {
+        self.client.get("/v1/sets/reload/status")
+    }
+
+    /// Time and changes of the last reload of each policy
+    pub fn status(&self) -> Result<SetsStatus, CouicError> {
+        self.client.get("/v1/sets/status")
     }
 }
@@ -1,5 +1,5 @@
 use crate::{CouicClient, CouicError};
-use common::{Policy, Stats, TagStats};
+use common::{Policy, Stats, StatsDelta, TagStats};
 
 pub struct StatsApi<'a> {
     client: &'a CouicClient,
@@ -10,11 +10,34 @@ impl<'a> StatsApi<'a> {
         Self { client }
     }
 
+    /// Fetch the stats, revalidating the previous ones of this client rather
+    /// than downloading them again when unchanged
     pub fn get(&self) -> Result<Stats, CouicError> {
-        self.client.get("/v1/stats")
+        self.client.get_cached("/v1/stats")
+    }
+
+    /// Fetch traffic rates, sampled over `window_secs` or, when `None`, since
+    /// the previous call
+    pub fn delta(&self, window_secs: Option<u64>) -> Result<StatsDelta, CouicError> {
+        let endpoint = window_secs.map_or_else(
+            || "/v1/stats/delta".to_string(),
+            |window_secs| format!("/v1/stats/delta?window_secs={window_secs}"),
+        );
+        self.client.get(&endpoint)
     }
 
     pub fn tag(&self, policy: Policy) -> Result<TagStats, CouicError> {
         self.client.get(&format!("/v1/stats/tags/{policy}"))
     }
+
+    /// Zero the XDP, per tag and per entry counters, entries being kept
+    pub fn reset(&self) -> Result<(), CouicError> {
+        self.client.post_empty("/v1/stats/reset")
+    }
+
+    /// Fetch metrics in the Prometheus/OpenMetrics text exposition format
+    pub fn metrics_prometheus(&self) -> Result<String, CouicError> {
+        self.client
+            .get_text("/v1/metrics?format=prometheus", "text/plain")
+    }
 }
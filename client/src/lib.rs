@@ -1,18 +1,57 @@
-use reqwest::Url;
-use reqwest::blocking::{Client as ReqwestClient, Response};
-use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
+use flate2::{Compression, write::GzEncoder};
+use reqwest::blocking::{
+    Client as ReqwestClient, ClientBuilder as ReqwestClientBuilder, RequestBuilder, Response,
+};
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, ETAG, HeaderMap, HeaderValue, IF_MATCH,
+    IF_NONE_MATCH, RETRY_AFTER, USER_AGENT,
+};
+use reqwest::{Identity, StatusCode, Url};
+use std::collections::HashMap;
 use std::fmt::Write;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::time::Duration;
-use std::{fs, io};
+use std::{fs, io, thread};
 
 use uuid::Uuid;
 
-use common::{ClientFile, CompositeError, ErrorCode};
+use common::{
+    ClientFile, ClientName, CompositeError, DRY_RUN_HEADER, ErrorCode, ErrorDetail, FORCE_HEADER,
+    PROPAGATE_HEADER, ServerVersion,
+};
 
 mod api;
 
-pub use api::v1::{ClientsApi, PeerApi, PolicyApi, SetsApi, StatsApi};
+pub use api::v1::{
+    BatchProgress, BatchSummary, ClientsApi, HealthApi, LockdownApi, LoggingApi, MaintenanceApi,
+    PeerApi, PolicyApi, SetsApi, StatsApi,
+};
+
+/// Flags of a write request, sent as headers when they differ from the
+/// server defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Bypass the protected ranges and minimum prefix guards on drop
+    /// additions
+    pub force: bool,
+    /// Forward the change to peers and reporting
+    pub propagate: bool,
+    /// Check an entry creation without applying it, when the daemon supports
+    /// `feature::DRY_RUN`
+    pub dry_run: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            force: false,
+            propagate: true,
+            dry_run: false,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, Default)]
 pub enum ApiVersion {
@@ -22,6 +61,18 @@ pub enum ApiVersion {
 
 pub struct CouicClientBuilder {
     version: ApiVersion,
+    user_agent: Option<String>,
+    timeout: Option<Duration>,
+    retries: Retries,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+}
+
+/// Resending of the requests refused with `429 Too Many Requests`
+#[derive(Debug, Clone, Copy)]
+struct Retries {
+    max: u32,
+    max_wait: Duration,
 }
 
 impl CouicClientBuilder {
@@ -29,6 +80,14 @@ impl CouicClientBuilder {
     pub fn new() -> Self {
         Self {
             version: ApiVersion::default(),
+            user_agent: None,
+            timeout: Some(TIMEOUT),
+            retries: Retries {
+                max: 0,
+                max_wait: MAX_RETRY_WAIT,
+            },
+            pool_idle_timeout: Some(POOL_IDLE_TIMEOUT),
+            pool_max_idle_per_host: POOL_MAX_IDLE_PER_HOST,
         }
     }
 
@@ -38,12 +97,80 @@ impl CouicClientBuilder {
         self
     }
 
+    /// Product identifying the tool embedding the client, e.g. `mytool/1.2`,
+    /// sent ahead of the client's own in the `User-Agent` header
+    #[must_use]
+    pub fn user_agent(mut self, product: impl Into<String>) -> Self {
+        self.user_agent = Some(product.into());
+        self
+    }
+
+    /// Total time allowed for a request, `None` disabling the limit.
+    /// Defaults to [`TIMEOUT`].
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Times a request refused with `429 Too Many Requests` is sent again,
+    /// after the delay of its `Retry-After` header, or with an exponential
+    /// backoff from one second without it. Defaults to 0.
+    #[must_use]
+    pub const fn rate_limit_retries(mut self, retries: u32) -> Self {
+        self.retries.max = retries;
+        self
+    }
+
+    /// Longest delay waited before resending a rate limited request, a longer
+    /// `Retry-After` returning [`CouicError::RateLimited`] at once. Defaults
+    /// to [`MAX_RETRY_WAIT`].
+    #[must_use]
+    pub const fn max_retry_wait(mut self, wait: Duration) -> Self {
+        self.retries.max_wait = wait;
+        self
+    }
+
+    /// Time an idle connection is kept open to be reused by the next
+    /// request, `None` keeping it until the daemon closes it. Defaults to
+    /// [`POOL_IDLE_TIMEOUT`].
+    #[must_use]
+    pub const fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Idle connections kept open to the daemon, 0 opening a connection per
+    /// request. Defaults to [`POOL_MAX_IDLE_PER_HOST`].
+    #[must_use]
+    pub const fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
     pub fn build_local(self, config: LocalConfig) -> Result<CouicClient, CouicError> {
-        CouicClient::new_local(config, self.version)
+        CouicClient::new_local(config, &self)
     }
 
     pub fn build_remote(self, config: &RemoteConfig) -> Result<CouicClient, CouicError> {
-        CouicClient::new_remote(config, self.version)
+        CouicClient::new_remote(config, &self)
+    }
+
+    /// HTTP client sending `headers`, with the timeout and connection pool
+    /// of the builder
+    fn http_client(&self, headers: HeaderMap) -> ReqwestClientBuilder {
+        ReqwestClient::builder()
+            .default_headers(headers)
+            .timeout(self.timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+    }
+
+    fn user_agent_value(&self) -> String {
+        self.user_agent.as_ref().map_or_else(
+            || USER_AGENT_VALUE.to_string(),
+            |product| format!("{product} {USER_AGENT_VALUE}"),
+        )
     }
 }
 
@@ -56,7 +183,22 @@ impl Default for CouicClientBuilder {
 pub const NAME: &str = "CouicClient";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+/// Long enough for a tool polling every few seconds to keep its connection,
+/// short enough not to hold daemon connections for idle tools
+pub const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Requests are sent one at a time, a couple of connections covering a
+/// client shared between threads
+pub const POOL_MAX_IDLE_PER_HOST: usize = 4;
+/// First delay between two sends of a rate limited request without
+/// `Retry-After`
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
 const USER_AGENT_VALUE: &str = concat!("CouicClient/", env!("CARGO_PKG_VERSION"));
+/// Permission bits a client certificate must not have: write access by group
+/// or others
+const CLIENT_CERT_FORBIDDEN_MODE: u32 = 0o022;
+/// Permission bits a client key must not have: any access by group or others
+const CLIENT_KEY_FORBIDDEN_MODE: u32 = 0o077;
 
 #[derive(Debug, thiserror::Error)]
 pub enum CouicError {
@@ -64,6 +206,12 @@ pub enum CouicError {
     Http(#[from] reqwest::Error),
     #[error("{}", format_api_error(*.status, error))]
     ApiError { status: u16, error: CompositeError },
+    /// `429 Too Many Requests`, with the delay of the `Retry-After` header
+    #[error("{}", format_rate_limited(*.retry_after, error))]
+    RateLimited {
+        retry_after: Option<Duration>,
+        error: CompositeError,
+    },
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
     #[error("TOML error: {0}")]
@@ -76,6 +224,54 @@ pub enum CouicError {
     UrlParse(#[from] url::ParseError),
 }
 
+impl CouicError {
+    /// Per-field details of an API error rejecting the request content, keyed
+    /// by field name (e.g. `cidr`, `entries[2]`), if the daemon sent any
+    #[must_use]
+    pub fn validation_fields(&self) -> Option<&HashMap<String, ErrorDetail>> {
+        if let Self::ApiError { error, .. } = self
+            && !error.errors.is_empty()
+        {
+            Some(&error.errors)
+        } else {
+            None
+        }
+    }
+}
+
+fn invalid_input(message: String) -> CouicError {
+    CouicError::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+/// Check `path` is a regular file without any of the `forbidden` permission
+/// bits
+fn check_file_perms(path: &Path, forbidden: u32, what: &str) -> Result<(), CouicError> {
+    let metadata = fs::metadata(path).map_err(|e| {
+        CouicError::Io(io::Error::new(
+            e.kind(),
+            format!("{what} {}: {e}", path.display()),
+        ))
+    })?;
+    if !metadata.is_file() {
+        return Err(invalid_input(format!(
+            "{what} {} is not a file",
+            path.display()
+        )));
+    }
+    let mode = metadata.permissions().mode();
+    if mode & forbidden != 0 {
+        return Err(CouicError::Io(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "{what} {} has unsafe permissions (mode {:o})",
+                path.display(),
+                mode & 0o777
+            ),
+        )));
+    }
+    Ok(())
+}
+
 fn format_api_error(status: u16, error: &CompositeError) -> String {
     let mut msg = format!(
         "API error:\n  Status: {}\n  Code: {}\n  Message: {}",
@@ -87,14 +283,43 @@ fn format_api_error(status: u16, error: &CompositeError) -> String {
             let _ = write!(msg, "\n    {field}: {detail}");
         }
     }
+    if error.code == ErrorCode::Efull {
+        msg.push_str(
+            "\n  Hint: the eBPF map capacity is reached, remove entries or wait for expirations",
+        );
+    }
+    msg
+}
+
+fn format_rate_limited(retry_after: Option<Duration>, error: &CompositeError) -> String {
+    let mut msg = format!("Rate limited: {}", error.message);
+    if let Some(delay) = retry_after {
+        let _ = write!(msg, ", retry in {}s", delay.as_secs());
+    }
     msg
 }
 
+/// Delay asked by a `Retry-After` header in seconds, the daemon never sending
+/// the HTTP date form
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
 #[derive(Debug)]
 pub struct CouicClient {
     base_url: Url,
     client: ReqwestClient,
     version: ApiVersion,
+    retries: Retries,
+    /// Last body received from each revalidated endpoint, with its `ETag`
+    cache: Mutex<HashMap<String, (String, String)>>,
 }
 
 #[derive(Debug, Clone)]
@@ -106,6 +331,8 @@ pub enum LocalCredential {
 pub struct LocalConfig {
     pub socket: String,
     pub credential: LocalCredential,
+    /// Client whose file `credential` must be, when it is a file
+    pub expected_client: Option<ClientName>,
 }
 
 impl LocalConfig {
@@ -114,6 +341,7 @@ impl LocalConfig {
         Self {
             socket: socket.into(),
             credential: LocalCredential::File(client_file.into()),
+            expected_client: None,
         }
     }
 
@@ -122,19 +350,52 @@ impl LocalConfig {
         Self {
             socket: socket.into(),
             credential: LocalCredential::Token(token),
+            expected_client: None,
         }
     }
+
+    /// Refuse a client file other than the one of `name`, e.g.
+    /// `couicctl.toml` for the default client
+    #[must_use]
+    pub fn expect_client(mut self, name: ClientName) -> Self {
+        self.expected_client = Some(name);
+        self
+    }
 }
 
 pub struct RemoteConfig {
-    pub token: Uuid,
-    pub host: String,
-    pub port: u16,
-    pub tls: bool,
+    token: Uuid,
+    host: String,
+    port: u16,
+    tls: bool,
+    /// PEM certificate and private key presented to a daemon requiring
+    /// mutual TLS
+    client_identity: Option<(String, String)>,
+}
+
+impl RemoteConfig {
+    #[must_use]
+    pub fn new(host: impl Into<String>, port: u16, token: Uuid, tls: bool) -> Self {
+        Self {
+            token,
+            host: host.into(),
+            port,
+            tls,
+            client_identity: None,
+        }
+    }
+
+    /// Present the PEM certificate `cert` to a daemon requiring mutual TLS,
+    /// `key` being its private key, only accessible by its owner
+    #[must_use]
+    pub fn client_identity(mut self, cert: impl Into<String>, key: impl Into<String>) -> Self {
+        self.client_identity = Some((cert.into(), key.into()));
+        self
+    }
 }
 
 impl CouicClient {
-    fn new_local(config: LocalConfig, version: ApiVersion) -> Result<Self, CouicError> {
+    fn new_local(config: LocalConfig, builder: &CouicClientBuilder) -> Result<Self, CouicError> {
         let socket = config.socket;
         if !Path::new(&socket).exists() {
             return Err(CouicError::Io(io::Error::new(
@@ -144,61 +405,97 @@ impl CouicClient {
         }
 
         let token = match config.credential {
-            LocalCredential::File(path) => Self::load_client_file(&path)?.token,
+            LocalCredential::File(path) => {
+                Self::load_client_file(&path, config.expected_client.as_ref())?.token
+            }
             LocalCredential::Token(token) => token,
         };
-        let headers = Self::set_headers(&token.to_string())?;
+        let headers = Self::set_headers(&token.to_string(), &builder.user_agent_value())?;
 
-        let client = ReqwestClient::builder()
-            .default_headers(headers)
-            .timeout(TIMEOUT)
-            .unix_socket(socket)
-            .build()?;
+        let client = builder.http_client(headers).unix_socket(socket).build()?;
 
         let base_url = Url::parse("http://localhost")?;
 
         Ok(Self {
             base_url,
+            cache: Mutex::default(),
             client,
-            version,
+            version: builder.version,
+            retries: builder.retries,
         })
     }
 
-    fn new_remote(config: &RemoteConfig, version: ApiVersion) -> Result<Self, CouicError> {
+    fn new_remote(config: &RemoteConfig, builder: &CouicClientBuilder) -> Result<Self, CouicError> {
         let scheme = if config.tls { "https" } else { "http" };
         let base_url = Url::parse(&format!("{scheme}://{}:{}", config.host, config.port))?;
-        let headers = Self::set_headers(&config.token.to_string())?;
+        let headers = Self::set_headers(&config.token.to_string(), &builder.user_agent_value())?;
 
-        let client = ReqwestClient::builder()
-            .default_headers(headers)
-            .timeout(TIMEOUT)
-            .build()?;
+        let mut client = builder.http_client(headers);
+        if let Some((cert, key)) = &config.client_identity {
+            if !config.tls {
+                return Err(invalid_input(
+                    "A client certificate requires TLS to be enabled".to_string(),
+                ));
+            }
+            client = client.identity(Self::load_identity(cert, key)?);
+        }
 
         Ok(Self {
             base_url,
-            client,
-            version,
+            cache: Mutex::default(),
+            client: client.build()?,
+            version: builder.version,
+            retries: builder.retries,
         })
     }
 
+    /// Load a PEM certificate and key, checked not to be writable, for the
+    /// certificate, or readable, for the key, by group or others
+    fn load_identity(cert: &str, key: &str) -> Result<Identity, CouicError> {
+        check_file_perms(
+            Path::new(cert),
+            CLIENT_CERT_FORBIDDEN_MODE,
+            "Client certificate",
+        )?;
+        check_file_perms(Path::new(key), CLIENT_KEY_FORBIDDEN_MODE, "Client key")?;
+        let mut pem = fs::read(cert)?;
+        pem.push(b'\n');
+        pem.extend(fs::read(key)?);
+        Ok(Identity::from_pem(&pem)?)
+    }
+
     #[must_use]
     pub const fn info(&self) -> &Url {
         &self.base_url
     }
 
-    fn load_client_file<P: AsRef<Path>>(path: P) -> Result<ClientFile, CouicError> {
+    /// Load the client file at `path`, checked to be named after a client,
+    /// and after `expected` when given
+    fn load_client_file<P: AsRef<Path>>(
+        path: P,
+        expected: Option<&ClientName>,
+    ) -> Result<ClientFile, CouicError> {
         let client_file = path.as_ref();
-        if client_file.is_file()
-            && client_file.extension().and_then(|ext| ext.to_str()) == Some("toml")
-        {
-            let content = fs::read_to_string(client_file)?;
-            let client: ClientFile = toml::de::from_str(&content)?;
-            return Ok(client);
+        ClientFile::client_name(client_file, expected).map_err(|e| invalid_input(e.message))?;
+        if !client_file.is_file() {
+            return Err(invalid_input(format!(
+                "Client file {} does not exist",
+                client_file.display()
+            )));
         }
-        Err(CouicError::Io(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid client file",
-        )))
+        if expected.is_some() {
+            // A link named after the expected client must lead to its file
+            ClientFile::client_name(&fs::canonicalize(client_file)?, expected)
+                .map_err(|e| invalid_input(e.message))?;
+        }
+        let content = fs::read_to_string(client_file)?;
+        toml::de::from_str(&content).map_err(|e| {
+            invalid_input(format!(
+                "{} is not a client file: {}",
+                client_file.display(),
+                e.message()
+            ))
+        })
     }
 
     #[must_use]
@@ -234,6 +531,40 @@ impl CouicClient {
         }
     }
 
+    #[must_use]
+    pub const fn health(&self) -> api::v1::HealthApi<'_> {
+        match self.version {
+            ApiVersion::V1 => api::v1::HealthApi::new(self),
+        }
+    }
+
+    #[must_use]
+    pub const fn logging(&self) -> api::v1::LoggingApi<'_> {
+        match self.version {
+            ApiVersion::V1 => api::v1::LoggingApi::new(self),
+        }
+    }
+
+    #[must_use]
+    pub const fn lockdown(&self) -> api::v1::LockdownApi<'_> {
+        match self.version {
+            ApiVersion::V1 => api::v1::LockdownApi::new(self),
+        }
+    }
+
+    #[must_use]
+    pub const fn maintenance(&self) -> api::v1::MaintenanceApi<'_> {
+        match self.version {
+            ApiVersion::V1 => api::v1::MaintenanceApi::new(self),
+        }
+    }
+
+    /// Version and optional features of the daemon, to adapt to what it
+    /// supports, e.g. `server_version()?.supports(feature::SET_PATCH)`
+    pub fn server_version(&self) -> Result<ServerVersion, CouicError> {
+        self.health().version()
+    }
+
     #[must_use]
     pub const fn peer(&self) -> api::v1::PeerApi<'_> {
         match self.version {
@@ -245,9 +576,9 @@ impl CouicClient {
         Ok(self.base_url.join(endpoint)?)
     }
 
-    fn set_headers(token: &str) -> Result<HeaderMap, CouicError> {
+    fn set_headers(token: &str, user_agent: &str) -> Result<HeaderMap, CouicError> {
         let mut headers = HeaderMap::with_capacity(4);
-        headers.insert(USER_AGENT, HeaderValue::from_static(USER_AGENT_VALUE));
+        headers.insert(USER_AGENT, HeaderValue::from_str(user_agent)?);
         headers.insert(
             AUTHORIZATION,
             HeaderValue::from_str(&format!("Bearer {token}"))?,
@@ -257,32 +588,78 @@ impl CouicClient {
         Ok(headers)
     }
 
-    fn parse_api_error(status: u16, text: &str) -> CouicError {
-        let error = serde_json::from_str(text)
+    /// Error of a response whose status is not a success
+    fn api_error(response: Response) -> CouicError {
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        let text = match response.text() {
+            Ok(text) => text,
+            Err(e) => return e.into(),
+        };
+        let error = serde_json::from_str(&text)
             .unwrap_or_else(|e| CompositeError::new(ErrorCode::Einternal, &e.to_string()));
-        CouicError::ApiError { status, error }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            CouicError::RateLimited { retry_after, error }
+        } else {
+            CouicError::ApiError {
+                status: status.as_u16(),
+                error,
+            }
+        }
     }
 
     fn handle_response<T: serde::de::DeserializeOwned>(
         response: Response,
     ) -> Result<T, CouicError> {
-        let status = response.status();
+        if !response.status().is_success() {
+            return Err(Self::api_error(response));
+        }
         let text = response.text()?;
+        Ok(serde_json::from_str(&text)?)
+    }
 
-        if status.is_success() {
-            Ok(serde_json::from_str(&text)?)
-        } else {
-            Err(Self::parse_api_error(status.as_u16(), &text))
+    /// Sends `request`, and again up to `rate_limit_retries` times while it
+    /// is refused with `429 Too Many Requests` and the delay asked is short
+    /// enough. The last response is returned whatever its status.
+    fn send(&self, request: RequestBuilder) -> Result<Response, CouicError> {
+        let mut retries = self.retries.max;
+        let mut backoff = RETRY_BACKOFF;
+        loop {
+            // Requests whose body cannot be cloned are sent once
+            let Some(attempt) = (retries > 0).then(|| request.try_clone()).flatten() else {
+                return Ok(request.send()?);
+            };
+            let response = attempt.send()?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            let wait = retry_after(response.headers()).unwrap_or(backoff);
+            if wait > self.retries.max_wait {
+                return Ok(response);
+            }
+            thread::sleep(wait);
+            backoff = backoff.saturating_mul(2);
+            retries = retries.saturating_sub(1);
         }
     }
 
+    /// Same as `handle_response`, along with the `ETag` header if any
+    fn handle_response_with_etag<T: serde::de::DeserializeOwned>(
+        response: Response,
+    ) -> Result<(T, Option<String>), CouicError> {
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Self::handle_response(response).map(|body| (body, etag))
+    }
+
     fn handle_empty_response(response: Response) -> Result<(), CouicError> {
-        let status = response.status();
-        if status.is_success() {
+        if response.status().is_success() {
             Ok(())
         } else {
-            let text = response.text()?;
-            Err(Self::parse_api_error(status.as_u16(), &text))
+            Err(Self::api_error(response))
         }
     }
 
@@ -291,43 +668,603 @@ impl CouicClient {
         endpoint: &str,
     ) -> Result<T, CouicError> {
         let url = self.url(endpoint)?;
-        let response = self.client.get(url).send()?;
+        let response = self.send(self.client.get(url))?;
         Self::handle_response(response)
     }
 
+    /// Response to a GET of `endpoint` whose body is read as it arrives,
+    /// bounded by the request timeout
+    pub(crate) fn get_stream(&self, endpoint: &str) -> Result<Response, CouicError> {
+        let url = self.url(endpoint)?;
+        let response = self.send(self.client.get(url))?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(Self::api_error(response))
+        }
+    }
+
+    /// Same as `get`, sending the `ETag` of the body last received from
+    /// `endpoint` so an unchanged one is not downloaded again
+    pub(crate) fn get_cached<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+    ) -> Result<T, CouicError> {
+        let url = self.url(endpoint)?;
+        let cached = self.lock_cache().get(endpoint).cloned();
+        let mut request = self.client.get(url);
+        if let Some((etag, _)) = &cached {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+        let response = self.send(request)?;
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED
+            && let Some((_, body)) = cached
+        {
+            return Ok(serde_json::from_str(&body)?);
+        }
+        if !status.is_success() {
+            return Err(Self::api_error(response));
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let text = response.text()?;
+        let body = serde_json::from_str(&text)?;
+        if let Some(etag) = etag {
+            self.lock_cache().insert(endpoint.to_string(), (etag, text));
+        }
+        Ok(body)
+    }
+
+    fn lock_cache(&self) -> MutexGuard<'_, HashMap<String, (String, String)>> {
+        // The cache is only an optimization, a poisoned one is still usable
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Same as `get`, along with the `ETag` of the resource
+    pub(crate) fn get_with_etag<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+    ) -> Result<(T, Option<String>), CouicError> {
+        let url = self.url(endpoint)?;
+        let response = self.send(self.client.get(url))?;
+        Self::handle_response_with_etag(response)
+    }
+
+    pub(crate) fn get_text(
+        &self,
+        endpoint: &str,
+        accept: &'static str,
+    ) -> Result<String, CouicError> {
+        let url = self.url(endpoint)?;
+        let response = self.send(
+            self.client
+                .get(url)
+                .header(ACCEPT, HeaderValue::from_static(accept)),
+        )?;
+        if response.status().is_success() {
+            Ok(response.text()?)
+        } else {
+            Err(Self::api_error(response))
+        }
+    }
+
     pub(crate) fn post<T: serde::de::DeserializeOwned, B: serde::Serialize + ?Sized>(
         &self,
         endpoint: &str,
         body: Option<&B>,
+    ) -> Result<T, CouicError> {
+        self.post_with_options(endpoint, body, WriteOptions::default())
+    }
+
+    /// Same as `post`, sending the flags of `options`
+    pub(crate) fn post_with_options<
+        T: serde::de::DeserializeOwned,
+        B: serde::Serialize + ?Sized,
+    >(
+        &self,
+        endpoint: &str,
+        body: Option<&B>,
+        options: WriteOptions,
     ) -> Result<T, CouicError> {
         let url = self.url(endpoint)?;
-        let req = self.client.post(url);
+        let req = Self::option_headers(self.client.post(url), options);
         let req = if let Some(b) = body { req.json(b) } else { req };
-        let response = req.send()?;
+        let response = self.send(req)?;
         Self::handle_response(response)
     }
 
+    /// Same as `post`, with the JSON body compressed with gzip
+    pub(crate) fn post_gzip<T: serde::de::DeserializeOwned, B: serde::Serialize + ?Sized>(
+        &self,
+        endpoint: &str,
+        body: &B,
+    ) -> Result<T, CouicError> {
+        let url = self.url(endpoint)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, body)?;
+        let response = self.send(
+            self.client
+                .post(url)
+                .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                .header(CONTENT_ENCODING, HeaderValue::from_static("gzip"))
+                .body(encoder.finish()?),
+        )?;
+        Self::handle_response(response)
+    }
+
+    /// Same as `post` without body, for endpoints answering without content
+    pub(crate) fn post_empty(&self, endpoint: &str) -> Result<(), CouicError> {
+        let url = self.url(endpoint)?;
+        let response = self.send(self.client.post(url))?;
+        Self::handle_empty_response(response)
+    }
+
     pub(crate) fn put<T: serde::de::DeserializeOwned, B: serde::Serialize>(
         &self,
         endpoint: &str,
         body: Option<&B>,
+    ) -> Result<T, CouicError> {
+        self.put_with_options(endpoint, body, WriteOptions::default())
+    }
+
+    /// Same as `put`, sending the flags of `options`
+    pub(crate) fn put_with_options<T: serde::de::DeserializeOwned, B: serde::Serialize>(
+        &self,
+        endpoint: &str,
+        body: Option<&B>,
+        options: WriteOptions,
     ) -> Result<T, CouicError> {
         let url = self.url(endpoint)?;
-        let req = self.client.put(url);
+        let req = Self::option_headers(self.client.put(url), options);
         let req = if let Some(b) = body { req.json(b) } else { req };
-        let response = req.send()?;
+        let response = self.send(req)?;
         Self::handle_response(response)
     }
 
-    pub(crate) fn post_empty(&self, endpoint: &str) -> Result<(), CouicError> {
+    pub(crate) fn patch<T: serde::de::DeserializeOwned, B: serde::Serialize>(
+        &self,
+        endpoint: &str,
+        body: &B,
+    ) -> Result<T, CouicError> {
         let url = self.url(endpoint)?;
-        let response = self.client.post(url).send()?;
-        Self::handle_empty_response(response)
+        let response = self.send(self.client.patch(url).json(body))?;
+        Self::handle_response(response)
+    }
+
+    /// Same as `put`, applied only if the resource still has one of the
+    /// entity tags of `if_match`, and returning the new `ETag`
+    pub(crate) fn put_if_match<T: serde::de::DeserializeOwned, B: serde::Serialize>(
+        &self,
+        endpoint: &str,
+        body: &B,
+        if_match: &str,
+    ) -> Result<(T, Option<String>), CouicError> {
+        let url = self.url(endpoint)?;
+        let response = self.send(
+            self.client
+                .put(url)
+                .header(IF_MATCH, HeaderValue::from_str(if_match)?)
+                .json(body),
+        )?;
+        Self::handle_response_with_etag(response)
+    }
+
+    fn option_headers(req: RequestBuilder, options: WriteOptions) -> RequestBuilder {
+        let req = if options.force {
+            req.header(FORCE_HEADER, HeaderValue::from_static("true"))
+        } else {
+            req
+        };
+        let req = if options.dry_run {
+            req.header(DRY_RUN_HEADER, HeaderValue::from_static("true"))
+        } else {
+            req
+        };
+        if options.propagate {
+            req
+        } else {
+            req.header(PROPAGATE_HEADER, HeaderValue::from_static("false"))
+        }
     }
 
     pub(crate) fn delete(&self, endpoint: &str) -> Result<(), CouicError> {
+        self.delete_with_options(endpoint, WriteOptions::default())
+    }
+
+    /// Same as `delete`, returning the body of the response
+    pub(crate) fn delete_with_body<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+    ) -> Result<T, CouicError> {
+        let url = self.url(endpoint)?;
+        let response = self.send(self.client.delete(url))?;
+        Self::handle_response(response)
+    }
+
+    /// Same as `delete`, sending the flags of `options`
+    pub(crate) fn delete_with_options(
+        &self,
+        endpoint: &str,
+        options: WriteOptions,
+    ) -> Result<(), CouicError> {
         let url = self.url(endpoint)?;
-        let response = self.client.delete(url).send()?;
+        let response = self.send(Self::option_headers(self.client.delete(url), options))?;
         Self::handle_empty_response(response)
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use common::{Expiration, Policy, RawEntry};
+    use std::io::{BufRead, BufReader, Read as _, Write as _};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+    use tempfile::TempDir;
+
+    /// HTTP server answering one connection per response, in order, and
+    /// giving back the request lines it received
+    fn serve(responses: Vec<String>) -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut request = line.trim_end().to_string();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim_end().is_empty() {
+                        break;
+                    }
+                    let _ = write!(request, "\n{}", header.trim_end());
+                    if let Some((name, value)) = header.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                requests.push(request);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                reader.into_inner().write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (port, server)
+    }
+
+    fn response(status: &str, headers: &[&str], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+        for header in headers {
+            let _ = write!(response, "{header}\r\n");
+        }
+        let _ = write!(
+            response,
+            "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        response
+    }
+
+    fn client(port: u16, builder: CouicClientBuilder) -> CouicClient {
+        builder
+            .build_remote(&RemoteConfig::new("127.0.0.1", port, Uuid::new_v4(), false))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_validation_fields_of_unprocessable_entity() {
+        let body = r#"{"code":"invalid","message":"Invalid request","errors":{"cidr":{"code":"invalid","message":"Invalid CIDR"},"entries[2]":{"code":"conflict","message":"Already present"}}}"#;
+        let (port, server) = serve(vec![
+            response("422 Unprocessable Entity", &[], body),
+            response(
+                "404 Not Found",
+                &[],
+                r#"{"code":"not_found","message":"Entry not found"}"#,
+            ),
+        ]);
+        let client = client(port, CouicClient::builder());
+
+        let err = client.get::<serde_json::Value>("/v1/drop").unwrap_err();
+        assert!(matches!(err, CouicError::ApiError { status: 422, .. }));
+        let fields = err.validation_fields().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields["cidr"].message, "Invalid CIDR");
+        assert_eq!(fields["entries[2]"].code, ErrorCode::Econflict);
+
+        // Errors without details have no fields
+        let err = client.get::<serde_json::Value>("/v1/drop").unwrap_err();
+        assert!(matches!(err, CouicError::ApiError { status: 404, .. }));
+        assert!(err.validation_fields().is_none());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_add_batch_summarizes_partial_failures() {
+        let entry = |cidr: &str| RawEntry {
+            cidr: cidr.parse().unwrap(),
+            tag: None,
+            expiration: Expiration::never(),
+            metadata: None,
+        };
+        let created = |cidr: &str| {
+            let added = entry(cidr).into_entry();
+            response("201 Created", &[], &serde_json::to_string(&added).unwrap())
+        };
+        let entries = [
+            entry("192.0.2.1/32"),
+            entry("192.0.2.2/32"),
+            entry("192.0.2.3/32"),
+            entry("192.0.2.4/32"),
+            entry("192.0.2.5/32"),
+        ];
+        let (port, server) = serve(vec![
+            created("192.0.2.1/32"),
+            response(
+                "409 Conflict",
+                &[],
+                r#"{"code":"conflict","message":"Entry already exists"}"#,
+            ),
+            response(
+                "429 Too Many Requests",
+                &["Retry-After: 3600"],
+                r#"{"code":"quota_exceeded","message":"Quota reached"}"#,
+            ),
+            created("192.0.2.4/32"),
+        ]);
+        let client = client(port, CouicClient::builder());
+
+        let mut reports = Vec::new();
+        let summary = client.policy().add_batch(
+            Policy::Drop,
+            &entries,
+            WriteOptions::default(),
+            |progress| reports.push((progress.done, progress.failed)),
+        );
+        server.join().unwrap();
+
+        let added: Vec<String> = summary.added.iter().map(|e| e.cidr.to_string()).collect();
+        assert_eq!(added, vec!["192.0.2.1/32", "192.0.2.4/32"]);
+        let failed: Vec<(String, bool)> = summary
+            .failed
+            .iter()
+            .map(|(cidr, e)| {
+                (
+                    cidr.to_string(),
+                    matches!(e, CouicError::RateLimited { .. }),
+                )
+            })
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                ("192.0.2.2/32".to_string(), false),
+                ("192.0.2.3/32".to_string(), true),
+                // The server is gone, which stops the batch
+                ("192.0.2.5/32".to_string(), false),
+            ]
+        );
+        assert_eq!(summary.skipped, 0);
+        assert_eq!(reports, vec![(1, 0), (2, 1), (3, 2), (4, 2)]);
+    }
+
+    #[test]
+    fn test_retry_after_is_a_delay_in_seconds() {
+        let delay = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            retry_after(&headers)
+        };
+        assert_eq!(delay("30"), Some(Duration::from_secs(30)));
+        assert_eq!(delay(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(delay("0"), Some(Duration::ZERO));
+        // The HTTP date form is never sent by the daemon
+        assert_eq!(delay("Wed, 21 Oct 2026 07:28:00 GMT"), None);
+        assert_eq!(delay("-1"), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_too_many_requests_is_rate_limited() {
+        let (port, server) = serve(vec![
+            response(
+                "429 Too Many Requests",
+                &["Retry-After: 7"],
+                r#"{"code":"quota_exceeded","message":"Quota reached","errors":{}}"#,
+            ),
+            response(
+                "503 Service Unavailable",
+                &["Retry-After: 7"],
+                r#"{"code":"internal","message":"Unavailable","errors":{}}"#,
+            ),
+        ]);
+        let client = client(port, CouicClient::builder());
+
+        let err = client.get::<serde_json::Value>("/v1/drop").unwrap_err();
+        let CouicError::RateLimited { retry_after, error } = err else {
+            panic!("expected a rate limit, got {err:?}");
+        };
+        assert_eq!(retry_after, Some(Duration::from_secs(7)));
+        assert_eq!(error.message, "Quota reached");
+
+        // Other statuses are API errors, whatever their headers
+        let err = client.get::<serde_json::Value>("/v1/drop").unwrap_err();
+        assert!(matches!(err, CouicError::ApiError { status: 503, .. }));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_user_agent_names_the_configured_product() {
+        let user_agent = |builder: CouicClientBuilder| {
+            let (port, server) = serve(vec![response("200 OK", &[], "[]")]);
+            let _: serde_json::Value = client(port, builder).get("/v1/drop").unwrap();
+            let requests = server.join().unwrap();
+            requests
+                .first()
+                .unwrap()
+                .lines()
+                .find_map(|header| {
+                    let (name, value) = header.split_once(':')?;
+                    name.eq_ignore_ascii_case("user-agent")
+                        .then(|| value.trim().to_string())
+                })
+                .unwrap()
+        };
+
+        assert_eq!(user_agent(CouicClient::builder()), USER_AGENT_VALUE);
+        assert_eq!(
+            user_agent(CouicClient::builder().user_agent("couicctl/1.2.3")),
+            format!("couicctl/1.2.3 {USER_AGENT_VALUE}")
+        );
+    }
+
+    #[test]
+    fn test_rate_limited_requests_are_retried_within_the_longest_wait() {
+        let limited = |delay: &str| {
+            response(
+                "429 Too Many Requests",
+                &[&format!("Retry-After: {delay}")],
+                r#"{"code":"quota_exceeded","message":"Quota reached","errors":{}}"#,
+            )
+        };
+        let builder = || {
+            CouicClient::builder()
+                .rate_limit_retries(3)
+                .max_retry_wait(Duration::from_secs(1))
+        };
+
+        let (port, server) = serve(vec![
+            limited("0"),
+            limited("0"),
+            response("200 OK", &[], "[]"),
+        ]);
+        let entries: serde_json::Value = client(port, builder()).get("/v1/drop").unwrap();
+        assert_eq!(entries, serde_json::json!([]));
+        assert_eq!(server.join().unwrap().len(), 3);
+
+        // A longer delay is not waited for
+        let (port, server) = serve(vec![limited("3600")]);
+        let err = client(port, builder())
+            .get::<serde_json::Value>("/v1/drop")
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CouicError::RateLimited {
+                retry_after: Some(delay),
+                ..
+            } if delay == Duration::from_secs(3600)
+        ));
+        assert_eq!(server.join().unwrap().len(), 1);
+
+        // Nor are more retries than allowed
+        let (port, server) = serve(vec![limited("0"), limited("0")]);
+        let err = client(port, builder().rate_limit_retries(1))
+            .get::<serde_json::Value>("/v1/drop")
+            .unwrap_err();
+        assert!(matches!(err, CouicError::RateLimited { .. }));
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_local_client_file_of_another_client() {
+        let dir = TempDir::new().unwrap();
+        let socket = file_with_mode(&dir, "couic.sock", 0o600);
+        let ops = dir.path().join("ops.toml");
+        fs::write(
+            &ops,
+            format!("token = \"{}\"\ngroup = \"admin\"\n", Uuid::new_v4()),
+        )
+        .unwrap();
+        let couicctl = ClientName::try_from("couicctl").unwrap();
+        let build = |file: &Path| {
+            let config = LocalConfig::from_file(&socket, file.to_string_lossy())
+                .expect_client(couicctl.clone());
+            CouicClient::builder().build_local(config)
+        };
+
+        let err = build(&ops).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("is the one of client ops, expected couicctl")
+        );
+
+        // Nor is it accepted behind a link named after the expected client
+        let link = dir.path().join("couicctl.toml");
+        std::os::unix::fs::symlink(&ops, &link).unwrap();
+        let err = build(&link).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("is the one of client ops, expected couicctl")
+        );
+
+        fs::remove_file(&link).unwrap();
+        fs::copy(&ops, &link).unwrap();
+        assert!(build(&link).is_ok());
+
+        // Without an expected client, any client file is loaded
+        let config = LocalConfig::from_file(&socket, ops.to_string_lossy());
+        assert!(CouicClient::builder().build_local(config).is_ok());
+    }
+
+    fn file_with_mode(dir: &TempDir, name: &str, mode: u32) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_client_identity_permissions() {
+        let dir = TempDir::new().unwrap();
+        let check_cert =
+            |path: &str| check_file_perms(Path::new(path), CLIENT_CERT_FORBIDDEN_MODE, "cert");
+        let check_key =
+            |path: &str| check_file_perms(Path::new(path), CLIENT_KEY_FORBIDDEN_MODE, "key");
+
+        // Certificates may be read by anyone, not written
+        assert!(check_cert(&file_with_mode(&dir, "cert.pem", 0o644)).is_ok());
+        let err = check_cert(&file_with_mode(&dir, "shared.pem", 0o664)).unwrap_err();
+        assert!(err.to_string().contains("unsafe permissions (mode 664)"));
+
+        // Keys are only accessible by their owner
+        assert!(check_key(&file_with_mode(&dir, "client.key", 0o600)).is_ok());
+        let err = check_key(&file_with_mode(&dir, "readable.key", 0o640)).unwrap_err();
+        assert!(err.to_string().contains("unsafe permissions (mode 640)"));
+
+        let err = check_key(dir.path().to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("is not a file"));
+        let err = check_key(&dir.path().join("missing.key").to_string_lossy()).unwrap_err();
+        assert!(matches!(err, CouicError::Io(e) if e.kind() == io::ErrorKind::NotFound));
+    }
+
+    #[test]
+    fn test_client_identity_requires_tls() {
+        let dir = TempDir::new().unwrap();
+        let config = RemoteConfig::new("127.0.0.1", 8443, Uuid::new_v4(), false).client_identity(
+            file_with_mode(&dir, "cert.pem", 0o644),
+            file_with_mode(&dir, "client.key", 0o600),
+        );
+        let err = CouicClient::builder().build_remote(&config).unwrap_err();
+        assert!(err.to_string().contains("requires TLS"));
+
+        // Permissions are checked before the content is read
+        let config = RemoteConfig::new("127.0.0.1", 8443, Uuid::new_v4(), true).client_identity(
+            file_with_mode(&dir, "cert.pem", 0o644),
+            file_with_mode(&dir, "open.key", 0o644),
+        );
+        let err = CouicClient::builder().build_remote(&config).unwrap_err();
+        assert!(err.to_string().contains("unsafe permissions"));
+    }
+}
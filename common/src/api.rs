@@ -15,6 +15,10 @@ pub struct RawEntryInput {
 #[derive(Debug, Clone, Deserialize)]
 pub struct SetInput {
     pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
     pub entries: Vec<String>,
 }
 
@@ -42,3 +46,16 @@ pub struct PeerJobInput {
     pub action: String,
     pub entry: RawEntryInput,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetPatchInput {
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogLevelChangeInput {
+    pub level: String,
+}
@@ -1,6 +1,48 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+
 use ipnet::IpNet;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+/// Error of a CIDR that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidCidr {
+    /// IPv6 address with a zone, e.g. `fe80::1%eth0/64`
+    Scoped(String),
+    Syntax(ipnet::AddrParseError),
+}
+
+impl fmt::Display for InvalidCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scoped(s) => write!(
+                f,
+                "{s} is a scoped IPv6 address: zones are not supported for firewall entries, which apply to every interface"
+            ),
+            Self::Syntax(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidCidr {}
+
+/// Refuse an IPv6 address or CIDR with a zone, e.g. `fe80::1%eth0`, which
+/// would otherwise be reported as a mere syntax error
+pub fn check_unscoped(s: &str) -> Result<(), InvalidCidr> {
+    match s.split_once('%') {
+        Some((addr, _)) if addr.parse::<Ipv6Addr>().is_ok() => {
+            Err(InvalidCidr::Scoped(s.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parse a CIDR as written, without normalizing it
+pub fn parse_cidr(s: &str) -> Result<IpNet, InvalidCidr> {
+    check_unscoped(s)?;
+    s.parse().map_err(InvalidCidr::Syntax)
+}
+
 /// A wrapper around `IpNet` that guarantees the CIDR is normalized to its network address.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct NormalizedCidr(IpNet);
@@ -12,12 +54,12 @@ impl NormalizedCidr {
     }
 
     pub fn from_addr_and_prefix(
-        addr: std::net::IpAddr,
+        addr: IpAddr,
         prefix_len: u8,
     ) -> Result<Self, ipnet::PrefixLenError> {
         let ipnet = match addr {
-            std::net::IpAddr::V4(ipv4) => IpNet::V4(ipnet::Ipv4Net::new(ipv4, prefix_len)?),
-            std::net::IpAddr::V6(ipv6) => IpNet::V6(ipnet::Ipv6Net::new(ipv6, prefix_len)?),
+            IpAddr::V4(ipv4) => IpNet::V4(ipnet::Ipv4Net::new(ipv4, prefix_len)?),
+            IpAddr::V6(ipv6) => IpNet::V6(ipnet::Ipv6Net::new(ipv6, prefix_len)?),
         };
         Ok(Self(ipnet.trunc()))
     }
@@ -28,7 +70,7 @@ impl NormalizedCidr {
     }
 
     #[must_use]
-    pub fn network(&self) -> std::net::IpAddr {
+    pub fn network(&self) -> IpAddr {
         self.0.network()
     }
 
@@ -47,6 +89,50 @@ impl NormalizedCidr {
         matches!(self.0, IpNet::V6(_))
     }
 
+    /// Whether `ip` belongs to this network
+    #[must_use]
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.contains(&ip)
+    }
+
+    /// Whether every address of `other` belongs to this network, `other`
+    /// being the same network or a narrower one
+    #[must_use]
+    pub fn supernet_of(&self, other: &Self) -> bool {
+        self.0.contains(&other.0)
+    }
+
+    /// Whether this network and `other` share addresses, one of them then
+    /// containing the other
+    #[must_use]
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.supernet_of(other) || other.supernet_of(self)
+    }
+
+    /// Why packets from this network would never be dropped by a host that
+    /// is not on their link, when every address of it is link-local, or
+    /// multicast
+    #[must_use]
+    pub fn source_caveat(&self) -> Option<&'static str> {
+        let (link_local, multicast) = match self.0 {
+            IpNet::V4(net) => (
+                net.prefix_len() >= 16 && net.network().is_link_local(),
+                net.prefix_len() >= 4 && net.network().is_multicast(),
+            ),
+            IpNet::V6(net) => (
+                net.prefix_len() >= 10 && net.network().is_unicast_link_local(),
+                net.prefix_len() >= 8 && net.network().is_multicast(),
+            ),
+        };
+        if link_local {
+            Some("link-local sources only reach a host from its own links, never forwarded")
+        } else if multicast {
+            Some("multicast addresses are never the source of a packet")
+        } else {
+            None
+        }
+    }
+
     #[must_use]
     pub fn to_lpm_key_v4(self) -> Option<(u32, u32)> {
         match self.0 {
@@ -89,11 +175,10 @@ impl std::fmt::Display for NormalizedCidr {
 }
 
 impl std::str::FromStr for NormalizedCidr {
-    type Err = ipnet::AddrParseError;
+    type Err = InvalidCidr;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let cidr: IpNet = s.parse()?;
-        Ok(Self::new(cidr))
+        parse_cidr(s).map(Self::new)
     }
 }
 
@@ -213,6 +298,81 @@ mod tests {
         assert_eq!(back.to_string(), "10.20.30.0/24");
     }
 
+    #[test]
+    fn test_normalized_cidr_contains() {
+        let cidr = NormalizedCidr::from_str("192.0.2.0/24").unwrap();
+        assert!(cidr.contains("192.0.2.0".parse().unwrap()));
+        assert!(cidr.contains("192.0.2.255".parse().unwrap()));
+        assert!(!cidr.contains("192.0.3.0".parse().unwrap()));
+        // Families never match, even for IPv4-mapped addresses
+        assert!(!cidr.contains("::ffff:192.0.2.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_normalized_cidr_supernet_of() {
+        let wide = NormalizedCidr::from_str("10.0.0.0/8").unwrap();
+        let narrow = NormalizedCidr::from_str("10.1.0.0/16").unwrap();
+        assert!(wide.supernet_of(&narrow));
+        assert!(wide.supernet_of(&wide));
+        assert!(!narrow.supernet_of(&wide));
+
+        let v6 = NormalizedCidr::from_str("::/0").unwrap();
+        assert!(!v6.supernet_of(&narrow));
+    }
+
+    #[test]
+    fn test_normalized_cidr_overlaps() {
+        let wide = NormalizedCidr::from_str("2001:db8::/32").unwrap();
+        let narrow = NormalizedCidr::from_str("2001:db8:1::/48").unwrap();
+        let apart = NormalizedCidr::from_str("2001:db9::/32").unwrap();
+        assert!(wide.overlaps(&narrow));
+        assert!(narrow.overlaps(&wide));
+        assert!(wide.overlaps(&wide));
+        assert!(!wide.overlaps(&apart));
+        assert!(!narrow.overlaps(&NormalizedCidr::from_str("0.0.0.0/0").unwrap()));
+    }
+
+    #[test]
+    fn test_normalized_cidr_refuses_scoped_addresses() {
+        let err = NormalizedCidr::from_str("fe80::1%eth0/64").unwrap_err();
+        assert_eq!(err, InvalidCidr::Scoped("fe80::1%eth0/64".to_string()));
+        assert!(err.to_string().contains("zones are not supported"));
+        assert!(matches!(
+            NormalizedCidr::from_str("fe80::1%eth0"),
+            Err(InvalidCidr::Scoped(_))
+        ));
+        assert!(matches!(
+            NormalizedCidr::from_str("192.0.2.0%24"),
+            Err(InvalidCidr::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_normalized_cidr_source_caveat() {
+        for cidr in ["169.254.0.0/16", "169.254.1.1/32", "fe80::/64"] {
+            let caveat = NormalizedCidr::from_str(cidr).unwrap().source_caveat();
+            assert!(caveat.unwrap().starts_with("link-local"), "{cidr}");
+        }
+        for cidr in ["224.0.0.0/4", "239.1.2.3/32", "ff02::1/128"] {
+            let caveat = NormalizedCidr::from_str(cidr).unwrap().source_caveat();
+            assert!(caveat.unwrap().starts_with("multicast"), "{cidr}");
+        }
+        for cidr in [
+            "0.0.0.0/0",
+            "169.0.0.0/8",
+            "192.0.2.0/24",
+            "fe80::/9",
+            "::/0",
+        ] {
+            assert!(
+                NormalizedCidr::from_str(cidr)
+                    .unwrap()
+                    .source_caveat()
+                    .is_none()
+            );
+        }
+    }
+
     #[test]
     fn test_normalized_cidr_serde_roundtrip() {
         let cidr = NormalizedCidr::from_str("192.168.1.0/24").unwrap();
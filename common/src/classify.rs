@@ -0,0 +1,196 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::entry::Entry;
+use crate::error::{CompositeError, ErrorCode};
+use crate::validation::ValidateFrom;
+
+/// Verdict the XDP program would apply to packets from an address
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Drop,
+    Ignore,
+    Pass,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Drop => write!(f, "drop"),
+            Self::Ignore => write!(f, "ignore"),
+            Self::Pass => write!(f, "pass"),
+        }
+    }
+}
+
+/// Longest-prefix matches of an address in both policies, and the resulting
+/// decision, ignore winning over drop
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Classification {
+    pub ip: IpAddr,
+    pub decision: Decision,
+    pub ignore: Option<Entry>,
+    pub drop: Option<Entry>,
+    /// Longest match in the monitor policy, which never changes the decision
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<Entry>,
+}
+
+impl Classification {
+    #[must_use]
+    pub const fn new(ip: IpAddr, ignore: Option<Entry>, drop: Option<Entry>) -> Self {
+        let decision = if ignore.is_some() {
+            Decision::Ignore
+        } else if drop.is_some() {
+            Decision::Drop
+        } else {
+            Decision::Pass
+        };
+        Self {
+            ip,
+            decision,
+            ignore,
+            drop,
+            monitor: None,
+        }
+    }
+
+    /// Entry deciding the verdict, if any
+    #[must_use]
+    pub const fn matched(&self) -> Option<&Entry> {
+        match self.decision {
+            Decision::Ignore => self.ignore.as_ref(),
+            Decision::Drop => self.drop.as_ref(),
+            Decision::Pass => None,
+        }
+    }
+}
+
+fn write_match(f: &mut fmt::Formatter<'_>, label: &str, entry: &Entry) -> fmt::Result {
+    writeln!(
+        f,
+        "{label}: {} (tag: {})",
+        entry.cidr,
+        entry.tag.as_deref().unwrap_or("-")
+    )
+}
+
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.ip, self.decision)?;
+        if let Some(entry) = self.matched() {
+            write_match(f, "Matched", entry)?;
+        }
+        if self.decision == Decision::Ignore
+            && let Some(entry) = &self.drop
+        {
+            write_match(f, "Overridden drop", entry)?;
+        }
+        if let Some(entry) = &self.monitor {
+            write_match(f, "Monitored", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Address path parameter of the classify endpoint
+#[derive(Debug, Clone, Copy)]
+pub struct ClassifyPath {
+    pub ip: IpAddr,
+}
+
+impl ValidateFrom for ClassifyPath {
+    type Input = String;
+
+    fn validate_from(input: Self::Input) -> Result<Self, CompositeError> {
+        input
+            .trim()
+            .parse()
+            .map(|ip| Self { ip })
+            .map_err(|e: std::net::AddrParseError| {
+                let mut err = CompositeError::new(ErrorCode::Ebadrequest, "Bad request");
+                err.add_detail("ip", ErrorCode::Einvalid, &e.to_string());
+                err
+            })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::Expiration;
+
+    fn entry(cidr: &str, tag: &str) -> Entry {
+        Entry {
+            creation: 0,
+            cidr: cidr.parse().unwrap(),
+            tag: Some(tag.to_string()),
+            expiration: Expiration::never(),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_classification_ignore_wins_over_drop() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let c = Classification::new(
+            ip,
+            Some(entry("10.0.0.0/24", "admin")),
+            Some(entry("10.0.0.0/8", "abuse")),
+        );
+        assert_eq!(c.decision, Decision::Ignore);
+        assert_eq!(c.matched().unwrap().tag.as_deref(), Some("admin"));
+        assert_eq!(
+            c.to_string(),
+            "10.0.0.1: ignore\nMatched: 10.0.0.0/24 (tag: admin)\nOverridden drop: 10.0.0.0/8 (tag: abuse)\n"
+        );
+    }
+
+    #[test]
+    fn test_classification_drop_and_pass() {
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+        let c = Classification::new(ip, None, Some(entry("2001:db8::/32", "abuse")));
+        assert_eq!(c.decision, Decision::Drop);
+        assert_eq!(
+            c.to_string(),
+            "2001:db8::1: drop\nMatched: 2001:db8::/32 (tag: abuse)\n"
+        );
+
+        let c = Classification::new(ip, None, None);
+        assert_eq!(c.decision, Decision::Pass);
+        assert!(c.matched().is_none());
+        assert_eq!(c.to_string(), "2001:db8::1: pass\n");
+    }
+
+    #[test]
+    fn test_classification_monitor_keeps_decision() {
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let c = Classification {
+            monitor: Some(entry("192.0.2.0/24", "candidate")),
+            ..Classification::new(ip, None, None)
+        };
+        assert_eq!(c.decision, Decision::Pass);
+        assert_eq!(
+            c.to_string(),
+            "192.0.2.1: pass\nMonitored: 192.0.2.0/24 (tag: candidate)\n"
+        );
+        let json = serde_json::to_string(&c).unwrap();
+        assert!(json.contains(r#""monitor":{"#));
+        let json = serde_json::to_string(&Classification::new(ip, None, None)).unwrap();
+        assert!(!json.contains("monitor"));
+    }
+
+    #[test]
+    fn test_classify_path_validation() {
+        let path = ClassifyPath::validate_from("192.0.2.1".to_string()).unwrap();
+        assert_eq!(path.ip, "192.0.2.1".parse::<IpAddr>().unwrap());
+
+        let err = ClassifyPath::validate_from("192.0.2.0/24".to_string()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Ebadrequest);
+        assert!(err.errors.contains_key("ip"));
+    }
+}
@@ -1,5 +1,7 @@
 //! API client types for the Couic firewall.
 
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -19,6 +21,44 @@ pub struct ClientFile {
     pub group: Group,
 }
 
+impl ClientFile {
+    /// Name of the client whose file is `path`, named `<client name>.toml`.
+    /// With `expected`, the file must be the one of that client, so that a
+    /// misnamed or misplaced file is not used in its stead.
+    pub fn client_name(
+        path: &Path,
+        expected: Option<&ClientName>,
+    ) -> Result<ClientName, CompositeError> {
+        let misnamed = |reason: &str| {
+            CompositeError::new(
+                ErrorCode::Einvalid,
+                &format!(
+                    "Client file {} must be named <client name>.toml{reason}",
+                    path.display()
+                ),
+            )
+        };
+        if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+            return Err(misnamed(""));
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| misnamed(""))?;
+        let name = ClientName::try_from(stem).map_err(|e| misnamed(&format!(": {e}")))?;
+        match expected {
+            Some(expected) if *expected != name => Err(CompositeError::new(
+                ErrorCode::Einvalid,
+                &format!(
+                    "Client file {} is the one of client {name}, expected {expected}",
+                    path.display()
+                ),
+            )),
+            Some(_) | None => Ok(name),
+        }
+    }
+}
+
 /// Response structure for an API client.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Client {
@@ -118,6 +158,32 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_client_file_name() {
+        let couicctl = ClientName::try_from("couicctl").unwrap();
+        let path = Path::new("/var/lib/couic/rbac/clients/couicctl.toml");
+        assert_eq!(ClientFile::client_name(path, None).unwrap(), couicctl);
+        assert_eq!(
+            ClientFile::client_name(path, Some(&couicctl)).unwrap(),
+            couicctl
+        );
+
+        for misnamed in ["couicctl.toml.bak", "couicctl", "couic ctl.toml", ".toml"] {
+            let err = ClientFile::client_name(Path::new(misnamed), None).unwrap_err();
+            assert_eq!(err.code, ErrorCode::Einvalid);
+            assert!(err.message.contains("<client name>.toml"), "{misnamed}");
+        }
+    }
+
+    #[test]
+    fn test_client_file_name_mismatch() {
+        let expected = ClientName::try_from("couicctl").unwrap();
+        let path = Path::new("/var/lib/couic/rbac/clients/fail2ban.toml");
+        let err = ClientFile::client_name(path, Some(&expected)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einvalid);
+        assert!(err.message.contains("client fail2ban, expected couicctl"));
+    }
+
     #[test]
     fn test_client_from_both_invalid() {
         let input = ClientInput {
@@ -1,6 +1,33 @@
 pub const MAX_CLIENT_NAME_LENGTH: usize = 48;
+/// Admin client the daemon creates for couicctl, which cannot be deleted
+pub const DEFAULT_CLIENT_NAME: &str = "couicctl";
 pub const MAX_SET_NAME_LENGTH: usize = 48;
 pub const MAX_TAG_NAME_LENGTH: usize = 64;
+pub const MAX_TAG_NAMESPACE_DEPTH: usize = 4;
+pub const MAX_NAMESPACED_TAG_LENGTH: usize = 192;
+pub const TAG_NAMESPACE_SEPARATOR: char = '/';
+pub const MAX_METADATA_KIND_LENGTH: usize = 64;
+pub const MAX_METADATA_DETAIL_LENGTH: usize = 512;
 pub const SET_EXTENSION: &str = ".couic";
 pub const MAX_SET_FILE_SIZE: u64 = 5 * 1024 * 1024; // 5MB
+/// Appended to the file name of a set for its detached signature, e.g.
+/// `blocklist.couic.sig`
+pub const SIGNATURE_EXTENSION: &str = ".sig";
+/// Appended to the file name of a disabled set, which reloads skip, e.g.
+/// `blocklist.couic.disabled`
+pub const DISABLED_SET_EXTENSION: &str = ".disabled";
+/// Prefix of the metadata comments heading a set file, e.g. `# @source: RIPEstat`
+pub const SET_HEADER_PREFIX: &str = "# @";
+pub const MAX_SET_HEADER_VALUE_LENGTH: usize = 256;
 pub const RESERVED_TAG_NAME: &str = "untagged";
+/// Request header bypassing the protected ranges guard on drop additions
+pub const FORCE_HEADER: &str = "x-couic-force";
+/// Request header set to `false` to keep an entry change away from peers and
+/// reporting
+pub const PROPAGATE_HEADER: &str = "x-couic-propagate";
+/// Request header set to `true` to check an entry creation without applying it
+pub const DRY_RUN_HEADER: &str = "x-couic-dry-run";
+/// Request header making an entry creation safe to retry
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Maximum length of an idempotency key
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
@@ -1,8 +1,14 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::cidr::NormalizedCidr;
 use crate::constants::SET_EXTENSION;
-use crate::expiration::Expiration;
+use crate::error::ErrorDetail;
+use crate::expiration::{Expiration, TimeFormat, format_rfc3339};
+use crate::policy::Policy;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Entry {
@@ -11,6 +17,9 @@ pub struct Entry {
     #[serde(default)]
     pub tag: Option<String>,
     pub expiration: Expiration,
+    /// Where the entry comes from, unknown for entries built client side
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<EntrySource>,
 }
 
 impl Entry {
@@ -20,6 +29,140 @@ impl Entry {
             .as_ref()
             .is_some_and(|tag| tag.ends_with(SET_EXTENSION))
     }
+
+    /// Serializes the entry with its timestamps in `format`
+    #[must_use]
+    pub const fn formatted(&self, format: TimeFormat) -> FormattedEntry<'_> {
+        FormattedEntry {
+            entry: self,
+            format,
+        }
+    }
+}
+
+/// Entry serialized with its timestamps in a given format, the same as the
+/// entry itself with epoch seconds
+#[derive(Debug, Clone, Copy)]
+pub struct FormattedEntry<'a> {
+    entry: &'a Entry,
+    format: TimeFormat,
+}
+
+impl Serialize for FormattedEntry<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.format {
+            TimeFormat::Epoch => self.entry.serialize(serializer),
+            TimeFormat::Rfc3339 => {
+                let mut state = serializer.serialize_struct("Entry", 5)?;
+                state.serialize_field("creation", &format_rfc3339(self.entry.creation))?;
+                state.serialize_field("cidr", &self.entry.cidr)?;
+                state.serialize_field("tag", &self.entry.tag)?;
+                state.serialize_field("expiration", &self.entry.expiration.to_rfc3339())?;
+                if let Some(source) = &self.entry.source {
+                    state.serialize_field("source", source)?;
+                } else {
+                    state.skip_field("source")?;
+                }
+                state.end()
+            }
+        }
+    }
+}
+
+/// How an entry was added
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// Added through the API by a client
+    Manual,
+    /// Received from a peer node
+    Peer,
+    /// Loaded from a set file
+    Set,
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Manual => write!(f, "manual"),
+            Self::Peer => write!(f, "peer"),
+            Self::Set => write!(f, "set"),
+        }
+    }
+}
+
+/// Provenance of an entry: how it was added and, for API changes, the name
+/// of the client that sent it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct EntrySource {
+    pub kind: SourceKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+}
+
+impl EntrySource {
+    #[must_use]
+    pub fn manual(client: impl Into<String>) -> Self {
+        Self {
+            kind: SourceKind::Manual,
+            client: Some(client.into()),
+        }
+    }
+
+    /// Entry received from the peer node authenticated as `client`
+    #[must_use]
+    pub fn peer(client: impl Into<String>) -> Self {
+        Self {
+            kind: SourceKind::Peer,
+            client: Some(client.into()),
+        }
+    }
+
+    #[must_use]
+    pub const fn set() -> Self {
+        Self {
+            kind: SourceKind::Set,
+            client: None,
+        }
+    }
+}
+
+impl fmt::Display for EntrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.client {
+            Some(client) => write!(f, "{} ({client})", self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// Outcome of replacing the manual entries of a policy
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReplaceSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    /// Entries left out as their CIDR is defined in a set
+    pub skipped: usize,
+    /// Changes that could not be applied, by CIDR, the others being applied
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub failed: HashMap<String, ErrorDetail>,
+}
+
+/// Entry removed once expired, recalled so that what just lapsed can be
+/// blocked again
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecentlyExpired {
+    pub policy: Policy,
+    pub cidr: NormalizedCidr,
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Unix timestamp of the expiration
+    pub expired_at: u64,
 }
 
 #[cfg(test)]
@@ -38,6 +181,7 @@ mod tests {
             cidr,
             tag: Some("testset.couic".to_string()),
             expiration: Expiration::never(),
+            source: None,
         };
         assert!(set_entry.in_set());
 
@@ -47,6 +191,7 @@ mod tests {
             cidr,
             tag: Some("regular-tag".to_string()),
             expiration: Expiration::never(),
+            source: None,
         };
         assert!(!regular_entry.in_set());
 
@@ -56,6 +201,7 @@ mod tests {
             cidr,
             tag: None,
             expiration: Expiration::never(),
+            source: None,
         };
         assert!(!no_tag_entry.in_set());
     }
@@ -68,6 +214,7 @@ mod tests {
             cidr,
             tag: Some("serde-tag".to_string()),
             expiration: Expiration::from_timestamp(2000),
+            source: None,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -77,4 +224,65 @@ mod tests {
         assert_eq!(deserialized.tag, Some("serde-tag".to_string()));
         assert_eq!(deserialized.expiration, Expiration::from_timestamp(2000));
     }
+
+    #[test]
+    fn test_entry_source_serde() {
+        let mut entry = Entry {
+            creation: 1000,
+            cidr: NormalizedCidr::from_str("192.0.2.0/24").unwrap(),
+            tag: None,
+            expiration: Expiration::never(),
+            source: None,
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert!(json.get("source").is_none());
+
+        entry.source = Some(EntrySource::peer("node-b"));
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(
+            json.get("source").unwrap(),
+            &serde_json::json!({"kind": "peer", "client": "node-b"})
+        );
+        let rfc3339 = serde_json::to_value(entry.formatted(TimeFormat::Rfc3339)).unwrap();
+        assert_eq!(rfc3339.get("source"), json.get("source"));
+        let deserialized: Entry = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, entry);
+
+        assert_eq!(EntrySource::manual("alice").to_string(), "manual (alice)");
+        assert_eq!(EntrySource::set().to_string(), "set");
+    }
+
+    #[test]
+    fn test_entry_formatted_timestamps() {
+        let entry = Entry {
+            creation: 1_680_000_000,
+            cidr: NormalizedCidr::from_str("192.0.2.0/24").unwrap(),
+            tag: None,
+            expiration: Expiration::never(),
+            source: None,
+        };
+        let epoch = serde_json::to_value(entry.formatted(TimeFormat::Epoch)).unwrap();
+        assert_eq!(epoch, serde_json::to_value(&entry).unwrap());
+
+        let rfc3339 = serde_json::to_value(entry.formatted(TimeFormat::Rfc3339)).unwrap();
+        assert_eq!(
+            rfc3339,
+            serde_json::json!({
+                "creation": "2023-03-28T10:40:00Z",
+                "cidr": "192.0.2.0/24",
+                "tag": null,
+                "expiration": null,
+            })
+        );
+
+        let entry = Entry {
+            expiration: Expiration::from_timestamp(1_680_003_600),
+            ..entry
+        };
+        let rfc3339 = serde_json::to_value(entry.formatted(TimeFormat::Rfc3339)).unwrap();
+        assert_eq!(
+            rfc3339.get("expiration").and_then(|e| e.as_str()),
+            Some("2023-03-28T11:40:00Z")
+        );
+    }
 }
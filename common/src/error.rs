@@ -11,7 +11,11 @@ pub enum ErrorCode {
     Eunauthorized,
     Enotfound,
     Econflict,
+    Efull,
+    Eprecondition,
+    Equota,
     Ebadrequest,
+    Etoolarge,
     Einvalid,
     Einternal,
     Enotimplemented,
@@ -24,7 +28,11 @@ impl fmt::Display for ErrorCode {
             Self::Eunauthorized => write!(f, "unauthorized"),
             Self::Enotfound => write!(f, "not_found"),
             Self::Econflict => write!(f, "conflict"),
+            Self::Efull => write!(f, "full"),
+            Self::Eprecondition => write!(f, "precondition_failed"),
+            Self::Equota => write!(f, "quota_exceeded"),
             Self::Ebadrequest => write!(f, "bad_request"),
+            Self::Etoolarge => write!(f, "payload_too_large"),
             Self::Einvalid => write!(f, "invalid"),
             Self::Einternal => write!(f, "internal"),
             Self::Enotimplemented => write!(f, "not_implemented"),
@@ -41,7 +49,11 @@ impl FromStr for ErrorCode {
             "unauthorized" => Ok(Self::Eunauthorized),
             "not_found" => Ok(Self::Enotfound),
             "conflict" => Ok(Self::Econflict),
+            "full" => Ok(Self::Efull),
+            "precondition_failed" => Ok(Self::Eprecondition),
+            "quota_exceeded" => Ok(Self::Equota),
             "bad_request" => Ok(Self::Ebadrequest),
+            "payload_too_large" => Ok(Self::Etoolarge),
             "invalid" => Ok(Self::Einvalid),
             "internal" => Ok(Self::Einternal),
             "not_implemented" => Ok(Self::Enotimplemented),
@@ -82,7 +94,11 @@ impl<'de> Deserialize<'de> for ErrorCode {
                     "unauthorized" => Ok(ErrorCode::Eunauthorized),
                     "not_found" => Ok(ErrorCode::Enotfound),
                     "conflict" => Ok(ErrorCode::Econflict),
+                    "full" => Ok(ErrorCode::Efull),
+                    "precondition_failed" => Ok(ErrorCode::Eprecondition),
+                    "quota_exceeded" => Ok(ErrorCode::Equota),
                     "bad_request" => Ok(ErrorCode::Ebadrequest),
+                    "payload_too_large" => Ok(ErrorCode::Etoolarge),
                     "invalid" => Ok(ErrorCode::Einvalid),
                     "internal" => Ok(ErrorCode::Einternal),
                     "not_implemented" => Ok(ErrorCode::Enotimplemented),
@@ -93,7 +109,11 @@ impl<'de> Deserialize<'de> for ErrorCode {
                             "unauthorized",
                             "not_found",
                             "conflict",
+                            "full",
+                            "precondition_failed",
+                            "quota_exceeded",
                             "bad_request",
+                            "payload_too_large",
                             "invalid",
                             "internal",
                             "not_implemented",
@@ -107,7 +127,7 @@ impl<'de> Deserialize<'de> for ErrorCode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ErrorDetail {
     pub code: ErrorCode,
     pub message: String,
@@ -170,7 +190,11 @@ mod tests {
         assert_eq!(ErrorCode::Eunauthorized.to_string(), "unauthorized");
         assert_eq!(ErrorCode::Enotfound.to_string(), "not_found");
         assert_eq!(ErrorCode::Econflict.to_string(), "conflict");
+        assert_eq!(ErrorCode::Efull.to_string(), "full");
+        assert_eq!(ErrorCode::Eprecondition.to_string(), "precondition_failed");
+        assert_eq!(ErrorCode::Equota.to_string(), "quota_exceeded");
         assert_eq!(ErrorCode::Ebadrequest.to_string(), "bad_request");
+        assert_eq!(ErrorCode::Etoolarge.to_string(), "payload_too_large");
         assert_eq!(ErrorCode::Einvalid.to_string(), "invalid");
         assert_eq!(ErrorCode::Einternal.to_string(), "internal");
         assert_eq!(ErrorCode::Enotimplemented.to_string(), "not_implemented");
@@ -188,10 +212,20 @@ mod tests {
         );
         assert_eq!(ErrorCode::from_str("not_found"), Ok(ErrorCode::Enotfound));
         assert_eq!(ErrorCode::from_str("conflict"), Ok(ErrorCode::Econflict));
+        assert_eq!(ErrorCode::from_str("full"), Ok(ErrorCode::Efull));
+        assert_eq!(
+            ErrorCode::from_str("precondition_failed"),
+            Ok(ErrorCode::Eprecondition)
+        );
+        assert_eq!(ErrorCode::from_str("quota_exceeded"), Ok(ErrorCode::Equota));
         assert_eq!(
             ErrorCode::from_str("bad_request"),
             Ok(ErrorCode::Ebadrequest)
         );
+        assert_eq!(
+            ErrorCode::from_str("payload_too_large"),
+            Ok(ErrorCode::Etoolarge)
+        );
         assert_eq!(ErrorCode::from_str("invalid"), Ok(ErrorCode::Einvalid));
         assert_eq!(ErrorCode::from_str("internal"), Ok(ErrorCode::Einternal));
         assert_eq!(
@@ -1,5 +1,6 @@
 use std::fmt;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde::de;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -37,16 +38,73 @@ impl Expiration {
         self.0 == 0
     }
 
+    #[must_use]
+    pub fn to_rfc3339(&self) -> Option<String> {
+        (!self.is_never()).then(|| format_rfc3339(self.0))
+    }
+
     #[must_use]
     pub fn is_expired(&self) -> bool {
+        self.expires_within(Duration::ZERO)
+    }
+
+    /// Whether the entry expires within `within` from now, or has expired
+    #[must_use]
+    pub fn expires_within(&self, within: Duration) -> bool {
         if self.0 == 0 {
             return false;
         }
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-        self.0 <= now
+            .map_or(0, |d| d.as_secs());
+        self.0 <= now.saturating_add(within.as_secs())
+    }
+}
+
+/// Representation of the timestamps of entries in API responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TimeFormat {
+    /// Unix timestamps in seconds, `0` meaning never for expirations
+    #[default]
+    Epoch,
+    /// RFC 3339 dates in UTC, `null` meaning never for expirations
+    Rfc3339,
+}
+
+/// Last second of year 9999, the latest date RFC 3339 can express
+const MAX_RFC3339_TIMESTAMP: u64 = 253_402_300_799;
+
+/// RFC 3339 date in UTC of a Unix timestamp in seconds, clamped to the end
+/// of year 9999
+#[must_use]
+pub fn format_rfc3339(ts: u64) -> String {
+    let secs = Duration::from_secs(ts.min(MAX_RFC3339_TIMESTAMP));
+    UNIX_EPOCH
+        .checked_add(secs)
+        .map(|time| humantime::format_rfc3339_seconds(time).to_string())
+        .unwrap_or_default()
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Epoch => write!(f, "epoch"),
+            Self::Rfc3339 => write!(f, "rfc3339"),
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "epoch" => Ok(Self::Epoch),
+            "rfc3339" => Ok(Self::Rfc3339),
+            _ => Err(format!(
+                "invalid time format: '{s}' (expected 'epoch' or 'rfc3339')"
+            )),
+        }
     }
 }
 
@@ -103,3 +161,22 @@ where
 
     Ok(exp)
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expires_within() {
+        let in_a_minute = Expiration::from_duration(Duration::from_secs(60));
+        assert!(!in_a_minute.is_expired());
+        assert!(!in_a_minute.expires_within(Duration::from_secs(30)));
+        assert!(in_a_minute.expires_within(Duration::from_secs(120)));
+
+        assert!(Expiration::from_timestamp(1).is_expired());
+        assert!(Expiration::from_timestamp(1).expires_within(Duration::from_secs(60)));
+
+        assert!(!Expiration::never().expires_within(Duration::MAX));
+    }
+}
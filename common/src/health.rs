@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ClientName, Group};
+
+/// Health report returned to an authenticated client.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Health {
+    pub status: String,
+    pub version: String,
+    pub client: ClientName,
+    pub group: Group,
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_serde_roundtrip() {
+        let health = Health {
+            status: "ok".to_string(),
+            version: "1.0.0".to_string(),
+            client: ClientName::try_from("couicctl").unwrap(),
+            group: Group::Admin,
+        };
+
+        let json = serde_json::to_string(&health).unwrap();
+        assert!(json.contains("\"group\":\"admin\""));
+        let deserialized: Health = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, health);
+    }
+}
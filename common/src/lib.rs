@@ -1,6 +1,7 @@
 pub mod action;
 pub mod api;
 pub mod cidr;
+pub mod classify;
 pub mod client;
 pub mod clientgroup;
 pub mod clientname;
@@ -8,6 +9,10 @@ pub mod constants;
 pub mod entry;
 pub mod error;
 pub mod expiration;
+pub mod health;
+pub mod lockdown;
+pub mod loglevel;
+pub mod maintenance;
 pub mod metadata;
 pub mod peer;
 pub mod policy;
@@ -17,30 +22,44 @@ pub mod report;
 pub mod set;
 pub mod setname;
 pub mod setpath;
+pub mod signature;
 pub mod stats;
 pub mod tag;
 pub mod validation;
+pub mod version;
 
 // Re-exports for convenient access
 pub use action::Action;
-pub use api::{PeerJobInput, RawEntryInput, SetInput};
-pub use cidr::NormalizedCidr;
+pub use api::{LogLevelChangeInput, PeerJobInput, RawEntryInput, SetInput, SetPatchInput};
+pub use cidr::{InvalidCidr, NormalizedCidr};
+pub use classify::{Classification, ClassifyPath, Decision};
 pub use client::{Client, ClientFile, ClientRaw};
 pub use clientgroup::{Group, InvalidGroup};
 pub use clientname::{ClientName, InvalidClientName};
 pub use constants::*;
-pub use entry::Entry;
+pub use entry::{Entry, EntrySource, FormattedEntry, RecentlyExpired, ReplaceSummary, SourceKind};
 pub use error::{CompositeError, ErrorCode, ErrorDetail};
-pub use expiration::Expiration;
-pub use metadata::Metadata;
-pub use peer::PeerJob;
+pub use expiration::{Expiration, TimeFormat};
+pub use health::Health;
+pub use lockdown::{Lockdown, LockdownInput};
+pub use loglevel::{InvalidLogLevel, LogFilter, LogLevel, LogLevelChange, LogTail};
+pub use maintenance::{CompactedMap, Compaction};
+pub use metadata::{InvalidMetadata, Metadata};
+pub use peer::{PeerJob, PeerJobResult, PeerResponse};
 pub use policy::Policy;
 pub use policypath::PolicyPath;
-pub use rawentry::RawEntry;
+pub use rawentry::{RawEntries, RawEntry};
 pub use report::Report;
-pub use set::{Set, SetSummary};
+pub use set::{
+    ChangedSet, MapsUsage, Set, SetActivation, SetHeader, SetPatch, SetPatchResult, SetSummary,
+    SetsReload, SetsReloadResult, SetsReloadStatus, SetsStatus, SetsSummary, SkippedSet,
+    render_set_file,
+};
 pub use setname::{InvalidSetName, SetName};
 pub use setpath::SetPath;
-pub use stats::{PktStats, Stats, TagStats};
+pub use stats::{
+    EntryCounters, EntryHits, EntryHitsKey, PktRates, PktStats, Stats, StatsDelta, TagStats,
+};
 pub use tag::{InvalidTag, Tag};
 pub use validation::ValidateFrom;
+pub use version::{ServerVersion, feature};
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::CompositeError;
+use crate::validation::ValidateFrom;
+
+/// Body of `POST /v1/lockdown`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockdownInput {
+    pub enabled: bool,
+    /// Enable it even though a management range is not ignored
+    #[serde(default)]
+    pub force: bool,
+}
+
+impl ValidateFrom for LockdownInput {
+    type Input = Self;
+
+    fn validate_from(input: Self::Input) -> Result<Self, CompositeError> {
+        Ok(input)
+    }
+}
+
+/// Whether XDP drops every packet not matched by an ignore entry, returned by
+/// `/v1/lockdown`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Lockdown {
+    pub enabled: bool,
+}
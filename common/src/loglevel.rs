@@ -0,0 +1,121 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::LogLevelChangeInput;
+use crate::{CompositeError, ErrorCode, ValidateFrom};
+
+#[derive(Debug, Clone)]
+pub struct InvalidLogLevel(pub String);
+
+impl fmt::Display for InvalidLogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidLogLevel {}
+
+/// Most verbose level logged by the daemon
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Error => write!(f, "error"),
+            Self::Warn => write!(f, "warn"),
+            Self::Info => write!(f, "info"),
+            Self::Debug => write!(f, "debug"),
+            Self::Trace => write!(f, "trace"),
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = InvalidLogLevel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(InvalidLogLevel(format!(
+                "invalid log level: '{s}' (expected 'off', 'error', 'warn', 'info', 'debug' or 'trace')"
+            ))),
+        }
+    }
+}
+
+/// New log level of the daemon, sent to `POST /v1/log-level`
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LogLevelChange {
+    pub level: LogLevel,
+}
+
+impl ValidateFrom for LogLevelChange {
+    type Input = LogLevelChangeInput;
+
+    fn validate_from(input: Self::Input) -> Result<Self, CompositeError> {
+        input
+            .level
+            .parse()
+            .map(|level| Self { level })
+            .map_err(|e: InvalidLogLevel| {
+                let mut err = CompositeError::new(ErrorCode::Einvalid, "Validation failed");
+                err.add_detail("level", ErrorCode::Einvalid, &e.to_string());
+                err
+            })
+    }
+}
+
+/// Log filter in effect on the daemon, returned by `/v1/log-level`: a level,
+/// or the directives of `RUST_LOG` until a level is set or after a reset
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogFilter {
+    pub filter: String,
+}
+
+/// Last lines of the daemon log file, returned by `/v1/logs/tail`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogTail {
+    /// Name of the log file within the log directory
+    pub file: String,
+    pub lines: Vec<String>,
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_level_change_validates_level() {
+        let change = LogLevelChange::validate_from(LogLevelChangeInput {
+            level: "DEBUG".to_string(),
+        })
+        .unwrap();
+        assert_eq!(change.level, LogLevel::Debug);
+        assert_eq!(change.level.to_string(), "debug");
+
+        let err = LogLevelChange::validate_from(LogLevelChangeInput {
+            level: "couic=debug".to_string(),
+        })
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einvalid);
+        assert!(err.errors.contains_key("level"));
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Policy;
+
+/// Userspace map of a policy and address family, compacted by
+/// `/v1/maintenance/compact`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompactedMap {
+    pub policy: Policy,
+    /// `ipv4` or `ipv6`
+    pub family: String,
+    pub entries: usize,
+    pub capacity_before: usize,
+    pub capacity_after: usize,
+}
+
+/// Maps compacted by `/v1/maintenance/compact`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Compaction {
+    pub maps: Vec<CompactedMap>,
+}
@@ -1,9 +1,82 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+use crate::constants::{MAX_METADATA_DETAIL_LENGTH, MAX_METADATA_KIND_LENGTH};
+
+#[derive(Debug, Clone)]
+pub struct InvalidMetadata {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for InvalidMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for InvalidMetadata {}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Metadata {
     pub kind: String,
     pub detail: String,
     pub extra: Option<Map<String, Value>>,
 }
+
+impl Metadata {
+    pub fn new(
+        kind: impl Into<String>,
+        detail: impl Into<String>,
+    ) -> Result<Self, InvalidMetadata> {
+        let metadata = Self {
+            kind: kind.into(),
+            detail: detail.into(),
+            extra: None,
+        };
+        metadata.validate()?;
+        Ok(metadata)
+    }
+
+    /// Check `kind` and `detail` against their maximum lengths
+    pub fn validate(&self) -> Result<(), InvalidMetadata> {
+        if self.kind.len() > MAX_METADATA_KIND_LENGTH {
+            return Err(InvalidMetadata {
+                field: "kind",
+                message: format!("must be at most {MAX_METADATA_KIND_LENGTH} characters"),
+            });
+        }
+        if self.detail.len() > MAX_METADATA_DETAIL_LENGTH {
+            return Err(InvalidMetadata {
+                field: "detail",
+                message: format!("must be at most {MAX_METADATA_DETAIL_LENGTH} characters"),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_new_valid() {
+        let metadata = Metadata::new("manual", "abuse report #42").unwrap();
+        assert_eq!(metadata.kind, "manual");
+        assert_eq!(metadata.detail, "abuse report #42");
+        assert!(metadata.extra.is_none());
+    }
+
+    #[test]
+    fn test_metadata_too_long() {
+        let result = Metadata::new("a".repeat(MAX_METADATA_KIND_LENGTH + 1), "");
+        assert_eq!(result.unwrap_err().field, "kind");
+
+        let result = Metadata::new("manual", "a".repeat(MAX_METADATA_DETAIL_LENGTH + 1));
+        assert_eq!(result.unwrap_err().field, "detail");
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
 use crate::action::Action;
+use crate::cidr::NormalizedCidr;
+use crate::error::{CompositeError, ErrorDetail};
 use crate::rawentry::RawEntry;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
@@ -9,6 +11,62 @@ pub struct PeerJob {
     pub entry: RawEntry,
 }
 
+/// Outcome of a peer job on the receiving node, `error` being set when the
+/// job could not be applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerJobResult {
+    pub cidr: NormalizedCidr,
+    pub action: Action,
+    pub applied: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetail>,
+}
+
+impl PeerJobResult {
+    #[must_use]
+    pub fn applied(job: &PeerJob) -> Self {
+        Self {
+            cidr: job.entry.cidr,
+            action: job.action.clone(),
+            applied: true,
+            error: None,
+        }
+    }
+
+    #[must_use]
+    pub fn failed(job: &PeerJob, error: &CompositeError) -> Self {
+        Self {
+            cidr: job.entry.cidr,
+            action: job.action.clone(),
+            applied: false,
+            error: Some(ErrorDetail {
+                code: error.code.clone(),
+                message: error.to_string(),
+            }),
+        }
+    }
+}
+
+/// Answer of the peer endpoint: the result of each job, or the jobs sent
+/// back as is by nodes older than per-job results, which answer that way
+/// only once all of them were applied
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PeerResponse {
+    Results(Vec<PeerJobResult>),
+    Jobs(Vec<PeerJob>),
+}
+
+impl PeerResponse {
+    #[must_use]
+    pub fn into_results(self) -> Vec<PeerJobResult> {
+        match self {
+            Self::Results(results) => results,
+            Self::Jobs(jobs) => jobs.iter().map(PeerJobResult::applied).collect(),
+        }
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
@@ -38,4 +96,64 @@ mod tests {
         assert_eq!(deserialized.entry.cidr, cidr);
         assert_eq!(deserialized.entry.tag, Some(tag));
     }
+
+    #[test]
+    fn test_peerjob_result_serialization() {
+        let job = PeerJob {
+            action: Action::Remove,
+            entry: RawEntry {
+                cidr: NormalizedCidr::from_str("10.0.0.0/8").unwrap(),
+                tag: None,
+                expiration: Expiration::never(),
+                metadata: None,
+            },
+        };
+
+        let applied = serde_json::to_value(PeerJobResult::applied(&job)).unwrap();
+        assert_eq!(
+            applied,
+            serde_json::json!({"cidr": "10.0.0.0/8", "action": "remove", "applied": true})
+        );
+
+        let error = CompositeError::new(crate::ErrorCode::Enotfound, "Entry not found");
+        let failed = serde_json::to_value(PeerJobResult::failed(&job, &error)).unwrap();
+        assert_eq!(
+            failed,
+            serde_json::json!({
+                "cidr": "10.0.0.0/8",
+                "action": "remove",
+                "applied": false,
+                "error": {"code": error.code, "message": "Entry not found"},
+            })
+        );
+
+        let parsed: PeerJobResult = serde_json::from_value(failed).unwrap();
+        assert!(!parsed.applied);
+        assert_eq!(parsed.error.unwrap().code, crate::ErrorCode::Enotfound);
+    }
+
+    #[test]
+    fn test_peer_response_accepts_both_shapes() {
+        let results: PeerResponse = serde_json::from_value(serde_json::json!([
+            {"cidr": "10.0.0.0/8", "action": "add", "applied": true},
+            {"cidr": "192.0.2.0/24", "action": "remove", "applied": false,
+             "error": {"code": "not_found", "message": "Entry not found"}},
+        ]))
+        .unwrap();
+        let applied: Vec<bool> = results.into_results().iter().map(|r| r.applied).collect();
+        assert_eq!(applied, vec![true, false]);
+
+        // Nodes predating per-job results send the jobs back
+        let jobs: PeerResponse = serde_json::from_value(serde_json::json!([
+            {"action": "add", "entry": {"cidr": "10.0.0.0/8", "expiration": 0}},
+            {"action": "remove", "entry": {"cidr": "192.0.2.0/24", "expiration": 0}},
+        ]))
+        .unwrap();
+        assert!(matches!(jobs, PeerResponse::Jobs(_)));
+        let results = jobs.into_results();
+        assert!(results.iter().all(|result| result.applied));
+        let removed = results.last().unwrap();
+        assert_eq!(removed.cidr.to_string(), "192.0.2.0/24");
+        assert_eq!(removed.action, Action::Remove);
+    }
 }
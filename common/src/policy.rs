@@ -21,6 +21,8 @@ impl std::error::Error for InvalidPolicy {}
 pub enum Policy {
     Drop,
     Ignore,
+    /// Counts the packets it would drop, without dropping them
+    Monitor,
 }
 
 impl fmt::Display for Policy {
@@ -28,6 +30,7 @@ impl fmt::Display for Policy {
         match self {
             Self::Drop => write!(f, "drop"),
             Self::Ignore => write!(f, "ignore"),
+            Self::Monitor => write!(f, "monitor"),
         }
     }
 }
@@ -35,12 +38,14 @@ impl fmt::Display for Policy {
 impl FromStr for Policy {
     type Err = InvalidPolicy;
 
+    /// Parses a policy name whatever its case, e.g. `DROP`
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
+        match s.to_ascii_lowercase().as_str() {
             "drop" => Ok(Self::Drop),
             "ignore" => Ok(Self::Ignore),
+            "monitor" => Ok(Self::Monitor),
             _ => Err(InvalidPolicy(format!(
-                "invalid policy: '{s}' (expected 'drop' or 'ignore')"
+                "invalid policy: '{s}' (expected 'drop', 'ignore' or 'monitor')"
             ))),
         }
     }
@@ -65,3 +70,43 @@ impl ValidateFrom for Policy {
         })
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const POLICIES: [Policy; 3] = [Policy::Drop, Policy::Ignore, Policy::Monitor];
+
+    #[test]
+    fn test_policy_display_from_str_roundtrip() {
+        // Displayed names are the set directory names, they must parse back
+        for policy in POLICIES {
+            let name = policy.to_string();
+            assert_eq!(name, name.to_lowercase());
+            assert_eq!(Policy::from_str(&name).unwrap(), policy);
+        }
+    }
+
+    #[test]
+    fn test_policy_from_str_case_insensitive() {
+        assert_eq!(Policy::from_str("DROP").unwrap(), Policy::Drop);
+        assert_eq!(Policy::from_str("Ignore").unwrap(), Policy::Ignore);
+        assert_eq!(Policy::from_str("mOnItOr").unwrap(), Policy::Monitor);
+        // Displayed back in lowercase, whatever the input case
+        assert_eq!(Policy::from_str("DROP").unwrap().to_string(), "drop");
+    }
+
+    #[test]
+    fn test_policy_rejects_unknown_names() {
+        for input in ["", "drops", " drop", "allow", "../drop"] {
+            assert!(Policy::from_str(input).is_err(), "{input:?}");
+            let err = Policy::validate_from(input.to_string()).unwrap_err();
+            assert_eq!(err.code, ErrorCode::Ebadrequest);
+            assert_eq!(
+                err.errors.get("policy").map(|detail| &detail.code),
+                Some(&ErrorCode::Einvalid)
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::api::PolicyPathInput;
+use crate::cidr::check_unscoped;
 use crate::{CompositeError, ErrorCode, NormalizedCidr, Policy, ValidateFrom};
 
 pub struct PolicyPath {
@@ -31,7 +32,10 @@ impl TryFrom<PolicyPathInput> for PolicyPath {
             None
         };
 
-        let ip = if let Ok(ip) = input.ip.parse::<std::net::IpAddr>() {
+        let ip = if let Err(e) = check_unscoped(&input.ip) {
+            errors.add_detail("ip", ErrorCode::Einvalid, &e.to_string());
+            None
+        } else if let Ok(ip) = input.ip.parse::<std::net::IpAddr>() {
             Some(ip)
         } else {
             errors.add_detail(
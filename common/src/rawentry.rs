@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
@@ -41,6 +42,7 @@ impl RawEntry {
             cidr,
             tag: tag.map(String::from),
             expiration,
+            source: None,
         };
         (entry, metadata)
     }
@@ -83,6 +85,15 @@ impl TryFrom<RawEntryInput> for RawEntry {
             );
         }
 
+        // Validate Metadata
+        if let Some(Err(e)) = input.metadata.as_ref().map(Metadata::validate) {
+            errors.add_detail(
+                &format!("metadata.{}", e.field),
+                ErrorCode::Einvalid,
+                &e.message,
+            );
+        }
+
         if errors.has_errors() {
             return Err(errors);
         }
@@ -108,6 +119,50 @@ impl ValidateFrom for RawEntry {
     }
 }
 
+/// Entries replacing the manual entries of a policy, each CIDR at most once
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct RawEntries(pub Vec<RawEntry>);
+
+impl ValidateFrom for RawEntries {
+    type Input = Vec<RawEntryInput>;
+
+    fn validate_from(input: Vec<RawEntryInput>) -> Result<Self, CompositeError> {
+        let mut errors = CompositeError::new(ErrorCode::Einvalid, "Validation failed");
+        let mut entries = Vec::with_capacity(input.len());
+        let mut seen = HashMap::with_capacity(input.len());
+
+        for (i, input) in input.into_iter().enumerate() {
+            match RawEntry::try_from(input) {
+                Ok(entry) => {
+                    if let Some(first) = seen.insert(entry.cidr, i) {
+                        errors.add_detail(
+                            &format!("entries[{i}].cidr"),
+                            ErrorCode::Einvalid,
+                            &format!("{} is already given by entries[{first}]", entry.cidr),
+                        );
+                    }
+                    entries.push(entry);
+                }
+                Err(e) => {
+                    for (field, detail) in e.errors {
+                        errors.add_detail(
+                            &format!("entries[{i}].{field}"),
+                            detail.code,
+                            &detail.message,
+                        );
+                    }
+                }
+            }
+        }
+
+        if errors.has_errors() {
+            return Err(errors);
+        }
+        Ok(Self(entries))
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 #[allow(clippy::indexing_slicing)]
@@ -216,6 +271,24 @@ mod tests {
         assert!(err.errors.contains_key("expiration"));
     }
 
+    #[test]
+    fn test_metadata_detail_too_long() {
+        let input = RawEntryInput {
+            cidr: "192.168.1.0/24".to_string(),
+            tag: None,
+            expiration: 0,
+            metadata: Some(Metadata {
+                kind: "manual".to_string(),
+                detail: "a".repeat(crate::MAX_METADATA_DETAIL_LENGTH + 1),
+                extra: None,
+            }),
+        };
+
+        let err = RawEntry::try_from(input).unwrap_err();
+        assert_eq!(err.errors.len(), 1);
+        assert!(err.errors.contains_key("metadata.detail"));
+    }
+
     #[test]
     fn test_expiration_zero_is_valid() {
         let input = RawEntryInput {
@@ -361,4 +434,38 @@ mod tests {
         assert!(err.errors.contains_key("tag"));
         assert!(err.errors.contains_key("expiration"));
     }
+
+    #[test]
+    fn test_raw_entries_validation() {
+        let input = |cidr: &str, tag: &str| RawEntryInput {
+            cidr: cidr.to_string(),
+            tag: Some(tag.to_string()),
+            expiration: 0,
+            metadata: None,
+        };
+
+        let entries = RawEntries::validate_from(vec![
+            input("192.0.2.0/24", "feed"),
+            input("2001:db8::/32", "feed"),
+        ])
+        .unwrap();
+        assert_eq!(entries.0.len(), 2);
+
+        let err = RawEntries::validate_from(vec![
+            input("192.0.2.0/24", "feed"),
+            input("not-a-cidr", "feed"),
+            input("192.0.2.1/24", "feed"),
+            input("198.51.100.0/24", "bad tag!"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.errors.len(), 3);
+        assert!(err.errors.contains_key("entries[1].cidr"));
+        assert!(err.errors.contains_key("entries[3].tag"));
+        // 192.0.2.1/24 is normalized to the network of entries[0]
+        assert!(
+            err.errors["entries[2].cidr"]
+                .message
+                .contains("already given by entries[0]")
+        );
+    }
 }
@@ -1,23 +1,118 @@
+use std::collections::HashSet;
 use std::fmt;
 
 use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 
-use crate::api::SetInput;
-use crate::constants::MAX_SET_FILE_SIZE;
+use crate::api::{SetInput, SetPatchInput};
+use crate::cidr::{NormalizedCidr, parse_cidr};
+use crate::constants::{MAX_SET_FILE_SIZE, MAX_SET_HEADER_VALUE_LENGTH, SET_HEADER_PREFIX};
 use crate::error::{CompositeError, ErrorCode};
+use crate::policy::Policy;
 use crate::setname::SetName;
 use crate::validation::ValidateFrom;
 
+const DESCRIPTION_KEY: &str = "description";
+const SOURCE_KEY: &str = "source";
+
+/// Metadata read from the `# @key: value` comments heading a set file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetHeader {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl SetHeader {
+    /// Parses the header of a set file content. Only the comments before the
+    /// first entry are considered, unknown keys being ignored.
+    #[must_use]
+    pub fn parse(content: &str) -> Self {
+        let mut header = Self::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with('#') {
+                break;
+            }
+            let Some((key, value)) = line
+                .strip_prefix(SET_HEADER_PREFIX)
+                .and_then(|field| field.split_once(':'))
+            else {
+                continue;
+            };
+            let value = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+            match key.trim() {
+                DESCRIPTION_KEY => header.description = value,
+                SOURCE_KEY => header.source = value,
+                _ => {}
+            }
+        }
+        header
+    }
+
+    /// Header lines to write ahead of the entries of a set file
+    #[must_use]
+    pub fn render(&self) -> String {
+        [
+            (DESCRIPTION_KEY, &self.description),
+            (SOURCE_KEY, &self.source),
+        ]
+        .iter()
+        .filter_map(|(key, value)| {
+            value
+                .as_ref()
+                .map(|value| format!("{SET_HEADER_PREFIX}{key}: {value}\n"))
+        })
+        .collect()
+    }
+
+    /// Overlays the fields set in `overrides`, an empty value clearing the
+    /// field
+    #[must_use]
+    pub fn merged(self, overrides: Self) -> Self {
+        let overlay = |current: Option<String>, new: Option<String>| match new {
+            Some(value) if value.is_empty() => None,
+            Some(value) => Some(value),
+            None => current,
+        };
+        Self {
+            description: overlay(self.description, overrides.description),
+            source: overlay(self.source, overrides.source),
+        }
+    }
+}
+
+/// Content of a set file, its header lines followed by one entry per line
+#[must_use]
+pub fn render_set_file(header: &SetHeader, entries: &[IpNet]) -> String {
+    header.render()
+        + &entries
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Set {
     pub name: SetName,
+    #[serde(flatten)]
+    pub header: SetHeader,
     pub entries: Vec<IpNet>,
 }
 
 impl fmt::Display for Set {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Set: {}", self.name)?;
+        if let Some(description) = &self.header.description {
+            writeln!(f, "Description: {description}")?;
+        }
+        if let Some(source) = &self.header.source {
+            writeln!(f, "Source: {source}")?;
+        }
         writeln!(f, "Entry count: {}", self.entries.len())?;
         writeln!(f, "Entries:")?;
         for entry in &self.entries {
@@ -30,20 +125,285 @@ impl fmt::Display for Set {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetSummary {
     pub name: SetName,
+    #[serde(flatten)]
+    pub header: SetHeader,
     pub entry_count: usize,
     pub file_size: u64,
+    /// Disabled sets are skipped by reloads, their file being kept
+    #[serde(default)]
+    pub disabled: bool,
 }
 
 impl fmt::Display for SetSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if self.disabled {
+            write!(f, " (disabled)")?;
+        }
+        write!(
+            f,
+            ": {} entries ({} bytes)",
+            self.entry_count, self.file_size
+        )?;
+        if let Some(description) = &self.header.description {
+            write!(f, " - {description}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Response of the disabling or enabling of a set, whose entries only change
+/// on the next reload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetActivation {
+    pub name: SetName,
+    pub disabled: bool,
+    pub reload_required: bool,
+}
+
+/// Entries of all the sets of a policy, those listed by several sets being
+/// counted once in `unique_entries`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetsSummary {
+    pub set_count: usize,
+    /// Entries of all the sets, as listed by each of them
+    pub total_entries: usize,
+    /// Distinct networks among the entries of all the sets
+    pub unique_entries: usize,
+    /// Distinct IPv4 networks
+    pub v4: usize,
+    /// Distinct IPv6 networks
+    pub v6: usize,
+    /// Usage of the maps of the policy, unknown for daemons not reporting it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maps: Option<MapsUsage>,
+}
+
+/// Entries of the IPv4 and IPv6 maps of a policy, manual ones included, and
+/// the number they can hold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapsUsage {
+    pub v4: usize,
+    pub max_v4: usize,
+    pub v6: usize,
+    pub max_v6: usize,
+}
+
+impl SetsSummary {
+    /// Summarizes the entries of each set, compared by network
+    pub fn new<'a>(sets: impl IntoIterator<Item = &'a [IpNet]>) -> Self {
+        let mut summary = Self::default();
+        let mut unique = HashSet::new();
+        for entries in sets {
+            summary.set_count = summary.set_count.saturating_add(1);
+            summary.total_entries = summary.total_entries.saturating_add(entries.len());
+            unique.extend(entries.iter().copied().map(NormalizedCidr::new));
+        }
+        summary.unique_entries = unique.len();
+        summary.v4 = unique
+            .iter()
+            .filter(|cidr| matches!(cidr.inner(), IpNet::V4(_)))
+            .count();
+        summary.v6 = summary.unique_entries.saturating_sub(summary.v4);
+        summary
+    }
+}
+
+impl fmt::Display for SetsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Sets: {}", self.set_count)?;
+        writeln!(f, "Total entries: {}", self.total_entries)?;
+        writeln!(
+            f,
+            "Unique entries: {} ({} IPv4, {} IPv6)",
+            self.unique_entries, self.v4, self.v6
+        )?;
+        if let Some(maps) = self.maps {
+            writeln!(
+                f,
+                "Map usage: {}/{} IPv4, {}/{} IPv6",
+                maps.v4, maps.max_v4, maps.v6, maps.max_v6
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Incremental change of a set, applied without resending its other entries
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetPatch {
+    #[serde(default)]
+    pub add: Vec<IpNet>,
+    #[serde(default)]
+    pub remove: Vec<IpNet>,
+}
+
+impl SetPatch {
+    /// Applies the patch to `entries`, returning the number of entries
+    /// actually added and removed. CIDRs are compared by network, so
+    /// `192.0.2.1/24` matches `192.0.2.0/24`, and added ones already present
+    /// are skipped.
+    pub fn apply(&self, entries: &mut Vec<IpNet>) -> (usize, usize) {
+        let before = entries.len();
+        let remove: HashSet<IpNet> = self.remove.iter().map(IpNet::trunc).collect();
+        entries.retain(|entry| !remove.contains(&entry.trunc()));
+        let removed = before.saturating_sub(entries.len());
+
+        let mut present: HashSet<IpNet> = entries.iter().map(IpNet::trunc).collect();
+        let mut added = 0usize;
+        for cidr in &self.add {
+            if present.insert(cidr.trunc()) {
+                entries.push(*cidr);
+                added = added.saturating_add(1);
+            }
+        }
+        (added, removed)
+    }
+}
+
+impl ValidateFrom for SetPatch {
+    type Input = SetPatchInput;
+
+    fn validate_from(input: SetPatchInput) -> Result<Self, CompositeError> {
+        let mut errors = CompositeError::new(ErrorCode::Einvalid, "Validation failed");
+
+        if input.add.is_empty() && input.remove.is_empty() {
+            errors.add_detail(
+                "add",
+                ErrorCode::Einvalid,
+                "At least one entry to add or remove is required",
+            );
+        }
+
+        let mut parse = |field: &str, values: Vec<String>| -> Vec<IpNet> {
+            let mut cidrs = Vec::with_capacity(values.len());
+            for (i, value) in values.iter().enumerate() {
+                match parse_cidr(value) {
+                    Ok(cidr) => cidrs.push(cidr),
+                    Err(e) => {
+                        errors.add_detail(
+                            &format!("{field}[{i}]"),
+                            ErrorCode::Einvalid,
+                            &e.to_string(),
+                        );
+                    }
+                }
+            }
+            cidrs
+        };
+        let add = parse("add", input.add);
+        let remove = parse("remove", input.remove);
+
+        if errors.has_errors() {
+            return Err(errors);
+        }
+        Ok(Self { add, remove })
+    }
+}
+
+/// Outcome of a set patch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetPatchResult {
+    pub name: SetName,
+    pub added: usize,
+    pub removed: usize,
+    pub entry_count: usize,
+}
+
+impl fmt::Display for SetPatchResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{}: {} entries ({} bytes)",
-            self.name, self.entry_count, self.file_size
+            "Set '{}': {} added, {} removed, {} entries",
+            self.name, self.added, self.removed, self.entry_count
         )
     }
 }
 
+/// A set file added, modified or removed since the sets were last loaded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChangedSet {
+    pub policy: Policy,
+    pub name: String,
+}
+
+impl fmt::Display for ChangedSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.policy, self.name)
+    }
+}
+
+/// Whether set files on disk differ from the loaded sets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetsReloadStatus {
+    pub reload_required: bool,
+    pub changed_sets: Vec<ChangedSet>,
+}
+
+impl fmt::Display for SetsReloadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.reload_required {
+            return writeln!(f, "Sets are up to date");
+        }
+        writeln!(f, "Reload required, changed sets:")?;
+        for set in &self.changed_sets {
+            writeln!(f, "\t{set}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Set file left out of a reload because its signature could not be
+/// verified, the entries it had loaded before being kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedSet {
+    pub name: String,
+    pub error: CompositeError,
+}
+
+/// Entries changed by the last reload of the sets of a policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetsReload {
+    pub policy: Policy,
+    /// Unix timestamp of the reload
+    pub time: u64,
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+    #[serde(default)]
+    pub skipped: Vec<SkippedSet>,
+}
+
+impl fmt::Display for SetsReload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} created, {} updated, {} removed",
+            self.policy, self.created, self.updated, self.removed
+        )?;
+        if !self.skipped.is_empty() {
+            write!(f, ", {} skipped", self.skipped.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Response of a sets reload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetsReloadResult {
+    pub reload_status: String,
+    #[serde(default)]
+    pub reloads: Vec<SetsReload>,
+}
+
+/// Last reload of the sets, per policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetsStatus {
+    /// Unix timestamp of the most recent reload, if sets were ever loaded
+    pub last_reload: Option<u64>,
+    pub reloads: Vec<SetsReload>,
+}
+
 pub fn validate_set_entries_size(entries: &[IpNet], errors: &mut CompositeError) {
     let approx_size: usize = entries
         .iter()
@@ -62,6 +422,26 @@ pub fn validate_set_entries_size(entries: &[IpNet], errors: &mut CompositeError)
     }
 }
 
+/// Header values are written as single comment lines
+fn validate_header_value(field: &str, value: Option<&String>, errors: &mut CompositeError) {
+    let Some(value) = value else {
+        return;
+    };
+    if value.chars().any(char::is_control) {
+        errors.add_detail(
+            field,
+            ErrorCode::Einvalid,
+            "Must not contain control characters",
+        );
+    } else if value.len() > MAX_SET_HEADER_VALUE_LENGTH {
+        errors.add_detail(
+            field,
+            ErrorCode::Einvalid,
+            &format!("Must not exceed {MAX_SET_HEADER_VALUE_LENGTH} characters"),
+        );
+    }
+}
+
 impl ValidateFrom for Set {
     type Input = SetInput;
 
@@ -77,10 +457,17 @@ impl ValidateFrom for Set {
             }
         };
 
+        validate_header_value(DESCRIPTION_KEY, input.description.as_ref(), &mut errors);
+        validate_header_value(SOURCE_KEY, input.source.as_ref(), &mut errors);
+        let header = SetHeader {
+            description: input.description.map(|v| v.trim().to_string()),
+            source: input.source.map(|v| v.trim().to_string()),
+        };
+
         // Validate entries (parse each CIDR string)
         let mut entries = Vec::with_capacity(input.entries.len());
         for (i, entry_str) in input.entries.iter().enumerate() {
-            match entry_str.parse::<IpNet>() {
+            match parse_cidr(entry_str) {
                 Ok(cidr) => entries.push(cidr),
                 Err(e) => {
                     errors.add_detail(
@@ -105,7 +492,11 @@ impl ValidateFrom for Set {
             return Err(errors);
         };
 
-        Ok(Self { name, entries })
+        Ok(Self {
+            name,
+            header,
+            entries,
+        })
     }
 }
 
@@ -118,6 +509,8 @@ mod tests {
     #[test]
     fn test_set_valid() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: "valid-set".to_string(),
             entries: vec!["192.168.1.0/24".to_string(), "10.0.0.0/8".to_string()],
         };
@@ -131,6 +524,8 @@ mod tests {
     #[test]
     fn test_set_invalid_name() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: String::new(),
             entries: vec!["192.168.1.0/24".to_string()],
         };
@@ -143,6 +538,8 @@ mod tests {
     #[test]
     fn test_set_invalid_entry() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: "valid-set".to_string(),
             entries: vec!["not-a-cidr".to_string()],
         };
@@ -155,6 +552,8 @@ mod tests {
     #[test]
     fn test_set_multiple_invalid_entries() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: "valid-set".to_string(),
             entries: vec![
                 "192.168.1.0/24".to_string(),
@@ -175,18 +574,22 @@ mod tests {
     #[test]
     fn test_set_empty_entries() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: "empty-set".to_string(),
             entries: vec![],
         };
         let result = Set::validate_from(input);
         assert!(result.is_ok());
         let set = result.unwrap();
-        assert!(set.entries.is_empty());
+        assert_eq!(set.entries, [] as [ipnet::IpNet; 0]);
     }
 
     #[test]
     fn test_set_both_invalid() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: String::new(),
             entries: vec!["not-a-cidr".to_string()],
         };
@@ -200,6 +603,8 @@ mod tests {
     #[test]
     fn test_set_display() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: "test-set".to_string(),
             entries: vec!["192.168.1.0/24".to_string()],
         };
@@ -214,13 +619,233 @@ mod tests {
     fn test_set_summary_display() {
         let summary = SetSummary {
             name: SetName::try_from("my-set").unwrap(),
+            header: SetHeader::default(),
             entry_count: 42,
             file_size: 1024,
+            disabled: false,
         };
         let display = summary.to_string();
-        assert!(display.contains("my-set"));
-        assert!(display.contains("42 entries"));
-        assert!(display.contains("1024 bytes"));
+        assert_eq!(display, "my-set: 42 entries (1024 bytes)");
+
+        let disabled = SetSummary {
+            disabled: true,
+            ..summary
+        };
+        assert_eq!(
+            disabled.to_string(),
+            "my-set (disabled): 42 entries (1024 bytes)"
+        );
+    }
+
+    #[test]
+    fn test_set_header_parse_stops_at_first_entry() {
+        let content = "# @description: Known scanners\n# plain comment\n\n# @source:  RIPEstat \n# @unknown: x\n10.0.0.0/8\n# @description: ignored\n";
+        assert_eq!(
+            SetHeader::parse(content),
+            SetHeader {
+                description: Some("Known scanners".to_string()),
+                source: Some("RIPEstat".to_string()),
+            }
+        );
+        assert_eq!(SetHeader::parse("10.0.0.0/8\n"), SetHeader::default());
+    }
+
+    #[test]
+    fn test_set_header_render_round_trips() {
+        let header = SetHeader {
+            description: Some("Known scanners".to_string()),
+            source: None,
+        };
+        assert_eq!(header.render(), "# @description: Known scanners\n");
+        assert_eq!(SetHeader::parse(&header.render()), header);
+        assert_eq!(SetHeader::default().render(), "");
+    }
+
+    #[test]
+    fn test_render_set_file_round_trips_header() {
+        let header = SetHeader {
+            description: None,
+            source: Some("RIPEstat".to_string()),
+        };
+        let entries: Vec<IpNet> = vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "2001:db8::/32".parse().unwrap(),
+        ];
+        let content = render_set_file(&header, &entries);
+        assert_eq!(content, "# @source: RIPEstat\n10.0.0.0/8\n2001:db8::/32");
+        assert_eq!(SetHeader::parse(&content), header);
+        assert_eq!(render_set_file(&SetHeader::default(), &[]), "");
+    }
+
+    #[test]
+    fn test_sets_summary_counts_overlaps_once() {
+        let scanners: Vec<IpNet> = vec![
+            "192.0.2.0/24".parse().unwrap(),
+            "198.51.100.7/32".parse().unwrap(),
+        ];
+        let feed: Vec<IpNet> = vec![
+            "192.0.2.1/24".parse().unwrap(),
+            "2001:db8::/32".parse().unwrap(),
+        ];
+        let summary = SetsSummary::new([scanners.as_slice(), feed.as_slice()]);
+        assert_eq!(
+            summary,
+            SetsSummary {
+                set_count: 2,
+                total_entries: 4,
+                unique_entries: 3,
+                v4: 2,
+                v6: 1,
+                maps: None,
+            }
+        );
+        assert_eq!(SetsSummary::new([]), SetsSummary::default());
+    }
+
+    #[test]
+    fn test_set_header_merged_keeps_unset_fields() {
+        let current = SetHeader {
+            description: Some("old".to_string()),
+            source: Some("feed".to_string()),
+        };
+        let merged = current.merged(SetHeader {
+            description: Some("new".to_string()),
+            source: None,
+        });
+        assert_eq!(merged.description.as_deref(), Some("new"));
+        assert_eq!(merged.source.as_deref(), Some("feed"));
+
+        let cleared = merged.merged(SetHeader {
+            description: None,
+            source: Some(String::new()),
+        });
+        assert_eq!(cleared.source, None);
+        assert_eq!(cleared.description.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn test_set_invalid_header() {
+        let input = SetInput {
+            name: "valid-set".to_string(),
+            description: Some("two\nlines".to_string()),
+            source: Some("x".repeat(MAX_SET_HEADER_VALUE_LENGTH + 1)),
+            entries: vec![],
+        };
+        let err = Set::validate_from(input).unwrap_err();
+        assert!(err.errors.contains_key("description"));
+        assert!(err.errors.contains_key("source"));
+    }
+
+    #[test]
+    fn test_set_patch_apply_dedups_by_network() {
+        let mut entries: Vec<IpNet> = vec![
+            "192.0.2.0/24".parse().unwrap(),
+            "198.51.100.0/24".parse().unwrap(),
+        ];
+        let patch = SetPatch {
+            add: vec![
+                "192.0.2.7/24".parse().unwrap(),
+                "203.0.113.0/24".parse().unwrap(),
+                "203.0.113.0/24".parse().unwrap(),
+            ],
+            remove: vec![
+                "198.51.100.0/24".parse().unwrap(),
+                "2001:db8::/32".parse().unwrap(),
+            ],
+        };
+        assert_eq!(patch.apply(&mut entries), (1, 1));
+        assert_eq!(
+            entries,
+            vec![
+                "192.0.2.0/24".parse::<IpNet>().unwrap(),
+                "203.0.113.0/24".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_patch_apply_to_large_set() {
+        let mut entries: Vec<IpNet> = (0..=u16::MAX)
+            .map(|i| format!("2001:db8:{i:x}::/48").parse().unwrap())
+            .collect();
+        let remove: Vec<IpNet> = entries.iter().step_by(2).copied().collect();
+        let add: Vec<IpNet> = entries.iter().take(100).copied().collect();
+        let patch = SetPatch { add, remove };
+        assert_eq!(patch.apply(&mut entries), (50, 32_768));
+        assert_eq!(entries.len(), 32_768 + 50);
+    }
+
+    #[test]
+    fn test_set_patch_validation() {
+        let err = SetPatch::validate_from(SetPatchInput {
+            add: vec!["192.0.2.0/24".to_string(), "bogus".to_string()],
+            remove: vec!["nope".to_string()],
+        })
+        .unwrap_err();
+        assert!(err.errors.contains_key("add[1]"));
+        assert!(err.errors.contains_key("remove[0]"));
+
+        let err = SetPatch::validate_from(SetPatchInput {
+            add: vec![],
+            remove: vec![],
+        })
+        .unwrap_err();
+        assert!(err.errors.contains_key("add"));
+    }
+
+    #[test]
+    fn test_sets_reload_status_display() {
+        let up_to_date = SetsReloadStatus {
+            reload_required: false,
+            changed_sets: vec![],
+        };
+        assert_eq!(up_to_date.to_string(), "Sets are up to date\n");
+
+        let status = SetsReloadStatus {
+            reload_required: true,
+            changed_sets: vec![ChangedSet {
+                policy: Policy::Drop,
+                name: "blocklist".to_string(),
+            }],
+        };
+        let display = status.to_string();
+        assert!(display.contains("Reload required"));
+        assert!(display.contains("drop/blocklist"));
+    }
+
+    #[test]
+    fn test_sets_reload_display() {
+        let reload = SetsReload {
+            policy: Policy::Ignore,
+            time: 1_700_000_000,
+            created: 3,
+            updated: 1,
+            removed: 0,
+            skipped: Vec::new(),
+        };
+        assert_eq!(
+            reload.to_string(),
+            "ignore: 3 created, 1 updated, 0 removed"
+        );
+    }
+
+    #[test]
+    fn test_sets_reload_display_skipped() {
+        let reload = SetsReload {
+            policy: Policy::Drop,
+            time: 1_700_000_000,
+            created: 0,
+            updated: 0,
+            removed: 0,
+            skipped: vec![SkippedSet {
+                name: "blocklist".to_string(),
+                error: CompositeError::new(ErrorCode::Einvalid, "Signature does not match"),
+            }],
+        };
+        assert_eq!(
+            reload.to_string(),
+            "drop: 0 created, 0 updated, 0 removed, 1 skipped"
+        );
     }
 
     #[test]
@@ -234,6 +859,8 @@ mod tests {
     #[test]
     fn test_set_ipv6_entries() {
         let input = SetInput {
+            description: None,
+            source: None,
             name: "ipv6-set".to_string(),
             entries: vec!["2001:db8::/32".to_string(), "::1/128".to_string()],
         };
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::constants::SIGNATURE_EXTENSION;
+use crate::error::{CompositeError, ErrorCode};
+
+pub const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+pub const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+
+/// Path of the detached signature of a set file, e.g. `blocklist.couic.sig`
+#[must_use]
+pub fn signature_path(set_path: &Path) -> PathBuf {
+    let mut path = set_path.as_os_str().to_owned();
+    path.push(SIGNATURE_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Decodes the body of a PEM block with the given label, the keys signing
+/// and verifying set files being read from PEM files
+pub fn pem_to_der(pem: &str, label: &str) -> Result<Vec<u8>, CompositeError> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let body: String = pem
+        .trim()
+        .strip_prefix(&begin)
+        .and_then(|rest| rest.strip_suffix(&end))
+        .ok_or_else(|| {
+            CompositeError::new(
+                ErrorCode::Einvalid,
+                &format!("Expected a PEM block labelled {label}"),
+            )
+        })?
+        .split_whitespace()
+        .collect();
+    STANDARD.decode(body).map_err(|e| {
+        CompositeError::new(ErrorCode::Einvalid, &format!("Invalid PEM encoding: {e}"))
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAorpS1fIpwKdveyNGFfV3AX7hfrHIynSbvNFrFbIHG4o=
+-----END PUBLIC KEY-----
+";
+
+    #[test]
+    fn test_pem_blocks_must_have_the_right_label() {
+        assert_eq!(pem_to_der(PUBLIC_KEY, PUBLIC_KEY_LABEL).unwrap().len(), 44);
+        let err = pem_to_der(PUBLIC_KEY, PRIVATE_KEY_LABEL).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einvalid);
+        assert!(pem_to_der("not a key", PUBLIC_KEY_LABEL).is_err());
+    }
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("/var/lib/couic/sets/drop/blocklist.couic")),
+            Path::new("/var/lib/couic/sets/drop/blocklist.couic.sig")
+        );
+    }
+}
@@ -1,8 +1,12 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::net::IpAddr;
 
 use serde::{Deserialize, Serialize};
 
+use crate::cidr::NormalizedCidr;
+use crate::policy::Policy;
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, Default)]
 #[repr(C)]
 pub struct PktStats {
@@ -12,19 +16,167 @@ pub struct PktStats {
 
 unsafe impl aya::Pod for PktStats {}
 
+/// Shift of the prefix length stored in the top byte of the LPM map values,
+/// below which lies the tag ID, so the XDP program knows the matched entry
+pub const LPM_PREFIX_LEN_SHIFT: u32 = 56;
+
+/// Value of an entry in the LPM maps
+#[must_use]
+pub fn lpm_value(tag_id: u64, prefix_len: u8) -> u64 {
+    (u64::from(prefix_len) << LPM_PREFIX_LEN_SHIFT) | (tag_id & ((1 << LPM_PREFIX_LEN_SHIFT) - 1))
+}
+
+/// Key of the per-entry hit counters of the XDP program: the network address
+/// of the matched entry, in network byte order, IPv4 ones taking the first 4
+/// bytes
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct EntryHitsKey {
+    pub prefix_len: u32,
+    /// 0 for drop, 1 for ignore, 2 for monitor
+    pub policy: u32,
+    pub addr: [u8; 16],
+}
+
+unsafe impl aya::Pod for EntryHitsKey {}
+
+impl EntryHitsKey {
+    #[must_use]
+    pub fn new(policy: Policy, cidr: NormalizedCidr) -> Self {
+        let mut addr = [0; 16];
+        match cidr.network() {
+            IpAddr::V4(ip) => {
+                for (byte, value) in addr.iter_mut().zip(ip.octets()) {
+                    *byte = value;
+                }
+            }
+            IpAddr::V6(ip) => addr = ip.octets(),
+        }
+        Self {
+            prefix_len: u32::from(cidr.prefix_len()),
+            policy: match policy {
+                Policy::Drop => 0,
+                Policy::Ignore => 1,
+                Policy::Monitor => 2,
+            },
+            addr,
+        }
+    }
+}
+
+/// Hit counters of an entry on a CPU, `last_hit` being in nanoseconds since
+/// boot (`CLOCK_MONOTONIC`)
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
+pub struct EntryCounters {
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    pub last_hit: u64,
+}
+
+unsafe impl aya::Pod for EntryCounters {}
+
+/// Packets matched by a single entry since it was created
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntryHits {
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    /// Unix timestamp of the last packet matched, if any
+    pub last_hit: Option<u64>,
+}
+
+/// Packet and byte rates, per second, between two counter snapshots
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct PktRates {
+    pub pps: f64,
+    pub bps: f64,
+}
+
+impl PktRates {
+    /// Rates between two snapshots taken `elapsed_secs` apart, counters going
+    /// backwards (e.g. an LRU eviction) being treated as no traffic
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn between(prev: &PktStats, current: &PktStats, elapsed_secs: f64) -> Self {
+        if elapsed_secs <= 0.0 {
+            return Self::default();
+        }
+        Self {
+            pps: current.rx_packets.saturating_sub(prev.rx_packets) as f64 / elapsed_secs,
+            bps: current.rx_bytes.saturating_sub(prev.rx_bytes) as f64 / elapsed_secs,
+        }
+    }
+
+    /// Rates of every key of `current`, keys missing from `prev` starting at zero
+    #[must_use]
+    pub fn between_maps(
+        prev: &HashMap<String, PktStats>,
+        current: &HashMap<String, PktStats>,
+        elapsed_secs: f64,
+    ) -> HashMap<String, Self> {
+        current
+            .iter()
+            .map(|(key, stats)| {
+                let prev = prev.get(key).copied().unwrap_or_default();
+                (key.clone(), Self::between(&prev, stats, elapsed_secs))
+            })
+            .collect()
+    }
+}
+
+/// Traffic rates over a window, per XDP action and per tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsDelta {
+    pub window_secs: f64,
+    pub xdp: HashMap<String, PktRates>,
+    pub drop_tags: HashMap<String, PktRates>,
+    pub ignore_tags: HashMap<String, PktRates>,
+    #[serde(default)]
+    pub monitor_tags: HashMap<String, PktRates>,
+}
+
+impl fmt::Display for StatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Window: {:.1}s", self.window_secs)?;
+        for (title, rates) in [
+            ("XDP Rates", &self.xdp),
+            ("Drop Tag Rates", &self.drop_tags),
+            ("Ignore Tag Rates", &self.ignore_tags),
+            ("Monitor Tag Rates", &self.monitor_tags),
+        ] {
+            if rates.is_empty() {
+                continue;
+            }
+            writeln!(f, "{title}:")?;
+            let mut keys: Vec<_> = rates.keys().collect();
+            keys.sort();
+            for key in keys {
+                if let Some(rate) = rates.get(key) {
+                    writeln!(f, "  {key}: {:.1} pps, {:.1} bps", rate.pps, rate.bps)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Stats {
     pub drop_cidr_count: usize,
     pub ignore_cidr_count: usize,
+    #[serde(default)]
+    pub monitor_cidr_count: usize,
     pub xdp: HashMap<String, PktStats>,
+    #[serde(default)]
+    pub drop_reasons: HashMap<String, PktStats>,
 }
 
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Drop CIDR Count: {}\nIgnore CIDR Count: {}\nXDP Stats:\n",
-            self.drop_cidr_count, self.ignore_cidr_count
+            "Drop CIDR Count: {}\nIgnore CIDR Count: {}\nMonitor CIDR Count: {}\nXDP Stats:\n",
+            self.drop_cidr_count, self.ignore_cidr_count, self.monitor_cidr_count
         )?;
         let mut actions: Vec<_> = self.xdp.keys().collect();
         actions.sort();
@@ -37,6 +189,20 @@ impl fmt::Display for Stats {
                 )?;
             }
         }
+        if !self.drop_reasons.is_empty() {
+            writeln!(f, "Drop Reasons:")?;
+            let mut reasons: Vec<_> = self.drop_reasons.keys().collect();
+            reasons.sort();
+            for reason in reasons {
+                if let Some(stats) = self.drop_reasons.get(reason) {
+                    write!(
+                        f,
+                        "  Reason: {}\n    RX Packets: {}\n    RX Bytes: {}\n",
+                        reason, stats.rx_packets, stats.rx_bytes
+                    )?;
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -76,6 +242,7 @@ impl fmt::Display for TagStats {
 #[allow(clippy::indexing_slicing)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
     fn test_pktstats_default() {
@@ -84,6 +251,56 @@ mod tests {
         assert_eq!(stats.rx_bytes, 0);
     }
 
+    #[test]
+    fn test_pktrates_between() {
+        let prev = PktStats {
+            rx_packets: 100,
+            rx_bytes: 1000,
+        };
+        let current = PktStats {
+            rx_packets: 300,
+            rx_bytes: 5000,
+        };
+        let rates = PktRates::between(&prev, &current, 2.0);
+        assert!((rates.pps - 100.0).abs() < f64::EPSILON);
+        assert!((rates.bps - 2000.0).abs() < f64::EPSILON);
+
+        // Counters going backwards and empty windows yield no traffic
+        assert_eq!(PktRates::between(&current, &prev, 2.0), PktRates::default());
+        assert_eq!(PktRates::between(&prev, &current, 0.0), PktRates::default());
+    }
+
+    #[test]
+    fn test_pktrates_between_maps() {
+        let prev = HashMap::from([(
+            "XDP_DROP".to_string(),
+            PktStats {
+                rx_packets: 10,
+                rx_bytes: 100,
+            },
+        )]);
+        let current = HashMap::from([
+            (
+                "XDP_DROP".to_string(),
+                PktStats {
+                    rx_packets: 20,
+                    rx_bytes: 200,
+                },
+            ),
+            (
+                "XDP_PASS".to_string(),
+                PktStats {
+                    rx_packets: 5,
+                    rx_bytes: 50,
+                },
+            ),
+        ]);
+        let rates = PktRates::between_maps(&prev, &current, 1.0);
+        assert_eq!(rates.len(), 2);
+        assert!((rates["XDP_DROP"].pps - 10.0).abs() < f64::EPSILON);
+        assert!((rates["XDP_PASS"].bps - 50.0).abs() < f64::EPSILON);
+    }
+
     #[test]
     fn test_pktstats_serde_roundtrip() {
         let stats = PktStats {
@@ -116,11 +333,14 @@ mod tests {
         let stats = Stats {
             drop_cidr_count: 10,
             ignore_cidr_count: 5,
+            monitor_cidr_count: 3,
             xdp,
+            drop_reasons: HashMap::new(),
         };
         let display = stats.to_string();
         assert!(display.contains("Drop CIDR Count: 10"));
         assert!(display.contains("Ignore CIDR Count: 5"));
+        assert!(display.contains("Monitor CIDR Count: 3"));
         assert!(display.contains("Action: drop"));
         assert!(display.contains("Action: pass"));
         assert!(display.contains("RX Packets: 100"));
@@ -132,7 +352,9 @@ mod tests {
         let stats = Stats {
             drop_cidr_count: 0,
             ignore_cidr_count: 0,
+            monitor_cidr_count: 0,
             xdp: HashMap::new(),
+            drop_reasons: HashMap::new(),
         };
         let display = stats.to_string();
         assert!(display.contains("Drop CIDR Count: 0"));
@@ -153,7 +375,9 @@ mod tests {
         let stats = Stats {
             drop_cidr_count: 10,
             ignore_cidr_count: 5,
+            monitor_cidr_count: 3,
             xdp,
+            drop_reasons: HashMap::new(),
         };
         let json = serde_json::to_string(&stats).unwrap();
         let deserialized: Stats = serde_json::from_str(&json).unwrap();
@@ -162,6 +386,35 @@ mod tests {
         assert_eq!(deserialized.xdp["drop"].rx_packets, 100);
     }
 
+    #[test]
+    fn test_stats_drop_reasons() {
+        let mut drop_reasons = HashMap::new();
+        drop_reasons.insert(
+            "matched_drop_v4".to_string(),
+            PktStats {
+                rx_packets: 42,
+                rx_bytes: 2100,
+            },
+        );
+        let stats = Stats {
+            drop_cidr_count: 1,
+            ignore_cidr_count: 0,
+            monitor_cidr_count: 0,
+            xdp: HashMap::new(),
+            drop_reasons,
+        };
+        let display = stats.to_string();
+        assert!(display.contains("Drop Reasons:"));
+        assert!(display.contains("Reason: matched_drop_v4"));
+        assert!(display.contains("RX Packets: 42"));
+
+        // Older daemons do not report drop reasons
+        let json = r#"{"drop_cidr_count":1,"ignore_cidr_count":0,"xdp":{}}"#;
+        let deserialized: Stats = serde_json::from_str(json).unwrap();
+        assert!(deserialized.drop_reasons.is_empty());
+        assert_eq!(deserialized.monitor_cidr_count, 0);
+    }
+
     #[test]
     fn test_tagstats_display_empty() {
         let tag_stats = TagStats {
@@ -214,4 +467,39 @@ mod tests {
         assert_eq!(deserialized.tags[&tag].rx_packets, 42);
         assert_eq!(deserialized.tags[&tag].rx_bytes, 1234);
     }
+
+    #[test]
+    fn test_lpm_value_packs_prefix_len() {
+        let value = lpm_value(42, 24);
+        assert_eq!(value >> LPM_PREFIX_LEN_SHIFT, 24);
+        assert_eq!(value & ((1 << LPM_PREFIX_LEN_SHIFT) - 1), 42);
+        assert_eq!(lpm_value(7, 0), 7);
+        assert_eq!(lpm_value(7, 128) >> LPM_PREFIX_LEN_SHIFT, 128);
+    }
+
+    #[test]
+    fn test_entry_hits_key() {
+        let v4 = EntryHitsKey::new(
+            Policy::Drop,
+            NormalizedCidr::from_str("192.0.2.0/24").unwrap(),
+        );
+        assert_eq!(v4.prefix_len, 24);
+        assert_eq!(v4.policy, 0);
+        assert_eq!(v4.addr[..4], [192, 0, 2, 0]);
+        assert!(v4.addr[4..].iter().all(|byte| *byte == 0));
+
+        let v6 = EntryHitsKey::new(
+            Policy::Ignore,
+            NormalizedCidr::from_str("2001:db8::/32").unwrap(),
+        );
+        assert_eq!(v6.prefix_len, 32);
+        assert_eq!(v6.policy, 1);
+        assert_eq!(v6.addr[..4], [0x20, 0x01, 0x0d, 0xb8]);
+
+        let monitor = EntryHitsKey::new(
+            Policy::Monitor,
+            NormalizedCidr::from_str("192.0.2.0/24").unwrap(),
+        );
+        assert_eq!(monitor.policy, 2);
+    }
 }
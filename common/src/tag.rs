@@ -4,7 +4,10 @@ use std::str::FromStr;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::constants::{MAX_TAG_NAME_LENGTH, RESERVED_TAG_NAME, SET_EXTENSION};
+use crate::constants::{
+    MAX_NAMESPACED_TAG_LENGTH, MAX_TAG_NAME_LENGTH, MAX_TAG_NAMESPACE_DEPTH, RESERVED_TAG_NAME,
+    SET_EXTENSION, TAG_NAMESPACE_SEPARATOR,
+};
 
 #[derive(Debug, Clone)]
 pub struct InvalidTag(pub String);
@@ -30,18 +33,95 @@ impl Tag {
     pub fn is_set_tag(&self) -> bool {
         self.0.ends_with(SET_EXTENSION)
     }
+
+    /// Namespace of the tag, e.g. `team/web` for `team/web/fail2ban`
+    #[must_use]
+    pub fn namespace(&self) -> Option<&str> {
+        self.0
+            .rsplit_once(TAG_NAMESPACE_SEPARATOR)
+            .map(|(namespace, _)| namespace)
+    }
+
+    /// Returns true if the tag is `namespace` itself or lives below it
+    #[must_use]
+    pub fn in_namespace(&self, namespace: &str) -> bool {
+        in_namespace(&self.0, namespace)
+    }
+}
+
+fn in_namespace(tag: &str, namespace: &str) -> bool {
+    tag.strip_prefix(namespace)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(TAG_NAMESPACE_SEPARATOR))
+}
+
+/// Match a tag against a filter pattern.
+///
+/// Supported patterns are exact (`tag`), prefix (`tag*`), suffix (`*tag`),
+/// substring (`*tag*`) and namespace (`team/web/*`). A namespace pattern
+/// matches the namespace itself and every tag below it.
+#[must_use]
+pub fn matches_pattern(tag: &str, pattern: &str) -> bool {
+    let starts_wild = pattern.starts_with('*');
+    let ends_wild = pattern.ends_with('*');
+
+    match (starts_wild, ends_wild) {
+        (true, true) => {
+            // *substring* or * or **
+            if pattern.len() <= 2 {
+                // "*" or "**" matches everything
+                true
+            } else {
+                pattern
+                    .strip_prefix('*')
+                    .and_then(|p| p.strip_suffix('*'))
+                    .is_some_and(|inner| tag.contains(inner))
+            }
+        }
+        (true, false) => {
+            // *suffix
+            pattern
+                .strip_prefix('*')
+                .is_some_and(|suffix| !suffix.is_empty() && tag.ends_with(suffix))
+        }
+        (false, true) => {
+            // namespace/* or prefix*
+            pattern.strip_suffix('*').is_some_and(|prefix| {
+                prefix.strip_suffix(TAG_NAMESPACE_SEPARATOR).map_or_else(
+                    || !prefix.is_empty() && tag.starts_with(prefix),
+                    |namespace| !namespace.is_empty() && in_namespace(tag, namespace),
+                )
+            })
+        }
+        (false, false) => {
+            // exact match
+            tag == pattern
+        }
+    }
+}
+
+fn validate_segment(segment: &str) -> Result<(), InvalidTag> {
+    if segment.len() > MAX_TAG_NAME_LENGTH {
+        return Err(InvalidTag(format!(
+            "Tag must be at most {MAX_TAG_NAME_LENGTH} characters"
+        )));
+    }
+
+    if !segment
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        return Err(InvalidTag(
+            "Tag can only contain alphanumeric characters, hyphens, and underscores".to_string(),
+        ));
+    }
+
+    Ok(())
 }
 
 impl TryFrom<String> for Tag {
     type Error = InvalidTag;
 
     fn try_from(tag: String) -> Result<Self, Self::Error> {
-        if tag.len() > MAX_TAG_NAME_LENGTH {
-            return Err(InvalidTag(format!(
-                "Tag must be at most {MAX_TAG_NAME_LENGTH} characters"
-            )));
-        }
-
         if tag.ends_with(SET_EXTENSION) {
             return Err(InvalidTag(format!(
                 "Tag cannot end with special suffix: {SET_EXTENSION}"
@@ -54,15 +134,31 @@ impl TryFrom<String> for Tag {
             )));
         }
 
-        if !tag.is_empty()
-            && !tag
-                .bytes()
-                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
-        {
-            return Err(InvalidTag(
-                "Tag can only contain alphanumeric characters, hyphens, and underscores"
-                    .to_string(),
-            ));
+        if !tag.contains(TAG_NAMESPACE_SEPARATOR) {
+            validate_segment(&tag)?;
+            return Ok(Self(tag));
+        }
+
+        if tag.len() > MAX_NAMESPACED_TAG_LENGTH {
+            return Err(InvalidTag(format!(
+                "Namespaced tag must be at most {MAX_NAMESPACED_TAG_LENGTH} characters"
+            )));
+        }
+
+        let segments: Vec<&str> = tag.split(TAG_NAMESPACE_SEPARATOR).collect();
+        if segments.len() > MAX_TAG_NAMESPACE_DEPTH {
+            return Err(InvalidTag(format!(
+                "Tag can have at most {MAX_TAG_NAMESPACE_DEPTH} namespace levels"
+            )));
+        }
+
+        for segment in segments {
+            if segment.is_empty() {
+                return Err(InvalidTag(format!(
+                    "Tag namespace levels separated by '{TAG_NAMESPACE_SEPARATOR}' cannot be empty"
+                )));
+            }
+            validate_segment(segment)?;
         }
 
         Ok(Self(tag))
@@ -203,6 +299,48 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_tag_namespaced_valid() {
+        let tag = Tag::try_from("team/web/fail2ban").unwrap();
+        assert_eq!(tag.as_str(), "team/web/fail2ban");
+        assert_eq!(tag.namespace(), Some("team/web"));
+        assert!(tag.in_namespace("team"));
+        assert!(tag.in_namespace("team/web"));
+        assert!(!tag.in_namespace("team/we"));
+
+        let flat = Tag::try_from("fail2ban").unwrap();
+        assert_eq!(flat.namespace(), None);
+    }
+
+    #[test]
+    fn test_tag_namespaced_invalid() {
+        assert!(Tag::try_from("/team").is_err());
+        assert!(Tag::try_from("team/").is_err());
+        assert!(Tag::try_from("team//web").is_err());
+        assert!(Tag::try_from("a/b/c/d/e").is_err());
+        assert!(Tag::try_from("team/web@").is_err());
+        assert!(Tag::try_from("team/web.couic").is_err());
+
+        let long_segment = format!("team/{}", "a".repeat(MAX_TAG_NAME_LENGTH + 1));
+        assert!(Tag::try_from(long_segment).is_err());
+
+        let segment = "a".repeat(MAX_TAG_NAME_LENGTH);
+        let too_long = format!("{segment}/{segment}/{segment}/a");
+        let result = Tag::try_from(too_long);
+        assert!(result.unwrap_err().0.contains("192"));
+    }
+
+    #[test]
+    fn test_matches_pattern_namespace() {
+        assert!(matches_pattern("team/web/fail2ban", "team/web/*"));
+        assert!(matches_pattern("team/web/a/b", "team/web/*"));
+        assert!(matches_pattern("team/web", "team/web/*"));
+        assert!(matches_pattern("team/web/fail2ban", "team/*"));
+        assert!(!matches_pattern("team/website", "team/web/*"));
+        assert!(!matches_pattern("other/web/fail2ban", "team/web/*"));
+        assert!(matches_pattern("team/website", "team/web*"));
+    }
+
     #[test]
     fn test_tag_is_set_tag() {
         let regular = Tag::try_from("regular").unwrap();
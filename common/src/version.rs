@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+/// Optional capabilities advertised by a daemon. Clients detect them by name
+/// and ignore the ones they do not know.
+pub mod feature {
+    /// `X-Couic-Force` header bypassing the protected ranges guard
+    pub const FORCE: &str = "force";
+    /// `X-Couic-Propagate: false` keeping a change away from peers and
+    /// reporting
+    pub const PROPAGATE_HEADER: &str = "propagate_header";
+    /// `Idempotency-Key` header on entry creation
+    pub const IDEMPOTENCY_KEYS: &str = "idempotency_keys";
+    /// `X-Couic-Dry-Run` header checking an entry creation without applying it
+    pub const DRY_RUN: &str = "dry_run";
+    /// `GET /v1/classify/{ip}`
+    pub const CLASSIFY: &str = "classify";
+    /// `GET /v1/stats/delta`
+    pub const STATS_DELTA: &str = "stats_delta";
+    /// Description and source headers of set files
+    pub const SET_HEADERS: &str = "set_headers";
+    /// `ETag` and `If-Match` on sets
+    pub const SET_ETAGS: &str = "set_etags";
+    /// `PATCH /v1/sets/{policy}/{name}`
+    pub const SET_PATCH: &str = "set_patch";
+    /// `POST /v1/sets/{policy}/{name}/reload`
+    pub const SET_RELOAD: &str = "set_reload";
+    /// `GET /v1/{policy}/stream`
+    pub const ENTRIES_STREAM: &str = "entries_stream";
+    /// `GET /v1/logs/tail`
+    pub const LOG_TAIL: &str = "log_tail";
+    /// `?expiring_within=` filter on entry lists
+    pub const EXPIRING_FILTER: &str = "expiring_filter";
+    /// `POST /v1/maintenance/compact`
+    pub const MAP_COMPACTION: &str = "map_compaction";
+    /// `POST /v1/sets/{policy}/{name}/disable` and `/enable`
+    pub const SET_DISABLE: &str = "set_disable";
+    /// `GET` and `POST /v1/lockdown`
+    pub const LOCKDOWN: &str = "lockdown";
+    /// Peering enabled on this daemon
+    pub const PEERING: &str = "peering";
+    /// Reporting enabled on this daemon
+    pub const REPORTING: &str = "reporting";
+    /// Local socket callers authenticated from their credentials
+    pub const LOCAL_TRUST: &str = "local_trust";
+    /// Request bodies compressed with gzip (`Content-Encoding: gzip`)
+    pub const GZIP_REQUESTS: &str = "gzip_requests";
+}
+
+/// Daemon version and capabilities, returned by `GET /v1/version`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServerVersion {
+    pub name: String,
+    pub version: String,
+    pub api_versions: Vec<String>,
+    pub features: Vec<String>,
+}
+
+impl ServerVersion {
+    /// Whether the daemon advertises `feature`, one of [`feature`]
+    #[must_use]
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_version_ignores_unknown_features() {
+        let json = r#"{"name":"couic","version":"9.9.9","api_versions":["v1","v2"],"features":["set_patch","teleport"]}"#;
+        let version: ServerVersion = serde_json::from_str(json).unwrap();
+        assert!(version.supports(feature::SET_PATCH));
+        assert!(!version.supports(feature::PEERING));
+        assert_eq!(version.api_versions, ["v1", "v2"]);
+    }
+}
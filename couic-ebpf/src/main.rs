@@ -5,22 +5,46 @@ use core::mem;
 
 use aya_ebpf::{
     bindings::xdp_action,
+    helpers::{bpf_ktime_get_ns, bpf_redirect},
     macros::{map, xdp},
     maps::{
-        LruPerCpuHashMap, PerCpuArray,
+        Array, LruPerCpuHashMap, PerCpuArray,
         lpm_trie::{Key, LpmTrie},
     },
     programs::XdpContext,
 };
 use network_types::{
     eth::{EthHdr, EtherType},
-    ip::{Ipv4Hdr, Ipv6Hdr},
+    ip::{IpProto, Ipv4Hdr, Ipv6Hdr},
 };
 
 const MAX_DROP_ENTRIES: u32 = 1 << 18; // 262144
 const MAX_IGNORE_ENTRIES: u32 = 1 << 16; // 65536
+const MAX_MONITOR_ENTRIES: u32 = 1 << 18; // 262144
 const XDP_ACTION_MAX: u32 = 5;
 const MAX_TRACKED_TAGS: u32 = 64;
+// Entries with hit counters, below the drop and monitor map capacities: as
+// counters are per CPU, sizing them to the maps would take hundreds of MB.
+// Counters of the least recently matched entries are evicted first.
+const MAX_TRACKED_ENTRIES: u32 = 1 << 16; // 65536
+const ICMP_TYPES: u32 = 256;
+
+// Drop reasons, indexes of the couic_drop_reasons map
+const DROP_REASON_MATCHED_V4: u32 = 0;
+const DROP_REASON_MATCHED_V6: u32 = 1;
+const DROP_REASON_MALFORMED: u32 = 2;
+const DROP_REASON_LOCKDOWN: u32 = 3;
+const DROP_REASON_MAX: u32 = 4;
+
+// LPM values hold the prefix length of the entry in their top byte, below
+// which lies the tag ID
+const PREFIX_LEN_SHIFT: u64 = 56;
+const TAG_ID_MASK: u64 = (1 << PREFIX_LEN_SHIFT) - 1;
+
+// Policies of the couic_entry_hits keys
+const ENTRY_POLICY_DROP: u32 = 0;
+const ENTRY_POLICY_IGNORE: u32 = 1;
+const ENTRY_POLICY_MONITOR: u32 = 2;
 
 #[derive(Debug, Copy, Clone)]
 #[repr(C)]
@@ -29,6 +53,34 @@ struct PktStats {
     pub rx_bytes: u64,
 }
 
+// Matched entry: network address in network byte order, IPv4 ones taking the
+// first 4 bytes
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct EntryHitsKey {
+    pub prefix_len: u32,
+    pub policy: u32,
+    pub addr: [u8; 16],
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct EntryCounters {
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    // bpf_ktime_get_ns of the last packet
+    pub last_hit: u64,
+}
+
+// Verdict of the packets matched by a drop entry, set from userspace
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct DropAction {
+    pub action: u32,
+    // Interface the packets are sent out of, with XDP_REDIRECT
+    pub ifindex: u32,
+}
+
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
@@ -43,6 +95,11 @@ static IPV6_DROP: LpmTrie<u128, u64> = LpmTrie::with_max_entries(MAX_DROP_ENTRIE
 static IPV4_IGNORE: LpmTrie<u32, u64> = LpmTrie::with_max_entries(MAX_IGNORE_ENTRIES, 0);
 #[map(name = "couic_ipv6_ignore")]
 static IPV6_IGNORE: LpmTrie<u128, u64> = LpmTrie::with_max_entries(MAX_IGNORE_ENTRIES, 0);
+// Entries counted as would-be drops, never dropping anything
+#[map(name = "couic_ipv4_monitor")]
+static IPV4_MONITOR: LpmTrie<u32, u64> = LpmTrie::with_max_entries(MAX_MONITOR_ENTRIES, 0);
+#[map(name = "couic_ipv6_monitor")]
+static IPV6_MONITOR: LpmTrie<u128, u64> = LpmTrie::with_max_entries(MAX_MONITOR_ENTRIES, 0);
 #[map(name = "couic_stats")]
 static STATS: PerCpuArray<PktStats> = PerCpuArray::with_max_entries(XDP_ACTION_MAX, 0);
 #[map(name = "couic_drop_stats_per_tag")]
@@ -51,12 +108,48 @@ static DROP_STATS_PER_TAG: LruPerCpuHashMap<u64, PktStats> =
 #[map(name = "couic_ignore_stats_per_tag")]
 static IGNORE_STATS_PER_TAG: LruPerCpuHashMap<u64, PktStats> =
     LruPerCpuHashMap::with_max_entries(MAX_TRACKED_TAGS, 0);
+#[map(name = "couic_monitor_stats_per_tag")]
+static MONITOR_STATS_PER_TAG: LruPerCpuHashMap<u64, PktStats> =
+    LruPerCpuHashMap::with_max_entries(MAX_TRACKED_TAGS, 0);
+#[map(name = "couic_entry_hits")]
+static ENTRY_HITS: LruPerCpuHashMap<EntryHitsKey, EntryCounters> =
+    LruPerCpuHashMap::with_max_entries(MAX_TRACKED_ENTRIES, 0);
+#[map(name = "couic_drop_reasons")]
+static DROP_REASONS: PerCpuArray<PktStats> = PerCpuArray::with_max_entries(DROP_REASON_MAX, 0);
+// ICMP types passed even from dropped sources, indexed by type (non-zero = pass)
+#[map(name = "couic_icmpv4_allow")]
+static ICMPV4_ALLOW: Array<u8> = Array::with_max_entries(ICMP_TYPES, 0);
+#[map(name = "couic_icmpv6_allow")]
+static ICMPV6_ALLOW: Array<u8> = Array::with_max_entries(ICMP_TYPES, 0);
+#[map(name = "couic_drop_action")]
+static DROP_ACTION: Array<DropAction> = Array::with_max_entries(1, 0);
+// Non-zero while every packet not matched by an ignore entry is dropped
+#[map(name = "couic_lockdown")]
+static LOCKDOWN: Array<u32> = Array::with_max_entries(1, 0);
 
 #[xdp]
 pub fn couic(ctx: XdpContext) -> u32 {
-    match try_couic(ctx) {
+    match try_couic(&ctx) {
         Ok(ret) => ret,
-        Err(_) => xdp_action::XDP_ABORTED,
+        Err(_) => {
+            bump_stats(&DROP_REASONS, DROP_REASON_MALFORMED, packet_size(&ctx));
+            xdp_action::XDP_ABORTED
+        }
+    }
+}
+
+#[inline(always)]
+fn packet_size(ctx: &XdpContext) -> u64 {
+    (ctx.data_end() - ctx.data()) as u64
+}
+
+#[inline(always)]
+fn bump_stats(map: &PerCpuArray<PktStats>, index: u32, pkt_size: u64) {
+    unsafe {
+        if let Some(rec) = map.get_ptr_mut(index) {
+            (*rec).rx_packets = (*rec).rx_packets.saturating_add(1);
+            (*rec).rx_bytes = (*rec).rx_bytes.saturating_add(pkt_size);
+        }
     }
 }
 
@@ -104,6 +197,39 @@ fn check_ipv6(address: u128) -> (u32, Option<u64>, bool) {
     (xdp_action::XDP_PASS, None, false)
 }
 
+#[inline(always)]
+fn icmp_type_allowed(ctx: &XdpContext, map: &Array<u8>, offset: usize) -> bool {
+    // Truncated packets never get an exception
+    let Ok(icmp_type) = (unsafe { ptr_at::<u8>(ctx, offset) }) else {
+        return false;
+    };
+    map.get(u32::from(unsafe { *icmp_type }))
+        .is_some_and(|allowed| *allowed != 0)
+}
+
+#[inline(always)]
+fn icmpv4_exception(ctx: &XdpContext, ipv4hdr: *const Ipv4Hdr) -> bool {
+    let hdr = unsafe { &*ipv4hdr };
+    // Only the first fragment carries the ICMP header
+    if hdr.proto != IpProto::Icmp || hdr.frag_offset() != 0 {
+        return false;
+    }
+    let ihl = usize::from(hdr.ihl());
+    if ihl < Ipv4Hdr::LEN {
+        return false;
+    }
+    icmp_type_allowed(ctx, &ICMPV4_ALLOW, EthHdr::LEN + ihl)
+}
+
+#[inline(always)]
+fn icmpv6_exception(ctx: &XdpContext, ipv6hdr: *const Ipv6Hdr) -> bool {
+    // Extension headers (including fragments) are not walked
+    if unsafe { (*ipv6hdr).next_hdr } != IpProto::Ipv6Icmp {
+        return false;
+    }
+    icmp_type_allowed(ctx, &ICMPV6_ALLOW, EthHdr::LEN + Ipv6Hdr::LEN)
+}
+
 #[inline(always)]
 fn bump_tag_stats(map: &LruPerCpuHashMap<u64, PktStats>, tag_id: u64, pkt_size: u64) {
     if let Some(ptr) = map.get_ptr_mut(&tag_id) {
@@ -120,40 +246,202 @@ fn bump_tag_stats(map: &LruPerCpuHashMap<u64, PktStats>, tag_id: u64, pkt_size:
     }
 }
 
-fn try_couic(ctx: XdpContext) -> Result<u32, ()> {
-    let ethhdr: *const EthHdr = unsafe { ptr_at(&ctx, 0)? };
+#[inline(always)]
+fn entry_policy(is_ignore: bool) -> u32 {
+    if is_ignore {
+        ENTRY_POLICY_IGNORE
+    } else {
+        ENTRY_POLICY_DROP
+    }
+}
+
+#[inline(always)]
+fn entry_key_v4(address: u32, value: u64, policy: u32) -> EntryHitsKey {
+    let prefix_len = ((value >> PREFIX_LEN_SHIFT) as u32).min(32);
+    let mask = if prefix_len == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix_len)
+    };
+    let network = (address & mask).to_be_bytes();
+    let mut addr = [0u8; 16];
+    addr[0] = network[0];
+    addr[1] = network[1];
+    addr[2] = network[2];
+    addr[3] = network[3];
+    EntryHitsKey {
+        prefix_len,
+        policy,
+        addr,
+    }
+}
+
+#[inline(always)]
+fn entry_key_v6(address: u128, value: u64, policy: u32) -> EntryHitsKey {
+    let prefix_len = ((value >> PREFIX_LEN_SHIFT) as u32).min(128);
+    let mask = if prefix_len == 0 {
+        0
+    } else {
+        u128::MAX << (128 - prefix_len)
+    };
+    EntryHitsKey {
+        prefix_len,
+        policy,
+        addr: (address & mask).to_be_bytes(),
+    }
+}
+
+#[inline(always)]
+fn bump_entry_hits(key: &EntryHitsKey, pkt_size: u64) {
+    let now = unsafe { bpf_ktime_get_ns() };
+    if let Some(ptr) = ENTRY_HITS.get_ptr_mut(key) {
+        unsafe {
+            (*ptr).rx_packets = (*ptr).rx_packets.saturating_add(1);
+            (*ptr).rx_bytes = (*ptr).rx_bytes.saturating_add(pkt_size);
+            (*ptr).last_hit = now;
+        }
+    } else {
+        let counters = EntryCounters {
+            rx_packets: 1,
+            rx_bytes: pkt_size,
+            last_hit: now,
+        };
+        let _ = ENTRY_HITS.insert(key, &counters, 0);
+    }
+}
+
+fn try_couic(ctx: &XdpContext) -> Result<u32, ()> {
+    let ethhdr: *const EthHdr = unsafe { ptr_at(ctx, 0)? };
 
+    let mut entry = None;
+    let mut locked_down = false;
     let (action, tag, is_ignore) = match unsafe { *ethhdr }.ether_type() {
         Ok(EtherType::Ipv4) => {
-            let ipv4hdr: *const Ipv4Hdr = unsafe { ptr_at(&ctx, EthHdr::LEN)? };
+            let ipv4hdr: *const Ipv4Hdr = unsafe { ptr_at(ctx, EthHdr::LEN)? };
             let source = u32::from_be_bytes(unsafe { (*ipv4hdr).src_addr });
-            check_ipv4(source)
+            let decision = check_ipv4(source);
+            // Ignored sources would not be dropped either
+            if let (_, _, false) = decision
+                && let Some(value) = IPV4_MONITOR.get(&Key::new(32, source.to_be()))
+            {
+                record_monitor(
+                    ctx,
+                    *value,
+                    &entry_key_v4(source, *value, ENTRY_POLICY_MONITOR),
+                );
+            }
+            if let (_, Some(value), is_ignore) = decision {
+                entry = Some(entry_key_v4(source, value, entry_policy(is_ignore)));
+            }
+            match decision {
+                (xdp_action::XDP_DROP, _, _) if icmpv4_exception(ctx, ipv4hdr) => {
+                    (xdp_action::XDP_PASS, None, false)
+                }
+                decision @ (xdp_action::XDP_DROP, _, _) => {
+                    bump_stats(&DROP_REASONS, DROP_REASON_MATCHED_V4, packet_size(ctx));
+                    decision
+                }
+                (_, _, false) if lockdown() && !icmpv4_exception(ctx, ipv4hdr) => {
+                    locked_down = true;
+                    bump_stats(&DROP_REASONS, DROP_REASON_LOCKDOWN, packet_size(ctx));
+                    (xdp_action::XDP_DROP, None, false)
+                }
+                decision => decision,
+            }
         }
         Ok(EtherType::Ipv6) => {
-            let ipv6hdr: *const Ipv6Hdr = unsafe { ptr_at(&ctx, EthHdr::LEN)? };
+            let ipv6hdr: *const Ipv6Hdr = unsafe { ptr_at(ctx, EthHdr::LEN)? };
             let source = u128::from_be_bytes(unsafe { (*ipv6hdr).src_addr });
-            check_ipv6(source)
+            let decision = check_ipv6(source);
+            // Ignored sources would not be dropped either
+            if let (_, _, false) = decision
+                && let Some(value) = IPV6_MONITOR.get(&Key::new(128, source.to_be()))
+            {
+                record_monitor(
+                    ctx,
+                    *value,
+                    &entry_key_v6(source, *value, ENTRY_POLICY_MONITOR),
+                );
+            }
+            if let (_, Some(value), is_ignore) = decision {
+                entry = Some(entry_key_v6(source, value, entry_policy(is_ignore)));
+            }
+            match decision {
+                (xdp_action::XDP_DROP, _, _) if icmpv6_exception(ctx, ipv6hdr) => {
+                    (xdp_action::XDP_PASS, None, false)
+                }
+                decision @ (xdp_action::XDP_DROP, _, _) => {
+                    bump_stats(&DROP_REASONS, DROP_REASON_MATCHED_V6, packet_size(ctx));
+                    decision
+                }
+                (_, _, false) if lockdown() && !icmpv6_exception(ctx, ipv6hdr) => {
+                    locked_down = true;
+                    bump_stats(&DROP_REASONS, DROP_REASON_LOCKDOWN, packet_size(ctx));
+                    (xdp_action::XDP_DROP, None, false)
+                }
+                decision => decision,
+            }
         }
         _ => (xdp_action::XDP_PASS, None, false),
     };
+    // Lockdown drops all the traffic not ignored, which is never redirected
+    let action = if action == xdp_action::XDP_DROP && !locked_down {
+        drop_action()
+    } else {
+        action
+    };
+
+    Ok(record_stats(ctx, action, tag, is_ignore, entry.as_ref()))
+}
+
+#[inline(always)]
+fn lockdown() -> bool {
+    LOCKDOWN.get(0).is_some_and(|enabled| *enabled != 0)
+}
+
+// Final verdict of a packet matched by a drop entry, dropped unless userspace
+// configured a redirection
+#[inline(always)]
+fn drop_action() -> u32 {
+    match DROP_ACTION.get(0) {
+        Some(config) if config.action == xdp_action::XDP_REDIRECT => unsafe {
+            bpf_redirect(config.ifindex, 0) as u32
+        },
+        _ => xdp_action::XDP_DROP,
+    }
+}
 
-    Ok(record_stats(&ctx, action, tag, is_ignore))
+// Counts a packet matched by a monitor entry and not ignored, whatever the
+// verdict of the drop policy
+#[inline(always)]
+fn record_monitor(ctx: &XdpContext, value: u64, key: &EntryHitsKey) {
+    let pkt_size = packet_size(ctx);
+    bump_tag_stats(&MONITOR_STATS_PER_TAG, value & TAG_ID_MASK, pkt_size);
+    bump_entry_hits(key, pkt_size);
 }
 
 #[inline(always)]
-fn record_stats(ctx: &XdpContext, action: u32, tag: Option<u64>, is_ignore: bool) -> u32 {
-    let pkt_size = (ctx.data_end() - ctx.data()) as u64;
+fn record_stats(
+    ctx: &XdpContext,
+    action: u32,
+    tag: Option<u64>,
+    is_ignore: bool,
+    entry: Option<&EntryHitsKey>,
+) -> u32 {
+    let pkt_size = packet_size(ctx);
 
     // Update action stats
-    unsafe {
-        if let Some(rec) = STATS.get_ptr_mut(action) {
-            (*rec).rx_packets = (*rec).rx_packets.saturating_add(1);
-            (*rec).rx_bytes = (*rec).rx_bytes.saturating_add(pkt_size);
-        }
+    bump_stats(&STATS, action, pkt_size);
+
+    // Update per-entry stats, counting the packets passed by an ICMP
+    // exception as well
+    if let Some(key) = entry {
+        bump_entry_hits(key, pkt_size);
     }
 
     // Update per-tag stats
-    if let Some(tag_id) = tag {
+    if let Some(value) = tag {
+        let tag_id = value & TAG_ID_MASK;
         if is_ignore {
             bump_tag_stats(&IGNORE_STATS_PER_TAG, tag_id, pkt_size);
         } else {
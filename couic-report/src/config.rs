@@ -1,4 +1,5 @@
-use serde::Deserialize;
+use chrono::NaiveTime;
+use serde::{Deserialize, Deserializer};
 use std::fs;
 use toml::from_str;
 use uuid::Uuid;
@@ -42,6 +43,53 @@ impl Default for Thresholds {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowStrategy {
+    /// Each batch covers the reports received since the previous one
+    #[default]
+    Tumbling,
+    /// Each batch covers the reports of the last `size_secs` seconds, batches
+    /// overlapping when it exceeds the batch interval
+    Sliding,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Default)]
+pub struct Window {
+    #[serde(default)]
+    pub strategy: WindowStrategy,
+    /// Length of a sliding window, defaults to the batch interval
+    #[serde(default)]
+    pub size_secs: Option<u64>,
+}
+
+/// Daily period during which notifications are held back, in the local time
+/// of the host. The period wraps around midnight when `end` is before `start`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct QuietHours {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&value, "%H:%M").map_err(|e| {
+        serde::de::Error::custom(format!("invalid time '{value}', expected HH:MM: {e}"))
+    })
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     #[serde(default)]
@@ -49,6 +97,10 @@ pub struct Config {
     #[serde(default = "default_batch_interval")]
     pub batch_interval_secs: u64,
     #[serde(default)]
+    pub window: Window,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    #[serde(default)]
     pub thresholds: Thresholds,
     #[serde(default = "default_server")]
     pub server: Server,
@@ -68,6 +120,16 @@ pub struct Server {
     #[serde(default = "default_server_port")]
     pub port: u16,
     pub secret: Uuid,
+    #[serde(default)]
+    pub proxy_protocol: ProxyProtocol,
+}
+
+/// PROXY protocol (v1 or v2) expected at the start of each connection, when
+/// couic-report sits behind a load balancer
+#[derive(Debug, Clone, Copy, Deserialize, Default)]
+pub struct ProxyProtocol {
+    #[serde(default)]
+    pub enabled: bool,
 }
 
 fn default_batch_interval() -> u64 {
@@ -90,19 +152,44 @@ fn default_server() -> Server {
         addr: default_server_addr(),
         port: default_server_port(),
         secret: Uuid::nil(),
+        proxy_protocol: ProxyProtocol::default(),
     }
 }
 
 pub fn load_config(path: &str) -> Result<Config, String> {
     let content =
         fs::read_to_string(path).map_err(|e| format!("failed to read config.toml: {e}"))?;
-    let cfg: Config = from_str(&content).map_err(|e| format!("invalid config.toml: {e}"))?;
+    parse_config(&content)
+}
+
+/// Parses and validates the content of a configuration file
+fn parse_config(content: &str) -> Result<Config, String> {
+    let cfg: Config = from_str(content).map_err(|e| format!("invalid config.toml: {e}"))?;
     if cfg.thresholds.red <= cfg.thresholds.orange {
         return Err(format!(
             "Invalid thresholds: red ({}) must be greater than orange ({})",
             cfg.thresholds.red, cfg.thresholds.orange
         ));
     }
+    if cfg.batch_interval_secs == 0 {
+        return Err("batch_interval_secs must be greater than 0".to_string());
+    }
+    if let Some(size) = cfg.window.size_secs {
+        if cfg.window.strategy != WindowStrategy::Sliding {
+            return Err("window.size_secs only applies to the sliding strategy".to_string());
+        }
+        if size < cfg.batch_interval_secs {
+            return Err(format!(
+                "Invalid window: size_secs ({size}) must not be lower than batch_interval_secs ({})",
+                cfg.batch_interval_secs
+            ));
+        }
+    }
+    if let Some(quiet_hours) = &cfg.quiet_hours
+        && quiet_hours.start == quiet_hours.end
+    {
+        return Err("Invalid quiet_hours: start and end must differ".to_string());
+    }
     if cfg.server.secret.is_nil() {
         return Err("secret cannot be empty in [server] configuration".to_string());
     }
@@ -116,3 +203,61 @@ pub fn load_config(path: &str) -> Result<Config, String> {
     }
     Ok(cfg)
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const SERVER: &str = "[server]\nsecret = \"0b9e4b8e-5f4b-4c3e-9d4a-2a1f3c6e7d80\"\n";
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    fn quiet_hours(start: &str, end: &str) -> Result<Config, String> {
+        parse_config(&format!(
+            "[quiet_hours]\nstart = \"{start}\"\nend = \"{end}\"\n{SERVER}"
+        ))
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let quiet = quiet_hours("09:00", "17:30").unwrap().quiet_hours.unwrap();
+        assert!(!quiet.contains(time("08:59")));
+        assert!(quiet.contains(time("09:00")));
+        assert!(quiet.contains(time("17:29")));
+        assert!(!quiet.contains(time("17:30")));
+    }
+
+    #[test]
+    fn quiet_hours_wrap_around_midnight() {
+        let quiet = quiet_hours("22:00", "07:00").unwrap().quiet_hours.unwrap();
+        assert!(quiet.contains(time("22:00")));
+        assert!(quiet.contains(time("23:59")));
+        assert!(quiet.contains(time("00:00")));
+        assert!(quiet.contains(time("06:59")));
+        assert!(!quiet.contains(time("07:00")));
+        assert!(!quiet.contains(time("12:00")));
+        assert!(!quiet.contains(time("21:59")));
+    }
+
+    #[test]
+    fn quiet_hours_bounds_must_differ() {
+        let err = quiet_hours("22:00", "22:00").unwrap_err();
+        assert!(err.contains("start and end must differ"), "{err}");
+    }
+
+    #[test]
+    fn quiet_hours_must_be_valid_times() {
+        for (start, end) in [("24:00", "07:00"), ("22:00", "7h"), ("22:60", "07:00")] {
+            let err = quiet_hours(start, end).unwrap_err();
+            assert!(err.contains("expected HH:MM"), "{start}-{end}: {err}");
+        }
+    }
+
+    #[test]
+    fn quiet_hours_are_optional() {
+        assert!(parse_config(SERVER).unwrap().quiet_hours.is_none());
+    }
+}
@@ -1,12 +1,22 @@
 mod config;
 mod notifier;
+mod proxy;
 mod stats;
 mod worker;
 
-use axum::{Json, Router, extract::Path, extract::State, http::StatusCode, routing::post};
+use axum::{
+    Json, Router,
+    extract::{ConnectInfo, Path, State},
+    http::StatusCode,
+    routing::post,
+};
 use clap::Parser;
-use std::sync::Arc;
-use tokio::{net::TcpListener, sync::mpsc};
+use std::{net::IpAddr, sync::Arc};
+use tokio::{
+    net::TcpListener,
+    signal::unix::{SignalKind, signal},
+    sync::mpsc,
+};
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
@@ -15,23 +25,25 @@ use common::Action;
 use crate::{
     config::load_config,
     notifier::{NotificationDispatcher, Notifier, discord::DiscordNotifier},
+    proxy::{ProxyListener, Source},
     stats::Report,
     worker::start_worker,
 };
 
 #[derive(Clone)]
 struct AppState {
-    tx: mpsc::UnboundedSender<Vec<Report>>,
+    tx: mpsc::UnboundedSender<(IpAddr, Vec<Report>)>,
     secret: Uuid,
 }
 
 async fn report_handler(
     Path(secret): Path<String>,
+    ConnectInfo(source): ConnectInfo<Source>,
     State(state): State<AppState>,
     Json(reports): Json<Vec<Report>>,
 ) -> Result<&'static str, StatusCode> {
     if secret != state.secret.to_string() {
-        debug!("Invalid secret received");
+        debug!("Invalid secret received from {source}");
         return Err(StatusCode::UNAUTHORIZED);
     }
 
@@ -41,10 +53,13 @@ async fn report_handler(
         .filter(|r| r.action == Action::Add)
         .collect();
     if !filtered.is_empty() {
-        debug!("Received {} valid reports", filtered.len());
-        state.tx.send(filtered).expect("send to worker");
+        debug!("Received {} valid reports from {source}", filtered.len());
+        state
+            .tx
+            .send((source.0.ip(), filtered))
+            .expect("send to worker");
     } else {
-        debug!("No valid reports received");
+        debug!("No valid reports received from {source}");
     }
     Ok("reports received")
 }
@@ -75,6 +90,18 @@ async fn main() {
         }
     };
 
+    info!(
+        "Batching reports every {}s, {:?} window",
+        cfg.batch_interval_secs, cfg.window.strategy
+    );
+    if let Some(quiet_hours) = &cfg.quiet_hours {
+        info!(
+            "Quiet hours from {} to {}",
+            quiet_hours.start.format("%H:%M"),
+            quiet_hours.end.format("%H:%M")
+        );
+    }
+
     let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
 
     if let Some(dc) = cfg.discord {
@@ -82,18 +109,19 @@ async fn main() {
         notifiers.push(Arc::new(DiscordNotifier::new(
             dc.webhook_url,
             cfg.server.name.clone(),
-            cfg.batch_interval_secs,
             cfg.thresholds.clone(),
         )));
     }
 
     let dispatcher = Arc::new(NotificationDispatcher::new(notifiers));
 
-    let (tx, rx) = mpsc::unbounded_channel::<Vec<Report>>();
-    tokio::spawn(start_worker(
+    let (tx, rx) = mpsc::unbounded_channel::<(IpAddr, Vec<Report>)>();
+    let worker = tokio::spawn(start_worker(
         rx,
         dispatcher,
         cfg.batch_interval_secs,
+        cfg.window,
+        cfg.quiet_hours,
         cfg.thresholds.clone(),
     ));
 
@@ -111,6 +139,34 @@ async fn main() {
         .await
         .expect("Failed to bind TCP listener");
 
-    info!("Server running on {}", bind_addr);
-    axum::serve(listener, app).await.unwrap();
+    let app = app.into_make_service_with_connect_info::<Source>();
+    if cfg.server.proxy_protocol.enabled {
+        info!("Server running on {} (PROXY protocol)", bind_addr);
+        axum::serve(ProxyListener::new(listener), app)
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+            .unwrap();
+    } else {
+        info!("Server running on {}", bind_addr);
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+            .unwrap();
+    }
+
+    // The server dropped the last sender of reports, so the worker flushes
+    // the current window and returns
+    if let Err(e) = worker.await {
+        error!("Report worker failed: {e}");
+    }
+}
+
+/// Resolves on SIGTERM or SIGINT
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    info!("Shutting down, waiting for pending requests");
 }
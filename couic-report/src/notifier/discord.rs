@@ -16,17 +16,11 @@ pub struct DiscordNotifier {
     webhook_url: String,
     client: Client,
     server_name: String,
-    batch_interval_secs: u64,
     thresholds: Thresholds,
 }
 
 impl DiscordNotifier {
-    pub fn new(
-        webhook_url: String,
-        server_name: String,
-        batch_interval_secs: u64,
-        thresholds: Thresholds,
-    ) -> Self {
+    pub fn new(webhook_url: String, server_name: String, thresholds: Thresholds) -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(CLIENT_TIMEOUT))
             .build()
@@ -36,13 +30,11 @@ impl DiscordNotifier {
             webhook_url,
             client,
             server_name,
-            batch_interval_secs,
             thresholds,
         }
     }
 
-    fn format_period(&self) -> String {
-        let secs = self.batch_interval_secs;
+    fn format_period(secs: u64) -> String {
         if secs.is_multiple_of(3600) {
             format!("{}-hour", secs / 3600)
         } else if secs.is_multiple_of(60) {
@@ -73,9 +65,23 @@ impl Notifier for DiscordNotifier {
         };
 
         let timestamp = Utc::now().to_rfc3339();
-        let period = self.format_period();
+        let period = Self::format_period(stats.period_secs);
         let color = self.get_color(stats.total_count);
 
+        let mut fields = vec![
+            serde_json::json!({ "name": "Total CIDRs", "value": format!(":shield: **{}**", stats.total_count), "inline": true }),
+            serde_json::json!({ "name": "Distinct CIDRs", "value": format!(":mag: **{}**", stats.distinct_cidrs), "inline": true }),
+            serde_json::json!({ "name": "Top Tag", "value": format!(":satellite: {}", top_tag_text), "inline": true }),
+        ];
+        if let Some((source, count)) = &stats.top_source
+            && stats.sources > 1
+        {
+            fields.push(serde_json::json!({ "name": "Sources", "value": format!(":satellite_orbital: **{}**, top **{}** (×{})", stats.sources, source, count), "inline": true }));
+        }
+        if stats.quiet_count > 0 {
+            fields.push(serde_json::json!({ "name": "During Quiet Hours", "value": format!(":zzz: **{}**", stats.quiet_count), "inline": true }));
+        }
+
         let embeds = vec![serde_json::json!({
             "title": format!("📊 {} Report Summary", period),
             "timestamp": timestamp,
@@ -88,11 +94,7 @@ impl Notifier for DiscordNotifier {
                 "text": "Couic Report",
                 "icon_url": "https://couic.net/images/white-150.png"
             },
-            "fields": [
-                { "name": "Total CIDRs", "value": format!(":shield: **{}**", stats.total_count), "inline": true },
-                { "name": "Distinct CIDRs", "value": format!(":mag: **{}**", stats.distinct_cidrs), "inline": true },
-                { "name": "Top Tag", "value": format!(":satellite: {}", top_tag_text), "inline": true }
-            ]
+            "fields": fields
         })];
 
         let body = serde_json::json!({
@@ -13,6 +13,7 @@ pub enum NotifyError {
 
 pub type NotifyResult<T> = Result<T, NotifyError>;
 
+#[allow(clippy::double_must_use)]
 #[async_trait]
 pub trait Notifier: Send + Sync {
     async fn send_statistics(&self, stats: &Statistics) -> NotifyResult<()>;
@@ -0,0 +1,359 @@
+use axum::{
+    extract::connect_info::Connected,
+    serve::{IncomingStream, Listener},
+};
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::timeout,
+};
+use tracing::warn;
+
+/// Signature opening a PROXY protocol v2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest PROXY protocol v1 header, CRLF included
+const V1_MAX_LENGTH: usize = 107;
+/// Delay given to a client to send its header before being dropped
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum ProxyError {
+    #[error("failed to read PROXY header: {0}")]
+    Io(#[from] io::Error),
+    #[error("no PROXY header received within {}s", HEADER_TIMEOUT.as_secs())]
+    Timeout,
+    #[error("malformed PROXY header: {0}")]
+    Malformed(&'static str),
+}
+
+/// Address of the reporting daemon: the peer of the connection, or the
+/// source carried by its PROXY header
+#[derive(Debug, Clone, Copy)]
+pub struct Source(pub SocketAddr);
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for Source {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+impl Connected<IncomingStream<'_, ProxyListener>> for Source {
+    fn connect_info(stream: IncomingStream<'_, ProxyListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+/// Listener reading the PROXY protocol header sent by a load balancer at the
+/// start of each connection, so that the address of the reporting daemon is
+/// seen instead of the one of the balancer. Headers are read in their own
+/// task, a slow client holding back no other; connections with a missing or
+/// malformed header are closed.
+pub struct ProxyListener {
+    accepted: mpsc::Receiver<(TcpStream, SocketAddr)>,
+    local_addr: io::Result<SocketAddr>,
+}
+
+impl ProxyListener {
+    pub fn new(listener: TcpListener) -> Self {
+        let local_addr = listener.local_addr();
+        let (tx, accepted) = mpsc::channel(64);
+        tokio::spawn(accept_loop(listener, tx));
+        Self {
+            accepted,
+            local_addr,
+        }
+    }
+}
+
+impl Listener for ProxyListener {
+    type Io = TcpStream;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.accepted.recv().await {
+            Some(connection) => connection,
+            // The accept loop only stops with the listener
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        match &self.local_addr {
+            Ok(addr) => Ok(*addr),
+            Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+        }
+    }
+}
+
+async fn accept_loop(mut listener: TcpListener, tx: mpsc::Sender<(TcpStream, SocketAddr)>) {
+    loop {
+        let (mut stream, peer) = Listener::accept(&mut listener).await;
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let header = match timeout(HEADER_TIMEOUT, read_header(&mut stream)).await {
+                Ok(header) => header,
+                Err(_) => Err(ProxyError::Timeout),
+            };
+            match header {
+                // LOCAL connections (health checks of the balancer) carry no source
+                Ok(source) => {
+                    let _ = tx.send((stream, source.unwrap_or(peer))).await;
+                }
+                Err(e) => warn!("Rejected connection from {peer}: {e}"),
+            }
+        });
+    }
+}
+
+/// Reads a PROXY protocol v1 or v2 header, returning the source address it
+/// carries, if any
+async fn read_header<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> Result<Option<SocketAddr>, ProxyError> {
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+    if start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(ProxyError::Malformed("missing PROXY signature"))
+    }
+}
+
+async fn read_v1<R: AsyncRead + Unpin>(
+    stream: &mut R,
+    start: &[u8],
+) -> Result<Option<SocketAddr>, ProxyError> {
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(ProxyError::Malformed("v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| ProxyError::Malformed("v1 header is not ASCII"))?;
+    parse_v1(line)
+}
+
+fn parse_v1(line: &str) -> Result<Option<SocketAddr>, ProxyError> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", protocol @ ("TCP4" | "TCP6"), source, _, port, _] => {
+            let ip: IpAddr = source
+                .parse()
+                .map_err(|_| ProxyError::Malformed("invalid v1 source address"))?;
+            if ip.is_ipv4() != (*protocol == "TCP4") {
+                return Err(ProxyError::Malformed("v1 address family mismatch"));
+            }
+            let port: u16 = port
+                .parse()
+                .map_err(|_| ProxyError::Malformed("invalid v1 source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(ProxyError::Malformed("invalid v1 header")),
+    }
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Option<SocketAddr>, ProxyError> {
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    let [version_command, family, high, low] = head;
+    let mut addresses = vec![0u8; usize::from(u16::from_be_bytes([high, low]))];
+    stream.read_exact(&mut addresses).await?;
+    if version_command >> 4 != 2 {
+        return Err(ProxyError::Malformed("unsupported v2 version"));
+    }
+    match version_command & 0x0f {
+        0x0 => Ok(None),
+        0x1 => parse_v2(family, &addresses),
+        _ => Err(ProxyError::Malformed("unsupported v2 command")),
+    }
+}
+
+fn parse_v2(family: u8, addresses: &[u8]) -> Result<Option<SocketAddr>, ProxyError> {
+    match family >> 4 {
+        // AF_INET: source, destination, source port, destination port
+        0x1 => {
+            let Some(block) = addresses.get(..12) else {
+                return Err(ProxyError::Malformed("truncated v2 IPv4 addresses"));
+            };
+            let ip = Ipv4Addr::new(block[0], block[1], block[2], block[3]);
+            let port = u16::from_be_bytes([block[8], block[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        // AF_INET6
+        0x2 => {
+            let Some(block) = addresses.get(..36) else {
+                return Err(ProxyError::Malformed("truncated v2 IPv6 addresses"));
+            };
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&block[..16]);
+            let port = u16::from_be_bytes([block[32], block[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port,
+            )))
+        }
+        // AF_UNSPEC and AF_UNIX carry no usable source
+        0x0 | 0x3 => Ok(None),
+        _ => Err(ProxyError::Malformed("unsupported v2 address family")),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    async fn header(bytes: &[u8]) -> Result<Option<SocketAddr>, ProxyError> {
+        let mut stream = bytes;
+        read_header(&mut stream).await
+    }
+
+    fn v2(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut bytes = V2_SIGNATURE.to_vec();
+        bytes.push(0x20 | command);
+        bytes.push(family);
+        bytes.extend_from_slice(&u16::try_from(addresses.len()).unwrap().to_be_bytes());
+        bytes.extend_from_slice(addresses);
+        bytes
+    }
+
+    fn malformed(result: Result<Option<SocketAddr>, ProxyError>, reason: &str) {
+        match result {
+            Err(ProxyError::Malformed(message)) => assert_eq!(message, reason),
+            other => panic!("expected {reason}, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn v1_tcp_headers_carry_the_source() {
+        let source = header(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /")
+            .await
+            .unwrap();
+        assert_eq!(source, Some("192.0.2.1:56324".parse().unwrap()));
+
+        let source = header(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n")
+            .await
+            .unwrap();
+        assert_eq!(source, Some("[2001:db8::1]:56324".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v1_unknown_headers_carry_no_source() {
+        assert_eq!(header(b"PROXY UNKNOWN\r\n").await.unwrap(), None);
+        assert_eq!(
+            header(b"PROXY UNKNOWN ffff:f::1 ffff:f::2 1 2\r\n")
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn v1_headers_are_checked() {
+        malformed(
+            header(b"PROXY TCP4 2001:db8::1 2001:db8::2 56324 443\r\n").await,
+            "v1 address family mismatch",
+        );
+        malformed(
+            header(b"PROXY TCP4 192.0.2.1 198.51.100.1 99999 443\r\n").await,
+            "invalid v1 source port",
+        );
+        malformed(
+            header(b"PROXY TCP4 192.0.2.1 198.51.100.1\r\n").await,
+            "invalid v1 header",
+        );
+
+        let mut long = b"PROXY TCP4 ".to_vec();
+        long.resize(V1_MAX_LENGTH + 10, b'1');
+        long.extend_from_slice(b"\r\n");
+        malformed(header(&long).await, "v1 header too long");
+
+        // The connection closed before the end of the line
+        let err = header(b"PROXY TCP4 192.0.2.1").await.unwrap_err();
+        assert!(matches!(err, ProxyError::Io(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn v2_proxy_headers_carry_the_source() {
+        let mut v4 = vec![192, 0, 2, 1, 198, 51, 100, 1];
+        v4.extend_from_slice(&56324u16.to_be_bytes());
+        v4.extend_from_slice(&443u16.to_be_bytes());
+        let source = header(&v2(0x1, 0x11, &v4)).await.unwrap();
+        assert_eq!(source, Some("192.0.2.1:56324".parse().unwrap()));
+
+        let mut v6 = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+        v6.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        v6.extend_from_slice(&56324u16.to_be_bytes());
+        v6.extend_from_slice(&443u16.to_be_bytes());
+        let source = header(&v2(0x1, 0x21, &v6)).await.unwrap();
+        assert_eq!(source, Some("[2001:db8::1]:56324".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v2_local_headers_carry_no_source() {
+        // Health checks of the balancer, whatever the addresses sent
+        assert_eq!(header(&v2(0x0, 0x00, &[])).await.unwrap(), None);
+        assert_eq!(header(&v2(0x0, 0x11, &[0; 12])).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn v2_headers_are_checked() {
+        // IPv4 family with the length of no address
+        malformed(
+            header(&v2(0x1, 0x11, &[192, 0, 2, 1])).await,
+            "truncated v2 IPv4 addresses",
+        );
+        malformed(
+            header(&v2(0x1, 0x21, &[0; 12])).await,
+            "truncated v2 IPv6 addresses",
+        );
+        malformed(
+            header(&v2(0x1, 0x41, &[0; 12])).await,
+            "unsupported v2 address family",
+        );
+        malformed(
+            header(&v2(0x2, 0x11, &[0; 12])).await,
+            "unsupported v2 command",
+        );
+        let mut version_1 = v2(0x1, 0x11, &[0; 12]);
+        if let Some(byte) = version_1.get_mut(V2_SIGNATURE.len()) {
+            *byte = 0x11;
+        }
+        malformed(header(&version_1).await, "unsupported v2 version");
+
+        // Fewer address bytes than announced
+        let mut truncated = v2(0x1, 0x11, &[0; 12]);
+        truncated.truncate(truncated.len() - 4);
+        let err = header(&truncated).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Io(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn headers_without_signature_are_refused() {
+        malformed(
+            header(b"GET /v1/reports HTTP/1.1\r\n").await,
+            "missing PROXY signature",
+        );
+        // Too short for a signature
+        let err = header(b"PROXY").await.unwrap_err();
+        assert!(matches!(err, ProxyError::Io(_)), "{err}");
+    }
+}
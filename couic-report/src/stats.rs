@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 
 pub use common::Report;
 
@@ -7,6 +8,14 @@ pub struct Statistics {
     pub total_count: usize,
     pub distinct_cidrs: usize,
     pub top_tag: Option<(String, usize)>,
+    /// Number of reporting daemons, told apart by address
+    pub sources: usize,
+    /// Daemon that sent the most reports
+    pub top_source: Option<(IpAddr, usize)>,
+    /// Length of the period covered, in seconds
+    pub period_secs: u64,
+    /// Reports received during quiet hours, held until this batch
+    pub quiet_count: usize,
 }
 
 #[derive(Debug, Default)]
@@ -14,11 +23,14 @@ pub struct StatisticsAccumulator {
     pub total_count: usize,
     cidrs: HashSet<String>,
     tag_counts: HashMap<String, usize>,
+    source_counts: HashMap<IpAddr, usize>,
 }
 
 impl StatisticsAccumulator {
-    pub fn add_reports(&mut self, reports: &[Report]) {
+    /// Adds the reports sent by the daemon at `source`
+    pub fn add_reports(&mut self, source: IpAddr, reports: &[Report]) {
         self.total_count += reports.len();
+        *self.source_counts.entry(source).or_insert(0) += reports.len();
 
         for report in reports {
             self.cidrs.insert(report.entry.cidr.to_string());
@@ -28,23 +40,67 @@ impl StatisticsAccumulator {
         }
     }
 
-    pub fn to_statistics(&self) -> Statistics {
+    pub fn to_statistics(&self, period_secs: u64, quiet_count: usize) -> Statistics {
         let top_tag = self
             .tag_counts
             .iter()
             .max_by_key(|(_, count)| *count)
             .map(|(tag, count)| (tag.clone(), *count));
+        let top_source = self
+            .source_counts
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(source, count)| (*source, *count));
 
         Statistics {
             total_count: self.total_count,
             distinct_cidrs: self.cidrs.len(),
             top_tag,
+            sources: self.source_counts.len(),
+            top_source,
+            period_secs,
+            quiet_count,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use common::{Action, Entry, Expiration, Policy};
+
+    fn report(cidr: &str) -> Report {
+        Report {
+            action: Action::Add,
+            policy: Policy::Drop,
+            entry: Entry {
+                creation: 0,
+                cidr: cidr.parse().unwrap(),
+                tag: None,
+                expiration: Expiration::never(),
+                source: None,
+            },
+            metadata: None,
         }
     }
 
-    pub fn reset(&mut self) {
-        self.total_count = 0;
-        self.cidrs.clear();
-        self.tag_counts.clear();
+    #[test]
+    fn reports_are_grouped_per_source() {
+        let busy: IpAddr = "192.0.2.1".parse().unwrap();
+        let quiet: IpAddr = "2001:db8::1".parse().unwrap();
+        let mut accumulator = StatisticsAccumulator::default();
+        accumulator.add_reports(
+            busy,
+            &[report("198.51.100.1/32"), report("198.51.100.2/32")],
+        );
+        accumulator.add_reports(quiet, &[report("198.51.100.1/32")]);
+        accumulator.add_reports(busy, &[report("198.51.100.3/32")]);
+
+        let stats = accumulator.to_statistics(900, 0);
+        assert_eq!(stats.total_count, 4);
+        assert_eq!(stats.distinct_cidrs, 3);
+        assert_eq!(stats.sources, 2);
+        assert_eq!(stats.top_source, Some((busy, 3)));
     }
 }
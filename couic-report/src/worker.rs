@@ -1,22 +1,96 @@
-use std::{sync::Arc, time::Duration};
+use chrono::Local;
+use std::{
+    collections::VecDeque,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc::UnboundedReceiver;
-use tracing::info;
+use tracing::{debug, info};
 
 use crate::{
-    config::{ThresholdMin, Thresholds},
+    config::{QuietHours, ThresholdMin, Thresholds, Window, WindowStrategy},
     notifier::NotificationDispatcher,
     stats::{Report, StatisticsAccumulator},
 };
 
+/// Reports sent in one request by the daemon at `source`
+struct Batch {
+    received: Instant,
+    /// Whether the batch was received during quiet hours
+    quiet: bool,
+    source: IpAddr,
+    reports: Vec<Report>,
+}
+
+type Batches = VecDeque<Batch>;
+
 pub async fn start_worker(
-    mut rx: UnboundedReceiver<Vec<Report>>,
+    mut rx: UnboundedReceiver<(IpAddr, Vec<Report>)>,
     dispatcher: Arc<NotificationDispatcher>,
     batch_interval_secs: u64,
+    window: Window,
+    quiet_hours: Option<QuietHours>,
     thresholds: Thresholds,
 ) {
-    let mut accumulator = StatisticsAccumulator::default();
+    let mut batches = Batches::new();
+    // Ticks skipped during quiet hours since the last active one
+    let mut held_ticks: u64 = 0;
     let mut tick = tokio::time::interval(Duration::from_secs(batch_interval_secs));
+    let window_secs = match window.strategy {
+        WindowStrategy::Tumbling => batch_interval_secs,
+        WindowStrategy::Sliding => window.size_secs.unwrap_or(batch_interval_secs),
+    };
+
+    let is_quiet = || quiet_hours.is_some_and(|quiet| quiet.contains(Local::now().time()));
+
+    loop {
+        tokio::select! {
+            received = rx.recv() => {
+                let Some((source, reports)) = received else {
+                    // Every sender is gone with the server: the reports of
+                    // the current window, quiet or not, would be lost
+                    let period_secs = window_secs + batch_interval_secs * held_ticks;
+                    info!("Flushing {} report batches before exiting", batches.len());
+                    dispatch_period(&mut batches, period_secs, &thresholds, &dispatcher).await;
+                    return;
+                };
+                batches.push_back(Batch {
+                    received: Instant::now(),
+                    quiet: is_quiet(),
+                    source,
+                    reports,
+                });
+            }
+            _ = tick.tick() => {
+                if is_quiet() {
+                    // Nothing is evicted nor reset, so the held reports are
+                    // summarized at the next active tick
+                    held_ticks += 1;
+                    debug!("Quiet hours, holding {} report batches", batches.len());
+                    continue;
+                }
 
+                // The period covers the window and the ticks held back
+                let period_secs = window_secs + batch_interval_secs * held_ticks;
+                held_ticks = 0;
+                dispatch_period(&mut batches, period_secs, &thresholds, &dispatcher).await;
+                if window.strategy == WindowStrategy::Tumbling {
+                    batches.clear();
+                }
+            }
+        }
+    }
+}
+
+/// Dispatch the statistics of the batches received during the last
+/// `period_secs`, evicting older ones
+async fn dispatch_period(
+    batches: &mut Batches,
+    period_secs: u64,
+    thresholds: &Thresholds,
+    dispatcher: &NotificationDispatcher,
+) {
     fn meets_min_threshold(count: usize, thresholds: &Thresholds) -> bool {
         match thresholds.threshold_min {
             ThresholdMin::Green => true,
@@ -25,19 +99,107 @@ pub async fn start_worker(
         }
     }
 
-    loop {
-        tokio::select! {
-            Some(reports) = rx.recv() => {
-                accumulator.add_reports(&reports);
-            }
-            _ = tick.tick() => {
-                if accumulator.total_count > 0 && meets_min_threshold(accumulator.total_count, &thresholds) {
-                    let stats = accumulator.to_statistics();
-                    info!("Dispatching statistics: total={} distinct={}", stats.total_count, stats.distinct_cidrs);
-                    dispatcher.dispatch(stats).await;
-                }
-                accumulator.reset();
-            }
+    if let Some(start) = Instant::now().checked_sub(Duration::from_secs(period_secs)) {
+        while batches.front().is_some_and(|batch| batch.received < start) {
+            batches.pop_front();
+        }
+    }
+
+    let mut accumulator = StatisticsAccumulator::default();
+    let mut quiet_count = 0;
+    for batch in batches.iter() {
+        accumulator.add_reports(batch.source, &batch.reports);
+        if batch.quiet {
+            quiet_count += batch.reports.len();
         }
     }
+    if accumulator.total_count > 0 && meets_min_threshold(accumulator.total_count, thresholds) {
+        let stats = accumulator.to_statistics(period_secs, quiet_count);
+        info!(
+            "Dispatching statistics: total={} distinct={} sources={}",
+            stats.total_count, stats.distinct_cidrs, stats.sources
+        );
+        dispatcher.dispatch(stats).await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::notifier::{Notifier, NotifyResult};
+    use crate::stats::Statistics;
+    use async_trait::async_trait;
+    use common::{Action, Entry, Expiration, Policy};
+    use std::sync::Mutex;
+    use tokio::sync::mpsc;
+
+    /// Keeps the statistics it is sent
+    #[derive(Default)]
+    struct Recorder {
+        sent: Mutex<Vec<Statistics>>,
+    }
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        async fn send_statistics(&self, stats: &Statistics) -> NotifyResult<()> {
+            self.sent.lock().unwrap().push(stats.clone());
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "recorder"
+        }
+    }
+
+    fn report(cidr: &str) -> Report {
+        Report {
+            action: Action::Add,
+            policy: Policy::Drop,
+            entry: Entry {
+                creation: 0,
+                cidr: cidr.parse().unwrap(),
+                tag: None,
+                expiration: Expiration::never(),
+                source: None,
+            },
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn pending_reports_are_flushed_on_shutdown() {
+        let recorder = Arc::new(Recorder::default());
+        let dispatcher = Arc::new(NotificationDispatcher::new(vec![
+            Arc::clone(&recorder) as Arc<dyn Notifier>
+        ]));
+        let (tx, rx) = mpsc::unbounded_channel();
+        let worker = tokio::spawn(start_worker(
+            rx,
+            dispatcher,
+            3600,
+            Window::default(),
+            None,
+            Thresholds::default(),
+        ));
+
+        // Lets the first tick, immediate, go by with nothing to dispatch, the
+        // next one being an hour away
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let source: IpAddr = "192.0.2.1".parse().unwrap();
+        tx.send((
+            source,
+            vec![report("198.51.100.1/32"), report("198.51.100.2/32")],
+        ))
+        .unwrap();
+        drop(tx);
+        worker.await.unwrap();
+
+        let sent = recorder.sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        let stats = sent.first().unwrap();
+        assert_eq!(stats.total_count, 2);
+        assert_eq!(stats.top_source, Some((source, 2)));
+    }
 }
@@ -0,0 +1,110 @@
+//! Userspace bookkeeping of the LPM stores, without the eBPF maps.
+//!
+//! Run with `cargo bench -p couic --features bench --bench lpm_store`.
+#![allow(clippy::unwrap_used)]
+
+use std::hint::black_box;
+use std::net::{IpAddr, Ipv4Addr};
+
+use common::{NormalizedCidr, Policy};
+use couic::bench::{LpmStore, NullMap, StoredEntry};
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use crossbeam_channel::unbounded;
+
+const ENTRIES: u32 = 100_000;
+
+fn cidrs() -> Vec<NormalizedCidr> {
+    (0..ENTRIES)
+        .map(|i| {
+            let ip = IpAddr::V4(Ipv4Addr::from(0x0a00_0000 | i));
+            NormalizedCidr::from_addr_and_prefix(ip, 32).unwrap()
+        })
+        .collect()
+}
+
+fn stored(i: u64) -> StoredEntry {
+    StoredEntry {
+        creation: i,
+        tag_id: i % 16,
+        expiration: 0,
+        source: 0,
+        propagate: false,
+    }
+}
+
+fn store() -> LpmStore<NullMap> {
+    // The expiry receiver is only fed by the cleanup thread, not started here
+    let (sender, _) = unbounded();
+    LpmStore::with_capacity(NullMap, ENTRIES as usize, Policy::Drop, sender)
+}
+
+fn filled_store(cidrs: &[NormalizedCidr]) -> LpmStore<NullMap> {
+    let store = store();
+    for (i, cidr) in (0..).zip(cidrs) {
+        store.add_stored(*cidr, stored(i)).unwrap();
+    }
+    store
+}
+
+fn bench_lpm_store(c: &mut Criterion) {
+    let cidrs = cidrs();
+    let mut group = c.benchmark_group("lpm_store");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(u64::from(ENTRIES)));
+
+    group.bench_function("add_stored", |b| {
+        b.iter_batched(
+            store,
+            |store| {
+                for (i, cidr) in (0..).zip(&cidrs) {
+                    store.add_stored(*cidr, stored(i)).unwrap();
+                }
+                store
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.bench_function("remove_stored", |b| {
+        b.iter_batched(
+            || filled_store(&cidrs),
+            |store| {
+                for cidr in &cidrs {
+                    store.remove_stored_if(*cidr, |_| Ok(())).unwrap();
+                }
+                store
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    group.bench_function("apply_batch", |b| {
+        let changes: Vec<_> = (0..)
+            .zip(&cidrs)
+            .map(|(i, cidr)| (*cidr, Some(stored(i))))
+            .collect();
+        b.iter_batched(
+            || (store(), changes.clone()),
+            |(store, changes)| {
+                black_box(store.apply_batch(changes));
+                store
+            },
+            BatchSize::LargeInput,
+        );
+    });
+
+    let filled = filled_store(&cidrs);
+    group.bench_function("list_stored", |b| {
+        b.iter(|| black_box(filled.list_stored().unwrap()));
+    });
+
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("longest_match", |b| {
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3));
+        b.iter(|| black_box(filled.longest_match(black_box(ip)).unwrap()));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_lpm_store);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+use std::time::SystemTime;
+
+use axum::http::{
+    HeaderMap, HeaderValue, StatusCode,
+    header::{CACHE_CONTROL, ETAG, LAST_MODIFIED},
+};
+use axum::response::{IntoResponse, Response};
+
+use crate::extractors::IfNoneMatch;
+
+/// Responses may be stored, but are revalidated before each use
+const CACHE_CONTROL_VALUE: &str = "no-cache";
+
+/// Headers letting clients revalidate a response instead of downloading it
+/// again, an invalid ETag being left out
+pub(super) fn validators(etag: &str, last_modified: Option<SystemTime>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL_VALUE));
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        headers.insert(ETAG, etag);
+    }
+    if let Some(modified) = last_modified
+        && let Ok(modified) = HeaderValue::from_str(&httpdate::fmt_http_date(modified))
+    {
+        headers.insert(LAST_MODIFIED, modified);
+    }
+    headers
+}
+
+/// Empty `304 Not Modified` when `if_none_match` shows the client already
+/// holds the response of ETag `etag`
+pub(super) fn not_modified(
+    if_none_match: &IfNoneMatch,
+    etag: &str,
+    last_modified: Option<SystemTime>,
+) -> Option<Response> {
+    if_none_match
+        .is_fresh(etag, last_modified)
+        .then(|| (StatusCode::NOT_MODIFIED, validators(etag, last_modified)).into_response())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn if_none_match(etags: Option<&str>, modified_since: Option<u64>) -> IfNoneMatch {
+        IfNoneMatch {
+            etags: etags.map(str::to_string),
+            modified_since: modified_since.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        }
+    }
+
+    #[test]
+    fn not_modified_matches_etags_weakly() {
+        let modified = Some(UNIX_EPOCH + Duration::from_secs(1_000));
+        for etags in ["\"a-1-0\"", "\"b\", W/\"a-1-0\"", "*"] {
+            let response = not_modified(&if_none_match(Some(etags), None), "\"a-1-0\"", modified);
+            let response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers()[ETAG], "\"a-1-0\"");
+            assert_eq!(
+                response.headers()[LAST_MODIFIED],
+                "Thu, 01 Jan 1970 00:16:40 GMT"
+            );
+        }
+        assert!(not_modified(&if_none_match(Some("\"a-0-0\""), None), "\"a-1-0\"", None).is_none());
+    }
+
+    #[test]
+    fn not_modified_falls_back_to_dates_without_etags() {
+        let modified = Some(UNIX_EPOCH + Duration::from_secs(1_000));
+        assert!(not_modified(&if_none_match(None, Some(1_000)), "\"a\"", modified).is_some());
+        assert!(not_modified(&if_none_match(None, Some(999)), "\"a\"", modified).is_none());
+        assert!(not_modified(&if_none_match(None, Some(1_000)), "\"a\"", None).is_none());
+        // ETags take precedence over dates
+        let both = if_none_match(Some("\"b\""), Some(1_000));
+        assert!(not_modified(&both, "\"a\"", modified).is_none());
+        assert!(not_modified(&if_none_match(None, None), "\"a\"", modified).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+use axum::{
+    Extension, Json, Router, extract::State, http::StatusCode, middleware, response::IntoResponse,
+    routing::get,
+};
+
+use super::AppState;
+use super::middleware::auth_middleware;
+use super::rbac::{Resource, Scope, Verb};
+use common::{Client, Health, ServerVersion, feature};
+
+/// API versions served by this daemon
+const API_VERSIONS: [&str; 1] = ["v1"];
+/// Capabilities of every daemon of this version, whatever its configuration
+const STATIC_FEATURES: [&str; 17] = [
+    feature::FORCE,
+    feature::PROPAGATE_HEADER,
+    feature::IDEMPOTENCY_KEYS,
+    feature::DRY_RUN,
+    feature::CLASSIFY,
+    feature::STATS_DELTA,
+    feature::SET_HEADERS,
+    feature::SET_ETAGS,
+    feature::SET_PATCH,
+    feature::SET_RELOAD,
+    feature::ENTRIES_STREAM,
+    feature::LOG_TAIL,
+    feature::EXPIRING_FILTER,
+    feature::MAP_COMPACTION,
+    feature::SET_DISABLE,
+    feature::LOCKDOWN,
+    feature::GZIP_REQUESTS,
+];
+
+/// Report daemon health along with the authenticated client
+async fn get_health(Extension(client): Extension<Client>) -> impl IntoResponse {
+    let health = Health {
+        status: "ok".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        client: client.name,
+        group: client.group,
+    };
+    (StatusCode::OK, Json(health)).into_response()
+}
+
+/// Report the daemon version and the optional features it supports
+async fn get_version(State(state): State<AppState>) -> impl IntoResponse {
+    let mut features: Vec<String> = STATIC_FEATURES.iter().map(ToString::to_string).collect();
+    if state.firewall_service.peering_enabled() {
+        features.push(feature::PEERING.to_string());
+    }
+    if state.firewall_service.reporting_enabled() {
+        features.push(feature::REPORTING.to_string());
+    }
+    if state.rbac_service.read().await.local_trust_enabled() {
+        features.push(feature::LOCAL_TRUST.to_string());
+    }
+
+    let version = ServerVersion {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        api_versions: API_VERSIONS.iter().map(ToString::to_string).collect(),
+        features,
+    };
+    (StatusCode::OK, Json(version)).into_response()
+}
+
+/// Create router for health and version endpoints
+pub(super) fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/v1/health",
+            get(get_health)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Health, Verb::Get))),
+        )
+        .route(
+            "/v1/version",
+            get(get_version)
+                .route_layer(middleware::from_fn_with_state(state, auth_middleware))
+                .route_layer(Extension(Scope::with(Resource::Health, Verb::Get))),
+        )
+}
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::{Entry, Policy, RawEntry};
+use tokio::sync::Mutex;
+
+/// How long the result of a keyed creation is replayed
+pub(super) const IDEMPOTENCY_TTL: Duration = Duration::from_secs(600);
+/// Recorded creations kept at most, the oldest being evicted first
+pub(super) const IDEMPOTENCY_CAPACITY: usize = 16_384;
+
+/// Keys are scoped per client and policy, so clients cannot replay each
+/// other's results
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct IdempotencyKey {
+    pub client: String,
+    pub policy: Policy,
+    pub key: String,
+}
+
+struct Recorded {
+    request: RawEntry,
+    entry: Entry,
+    at: Instant,
+}
+
+/// Outcome of looking up a creation request by its key
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Lookup {
+    /// Never seen or expired, the request is to be processed
+    Miss,
+    /// Same key and request, the recorded entry is returned as is
+    Replay(Entry),
+    /// Same key for another request
+    Mismatch,
+}
+
+/// Results of the recent successful entry creations carrying an
+/// `Idempotency-Key` header
+pub(super) struct IdempotencyCache {
+    recorded: HashMap<IdempotencyKey, Recorded>,
+    /// Recording order, oldest first, for expiry and eviction
+    order: VecDeque<(Instant, IdempotencyKey)>,
+    /// Locks of the keys being processed, so requests with the same key
+    /// wait for each other while others go on
+    in_flight: HashMap<IdempotencyKey, Arc<Mutex<()>>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            recorded: HashMap::new(),
+            order: VecDeque::new(),
+            in_flight: HashMap::new(),
+            ttl,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Lock of `key`, to hold while its request is processed and given back
+    /// with `release`
+    pub fn slot(&mut self, key: &IdempotencyKey) -> Arc<Mutex<()>> {
+        Arc::clone(self.in_flight.entry(key.clone()).or_default())
+    }
+
+    /// Forgets the lock of `key` once no other request waits for it
+    pub fn release(&mut self, key: &IdempotencyKey, slot: &Arc<Mutex<()>>) {
+        // Held by the map and by the releasing request only
+        if Arc::strong_count(slot) <= 2 {
+            self.in_flight.remove(key);
+        }
+    }
+
+    pub fn lookup(&self, key: &IdempotencyKey, request: &RawEntry, now: Instant) -> Lookup {
+        match self.recorded.get(key) {
+            Some(recorded) if now.duration_since(recorded.at) < self.ttl => {
+                if recorded.request == *request {
+                    Lookup::Replay(recorded.entry.clone())
+                } else {
+                    Lookup::Mismatch
+                }
+            }
+            _ => Lookup::Miss,
+        }
+    }
+
+    pub fn record(&mut self, key: IdempotencyKey, request: RawEntry, entry: Entry, now: Instant) {
+        while let Some((at, oldest)) = self.order.front() {
+            if now.duration_since(*at) < self.ttl && self.recorded.len() < self.capacity {
+                break;
+            }
+            // A key recorded again since is queued later
+            if self
+                .recorded
+                .get(oldest)
+                .is_some_and(|recorded| recorded.at == *at)
+            {
+                self.recorded.remove(oldest);
+            }
+            self.order.pop_front();
+        }
+        self.order.push_back((now, key.clone()));
+        self.recorded.insert(
+            key,
+            Recorded {
+                request,
+                entry,
+                at: now,
+            },
+        );
+    }
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new(IDEMPOTENCY_TTL, IDEMPOTENCY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use common::Expiration;
+
+    fn key(client: &str, key: &str) -> IdempotencyKey {
+        IdempotencyKey {
+            client: client.to_string(),
+            policy: Policy::Drop,
+            key: key.to_string(),
+        }
+    }
+
+    fn request(cidr: &str) -> RawEntry {
+        RawEntry {
+            cidr: cidr.parse().unwrap(),
+            tag: None,
+            expiration: Expiration::never(),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_repeated_key_replays_original_entry() {
+        let mut cache = IdempotencyCache::default();
+        let now = Instant::now();
+        let req = request("192.0.2.1/32");
+        assert_eq!(cache.lookup(&key("a", "k1"), &req, now), Lookup::Miss);
+
+        let entry = req.clone().into_entry();
+        cache.record(key("a", "k1"), req.clone(), entry.clone(), now);
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            cache.lookup(&key("a", "k1"), &req, later),
+            Lookup::Replay(entry)
+        );
+        // Keys of other clients are distinct
+        assert_eq!(cache.lookup(&key("b", "k1"), &req, later), Lookup::Miss);
+    }
+
+    #[test]
+    fn test_reused_key_with_other_request_mismatches() {
+        let mut cache = IdempotencyCache::default();
+        let now = Instant::now();
+        let req = request("192.0.2.1/32");
+        cache.record(key("a", "k1"), req.clone(), req.into_entry(), now);
+
+        assert_eq!(
+            cache.lookup(&key("a", "k1"), &request("192.0.2.2/32"), now),
+            Lookup::Mismatch
+        );
+    }
+
+    #[test]
+    fn test_expired_and_evicted_keys_miss() {
+        let mut cache = IdempotencyCache::new(Duration::from_secs(10), 2);
+        let now = Instant::now();
+        let req = request("192.0.2.1/32");
+        cache.record(key("a", "k1"), req.clone(), req.clone().into_entry(), now);
+        assert_eq!(
+            cache.lookup(&key("a", "k1"), &req, now + Duration::from_secs(10)),
+            Lookup::Miss
+        );
+
+        let t1 = now + Duration::from_secs(1);
+        let t2 = now + Duration::from_secs(2);
+        cache.record(key("a", "k2"), req.clone(), req.clone().into_entry(), t1);
+        cache.record(key("a", "k3"), req.clone(), req.clone().into_entry(), t2);
+        // At capacity, the oldest key made room for the newest
+        assert_eq!(cache.lookup(&key("a", "k1"), &req, t2), Lookup::Miss);
+        assert!(matches!(
+            cache.lookup(&key("a", "k3"), &req, t2),
+            Lookup::Replay(_)
+        ));
+    }
+
+    #[test]
+    fn test_key_recorded_again_outlives_its_first_record() {
+        let mut cache = IdempotencyCache::new(Duration::from_secs(10), 2);
+        let now = Instant::now();
+        let req = request("192.0.2.1/32");
+        cache.record(key("a", "k1"), req.clone(), req.clone().into_entry(), now);
+
+        let t1 = now + Duration::from_secs(10);
+        let t2 = now + Duration::from_secs(11);
+        cache.record(key("a", "k1"), req.clone(), req.clone().into_entry(), t1);
+        cache.record(key("a", "k2"), req.clone(), req.clone().into_entry(), t2);
+        // The expired first record of k1 does not evict the second one
+        assert!(matches!(
+            cache.lookup(&key("a", "k1"), &req, t2),
+            Lookup::Replay(_)
+        ));
+        assert_eq!(cache.order.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_slot_is_shared_per_key_until_released() {
+        let mut cache = IdempotencyCache::default();
+        let first = cache.slot(&key("a", "k1"));
+        let held = first.lock().await;
+
+        // Same key waits, other keys go on
+        let waiting = cache.slot(&key("a", "k1"));
+        assert!(Arc::ptr_eq(&first, &waiting));
+        assert!(waiting.try_lock().is_err());
+        assert!(cache.slot(&key("a", "k2")).try_lock().is_ok());
+
+        // Kept while another request waits for it
+        cache.release(&key("a", "k1"), &first);
+        assert!(cache.in_flight.contains_key(&key("a", "k1")));
+        drop(held);
+        drop(first);
+        cache.release(&key("a", "k1"), &waiting);
+        assert!(!cache.in_flight.contains_key(&key("a", "k1")));
+    }
+}
@@ -0,0 +1,73 @@
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use tracing::warn;
+
+use crate::extractors::ValidatedJson;
+use common::{Client, LockdownInput};
+
+use super::AppState;
+use super::middleware::auth_middleware;
+use super::rbac::{Resource, Scope, Verb};
+
+// Report whether lockdown is enabled
+async fn get_lockdown(State(state): State<AppState>) -> impl IntoResponse {
+    match state.firewall_service.lockdown() {
+        Ok(lockdown) => (StatusCode::OK, Json(lockdown)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// Drop every packet not matched by an ignore entry, or stop doing so
+async fn set_lockdown(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Client>,
+    ValidatedJson(input): ValidatedJson<LockdownInput>,
+) -> impl IntoResponse {
+    warn!(
+        actor.name = %actor.name,
+        actor.group = %actor.group,
+        enabled = input.enabled,
+        force = input.force,
+        "lockdown change requested"
+    );
+    match state
+        .firewall_service
+        .set_lockdown(input.enabled, input.force)
+    {
+        Ok(lockdown) => (StatusCode::OK, Json(lockdown)).into_response(),
+        Err(e) => {
+            warn!(
+                actor.name = %actor.name,
+                error = %e,
+                "lockdown change refused"
+            );
+            e.into_response()
+        }
+    }
+}
+
+/// Create router for the lockdown endpoints
+pub(super) fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/v1/lockdown",
+            get(get_lockdown)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Lockdown, Verb::Get))),
+        )
+        .route(
+            "/v1/lockdown",
+            post(set_lockdown)
+                .route_layer(middleware::from_fn_with_state(state, auth_middleware))
+                .route_layer(Extension(Scope::with(Resource::Lockdown, Verb::Update))),
+        )
+}
@@ -0,0 +1,117 @@
+use axum::{
+    Extension, Json, Router,
+    extract::State,
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get, post},
+};
+use tracing::{error, warn};
+
+use crate::error::CompositeError;
+use crate::extractors::ValidatedJson;
+use common::{Client, ErrorCode, LogFilter, LogLevelChange};
+
+use super::AppState;
+use super::middleware::auth_middleware;
+use super::rbac::{Resource, Scope, Verb};
+
+// Report the log filter in effect
+async fn get_log_level(State(state): State<AppState>) -> impl IntoResponse {
+    match state.log_level.current() {
+        Ok(filter) => (StatusCode::OK, Json(LogFilter { filter })).into_response(),
+        Err(e) => reload_error(&e).into_response(),
+    }
+}
+
+// Replace the log filter until the next change or restart
+async fn set_log_level(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Client>,
+    ValidatedJson(change): ValidatedJson<LogLevelChange>,
+) -> impl IntoResponse {
+    let previous = state.log_level.current().unwrap_or_default();
+    // Logged before the change, at a level kept by most filters
+    warn!(
+        actor.name = %actor.name,
+        actor.group = %actor.group,
+        previous = %previous,
+        level = %change.level,
+        "log level changed"
+    );
+    match state.log_level.set(change.level) {
+        Ok(()) => {
+            let filter = change.level.to_string();
+            (StatusCode::OK, Json(LogFilter { filter })).into_response()
+        }
+        Err(e) => {
+            error!(
+                actor.name = %actor.name,
+                error = %e,
+                "failed to change log level"
+            );
+            reload_error(&e).into_response()
+        }
+    }
+}
+
+// Restore the log filter of the startup, e.g. the directives of RUST_LOG
+async fn reset_log_level(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Client>,
+) -> impl IntoResponse {
+    let previous = state.log_level.current().unwrap_or_default();
+    warn!(
+        actor.name = %actor.name,
+        actor.group = %actor.group,
+        previous = %previous,
+        "log level reset"
+    );
+    match state.log_level.reset() {
+        Ok(filter) => (StatusCode::OK, Json(LogFilter { filter })).into_response(),
+        Err(e) => {
+            error!(
+                actor.name = %actor.name,
+                error = %e,
+                "failed to reset log level"
+            );
+            reload_error(&e).into_response()
+        }
+    }
+}
+
+fn reload_error(e: &tracing_subscriber::reload::Error) -> CompositeError {
+    CompositeError::new(
+        ErrorCode::Einternal,
+        &format!("Failed to access the log filter: {e}"),
+    )
+}
+
+/// Create router for the log level endpoints
+pub(super) fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/v1/log-level",
+            get(get_log_level)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Logging, Verb::Get))),
+        )
+        .route(
+            "/v1/log-level",
+            post(set_log_level)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Logging, Verb::Update))),
+        )
+        .route(
+            "/v1/log-level",
+            delete(reset_log_level)
+                .route_layer(middleware::from_fn_with_state(state, auth_middleware))
+                .route_layer(Extension(Scope::with(Resource::Logging, Verb::Update))),
+        )
+}
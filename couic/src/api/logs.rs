@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    middleware,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::get,
+};
+use futures_util::stream;
+use tracing::warn;
+
+use crate::error::CompositeError;
+use crate::extractors::LogTailQueryParams;
+use common::{ErrorCode, LogTail};
+
+use super::AppState;
+use super::middleware::auth_middleware;
+use super::rbac::{Resource, Scope, Verb};
+
+/// Lines returned without `lines`
+const DEFAULT_TAIL_LINES: usize = 100;
+const MAX_TAIL_LINES: usize = 10_000;
+/// Bytes read at once, backwards from the end of the log file
+const TAIL_CHUNK_SIZE: u64 = 8192;
+/// Bytes read at most for a tail, so that long lines cannot load a whole log
+const MAX_TAIL_BYTES: u64 = 4 << 20;
+/// Interval between two checks for lines appended to the log file
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+// Last lines of the current log file, streamed as they are appended with
+// `follow`
+async fn get_log_tail(
+    State(state): State<AppState>,
+    Query(params): Query<LogTailQueryParams>,
+) -> Response {
+    let count = params.lines.unwrap_or(DEFAULT_TAIL_LINES);
+    if !(1..=MAX_TAIL_LINES).contains(&count) {
+        let mut ce = CompositeError::new(ErrorCode::Einvalid, "invalid query parameters");
+        ce.add_detail(
+            "lines",
+            ErrorCode::Einvalid,
+            &format!("must be between 1 and {MAX_TAIL_LINES}"),
+        );
+        return ce.into_response();
+    }
+
+    let dir = Arc::clone(&state.log_dir);
+    let (path, lines, offset) =
+        match tokio::task::spawn_blocking(move || read_tail(&dir, count)).await {
+            Ok(Ok(tail)) => tail,
+            Ok(Err(ce)) => return ce.into_response(),
+            Err(e) => {
+                return CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Failed to read the log tail: {e}"),
+                )
+                .into_response();
+            }
+        };
+    if params.follow {
+        let follower = Follower {
+            dir: Arc::clone(&state.log_dir),
+            path,
+            offset,
+            pending: lines.into(),
+        };
+        Sse::new(follow(follower))
+            .keep_alive(KeepAlive::default())
+            .into_response()
+    } else {
+        let file = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        (StatusCode::OK, Json(LogTail { file, lines })).into_response()
+    }
+}
+
+/// Current log file with its last `count` lines, and the offset following
+/// the last of them
+fn read_tail(dir: &Path, count: usize) -> Result<(PathBuf, Vec<String>, u64), CompositeError> {
+    let path = current_log_file(dir)
+        .map_err(|e| read_error(dir, &e))?
+        .ok_or_else(|| {
+            CompositeError::new(
+                ErrorCode::Enotfound,
+                &format!("No log file in {}", dir.display()),
+            )
+        })?;
+    let mut file = File::open(&path).map_err(|e| read_error(&path, &e))?;
+    let (lines, offset) = tail_lines(&mut file, count).map_err(|e| read_error(&path, &e))?;
+    Ok((path, lines, offset))
+}
+
+fn read_error(path: &Path, e: &io::Error) -> CompositeError {
+    CompositeError::new(
+        ErrorCode::Einternal,
+        &format!("Failed to read {}: {e}", path.display()),
+    )
+}
+
+/// Most recently written log file of the daemon in `dir`. Only regular files
+/// are considered, so that a symbolic link cannot lead out of the directory.
+fn current_log_file(dir: &Path) -> io::Result<Option<PathBuf>> {
+    let mut newest = None;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_log = name
+            .to_str()
+            .is_some_and(|name| name.starts_with("couic.") && name.ends_with(".log"));
+        // Unlike fs::metadata, does not follow symbolic links
+        let metadata = entry.metadata()?;
+        if !is_log || !metadata.is_file() {
+            continue;
+        }
+        let candidate = (metadata.modified()?, entry.path());
+        if newest.as_ref().is_none_or(|newest| candidate > *newest) {
+            newest = Some(candidate);
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
+
+/// Last `count` complete lines of `file`, read backwards by chunks so that a
+/// large log is not loaded whole, and the offset following the last of them.
+/// At most `MAX_TAIL_BYTES` are read, which may return fewer lines.
+fn tail_lines(file: &mut File, count: usize) -> io::Result<(Vec<String>, u64)> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let floor = len.saturating_sub(MAX_TAIL_BYTES);
+    let mut start = len;
+    let mut chunks = Vec::new();
+    let mut found = 0_usize;
+    // One more newline than lines, as the first one read may be cut
+    while start > floor && found <= count {
+        let size = TAIL_CHUNK_SIZE.min(start.saturating_sub(floor));
+        start = start.saturating_sub(size);
+        file.seek(SeekFrom::Start(start))?;
+        let mut chunk = Vec::new();
+        file.by_ref().take(size).read_to_end(&mut chunk)?;
+        found = found.saturating_add(newlines(&chunk));
+        chunks.push(chunk);
+    }
+    let tail: Vec<u8> = chunks.into_iter().rev().flatten().collect();
+    let (lines, consumed) = complete_lines(&tail);
+    // The first line is cut unless the file was read from its start
+    let skip = lines
+        .len()
+        .saturating_sub(count)
+        .max(usize::from(start > 0));
+    Ok((
+        lines.into_iter().skip(skip).collect(),
+        start.saturating_add(consumed),
+    ))
+}
+
+fn newlines(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|byte| **byte == b'\n').count()
+}
+
+/// Lines of `bytes` ended by a newline, and the number of bytes they span.
+/// A line still being written is left for later.
+fn complete_lines(bytes: &[u8]) -> (Vec<String>, u64) {
+    let Some(end) = bytes.iter().rposition(|byte| *byte == b'\n') else {
+        return (Vec::new(), 0);
+    };
+    let (complete, _) = bytes.split_at(end);
+    let lines = String::from_utf8_lossy(complete)
+        // Carriage returns cannot be sent in events
+        .split('\n')
+        .map(|line| line.replace('\r', ""))
+        .collect();
+    (
+        lines,
+        u64::try_from(end).unwrap_or(u64::MAX).saturating_add(1),
+    )
+}
+
+/// Position of a follow stream in the log directory
+struct Follower {
+    dir: Arc<Path>,
+    path: PathBuf,
+    offset: u64,
+    pending: VecDeque<String>,
+}
+
+impl Follower {
+    /// Queue the lines appended since the last poll, moving to the new log
+    /// file on rotation
+    fn poll(&mut self) -> io::Result<()> {
+        if let Some(current) = current_log_file(&self.dir)?
+            && current != self.path
+        {
+            self.path = current;
+            self.offset = 0;
+        }
+        let mut file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            // Truncated
+            self.offset = 0;
+        }
+        if len == self.offset {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut appended = Vec::new();
+        file.take(len.saturating_sub(self.offset))
+            .read_to_end(&mut appended)?;
+        let (lines, consumed) = complete_lines(&appended);
+        self.offset = self.offset.saturating_add(consumed);
+        self.pending.extend(lines);
+        Ok(())
+    }
+}
+
+/// One event per log line, from the tail onwards
+fn follow(follower: Follower) -> impl futures_util::Stream<Item = Result<Event, Infallible>> {
+    stream::unfold(follower, |mut follower| async move {
+        loop {
+            if let Some(line) = follower.pending.pop_front() {
+                return Some((Ok(Event::default().data(line)), follower));
+            }
+            tokio::time::sleep(FOLLOW_INTERVAL).await;
+            let polled = tokio::task::spawn_blocking(move || {
+                let result = follower.poll();
+                (follower, result)
+            })
+            .await;
+            let Ok((polled, result)) = polled else {
+                warn!("log follow task failed, ending the stream");
+                return None;
+            };
+            follower = polled;
+            if let Err(e) = result {
+                warn!(
+                    file = %follower.path.display(),
+                    error = %e,
+                    "failed to follow log file"
+                );
+            }
+        }
+    })
+}
+
+/// Create router for the log endpoints
+pub(super) fn router(state: AppState) -> Router<AppState> {
+    Router::new().route(
+        "/v1/logs/tail",
+        get(get_log_tail)
+            .route_layer(middleware::from_fn_with_state(state, auth_middleware))
+            .route_layer(Extension(Scope::with(Resource::Logging, Verb::Get))),
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn write_log(dir: &Path, name: &str, lines: usize) -> PathBuf {
+        let path = dir.join(name);
+        let mut file = File::create(&path).unwrap();
+        for i in 0..lines {
+            writeln!(file, "line {i}").unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_tail_lines_reads_the_last_lines() {
+        let dir = TempDir::new().unwrap();
+        // Spans several chunks
+        let path = write_log(dir.path(), "couic.log", 5000);
+        let len = fs::metadata(&path).unwrap().len();
+
+        let (lines, offset) = tail_lines(&mut File::open(&path).unwrap(), 3).unwrap();
+        assert_eq!(lines, vec!["line 4997", "line 4998", "line 4999"]);
+        assert_eq!(offset, len);
+
+        let (lines, _) = tail_lines(&mut File::open(&path).unwrap(), MAX_TAIL_LINES).unwrap();
+        assert_eq!(lines.len(), 5000);
+        assert_eq!(lines.first().unwrap(), "line 0");
+    }
+
+    #[test]
+    fn test_tail_lines_reads_at_most_the_byte_cap() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("couic.log");
+        let long = "x".repeat(1 << 20);
+        let mut file = File::create(&path).unwrap();
+        for i in 0..6 {
+            writeln!(file, "{i} {long}").unwrap();
+        }
+        let len = fs::metadata(&path).unwrap().len();
+
+        // The line cut by the cap is left out
+        let (lines, offset) = tail_lines(&mut File::open(&path).unwrap(), 10).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.first().unwrap().starts_with("3 "));
+        assert_eq!(offset, len);
+    }
+
+    #[test]
+    fn test_tail_lines_leaves_a_partial_line() {
+        let dir = TempDir::new().unwrap();
+        let path = write_log(dir.path(), "couic.log", 2);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"partial")
+            .unwrap();
+
+        let (lines, offset) = tail_lines(&mut File::open(&path).unwrap(), 10).unwrap();
+        assert_eq!(lines, vec!["line 0", "line 1"]);
+        assert_eq!(offset, 14);
+    }
+
+    #[test]
+    fn test_current_log_file_ignores_other_files() {
+        let dir = TempDir::new().unwrap();
+        assert!(current_log_file(dir.path()).unwrap().is_none());
+
+        let log = write_log(dir.path(), "couic.2026-10-18.log", 1);
+        write_log(dir.path(), "other.log", 1);
+        std::os::unix::fs::symlink("/etc/hostname", dir.path().join("couic.link.log")).unwrap();
+        assert_eq!(current_log_file(dir.path()).unwrap(), Some(log));
+    }
+
+    #[test]
+    fn test_follower_reads_appended_lines_and_rotations() {
+        let dir = TempDir::new().unwrap();
+        let path = write_log(dir.path(), "couic.log", 1);
+        let (lines, offset) = tail_lines(&mut File::open(&path).unwrap(), 10).unwrap();
+        let mut follower = Follower {
+            dir: Arc::from(dir.path()),
+            path: path.clone(),
+            offset,
+            pending: lines.into(),
+        };
+        assert_eq!(follower.pending.pop_front().unwrap(), "line 0");
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"line 1\nline").unwrap();
+        follower.poll().unwrap();
+        assert_eq!(Vec::from(follower.pending.clone()), vec!["line 1"]);
+        follower.pending.clear();
+        file.write_all(b" 2\n").unwrap();
+        follower.poll().unwrap();
+        assert_eq!(Vec::from(follower.pending.clone()), vec!["line 2"]);
+        follower.pending.clear();
+
+        // Written after the previous file, so more recent
+        std::thread::sleep(Duration::from_millis(20));
+        write_log(dir.path(), "couic.2026-10-18.log", 1);
+        follower.poll().unwrap();
+        assert_eq!(Vec::from(follower.pending.clone()), vec!["line 0"]);
+    }
+}
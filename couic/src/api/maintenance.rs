@@ -0,0 +1,37 @@
+use axum::{
+    Extension, Json, Router, extract::State, http::StatusCode, middleware, response::IntoResponse,
+    routing::post,
+};
+use tracing::info;
+
+use common::Client;
+
+use super::AppState;
+use super::middleware::auth_middleware;
+use super::rbac::{Resource, Scope, Verb};
+
+// Shrink the userspace maps now rather than at the next periodic shrink
+async fn compact_maps(
+    State(state): State<AppState>,
+    Extension(actor): Extension<Client>,
+) -> impl IntoResponse {
+    info!(
+        actor.name = %actor.name,
+        actor.group = %actor.group,
+        "userspace maps compaction requested"
+    );
+    match state.firewall_service.compact() {
+        Ok(compaction) => (StatusCode::OK, Json(compaction)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Create router for the maintenance endpoints
+pub(super) fn router(state: AppState) -> Router<AppState> {
+    Router::new().route(
+        "/v1/maintenance/compact",
+        post(compact_maps)
+            .route_layer(middleware::from_fn_with_state(state, auth_middleware))
+            .route_layer(Extension(Scope::with(Resource::Maintenance, Verb::Update))),
+    )
+}
@@ -1,20 +1,40 @@
+use axum::http::{StatusCode, header::USER_AGENT};
 use axum::{
-    extract::{Request, State},
+    extract::{ConnectInfo, Request, State, connect_info::Connected},
     middleware::Next,
     response::{IntoResponse, Response},
+    serve::IncomingStream,
 };
-use tracing::warn;
+use tokio::net::UnixListener;
+use tracing::{Instrument, info_span, warn};
 use uuid::Uuid;
 
 use super::AppState;
-use super::rbac::Scope;
+use super::rbac::{PeerCred, Scope};
 use crate::error::CompositeError;
-use common::ErrorCode;
+use common::{Client, ErrorCode};
 
-/// Custom authentication middleware for Bearer Token
+/// Credentials of the process on the other end of an accepted API socket
+/// connection, `None` when the kernel could not report them
+#[derive(Debug, Clone, Copy)]
+pub struct SocketPeer(pub Option<PeerCred>);
+
+impl Connected<IncomingStream<'_, UnixListener>> for SocketPeer {
+    fn connect_info(stream: IncomingStream<'_, UnixListener>) -> Self {
+        let peer = stream.io().peer_cred().ok().map(|cred| PeerCred {
+            uid: cred.uid(),
+            gid: cred.gid(),
+        });
+        Self(peer)
+    }
+}
+
+/// Custom authentication middleware for Bearer Token, falling back to the
+/// socket peer credentials when local trust is configured
+#[allow(clippy::result_large_err)]
 pub async fn auth_middleware(
     State(state): State<AppState>,
-    mut req: Request,
+    req: Request,
     next: Next,
 ) -> Result<Response, Response> {
     // Extract and validate Authorization header
@@ -24,8 +44,7 @@ pub async fn auth_middleware(
         .and_then(|h| h.to_str().ok());
 
     let Some(header_value) = auth_header else {
-        warn!("Missing Authorization header");
-        return Err(unauthorized_error());
+        return local_auth(&state, req, next).await;
     };
 
     // Parse Bearer token
@@ -52,18 +71,116 @@ pub async fn auth_middleware(
         .check_authorization(uuid_token, *scope);
 
     if let Some(client) = client {
-        // Store client for handlers logging
-        req.extensions_mut().insert(client);
+        Ok(run_as(client, req, next).await)
+    } else {
+        warn!(
+            user_agent = user_agent(&req),
+            "Unauthorized access attempt with token: {}", uuid_token
+        );
+        Err(unauthorized_error())
+    }
+}
+
+/// Authorizes a request without token from the credentials of its connection
+#[allow(clippy::result_large_err)]
+async fn local_auth(state: &AppState, req: Request, next: Next) -> Result<Response, Response> {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketPeer>>()
+        .and_then(|ConnectInfo(SocketPeer(peer))| *peer);
+
+    let Some(peer) = peer else {
+        warn!("Missing Authorization header");
+        return Err(unauthorized_error());
+    };
+
+    let Some(scope) = req.extensions().get::<Scope>() else {
+        warn!("Missing scope extension on protected route");
+        return Err(unauthorized_error());
+    };
+
+    let rbac = state.rbac_service.read().await;
+    if !rbac.local_trust_enabled() {
+        warn!("Missing Authorization header");
+        return Err(unauthorized_error());
+    }
+    let client = rbac.check_local_authorization(peer, *scope);
+    drop(rbac);
 
-        Ok(next.run(req).await)
+    if let Some(client) = client {
+        Ok(run_as(client, req, next).await)
     } else {
-        warn!("Unauthorized access attempt with token: {}", uuid_token);
+        warn!(
+            user_agent = user_agent(&req),
+            "Unauthorized local access attempt from uid {} gid {}", peer.uid, peer.gid
+        );
         Err(unauthorized_error())
     }
 }
 
+/// Runs an authorized request, its logs carrying the client and the tool
+/// it identifies itself as
+async fn run_as(client: Client, mut req: Request, next: Next) -> Response {
+    let span = info_span!(
+        "request",
+        client = %client.name,
+        user_agent = user_agent(&req),
+    );
+    // Store client for handlers logging
+    req.extensions_mut().insert(client);
+    next.run(req).instrument(span).await
+}
+
+fn user_agent(req: &Request) -> &str {
+    req.headers()
+        .get(USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("-")
+}
+
 fn unauthorized_error() -> Response {
     CompositeError::new(ErrorCode::Eunauthorized, "Unauthorized")
         .render_json()
         .into_response()
 }
+
+/// Renders any 413, from the body limit layer or a body extractor, as the
+/// API JSON error naming the configured limit
+pub async fn payload_too_large(State(limit): State<usize>, response: Response) -> Response {
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+    CompositeError::new(
+        ErrorCode::Etoolarge,
+        &format!("Request body exceeds the limit of {limit} bytes"),
+    )
+    .render_json()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::HeaderValue;
+
+    fn request(user_agent: Option<HeaderValue>) -> Request {
+        let mut req = Request::new(Body::empty());
+        if let Some(user_agent) = user_agent {
+            req.headers_mut().insert(USER_AGENT, user_agent);
+        }
+        req
+    }
+
+    #[test]
+    fn user_agent_is_logged_or_replaced_by_a_dash() {
+        let sent = HeaderValue::from_static("couicctl/1.2.3 CouicClient/1.2.3");
+        assert_eq!(
+            user_agent(&request(Some(sent))),
+            "couicctl/1.2.3 CouicClient/1.2.3"
+        );
+        assert_eq!(user_agent(&request(None)), "-");
+        let not_utf8 = HeaderValue::from_bytes(b"couicctl/\xff").unwrap();
+        assert_eq!(user_agent(&request(Some(not_utf8))), "-");
+    }
+}
@@ -1,40 +1,1100 @@
+mod caching;
 mod clients;
+mod health;
+mod idempotency;
+mod lockdown;
+mod logging;
+mod logs;
+mod maintenance;
 mod middleware;
 mod policies;
+mod quota;
 pub mod rbac;
+mod requests;
 mod sets;
 mod stats;
 
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use axum::Router;
-use tokio::sync::RwLock;
+use axum::{
+    Router,
+    extract::DefaultBodyLimit,
+    middleware::{from_fn_with_state, map_response_with_state},
+};
+use tokio::sync::{Mutex, RwLock};
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{NotForContentType, Predicate, SizeAbove},
+};
+use tower_http::decompression::RequestDecompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
 
+use crate::config::{Compression, LogLevelHandle};
 use crate::firewall::service::FirewallService;
 use rbac::RBACService;
 
+pub use middleware::SocketPeer;
+
+/// State shared by the API routers
 #[derive(Clone)]
-pub(crate) struct AppState {
+pub struct AppState {
     firewall_service: Arc<FirewallService>,
     rbac_service: Arc<RwLock<RBACService>>,
+    stats_snapshot: Arc<Mutex<Option<stats::StatsSnapshot>>>,
+    idempotency: Arc<Mutex<idempotency::IdempotencyCache>>,
+    quotas: Arc<Mutex<quota::QuotaTracker>>,
+    log_level: LogLevelHandle,
+    log_dir: Arc<Path>,
+    requests: Arc<requests::RequestMetrics>,
 }
 
 impl AppState {
-    pub fn new(firewall_service: FirewallService, rbac_service: RBACService) -> Self {
+    pub fn new(
+        firewall_service: Arc<FirewallService>,
+        rbac_service: RBACService,
+        log_level: LogLevelHandle,
+        log_dir: PathBuf,
+    ) -> Self {
         Self {
-            firewall_service: Arc::new(firewall_service),
+            firewall_service,
             rbac_service: Arc::new(RwLock::new(rbac_service)),
+            stats_snapshot: Arc::new(Mutex::new(None)),
+            idempotency: Arc::new(Mutex::new(idempotency::IdempotencyCache::default())),
+            quotas: Arc::new(Mutex::new(quota::QuotaTracker::default())),
+            log_level,
+            log_dir: Arc::from(log_dir),
+            requests: Arc::new(requests::RequestMetrics::default()),
         }
     }
 }
 
-pub fn create_router(firewall_service: FirewallService, rbac_service: RBACService) -> Router {
-    let state = AppState::new(firewall_service, rbac_service);
-
-    Router::new()
+pub fn create_router(state: AppState, compression: Compression, max_body_size: usize) -> Router {
+    let requests = Arc::clone(&state.requests);
+    let router = Router::new()
         .merge(policies::router(state.clone()))
         .merge(sets::router(state.clone()))
         .merge(stats::router(state.clone()))
         .merge(clients::router(state.clone()))
+        .merge(health::router(state.clone()))
+        .merge(logging::router(state.clone()))
+        .merge(logs::router(state.clone()))
+        .merge(maintenance::router(state.clone()))
+        .merge(lockdown::router(state.clone()))
         .with_state(state)
+        // The body limit layer replaces the 2 MiB default of the axum
+        // extractors, which still bound gzip request bodies once decompressed
+        .layer(DefaultBodyLimit::max(max_body_size))
+        .layer(RequestDecompressionLayer::new())
+        .layer(RequestBodyLimitLayer::new(max_body_size))
+        .layer(map_response_with_state(
+            max_body_size,
+            middleware::payload_too_large,
+        ))
+        // Outermost, so that oversized requests are counted too
+        .layer(from_fn_with_state(requests, requests::record_requests));
+
+    if compression.enabled {
+        // Compressed event streams would be held back until enough data
+        let predicate = SizeAbove::new(compression.min_size).and(NotForContentType::SSE);
+        router.layer(CompressionLayer::new().compress_when(predicate))
+    } else {
+        router
+    }
+}
+
+/// Router of the unauthenticated listener serving only Prometheus metrics
+pub fn create_metrics_router(state: &AppState) -> Router {
+    stats::metrics_router().with_state(state.clone())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::firewall::backend::MemoryBackend;
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Method, Request, StatusCode};
+    use common::{EntryHitsKey, Expiration, NormalizedCidr, Policy};
+    use futures_util::StreamExt;
+    use serde_json::{Value, json};
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+    use tracing_subscriber::{EnvFilter, Registry, reload};
+
+    /// Router on top of an in-memory backend, with the token of the default
+    /// admin client
+    struct TestApp {
+        router: Router,
+        token: String,
+        backend: MemoryBackend,
+        /// Filter reloaded by the log level routes, never installed
+        _log_filter: reload::Layer<EnvFilter, Registry>,
+        working_dir: TempDir,
+    }
+
+    impl TestApp {
+        fn new() -> Self {
+            Self::with_config(|_| {})
+        }
+
+        /// App whose default test configuration is changed by `configure`
+        fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+            let working_dir = TempDir::new().unwrap();
+            for dir in ["rbac/clients", "sets/ignore", "sets/drop", "sets/monitor"] {
+                std::fs::create_dir_all(working_dir.path().join(dir)).unwrap();
+            }
+            let user = std::env::var("USER").unwrap_or_else(|_| "root".to_string());
+            let mut config = Config {
+                working_dir: working_dir.path().to_string_lossy().to_string(),
+                group: user.clone(),
+                user,
+                ..Default::default()
+            };
+            configure(&mut config);
+
+            let backend = MemoryBackend::new(4);
+            let firewall =
+                FirewallService::with_backend(config.clone(), Box::new(backend.clone())).unwrap();
+            let rbac = RBACService::new(config.clone()).unwrap();
+            let token = rbac.list_clients().first().unwrap().token.to_string();
+            let (log_filter, log_level) = LogLevelHandle::new(EnvFilter::new("info"));
+            let router = create_router(
+                AppState::new(
+                    Arc::new(firewall),
+                    rbac,
+                    log_level,
+                    PathBuf::from(&config.logging.dir),
+                ),
+                config.server.compression,
+                config.server.max_body_size,
+            );
+            Self {
+                router,
+                token,
+                backend,
+                _log_filter: log_filter,
+                working_dir,
+            }
+        }
+
+        async fn request(
+            &self,
+            method: Method,
+            uri: &str,
+            body: Option<Value>,
+        ) -> (StatusCode, Value) {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .header("Content-Type", "application/json")
+                .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                .unwrap();
+            let response = self.router.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+            (status, value)
+        }
+
+        /// POST of `entry` to `uri`, checked without being applied
+        async fn dry_run(&self, uri: &str, entry: &Value) -> (StatusCode, Value) {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .header("Content-Type", "application/json")
+                .header(common::DRY_RUN_HEADER, "true")
+                .body(Body::from(entry.to_string()))
+                .unwrap();
+            let response = self.router.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (
+                status,
+                serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+            )
+        }
+
+        /// Status and `ETag` of a GET revalidating the `if_none_match` ETag
+        async fn revalidate(&self, uri: &str, if_none_match: &str) -> (StatusCode, String) {
+            let request = Request::builder()
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .header("If-None-Match", if_none_match)
+                .body(Body::empty())
+                .unwrap();
+            let response = self.router.clone().oneshot(request).await.unwrap();
+            let etag = response.headers()["ETag"].to_str().unwrap().to_string();
+            (response.status(), etag)
+        }
+    }
+
+    #[tokio::test]
+    async fn policies_routes_manage_entries() {
+        let app = TestApp::new();
+        let entry = json!({"cidr": "192.0.2.0/24", "tag": "scanner", "expiration": 0});
+
+        let (status, body) = app
+            .request(Method::POST, "/v1/drop", Some(entry.clone()))
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert_eq!(body["tag"], "scanner");
+
+        let (status, _) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/drop/192.0.2.0/24", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["cidr"], "192.0.2.0/24");
+
+        let (status, body) = app.request(Method::GET, "/v1/drop", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, _) = app
+            .request(Method::DELETE, "/v1/drop/192.0.2.0/24", None)
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = app
+            .request(Method::GET, "/v1/drop/192.0.2.0/24", None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn policies_routes_record_the_source_of_entries() {
+        let app = TestApp::new();
+        let entry = json!({"cidr": "192.0.2.0/24", "expiration": 0});
+        let (status, body) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert_eq!(body["source"]["kind"], "manual");
+        let client = body["source"]["client"].clone();
+        assert!(client.is_string());
+
+        let jobs =
+            json!([{"action": "add", "entry": {"cidr": "198.51.100.0/24", "expiration": 0}}]);
+        let (status, _) = app.request(Method::POST, "/v1/drop/peer", Some(jobs)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (_, body) = app
+            .request(Method::GET, "/v1/drop/198.51.100.0/24", None)
+            .await;
+        assert_eq!(body["source"], json!({"kind": "peer", "client": client}));
+
+        let set = json!({"name": "feed", "entries": ["203.0.113.0/24"]});
+        let (status, _) = app.request(Method::POST, "/v1/sets/drop", Some(set)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        app.request(Method::POST, "/v1/sets/reload", None).await;
+        let (_, body) = app
+            .request(Method::GET, "/v1/drop/203.0.113.0/24", None)
+            .await;
+        assert_eq!(body["source"], json!({"kind": "set"}));
+    }
+
+    #[tokio::test]
+    async fn policies_routes_format_timestamps_on_request() {
+        let app = TestApp::new();
+        let entry = json!({"cidr": "192.0.2.0/24", "expiration": 0});
+        let (status, _) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (_, body) = app
+            .request(Method::GET, "/v1/drop/192.0.2.0/24", None)
+            .await;
+        assert!(body["creation"].is_u64());
+        assert_eq!(body["expiration"], 0);
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/drop?time_format=rfc3339", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body[0]["creation"].as_str().unwrap().ends_with('Z'));
+        assert!(body[0]["expiration"].is_null());
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/drop/192.0.2.0/24?time_format=iso", None)
+            .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"]["time_format"]["code"], "invalid");
+    }
+
+    #[tokio::test]
+    async fn policies_routes_filter_entries_expiring_soon() {
+        let app = TestApp::new();
+        let expiration = Expiration::from_duration(Duration::from_secs(60)).as_timestamp();
+        for entry in [
+            json!({"cidr": "192.0.2.0/24", "expiration": expiration}),
+            json!({"cidr": "198.51.100.0/24", "expiration": 0}),
+        ] {
+            let (status, _) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/drop?expiring_within=120", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let entries = body.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["cidr"], "192.0.2.0/24");
+
+        let (_, body) = app
+            .request(Method::GET, "/v1/drop?expiring_within=0", None)
+            .await;
+        assert!(body.as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn policies_routes_stream_entries_as_json_lines() {
+        let app = TestApp::new();
+        for cidr in ["192.0.2.0/24", "2001:db8::/32"] {
+            let entry = json!({"cidr": cidr, "expiration": 0, "tag": "scanner"});
+            let (status, _) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let request = Request::builder()
+            .uri("/v1/drop/stream")
+            .header("Authorization", format!("Bearer {}", app.token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut cidrs: Vec<String> = std::str::from_utf8(&bytes)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let entry: Value = serde_json::from_str(line).unwrap();
+                assert_eq!(entry["tag"], "scanner");
+                entry["cidr"].as_str().unwrap().to_string()
+            })
+            .collect();
+        cidrs.sort();
+        assert_eq!(cidrs, vec!["192.0.2.0/24", "2001:db8::/32"]);
+
+        let (status, _) = app.request(Method::GET, "/v1/ignore/stream", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn policies_routes_refuse_entries_beyond_map_capacity() {
+        let app = TestApp::new();
+        for i in 0..4 {
+            let entry = json!({"cidr": format!("198.51.100.{i}/32"), "expiration": 0});
+            let (status, _) = app.request(Method::POST, "/v1/ignore", Some(entry)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let entry = json!({"cidr": "198.51.100.4/32", "expiration": 0});
+        let (status, body) = app.request(Method::POST, "/v1/ignore", Some(entry)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "full");
+    }
+
+    #[tokio::test]
+    async fn replace_route_refuses_creations_over_the_quota_left() {
+        let app = TestApp::with_config(|config| {
+            config.rbac.quotas.groups.insert(common::Group::Admin, 2);
+        });
+        let entries = |count: u8| {
+            let entries: Vec<Value> = (0..count)
+                .map(|i| json!({"cidr": format!("198.51.100.{i}/32"), "expiration": 0}))
+                .collect();
+            Body::from(Value::from(entries).to_string())
+        };
+        let put = |body: Body| {
+            Request::builder()
+                .method(Method::PUT)
+                .uri("/v1/drop")
+                .header("Authorization", format!("Bearer {}", app.token))
+                .header("Content-Type", "application/json")
+                .body(body)
+                .unwrap()
+        };
+
+        let response = app.router.clone().oneshot(put(entries(3))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("Retry-After"));
+        let (_, body) = app.request(Method::GET, "/v1/drop", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 0);
+
+        let response = app.router.clone().oneshot(put(entries(2))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, _) = app
+            .request(
+                Method::POST,
+                "/v1/drop",
+                Some(json!({"cidr": "198.51.100.9/32", "expiration": 0})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn policies_routes_check_dry_runs_without_applying() {
+        let app = TestApp::new();
+        let entry = json!({"cidr": "192.0.2.0/24", "tag": "scanner", "expiration": 0});
+
+        let (status, body) = app.dry_run("/v1/drop", &entry).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["cidr"], "192.0.2.0/24");
+        let (_, body) = app.request(Method::GET, "/v1/drop", None).await;
+        assert!(body.as_array().unwrap().is_empty());
+
+        let (status, _) = app
+            .request(Method::POST, "/v1/drop", Some(entry.clone()))
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = app.dry_run("/v1/drop", &entry).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let loopback = json!({"cidr": "127.0.0.1/32", "expiration": 0});
+        let (status, _) = app.dry_run("/v1/drop", &loopback).await;
+        assert!(status.is_client_error());
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests_per_route_and_status() {
+        let app = TestApp::new();
+        app.request(Method::GET, "/v1/drop", None).await;
+        app.request(Method::GET, "/v1/drop/192.0.2.0/24", None)
+            .await;
+        app.request(Method::GET, "/v1/nowhere/at/all/really", None)
+            .await;
+
+        let request = Request::builder()
+            .uri("/v1/metrics?format=prometheus")
+            .header("Authorization", format!("Bearer {}", app.token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report = String::from_utf8(bytes.to_vec()).unwrap();
+
+        assert!(report.contains("couic_active_clients 1\n"), "{report}");
+        for line in [
+            r#"couic_http_requests_total{method="GET",path="/v1/{policy}",status="200"} 1"#,
+            r#"couic_http_requests_total{method="GET",path="/v1/{policy}/{ip}/{prefix}",status="404"} 1"#,
+            r#"couic_http_requests_total{method="GET",path="unmatched",status="404"} 1"#,
+        ] {
+            assert!(report.contains(line), "{line} missing from {report}");
+        }
+        assert!(report.ends_with("# EOF\n"));
+    }
+
+    #[tokio::test]
+    async fn log_level_routes_reload_the_filter() {
+        let app = TestApp::new();
+
+        let (status, body) = app.request(Method::GET, "/v1/log-level", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["filter"], "info");
+
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/v1/log-level",
+                Some(json!({"level": "Debug"})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["filter"], "debug");
+        let (_, body) = app.request(Method::GET, "/v1/log-level", None).await;
+        assert_eq!(body["filter"], "debug");
+
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/v1/log-level",
+                Some(json!({"level": "verbose"})),
+            )
+            .await;
+        assert!(status.is_client_error());
+        assert_eq!(body["errors"]["level"]["code"], "invalid");
+
+        let (status, body) = app.request(Method::DELETE, "/v1/log-level", None).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["filter"], "info");
+        let (_, body) = app.request(Method::GET, "/v1/log-level", None).await;
+        assert_eq!(body["filter"], "info");
+    }
+
+    #[tokio::test]
+    async fn peer_route_accepts_gzip_bodies() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let app = TestApp::with_config(|config| config.server.max_body_size = 4096);
+        let post_gzip = async |jobs: Value| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(jobs.to_string().as_bytes()).unwrap();
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/v1/drop/peer")
+                .header("Authorization", format!("Bearer {}", app.token))
+                .header("Content-Type", "application/json")
+                .header("Content-Encoding", "gzip")
+                .body(Body::from(encoder.finish().unwrap()))
+                .unwrap();
+            let response = app.router.clone().oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice::<Value>(&bytes).unwrap())
+        };
+
+        let jobs: Vec<Value> = ["192.0.2.0/24", "198.51.100.0/24"]
+            .iter()
+            .map(|cidr| json!({"action": "add", "entry": {"cidr": cidr, "expiration": 0}}))
+            .collect();
+        let (status, body) = post_gzip(json!(jobs)).await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert!(
+            body.as_array()
+                .unwrap()
+                .iter()
+                .all(|r| r["applied"] == true)
+        );
+        let (status, _) = app
+            .request(Method::GET, "/v1/drop/198.51.100.0/24", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Small once compressed, but beyond the limit once decompressed
+        let tag = "a".repeat(8192);
+        let jobs = json!([{"action": "add", "entry": {"cidr": "203.0.113.0/24", "tag": tag}}]);
+        let (status, body) = post_gzip(jobs).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["code"], "payload_too_large");
+    }
+
+    #[tokio::test]
+    async fn logs_routes_return_the_log_tail() {
+        let app = TestApp::with_config(|config| config.logging.dir = config.working_dir.clone());
+        let (status, _) = app.request(Method::GET, "/v1/logs/tail", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let log = app.working_dir.path().join("couic.log");
+        std::fs::write(log, "first\nsecond\nthird\n").unwrap();
+        let (status, body) = app
+            .request(Method::GET, "/v1/logs/tail?lines=2", None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(
+            body,
+            json!({"file": "couic.log", "lines": ["second", "third"]})
+        );
+
+        let request = Request::builder()
+            .uri("/v1/logs/tail?lines=1&follow=true")
+            .header("Authorization", format!("Bearer {}", app.token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut events = response.into_body().into_data_stream();
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event, "data: third\n\n");
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/logs/tail?lines=0", None)
+            .await;
+        assert!(status.is_client_error());
+        assert_eq!(body["errors"]["lines"]["code"], "invalid");
+    }
+
+    #[tokio::test]
+    async fn maintenance_route_compacts_every_map() {
+        let app = TestApp::new();
+        let entries: Vec<Value> = (0..4)
+            .map(|i| json!({"cidr": format!("10.0.{i}.0/24"), "expiration": 0}))
+            .collect();
+        let (status, body) = app
+            .request(Method::PUT, "/v1/drop", Some(Value::Array(entries)))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let (status, body) = app.request(Method::PUT, "/v1/drop", Some(json!([]))).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        let (status, body) = app
+            .request(Method::POST, "/v1/maintenance/compact", None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let maps = body["maps"].as_array().unwrap();
+        assert_eq!(maps.len(), 6);
+        let drop_v4 = maps
+            .iter()
+            .find(|map| map["policy"] == "drop" && map["family"] == "ipv4")
+            .unwrap();
+        assert_eq!(drop_v4["entries"], 0);
+        assert!(drop_v4["capacity_before"].as_u64().unwrap() >= 4);
+        assert_eq!(drop_v4["capacity_after"], 0);
+    }
+
+    #[tokio::test]
+    async fn lockdown_routes_require_management_ranges_to_be_ignored() {
+        let app = TestApp::with_config(|config| {
+            config.firewall.management_cidrs = vec!["192.0.2.0/24".parse().unwrap()];
+        });
+        let enable = json!({"enabled": true});
+
+        let (status, body) = app
+            .request(Method::POST, "/v1/lockdown", Some(enable.clone()))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{body}");
+        assert_eq!(body["errors"]["192.0.2.0/24"]["code"], "conflict");
+        assert!(!app.backend.lockdown());
+
+        // An expiring entry would leave the range cut off once expired
+        let expiration = Expiration::from_duration(Duration::from_secs(3600)).as_timestamp();
+        let (status, _) = app
+            .request(
+                Method::POST,
+                "/v1/ignore",
+                Some(json!({"cidr": "192.0.2.0/24", "expiration": expiration})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, body) = app
+            .request(Method::POST, "/v1/lockdown", Some(enable.clone()))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{body}");
+        assert!(
+            body["errors"]["192.0.2.0/24"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("expiring")
+        );
+
+        // A wider ignore entry covers the management range
+        let (status, _) = app
+            .request(
+                Method::POST,
+                "/v1/ignore",
+                Some(json!({"cidr": "192.0.0.0/16", "expiration": 0})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, body) = app
+            .request(Method::POST, "/v1/lockdown", Some(enable))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["enabled"], true);
+        assert!(app.backend.lockdown());
+        let (_, body) = app.request(Method::GET, "/v1/lockdown", None).await;
+        assert_eq!(body["enabled"], true);
+
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/v1/lockdown",
+                Some(json!({"enabled": false})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["enabled"], false);
+        assert!(!app.backend.lockdown());
+    }
+
+    #[tokio::test]
+    async fn lockdown_keeps_the_ignore_entries_of_management_ranges() {
+        let app = TestApp::with_config(|config| {
+            config.firewall.management_cidrs = vec!["192.0.2.0/24".parse().unwrap()];
+        });
+        for cidr in ["192.0.0.0/16", "198.51.100.0/24"] {
+            let entry = json!({"cidr": cidr, "expiration": 0});
+            let (status, _) = app.request(Method::POST, "/v1/ignore", Some(entry)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let (status, _) = app
+            .request(Method::POST, "/v1/lockdown", Some(json!({"enabled": true})))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = app
+            .request(Method::DELETE, "/v1/ignore/192.0.0.0/16", None)
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{body}");
+        assert_eq!(body["errors"]["192.0.0.0/16"]["code"], "conflict");
+        let (status, body) = app
+            .request(Method::PUT, "/v1/ignore", Some(json!([])))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{body}");
+        let (_, body) = app.request(Method::GET, "/v1/ignore", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        // Other entries are removed as usual
+        let (status, _) = app
+            .request(Method::DELETE, "/v1/ignore/198.51.100.0/24", None)
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri("/v1/ignore/192.0.0.0/16")
+            .header("Authorization", format!("Bearer {}", app.token))
+            .header(common::FORCE_HEADER, "true")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn lockdown_without_management_ranges_must_be_forced() {
+        let app = TestApp::new();
+        let (status, _) = app
+            .request(Method::POST, "/v1/lockdown", Some(json!({"enabled": true})))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = app
+            .request(
+                Method::POST,
+                "/v1/lockdown",
+                Some(json!({"enabled": true, "force": true})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert!(app.backend.lockdown());
+    }
+
+    #[tokio::test]
+    async fn sets_reload_route_reports_changes_per_policy() {
+        let app = TestApp::new();
+        let set = json!({"name": "set-a", "entries": ["192.0.2.0/24", "2001:db8::/32"]});
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/monitor", Some(set))
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = app.request(Method::POST, "/v1/sets/reload", None).await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert_eq!(body["reload_status"], "OK");
+        let policies: Vec<&Value> = body["reloads"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|reload| &reload["policy"])
+            .collect();
+        assert_eq!(
+            policies,
+            [&json!("ignore"), &json!("drop"), &json!("monitor")]
+        );
+        assert_eq!(body["reloads"][2]["created"], 2);
+        assert_eq!(body["reloads"][1]["created"], 0);
+
+        let set = json!({"name": "set-a", "entries": ["192.0.2.0/24"]});
+        let (status, _) = app
+            .request(Method::PUT, "/v1/sets/monitor/set-a", Some(set))
+            .await;
+        assert!(status.is_success());
+        let (_, body) = app.request(Method::POST, "/v1/sets/reload", None).await;
+        assert_eq!(body["reloads"][2]["created"], 0);
+        assert_eq!(body["reloads"][2]["updated"], 0);
+        assert_eq!(body["reloads"][2]["removed"], 1);
+    }
+
+    #[tokio::test]
+    async fn sets_routes_disable_and_enable_sets() {
+        let app = TestApp::new();
+        let set = json!({"name": "set-a", "entries": ["192.0.2.0/24", "2001:db8::/32"]});
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/monitor", Some(set))
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+        app.request(Method::POST, "/v1/sets/reload", None).await;
+
+        let (status, body) = app
+            .request(Method::POST, "/v1/sets/monitor/set-a/disable", None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(
+            body,
+            json!({"name": "set-a", "disabled": true, "reload_required": true})
+        );
+        let (_, body) = app.request(Method::GET, "/v1/sets/monitor", None).await;
+        assert_eq!(body[0]["disabled"], true);
+        let (status, _) = app
+            .request(Method::GET, "/v1/sets/monitor/summary", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Entries are removed on reload, the file being kept
+        let (_, body) = app.request(Method::POST, "/v1/sets/reload", None).await;
+        assert_eq!(body["reloads"][2]["removed"], 2);
+        let (_, body) = app.request(Method::GET, "/v1/monitor", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 0);
+        let disabled = app
+            .working_dir
+            .path()
+            .join("sets/monitor/set-a.couic.disabled");
+        assert!(disabled.exists());
+
+        let set = json!({"name": "set-a", "entries": ["198.51.100.0/24"]});
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/monitor", Some(set))
+            .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = app
+            .request(Method::POST, "/v1/sets/monitor/set-a/enable", None)
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["disabled"], false);
+        let (_, body) = app.request(Method::POST, "/v1/sets/reload", None).await;
+        assert_eq!(body["reloads"][2]["created"], 2);
+
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/monitor/set-b/disable", None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn sets_routes_summarize_created_sets() {
+        let app = TestApp::new();
+        for (name, entries) in [
+            ("set-a", json!(["192.0.2.0/24", "198.51.100.7/32"])),
+            ("set-b", json!(["192.0.2.0/24", "2001:db8::/32"])),
+        ] {
+            let set = json!({"name": name, "entries": entries});
+            let (status, _) = app
+                .request(Method::POST, "/v1/sets/monitor", Some(set))
+                .await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/sets/monitor/summary", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["set_count"], 2);
+        assert_eq!(body["total_entries"], 4);
+        assert_eq!(body["unique_entries"], 3);
+        // Sets are not loaded until reloaded
+        assert_eq!(
+            body["maps"],
+            json!({"v4": 0, "max_v4": 4, "v6": 0, "max_v6": 4})
+        );
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/sets/monitor/set-b", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["entries"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sets_routes_keep_names_unique_across_policies_when_configured() {
+        let set = json!({"name": "malware", "entries": ["192.0.2.0/24"]});
+        for (unique_names, expected) in [(false, StatusCode::CREATED), (true, StatusCode::CONFLICT)]
+        {
+            let app = TestApp::with_config(|config| config.sets.unique_names = unique_names);
+            let (status, _) = app
+                .request(Method::POST, "/v1/sets/drop", Some(set.clone()))
+                .await;
+            assert_eq!(status, StatusCode::CREATED);
+
+            let (status, body) = app
+                .request(Method::POST, "/v1/sets/ignore", Some(set.clone()))
+                .await;
+            assert_eq!(status, expected, "{body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn sets_routes_refuse_sets_over_the_limit() {
+        let app = TestApp::with_config(|config| config.sets.max_sets = 2);
+        for (name, expected) in [
+            ("set-a", StatusCode::CREATED),
+            ("set-b", StatusCode::CREATED),
+            ("set-c", StatusCode::CONFLICT),
+        ] {
+            let set = json!({"name": name, "entries": ["192.0.2.0/24"]});
+            let (status, body) = app.request(Method::POST, "/v1/sets/drop", Some(set)).await;
+            assert_eq!(status, expected, "{body}");
+        }
+
+        // A disabled set still counts, it would be over the limit once enabled
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/drop/set-b/disable", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let set = json!({"name": "set-c", "entries": ["192.0.2.0/24"]});
+        let (status, body) = app.request(Method::POST, "/v1/sets/drop", Some(set)).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{body}");
+
+        // The limit applies per policy
+        let set = json!({"name": "set-c", "entries": ["192.0.2.0/24"]});
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/monitor", Some(set))
+            .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn sets_routes_reload_a_single_set() {
+        let app = TestApp::new();
+        for (name, entries) in [
+            ("set-a", json!(["192.0.2.0/24", "198.51.100.7/32"])),
+            ("set-b", json!(["192.0.2.0/24", "2001:db8::/32"])),
+        ] {
+            let set = json!({"name": name, "entries": entries});
+            let (status, _) = app
+                .request(Method::POST, "/v1/sets/monitor", Some(set))
+                .await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (status, body) = app
+            .request(Method::POST, "/v1/sets/monitor/set-a/reload", None)
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{body}");
+        assert_eq!(body["reloads"][0]["created"], 2);
+        let (_, body) = app
+            .request(Method::GET, "/v1/sets/reload/status", None)
+            .await;
+        assert_eq!(
+            body["changed_sets"],
+            json!([{"policy": "monitor", "name": "set-b"}])
+        );
+
+        // The entry already loaded by set-a stays with it
+        let (_, body) = app
+            .request(Method::POST, "/v1/sets/monitor/set-b/reload", None)
+            .await;
+        assert_eq!(body["reloads"][0]["created"], 1);
+        let (_, body) = app.request(Method::GET, "/v1/monitor", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 3);
+
+        // A deleted set is unloaded, the entries of the other sets being kept
+        let (status, _) = app
+            .request(Method::DELETE, "/v1/sets/monitor/set-a", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = app
+            .request(Method::POST, "/v1/sets/monitor/set-a/reload", None)
+            .await;
+        assert_eq!(body["reloads"][0]["removed"], 2);
+        let (_, body) = app.request(Method::GET, "/v1/monitor", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, _) = app
+            .request(Method::POST, "/v1/sets/monitor/set-a/reload", None)
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn policies_routes_refuse_tags_not_allowed() {
+        let app = TestApp::with_config(|config| {
+            config.tags.allowed_patterns = vec!["team/*".to_string()];
+        });
+        for (tag, expected) in [
+            (json!("team/web"), StatusCode::CREATED),
+            (json!("scanner"), StatusCode::UNPROCESSABLE_ENTITY),
+            (json!(null), StatusCode::UNPROCESSABLE_ENTITY),
+        ] {
+            let entry = json!({"cidr": "192.0.2.1/32", "tag": tag, "expiration": 0});
+            let (status, body) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+            assert_eq!(status, expected, "{body}");
+        }
+
+        let (status, body) = app
+            .request(
+                Method::PUT,
+                "/v1/drop",
+                Some(json!([{"cidr": "192.0.2.2/32", "tag": "scanner", "expiration": 0}])),
+            )
+            .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"]["192.0.2.2/32"]["code"], "invalid");
+    }
+
+    #[tokio::test]
+    async fn policies_routes_refuse_scoped_addresses() {
+        let app = TestApp::new();
+        let entry = json!({"cidr": "fe80::1%eth0/64", "expiration": 0});
+        let (status, body) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+        assert!(
+            body["errors"]["cidr"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("zones are not supported"),
+            "{body}"
+        );
+
+        // Multicast drops are accepted, with a warning
+        let entry = json!({"cidr": "ff02::1/128", "expiration": 0});
+        let (status, _) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn stats_routes_read_and_reset_backend_counters() {
+        let app = TestApp::new();
+        let entry = json!({"cidr": "203.0.113.0/24", "expiration": 0});
+        let (status, _) = app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let cidr = "203.0.113.0/24".parse::<NormalizedCidr>().unwrap();
+        app.backend.record_xdp(1, 1500);
+        app.backend
+            .record_hit(Policy::Drop, 0, EntryHitsKey::new(Policy::Drop, cidr), 1500);
+
+        let (status, body) = app.request(Method::GET, "/v1/stats", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["drop_cidr_count"], 1);
+        assert_eq!(body["xdp"]["XDP_DROP"]["rx_packets"], 1);
+
+        let (status, body) = app
+            .request(Method::GET, "/v1/drop/203.0.113.0/24/hits", None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["rx_bytes"], 1500);
+
+        let (status, _) = app.request(Method::POST, "/v1/stats/reset", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, body) = app.request(Method::GET, "/v1/stats", None).await;
+        assert_eq!(body["xdp"]["XDP_DROP"]["rx_packets"], 0);
+        assert_eq!(body["drop_cidr_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn list_and_stats_routes_answer_unchanged_content_with_304() {
+        let app = TestApp::new();
+
+        let (status, etag) = app.revalidate("/v1/drop", "\"none\"").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, same) = app.revalidate("/v1/drop", &etag).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert_eq!(same, etag);
+
+        let entry = json!({"cidr": "192.0.2.1/32", "expiration": 0});
+        app.request(Method::POST, "/v1/drop", Some(entry)).await;
+        let (status, changed) = app.revalidate("/v1/drop", &etag).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(changed, etag);
+        // Other policies are left unchanged
+        let (_, ignore_etag) = app.revalidate("/v1/ignore", "*").await;
+        assert_eq!(
+            app.revalidate("/v1/ignore", &ignore_etag).await.0,
+            StatusCode::NOT_MODIFIED
+        );
+
+        let (_, etag) = app.revalidate("/v1/stats", "\"none\"").await;
+        assert_eq!(
+            app.revalidate("/v1/stats", &etag).await.0,
+            StatusCode::NOT_MODIFIED
+        );
+        app.backend.record_xdp(2, 64);
+        assert_eq!(app.revalidate("/v1/stats", &etag).await.0, StatusCode::OK);
+    }
 }
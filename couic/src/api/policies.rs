@@ -1,14 +1,32 @@
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use axum::{
     Extension, Json, Router,
-    extract::State,
-    http::StatusCode,
+    body::Body,
+    extract::{Query, State},
+    http::{
+        StatusCode,
+        header::{CONTENT_TYPE, RETRY_AFTER},
+    },
     middleware,
-    response::IntoResponse,
-    routing::{delete, get, post},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
 };
-use tracing::{error, info};
+use futures_util::stream;
+use tracing::{error, info, warn};
+
+use super::caching;
+use super::idempotency::{self, Lookup};
+use super::quota;
+use crate::error::CompositeError;
+use crate::firewall::service::FirewallService;
 
-use crate::extractors::ValidatedPath;
+use crate::extractors::{
+    DryRun, Force, IdempotencyKey, IfNoneMatch, ListQueryParams, Propagate, TimeFormatQuery,
+    ValidatedPath,
+};
 use crate::{
     api::{
         AppState,
@@ -17,30 +35,243 @@ use crate::{
     },
     extractors::ValidatedJson,
 };
-use common::{Action, Client, PeerJob, Policy, PolicyPath, RawEntry};
+use common::{
+    Action, ClassifyPath, Client, Entry, EntrySource, ErrorCode, Metadata, PeerJob, PeerJobResult,
+    Policy, PolicyPath, RawEntries, RawEntry,
+};
+
+/// Entries serialized at once in the body of `stream_entries`
+const STREAM_CHUNK_ENTRIES: usize = 256;
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
 
 /// List all entries based on policy
 async fn list_entries(
     State(state): State<AppState>,
     ValidatedPath(policy): ValidatedPath<Policy>,
+    Query(params): Query<ListQueryParams>,
+    TimeFormatQuery(time_format): TimeFormatQuery,
+    if_none_match: IfNoneMatch,
 ) -> impl IntoResponse {
+    if let Some(within) = params.expiring_within {
+        // Depends on the time of the request: no validators
+        return match state.firewall_service.list_entries(policy) {
+            Ok(entries) => {
+                let within = Duration::from_secs(within);
+                let entries: Vec<_> = entries
+                    .iter()
+                    .filter(|entry| entry.expiration.expires_within(within))
+                    .map(|entry| entry.formatted(time_format))
+                    .collect();
+                (StatusCode::OK, Json(entries)).into_response()
+            }
+            Err(ce) => ce.into_response(),
+        };
+    }
+
+    // Read first, so the entries listed are at least as recent as the ETag
+    let (etag, modified) = state.firewall_service.entries_revision(policy);
+    if let Some(response) = caching::not_modified(&if_none_match, &etag, Some(modified)) {
+        return response;
+    }
     match state.firewall_service.list_entries(policy) {
-        Ok(entries) => (StatusCode::OK, Json(entries)).into_response(),
+        Ok(entries) => (
+            StatusCode::OK,
+            caching::validators(&etag, Some(modified)),
+            Json(
+                entries
+                    .iter()
+                    .map(|entry| entry.formatted(time_format))
+                    .collect::<Vec<_>>(),
+            ),
+        )
+            .into_response(),
         Err(ce) => ce.into_response(),
     }
 }
 
+/// Stream the entries of a policy as JSON Lines, serialized chunk by chunk
+/// as the body is sent
+async fn stream_entries(
+    State(state): State<AppState>,
+    ValidatedPath(policy): ValidatedPath<Policy>,
+    TimeFormatQuery(time_format): TimeFormatQuery,
+) -> impl IntoResponse {
+    let mut entries = match Arc::clone(&state.firewall_service).iter_entries(policy) {
+        Ok(entries) => entries,
+        Err(ce) => return ce.into_response(),
+    };
+    let chunks = std::iter::from_fn(move || {
+        let mut chunk = Vec::new();
+        for entry in entries.by_ref().take(STREAM_CHUNK_ENTRIES) {
+            let line = entry
+                .map_err(|ce| io::Error::other(ce.to_string()))
+                .and_then(|entry| Ok(serde_json::to_vec(&entry.formatted(time_format))?));
+            match line {
+                Ok(line) => {
+                    chunk.extend(line);
+                    chunk.push(b'\n');
+                }
+                // Aborts the response, which the client sees truncated
+                Err(e) => {
+                    error!(error = %e, "failed to stream entries");
+                    return Some(Err(e));
+                }
+            }
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    });
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+        Body::from_stream(stream::iter(chunks)),
+    )
+        .into_response()
+}
+
 // Create a new entry based on policy
+#[allow(clippy::too_many_arguments)] // one per extractor
 async fn create_entry(
     State(state): State<AppState>,
     ValidatedPath(policy): ValidatedPath<Policy>,
     Extension(client): Extension<Client>,
+    Force(force): Force,
+    Propagate(propagate): Propagate,
+    DryRun(dry_run): DryRun,
+    IdempotencyKey(key): IdempotencyKey,
     ValidatedJson(raw_entry): ValidatedJson<RawEntry>,
 ) -> impl IntoResponse {
-    let (entry, metadata) = raw_entry.into_entry_and_metadata();
+    if dry_run {
+        // Neither applied nor counted against the quota
+        let entry = raw_entry.into_entry();
+        return match state.firewall_service.check_entry(policy, &entry, force) {
+            Ok(()) => (StatusCode::OK, Json(entry)).into_response(),
+            Err(ce) => ce.into_response(),
+        };
+    }
+
+    let Some(key) = key else {
+        let (entry, metadata) = manual_entry(raw_entry, &client);
+        return add_entry_within_quota(&state, policy, &client, force, propagate, &entry, metadata)
+            .await;
+    };
+
+    let key = idempotency::IdempotencyKey {
+        client: client.name.to_string(),
+        policy,
+        key,
+    };
+    // Held until the result is recorded, so a concurrent retry with the same
+    // key waits for it
+    let slot = state.idempotency.lock().await.slot(&key);
+    let held = slot.lock().await;
+    let now = Instant::now();
+    let lookup = state.idempotency.lock().await.lookup(&key, &raw_entry, now);
+    let response = match lookup {
+        Lookup::Replay(entry) => {
+            info!(
+                client.name = %client.name,
+                policy = %policy,
+                cidr = %entry.cidr,
+                idempotency_key = %key.key,
+                "entry creation replayed"
+            );
+            (StatusCode::CREATED, Json(entry)).into_response()
+        }
+        Lookup::Mismatch => CompositeError::new(
+            ErrorCode::Einvalid,
+            &format!(
+                "Idempotency key {} was used for a different request",
+                key.key
+            ),
+        )
+        .into_response(),
+        Lookup::Miss => {
+            let request = raw_entry.clone();
+            let (entry, metadata) = manual_entry(raw_entry, &client);
+            let response =
+                add_entry_within_quota(&state, policy, &client, force, propagate, &entry, metadata)
+                    .await;
+            if response.status().is_success() {
+                state
+                    .idempotency
+                    .lock()
+                    .await
+                    .record(key.clone(), request, entry, now);
+            }
+            response
+        }
+    };
+    drop(held);
+    state.idempotency.lock().await.release(&key, &slot);
+    response
+}
+
+/// Entry sent by `client`, recorded as its source
+fn manual_entry(raw_entry: RawEntry, client: &Client) -> (Entry, Option<Metadata>) {
+    let (mut entry, metadata) = raw_entry.into_entry_and_metadata();
+    entry.source = Some(EntrySource::manual(client.name.as_str()));
+    (entry, metadata)
+}
+
+/// Add an entry, counted against the daily creation quota of the client
+async fn add_entry_within_quota(
+    state: &AppState,
+    policy: Policy,
+    client: &Client,
+    force: bool,
+    propagate: bool,
+    entry: &Entry,
+    metadata: Option<Metadata>,
+) -> Response {
+    let Some(limit) = state.rbac_service.read().await.creation_quota(client) else {
+        return add_entry(state, policy, client, force, propagate, entry, metadata);
+    };
+
+    // Held until the creation is counted, so concurrent requests cannot
+    // exceed the quota
+    let mut quotas = state.quotas.lock().await;
+    let now = quota::unix_now();
+    if let Err(exceeded) = quotas.check(client.name.as_str(), limit, now) {
+        warn!(
+            client.name = %client.name,
+            policy = %policy,
+            cidr = %entry.cidr,
+            quota = exceeded.quota,
+            "entry creation refused, daily quota reached"
+        );
+        return quota_exceeded(client, &exceeded);
+    }
+
+    let response = add_entry(state, policy, client, force, propagate, entry, metadata);
+    if response.status().is_success() {
+        quotas.record(client.name.as_str(), now);
+    }
+    response
+}
+
+fn quota_exceeded(client: &Client, exceeded: &quota::QuotaExceeded) -> Response {
+    let ce = CompositeError::new(
+        ErrorCode::Equota,
+        &format!(
+            "Client {} reached its quota of {} entries per day",
+            client.name, exceeded.quota
+        ),
+    );
+    ([(RETRY_AFTER, exceeded.retry_after.to_string())], ce).into_response()
+}
+
+fn add_entry(
+    state: &AppState,
+    policy: Policy,
+    client: &Client,
+    force: bool,
+    propagate: bool,
+    entry: &Entry,
+    metadata: Option<Metadata>,
+) -> Response {
     match state
         .firewall_service
-        .add_entry(policy, &entry, metadata, true)
+        .add_entry(policy, entry, metadata, propagate, force)
     {
         Ok(()) => {
             info!(
@@ -50,6 +281,8 @@ async fn create_entry(
                 cidr = %entry.cidr,
                 tag = entry.tag.as_deref().unwrap_or(""),
                 expiration = %entry.expiration,
+                forced = force,
+                propagated = propagate,
                 "entry created"
             );
             (StatusCode::CREATED, Json(entry)).into_response()
@@ -61,110 +294,329 @@ async fn create_entry(
     }
 }
 
+/// Replace the manual entries of a policy with the given ones
+async fn replace_entries(
+    State(state): State<AppState>,
+    ValidatedPath(policy): ValidatedPath<Policy>,
+    Extension(client): Extension<Client>,
+    Force(force): Force,
+    Propagate(propagate): Propagate,
+    ValidatedJson(RawEntries(raw_entries)): ValidatedJson<RawEntries>,
+) -> Response {
+    let entries: Vec<_> = raw_entries
+        .into_iter()
+        .map(|raw_entry| manual_entry(raw_entry, &client))
+        .collect();
+    let Some(limit) = state.rbac_service.read().await.creation_quota(&client) else {
+        return spawn_replace(state, policy, client, force, propagate, entries, None).await;
+    };
+
+    // The entries the replace may create are reserved, so the quota lock is
+    // not held while it runs. A replace is refused when the quota is already
+    // reached, or when it would create more entries than left.
+    let reservation = {
+        let mut quotas = state.quotas.lock().await;
+        let remaining = match quotas.remaining(client.name.as_str(), limit, quota::unix_now()) {
+            Ok(remaining) => remaining,
+            Err(exceeded) => {
+                warn!(
+                    client.name = %client.name,
+                    policy = %policy,
+                    quota = exceeded.quota,
+                    "entries replace refused, daily quota reached"
+                );
+                return quota_exceeded(&client, &exceeded);
+            }
+        };
+        let reserved = remaining.min(u32::try_from(entries.len()).unwrap_or(u32::MAX));
+        quotas.reserve(client.name.as_str(), reserved);
+        Reservation {
+            remaining,
+            reserved,
+        }
+    };
+    spawn_replace(
+        state,
+        policy,
+        client,
+        force,
+        propagate,
+        entries,
+        Some(reservation),
+    )
+    .await
+}
+
+/// Creations set aside for a replace within the quota of its client
+#[derive(Clone, Copy)]
+struct Reservation {
+    /// Entries the client may still create
+    remaining: u32,
+    reserved: u32,
+}
+
+/// Replace the entries on a blocking thread, then give the reservation back
+/// and count the created entries, even when the request was dropped
+/// meanwhile
+async fn spawn_replace(
+    state: AppState,
+    policy: Policy,
+    client: Client,
+    force: bool,
+    propagate: bool,
+    entries: Vec<(Entry, Option<Metadata>)>,
+    reservation: Option<Reservation>,
+) -> Response {
+    let task = tokio::task::spawn_blocking(move || {
+        let max_created = reservation.map(|r| usize::try_from(r.remaining).unwrap_or(usize::MAX));
+        let (response, created) = replace_policy(
+            &state,
+            policy,
+            &client,
+            force,
+            propagate,
+            entries,
+            max_created,
+        );
+        if let Some(reservation) = reservation {
+            let mut quotas = state.quotas.blocking_lock();
+            quotas.release(client.name.as_str(), reservation.reserved);
+            quotas.record_many(client.name.as_str(), created, quota::unix_now());
+        }
+        response
+    });
+    task.await.unwrap_or_else(|e| {
+        CompositeError::new(ErrorCode::Einternal, &format!("Replace failed: {e}")).into_response()
+    })
+}
+
+/// Replace the entries, returning the number of created ones along with the
+/// response
+fn replace_policy(
+    state: &AppState,
+    policy: Policy,
+    client: &Client,
+    force: bool,
+    propagate: bool,
+    entries: Vec<(Entry, Option<Metadata>)>,
+    max_created: Option<usize>,
+) -> (Response, u32) {
+    match state
+        .firewall_service
+        .replace_policy(policy, entries, propagate, force, max_created)
+    {
+        Ok(summary) => {
+            info!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                created = summary.created,
+                updated = summary.updated,
+                removed = summary.removed,
+                unchanged = summary.unchanged,
+                skipped = summary.skipped,
+                failed = summary.failed.len(),
+                forced = force,
+                propagated = propagate,
+                "entries replaced"
+            );
+            let created = u32::try_from(summary.created).unwrap_or(u32::MAX);
+            let status = if summary.failed.is_empty() {
+                StatusCode::OK
+            } else {
+                StatusCode::MULTI_STATUS
+            };
+            ((status, Json(summary)).into_response(), created)
+        }
+        Err(ce) if ce.code == ErrorCode::Equota => {
+            warn!(
+                client.name = %client.name,
+                policy = %policy,
+                error = %ce,
+                "entries replace refused, daily quota too low"
+            );
+            let retry_after = quota::reset_in(quota::unix_now());
+            (
+                ([(RETRY_AFTER, retry_after.to_string())], ce).into_response(),
+                0,
+            )
+        }
+        Err(ce) => {
+            error!(
+                client.name = %client.name,
+                policy = %policy,
+                error = %ce,
+                "failed to replace entries"
+            );
+            (ce.into_response(), 0)
+        }
+    }
+}
+
 /// Get a specific entry based on policy
 async fn get_entry(
     State(state): State<AppState>,
     ValidatedPath(policy_path): ValidatedPath<PolicyPath>,
+    TimeFormatQuery(time_format): TimeFormatQuery,
 ) -> impl IntoResponse {
     match state
         .firewall_service
         .get_entry(policy_path.policy, policy_path.cidr)
     {
-        Ok(entry) => (StatusCode::OK, Json(entry)).into_response(),
+        Ok(entry) => (StatusCode::OK, Json(entry.formatted(time_format))).into_response(),
         Err(ce) => ce.into_response(),
     }
 }
 
-/// Delete an entry based on policy
-async fn delete_entry(
+/// Packets matched by a specific entry
+async fn get_entry_hits(
     State(state): State<AppState>,
     ValidatedPath(policy_path): ValidatedPath<PolicyPath>,
-    Extension(client): Extension<Client>,
 ) -> impl IntoResponse {
     match state
         .firewall_service
-        .get_entry(policy_path.policy, policy_path.cidr)
+        .get_entry_hits(policy_path.policy, policy_path.cidr)
     {
-        Ok(entry) => {
-            if entry.in_set() {
-                error!(
-                    "failed to delete entry: policy={}, cidr={} is in a set",
-                    policy_path.policy, policy_path.cidr
-                );
-                return (
-                    StatusCode::UNPROCESSABLE_ENTITY,
-                    "Entry defined in a set cannot be removed",
-                )
-                    .into_response();
-            }
+        Ok(hits) => (StatusCode::OK, Json(hits)).into_response(),
+        Err(ce) => ce.into_response(),
+    }
+}
 
-            match state
-                .firewall_service
-                .remove_entry(policy_path.policy, policy_path.cidr, true)
-            {
-                Ok(()) => {
-                    info!(
-                        client.name = %client.name,
-                        client.group = %client.group,
-                        policy = %policy_path.policy,
-                        cidr = %policy_path.cidr,
-                        "entry deleted"
-                    );
-                    StatusCode::NO_CONTENT.into_response()
-                }
-                Err(ce) => {
-                    error!(
-                        client.name = %client.name,
-                        client.group = %client.group,
-                        policy = %policy_path.policy,
-                        cidr = %policy_path.cidr,
-                        error = %ce,
-                        "failed to delete entry"
-                    );
-                    ce.into_response()
-                }
-            }
-        }
+/// Classify an address against both policies
+async fn classify(
+    State(state): State<AppState>,
+    ValidatedPath(path): ValidatedPath<ClassifyPath>,
+) -> impl IntoResponse {
+    match state.firewall_service.classify(path.ip) {
+        Ok(classification) => (StatusCode::OK, Json(classification)).into_response(),
         Err(ce) => ce.into_response(),
     }
 }
 
+/// List the last entries removed once expired, the most recent first
+async fn list_recently_expired(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        Json(state.firewall_service.recently_expired()),
+    )
+}
+
+/// Delete an entry based on policy
+async fn delete_entry(
+    State(state): State<AppState>,
+    ValidatedPath(policy_path): ValidatedPath<PolicyPath>,
+    Extension(client): Extension<Client>,
+    Force(force): Force,
+    Propagate(propagate): Propagate,
+) -> impl IntoResponse {
+    match state.firewall_service.remove_entry_if_not_in_set(
+        policy_path.policy,
+        policy_path.cidr,
+        propagate,
+        force,
+    ) {
+        Ok(()) => {
+            info!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy_path.policy,
+                cidr = %policy_path.cidr,
+                propagated = propagate,
+                "entry deleted"
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(ce) => {
+            error!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy_path.policy,
+                cidr = %policy_path.cidr,
+                error = %ce,
+                "failed to delete entry"
+            );
+            ce.into_response()
+        }
+    }
+}
+
 async fn peer_entries(
     State(state): State<AppState>,
     ValidatedPath(policy): ValidatedPath<Policy>,
     Extension(client): Extension<Client>,
     Json(jobs): Json<Vec<PeerJob>>,
 ) -> impl IntoResponse {
-    for job in &jobs {
-        match job.action {
-            Action::Add => {
-                if let Err(ce) = state.firewall_service.add_entry(
+    let jobs_count = jobs.len();
+    let service = Arc::clone(&state.firewall_service);
+    let source = EntrySource::peer(client.name.as_str());
+    // A large batch would otherwise hold a runtime worker while applied
+    let results = match tokio::task::spawn_blocking(move || {
+        apply_peer_jobs(&service, policy, &jobs, &source)
+    })
+    .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            return CompositeError::new(
+                ErrorCode::Einternal,
+                &format!("Failed to apply peer jobs: {e}"),
+            )
+            .into_response();
+        }
+    };
+
+    let failed = results.iter().filter(|result| !result.applied).count();
+    if failed == 0 {
+        info!(
+            client.name = %client.name,
+            client.group = %client.group,
+            policy = %policy,
+            jobs_count,
+            "peer entries synchronized"
+        );
+        (StatusCode::CREATED, Json(results)).into_response()
+    } else {
+        error!(
+            client.name = %client.name,
+            client.group = %client.group,
+            policy = %policy,
+            jobs_count,
+            failed_count = failed,
+            "peer entries partially synchronized"
+        );
+        (StatusCode::MULTI_STATUS, Json(results)).into_response()
+    }
+}
+
+/// Apply the jobs of a peer in order, with the outcome of each
+fn apply_peer_jobs(
+    service: &FirewallService,
+    policy: Policy,
+    jobs: &[PeerJob],
+    source: &EntrySource,
+) -> Vec<PeerJobResult> {
+    jobs.iter()
+        .map(|job| {
+            let outcome = match job.action {
+                Action::Add => service.add_entry(
                     policy,
-                    &job.entry.clone().into_entry(),
+                    &Entry {
+                        source: Some(source.clone()),
+                        ..job.entry.clone().into_entry()
+                    },
                     None,
                     false,
-                ) {
-                    return ce.into_response();
-                }
-            }
-            Action::Remove => {
-                if let Err(ce) = state
-                    .firewall_service
-                    .remove_entry(policy, job.entry.cidr, false)
-                {
-                    return ce.into_response();
-                }
+                    // The protected ranges guard ran on the originating node
+                    true,
+                ),
+                Action::Remove => service.remove_entry(policy, job.entry.cidr, false),
+            };
+            match outcome {
+                Ok(()) => PeerJobResult::applied(job),
+                Err(ce) => PeerJobResult::failed(job, &ce),
             }
-        }
-    }
-    info!(
-        client.name = %client.name,
-        client.group = %client.group,
-        policy = %policy,
-        jobs_count = jobs.len(),
-        "peer entries synchronized"
-    );
-    (StatusCode::CREATED, Json(jobs)).into_response()
+        })
+        .collect()
 }
 
 /// Create router for endpoints based on policy
@@ -179,6 +631,15 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Policy, Verb::List))),
         )
+        .route(
+            "/v1/{policy}/stream",
+            get(stream_entries)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Policy, Verb::List))),
+        )
         .route(
             "/v1/{policy}",
             post(create_entry)
@@ -188,6 +649,15 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Policy, Verb::Create))),
         )
+        .route(
+            "/v1/{policy}",
+            put(replace_entries)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Policy, Verb::Update))),
+        )
         .route(
             "/v1/{policy}/{ip}/{prefix}",
             get(get_entry)
@@ -206,6 +676,33 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Policy, Verb::Delete))),
         )
+        .route(
+            "/v1/{policy}/{ip}/{prefix}/hits",
+            get(get_entry_hits)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Stats, Verb::Get))),
+        )
+        .route(
+            "/v1/classify/{ip}",
+            get(classify)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Policy, Verb::Get))),
+        )
+        .route(
+            "/v1/recently-expired",
+            get(list_recently_expired)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Policy, Verb::List))),
+        )
         .route(
             "/v1/{policy}/peer",
             post(peer_entries)
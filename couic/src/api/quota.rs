@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Entries created per client during the current day (UTC), counted against
+/// the RBAC creation quotas
+#[derive(Default)]
+pub(super) struct QuotaTracker {
+    day: u64,
+    created: HashMap<String, u32>,
+    /// Creations set aside per client by the requests in progress
+    reserved: HashMap<String, u32>,
+}
+
+/// A client reached its daily creation quota
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct QuotaExceeded {
+    pub quota: u32,
+    /// Seconds until the counters are reset
+    pub retry_after: u64,
+}
+
+impl QuotaTracker {
+    /// Whether `client` may create one more entry, `now` being a Unix
+    /// timestamp in seconds
+    pub fn check(&mut self, client: &str, quota: u32, now: u64) -> Result<(), QuotaExceeded> {
+        self.remaining(client, quota, now).map(|_| ())
+    }
+
+    /// Entries `client` may still create today, reserved ones deducted, an
+    /// error when none is left
+    pub fn remaining(&mut self, client: &str, quota: u32, now: u64) -> Result<u32, QuotaExceeded> {
+        self.roll(now);
+        let created = self.created.get(client).copied().unwrap_or(0);
+        let reserved = self.reserved.get(client).copied().unwrap_or(0);
+        match quota.saturating_sub(created.saturating_add(reserved)) {
+            0 => Err(QuotaExceeded {
+                quota,
+                retry_after: reset_in(now),
+            }),
+            remaining => Ok(remaining),
+        }
+    }
+
+    /// Set `count` creations of `client` aside until they are released, so
+    /// that concurrent requests cannot take them
+    pub fn reserve(&mut self, client: &str, count: u32) {
+        let reserved = self.reserved.entry(client.to_string()).or_insert(0);
+        *reserved = reserved.saturating_add(count);
+    }
+
+    /// Give back `count` creations reserved by `client`
+    pub fn release(&mut self, client: &str, count: u32) {
+        if let Some(reserved) = self.reserved.get_mut(client) {
+            *reserved = reserved.saturating_sub(count);
+            if *reserved == 0 {
+                self.reserved.remove(client);
+            }
+        }
+    }
+
+    /// Count a successful creation by `client`
+    pub fn record(&mut self, client: &str, now: u64) {
+        self.record_many(client, 1, now);
+    }
+
+    /// Count `count` successful creations by `client`
+    pub fn record_many(&mut self, client: &str, count: u32, now: u64) {
+        self.roll(now);
+        let created = self.created.entry(client.to_string()).or_insert(0);
+        *created = created.saturating_add(count);
+    }
+
+    /// Reset the counters when the day changed
+    fn roll(&mut self, now: u64) {
+        let day = now / SECONDS_PER_DAY;
+        if day != self.day {
+            self.day = day;
+            self.created.clear();
+        }
+    }
+}
+
+/// Seconds until the counters are reset, at the end of the day of `now`
+pub(super) const fn reset_in(now: u64) -> u64 {
+    SECONDS_PER_DAY.saturating_sub(now % SECONDS_PER_DAY)
+}
+
+pub(super) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const NOON: u64 = 20_000 * SECONDS_PER_DAY + SECONDS_PER_DAY / 2;
+
+    #[test]
+    fn quota_is_enforced_per_client() {
+        let mut tracker = QuotaTracker::default();
+        for _ in 0..2 {
+            assert!(tracker.check("fail2ban", 2, NOON).is_ok());
+            tracker.record("fail2ban", NOON);
+        }
+        assert_eq!(
+            tracker.check("fail2ban", 2, NOON),
+            Err(QuotaExceeded {
+                quota: 2,
+                retry_after: SECONDS_PER_DAY / 2,
+            })
+        );
+        assert!(tracker.check("crowdsec", 2, NOON).is_ok());
+    }
+
+    #[test]
+    fn counters_reset_every_day() {
+        let mut tracker = QuotaTracker::default();
+        tracker.record("fail2ban", NOON);
+        assert!(tracker.check("fail2ban", 1, NOON).is_err());
+        assert!(tracker.check("fail2ban", 1, NOON + SECONDS_PER_DAY).is_ok());
+    }
+
+    #[test]
+    fn bulk_creations_are_counted() {
+        let mut tracker = QuotaTracker::default();
+        tracker.record_many("fail2ban", 3, NOON);
+        assert!(tracker.check("fail2ban", 4, NOON).is_ok());
+        tracker.record("fail2ban", NOON);
+        assert!(tracker.check("fail2ban", 4, NOON).is_err());
+    }
+
+    #[test]
+    fn reserved_creations_are_deducted_until_released() {
+        let mut tracker = QuotaTracker::default();
+        tracker.record("fail2ban", NOON);
+        assert_eq!(tracker.remaining("fail2ban", 4, NOON), Ok(3));
+        tracker.reserve("fail2ban", 3);
+        assert!(tracker.check("fail2ban", 4, NOON).is_err());
+        // A new day does not give the reserved creations back
+        assert_eq!(
+            tracker.remaining("fail2ban", 4, NOON + SECONDS_PER_DAY),
+            Ok(1)
+        );
+
+        tracker.release("fail2ban", 3);
+        tracker.record_many("fail2ban", 2, NOON + SECONDS_PER_DAY);
+        assert_eq!(
+            tracker.remaining("fail2ban", 4, NOON + SECONDS_PER_DAY),
+            Ok(2)
+        );
+        assert!(tracker.reserved.is_empty());
+    }
+}
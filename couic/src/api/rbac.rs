@@ -3,15 +3,14 @@ use std::fmt;
 use std::fs;
 use std::path::Path;
 
+use nix::unistd;
 use tracing::info;
 use uuid::Uuid;
 
 use crate::config::Config;
 use crate::error::CompositeError;
 use crate::security::{SEC_FILE_PERM, SecurityService};
-use common::{Client, ClientFile, ClientName, ErrorCode, Group};
-
-const DEFAULT_USER: &str = "couicctl";
+use common::{Client, ClientFile, ClientName, DEFAULT_CLIENT_NAME, ErrorCode, Group};
 
 impl fmt::Display for Scope {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -25,6 +24,10 @@ pub enum Resource {
     Sets,
     Stats,
     Clients,
+    Health,
+    Logging,
+    Maintenance,
+    Lockdown,
     Any,
 }
 
@@ -56,23 +59,76 @@ impl Scope {
     }
 }
 
+/// Credentials of the process connected to the API socket
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct PeerCred {
+    pub uid: u32,
+    pub gid: u32,
+}
+
 pub struct RBACService {
     clients: HashMap<Uuid, Client>,
     roles: HashMap<Group, HashSet<Scope>>,
+    local_users: HashMap<u32, Group>,
+    local_groups: HashMap<u32, Group>,
     config: Config,
 }
 
 impl RBACService {
     pub fn new(config: Config) -> Result<Self, CompositeError> {
+        let (local_users, local_groups) = Self::resolve_local_trust(&config)?;
         let mut service = Self {
             clients: HashMap::new(),
             roles: Self::default_roles(),
+            local_users,
+            local_groups,
             config,
         };
         service.load_clients()?;
         Ok(service)
     }
 
+    /// Resolves the user and group names of the local trust configuration to
+    /// their numeric IDs, as reported by the socket peer credentials
+    #[allow(clippy::type_complexity)]
+    fn resolve_local_trust(
+        config: &Config,
+    ) -> Result<(HashMap<u32, Group>, HashMap<u32, Group>), CompositeError> {
+        let trust = &config.server.local_trust;
+        let mut users = HashMap::new();
+        for (name, group) in &trust.users {
+            let user = unistd::User::from_name(name)
+                .ok()
+                .flatten()
+                .ok_or_else(|| {
+                    CompositeError::new(
+                        ErrorCode::Einvalid,
+                        &format!("Unknown local trust user: {name}"),
+                    )
+                })?;
+            users.insert(user.uid.as_raw(), group.clone());
+        }
+        let mut groups = HashMap::new();
+        for (name, group) in &trust.groups {
+            let unix_group = unistd::Group::from_name(name)
+                .ok()
+                .flatten()
+                .ok_or_else(|| {
+                    CompositeError::new(
+                        ErrorCode::Einvalid,
+                        &format!("Unknown local trust group: {name}"),
+                    )
+                })?;
+            groups.insert(unix_group.gid.as_raw(), group.clone());
+        }
+        Ok((users, groups))
+    }
+
+    /// Whether callers of the socket may authenticate from their credentials
+    pub fn local_trust_enabled(&self) -> bool {
+        !self.local_users.is_empty() || !self.local_groups.is_empty()
+    }
+
     fn default_roles() -> HashMap<Group, HashSet<Scope>> {
         HashMap::from([
             (
@@ -82,6 +138,7 @@ impl RBACService {
             (
                 Group::ClientRo,
                 HashSet::from([
+                    Scope::with(Resource::Health, Verb::Get),
                     Scope::with(Resource::Policy, Verb::Get),
                     Scope::with(Resource::Policy, Verb::List),
                     Scope::with(Resource::Sets, Verb::Get),
@@ -91,6 +148,7 @@ impl RBACService {
             (
                 Group::ClientRw,
                 HashSet::from([
+                    Scope::with(Resource::Health, Verb::Get),
                     Scope::with(Resource::Policy, Verb::Any),
                     Scope::with(Resource::Sets, Verb::Any),
                 ]),
@@ -98,13 +156,17 @@ impl RBACService {
             (
                 Group::Monitoring,
                 HashSet::from([
+                    Scope::with(Resource::Health, Verb::Get),
                     Scope::with(Resource::Stats, Verb::List),
                     Scope::with(Resource::Stats, Verb::Get),
                 ]),
             ),
             (
                 Group::Peering,
-                HashSet::from([Scope::with(Resource::Policy, Verb::Peer)]),
+                HashSet::from([
+                    Scope::with(Resource::Health, Verb::Get),
+                    Scope::with(Resource::Policy, Verb::Peer),
+                ]),
             ),
         ])
     }
@@ -128,6 +190,10 @@ impl RBACService {
         self.clients.values().cloned().collect()
     }
 
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
     pub fn add_client(&mut self, client: &Client) -> Result<Client, CompositeError> {
         if self
             .clients
@@ -156,7 +222,7 @@ impl RBACService {
             .map(|c| c.token)
         {
             if let Some(client) = self.clients.get(&token)
-                && client.name.as_str() == DEFAULT_USER
+                && client.name.as_str() == DEFAULT_CLIENT_NAME
             {
                 return Err(CompositeError::new(
                     ErrorCode::Einvalid,
@@ -184,15 +250,47 @@ impl RBACService {
 
     pub fn check_authorization(&self, token: Uuid, scope: Scope) -> Option<Client> {
         let client = self.clients.get(&token)?;
-        let permissions = self.roles.get(&client.group)?;
-
-        // Check if any permission matches
-        permissions
-            .iter()
-            .any(|perm| perm.matches(scope))
+        self.is_allowed(&client.group, scope)
             .then_some(client.clone())
     }
 
+    /// Authorizes a socket caller from its credentials, the user mapping
+    /// taking precedence over the group one. The returned client is
+    /// synthesized for logging and carries a nil token.
+    pub fn check_local_authorization(&self, peer: PeerCred, scope: Scope) -> Option<Client> {
+        let (name, group) = if let Some(group) = self.local_users.get(&peer.uid) {
+            (format!("local-uid-{}", peer.uid), group)
+        } else {
+            let group = self.local_groups.get(&peer.gid)?;
+            (format!("local-gid-{}", peer.gid), group)
+        };
+        if !self.is_allowed(group, scope) {
+            return None;
+        }
+        Some(Client {
+            name: ClientName::try_from(name.as_str()).ok()?,
+            token: Uuid::nil(),
+            group: group.clone(),
+        })
+    }
+
+    /// Entries `client` may create per day, `None` when unlimited
+    pub fn creation_quota(&self, client: &Client) -> Option<u32> {
+        let quotas = &self.config.rbac.quotas;
+        quotas
+            .clients
+            .get(client.name.as_str())
+            .or_else(|| quotas.groups.get(&client.group))
+            .copied()
+            .or_else(|| quotas.default.filter(|_| client.group != Group::Admin))
+    }
+
+    fn is_allowed(&self, group: &Group, scope: Scope) -> bool {
+        self.roles
+            .get(group)
+            .is_some_and(|permissions| permissions.iter().any(|perm| perm.matches(scope)))
+    }
+
     /// Reloads clients from configuration directories
     fn load_clients(&mut self) -> Result<(), CompositeError> {
         let clients_dir = Path::new(&self.config.working_dir)
@@ -220,7 +318,7 @@ impl RBACService {
             }
 
             let client = self.load_client_from_file(&path)?;
-            if client.name.as_str() == DEFAULT_USER {
+            if client.name.as_str() == DEFAULT_CLIENT_NAME {
                 found_default_client = true;
             }
 
@@ -282,22 +380,7 @@ impl RBACService {
             )
         })?;
 
-        let file_name = path
-            .file_stem()
-            .and_then(|name| name.to_str())
-            .ok_or_else(|| {
-                CompositeError::new(
-                    ErrorCode::Einvalid,
-                    &format!("Invalid file name: {}", path.display()),
-                )
-            })?;
-
-        let name = ClientName::try_from(file_name).map_err(|e| {
-            CompositeError::new(
-                ErrorCode::Einvalid,
-                &format!("Invalid client name {file_name}: {e}"),
-            )
-        })?;
+        let name = ClientFile::client_name(path, None)?;
 
         Ok(Client {
             name,
@@ -309,7 +392,7 @@ impl RBACService {
     /// Creates the default client if missing
     fn create_default_client(&self) -> Result<Client, CompositeError> {
         let token = Uuid::new_v4();
-        let name = ClientName::try_from(DEFAULT_USER).map_err(|e| {
+        let name = ClientName::try_from(DEFAULT_CLIENT_NAME).map_err(|e| {
             CompositeError::new(
                 ErrorCode::Einternal,
                 &format!("Invalid default client name: {e}"),
@@ -455,14 +538,14 @@ mod tests {
         // Should have default client
         assert_eq!(service.clients.len(), 1);
         let default_client = service.clients.values().next().unwrap();
-        assert_eq!(default_client.name.as_str(), DEFAULT_USER);
+        assert_eq!(default_client.name.as_str(), DEFAULT_CLIENT_NAME);
         assert_eq!(default_client.group, Group::Admin);
         assert!(!default_client.token.is_nil());
         // Default client file should be couicctl.toml
         let client_file = Path::new(&service.config.working_dir)
             .join("rbac")
             .join("clients")
-            .join(format!("{}.toml", DEFAULT_USER));
+            .join(format!("{}.toml", DEFAULT_CLIENT_NAME));
         assert!(client_file.exists());
 
         // Should have all roles configured
@@ -482,6 +565,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_creation_quota_precedence() {
+        let (mut config, _temp_dir) = create_test_config();
+        config.rbac.quotas = crate::config::Quotas {
+            default: Some(100),
+            groups: HashMap::from([(Group::ClientRw, 500)]),
+            clients: HashMap::from([("fail2ban".to_string(), 10)]),
+        };
+        let service = RBACService::new(config).unwrap();
+
+        let quota = |name, group| service.creation_quota(&make_client(name, group));
+        assert_eq!(quota("fail2ban", Group::ClientRw), Some(10));
+        assert_eq!(quota("crowdsec", Group::ClientRw), Some(500));
+        assert_eq!(quota("crowdsec", Group::ClientRo), Some(100));
+        // Admins are only limited when named explicitly
+        assert_eq!(quota("couicctl", Group::Admin), None);
+        assert_eq!(quota("fail2ban", Group::Admin), Some(10));
+    }
+
     #[test]
     fn test_add_client() {
         let (config, _temp_dir) = create_test_config();
@@ -549,7 +651,8 @@ mod tests {
         let (config, _temp_dir) = create_test_config();
         let mut service = RBACService::new(config).unwrap();
 
-        let result = service.delete_client_by_name(&ClientName::try_from(DEFAULT_USER).unwrap());
+        let result =
+            service.delete_client_by_name(&ClientName::try_from(DEFAULT_CLIENT_NAME).unwrap());
         assert!(result.is_err());
         assert!(
             result
@@ -565,13 +668,13 @@ mod tests {
         let service = RBACService::new(config).unwrap();
 
         let admin_client = service
-            .get_client_by_name(&ClientName::try_from(DEFAULT_USER).unwrap())
+            .get_client_by_name(&ClientName::try_from(DEFAULT_CLIENT_NAME).unwrap())
             .unwrap();
         let scope = Scope::with(Resource::Policy, Verb::Delete);
 
         let result = service.check_authorization(admin_client.token, scope);
         assert!(result.is_some());
-        assert_eq!(result.unwrap().name.as_str(), DEFAULT_USER);
+        assert_eq!(result.unwrap().name.as_str(), DEFAULT_CLIENT_NAME);
     }
 
     #[test]
@@ -643,6 +746,20 @@ mod tests {
                 .check_authorization(client_token, stats_scope)
                 .is_none()
         );
+
+        // Maintenance and lockdown are left to admins
+        let maintenance_scope = Scope::with(Resource::Maintenance, Verb::Update);
+        assert!(
+            service
+                .check_authorization(client_token, maintenance_scope)
+                .is_none()
+        );
+        let lockdown_scope = Scope::with(Resource::Lockdown, Verb::Update);
+        assert!(
+            service
+                .check_authorization(client_token, lockdown_scope)
+                .is_none()
+        );
     }
 
     #[test]
@@ -701,6 +818,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_authorize_health_all_groups() {
+        let (config, _temp_dir) = create_test_config();
+        let mut service = RBACService::new(config).unwrap();
+
+        let groups = [
+            Group::Admin,
+            Group::ClientRo,
+            Group::ClientRw,
+            Group::Monitoring,
+            Group::Peering,
+        ];
+        for (i, group) in groups.into_iter().enumerate() {
+            let request = make_client(&format!("health-client-{i}"), group);
+            let token = service.add_client(&request).unwrap().token;
+            let scope = Scope::with(Resource::Health, Verb::Get);
+            assert!(service.check_authorization(token, scope).is_some());
+        }
+    }
+
     #[test]
     fn test_authorize_invalid_token() {
         let (config, _temp_dir) = create_test_config();
@@ -712,6 +849,66 @@ mod tests {
         assert!(service.check_authorization(invalid_token, scope).is_none());
     }
 
+    #[test]
+    fn test_local_authorization_disabled_by_default() {
+        let (config, _temp_dir) = create_test_config();
+        let service = RBACService::new(config).unwrap();
+
+        assert!(!service.local_trust_enabled());
+        let peer = PeerCred { uid: 0, gid: 0 };
+        let scope = Scope::with(Resource::Health, Verb::Get);
+        assert!(service.check_local_authorization(peer, scope).is_none());
+    }
+
+    #[test]
+    fn test_local_authorization_user_precedes_group() {
+        let (mut config, _temp_dir) = create_test_config();
+        config
+            .server
+            .local_trust
+            .users
+            .insert("root".to_string(), Group::Admin);
+        config
+            .server
+            .local_trust
+            .groups
+            .insert("root".to_string(), Group::ClientRo);
+        let service = RBACService::new(config).unwrap();
+        assert!(service.local_trust_enabled());
+
+        let delete = Scope::with(Resource::Policy, Verb::Delete);
+        let client = service
+            .check_local_authorization(PeerCred { uid: 0, gid: 0 }, delete)
+            .unwrap();
+        assert_eq!(client.name.as_str(), "local-uid-0");
+        assert_eq!(client.group, Group::Admin);
+        assert!(client.token.is_nil());
+
+        // Another user of the trusted group only gets the group role
+        let member = PeerCred { uid: 4242, gid: 0 };
+        assert!(service.check_local_authorization(member, delete).is_none());
+        let get = Scope::with(Resource::Policy, Verb::Get);
+        let client = service.check_local_authorization(member, get).unwrap();
+        assert_eq!(client.name.as_str(), "local-gid-0");
+
+        let stranger = PeerCred {
+            uid: 4242,
+            gid: 4242,
+        };
+        assert!(service.check_local_authorization(stranger, get).is_none());
+    }
+
+    #[test]
+    fn test_local_trust_unknown_user_rejected() {
+        let (mut config, _temp_dir) = create_test_config();
+        config
+            .server
+            .local_trust
+            .users
+            .insert("no-such-user-couic".to_string(), Group::Admin);
+        assert!(RBACService::new(config).is_err());
+    }
+
     #[test]
     fn test_list_clients() {
         let (config, _temp_dir) = create_test_config();
@@ -727,7 +924,7 @@ mod tests {
         assert_eq!(clients.len(), 3); // default + 2 new
 
         let names: Vec<&str> = clients.iter().map(|c| c.name.as_str()).collect();
-        assert!(names.contains(&DEFAULT_USER));
+        assert!(names.contains(&DEFAULT_CLIENT_NAME));
         assert!(names.contains(&"client1"));
         assert!(names.contains(&"client2"));
     }
@@ -888,7 +1085,7 @@ group = "admin"
 
         // Verify the default client has the token from the file
         let default = service
-            .get_client_by_name(&ClientName::try_from(DEFAULT_USER).unwrap())
+            .get_client_by_name(&ClientName::try_from(DEFAULT_CLIENT_NAME).unwrap())
             .unwrap();
         assert_eq!(default.token, default_token);
     }
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+
+/// Label of the requests matching no route, so that unknown paths do not
+/// each get their own counter
+const UNMATCHED_PATH: &str = "unmatched";
+/// Label of the requests with an extension method, counted before
+/// authentication like the others
+const OTHER_METHOD: &str = "other";
+
+/// Method, route and status of a served request
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct RequestKey {
+    pub method: String,
+    /// Route template, e.g. `/v1/{policy}`
+    pub path: String,
+    pub status: u16,
+}
+
+/// Requests served by the API, counted per method, route and status
+#[derive(Debug, Default)]
+pub(super) struct RequestMetrics {
+    counts: Mutex<BTreeMap<RequestKey, u64>>,
+}
+
+impl RequestMetrics {
+    fn record(&self, key: RequestKey) {
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = counts.entry(key).or_default();
+        *count = count.saturating_add(1);
+    }
+
+    /// Counters sorted by method, route and status
+    pub fn snapshot(&self) -> Vec<(RequestKey, u64)> {
+        self.counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect()
+    }
+}
+
+/// Counts each request once its response is ready
+pub(super) async fn record_requests(
+    State(metrics): State<Arc<RequestMetrics>>,
+    matched: Option<MatchedPath>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = method_label(req.method()).to_string();
+    let path = matched.map_or_else(|| UNMATCHED_PATH.to_string(), |m| m.as_str().to_string());
+    let response = next.run(req).await;
+    metrics.record(RequestKey {
+        method,
+        path,
+        status: response.status().as_u16(),
+    });
+    response
+}
+
+/// Standard methods are counted under their name, the others together
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::PATCH => "PATCH",
+        Method::DELETE => "DELETE",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => OTHER_METHOD,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn key(path: &str, status: u16) -> RequestKey {
+        RequestKey {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            status,
+        }
+    }
+
+    #[test]
+    fn requests_are_counted_per_route_and_status() {
+        let metrics = RequestMetrics::default();
+        metrics.record(key("/v1/{policy}", 200));
+        metrics.record(key("/v1/stats", 200));
+        metrics.record(key("/v1/{policy}", 200));
+        metrics.record(key("/v1/{policy}", 404));
+
+        assert_eq!(
+            metrics.snapshot(),
+            vec![
+                (key("/v1/stats", 200), 1),
+                (key("/v1/{policy}", 200), 2),
+                (key("/v1/{policy}", 404), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn extension_methods_share_one_label() {
+        assert_eq!(method_label(&Method::DELETE), "DELETE");
+        for name in ["PURGE", "X-ANYTHING-1", "get"] {
+            let method = Method::from_bytes(name.as_bytes()).unwrap();
+            assert_eq!(method_label(&method), OTHER_METHOD);
+        }
+    }
+}
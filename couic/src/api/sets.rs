@@ -1,18 +1,18 @@
 use axum::{
     Extension, Json, Router,
     extract::State,
-    http::StatusCode,
+    http::{StatusCode, header::ETAG},
     middleware,
     response::IntoResponse,
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use super::middleware::auth_middleware;
 use super::rbac::{Resource, Scope, Verb};
-use crate::extractors::ValidatedJson;
+use crate::extractors::{Force, IfMatch, ValidatedJson};
 use crate::{api::AppState, extractors::ValidatedPath};
-use common::{Client, Policy, Set, SetPath};
+use common::{Client, Policy, Set, SetActivation, SetPatch, SetPath, SetsReloadResult};
 
 /// List all sets for a given policy
 async fn list_sets(
@@ -25,6 +25,17 @@ async fn list_sets(
     }
 }
 
+/// Count the entries of all the sets of a policy, overlaps once
+async fn get_sets_summary(
+    State(state): State<AppState>,
+    ValidatedPath(policy): ValidatedPath<Policy>,
+) -> impl IntoResponse {
+    match state.firewall_service.sets_summary(policy) {
+        Ok(summary) => (StatusCode::OK, Json(summary)).into_response(),
+        Err(ce) => ce.into_response(),
+    }
+}
+
 /// Create a new set
 async fn create_set(
     State(state): State<AppState>,
@@ -34,9 +45,9 @@ async fn create_set(
 ) -> impl IntoResponse {
     match state
         .firewall_service
-        .create_set(policy, &set.name, &set.entries)
+        .create_set(policy, &set.name, &set.header, &set.entries)
     {
-        Ok(response) => {
+        Ok((response, etag)) => {
             info!(
                 client.name = %client.name,
                 client.group = %client.group,
@@ -45,7 +56,7 @@ async fn create_set(
                 set.entry_count = set.entries.len(),
                 "set created"
             );
-            (StatusCode::CREATED, Json(response)).into_response()
+            (StatusCode::CREATED, [(ETAG, etag)], Json(response)).into_response()
         }
         Err(ce) => {
             error!(
@@ -67,23 +78,28 @@ async fn get_set(
     ValidatedPath(SetPath { policy, name }): ValidatedPath<SetPath>,
 ) -> impl IntoResponse {
     match state.firewall_service.get_set(policy, &name) {
-        Ok(set) => (StatusCode::OK, Json(set)).into_response(),
+        Ok((set, etag)) => (StatusCode::OK, [(ETAG, etag)], Json(set)).into_response(),
         Err(ce) => ce.into_response(),
     }
 }
 
-/// Update a set (replace all entries)
+/// Update a set (replace all entries), only if it still matches the
+/// `If-Match` ETags when given
 async fn update_set(
     State(state): State<AppState>,
     ValidatedPath(SetPath { policy, name }): ValidatedPath<SetPath>,
     Extension(client): Extension<Client>,
+    IfMatch(if_match): IfMatch,
     ValidatedJson(set): ValidatedJson<Set>,
 ) -> impl IntoResponse {
-    match state
-        .firewall_service
-        .update_set(policy, &name, &set.entries)
-    {
-        Ok(response) => {
+    match state.firewall_service.update_set(
+        policy,
+        &name,
+        &set.header,
+        &set.entries,
+        if_match.as_deref(),
+    ) {
+        Ok((response, etag)) => {
             info!(
                 client.name = %client.name,
                 client.group = %client.group,
@@ -92,7 +108,7 @@ async fn update_set(
                 set.entry_count = set.entries.len(),
                 "set updated"
             );
-            (StatusCode::OK, Json(response)).into_response()
+            (StatusCode::OK, [(ETAG, etag)], Json(response)).into_response()
         }
         Err(ce) => {
             error!(
@@ -108,6 +124,46 @@ async fn update_set(
     }
 }
 
+/// Add and remove entries of a set, only if it still matches the `If-Match`
+/// ETags when given
+async fn patch_set(
+    State(state): State<AppState>,
+    ValidatedPath(SetPath { policy, name }): ValidatedPath<SetPath>,
+    Extension(client): Extension<Client>,
+    IfMatch(if_match): IfMatch,
+    ValidatedJson(patch): ValidatedJson<SetPatch>,
+) -> impl IntoResponse {
+    match state
+        .firewall_service
+        .patch_set(policy, &name, &patch, if_match.as_deref())
+    {
+        Ok((response, etag)) => {
+            info!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                set.name = %name,
+                set.added = response.added,
+                set.removed = response.removed,
+                set.entry_count = response.entry_count,
+                "set patched"
+            );
+            (StatusCode::OK, [(ETAG, etag)], Json(response)).into_response()
+        }
+        Err(ce) => {
+            error!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                set.name = %name,
+                error = %ce,
+                "failed to patch set"
+            );
+            ce.into_response()
+        }
+    }
+}
+
 /// Delete a set
 async fn delete_set(
     State(state): State<AppState>,
@@ -123,11 +179,18 @@ async fn delete_set(
                 set.name = %name,
                 "set deleted"
             );
+            let reload_required = match state.firewall_service.sets_reload_status() {
+                Ok(status) => status.reload_required,
+                Err(ce) => {
+                    warn!(error = %ce, "failed to compute sets reload status");
+                    true
+                }
+            };
             (
                 StatusCode::OK,
                 Json(serde_json::json!({
                     "deleted": true,
-                    "reload_required": true
+                    "reload_required": reload_required
                 })),
             )
                 .into_response()
@@ -146,23 +209,91 @@ async fn delete_set(
     }
 }
 
+/// Disable a set, keeping its file
+async fn disable_set(
+    State(state): State<AppState>,
+    ValidatedPath(path): ValidatedPath<SetPath>,
+    Extension(client): Extension<Client>,
+) -> impl IntoResponse {
+    set_disabled(&state, path, &client, true)
+}
+
+/// Enable a disabled set
+async fn enable_set(
+    State(state): State<AppState>,
+    ValidatedPath(path): ValidatedPath<SetPath>,
+    Extension(client): Extension<Client>,
+) -> impl IntoResponse {
+    set_disabled(&state, path, &client, false)
+}
+
+fn set_disabled(
+    state: &AppState,
+    SetPath { policy, name }: SetPath,
+    client: &Client,
+    disabled: bool,
+) -> axum::response::Response {
+    match state.firewall_service.set_disabled(policy, &name, disabled) {
+        Ok(()) => {
+            info!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                set.name = %name,
+                disabled,
+                "set activation changed"
+            );
+            let reload_required = match state.firewall_service.sets_reload_status() {
+                Ok(status) => status.reload_required,
+                Err(ce) => {
+                    warn!(error = %ce, "failed to compute sets reload status");
+                    true
+                }
+            };
+            (
+                StatusCode::OK,
+                Json(SetActivation {
+                    name,
+                    disabled,
+                    reload_required,
+                }),
+            )
+                .into_response()
+        }
+        Err(ce) => {
+            error!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                set.name = %name,
+                error = %ce,
+                "failed to change set activation"
+            );
+            ce.into_response()
+        }
+    }
+}
+
 /// Handler for reloading sets
 async fn post_sets_reload(
     State(state): State<AppState>,
     Extension(client): Extension<Client>,
+    Force(force): Force,
 ) -> impl IntoResponse {
-    match state.firewall_service.reload_sets() {
-        Ok(()) => {
+    match state.firewall_service.reload_sets(force) {
+        Ok(reloads) => {
             info!(
                 client.name = %client.name,
                 client.group = %client.group,
+                forced = force,
                 "sets reloaded"
             );
             (
                 StatusCode::CREATED,
-                Json(serde_json::json!({
-                    "reload_status": "OK"
-                })),
+                Json(SetsReloadResult {
+                    reload_status: "OK".to_string(),
+                    reloads,
+                }),
             )
                 .into_response()
         }
@@ -178,6 +309,62 @@ async fn post_sets_reload(
     }
 }
 
+/// Handler for reloading a single set
+async fn post_set_reload(
+    State(state): State<AppState>,
+    ValidatedPath(SetPath { policy, name }): ValidatedPath<SetPath>,
+    Extension(client): Extension<Client>,
+    Force(force): Force,
+) -> impl IntoResponse {
+    match state.firewall_service.reload_set(policy, &name, force) {
+        Ok(reload) => {
+            info!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                set.name = %name,
+                forced = force,
+                "set reloaded"
+            );
+            (
+                StatusCode::CREATED,
+                Json(SetsReloadResult {
+                    reload_status: "OK".to_string(),
+                    reloads: vec![reload],
+                }),
+            )
+                .into_response()
+        }
+        Err(ce) => {
+            error!(
+                client.name = %client.name,
+                client.group = %client.group,
+                policy = %policy,
+                set.name = %name,
+                error = %ce,
+                "failed to reload set"
+            );
+            ce.into_response()
+        }
+    }
+}
+
+/// Handler reporting whether set files differ from the loaded sets
+async fn get_sets_reload_status(State(state): State<AppState>) -> impl IntoResponse {
+    match state.firewall_service.sets_reload_status() {
+        Ok(status) => (StatusCode::OK, Json(status)).into_response(),
+        Err(ce) => ce.into_response(),
+    }
+}
+
+/// Handler reporting the time and changes of the last reload of each policy
+async fn get_sets_status(State(state): State<AppState>) -> impl IntoResponse {
+    match state.firewall_service.sets_status() {
+        Ok(status) => (StatusCode::OK, Json(status)).into_response(),
+        Err(ce) => ce.into_response(),
+    }
+}
+
 /// Create router for sets endpoints
 pub(super) fn router(state: AppState) -> Router<AppState> {
     Router::new()
@@ -199,6 +386,15 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Sets, Verb::Create))),
         )
+        .route(
+            "/v1/sets/{policy}/summary",
+            get(get_sets_summary)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::List))),
+        )
         .route(
             "/v1/sets/{policy}/{name}",
             get(get_set)
@@ -217,6 +413,15 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Sets, Verb::Update))),
         )
+        .route(
+            "/v1/sets/{policy}/{name}",
+            patch(patch_set)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::Update))),
+        )
         .route(
             "/v1/sets/{policy}/{name}",
             delete(delete_set)
@@ -226,6 +431,51 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Sets, Verb::Delete))),
         )
+        .route(
+            "/v1/sets/{policy}/{name}/disable",
+            post(disable_set)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::Update))),
+        )
+        .route(
+            "/v1/sets/{policy}/{name}/enable",
+            post(enable_set)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::Update))),
+        )
+        .route(
+            "/v1/sets/{policy}/{name}/reload",
+            post(post_set_reload)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::Create))),
+        )
+        .route(
+            "/v1/sets/status",
+            get(get_sets_status)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::Get))),
+        )
+        .route(
+            "/v1/sets/reload/status",
+            get(get_sets_reload_status)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Sets, Verb::Get))),
+        )
         .route(
             "/v1/sets/reload",
             post(post_sets_reload)
@@ -1,19 +1,30 @@
+use std::time::{Duration, Instant};
+
 use axum::{
     Extension, Json, Router,
     extract::{Query, State},
     http::StatusCode,
     middleware,
-    response::IntoResponse,
-    routing::get,
+    response::{IntoResponse, Response},
+    routing::{get, post},
 };
 
 use serde::Serialize;
+use tracing::{error, info};
 
+use super::caching;
 use super::middleware::auth_middleware;
 use super::rbac::{Resource, Scope, Verb};
-use crate::extractors::QueryParams;
+use super::requests::RequestKey;
+use crate::error::CompositeError;
+use crate::extractors::{DeltaQueryParams, IfNoneMatch, QueryParams};
+use crate::firewall::service::{FirewallService, content_etag};
 use crate::{api::AppState, extractors::ValidatedPath};
-use common::{Policy, Stats, TagStats};
+use common::{Client, ErrorCode, PktRates, Policy, Stats, StatsDelta, TagStats};
+
+/// Window used by the delta endpoint when no previous snapshot is cached
+const DEFAULT_DELTA_WINDOW_SECS: u64 = 1;
+const MAX_DELTA_WINDOW_SECS: u64 = 60;
 
 #[derive(Debug, Serialize)]
 struct Metrics {
@@ -21,10 +32,77 @@ struct Metrics {
     stats: Stats,
     drop_tags: TagStats,
     ignore_tags: TagStats,
+    monitor_tags: TagStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reports_dropped: Option<u64>,
+}
+
+/// Activity of the API, only rendered in the Prometheus format
+struct ApiMetrics {
+    active_clients: usize,
+    requests: Vec<(RequestKey, u64)>,
+}
+
+impl ApiMetrics {
+    async fn collect(state: &AppState) -> Self {
+        Self {
+            active_clients: state.rbac_service.read().await.client_count(),
+            requests: state.requests.snapshot(),
+        }
+    }
+}
+
+/// Counters kept between two delta requests
+pub(super) struct StatsSnapshot {
+    taken: Instant,
+    metrics: Metrics,
+}
+
+impl StatsSnapshot {
+    fn take(firewall_service: &FirewallService) -> Result<Self, CompositeError> {
+        Ok(Self {
+            taken: Instant::now(),
+            metrics: collect_metrics(firewall_service)?,
+        })
+    }
+
+    fn delta_since(&self, prev: &Self) -> StatsDelta {
+        let window_secs = self.taken.duration_since(prev.taken).as_secs_f64();
+        StatsDelta {
+            window_secs,
+            xdp: PktRates::between_maps(
+                &prev.metrics.stats.xdp,
+                &self.metrics.stats.xdp,
+                window_secs,
+            ),
+            drop_tags: PktRates::between_maps(
+                &prev.metrics.drop_tags.tags,
+                &self.metrics.drop_tags.tags,
+                window_secs,
+            ),
+            ignore_tags: PktRates::between_maps(
+                &prev.metrics.ignore_tags.tags,
+                &self.metrics.ignore_tags.tags,
+                window_secs,
+            ),
+            monitor_tags: PktRates::between_maps(
+                &prev.metrics.monitor_tags.tags,
+                &self.metrics.monitor_tags.tags,
+                window_secs,
+            ),
+        }
+    }
 }
 
 /// Helper function to convert all stats to Prometheus format
-fn prometheus_report(stats: &Stats, drop_tags: &TagStats, ignore_tags: &TagStats) -> String {
+fn prometheus_report(metrics: &Metrics, api: &ApiMetrics) -> String {
+    let Metrics {
+        stats,
+        drop_tags,
+        ignore_tags,
+        monitor_tags,
+        reports_dropped,
+    } = metrics;
     let mut report = String::with_capacity(4096);
 
     // couic_drop_cidr_total
@@ -43,6 +121,14 @@ fn prometheus_report(stats: &Stats, drop_tags: &TagStats, ignore_tags: &TagStats
         stats.ignore_cidr_count
     ));
 
+    // couic_monitor_cidr_total
+    report.push_str("# HELP couic_monitor_cidr_total Current number of CIDR monitored by couic.\n");
+    report.push_str("# TYPE couic_monitor_cidr_total gauge\n");
+    report.push_str(&format!(
+        "couic_monitor_cidr_total {}\n",
+        stats.monitor_cidr_count
+    ));
+
     // couic_stats_rx_packets_total
     report.push_str(
         "# HELP couic_stats_rx_packets_total Current number of packets handled by XDP.\n",
@@ -65,6 +151,16 @@ fn prometheus_report(stats: &Stats, drop_tags: &TagStats, ignore_tags: &TagStats
         ));
     }
 
+    // couic_drop_reason_total
+    report.push_str("# HELP couic_drop_reason_total Number of packets dropped per reason.\n");
+    report.push_str("# TYPE couic_drop_reason_total counter\n");
+    for (k, v) in &stats.drop_reasons {
+        report.push_str(&format!(
+            "couic_drop_reason_total{{reason=\"{}\"}} {}\n",
+            k, v.rx_packets
+        ));
+    }
+
     // couic_drop_tag_rx_packets_total
     report.push_str("# HELP couic_drop_tag_rx_packets_total Number of packets dropped per tag.\n");
     report.push_str("# TYPE couic_drop_tag_rx_packets_total counter\n");
@@ -106,6 +202,54 @@ fn prometheus_report(stats: &Stats, drop_tags: &TagStats, ignore_tags: &TagStats
         ));
     }
 
+    // couic_monitor_tag_rx_packets_total
+    report.push_str(
+        "# HELP couic_monitor_tag_rx_packets_total Number of packets monitor entries would have dropped per tag.\n",
+    );
+    report.push_str("# TYPE couic_monitor_tag_rx_packets_total counter\n");
+    for (tag, pkt_stats) in &monitor_tags.tags {
+        report.push_str(&format!(
+            "couic_monitor_tag_rx_packets_total{{tag=\"{}\"}} {}\n",
+            tag, pkt_stats.rx_packets
+        ));
+    }
+
+    // couic_monitor_tag_rx_bytes_total
+    report.push_str(
+        "# HELP couic_monitor_tag_rx_bytes_total Number of bytes monitor entries would have dropped per tag.\n",
+    );
+    report.push_str("# TYPE couic_monitor_tag_rx_bytes_total counter\n");
+    for (tag, pkt_stats) in &monitor_tags.tags {
+        report.push_str(&format!(
+            "couic_monitor_tag_rx_bytes_total{{tag=\"{}\"}} {}\n",
+            tag, pkt_stats.rx_bytes
+        ));
+    }
+
+    // couic_reports_dropped_total
+    if let Some(dropped) = reports_dropped {
+        report.push_str(
+            "# HELP couic_reports_dropped_total Number of reports dropped by a full reporting queue.\n",
+        );
+        report.push_str("# TYPE couic_reports_dropped_total counter\n");
+        report.push_str(&format!("couic_reports_dropped_total {dropped}\n"));
+    }
+
+    // couic_active_clients
+    report.push_str("# HELP couic_active_clients Number of clients known to RBAC.\n");
+    report.push_str("# TYPE couic_active_clients gauge\n");
+    report.push_str(&format!("couic_active_clients {}\n", api.active_clients));
+
+    // couic_http_requests_total
+    report.push_str("# HELP couic_http_requests_total Number of API requests served.\n");
+    report.push_str("# TYPE couic_http_requests_total counter\n");
+    for (key, count) in &api.requests {
+        report.push_str(&format!(
+            "couic_http_requests_total{{method=\"{}\",path=\"{}\",status=\"{}\"}} {}\n",
+            key.method, key.path, key.status, count
+        ));
+    }
+
     // OpenMetrics requires EOF marker
     report.push_str("# EOF\n");
 
@@ -113,11 +257,33 @@ fn prometheus_report(stats: &Stats, drop_tags: &TagStats, ignore_tags: &TagStats
 }
 
 /// Handler for XDP statistics endpoint
-async fn get_stats(State(state): State<AppState>) -> impl IntoResponse {
-    match state.firewall_service.get_stats() {
-        Ok(stats) => (StatusCode::OK, Json(stats)).into_response(),
-        Err(ce) => ce.into_response(),
+async fn get_stats(State(state): State<AppState>, if_none_match: IfNoneMatch) -> impl IntoResponse {
+    let stats = match state.firewall_service.get_stats() {
+        Ok(stats) => stats,
+        Err(ce) => return ce.into_response(),
+    };
+    // Counters change with traffic, so the ETag follows the content. Going
+    // through a value sorts the keys of the maps, for a stable hash.
+    let stats = match serde_json::to_value(stats) {
+        Ok(stats) => stats,
+        Err(e) => {
+            return CompositeError::new(
+                ErrorCode::Einternal,
+                &format!("Failed to serialize stats: {e}"),
+            )
+            .into_response();
+        }
+    };
+    let etag = content_etag(&stats.to_string());
+    if let Some(response) = caching::not_modified(&if_none_match, &etag, None) {
+        return response;
     }
+    (
+        StatusCode::OK,
+        caching::validators(&etag, None),
+        Json(stats),
+    )
+        .into_response()
 }
 
 /// Handler for statistics per tag endpoint
@@ -131,47 +297,134 @@ async fn get_stats_tag(
     }
 }
 
-/// Handler for metrics endpoint
-async fn get_metrics(
+/// Collects XDP and per tag statistics for the metrics endpoints
+fn collect_metrics(firewall_service: &FirewallService) -> Result<Metrics, CompositeError> {
+    Ok(Metrics {
+        stats: firewall_service.get_stats()?,
+        drop_tags: firewall_service.get_stats_tags(Policy::Drop)?,
+        ignore_tags: firewall_service.get_stats_tags(Policy::Ignore)?,
+        monitor_tags: firewall_service.get_stats_tags(Policy::Monitor)?,
+        reports_dropped: firewall_service.reports_dropped(),
+    })
+}
+
+fn prometheus_response(metrics: &Metrics, api: &ApiMetrics) -> Response {
+    let metrics_text = prometheus_report(metrics, api);
+    (
+        StatusCode::OK,
+        [(
+            "content-type",
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
+        metrics_text,
+    )
+        .into_response()
+}
+
+/// Handler for rates endpoint. With `window_secs`, counters are sampled twice
+/// that many seconds apart. Otherwise rates are computed since the previous
+/// call, or over a default window on the first call.
+async fn get_stats_delta(
     State(state): State<AppState>,
-    Query(params): Query<QueryParams>,
+    Query(params): Query<DeltaQueryParams>,
 ) -> impl IntoResponse {
-    let stats = match state.firewall_service.get_stats() {
-        Ok(s) => s,
-        Err(ce) => return ce.into_response(),
+    if let Some(window_secs) = params.window_secs
+        && !(1..=MAX_DELTA_WINDOW_SECS).contains(&window_secs)
+    {
+        let mut ce = CompositeError::new(ErrorCode::Einvalid, "invalid query parameters");
+        ce.add_detail(
+            "window_secs",
+            ErrorCode::Einvalid,
+            &format!("must be between 1 and {MAX_DELTA_WINDOW_SECS}"),
+        );
+        return ce.into_response();
+    }
+
+    let cached = match params.window_secs {
+        Some(_) => None,
+        None => state.stats_snapshot.lock().await.take(),
+    };
+    let prev = match cached {
+        Some(prev) => prev,
+        None => {
+            let prev = match StatsSnapshot::take(&state.firewall_service) {
+                Ok(s) => s,
+                Err(ce) => return ce.into_response(),
+            };
+            let window_secs = params.window_secs.unwrap_or(DEFAULT_DELTA_WINDOW_SECS);
+            tokio::time::sleep(Duration::from_secs(window_secs)).await;
+            prev
+        }
     };
 
-    let drop_tags = match state.firewall_service.get_stats_tags(Policy::Drop) {
-        Ok(t) => t,
+    let current = match StatsSnapshot::take(&state.firewall_service) {
+        Ok(s) => s,
         Err(ce) => return ce.into_response(),
     };
+    let delta = current.delta_since(&prev);
+    *state.stats_snapshot.lock().await = Some(current);
+
+    (StatusCode::OK, Json(delta)).into_response()
+}
+
+/// Handler zeroing the XDP, per tag and per entry counters
+async fn post_stats_reset(
+    State(state): State<AppState>,
+    Extension(client): Extension<Client>,
+) -> impl IntoResponse {
+    match state.firewall_service.reset_stats() {
+        Ok(()) => {
+            // Rates since a snapshot taken before the reset are meaningless
+            *state.stats_snapshot.lock().await = None;
+            info!(
+                client.name = %client.name,
+                client.group = %client.group,
+                "stats reset"
+            );
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(ce) => {
+            error!(
+                client.name = %client.name,
+                client.group = %client.group,
+                error = %ce,
+                "failed to reset stats"
+            );
+            ce.into_response()
+        }
+    }
+}
 
-    let ignore_tags = match state.firewall_service.get_stats_tags(Policy::Ignore) {
-        Ok(t) => t,
+/// Handler for metrics endpoint
+async fn get_metrics(
+    State(state): State<AppState>,
+    Query(params): Query<QueryParams>,
+) -> impl IntoResponse {
+    let metrics = match collect_metrics(&state.firewall_service) {
+        Ok(m) => m,
         Err(ce) => return ce.into_response(),
     };
 
     if params.format.as_deref() == Some("prometheus") {
-        let metrics_text = prometheus_report(&stats, &drop_tags, &ignore_tags);
-        (
-            StatusCode::OK,
-            [(
-                "content-type",
-                "application/openmetrics-text; version=1.0.0; charset=utf-8",
-            )],
-            metrics_text,
-        )
-            .into_response()
+        prometheus_response(&metrics, &ApiMetrics::collect(&state).await)
     } else {
-        let metrics = Metrics {
-            stats,
-            drop_tags,
-            ignore_tags,
-        };
         (StatusCode::OK, Json(metrics)).into_response()
     }
 }
 
+/// Handler for the unauthenticated metrics listener, Prometheus format only
+async fn get_prometheus_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    match collect_metrics(&state.firewall_service) {
+        Ok(metrics) => prometheus_response(&metrics, &ApiMetrics::collect(&state).await),
+        Err(ce) => ce.into_response(),
+    }
+}
+
+/// Create router for the dedicated metrics listener
+pub(super) fn metrics_router() -> Router<AppState> {
+    Router::new().route("/metrics", get(get_prometheus_metrics))
+}
+
 /// Create router for stats endpoints
 pub(super) fn router(state: AppState) -> Router<AppState> {
     Router::new()
@@ -184,6 +437,15 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Stats, Verb::List))),
         )
+        .route(
+            "/v1/stats/delta",
+            get(get_stats_delta)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Stats, Verb::List))),
+        )
         .route(
             "/v1/stats/tags/{policy}",
             get(get_stats_tag)
@@ -193,6 +455,15 @@ pub(super) fn router(state: AppState) -> Router<AppState> {
                 ))
                 .route_layer(Extension(Scope::with(Resource::Stats, Verb::Get))),
         )
+        .route(
+            "/v1/stats/reset",
+            post(post_stats_reset)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    auth_middleware,
+                ))
+                .route_layer(Extension(Scope::with(Resource::Stats, Verb::Delete))),
+        )
         .route(
             "/v1/metrics",
             get(get_metrics)
@@ -1,15 +1,22 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fs;
+use std::io::IsTerminal;
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::Path;
 
+use ipnet::IpNet;
+use serde::de::IntoDeserializer;
 use serde::{Deserialize, Serialize};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_appender::rolling::{Builder, Rotation};
-use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt as tracing_fmt;
 use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Registry, reload};
 use uuid::Uuid;
 
 use crate::security::{SEC_DIR_PERM, SecurityService};
+use common::{ClientName, Group, LogLevel};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
@@ -51,9 +58,85 @@ fn default_max_log_files() -> usize {
     7
 }
 
+fn default_stats_interval_secs() -> u64 {
+    0
+}
+
+fn default_compression_enabled() -> bool {
+    true
+}
+
+fn default_compression_min_size() -> u16 {
+    1024
+}
+
+fn default_max_body_size() -> usize {
+    8 * 1024 * 1024
+}
+
+const fn default_reporting_queue_capacity() -> usize {
+    crate::firewall::reporting::DEFAULT_QUEUE_CAPACITY
+}
+
+fn default_metrics_addr() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+fn default_metrics_port() -> u16 {
+    2901
+}
+
+fn default_cleanup_interval_secs() -> u64 {
+    1
+}
+
+fn default_shrink_interval_cycles() -> u32 {
+    3600
+}
+
+fn default_max_sets() -> usize {
+    1000
+}
+
+fn default_watch_debounce_ms() -> u64 {
+    1000
+}
+
+fn default_recently_expired() -> usize {
+    256
+}
+
+fn default_peering_batch_size() -> usize {
+    1000
+}
+
+fn default_peering_batch_max_age_ms() -> u64 {
+    200
+}
+
+fn default_min_prefix_v4() -> u8 {
+    8
+}
+
+fn default_min_prefix_v6() -> u8 {
+    32
+}
+
+/// Loopback and link-local ranges, for both families
+fn default_protected_cidrs() -> Vec<IpNet> {
+    ["127.0.0.0/8", "169.254.0.0/16", "::1/128", "fe80::/10"]
+        .into_iter()
+        .filter_map(|cidr| cidr.parse().ok())
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub ifaces: Vec<String>,
+    /// Attach and detach the XDP program as interfaces matching `ifaces`
+    /// appear and disappear, instead of only at startup
+    #[serde(default)]
+    pub hotplug: bool,
     #[serde(default)]
     pub operation_mode: OperationMode,
     pub working_dir: String,
@@ -63,11 +146,255 @@ pub struct Config {
     pub server: Server,
     pub reporting: Option<Reporting>,
     pub peering: Option<Peering>,
+    pub metrics: Option<Metrics>,
+    #[serde(default)]
+    pub icmp_exceptions: IcmpExceptions,
+    #[serde(default)]
+    pub firewall: Firewall,
+    #[serde(default)]
+    pub rbac: Rbac,
+    #[serde(default)]
+    pub sets: Sets,
+    #[serde(default)]
+    pub tags: Tags,
+    #[serde(default)]
+    pub runtime: Runtime,
+}
+
+/// Threads of the async runtime serving the API, the tokio defaults being
+/// kept for unset values
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Runtime {
+    /// Worker threads, one per CPU core by default
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    /// Threads running blocking tasks, such as file reads, 512 by default
+    #[serde(default)]
+    pub max_blocking_threads: Option<usize>,
+}
+
+/// Checks applied to the set files before they are loaded
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Sets {
+    /// Ed25519 public key (PEM) the set files must be signed with. Files whose
+    /// `.sig` is missing or does not match are skipped on reload.
+    #[serde(default)]
+    pub public_key: Option<String>,
+    /// Refuse to create a set named like a set of another policy
+    #[serde(default)]
+    pub unique_names: bool,
+    /// Maximum number of sets per policy the API may create, each reload
+    /// reading all the set files
+    #[serde(default = "default_max_sets")]
+    pub max_sets: usize,
+    #[serde(default)]
+    pub watch: SetsWatch,
+}
+
+impl Default for Sets {
+    fn default() -> Self {
+        Self {
+            public_key: None,
+            unique_names: false,
+            max_sets: default_max_sets(),
+            watch: SetsWatch::default(),
+        }
+    }
+}
+
+/// Reload of the sets when their files change, for files written by an
+/// external sync
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct SetsWatch {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Milliseconds without change awaited before reloading, so a sync
+    /// writing many files triggers a single reload
+    #[serde(default = "default_watch_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+impl Default for SetsWatch {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            debounce_ms: default_watch_debounce_ms(),
+        }
+    }
+}
+
+/// Conventions the tags of the entries created through the API must follow
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+    /// Patterns, as accepted by the tag filters (e.g. `team/*`), one of which
+    /// the tag of a new entry must match. Any tag is accepted when empty, and
+    /// the tags of the entries of sets are never checked.
+    #[serde(default)]
+    pub allowed_patterns: Vec<String>,
+}
+
+/// Limits applied to the API clients on top of their group permissions
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Rbac {
+    #[serde(default)]
+    pub quotas: Quotas,
+}
+
+/// Maximum number of entries a client may create per day (UTC). The limit of
+/// a client takes precedence over the one of its group, then over `default`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Quotas {
+    /// Limit of the clients without a more specific one, except admins
+    #[serde(default)]
+    pub default: Option<u32>,
+    /// Limit per group
+    #[serde(default)]
+    pub groups: HashMap<Group, u32>,
+    /// Limit per client name
+    #[serde(default)]
+    pub clients: HashMap<String, u32>,
+}
+
+/// ICMP types always passed by XDP, even from dropped sources
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct IcmpExceptions {
+    #[serde(default)]
+    pub ipv4: Vec<u8>,
+    #[serde(default)]
+    pub ipv6: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Firewall {
+    #[serde(default)]
+    pub cleanup: Cleanup,
+    /// Keep tag IDs, and so per-tag stats, stable across restarts
+    #[serde(default)]
+    pub persist_tags: bool,
+    /// Ranges no drop entry may overlap unless forced
+    #[serde(default = "default_protected_cidrs")]
+    pub protected_cidrs: Vec<IpNet>,
+    #[serde(default)]
+    pub min_prefix: MinPrefix,
+    /// Number of expired entries recalled by `GET /v1/recently-expired`, 0
+    /// to recall none
+    #[serde(default = "default_recently_expired")]
+    pub recently_expired: usize,
+    /// What XDP does with the packets matched by a drop entry
+    #[serde(default)]
+    pub drop_action: DropAction,
+    /// Ranges Couic is managed from, which must be ignored before lockdown
+    /// may be enabled
+    #[serde(default)]
+    pub management_cidrs: Vec<IpNet>,
+}
+
+/// Verdict of the packets matched by a drop entry, either `"drop"` or
+/// `{ redirect = "<iface>" }`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DropAction {
+    #[default]
+    Drop,
+    /// Send them out of this interface instead, e.g. towards a host capturing
+    /// the blocked traffic
+    Redirect(String),
+}
+
+impl Default for Firewall {
+    fn default() -> Self {
+        Self {
+            cleanup: Cleanup::default(),
+            persist_tags: false,
+            protected_cidrs: default_protected_cidrs(),
+            min_prefix: MinPrefix::default(),
+            recently_expired: default_recently_expired(),
+            drop_action: DropAction::default(),
+            management_cidrs: Vec::new(),
+        }
+    }
+}
+
+/// Shortest prefix length a drop entry may have unless forced, per family
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct MinPrefix {
+    #[serde(default = "default_min_prefix_v4")]
+    pub v4: u8,
+    #[serde(default = "default_min_prefix_v6")]
+    pub v6: u8,
+}
+
+impl Default for MinPrefix {
+    fn default() -> Self {
+        Self {
+            v4: default_min_prefix_v4(),
+            v6: default_min_prefix_v6(),
+        }
+    }
+}
+
+/// Cadence of the thread removing expired entries from the LPM stores
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Cleanup {
+    /// Seconds between two cleanup passes
+    #[serde(default = "default_cleanup_interval_secs")]
+    pub interval_secs: u64,
+    /// Number of cleanup passes between two shrinks of the entry tables
+    #[serde(default = "default_shrink_interval_cycles")]
+    pub shrink_interval_cycles: u32,
+}
+
+impl Default for Cleanup {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_cleanup_interval_secs(),
+            shrink_interval_cycles: default_shrink_interval_cycles(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Server {
     pub socket: String,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default)]
+    pub local_trust: LocalTrust,
+    /// Largest request body accepted in bytes, leaving room for the JSON
+    /// encoding of a set file of `MAX_SET_FILE_SIZE`
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+}
+
+/// RBAC groups granted without a token to processes calling the API socket,
+/// from the credentials of their connection. Empty maps keep token auth only.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct LocalTrust {
+    /// Group granted per user name of the calling process
+    #[serde(default)]
+    pub users: HashMap<String, Group>,
+    /// Group granted per primary group name of the calling process
+    #[serde(default)]
+    pub groups: HashMap<String, Group>,
+}
+
+/// Compression of API responses, negotiated with `Accept-Encoding`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    #[serde(default = "default_compression_enabled")]
+    pub enabled: bool,
+    /// Responses up to this size in bytes are sent uncompressed
+    #[serde(default = "default_compression_min_size")]
+    pub min_size: u16,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            enabled: default_compression_enabled(),
+            min_size: default_compression_min_size(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -79,6 +406,13 @@ pub struct Logging {
     pub max_log_files: usize,
     #[serde(default)]
     pub format: LogFormat,
+    /// Send logs to the systemd journal with their level as priority,
+    /// instead of stdout
+    #[serde(default)]
+    pub journald: bool,
+    /// Interval between XDP traffic summaries in the logs (0 = disabled)
+    #[serde(default = "default_stats_interval_secs")]
+    pub stats_interval_secs: u64,
 }
 
 impl Default for Logging {
@@ -88,6 +422,8 @@ impl Default for Logging {
             rotation: LogRotation::default(),
             max_log_files: default_max_log_files(),
             format: LogFormat::default(),
+            journald: false,
+            stats_interval_secs: default_stats_interval_secs(),
         }
     }
 }
@@ -96,6 +432,15 @@ impl Default for Logging {
 pub struct Peering {
     pub enabled: bool,
     pub peers: Vec<Peer>,
+    /// Most jobs sent to a peer in one request
+    #[serde(default = "default_peering_batch_size")]
+    pub batch_size: usize,
+    /// Longest time a job waits for others to be sent along, in milliseconds
+    #[serde(default = "default_peering_batch_max_age_ms")]
+    pub batch_max_age_ms: u64,
+    /// Gzip the bodies of the requests to peers
+    #[serde(default)]
+    pub compression: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -106,10 +451,62 @@ pub struct Peer {
     pub token: Uuid,
 }
 
+/// Unauthenticated HTTP listener serving only Prometheus metrics
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    #[serde(default = "default_metrics_addr")]
+    pub addr: IpAddr,
+    #[serde(default = "default_metrics_port")]
+    pub port: u16,
+    /// Required to bind a non-loopback address, metrics being served without auth
+    #[serde(default)]
+    pub allow_non_loopback: bool,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            addr: default_metrics_addr(),
+            port: default_metrics_port(),
+            allow_non_loopback: false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Reporting {
     pub enabled: bool,
-    pub webhook: String,
+    /// Single target, same as a one-element `targets`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+    /// Targets receiving every report, each with its own queue and retries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<ReportingTarget>,
+    /// Maximum number of pending reports, the oldest being dropped beyond it
+    #[serde(default = "default_reporting_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+impl Reporting {
+    /// Configured targets, `webhook` first
+    pub fn all_targets(&self) -> Vec<ReportingTarget> {
+        self.webhook
+            .iter()
+            .map(|url| ReportingTarget {
+                url: url.clone(),
+                secret: None,
+            })
+            .chain(self.targets.iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReportingTarget {
+    pub url: String,
+    /// Sent as a bearer token in the `Authorization` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -122,15 +519,308 @@ pub enum ConfigError {
     Security(#[from] crate::security::SecurityError),
     #[error("Failed to setup logging: {0}")]
     LoggingSetup(tracing_appender::rolling::InitError),
+    #[error("Failed to connect to journald: {0}")]
+    Journald(std::io::Error),
+    #[error("Invalid value for environment variable {var}: {message}")]
+    Env { var: &'static str, message: String },
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Validation(Vec<String>),
+}
+
+fn parse_env<T: for<'de> Deserialize<'de>>(
+    var: &'static str,
+    value: &str,
+) -> Result<T, ConfigError> {
+    T::deserialize(value.into_deserializer()).map_err(|e: serde::de::value::Error| {
+        ConfigError::Env {
+            var,
+            message: e.to_string(),
+        }
+    })
+}
+
+fn parse_env_number<T: std::str::FromStr>(var: &'static str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| ConfigError::Env {
+        var,
+        message: e.to_string(),
+    })
 }
 
 impl Config {
+    /// Load the configuration file, then apply `COUIC_*` environment overrides.
     pub fn new(path: &str) -> Result<Self, ConfigError> {
         let config_file = fs::read_to_string(path)?;
-        let cfg: Self = toml::from_str(&config_file)?;
+        let mut cfg: Self = toml::from_str(&config_file)?;
+        cfg.apply_env(|var| std::env::var(var).ok())?;
         Ok(cfg)
     }
 
+    /// Override configuration values from environment variables, which take
+    /// precedence over the configuration file.
+    ///
+    /// Variables are named `COUIC_<KEY>` for top-level keys and
+    /// `COUIC_<SECTION>_<KEY>` for keys in a section, e.g. `COUIC_SERVER_SOCKET`.
+    pub fn apply_env<F>(&mut self, lookup: F) -> Result<(), ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(value) = lookup("COUIC_IFACES") {
+            let ifaces: Vec<String> = value
+                .split(',')
+                .map(str::trim)
+                .filter(|iface| !iface.is_empty())
+                .map(String::from)
+                .collect();
+            if ifaces.is_empty() {
+                return Err(ConfigError::Env {
+                    var: "COUIC_IFACES",
+                    message: "at least one interface is required".to_string(),
+                });
+            }
+            self.ifaces = ifaces;
+        }
+        if let Some(value) = lookup("COUIC_OPERATION_MODE") {
+            self.operation_mode = parse_env("COUIC_OPERATION_MODE", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_HOTPLUG") {
+            self.hotplug = parse_env_number("COUIC_HOTPLUG", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_WORKING_DIR") {
+            self.working_dir = value;
+        }
+        if let Some(value) = lookup("COUIC_USER") {
+            self.user = value;
+        }
+        if let Some(value) = lookup("COUIC_GROUP") {
+            self.group = value;
+        }
+        if let Some(value) = lookup("COUIC_LOGGING_DIR") {
+            self.logging.dir = value;
+        }
+        if let Some(value) = lookup("COUIC_LOGGING_ROTATION") {
+            self.logging.rotation = parse_env("COUIC_LOGGING_ROTATION", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_LOGGING_MAX_LOG_FILES") {
+            self.logging.max_log_files = parse_env_number("COUIC_LOGGING_MAX_LOG_FILES", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_LOGGING_FORMAT") {
+            self.logging.format = parse_env("COUIC_LOGGING_FORMAT", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_LOGGING_JOURNALD") {
+            self.logging.journald = parse_env_number("COUIC_LOGGING_JOURNALD", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_LOGGING_STATS_INTERVAL_SECS") {
+            self.logging.stats_interval_secs =
+                parse_env_number("COUIC_LOGGING_STATS_INTERVAL_SECS", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_SERVER_SOCKET") {
+            self.server.socket = value;
+        }
+        if let Some(value) = lookup("COUIC_FIREWALL_CLEANUP_INTERVAL_SECS") {
+            self.firewall.cleanup.interval_secs =
+                parse_env_number("COUIC_FIREWALL_CLEANUP_INTERVAL_SECS", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_FIREWALL_CLEANUP_SHRINK_INTERVAL_CYCLES") {
+            self.firewall.cleanup.shrink_interval_cycles =
+                parse_env_number("COUIC_FIREWALL_CLEANUP_SHRINK_INTERVAL_CYCLES", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_FIREWALL_PERSIST_TAGS") {
+            self.firewall.persist_tags = parse_env_number("COUIC_FIREWALL_PERSIST_TAGS", &value)?;
+        }
+        if let Some(value) = lookup("COUIC_RUNTIME_WORKER_THREADS") {
+            self.runtime.worker_threads =
+                Some(parse_env_number("COUIC_RUNTIME_WORKER_THREADS", &value)?);
+        }
+        if let Some(value) = lookup("COUIC_RUNTIME_MAX_BLOCKING_THREADS") {
+            self.runtime.max_blocking_threads = Some(parse_env_number(
+                "COUIC_RUNTIME_MAX_BLOCKING_THREADS",
+                &value,
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Check configuration semantics and report every problem found at once
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.ifaces.is_empty() {
+            problems.push("ifaces: at least one interface is required".to_string());
+        }
+
+        if !Path::new(&self.working_dir).is_absolute() {
+            problems.push(format!(
+                "working_dir: '{}' must be an absolute path",
+                self.working_dir
+            ));
+        }
+
+        match Path::new(&self.server.socket).parent() {
+            Some(parent) if parent.as_os_str().is_empty() || parent.is_dir() => {}
+            Some(parent) => problems.push(format!(
+                "server.socket: parent directory '{}' does not exist",
+                parent.display()
+            )),
+            None => problems.push(format!(
+                "server.socket: '{}' is not a valid socket path",
+                self.server.socket
+            )),
+        }
+
+        if self.server.max_body_size == 0 {
+            problems.push("server.max_body_size: must be greater than 0".to_string());
+        }
+
+        if let Some(peering) = &self.peering {
+            let mut seen = std::collections::HashSet::new();
+            for peer in &peering.peers {
+                let address = format!("{}:{}", peer.host, peer.port);
+                if !seen.insert(address.clone()) {
+                    problems.push(format!("peering.peers: duplicate peer '{address}'"));
+                }
+            }
+            if peering.batch_size == 0 {
+                problems.push("peering.batch_size: must be greater than 0".to_string());
+            }
+            if peering.batch_max_age_ms == 0 {
+                problems.push("peering.batch_max_age_ms: must be greater than 0".to_string());
+            }
+        }
+
+        if let Some(reporting) = &self.reporting {
+            let targets = reporting.all_targets();
+            if targets.is_empty() {
+                problems
+                    .push("reporting: at least one of webhook or targets must be set".to_string());
+            }
+            let mut seen = std::collections::HashSet::new();
+            for (index, target) in targets.iter().enumerate() {
+                // Index into `targets`, the webhook coming first
+                let field = match (&reporting.webhook, index) {
+                    (Some(_), 0) => "reporting.webhook".to_string(),
+                    (Some(_), i) => format!("reporting.targets[{}].url", i.saturating_sub(1)),
+                    (None, i) => format!("reporting.targets[{i}].url"),
+                };
+                match reqwest::Url::parse(&target.url) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                    Ok(url) => problems.push(format!(
+                        "{field}: unsupported scheme '{}', expected http or https",
+                        url.scheme()
+                    )),
+                    Err(e) => {
+                        problems.push(format!("{field}: '{}' is not a valid URL: {e}", target.url))
+                    }
+                }
+                if !seen.insert(target.url.as_str()) {
+                    problems.push(format!("{field}: duplicate target '{}'", target.url));
+                }
+                if let Some(secret) = &target.secret
+                    && (secret.is_empty() || !secret.bytes().all(|b| b.is_ascii_graphic()))
+                {
+                    problems.push(format!(
+                        "{}: must be non-empty printable ASCII without spaces",
+                        field.replace(".url", ".secret")
+                    ));
+                }
+            }
+            if reporting.queue_capacity == 0 {
+                problems.push("reporting.queue_capacity: must be greater than 0".to_string());
+            }
+        }
+
+        for name in self.rbac.quotas.clients.keys() {
+            if let Err(e) = ClientName::try_from(name.as_str()) {
+                problems.push(format!(
+                    "rbac.quotas.clients: invalid client name '{name}': {e}"
+                ));
+            }
+        }
+
+        if let Some(metrics) = &self.metrics
+            && !metrics.addr.is_loopback()
+            && !metrics.allow_non_loopback
+        {
+            problems.push(format!(
+                "metrics.addr: '{}' is not a loopback address, set allow_non_loopback = true to serve unauthenticated metrics on it",
+                metrics.addr
+            ));
+        }
+
+        if let Some(public_key) = &self.sets.public_key
+            && !Path::new(public_key).is_absolute()
+        {
+            problems.push(format!(
+                "sets.public_key: '{public_key}' must be an absolute path"
+            ));
+        }
+
+        if self
+            .tags
+            .allowed_patterns
+            .iter()
+            .any(|pattern| pattern.is_empty())
+        {
+            problems.push("tags.allowed_patterns: patterns must not be empty".to_string());
+        }
+
+        if self.sets.max_sets == 0 {
+            problems.push("sets.max_sets: must be greater than 0".to_string());
+        }
+
+        if self.sets.watch.debounce_ms == 0 {
+            problems.push("sets.watch.debounce_ms: must be greater than 0".to_string());
+        }
+
+        if self.firewall.cleanup.interval_secs == 0 {
+            problems.push("firewall.cleanup.interval_secs: must be greater than 0".to_string());
+        }
+
+        if self.firewall.cleanup.shrink_interval_cycles == 0 {
+            problems.push(
+                "firewall.cleanup.shrink_interval_cycles: must be greater than 0".to_string(),
+            );
+        }
+
+        if self.firewall.min_prefix.v4 > 32 {
+            problems.push("firewall.min_prefix.v4: must be between 0 and 32".to_string());
+        }
+
+        if self.firewall.min_prefix.v6 > 128 {
+            problems.push("firewall.min_prefix.v6: must be between 0 and 128".to_string());
+        }
+
+        if self.runtime.worker_threads == Some(0) {
+            problems.push("runtime.worker_threads: must be greater than 0".to_string());
+        }
+
+        if self.runtime.max_blocking_threads == Some(0) {
+            problems.push("runtime.max_blocking_threads: must be greater than 0".to_string());
+        }
+
+        if let DropAction::Redirect(iface) = &self.firewall.drop_action {
+            if iface.is_empty() || iface.contains(['*', '?']) {
+                problems.push(format!(
+                    "firewall.drop_action: '{iface}' must be the name of an interface"
+                ));
+            }
+            if self.operation_mode == OperationMode::Offloaded {
+                problems.push(
+                    "firewall.drop_action: redirecting is not supported in offloaded mode"
+                        .to_string(),
+                );
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Validation(problems))
+        }
+    }
+
     pub fn init_working_dir(&self) -> Result<(), ConfigError> {
         let working_dir = Path::new(&self.working_dir);
 
@@ -146,7 +836,14 @@ impl Config {
         }
 
         // Create subdirectories
-        let subdirs = ["rbac", "sets", "rbac/clients", "sets/ignore", "sets/drop"];
+        let subdirs = [
+            "rbac",
+            "sets",
+            "rbac/clients",
+            "sets/ignore",
+            "sets/drop",
+            "sets/monitor",
+        ];
         for subdir in subdirs {
             let dir_path = working_dir.join(subdir);
             if dir_path.exists() {
@@ -171,9 +868,10 @@ impl Config {
         Ok(())
     }
 
-    pub fn init_logger(&self) -> Result<(WorkerGuard, WorkerGuard), ConfigError> {
-        let env_filter =
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    pub fn init_logger(&self) -> Result<(LogLevelHandle, WorkerGuard, WorkerGuard), ConfigError> {
+        let (env_filter, log_level) = LogLevelHandle::new(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        );
 
         let (stdout_nb, stdout_guard) = tracing_appender::non_blocking(std::io::stdout());
 
@@ -186,31 +884,95 @@ impl Config {
             .map_err(ConfigError::LoggingSetup)?;
         let (file_nb, file_guard) = tracing_appender::non_blocking(file_appender);
 
+        // Under systemd, stdout would reach the journal a second time,
+        // without priorities
+        let (stdout_layer, journald_layer) = if self.logging.journald {
+            let journald = tracing_journald::layer()
+                .map_err(ConfigError::Journald)?
+                .with_syslog_identifier("couic".to_string());
+            (None, Some(journald))
+        } else {
+            let stdout_ansi = use_ansi(
+                std::io::stdout().is_terminal(),
+                std::env::var_os("NO_COLOR").as_deref(),
+            );
+            let stdout = tracing_fmt::layer()
+                .with_ansi(stdout_ansi)
+                .with_writer(stdout_nb);
+            (Some(stdout), None)
+        };
         let registry = tracing_subscriber::registry()
             .with(env_filter)
-            .with(tracing_fmt::layer().with_writer(stdout_nb));
+            .with(stdout_layer)
+            .with(journald_layer);
 
+        // Log files never hold escape codes
         match self.logging.format {
             LogFormat::Text => {
                 registry
-                    .with(tracing_fmt::layer().with_writer(file_nb))
+                    .with(tracing_fmt::layer().with_ansi(false).with_writer(file_nb))
                     .init();
             }
             LogFormat::Json => {
                 registry
-                    .with(tracing_fmt::layer().json().with_writer(file_nb))
+                    .with(
+                        tracing_fmt::layer()
+                            .json()
+                            .with_ansi(false)
+                            .with_writer(file_nb),
+                    )
                     .init();
             }
         }
 
-        Ok((stdout_guard, file_guard))
+        Ok((log_level, stdout_guard, file_guard))
     }
 }
 
+/// Changes the log filter of the running daemon, without a restart that would
+/// detach XDP
+#[derive(Clone)]
+pub struct LogLevelHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives of the startup filter, restored by `reset`
+    initial: String,
+}
+
+impl LogLevelHandle {
+    /// Wraps `filter` in a layer it can reload
+    pub fn new(filter: EnvFilter) -> (reload::Layer<EnvFilter, Registry>, Self) {
+        let initial = filter.to_string();
+        let (layer, handle) = reload::Layer::new(filter);
+        (layer, Self { handle, initial })
+    }
+
+    /// Directives of the filter in effect, e.g. `info`
+    pub fn current(&self) -> Result<String, reload::Error> {
+        self.handle.with_current(ToString::to_string)
+    }
+
+    pub fn set(&self, level: LogLevel) -> Result<(), reload::Error> {
+        self.handle.reload(EnvFilter::new(level.to_string()))
+    }
+
+    /// Restores the startup filter, returning its directives
+    pub fn reset(&self) -> Result<String, reload::Error> {
+        self.handle.reload(EnvFilter::new(&self.initial))?;
+        Ok(self.initial.clone())
+    }
+}
+
+/// Whether stdout logs are colored: only on a terminal, unless `NO_COLOR` is
+/// set to a non-empty value
+fn use_ansi(is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+    is_terminal && no_color.is_none_or(OsStr::is_empty)
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             ifaces: vec!["lo".to_string()],
+            hotplug: false,
             operation_mode: OperationMode::default(),
             working_dir: "/tmp".to_string(),
             user: "test".to_string(),
@@ -218,9 +980,19 @@ impl Default for Config {
             logging: Logging::default(),
             server: Server {
                 socket: "/tmp/couic.sock".to_string(),
+                compression: Compression::default(),
+                local_trust: LocalTrust::default(),
+                max_body_size: default_max_body_size(),
             },
             reporting: None,
             peering: None,
+            metrics: None,
+            icmp_exceptions: IcmpExceptions::default(),
+            firewall: Firewall::default(),
+            rbac: Rbac::default(),
+            sets: Sets::default(),
+            tags: Tags::default(),
+            runtime: Runtime::default(),
         }
     }
 }
@@ -326,6 +1098,9 @@ token = "f657b53a-610e-4a5d-ae76-f1722d2854ac"
         let peering = config.peering.unwrap();
         assert!(peering.enabled);
         assert_eq!(peering.peers.len(), 2);
+        assert_eq!(peering.batch_size, 1000);
+        assert_eq!(peering.batch_max_age_ms, 200);
+        assert!(!peering.compression);
 
         assert_eq!(peering.peers[0].host, "peer1.example.com");
         assert_eq!(peering.peers[0].port, 8080);
@@ -425,6 +1200,7 @@ peers = []
     fn test_comprehensive_config_roundtrip() {
         let original_config = Config {
             ifaces: vec!["eth0".to_string(), "eth1".to_string()],
+            hotplug: false,
             operation_mode: OperationMode::default(),
             working_dir: "/var/lib/couic".to_string(),
             user: "couic".to_string(),
@@ -435,6 +1211,9 @@ peers = []
             },
             server: Server {
                 socket: "/var/run/couic.sock".to_string(),
+                compression: Compression::default(),
+                local_trust: LocalTrust::default(),
+                max_body_size: default_max_body_size(),
             },
             peering: Some(Peering {
                 enabled: true,
@@ -444,11 +1223,59 @@ peers = []
                     tls: true,
                     token: uuid::Uuid::parse_str("f657b53a-610e-4a5d-ae76-f1722d2854ac").unwrap(),
                 }],
+                batch_size: default_peering_batch_size(),
+                batch_max_age_ms: default_peering_batch_max_age_ms(),
+                compression: false,
             }),
             reporting: Some(Reporting {
                 enabled: true,
-                webhook: "http://example.com/webhook".to_string(),
+                webhook: Some("http://example.com/webhook".to_string()),
+                targets: vec![],
+                queue_capacity: default_reporting_queue_capacity(),
             }),
+            metrics: Some(Metrics {
+                port: 9100,
+                ..Metrics::default()
+            }),
+            icmp_exceptions: IcmpExceptions {
+                ipv4: vec![3],
+                ipv6: vec![2],
+            },
+            firewall: Firewall {
+                cleanup: Cleanup {
+                    interval_secs: 5,
+                    shrink_interval_cycles: 720,
+                },
+                persist_tags: true,
+                protected_cidrs: vec!["10.0.0.0/8".parse().unwrap()],
+                min_prefix: MinPrefix { v4: 16, v6: 48 },
+                recently_expired: 64,
+                drop_action: DropAction::Redirect("mon0".to_string()),
+                management_cidrs: vec!["192.0.2.0/24".parse().unwrap()],
+            },
+            rbac: Rbac {
+                quotas: Quotas {
+                    default: Some(1000),
+                    groups: HashMap::from([(Group::ClientRw, 5000)]),
+                    clients: HashMap::from([("fail2ban".to_string(), 200)]),
+                },
+            },
+            sets: Sets {
+                public_key: Some("/etc/couic/sets.pub".to_string()),
+                unique_names: true,
+                max_sets: 200,
+                watch: SetsWatch {
+                    enabled: true,
+                    debounce_ms: 500,
+                },
+            },
+            tags: Tags {
+                allowed_patterns: vec!["team/*".to_string(), "fail2ban".to_string()],
+            },
+            runtime: Runtime {
+                worker_threads: Some(2),
+                max_blocking_threads: None,
+            },
         };
 
         // Serialize to TOML
@@ -467,6 +1294,15 @@ peers = []
         assert_eq!(original_config.group, loaded_config.group);
         assert_eq!(original_config.logging.dir, loaded_config.logging.dir);
         assert_eq!(original_config.server.socket, loaded_config.server.socket);
+        assert_eq!(
+            original_config.icmp_exceptions,
+            loaded_config.icmp_exceptions
+        );
+        assert_eq!(original_config.firewall, loaded_config.firewall);
+        assert_eq!(original_config.metrics, loaded_config.metrics);
+        assert_eq!(original_config.rbac, loaded_config.rbac);
+        assert_eq!(original_config.sets, loaded_config.sets);
+        assert_eq!(original_config.runtime, loaded_config.runtime);
 
         assert!(loaded_config.peering.is_some());
         let loaded_peering = loaded_config.peering.unwrap();
@@ -504,6 +1340,8 @@ peers = []
         assert_eq!(logging.rotation, LogRotation::Daily);
         assert_eq!(logging.max_log_files, 7);
         assert_eq!(logging.format, LogFormat::Text);
+        assert!(!logging.journald);
+        assert_eq!(logging.stats_interval_secs, 0);
     }
 
     #[test]
@@ -539,6 +1377,7 @@ socket = "/var/run/couic.sock"
         assert_eq!(config.logging.rotation, LogRotation::Daily);
         assert_eq!(config.logging.max_log_files, 7);
         assert_eq!(config.logging.format, LogFormat::Text);
+        assert_eq!(config.logging.stats_interval_secs, 0);
     }
 
     #[test]
@@ -554,6 +1393,7 @@ dir = "/var/log/couic"
 rotation = "weekly"
 max_log_files = 24
 format = "text"
+stats_interval_secs = 300
 
 [server]
 socket = "/var/run/couic.sock"
@@ -568,6 +1408,7 @@ socket = "/var/run/couic.sock"
         assert_eq!(config.logging.rotation, LogRotation::Weekly);
         assert_eq!(config.logging.max_log_files, 24);
         assert_eq!(config.logging.format, LogFormat::Text);
+        assert_eq!(config.logging.stats_interval_secs, 300);
     }
 
     #[test]
@@ -577,6 +1418,8 @@ socket = "/var/run/couic.sock"
             rotation: LogRotation::Weekly,
             max_log_files: 10,
             format: LogFormat::Text,
+            journald: true,
+            stats_interval_secs: 60,
         };
 
         let toml_string = toml::to_string(&logging).unwrap();
@@ -586,6 +1429,11 @@ socket = "/var/run/couic.sock"
         assert_eq!(logging.rotation, deserialized.rotation);
         assert_eq!(logging.max_log_files, deserialized.max_log_files);
         assert_eq!(logging.format, deserialized.format);
+        assert_eq!(logging.journald, deserialized.journald);
+        assert_eq!(
+            logging.stats_interval_secs,
+            deserialized.stats_interval_secs
+        );
     }
 
     #[test]
@@ -707,4 +1555,571 @@ socket = "/tmp/couic.sock"
         let config = Config::new(temp_file.path().to_str().unwrap()).unwrap();
         assert_eq!(config.operation_mode, OperationMode::Generic);
     }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let mut config = Config::default();
+        config
+            .apply_env(|var| match var {
+                "COUIC_IFACES" => Some("eth0, eth1".to_string()),
+                "COUIC_OPERATION_MODE" => Some("native".to_string()),
+                "COUIC_HOTPLUG" => Some("true".to_string()),
+                "COUIC_WORKING_DIR" => Some("/var/lib/couic".to_string()),
+                "COUIC_SERVER_SOCKET" => Some("/run/couic.sock".to_string()),
+                "COUIC_LOGGING_FORMAT" => Some("json".to_string()),
+                "COUIC_LOGGING_MAX_LOG_FILES" => Some("14".to_string()),
+                "COUIC_LOGGING_JOURNALD" => Some("true".to_string()),
+                "COUIC_FIREWALL_CLEANUP_INTERVAL_SECS" => Some("5".to_string()),
+                "COUIC_FIREWALL_PERSIST_TAGS" => Some("true".to_string()),
+                "COUIC_RUNTIME_WORKER_THREADS" => Some("2".to_string()),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(config.ifaces, vec!["eth0", "eth1"]);
+        assert_eq!(config.operation_mode, OperationMode::Native);
+        assert!(config.hotplug);
+        assert_eq!(config.working_dir, "/var/lib/couic");
+        assert_eq!(config.server.socket, "/run/couic.sock");
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(config.logging.max_log_files, 14);
+        assert!(config.logging.journald);
+        assert_eq!(config.firewall.cleanup.interval_secs, 5);
+        assert!(config.firewall.persist_tags);
+        assert_eq!(config.runtime.worker_threads, Some(2));
+        // Untouched values keep the file configuration
+        assert_eq!(config.user, "test");
+        assert_eq!(config.logging.rotation, LogRotation::Daily);
+    }
+
+    #[test]
+    fn test_apply_env_invalid_values() {
+        let invalid = [
+            ("COUIC_OPERATION_MODE", "turbo"),
+            ("COUIC_LOGGING_MAX_LOG_FILES", "many"),
+            ("COUIC_IFACES", " , "),
+            ("COUIC_RUNTIME_WORKER_THREADS", "-1"),
+        ];
+        for (name, value) in invalid {
+            let mut config = Config::default();
+            let err = config
+                .apply_env(|var| (var == name).then(|| value.to_string()))
+                .unwrap_err();
+            assert!(matches!(err, ConfigError::Env { var, .. } if var == name));
+            assert!(err.to_string().contains(name));
+        }
+    }
+
+    #[test]
+    fn test_validate_default_config() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_all_problems() {
+        let peer = Peer {
+            host: "10.0.0.1".to_string(),
+            port: 8080,
+            tls: false,
+            token: Uuid::nil(),
+        };
+        let config = Config {
+            ifaces: vec![],
+            working_dir: "relative/dir".to_string(),
+            server: Server {
+                socket: "/nonexistent/couic/couic.sock".to_string(),
+                compression: Compression::default(),
+                local_trust: LocalTrust::default(),
+                max_body_size: default_max_body_size(),
+            },
+            peering: Some(Peering {
+                enabled: true,
+                peers: vec![peer.clone(), peer],
+                batch_size: default_peering_batch_size(),
+                batch_max_age_ms: default_peering_batch_max_age_ms(),
+                compression: false,
+            }),
+            reporting: Some(Reporting {
+                enabled: true,
+                webhook: Some("not a url".to_string()),
+                targets: vec![],
+                queue_capacity: default_reporting_queue_capacity(),
+            }),
+            ..Config::default()
+        };
+
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(problems.len(), 5);
+        assert!(problems[0].starts_with("ifaces"));
+        assert!(problems[1].starts_with("working_dir"));
+        assert!(problems[2].starts_with("server.socket"));
+        assert!(problems[3].contains("10.0.0.1:8080"));
+        assert!(problems[4].starts_with("reporting.webhook"));
+    }
+
+    #[test]
+    fn test_validate_sets_public_key_path() {
+        let config = Config {
+            sets: Sets {
+                public_key: Some("sets.pub".to_string()),
+                ..Sets::default()
+            },
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("sets.public_key"));
+    }
+
+    #[test]
+    fn test_validate_sets_max_sets() {
+        let config = Config {
+            sets: Sets {
+                max_sets: 0,
+                ..Sets::default()
+            },
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("sets.max_sets"));
+    }
+
+    #[test]
+    fn test_validate_sets_watch_debounce() {
+        let config = Config {
+            sets: Sets {
+                watch: SetsWatch {
+                    enabled: true,
+                    debounce_ms: 0,
+                },
+                ..Sets::default()
+            },
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("sets.watch.debounce_ms"));
+        assert_eq!(Config::default().sets.watch.debounce_ms, 1000);
+    }
+
+    #[test]
+    fn test_validate_webhook_scheme() {
+        let config = Config {
+            reporting: Some(Reporting {
+                enabled: true,
+                webhook: Some("ftp://example.org/hook".to_string()),
+                targets: vec![],
+                queue_capacity: default_reporting_queue_capacity(),
+            }),
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("unsupported scheme 'ftp'"));
+    }
+
+    #[test]
+    fn test_reporting_targets_include_webhook() {
+        let reporting: Reporting = toml::from_str(
+            r#"
+            enabled = true
+            webhook = "http://127.0.0.1:8000/v1/reports/550e8400-e29b-41d4-a716-446655440000"
+
+            [[targets]]
+            url = "https://collector.example.org/reports"
+            secret = "s3cr3t"
+            "#,
+        )
+        .unwrap();
+        let targets = reporting.all_targets();
+        assert_eq!(targets.len(), 2);
+        assert!(targets[0].url.starts_with("http://127.0.0.1:8000"));
+        assert_eq!(targets[0].secret, None);
+        assert_eq!(targets[1].secret.as_deref(), Some("s3cr3t"));
+
+        let config = Config {
+            reporting: Some(Reporting {
+                enabled: true,
+                webhook: None,
+                targets: vec![
+                    ReportingTarget {
+                        url: "https://collector.example.org/reports".to_string(),
+                        secret: Some("two words".to_string()),
+                    },
+                    ReportingTarget {
+                        url: "https://collector.example.org/reports".to_string(),
+                        secret: None,
+                    },
+                ],
+                queue_capacity: default_reporting_queue_capacity(),
+            }),
+            ..Config::default()
+        };
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            problems,
+            vec![
+                "reporting.targets[0].secret: must be non-empty printable ASCII without spaces"
+                    .to_string(),
+                "reporting.targets[1].url: duplicate target 'https://collector.example.org/reports'"
+                    .to_string(),
+            ]
+        );
+
+        let config = Config {
+            reporting: Some(Reporting {
+                webhook: None,
+                ..reporting
+            }),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reporting_queue_capacity() {
+        let config = Config {
+            reporting: Some(Reporting {
+                enabled: true,
+                webhook: Some("https://example.org/hook".to_string()),
+                targets: vec![],
+                queue_capacity: 0,
+            }),
+            ..Config::default()
+        };
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.starts_with("reporting.queue_capacity"))
+        );
+    }
+
+    #[test]
+    fn test_icmp_exceptions() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+
+        let config_content = r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[icmp_exceptions]
+ipv4 = [3, 11]
+ipv6 = [2]
+"#;
+
+        temp_file.write_all(config_content.as_bytes()).unwrap();
+
+        let config = Config::new(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.icmp_exceptions.ipv4, vec![3, 11]);
+        assert_eq!(config.icmp_exceptions.ipv6, vec![2]);
+    }
+
+    #[test]
+    fn test_icmp_exceptions_default_empty() {
+        let config: Config = toml::from_str(&toml::to_string(&Config::default()).unwrap()).unwrap();
+        assert_eq!(config.icmp_exceptions, IcmpExceptions::default());
+    }
+
+    #[test]
+    fn test_firewall_cleanup_defaults() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+
+        let config_content = r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[firewall.cleanup]
+interval_secs = 10
+"#;
+
+        temp_file.write_all(config_content.as_bytes()).unwrap();
+
+        let config = Config::new(temp_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(config.firewall.cleanup.interval_secs, 10);
+        assert_eq!(config.firewall.cleanup.shrink_interval_cycles, 3600);
+        assert_eq!(Config::default().firewall.cleanup.interval_secs, 1);
+        // Protected ranges default to loopback and link-local
+        assert_eq!(config.firewall.protected_cidrs.len(), 4);
+        assert_eq!(
+            config.firewall.protected_cidrs,
+            Config::default().firewall.protected_cidrs
+        );
+        assert_eq!(config.firewall.min_prefix, MinPrefix { v4: 8, v6: 32 });
+    }
+
+    #[test]
+    fn test_firewall_protected_cidrs_override() {
+        let config: Config = toml::from_str(
+            r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[firewall]
+protected_cidrs = ["192.0.2.0/24"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.firewall.protected_cidrs,
+            vec!["192.0.2.0/24".parse::<IpNet>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_zero_cleanup_intervals() {
+        let config = Config {
+            firewall: Firewall {
+                cleanup: Cleanup {
+                    interval_secs: 0,
+                    shrink_interval_cycles: 0,
+                },
+                ..Firewall::default()
+            },
+            ..Config::default()
+        };
+
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("firewall.cleanup.interval_secs"));
+        assert!(problems[1].starts_with("firewall.cleanup.shrink_interval_cycles"));
+    }
+
+    #[test]
+    fn test_validate_runtime_threads() {
+        let config: Config = toml::from_str(
+            r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[runtime]
+worker_threads = 0
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.runtime.max_blocking_threads, None);
+
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            problems,
+            vec!["runtime.worker_threads: must be greater than 0"]
+        );
+    }
+
+    #[test]
+    fn test_validate_min_prefix_bounds() {
+        let config: Config = toml::from_str(
+            r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[firewall.min_prefix]
+v4 = 33
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.firewall.min_prefix.v6, 32);
+
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            problems,
+            vec!["firewall.min_prefix.v4: must be between 0 and 32"]
+        );
+    }
+
+    #[test]
+    fn test_firewall_drop_action() {
+        assert_eq!(Config::default().firewall.drop_action, DropAction::Drop);
+
+        let config: Config = toml::from_str(
+            r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+operation_mode = "offloaded"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[firewall]
+drop_action = { redirect = "mon*" }
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.firewall.drop_action,
+            DropAction::Redirect("mon*".to_string())
+        );
+
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            problems,
+            vec![
+                "firewall.drop_action: 'mon*' must be the name of an interface",
+                "firewall.drop_action: redirecting is not supported in offloaded mode",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_metrics_defaults() {
+        let config: Config = toml::from_str(
+            r#"
+ifaces = ["eth0"]
+working_dir = "/var/lib/couic"
+user = "couic"
+group = "couic"
+
+[logging]
+dir = "/var/log/couic"
+
+[server]
+socket = "/var/run/couic.sock"
+
+[metrics]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.metrics, Some(Metrics::default()));
+        assert!(Config::default().metrics.is_none());
+    }
+
+    #[test]
+    fn test_validate_metrics_non_loopback_requires_opt_in() {
+        let mut config = Config {
+            metrics: Some(Metrics {
+                addr: "0.0.0.0".parse().unwrap(),
+                ..Metrics::default()
+            }),
+            ..Config::default()
+        };
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("metrics.addr"));
+
+        if let Some(metrics) = config.metrics.as_mut() {
+            metrics.allow_non_loopback = true;
+        }
+        assert!(config.validate().is_ok());
+
+        let loopback_v6 = Config {
+            metrics: Some(Metrics {
+                addr: "::1".parse().unwrap(),
+                ..Metrics::default()
+            }),
+            ..Config::default()
+        };
+        assert!(loopback_v6.validate().is_ok());
+    }
+
+    #[test]
+    fn test_server_compression() {
+        let server: Server = toml::from_str(r#"socket = "/run/couic.sock""#).unwrap();
+        assert_eq!(server.compression, Compression::default());
+        assert!(server.compression.enabled);
+        assert_eq!(server.compression.min_size, 1024);
+
+        let server: Server = toml::from_str(
+            r#"
+socket = "/run/couic.sock"
+
+[compression]
+enabled = false
+"#,
+        )
+        .unwrap();
+        assert!(!server.compression.enabled);
+        assert_eq!(server.compression.min_size, 1024);
+    }
+
+    #[test]
+    fn test_use_ansi_only_on_terminal_without_no_color() {
+        assert!(use_ansi(true, None));
+        assert!(use_ansi(true, Some(OsStr::new(""))));
+        assert!(!use_ansi(true, Some(OsStr::new("1"))));
+        assert!(!use_ansi(false, None));
+    }
+
+    #[test]
+    fn test_log_level_reset_restores_the_startup_directives() {
+        let (_layer, handle) = LogLevelHandle::new(EnvFilter::new("warn,couic=debug"));
+        let startup = handle.current().unwrap();
+        handle.set(LogLevel::Trace).unwrap();
+        assert_eq!(handle.current().unwrap(), "trace");
+
+        assert_eq!(handle.reset().unwrap(), startup);
+        assert_eq!(handle.current().unwrap(), startup);
+        assert!(startup.contains("couic=debug"));
+    }
+
+    #[test]
+    fn test_server_max_body_size() {
+        let server: Server = toml::from_str(r#"socket = "/run/couic.sock""#).unwrap();
+        assert_eq!(server.max_body_size, 8 * 1024 * 1024);
+
+        let mut config = Config::default();
+        config.server.max_body_size = 0;
+        let Err(ConfigError::Validation(problems)) = config.validate() else {
+            panic!("expected validation error");
+        };
+        assert_eq!(
+            problems,
+            vec!["server.max_body_size: must be greater than 0"]
+        );
+    }
 }
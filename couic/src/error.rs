@@ -22,8 +22,12 @@ impl CompositeError {
             ErrorCode::Eprocessing => StatusCode::ACCEPTED,
             ErrorCode::Eunauthorized => StatusCode::UNAUTHORIZED,
             ErrorCode::Enotfound => StatusCode::NOT_FOUND,
-            ErrorCode::Econflict => StatusCode::CONFLICT,
+            // Same status as before the map capacity had its own code
+            ErrorCode::Econflict | ErrorCode::Efull => StatusCode::CONFLICT,
+            ErrorCode::Eprecondition => StatusCode::PRECONDITION_FAILED,
+            ErrorCode::Equota => StatusCode::TOO_MANY_REQUESTS,
             ErrorCode::Ebadrequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Etoolarge => StatusCode::PAYLOAD_TOO_LARGE,
             ErrorCode::Einvalid => StatusCode::UNPROCESSABLE_ENTITY,
             ErrorCode::Einternal => StatusCode::INTERNAL_SERVER_ERROR,
             ErrorCode::Enotimplemented => StatusCode::NOT_IMPLEMENTED,
@@ -100,6 +104,22 @@ mod tests {
             CompositeError::new(ErrorCode::Econflict, "").to_status_code(),
             StatusCode::CONFLICT
         );
+        assert_eq!(
+            CompositeError::new(ErrorCode::Efull, "").to_status_code(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            CompositeError::new(ErrorCode::Eprecondition, "").to_status_code(),
+            StatusCode::PRECONDITION_FAILED
+        );
+        assert_eq!(
+            CompositeError::new(ErrorCode::Equota, "").to_status_code(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            CompositeError::new(ErrorCode::Etoolarge, "").to_status_code(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
         assert_eq!(
             CompositeError::new(ErrorCode::Einvalid, "").to_status_code(),
             StatusCode::UNPROCESSABLE_ENTITY
@@ -1,18 +1,51 @@
+use std::time::SystemTime;
+
 use axum::{
     Json,
-    extract::{FromRequest, FromRequestParts, Path, Request},
+    extract::{FromRequest, FromRequestParts, Path, Query, Request},
+    http::{
+        StatusCode,
+        header::{IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+    },
 };
 use serde::Deserialize;
 use serde::de::DeserializeOwned;
 
 use crate::error::CompositeError;
-use common::{ErrorCode, ValidateFrom};
+use common::{
+    DRY_RUN_HEADER, ErrorCode, FORCE_HEADER, IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH,
+    PROPAGATE_HEADER, TimeFormat, ValidateFrom,
+};
 
 #[derive(Deserialize, Debug)]
 pub struct QueryParams {
     pub format: Option<String>,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct DeltaQueryParams {
+    pub window_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LogTailQueryParams {
+    pub lines: Option<usize>,
+    /// Keep streaming the lines appended to the log
+    #[serde(default)]
+    pub follow: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListQueryParams {
+    /// Only list entries expiring within this many seconds, or expired
+    pub expiring_within: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TimeFormatParams {
+    time_format: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct ValidatedJson<T>(pub T);
 
@@ -28,6 +61,9 @@ where
         let Json(input) = Json::<T::Input>::from_request(req, state)
             .await
             .map_err(|e| {
+                if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                    return CompositeError::new(ErrorCode::Etoolarge, "Request body too large");
+                }
                 CompositeError::new(ErrorCode::Ebadrequest, &format!("Invalid JSON format: {e}"))
             })?;
 
@@ -63,3 +99,221 @@ where
         Ok(Self(validated))
     }
 }
+
+/// Whether the client asked to bypass the protected ranges guard
+pub struct Force(pub bool);
+
+impl<S> FromRequestParts<S> for Force
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        bool_header(parts, FORCE_HEADER, false).map(Self)
+    }
+}
+
+/// Whether an entry change is forwarded to peers and reporting, unless the
+/// client manages them out-of-band
+pub struct Propagate(pub bool);
+
+impl<S> FromRequestParts<S> for Propagate
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        bool_header(parts, PROPAGATE_HEADER, true).map(Self)
+    }
+}
+
+/// Format of the timestamps of entries in the response, epoch seconds unless
+/// asked otherwise with `?time_format=`
+pub struct TimeFormatQuery(pub TimeFormat);
+
+impl<S> FromRequestParts<S> for TimeFormatQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<TimeFormatParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| {
+                CompositeError::new(ErrorCode::Ebadrequest, &format!("Invalid query: {e}"))
+            })?;
+        let Some(value) = params.time_format else {
+            return Ok(Self(TimeFormat::default()));
+        };
+        value.parse().map(Self).map_err(|e: String| {
+            let mut err = CompositeError::new(ErrorCode::Einvalid, "Validation failed");
+            err.add_detail("time_format", ErrorCode::Einvalid, &e);
+            err
+        })
+    }
+}
+
+/// Whether the client asked to check an entry creation without applying it
+pub struct DryRun(pub bool);
+
+impl<S> FromRequestParts<S> for DryRun
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        bool_header(parts, DRY_RUN_HEADER, false).map(Self)
+    }
+}
+
+/// Client chosen key identifying a creation request across its retries
+pub struct IdempotencyKey(pub Option<String>);
+
+impl<S> FromRequestParts<S> for IdempotencyKey
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(Self(None));
+        };
+        match value.to_str().map(str::trim) {
+            Ok(key)
+                if !key.is_empty()
+                    && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH
+                    && key.chars().all(|c| c.is_ascii_graphic()) =>
+            {
+                Ok(Self(Some(key.to_string())))
+            }
+            _ => Err(CompositeError::new(
+                ErrorCode::Ebadrequest,
+                &format!(
+                    "Invalid {IDEMPOTENCY_KEY_HEADER} header: expected 1 to {MAX_IDEMPOTENCY_KEY_LENGTH} visible ASCII characters"
+                ),
+            )),
+        }
+    }
+}
+
+/// ETags a set update is conditioned on, from the `If-Match` header
+pub struct IfMatch(pub Option<String>);
+
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IF_MATCH) else {
+            return Ok(Self(None));
+        };
+        match value.to_str().map(str::trim) {
+            Ok(etags) if !etags.is_empty() => Ok(Self(Some(etags.to_string()))),
+            _ => Err(CompositeError::new(
+                ErrorCode::Ebadrequest,
+                &format!("Invalid {IF_MATCH} header: expected ETags or *"),
+            )),
+        }
+    }
+}
+
+/// Validators of a cached response, from the `If-None-Match` and
+/// `If-Modified-Since` headers. An unparsable date is ignored, as if absent.
+pub struct IfNoneMatch {
+    pub etags: Option<String>,
+    pub modified_since: Option<SystemTime>,
+}
+
+impl IfNoneMatch {
+    /// Whether the client already holds the response of ETag `etag`, last
+    /// modified at `last_modified`. A date is only considered without ETags.
+    pub fn is_fresh(&self, etag: &str, last_modified: Option<SystemTime>) -> bool {
+        if let Some(etags) = &self.etags {
+            // Weak comparison, as for a GET
+            return etags
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+        }
+        match (self.modified_since, last_modified) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = CompositeError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let etags = match parts.headers.get(IF_NONE_MATCH).map(|v| v.to_str()) {
+            None => None,
+            Some(Ok(etags)) if !etags.trim().is_empty() => Some(etags.trim().to_string()),
+            Some(_) => {
+                return Err(CompositeError::new(
+                    ErrorCode::Ebadrequest,
+                    &format!("Invalid {IF_NONE_MATCH} header: expected ETags or *"),
+                ));
+            }
+        };
+        let modified_since = parts
+            .headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok());
+        Ok(Self {
+            etags,
+            modified_since,
+        })
+    }
+}
+
+/// Boolean value of a request header, `default` when absent
+fn bool_header(
+    parts: &axum::http::request::Parts,
+    name: &str,
+    default: bool,
+) -> Result<bool, CompositeError> {
+    let Some(value) = parts.headers.get(name) else {
+        return Ok(default);
+    };
+    match value.to_str().map(str::trim) {
+        Ok(v) if v.eq_ignore_ascii_case("true") => Ok(true),
+        Ok(v) if v.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(CompositeError::new(
+            ErrorCode::Ebadrequest,
+            &format!("Invalid {name} header: expected true or false"),
+        )),
+    }
+}
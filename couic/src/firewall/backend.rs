@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+#[cfg(test)]
+use std::collections::HashSet;
+use std::io;
+#[cfg(test)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use aya::{
+    Ebpf, Pod, include_bytes_aligned,
+    maps::{
+        Array, LpmTrie, MapData, MapError, PerCpuArray, PerCpuHashMap as LruHashMap, PerCpuValues,
+    },
+    programs::{Xdp, XdpFlags},
+};
+use tracing::{error, info};
+
+use super::hotplug::{LinkMonitor, XdpAttachments};
+use super::iface::{iface_index, present_ifaces, resolve_ifaces};
+#[cfg(test)]
+use super::lpm::StoredEntry;
+use super::lpm::{LpmBackend, LpmMap};
+use super::service::FirewallServiceError;
+use crate::config::{Config, DropAction, OperationMode};
+use crate::error::CompositeError;
+#[cfg(test)]
+use common::NormalizedCidr;
+use common::{EntryCounters, EntryHitsKey, ErrorCode, PktStats, Policy};
+
+// XDP actions, as numbered by the kernel
+const XDP_DROP: u32 = 1;
+const XDP_REDIRECT: u32 = 4;
+
+/// Verdict of the packets matched by a drop entry, as read by the XDP program
+/// from `couic_drop_action`
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
+struct DropActionValue {
+    action: u32,
+    /// Interface the packets are redirected to, with `XDP_REDIRECT`
+    ifindex: u32,
+}
+
+unsafe impl Pod for DropActionValue {}
+
+/// Kernel side of the firewall: the maps the XDP program matches packets
+/// against and the counters it updates
+pub trait FirewallBackend: Send + Sync + 'static {
+    /// LPM map of `policy` for one address family, with the number of
+    /// entries it holds at most. Each map is handed over once.
+    fn take_lpm_map(
+        &mut self,
+        policy: Policy,
+        ipv4: bool,
+    ) -> Result<(Box<dyn LpmBackend>, usize), FirewallServiceError>;
+
+    /// Counters of each XDP action, in `XDP_*` order
+    fn xdp_stats(&self) -> Result<Vec<PktStats>, CompositeError>;
+
+    /// Counters of each drop reason, in `DROP_REASON_*` order
+    fn drop_reasons(&self) -> Result<Vec<PktStats>, CompositeError>;
+
+    /// Counters of each tag matched by an entry of `policy`
+    fn tag_stats(&self, policy: Policy) -> Result<Vec<(u64, PktStats)>, CompositeError>;
+
+    /// Hit counters of an entry, `last_hit` being the latest of all CPUs,
+    /// `None` when it was never hit
+    fn entry_hits(&self, key: &EntryHitsKey) -> Result<Option<EntryCounters>, CompositeError>;
+
+    /// Forgets the hit counters of an entry
+    fn remove_entry_hits(&self, key: &EntryHitsKey) -> Result<(), CompositeError>;
+
+    /// Zeroes the XDP, drop reason, per tag and per entry counters
+    fn reset_stats(&self) -> Result<(), CompositeError>;
+
+    /// Drops every packet not matched by an ignore entry, or stops doing so
+    fn set_lockdown(&self, enabled: bool) -> Result<(), CompositeError>;
+}
+
+/// Maps of the XDP program, loaded and attached to the configured interfaces
+pub struct EbpfBackend {
+    _xdp: Arc<Mutex<XdpAttachments>>,
+    /// LPM maps not handed over to their store yet
+    lpm_maps: HashMap<(Policy, bool), LpmMap>,
+    stats: Mutex<PerCpuArray<MapData, PktStats>>,
+    drop_reasons: Mutex<PerCpuArray<MapData, PktStats>>,
+    drop_stats_per_tag: Mutex<LruHashMap<MapData, u64, PktStats>>,
+    ignore_stats_per_tag: Mutex<LruHashMap<MapData, u64, PktStats>>,
+    monitor_stats_per_tag: Mutex<LruHashMap<MapData, u64, PktStats>>,
+    entry_hits: Mutex<LruHashMap<MapData, EntryHitsKey, EntryCounters>>,
+    lockdown: Mutex<Array<MapData, u32>>,
+}
+
+impl EbpfBackend {
+    pub fn load(config: &Config) -> Result<Self, FirewallServiceError> {
+        let mut ebpf = Ebpf::load(include_bytes_aligned!(concat!(env!("OUT_DIR"), "/couic")))?;
+
+        // ICMP exceptions must be in place before the program sees traffic
+        for (name, types) in [
+            ("couic_icmpv4_allow", &config.icmp_exceptions.ipv4),
+            ("couic_icmpv6_allow", &config.icmp_exceptions.ipv6),
+        ] {
+            let mut allow: Array<MapData, u8> = Array::try_from(take_map(&mut ebpf, name)?)?;
+            for icmp_type in types {
+                allow.set(u32::from(*icmp_type), 1, 0)?;
+            }
+            if !types.is_empty() {
+                info!("ICMP types always passed ({name}): {types:?}");
+            }
+        }
+
+        set_drop_action(&mut ebpf, &config.firewall.drop_action)?;
+
+        let program: &mut Xdp = ebpf
+            .program_mut("couic")
+            .ok_or_else(|| FirewallServiceError::ProgramNotFound("couic".to_string()))?
+            .try_into()?;
+        program.load()?;
+
+        let xdp_flags = match config.operation_mode {
+            OperationMode::Generic => XdpFlags::SKB_MODE,
+            OperationMode::Native => XdpFlags::DRV_MODE,
+            OperationMode::Offloaded => XdpFlags::HW_MODE,
+        };
+        // With hotplug, interfaces are attached once the program is held
+        if !config.hotplug {
+            let ifaces = resolve_ifaces(&config.ifaces)?;
+            if ifaces != config.ifaces {
+                info!("Interfaces {:?} resolved to {ifaces:?}", config.ifaces);
+            }
+            for iface in &ifaces {
+                program.attach(iface, xdp_flags)?;
+                info!(
+                    "XDP program attached to interface: {iface} (mode: {:?})",
+                    config.operation_mode
+                );
+            }
+        }
+
+        let mut lpm_maps = HashMap::new();
+        for policy in [Policy::Drop, Policy::Ignore, Policy::Monitor] {
+            let v4 = LpmTrie::try_from(take_map(&mut ebpf, &format!("couic_ipv4_{policy}"))?)?;
+            lpm_maps.insert((policy, true), LpmMap::V4(v4));
+            let v6 = LpmTrie::try_from(take_map(&mut ebpf, &format!("couic_ipv6_{policy}"))?)?;
+            lpm_maps.insert((policy, false), LpmMap::V6(v6));
+        }
+        let stats = PerCpuArray::try_from(take_map(&mut ebpf, "couic_stats")?)?;
+        let drop_reasons = PerCpuArray::try_from(take_map(&mut ebpf, "couic_drop_reasons")?)?;
+        let drop_stats_per_tag =
+            LruHashMap::try_from(take_map(&mut ebpf, "couic_drop_stats_per_tag")?)?;
+        let ignore_stats_per_tag =
+            LruHashMap::try_from(take_map(&mut ebpf, "couic_ignore_stats_per_tag")?)?;
+        let monitor_stats_per_tag =
+            LruHashMap::try_from(take_map(&mut ebpf, "couic_monitor_stats_per_tag")?)?;
+        let entry_hits = LruHashMap::try_from(take_map(&mut ebpf, "couic_entry_hits")?)?;
+        let lockdown = Array::try_from(take_map(&mut ebpf, "couic_lockdown")?)?;
+
+        let xdp = Arc::new(Mutex::new(XdpAttachments::new(ebpf, xdp_flags)));
+        if config.hotplug {
+            launch_hotplug(config, &xdp)?;
+        }
+
+        Ok(Self {
+            _xdp: xdp,
+            lpm_maps,
+            stats: Mutex::new(stats),
+            drop_reasons: Mutex::new(drop_reasons),
+            drop_stats_per_tag: Mutex::new(drop_stats_per_tag),
+            ignore_stats_per_tag: Mutex::new(ignore_stats_per_tag),
+            monitor_stats_per_tag: Mutex::new(monitor_stats_per_tag),
+            entry_hits: Mutex::new(entry_hits),
+            lockdown: Mutex::new(lockdown),
+        })
+    }
+
+    fn stats_per_tag(&self, policy: Policy) -> &Mutex<LruHashMap<MapData, u64, PktStats>> {
+        match policy {
+            Policy::Drop => &self.drop_stats_per_tag,
+            Policy::Ignore => &self.ignore_stats_per_tag,
+            Policy::Monitor => &self.monitor_stats_per_tag,
+        }
+    }
+}
+
+impl FirewallBackend for EbpfBackend {
+    fn take_lpm_map(
+        &mut self,
+        policy: Policy,
+        ipv4: bool,
+    ) -> Result<(Box<dyn LpmBackend>, usize), FirewallServiceError> {
+        let map = self.lpm_maps.remove(&(policy, ipv4)).ok_or_else(|| {
+            let family = if ipv4 { "ipv4" } else { "ipv6" };
+            FirewallServiceError::ProgramNotFound(format!("couic_{family}_{policy}"))
+        })?;
+        let max_entries = map.max_entries()?;
+        Ok((Box::new(map), max_entries))
+    }
+
+    fn xdp_stats(&self) -> Result<Vec<PktStats>, CompositeError> {
+        lock_map(&self.stats)?
+            .iter()
+            .enumerate()
+            .map(|(i, vals)| {
+                vals.map(|vals| sum_per_cpu(&vals)).map_err(|e| {
+                    error!("Error reading stats at index {i}: {e}");
+                    CompositeError::new(
+                        ErrorCode::Einternal,
+                        &format!("Error reading stats at index {i}: {e}"),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn drop_reasons(&self) -> Result<Vec<PktStats>, CompositeError> {
+        lock_map(&self.drop_reasons)?
+            .iter()
+            .enumerate()
+            .map(|(i, vals)| {
+                vals.map(|vals| sum_per_cpu(&vals)).map_err(|e| {
+                    error!("Error reading drop reason stats at index {i}: {e}");
+                    CompositeError::new(
+                        ErrorCode::Einternal,
+                        &format!("Error reading drop reason stats at index {i}: {e}"),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn tag_stats(&self, policy: Policy) -> Result<Vec<(u64, PktStats)>, CompositeError> {
+        lock_map(self.stats_per_tag(policy))?
+            .iter()
+            .map(|item| {
+                item.map(|(tag_id, vals)| (tag_id, sum_per_cpu(&vals)))
+                    .map_err(|e| {
+                        error!("Error reading tag stats: {e}");
+                        CompositeError::new(
+                            ErrorCode::Einternal,
+                            &format!("Error reading tag stats: {e}"),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    fn entry_hits(&self, key: &EntryHitsKey) -> Result<Option<EntryCounters>, CompositeError> {
+        let per_cpu = match lock_map(&self.entry_hits)?.get(key, 0) {
+            Ok(per_cpu) => per_cpu,
+            Err(MapError::KeyNotFound) => return Ok(None),
+            Err(e) => {
+                return Err(CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Error reading entry hits: {e}"),
+                ));
+            }
+        };
+        let counters = per_cpu
+            .iter()
+            .fold(EntryCounters::default(), |mut acc, counters| {
+                acc.rx_packets = acc.rx_packets.saturating_add(counters.rx_packets);
+                acc.rx_bytes = acc.rx_bytes.saturating_add(counters.rx_bytes);
+                acc.last_hit = acc.last_hit.max(counters.last_hit);
+                acc
+            });
+        Ok(Some(counters))
+    }
+
+    fn remove_entry_hits(&self, key: &EntryHitsKey) -> Result<(), CompositeError> {
+        match lock_map(&self.entry_hits)?.remove(key) {
+            Ok(()) | Err(MapError::KeyNotFound) => Ok(()),
+            Err(e) => Err(map_write_error("entry hits", &e)),
+        }
+    }
+
+    /// Per CPU values are written for every possible CPU, as the kernel
+    /// expects
+    fn reset_stats(&self) -> Result<(), CompositeError> {
+        let nr_cpus = aya::util::nr_cpus().map_err(|(_, e)| {
+            CompositeError::new(
+                ErrorCode::Einternal,
+                &format!("Failed to count possible CPUs: {e}"),
+            )
+        })?;
+
+        for map in [&self.stats, &self.drop_reasons] {
+            let mut map = lock_map(map)?;
+            for index in 0..map.len() {
+                map.set(index, zeroed_per_cpu(nr_cpus)?, 0)
+                    .map_err(|e| map_write_error("stats", &e))?;
+            }
+        }
+
+        // Tags keep their series, at zero, until the LRU evicts them
+        for policy in [Policy::Drop, Policy::Ignore, Policy::Monitor] {
+            let mut map = lock_map(self.stats_per_tag(policy))?;
+            let tag_ids: Vec<u64> = map.keys().filter_map(Result::ok).collect();
+            for tag_id in tag_ids {
+                map.insert(tag_id, zeroed_per_cpu(nr_cpus)?, 0)
+                    .map_err(|e| map_write_error("tag stats", &e))?;
+            }
+        }
+
+        // A missing key reads as an entry never hit
+        let mut entry_hits = lock_map(&self.entry_hits)?;
+        let keys: Vec<EntryHitsKey> = entry_hits.keys().filter_map(Result::ok).collect();
+        for key in keys {
+            match entry_hits.remove(&key) {
+                Ok(()) | Err(MapError::KeyNotFound) => {}
+                Err(e) => return Err(map_write_error("entry hits", &e)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_lockdown(&self, enabled: bool) -> Result<(), CompositeError> {
+        lock_map(&self.lockdown)?
+            .set(0, u32::from(enabled), 0)
+            .map_err(|e| {
+                CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Error writing lockdown state: {e}"),
+                )
+            })
+    }
+}
+
+fn take_map(ebpf: &mut Ebpf, name: &str) -> Result<aya::maps::Map, FirewallServiceError> {
+    ebpf.take_map(name)
+        .ok_or_else(|| FirewallServiceError::ProgramNotFound(name.to_string()))
+}
+
+/// Tell the program what to do with the packets matched by a drop entry,
+/// before it sees traffic
+fn set_drop_action(ebpf: &mut Ebpf, drop_action: &DropAction) -> Result<(), FirewallServiceError> {
+    let value = match drop_action {
+        DropAction::Drop => DropActionValue {
+            action: XDP_DROP,
+            ifindex: 0,
+        },
+        DropAction::Redirect(iface) => {
+            let ifindex = iface_index(iface)?;
+            info!("Packets matched by drop entries redirected to {iface} (ifindex {ifindex})");
+            DropActionValue {
+                action: XDP_REDIRECT,
+                ifindex,
+            }
+        }
+    };
+    let mut map: Array<MapData, DropActionValue> =
+        Array::try_from(take_map(ebpf, "couic_drop_action")?)?;
+    map.set(0, value, 0)?;
+    Ok(())
+}
+
+/// Attach the program to the interfaces matching `ifaces`, then keep
+/// monitoring them for additions and removals
+fn launch_hotplug(
+    config: &Config,
+    xdp: &Arc<Mutex<XdpAttachments>>,
+) -> Result<(), FirewallServiceError> {
+    // Subscribe first so no interface appearing meanwhile is missed
+    let monitor = LinkMonitor::new()?;
+    let present = present_ifaces(&config.ifaces)?;
+    {
+        let mut attachments = xdp
+            .lock()
+            .map_err(|e| io::Error::other(format!("XDP attachments lock: {e}")))?;
+        attachments.sync(&present);
+        info!(
+            "Interfaces {:?} resolved to {:?} (mode: {:?}), monitoring for changes",
+            config.ifaces,
+            attachments.attached(),
+            config.operation_mode
+        );
+    }
+    monitor.launch(config.ifaces.clone(), Arc::clone(xdp));
+    Ok(())
+}
+
+fn sum_per_cpu(vals: &PerCpuValues<PktStats>) -> PktStats {
+    vals.iter().fold(PktStats::default(), |mut acc, cpuvalue| {
+        acc.rx_packets = acc.rx_packets.saturating_add(cpuvalue.rx_packets);
+        acc.rx_bytes = acc.rx_bytes.saturating_add(cpuvalue.rx_bytes);
+        acc
+    })
+}
+
+fn lock_map<T>(map: &Mutex<T>) -> Result<MutexGuard<'_, T>, CompositeError> {
+    map.lock()
+        .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))
+}
+
+/// One zeroed value per possible CPU
+fn zeroed_per_cpu<T: Pod + Default + Clone>(
+    nr_cpus: usize,
+) -> Result<PerCpuValues<T>, CompositeError> {
+    PerCpuValues::try_from(vec![T::default(); nr_cpus]).map_err(|e| {
+        CompositeError::new(
+            ErrorCode::Einternal,
+            &format!("Failed to build per CPU values: {e}"),
+        )
+    })
+}
+
+fn map_write_error(what: &str, e: &MapError) -> CompositeError {
+    CompositeError::new(
+        ErrorCode::Einternal,
+        &format!("Error resetting {what}: {e}"),
+    )
+}
+
+/// Backend keeping its maps in memory, for tests needing neither root nor
+/// the eBPF program. Counters only change through the `record_*` methods,
+/// clones sharing them.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct MemoryBackend {
+    max_entries: usize,
+    counters: Arc<Mutex<MemoryCounters>>,
+    lockdown: Arc<AtomicBool>,
+    failing: Arc<Mutex<HashSet<NormalizedCidr>>>,
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryCounters {
+    xdp: Vec<PktStats>,
+    drop_reasons: Vec<PktStats>,
+    tags: HashMap<Policy, HashMap<u64, PktStats>>,
+    entry_hits: HashMap<EntryHitsKey, EntryCounters>,
+}
+
+#[cfg(test)]
+impl MemoryBackend {
+    /// Number of XDP actions and drop reasons counted by the XDP program
+    const XDP_ACTIONS: usize = 5;
+    const DROP_REASONS: usize = 4;
+
+    /// Backend whose LPM maps each hold at most `max_entries` entries
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            counters: Arc::new(Mutex::new(MemoryCounters {
+                xdp: vec![PktStats::default(); Self::XDP_ACTIONS],
+                drop_reasons: vec![PktStats::default(); Self::DROP_REASONS],
+                ..MemoryCounters::default()
+            })),
+            lockdown: Arc::new(AtomicBool::new(false)),
+            failing: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Makes the maps fail to insert `cidr`
+    pub fn fail_insert(&self, cidr: NormalizedCidr) {
+        self.failing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cidr);
+    }
+
+    /// Whether lockdown was last enabled
+    pub fn lockdown(&self) -> bool {
+        self.lockdown.load(Ordering::Relaxed)
+    }
+
+    /// Counts a packet of `bytes` under the XDP action at `action`
+    pub fn record_xdp(&self, action: usize, bytes: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(stats) = counters.xdp.get_mut(action) {
+            count_packet(stats, bytes);
+        }
+    }
+
+    /// Counts a packet of `bytes` matching the entry `key` tagged `tag_id`
+    pub fn record_hit(&self, policy: Policy, tag_id: u64, key: EntryHitsKey, bytes: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        count_packet(
+            counters
+                .tags
+                .entry(policy)
+                .or_default()
+                .entry(tag_id)
+                .or_default(),
+            bytes,
+        );
+        let hits = counters.entry_hits.entry(key).or_default();
+        hits.rx_packets = hits.rx_packets.saturating_add(1);
+        hits.rx_bytes = hits.rx_bytes.saturating_add(bytes);
+    }
+
+    fn counters(&self) -> Result<MutexGuard<'_, MemoryCounters>, CompositeError> {
+        lock_map(&self.counters)
+    }
+}
+
+#[cfg(test)]
+fn count_packet(stats: &mut PktStats, bytes: u64) {
+    stats.rx_packets = stats.rx_packets.saturating_add(1);
+    stats.rx_bytes = stats.rx_bytes.saturating_add(bytes);
+}
+
+/// LPM map of a `MemoryBackend`, failing to insert the CIDRs it was told to
+#[cfg(test)]
+struct MemoryMap {
+    failing: Arc<Mutex<HashSet<NormalizedCidr>>>,
+}
+
+#[cfg(test)]
+impl LpmBackend for MemoryMap {
+    fn insert_entry(
+        &mut self,
+        cidr: &NormalizedCidr,
+        _entry: &StoredEntry,
+    ) -> Result<(), CompositeError> {
+        let failing = self.failing.lock().unwrap_or_else(|e| e.into_inner());
+        if failing.contains(cidr) {
+            return Err(CompositeError::new(
+                ErrorCode::Einternal,
+                &format!("Failed to insert {cidr}"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn remove_entry(&mut self, _cidr: &NormalizedCidr) -> Result<(), CompositeError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl FirewallBackend for MemoryBackend {
+    fn take_lpm_map(
+        &mut self,
+        _policy: Policy,
+        _ipv4: bool,
+    ) -> Result<(Box<dyn LpmBackend>, usize), FirewallServiceError> {
+        let map = MemoryMap {
+            failing: Arc::clone(&self.failing),
+        };
+        Ok((Box::new(map), self.max_entries))
+    }
+
+    fn xdp_stats(&self) -> Result<Vec<PktStats>, CompositeError> {
+        Ok(self.counters()?.xdp.clone())
+    }
+
+    fn drop_reasons(&self) -> Result<Vec<PktStats>, CompositeError> {
+        Ok(self.counters()?.drop_reasons.clone())
+    }
+
+    fn tag_stats(&self, policy: Policy) -> Result<Vec<(u64, PktStats)>, CompositeError> {
+        Ok(self
+            .counters()?
+            .tags
+            .get(&policy)
+            .map(|tags| {
+                tags.iter()
+                    .map(|(tag_id, stats)| (*tag_id, *stats))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn entry_hits(&self, key: &EntryHitsKey) -> Result<Option<EntryCounters>, CompositeError> {
+        Ok(self.counters()?.entry_hits.get(key).copied())
+    }
+
+    fn remove_entry_hits(&self, key: &EntryHitsKey) -> Result<(), CompositeError> {
+        self.counters()?.entry_hits.remove(key);
+        Ok(())
+    }
+
+    fn reset_stats(&self) -> Result<(), CompositeError> {
+        let mut counters = self.counters()?;
+        counters.xdp.fill(PktStats::default());
+        counters.drop_reasons.fill(PktStats::default());
+        for tags in counters.tags.values_mut() {
+            tags.values_mut()
+                .for_each(|stats| *stats = PktStats::default());
+        }
+        counters.entry_hits.clear();
+        Ok(())
+    }
+
+    fn set_lockdown(&self, enabled: bool) -> Result<(), CompositeError> {
+        self.lockdown.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zeroed_per_cpu_covers_every_possible_cpu() {
+        let nr_cpus = aya::util::nr_cpus().unwrap();
+        let values: PerCpuValues<PktStats> = zeroed_per_cpu(nr_cpus).unwrap();
+        assert_eq!(values.len(), nr_cpus);
+        assert!(
+            values
+                .iter()
+                .all(|stats| stats.rx_packets == 0 && stats.rx_bytes == 0)
+        );
+        // The kernel rejects per CPU writes of any other length
+        assert!(zeroed_per_cpu::<PktStats>(nr_cpus + 1).is_err());
+    }
+
+    #[test]
+    fn memory_backend_resets_counters() {
+        let backend = MemoryBackend::new(16);
+        let key = EntryHitsKey::default();
+        backend.record_xdp(1, 64);
+        backend.record_hit(Policy::Drop, 3, key, 64);
+
+        assert_eq!(backend.xdp_stats().unwrap()[1].rx_packets, 1);
+        assert_eq!(backend.tag_stats(Policy::Drop).unwrap().len(), 1);
+        assert_eq!(backend.entry_hits(&key).unwrap().unwrap().rx_bytes, 64);
+
+        backend.reset_stats().unwrap();
+        assert!(
+            backend
+                .xdp_stats()
+                .unwrap()
+                .iter()
+                .all(|s| s.rx_packets == 0)
+        );
+        // Tags keep their series, at zero
+        let tags = backend.tag_stats(Policy::Drop).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].1.rx_packets, 0);
+        assert!(backend.entry_hits(&key).unwrap().is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use common::RecentlyExpired;
+
+/// Last entries removed once expired, the oldest being evicted beyond the
+/// capacity
+#[derive(Debug, Clone)]
+pub struct ExpiredLog {
+    entries: Arc<Mutex<VecDeque<RecentlyExpired>>>,
+    capacity: usize,
+}
+
+impl ExpiredLog {
+    /// Log keeping up to `capacity` entries, none when 0
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn record(&self, expired: RecentlyExpired) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(expired);
+    }
+
+    /// Logged entries, the most recently expired first
+    pub fn recent(&self) -> Vec<RecentlyExpired> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use common::Policy;
+
+    fn expired(expired_at: u64) -> RecentlyExpired {
+        RecentlyExpired {
+            policy: Policy::Drop,
+            cidr: "192.0.2.1/32".parse().unwrap(),
+            tag: None,
+            expired_at,
+        }
+    }
+
+    fn expirations(log: &ExpiredLog) -> Vec<u64> {
+        log.recent().iter().map(|e| e.expired_at).collect()
+    }
+
+    #[test]
+    fn full_log_evicts_oldest_entries() {
+        let log = ExpiredLog::new(2);
+        for expired_at in 1..=3 {
+            log.record(expired(expired_at));
+        }
+        assert_eq!(expirations(&log), vec![3, 2]);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let log = ExpiredLog::new(0);
+        log.record(expired(1));
+        assert!(log.recent().is_empty());
+    }
+}
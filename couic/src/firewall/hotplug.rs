@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use aya::Ebpf;
+use aya::programs::{ProgramError, Xdp, XdpFlags, xdp::XdpLinkId};
+use caps::Capability;
+use nix::errno::Errno;
+use nix::sys::socket::{
+    AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType, bind, recv, setsockopt,
+    socket, sockopt,
+};
+use nix::sys::time::TimeVal;
+use tracing::{debug, error, info, warn};
+
+use super::iface::{NetInterface, present_ifaces};
+use crate::security::SecurityService;
+
+/// Multicast group of the link add/remove/change notifications
+const RTMGRP_LINK: u32 = 1;
+/// Interfaces are rescanned at least this often, so failed attachments are
+/// retried even without link events
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+/// Capabilities kept by the monitor thread to attach and detach XDP programs
+const ATTACH_CAPS: [Capability; 2] = [Capability::CAP_NET_ADMIN, Capability::CAP_SYS_ADMIN];
+
+/// XDP program attachments, tracked to attach and detach interfaces at
+/// runtime. Holding the `Ebpf` keeps the program loaded.
+pub struct XdpAttachments {
+    ebpf: Ebpf,
+    flags: XdpFlags,
+    /// Attached interfaces, by name, with their index at attach time
+    links: HashMap<String, (u32, XdpLinkId)>,
+}
+
+impl XdpAttachments {
+    pub fn new(ebpf: Ebpf, flags: XdpFlags) -> Self {
+        Self {
+            ebpf,
+            flags,
+            links: HashMap::new(),
+        }
+    }
+
+    fn program(&mut self) -> Result<&mut Xdp, ProgramError> {
+        self.ebpf
+            .program_mut("couic")
+            .ok_or(ProgramError::NotLoaded)?
+            .try_into()
+    }
+
+    /// Attach the program to the `present` interfaces it is not attached to
+    /// yet, and forget the interfaces that are gone. An interface recreated
+    /// with the same name has a new index and is attached again.
+    pub fn sync(&mut self, present: &[NetInterface]) {
+        let gone: Vec<String> = self
+            .links
+            .iter()
+            .filter(|(name, (index, _))| {
+                !present
+                    .iter()
+                    .any(|iface| &iface.name == *name && iface.index == *index)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in gone {
+            let Some((_, link_id)) = self.links.remove(&name) else {
+                continue;
+            };
+            // The kernel drops the attachment along with the interface, so
+            // failing to detach is expected
+            match self.program().and_then(|program| program.detach(link_id)) {
+                Ok(()) => info!("XDP program detached from interface: {name}"),
+                Err(e) => {
+                    debug!("Detaching XDP program from {name}: {e}");
+                    info!("XDP program detached from removed interface: {name}");
+                }
+            }
+        }
+
+        let flags = self.flags;
+        for iface in present {
+            if self.links.contains_key(&iface.name) {
+                continue;
+            }
+            match self
+                .program()
+                .and_then(|program| program.attach_to_if_index(iface.index, flags))
+            {
+                Ok(link_id) => {
+                    info!("XDP program attached to interface: {}", iface.name);
+                    self.links
+                        .insert(iface.name.clone(), (iface.index, link_id));
+                }
+                Err(e) => error!(
+                    "Failed to attach XDP program to interface {}, will retry: {e}",
+                    iface.name
+                ),
+            }
+        }
+    }
+
+    pub fn attached(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.links.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Error opening the netlink socket, telling how to allow it when its
+/// address family is refused, as by `RestrictAddressFamilies=` in a systemd
+/// unit
+fn open_error(errno: Errno) -> io::Error {
+    let hint = match errno {
+        Errno::EAFNOSUPPORT | Errno::EPERM | Errno::EACCES => {
+            " (allow AF_NETLINK, e.g. in RestrictAddressFamilies= of the systemd unit, or disable hotplug)"
+        }
+        _ => "",
+    };
+    io::Error::new(
+        io::Error::from(errno).kind(),
+        format!("hotplug: cannot open the netlink socket of link notifications: {errno}{hint}"),
+    )
+}
+
+/// Netlink socket subscribed to link notifications
+pub struct LinkMonitor {
+    socket: OwnedFd,
+}
+
+impl LinkMonitor {
+    pub fn new() -> io::Result<Self> {
+        Self::open().map_err(open_error)
+    }
+
+    fn open() -> nix::Result<Self> {
+        let socket = socket(
+            AddressFamily::Netlink,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkRoute,
+        )?;
+        bind(socket.as_raw_fd(), &NetlinkAddr::new(0, RTMGRP_LINK))?;
+        let timeout = i64::try_from(RESCAN_INTERVAL.as_secs()).unwrap_or(i64::MAX);
+        setsockopt(&socket, sockopt::ReceiveTimeout, &TimeVal::new(timeout, 0))?;
+        Ok(Self { socket })
+    }
+
+    /// Block until a link notification arrives or the rescan interval
+    /// elapsed. The messages are not decoded: interfaces are rescanned.
+    fn wait(&self) -> nix::Result<()> {
+        let mut buf = [0u8; 8192];
+        match recv(self.socket.as_raw_fd(), &mut buf, MsgFlags::empty()) {
+            Ok(_) | Err(Errno::EAGAIN | Errno::ENOBUFS) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Background thread attaching the XDP program to the interfaces
+    /// matching `patterns` as they appear
+    pub fn launch(self, patterns: Vec<String>, attachments: Arc<Mutex<XdpAttachments>>) {
+        thread::spawn(move || {
+            if let Err(e) = SecurityService::drop_caps_nonewprivs_except(&ATTACH_CAPS) {
+                error!("Interface monitor: {e}");
+                return;
+            }
+            loop {
+                if let Err(e) = self.wait() {
+                    error!("Interface monitor: {e}");
+                    thread::sleep(RESCAN_INTERVAL);
+                }
+                let present = match present_ifaces(&patterns) {
+                    Ok(present) => present,
+                    Err(e) => {
+                        error!("Interface monitor: cannot list interfaces: {e}");
+                        continue;
+                    }
+                };
+                match attachments.lock() {
+                    Ok(mut attachments) => attachments.sync(&present),
+                    Err(e) => {
+                        warn!("Interface monitor exiting: {e}");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refused_netlink_socket_tells_how_to_allow_it() {
+        let err = open_error(Errno::EAFNOSUPPORT);
+        assert!(err.to_string().starts_with(
+            "hotplug: cannot open the netlink socket of link notifications: EAFNOSUPPORT"
+        ));
+        assert!(err.to_string().contains("allow AF_NETLINK"));
+        assert_eq!(err.kind(), io::Error::from(Errno::EAFNOSUPPORT).kind());
+
+        let err = open_error(Errno::ENOMEM);
+        assert!(!err.to_string().contains("AF_NETLINK"));
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Token of `ifaces` standing for every physical interface of the host
+pub const ALL_PHYSICAL: &str = "all-physical";
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+#[derive(Debug, thiserror::Error)]
+pub enum IfaceError {
+    #[error("Cannot list network interfaces: {0}")]
+    Io(#[from] io::Error),
+    #[error("No network interface matches '{0}'")]
+    NoMatch(String),
+}
+
+/// Network interface of the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetInterface {
+    pub name: String,
+    pub index: u32,
+    /// Backed by a device, as opposed to loopback, bridges, veths, tunnels...
+    pub physical: bool,
+}
+
+/// Whether `pattern` needs the host interfaces to be resolved
+fn is_pattern(pattern: &str) -> bool {
+    pattern == ALL_PHYSICAL || pattern.contains(['*', '?'])
+}
+
+/// Resolve the configured `ifaces` into interface names: `all-physical` and
+/// glob patterns (`*`, `?`) are expanded against the interfaces present at
+/// startup, plain names are kept as is
+pub fn resolve_ifaces(patterns: &[String]) -> Result<Vec<String>, IfaceError> {
+    let available = if patterns.iter().any(|pattern| is_pattern(pattern)) {
+        system_interfaces()?
+    } else {
+        Vec::new()
+    };
+    expand(patterns, &available)
+}
+
+/// Interfaces currently present on the host matching any of `patterns`,
+/// plain names included
+pub fn present_ifaces(patterns: &[String]) -> io::Result<Vec<NetInterface>> {
+    Ok(system_interfaces()?
+        .into_iter()
+        .filter(|iface| patterns.iter().any(|pattern| matches(pattern, iface)))
+        .collect())
+}
+
+/// Index of the interface named `name`, which must be present
+pub fn iface_index(name: &str) -> Result<u32, IfaceError> {
+    system_interfaces()?
+        .into_iter()
+        .find(|iface| iface.name == name)
+        .map(|iface| iface.index)
+        .ok_or_else(|| IfaceError::NoMatch(name.to_string()))
+}
+
+fn system_interfaces() -> io::Result<Vec<NetInterface>> {
+    let mut interfaces = Vec::new();
+    for dir_entry in fs::read_dir(SYS_CLASS_NET)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        let dir = Path::new(SYS_CLASS_NET).join(&name);
+        // The interface may vanish while listing
+        let Ok(index) = fs::read_to_string(dir.join("ifindex")) else {
+            continue;
+        };
+        let Ok(index) = index.trim().parse() else {
+            continue;
+        };
+        let physical = dir.join("device").exists();
+        interfaces.push(NetInterface {
+            name,
+            index,
+            physical,
+        });
+    }
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(interfaces)
+}
+
+fn matches(pattern: &str, iface: &NetInterface) -> bool {
+    if pattern == ALL_PHYSICAL {
+        iface.physical
+    } else {
+        glob_match(pattern.as_bytes(), iface.name.as_bytes())
+    }
+}
+
+fn expand(patterns: &[String], available: &[NetInterface]) -> Result<Vec<String>, IfaceError> {
+    let mut resolved: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matches: Vec<&str> = if is_pattern(pattern) {
+            available
+                .iter()
+                .filter(|iface| matches(pattern, iface))
+                .map(|iface| iface.name.as_str())
+                .collect()
+        } else {
+            vec![pattern.as_str()]
+        };
+        if matches.is_empty() {
+            return Err(IfaceError::NoMatch(pattern.clone()));
+        }
+        for name in matches {
+            if !resolved.iter().any(|iface| iface == name) {
+                resolved.push(name.to_string());
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+/// Match `name` against `pattern`, where `*` matches any sequence of
+/// characters and `?` a single one
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob_match(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn host() -> Vec<NetInterface> {
+        [
+            ("docker0", false),
+            ("eth0", true),
+            ("eth1", true),
+            ("lo", false),
+            ("veth1a2b", false),
+            ("wlp2s0", true),
+        ]
+        .into_iter()
+        .zip(1..)
+        .map(|((name, physical), index)| NetInterface {
+            name: name.to_string(),
+            index,
+            physical,
+        })
+        .collect()
+    }
+
+    fn patterns(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"eth*", b"eth0"));
+        assert!(glob_match(b"eth*", b"eth"));
+        assert!(glob_match(b"*0", b"docker0"));
+        assert!(glob_match(b"wlp?s0", b"wlp2s0"));
+        assert!(!glob_match(b"eth?", b"eth10"));
+        assert!(!glob_match(b"eth*", b"veth1a2b"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let host = host();
+        let names = |pattern: &str| -> Vec<&str> {
+            host.iter()
+                .filter(|iface| matches(pattern, iface))
+                .map(|iface| iface.name.as_str())
+                .collect()
+        };
+        assert_eq!(names("lo"), vec!["lo"]);
+        assert_eq!(names("*eth*"), vec!["eth0", "eth1", "veth1a2b"]);
+        assert_eq!(names(ALL_PHYSICAL), vec!["eth0", "eth1", "wlp2s0"]);
+    }
+
+    #[test]
+    fn test_expand_all_physical() {
+        assert_eq!(
+            expand(&patterns(&[ALL_PHYSICAL]), &host()).unwrap(),
+            vec!["eth0", "eth1", "wlp2s0"]
+        );
+    }
+
+    #[test]
+    fn test_expand_keeps_order_and_deduplicates() {
+        assert_eq!(
+            expand(&patterns(&["eth1", "eth*", "lo"]), &host()).unwrap(),
+            vec!["eth1", "eth0", "lo"]
+        );
+    }
+
+    #[test]
+    fn test_expand_plain_names_are_kept() {
+        assert_eq!(expand(&patterns(&["ens3"]), &[]).unwrap(), vec!["ens3"]);
+    }
+
+    #[test]
+    fn test_expand_pattern_without_match() {
+        let err = expand(&patterns(&["enp*"]), &host()).unwrap_err();
+        assert!(matches!(err, IfaceError::NoMatch(pattern) if pattern == "enp*"));
+    }
+}
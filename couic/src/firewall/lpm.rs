@@ -1,24 +1,32 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use aya::maps::{IterableMap, LpmTrie, MapData, lpm_trie::Key};
 use crossbeam_channel::Sender;
 use tracing::{debug, error, info};
 
-use super::tag::TagId;
+use crate::config::Cleanup;
 use crate::error::CompositeError;
-use common::{ErrorCode, NormalizedCidr};
+use crate::firewall::source::SourceId;
+use common::stats::lpm_value;
+use common::{ErrorCode, NormalizedCidr, Policy};
 
-const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
-const SHRINK_INTERVAL_CYCLES: u32 = 3600; // Shrink every hour
+/// Reload changes applied per lock acquisition, bounding how long API calls
+/// wait on a large set reload
+const BATCH_CHUNK_SIZE: usize = 1024;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct StoredEntry {
     pub creation: u64,
     pub tag_id: u64,
     pub expiration: u64,
+    pub source: SourceId,
+    /// Added locally with propagation, its expiry being reported
+    pub propagate: bool,
 }
 
 impl StoredEntry {
@@ -30,37 +38,79 @@ impl StoredEntry {
     }
 }
 
+/// Entry removed by the cleanup thread once expired, whose tag is still
+/// held until the receiver releases it
+#[derive(Debug, Clone, Copy)]
+pub struct ExpiredEntry {
+    pub policy: Policy,
+    pub cidr: NormalizedCidr,
+    pub stored: StoredEntry,
+}
+
 #[derive(Debug, thiserror::Error)]
-pub(crate) enum LpmStoreError {
+pub enum LpmStoreError {
     #[error("Error getting map info: {0}")]
     MapInfoError(String),
 }
 
-pub(crate) enum LpmMap {
+/// Kernel side of an [`LpmStore`], holding the tag of each CIDR for the XDP
+/// program
+pub trait LpmBackend: Send + Sync + 'static {
+    fn insert_entry(
+        &mut self,
+        cidr: &NormalizedCidr,
+        entry: &StoredEntry,
+    ) -> Result<(), CompositeError>;
+
+    fn remove_entry(&mut self, cidr: &NormalizedCidr) -> Result<(), CompositeError>;
+}
+
+impl LpmBackend for Box<dyn LpmBackend> {
+    fn insert_entry(
+        &mut self,
+        cidr: &NormalizedCidr,
+        entry: &StoredEntry,
+    ) -> Result<(), CompositeError> {
+        (**self).insert_entry(cidr, entry)
+    }
+
+    fn remove_entry(&mut self, cidr: &NormalizedCidr) -> Result<(), CompositeError> {
+        (**self).remove_entry(cidr)
+    }
+}
+
+pub enum LpmMap {
     V4(LpmTrie<MapData, u32, u64>),
     V6(LpmTrie<MapData, u128, u64>),
 }
 
 impl LpmMap {
+    /// Number of entries the kernel map holds at most
+    pub fn max_entries(&self) -> Result<usize, LpmStoreError> {
+        let info = match self {
+            LpmMap::V4(map) => map.map().info(),
+            LpmMap::V6(map) => map.map().info(),
+        };
+        info.map(|info| info.max_entries() as usize)
+            .map_err(|e| LpmStoreError::MapInfoError(e.to_string()))
+    }
+}
+
+impl LpmBackend for LpmMap {
     fn insert_entry(
         &mut self,
         cidr: &NormalizedCidr,
         entry: &StoredEntry,
     ) -> Result<(), CompositeError> {
+        let value = lpm_value(entry.tag_id, cidr.prefix_len());
         match self {
             LpmMap::V4(map) => {
-                let (prefix_len, addr) = cidr.to_lpm_key_v4().ok_or_else(|| {
-                    CompositeError::new(ErrorCode::Einvalid, "Expected IPv4 address")
-                })?;
-                let key = Key::new(prefix_len, addr);
-                map.insert(&key, entry.tag_id, 0)
+                let (prefix_len, addr) = lpm_key_v4(cidr)?;
+                map.insert(&Key::new(prefix_len, addr), value, 0)
             }
             LpmMap::V6(map) => {
-                let (prefix_len, addr) = cidr.to_lpm_key_v6().ok_or_else(|| {
-                    CompositeError::new(ErrorCode::Einvalid, "Expected IPv6 address")
-                })?;
-                let key = Key::new(prefix_len, addr);
-                map.insert(&key, entry.tag_id, 0)
+                let (prefix_len, addr) = lpm_key_v6(cidr)?;
+                map.insert(&Key::new(prefix_len, addr), value, 0)
             }
         }
         .map_err(|e| CompositeError::new(ErrorCode::Einternal, &format!("ebpf insert error: {e}")))
@@ -69,55 +119,331 @@ impl LpmMap {
     fn remove_entry(&mut self, cidr: &NormalizedCidr) -> Result<(), CompositeError> {
         match self {
             LpmMap::V4(map) => {
-                let (prefix_len, addr) = cidr.to_lpm_key_v4().ok_or_else(|| {
-                    CompositeError::new(ErrorCode::Einvalid, "Expected IPv4 address")
-                })?;
-                let key = Key::new(prefix_len, addr);
-                map.remove(&key)
+                let (prefix_len, addr) = lpm_key_v4(cidr)?;
+                map.remove(&Key::new(prefix_len, addr))
             }
             LpmMap::V6(map) => {
-                let (prefix_len, addr) = cidr.to_lpm_key_v6().ok_or_else(|| {
-                    CompositeError::new(ErrorCode::Einvalid, "Expected IPv6 address")
-                })?;
-                let key = Key::new(prefix_len, addr);
-                map.remove(&key)
+                let (prefix_len, addr) = lpm_key_v6(cidr)?;
+                map.remove(&Key::new(prefix_len, addr))
             }
         }
         .map_err(|e| CompositeError::new(ErrorCode::Einternal, &format!("ebpf delete error: {e}")))
     }
 }
 
-pub struct LpmStore {
-    ebpf_map: Arc<RwLock<LpmMap>>,
+/// Key of `cidr` in an IPv4 map, refused with `Einvalid` for an IPv6 CIDR
+/// before anything reaches the kernel
+fn lpm_key_v4(cidr: &NormalizedCidr) -> Result<(u32, u32), CompositeError> {
+    cidr.to_lpm_key_v4()
+        .ok_or_else(|| family_mismatch(cidr, "IPv4"))
+}
+
+/// Key of `cidr` in an IPv6 map, refused with `Einvalid` for an IPv4 CIDR
+/// before anything reaches the kernel
+fn lpm_key_v6(cidr: &NormalizedCidr) -> Result<(u32, u128), CompositeError> {
+    cidr.to_lpm_key_v6()
+        .ok_or_else(|| family_mismatch(cidr, "IPv6"))
+}
+
+fn family_mismatch(cidr: &NormalizedCidr, expected: &str) -> CompositeError {
+    let mut ce = CompositeError::new(ErrorCode::Einvalid, "IP version mismatch with map type");
+    ce.add_detail(
+        "cidr",
+        ErrorCode::Einvalid,
+        &format!("`{cidr}` is not an {expected} network"),
+    );
+    ce
+}
+
+/// Keeps the `Einvalid` refusal of a backend as is and reports anything
+/// else, e.g. a kernel error, as an unexpected internal error
+fn backend_error(e: CompositeError, action: &str) -> CompositeError {
+    if e.code == ErrorCode::Einvalid {
+        e
+    } else {
+        CompositeError::new(
+            ErrorCode::Einternal,
+            &format!("unexpected error occurs while {action} ebpf entry: {e}"),
+        )
+    }
+}
+
+/// Backend keeping no kernel state, to exercise the userspace bookkeeping
+/// alone, e.g. in benchmarks
+#[cfg(any(test, feature = "bench"))]
+#[derive(Debug, Default)]
+pub struct NullMap;
+
+#[cfg(any(test, feature = "bench"))]
+impl LpmBackend for NullMap {
+    fn insert_entry(
+        &mut self,
+        _cidr: &NormalizedCidr,
+        _entry: &StoredEntry,
+    ) -> Result<(), CompositeError> {
+        Ok(())
+    }
+
+    fn remove_entry(&mut self, _cidr: &NormalizedCidr) -> Result<(), CompositeError> {
+        Ok(())
+    }
+}
+
+/// Removes `cidr` from `items` if `check` accepts the stored entry and
+/// `remove` (the eBPF side of the removal) succeeds.
+fn remove_item_if<F, R>(
+    items: &mut HashMap<NormalizedCidr, StoredEntry>,
+    cidr: NormalizedCidr,
+    check: F,
+    remove: R,
+) -> Result<StoredEntry, CompositeError>
+where
+    F: FnOnce(&StoredEntry) -> Result<(), CompositeError>,
+    R: FnOnce() -> Result<(), CompositeError>,
+{
+    if let std::collections::hash_map::Entry::Occupied(entry) = items.entry(cidr) {
+        check(entry.get())?;
+
+        debug!("Removing entry: {cidr:?}");
+        remove()?;
+
+        Ok(entry.remove())
+    } else {
+        let mut ce = CompositeError::new(ErrorCode::Enotfound, "submitted entry not found");
+        ce.add_detail(
+            "cidr",
+            ErrorCode::Enotfound,
+            &format!("cidr `{cidr}` not found"),
+        );
+        Err(ce)
+    }
+}
+
+/// Deletes `cidr` from the eBPF map
+fn remove_from_map(
+    ebpf_map: &mut impl LpmBackend,
+    cidr: &NormalizedCidr,
+) -> Result<(), CompositeError> {
+    ebpf_map
+        .remove_entry(cidr)
+        .map_err(|e| backend_error(e, "deleting"))
+}
+
+/// Refuses a new `cidr` once the map holds `max_entries`, `reserved` of them
+/// being set aside for a reload, with `Efull` so it is told apart from a
+/// duplicate entry
+fn check_capacity(
+    items: &HashMap<NormalizedCidr, StoredEntry>,
     max_entries: usize,
+    reserved: usize,
+    cidr: NormalizedCidr,
+) -> Result<(), CompositeError> {
+    if items.len().saturating_add(reserved) >= max_entries && !items.contains_key(&cidr) {
+        let reserved = if reserved > 0 {
+            format!(", {reserved} of them reserved by a sets reload")
+        } else {
+            String::new()
+        };
+        return Err(CompositeError::new(
+            ErrorCode::Efull,
+            &format!("couic underlying ebpf map is full: max {max_entries} entries{reserved}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Conflict of an addition on a `cidr` already in the map
+fn already_exists(cidr: NormalizedCidr) -> CompositeError {
+    let mut ce = CompositeError::new(ErrorCode::Econflict, "submitted entry is not valid");
+    ce.add_detail(
+        "cidr",
+        ErrorCode::Econflict,
+        &format!("{cidr} already exists"),
+    );
+    ce
+}
+
+/// Result of each change of an `apply_batch`, the previous entry of its
+/// CIDR on success
+pub type BatchResults = Vec<(NormalizedCidr, Result<Option<StoredEntry>, CompositeError>)>;
+
+/// Change of an `apply_batch` staged in `items`, `staged` being the entry
+/// left there and `previous` the one it replaced, which the eBPF map still
+/// holds
+struct StagedChange {
+    index: usize,
+    cidr: NormalizedCidr,
+    staged: Option<StoredEntry>,
+    previous: Option<StoredEntry>,
+}
+
+/// Applies `changes` in chunks of at most `chunk_size`, `apply` holding the
+/// locks for a single chunk so that other callers can take them in between
+fn apply_in_chunks<T, R, F>(changes: Vec<T>, chunk_size: usize, mut apply: F) -> Vec<R>
+where
+    F: FnMut(Vec<T>) -> Vec<R>,
+{
+    let mut results = Vec::with_capacity(changes.len());
+    let mut changes = changes.into_iter().peekable();
+    while changes.peek().is_some() {
+        let chunk: Vec<T> = changes.by_ref().take(chunk_size.max(1)).collect();
+        results.extend(apply(chunk));
+        thread::yield_now();
+    }
+    results
+}
+
+/// Removes the entries expired at `now` that `remove_from_map` managed to
+/// delete from the eBPF map, returning them. Drop and ignore stores expire
+/// alike: once an ignore entry is gone, a covering drop entry applies again.
+fn purge_expired<F>(
+    items: &mut HashMap<NormalizedCidr, StoredEntry>,
+    now: u64,
+    mut remove_from_map: F,
+) -> Vec<(NormalizedCidr, StoredEntry)>
+where
+    F: FnMut(&NormalizedCidr) -> bool,
+{
+    let mut removed = Vec::new();
+    items.retain(|cidr, entry| {
+        if entry.expired(now) && remove_from_map(cidr) {
+            removed.push((*cidr, *entry));
+            false
+        } else {
+            true
+        }
+    });
+    removed
+}
+
+/// Most specific stored CIDR containing `ip`, probing prefix lengths from
+/// the longest down like the kernel LPM trie lookup
+fn longest_match(
+    items: &HashMap<NormalizedCidr, StoredEntry>,
+    ip: IpAddr,
+) -> Option<(NormalizedCidr, StoredEntry)> {
+    let max_prefix_len = if ip.is_ipv4() { 32 } else { 128 };
+    (0..=max_prefix_len).rev().find_map(|prefix_len| {
+        let cidr = NormalizedCidr::from_addr_and_prefix(ip, prefix_len).ok()?;
+        items.get(&cidr).map(|stored| (cidr, *stored))
+    })
+}
+
+/// Least specific stored CIDR containing every address of `cidr`, itself
+/// included
+fn covering_match<F>(
+    items: &HashMap<NormalizedCidr, StoredEntry>,
+    cidr: NormalizedCidr,
+    keep: F,
+) -> Option<NormalizedCidr>
+where
+    F: Fn(&StoredEntry) -> bool,
+{
+    (0..=cidr.prefix_len()).find_map(|prefix_len| {
+        let candidate = NormalizedCidr::from_addr_and_prefix(cidr.network(), prefix_len).ok()?;
+        items
+            .get(&candidate)
+            .is_some_and(&keep)
+            .then_some(candidate)
+    })
+}
+
+/// Changes made to the entries of a store, telling clients whether a listing
+/// they hold is still current
+#[derive(Debug)]
+struct Revision {
+    count: AtomicU64,
+    /// Unix timestamp of the last change, or of the store creation
+    modified: AtomicU64,
+}
+
+impl Revision {
+    fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            modified: AtomicU64::new(unix_now()),
+        }
+    }
+
+    fn bump(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.modified.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Number of changes so far, and the Unix timestamp of the last one
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.count.load(Ordering::Relaxed),
+            self.modified.load(Ordering::Relaxed),
+        )
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Room set aside in a store by [`LpmStore::reserve`], given back once
+/// dropped
+#[derive(Debug)]
+pub struct Reservation<'a> {
+    reserved: &'a AtomicUsize,
+    count: usize,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.reserved.fetch_sub(self.count, Ordering::Relaxed);
+    }
+}
+
+pub struct LpmStore<M: LpmBackend = Box<dyn LpmBackend>> {
+    ebpf_map: Arc<RwLock<M>>,
+    max_entries: usize,
+    /// Entries promised to a reload being applied, which additions cannot
+    /// take
+    reserved: AtomicUsize,
     items: Arc<RwLock<HashMap<NormalizedCidr, StoredEntry>>>,
-    tag_release_sender: Sender<TagId>,
+    revision: Arc<Revision>,
+    policy: Policy,
+    expiry_sender: Sender<ExpiredEntry>,
 }
 
-impl LpmStore {
-    pub fn new(ebpf_map: LpmMap, tag_release_sender: Sender<TagId>) -> Result<Self, LpmStoreError> {
-        // Get max entries from map info
-        let max_entries = match &ebpf_map {
-            LpmMap::V4(map) => match map.map().info() {
-                Ok(info) => info.max_entries() as usize,
-                Err(e) => return Err(LpmStoreError::MapInfoError(e.to_string())),
-            },
-            LpmMap::V6(map) => match map.map().info() {
-                Ok(info) => info.max_entries() as usize,
-                Err(e) => return Err(LpmStoreError::MapInfoError(e.to_string())),
-            },
-        };
+impl<M: LpmBackend> LpmStore<M> {
+    /// Store of at most `max_entries` entries, whose cleanup thread removes
+    /// the expired ones
+    pub fn new(
+        ebpf_map: M,
+        max_entries: usize,
+        policy: Policy,
+        expiry_sender: Sender<ExpiredEntry>,
+        cleanup: Cleanup,
+    ) -> Self {
+        let store = Self::with_capacity(ebpf_map, max_entries, policy, expiry_sender);
+        store.launch_cleanup_thread(cleanup);
+        store
+    }
 
-        let store = Self {
+    /// Store of at most `max_entries` entries, without the cleanup thread
+    /// removing the expired ones
+    pub fn with_capacity(
+        ebpf_map: M,
+        max_entries: usize,
+        policy: Policy,
+        expiry_sender: Sender<ExpiredEntry>,
+    ) -> Self {
+        Self {
             ebpf_map: Arc::new(RwLock::new(ebpf_map)),
             max_entries,
+            reserved: AtomicUsize::new(0),
             items: Arc::new(RwLock::new(HashMap::new())),
-            tag_release_sender,
-        };
-
-        store.launch_cleanup_thread();
-
-        Ok(store)
+            revision: Arc::new(Revision::new()),
+            policy,
+            expiry_sender,
+        }
     }
 
     pub fn add_stored(
@@ -130,15 +456,12 @@ impl LpmStore {
             .write()
             .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
 
-        if items.len() >= self.max_entries {
-            return Err(CompositeError::new(
-                ErrorCode::Econflict,
-                &format!(
-                    "couic underlying ebpf map is full: max {} entries",
-                    self.max_entries
-                ),
-            ));
-        }
+        check_capacity(
+            &items,
+            self.max_entries,
+            self.reserved.load(Ordering::Relaxed),
+            cidr,
+        )?;
 
         match items.entry(cidr) {
             std::collections::hash_map::Entry::Vacant(vacant_entry) => {
@@ -146,31 +469,35 @@ impl LpmStore {
                     CompositeError::new(ErrorCode::Einternal, "Failed to acquire ebpf_map lock")
                 })?;
 
-                ebpf_map.insert_entry(&cidr, &stored_entry).map_err(|e| {
-                    if e.to_string().contains("mismatch") {
-                        CompositeError::new(ErrorCode::Einvalid, &e.to_string())
-                    } else {
-                        CompositeError::new(
-                            ErrorCode::Einternal,
-                            &format!("unexpected error occurs while inserting ebpf entry: {e}"),
-                        )
-                    }
-                })?;
+                ebpf_map
+                    .insert_entry(&cidr, &stored_entry)
+                    .map_err(|e| backend_error(e, "inserting"))?;
 
                 vacant_entry.insert(stored_entry);
+                self.revision.bump();
                 Ok(())
             }
-            std::collections::hash_map::Entry::Occupied(_) => {
-                let mut ce =
-                    CompositeError::new(ErrorCode::Econflict, "submitted entry is not valid");
-                ce.add_detail(
-                    "cidr",
-                    ErrorCode::Econflict,
-                    &format!("{cidr} already exists"),
-                );
-                Err(ce)
-            }
+            std::collections::hash_map::Entry::Occupied(_) => Err(already_exists(cidr)),
+        }
+    }
+
+    /// Fails the way `add_stored` would for `cidr`, without adding it
+    pub fn check_add(&self, cidr: NormalizedCidr) -> Result<(), CompositeError> {
+        let items = self
+            .items
+            .read()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+
+        check_capacity(
+            &items,
+            self.max_entries,
+            self.reserved.load(Ordering::Relaxed),
+            cidr,
+        )?;
+        if items.contains_key(&cidr) {
+            return Err(already_exists(cidr));
         }
+        Ok(())
     }
 
     pub fn get_stored(&self, cidr: NormalizedCidr) -> Result<StoredEntry, CompositeError> {
@@ -190,105 +517,185 @@ impl LpmStore {
         })
     }
 
-    pub(crate) fn update_stored(
-        &self,
-        cidr: NormalizedCidr,
-        new_stored: StoredEntry,
-    ) -> Result<StoredEntry, CompositeError> {
-        let mut items = self
+    /// Checks with `check` that reload changes fit in the store, under the
+    /// lock of the additions, and sets aside the number of entries it
+    /// returns until the reservation is dropped. Additions made while the
+    /// changes are applied in chunks cannot take that room, so the reload
+    /// never overflows the map halfway.
+    pub fn reserve<F>(&self, check: F) -> Result<Reservation<'_>, CompositeError>
+    where
+        F: FnOnce(&HashMap<NormalizedCidr, StoredEntry>, usize) -> Result<usize, CompositeError>,
+    {
+        let items = self
             .items
             .write()
             .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        let available = self
+            .max_entries
+            .saturating_sub(self.reserved.load(Ordering::Relaxed));
+        let count = check(&items, available)?;
+        self.reserved.fetch_add(count, Ordering::Relaxed);
+        Ok(Reservation {
+            reserved: &self.reserved,
+            count,
+        })
+    }
 
-        if let Some(existing) = items.get_mut(&cidr) {
-            let old_stored = *existing;
-
-            // Update eBPF map if tag changed
-            if existing.tag_id != new_stored.tag_id {
-                let mut ebpf_map = self.ebpf_map.write().map_err(|_| {
-                    CompositeError::new(ErrorCode::Einternal, "Failed to acquire ebpf_map lock")
-                })?;
+    /// Applies reload changes, `Some` adding or replacing the entry of a
+    /// CIDR and `None` removing it, returning the previous entry of each.
+    ///
+    /// The whole batch is staged in `items` under one short critical section
+    /// without touching the eBPF map, so lookups see all of it or none of it.
+    /// The eBPF map then catches up in chunks of [`BATCH_CHUNK_SIZE`], the
+    /// locks being released in between so API calls are not stalled by large
+    /// reloads: until its chunk is written, a CIDR keeps matching packets as
+    /// it did before the batch. A change whose eBPF write fails is rolled
+    /// back in `items` and reported, unless an API call changed the CIDR
+    /// since, that call having written the eBPF map itself.
+    pub fn apply_batch(&self, changes: Vec<(NormalizedCidr, Option<StoredEntry>)>) -> BatchResults {
+        self.apply_batch_in_chunks(changes, BATCH_CHUNK_SIZE, || {})
+    }
 
-                ebpf_map.insert_entry(&cidr, &new_stored).map_err(|e| {
-                    CompositeError::new(ErrorCode::Einternal, &format!("ebpf update error: {e}"))
-                })?;
+    /// Same as `apply_batch` with chunks of `chunk_size`, `between` being
+    /// called without the locks after each chunk
+    fn apply_batch_in_chunks(
+        &self,
+        changes: Vec<(NormalizedCidr, Option<StoredEntry>)>,
+        chunk_size: usize,
+        mut between: impl FnMut(),
+    ) -> BatchResults {
+        let (mut results, staged) = self.stage_batch(changes);
+        let failed = apply_in_chunks(staged, chunk_size, |chunk| {
+            let failed = self.write_chunk(chunk);
+            between();
+            failed
+        });
+        for (index, e) in failed {
+            if let Some((_, result)) = results.get_mut(index) {
+                *result = Err(e);
             }
-
-            *existing = new_stored;
-            Ok(old_stored)
-        } else {
-            let mut ce = CompositeError::new(ErrorCode::Enotfound, "submitted entry not found");
-            ce.add_detail(
-                "cidr",
-                ErrorCode::Enotfound,
-                &format!("cidr `{cidr}` not found"),
-            );
-            Err(ce)
         }
+        results
     }
 
-    pub(crate) fn add_or_update_stored(
+    /// Applies the changes of `apply_batch` to `items` only, returning their
+    /// results and the changes left to write to the eBPF map
+    fn stage_batch(
         &self,
-        cidr: NormalizedCidr,
-        new_stored: StoredEntry,
-    ) -> Result<Option<StoredEntry>, CompositeError> {
-        let mut items = self
-            .items
-            .write()
-            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        changes: Vec<(NormalizedCidr, Option<StoredEntry>)>,
+    ) -> (BatchResults, Vec<StagedChange>) {
+        let Ok(mut items) = self.items.write() else {
+            let results = changes
+                .into_iter()
+                .map(|(cidr, _)| {
+                    (
+                        cidr,
+                        Err(CompositeError::new(
+                            ErrorCode::Einternal,
+                            "Failed to acquire lock",
+                        )),
+                    )
+                })
+                .collect();
+            return (results, Vec::new());
+        };
 
-        if items.len() >= self.max_entries && !items.contains_key(&cidr) {
-            return Err(CompositeError::new(
-                ErrorCode::Econflict,
-                &format!(
-                    "couic underlying ebpf map is full: max {} entries",
-                    self.max_entries
-                ),
-            ));
+        let mut staged = Vec::with_capacity(changes.len());
+        let results: Vec<_> = changes
+            .into_iter()
+            .enumerate()
+            .map(|(index, (cidr, change))| {
+                let result = match change {
+                    Some(new_stored) => check_capacity(&items, self.max_entries, 0, cidr)
+                        .map(|()| items.insert(cidr, new_stored)),
+                    None => remove_item_if(&mut items, cidr, |_| Ok(()), || Ok(())).map(Some),
+                };
+                // The eBPF map only holds the tag
+                if let Ok(previous) = result
+                    && previous.map(|stored| stored.tag_id) != change.map(|stored| stored.tag_id)
+                {
+                    staged.push(StagedChange {
+                        index,
+                        cidr,
+                        staged: change,
+                        previous,
+                    });
+                }
+                (cidr, result)
+            })
+            .collect();
+        if results.iter().any(|(_, result)| result.is_ok()) {
+            self.revision.bump();
         }
+        (results, staged)
+    }
 
-        match items.entry(cidr) {
-            std::collections::hash_map::Entry::Occupied(mut e) => {
-                let old_stored = *e.get();
-
-                // Update eBPF map if tag changed
-                if old_stored.tag_id != new_stored.tag_id {
-                    let mut ebpf_map = self.ebpf_map.write().map_err(|_| {
-                        CompositeError::new(ErrorCode::Einternal, "Failed to acquire ebpf_map lock")
-                    })?;
-
-                    ebpf_map.insert_entry(&cidr, &new_stored).map_err(|e| {
-                        CompositeError::new(
-                            ErrorCode::Einternal,
-                            &format!("ebpf update error: {e}"),
-                        )
-                    })?;
-                }
+    /// Writes a chunk of staged changes to the eBPF map under both locks,
+    /// returning the failed ones, which are rolled back in `items`
+    fn write_chunk(&self, chunk: Vec<StagedChange>) -> Vec<(usize, CompositeError)> {
+        let (Ok(mut items), Ok(mut ebpf_map)) = (self.items.write(), self.ebpf_map.write()) else {
+            return chunk
+                .into_iter()
+                .map(|change| {
+                    (
+                        change.index,
+                        CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"),
+                    )
+                })
+                .collect();
+        };
 
-                e.insert(new_stored);
-                Ok(Some(old_stored))
+        let mut failed = Vec::new();
+        for change in chunk {
+            // Changed since it was staged, by a call that wrote the eBPF map
+            if items.get(&change.cidr) != change.staged.as_ref() {
+                continue;
             }
-            std::collections::hash_map::Entry::Vacant(vacant_entry) => {
-                let mut ebpf_map = self.ebpf_map.write().map_err(|_| {
-                    CompositeError::new(ErrorCode::Einternal, "Failed to acquire ebpf_map lock")
-                })?;
-
-                ebpf_map.insert_entry(&cidr, &new_stored).map_err(|e| {
-                    if e.to_string().contains("mismatch") {
-                        CompositeError::new(ErrorCode::Einvalid, &e.to_string())
-                    } else {
-                        CompositeError::new(
-                            ErrorCode::Einternal,
-                            &format!("unexpected error occurs while inserting ebpf entry: {e}"),
-                        )
-                    }
-                })?;
-
-                debug!("Adding entry: {cidr:?}");
-                vacant_entry.insert(new_stored);
-                Ok(None)
+            let written = match &change.staged {
+                Some(new_stored) => ebpf_map
+                    .insert_entry(&change.cidr, new_stored)
+                    .map_err(|e| backend_error(e, "inserting")),
+                None => remove_from_map(&mut *ebpf_map, &change.cidr),
+            };
+            if let Err(e) = written {
+                match change.previous {
+                    Some(previous) => items.insert(change.cidr, previous),
+                    None => items.remove(&change.cidr),
+                };
+                failed.push((change.index, e));
             }
         }
+        if !failed.is_empty() {
+            self.revision.bump();
+        }
+        failed
+    }
+
+    pub fn longest_match(
+        &self,
+        ip: IpAddr,
+    ) -> Result<Option<(NormalizedCidr, StoredEntry)>, CompositeError> {
+        let items = self
+            .items
+            .read()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        Ok(longest_match(&items, ip))
+    }
+
+    /// Entry covering the whole of `cidr` among those `keep` accepts
+    pub fn covering<F>(
+        &self,
+        cidr: NormalizedCidr,
+        keep: F,
+    ) -> Result<Option<NormalizedCidr>, CompositeError>
+    where
+        F: Fn(&StoredEntry) -> bool,
+    {
+        let items = self
+            .items
+            .read()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        Ok(covering_match(&items, cidr, keep))
     }
 
     pub fn list_stored(&self) -> Result<Vec<(NormalizedCidr, StoredEntry)>, CompositeError> {
@@ -303,40 +710,30 @@ impl LpmStore {
             .collect())
     }
 
-    pub fn remove_stored(&self, cidr: NormalizedCidr) -> Result<StoredEntry, CompositeError> {
+    /// Removes an entry only if `check` accepts it, the check and the removal
+    /// happening under the same write lock.
+    pub fn remove_stored_if<F>(
+        &self,
+        cidr: NormalizedCidr,
+        check: F,
+    ) -> Result<StoredEntry, CompositeError>
+    where
+        F: FnOnce(&StoredEntry) -> Result<(), CompositeError>,
+    {
         let mut items = self
             .items
             .write()
             .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
 
-        if let std::collections::hash_map::Entry::Occupied(entry) = items.entry(cidr) {
-            debug!("Removing entry: {cidr:?}");
-
+        let removed = remove_item_if(&mut items, cidr, check, || {
             let mut ebpf_map = self.ebpf_map.write().map_err(|_| {
                 CompositeError::new(ErrorCode::Einternal, "Failed to acquire ebpf_map lock")
             })?;
 
-            ebpf_map.remove_entry(&cidr).map_err(|e| {
-                if e.to_string().contains("mismatch") {
-                    CompositeError::new(ErrorCode::Einvalid, &e.to_string())
-                } else {
-                    CompositeError::new(
-                        ErrorCode::Einternal,
-                        &format!("unexpected error occurs while deleting ebpf entry: {e}"),
-                    )
-                }
-            })?;
-
-            Ok(entry.remove())
-        } else {
-            let mut ce = CompositeError::new(ErrorCode::Enotfound, "submitted entry not found");
-            ce.add_detail(
-                "cidr",
-                ErrorCode::Enotfound,
-                &format!("cidr `{cidr}` not found"),
-            );
-            Err(ce)
-        }
+            remove_from_map(&mut *ebpf_map, &cidr)
+        })?;
+        self.revision.bump();
+        Ok(removed)
     }
 
     pub(crate) fn list_sets_stored(&self) -> Vec<(NormalizedCidr, StoredEntry)> {
@@ -351,6 +748,27 @@ impl LpmStore {
             .unwrap_or_default()
     }
 
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    /// Changes made to the entries so far, expirations included
+    pub fn revision(&self) -> (u64, u64) {
+        self.revision.get()
+    }
+
+    /// Release the memory of removed entries now rather than at the next
+    /// periodic shrink, returning the capacity before and after
+    pub fn compact(&self) -> Result<(usize, usize), CompositeError> {
+        let mut items = self
+            .items
+            .write()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        let before = items.capacity();
+        items.shrink_to_fit();
+        Ok((before, items.capacity()))
+    }
+
     pub fn count(&self) -> usize {
         self.items
             .read()
@@ -358,16 +776,23 @@ impl LpmStore {
             .unwrap_or_default()
     }
 
-    fn launch_cleanup_thread(&self) {
+    fn launch_cleanup_thread(&self, cleanup: Cleanup) {
+        // Zero values are rejected by config validation, guard anyway so the
+        // thread never busy-loops nor stops shrinking
+        let interval = Duration::from_secs(cleanup.interval_secs.max(1));
+        let shrink_interval_cycles = cleanup.shrink_interval_cycles.max(1);
+
         let items_clone = self.items.clone();
         let ebpf_map_clone = self.ebpf_map.clone();
-        let tag_release_sender = self.tag_release_sender.clone();
+        let revision = self.revision.clone();
+        let policy = self.policy;
+        let expiry_sender = self.expiry_sender.clone();
 
         thread::spawn(move || {
             let mut cycle_count: u32 = 0;
 
             loop {
-                thread::sleep(CLEANUP_INTERVAL);
+                thread::sleep(interval);
                 cycle_count = cycle_count.wrapping_add(1);
 
                 let Ok(mut items) = items_clone.write() else {
@@ -376,7 +801,7 @@ impl LpmStore {
                 };
 
                 // Shrink HashMap capacity periodically
-                if cycle_count.is_multiple_of(SHRINK_INTERVAL_CYCLES) {
+                if cycle_count.is_multiple_of(shrink_interval_cycles) {
                     let len = items.len();
                     let capacity = items.capacity();
                     // HashMap uses ~87.5% load factor, so minimum capacity for len items is ceil(len * 8/7)
@@ -396,33 +821,18 @@ impl LpmStore {
                     continue;
                 }
 
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
+                let now = unix_now();
 
                 let Ok(mut ebpf_map) = ebpf_map_clone.write() else {
                     error!("cleanup error: Failed to acquire ebpf_map lock");
                     continue;
                 };
 
-                let mut removed_count = 0;
-                items.retain(|cidr, entry| {
-                    if !entry.expired(now) {
-                        return true;
-                    }
-
+                let removed = purge_expired(&mut items, now, |cidr| {
                     match ebpf_map.remove_entry(cidr) {
-                        Ok(()) => {
-                            // Send tag_id to release channel
-                            if let Err(e) = tag_release_sender.send(entry.tag_id) {
-                                error!("cleanup: Failed to send tag {} for release: {e}", entry.tag_id);
-                            }
-                            removed_count += 1;
-                            false
-                        }
+                        Ok(()) => true,
                         Err(e) => {
-                            if e.to_string().contains("mismatch") {
+                            if e.code == ErrorCode::Einvalid {
                                 error!(
                                     "cleanup error: IP version mismatch with map type for {cidr:?}"
                                 );
@@ -431,10 +841,28 @@ impl LpmStore {
                                     "cleanup error: unexpected error while deleting ebpf entry {cidr:?}: {e}"
                                 );
                             }
-                            true
+                            false
                         }
                     }
                 });
+                if !removed.is_empty() {
+                    revision.bump();
+                }
+                drop(ebpf_map);
+                drop(items);
+
+                let removed_count = removed.len();
+                for (cidr, stored) in removed {
+                    // Hand over to the expiry worker, which releases the tag
+                    let expired = ExpiredEntry {
+                        policy,
+                        cidr,
+                        stored,
+                    };
+                    if let Err(e) = expiry_sender.send(expired) {
+                        error!("cleanup: Failed to send expired entry {cidr} for release: {e}");
+                    }
+                }
 
                 if removed_count > 0 {
                     info!("cleanup: removed {removed_count} expired entries");
@@ -443,3 +871,461 @@ impl LpmStore {
         });
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::firewall::source::UNKNOWN_SOURCE;
+    use crate::firewall::tag::TagId;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    const SET_TAG: TagId = 1;
+    const MANUAL_TAG: TagId = 2;
+
+    fn stored(tag_id: TagId) -> StoredEntry {
+        StoredEntry {
+            creation: 0,
+            tag_id,
+            expiration: 0,
+            source: UNKNOWN_SOURCE,
+            propagate: false,
+        }
+    }
+
+    fn null_store(max_entries: usize) -> LpmStore<NullMap> {
+        let (sender, _) = crossbeam_channel::unbounded();
+        LpmStore::with_capacity(NullMap, max_entries, Policy::Drop, sender)
+    }
+
+    /// eBPF map recording the tag of each CIDR, failing to insert the
+    /// CIDRs of `failing`
+    #[derive(Clone, Default)]
+    struct RecordingMap {
+        tags: Arc<Mutex<HashMap<NormalizedCidr, TagId>>>,
+        failing: Arc<Mutex<HashSet<NormalizedCidr>>>,
+    }
+
+    impl RecordingMap {
+        fn tags(&self) -> HashMap<NormalizedCidr, TagId> {
+            self.tags.lock().unwrap().clone()
+        }
+    }
+
+    impl LpmBackend for RecordingMap {
+        fn insert_entry(
+            &mut self,
+            cidr: &NormalizedCidr,
+            entry: &StoredEntry,
+        ) -> Result<(), CompositeError> {
+            if self.failing.lock().unwrap().contains(cidr) {
+                return Err(CompositeError::new(ErrorCode::Einternal, "insert failed"));
+            }
+            self.tags.lock().unwrap().insert(*cidr, entry.tag_id);
+            Ok(())
+        }
+
+        fn remove_entry(&mut self, cidr: &NormalizedCidr) -> Result<(), CompositeError> {
+            self.tags
+                .lock()
+                .unwrap()
+                .remove(cidr)
+                .map(|_| ())
+                .ok_or_else(|| CompositeError::new(ErrorCode::Enotfound, "not found"))
+        }
+    }
+
+    fn recording_store(map: &RecordingMap, max_entries: usize) -> LpmStore<RecordingMap> {
+        let (sender, _) = crossbeam_channel::unbounded();
+        LpmStore::with_capacity(map.clone(), max_entries, Policy::Drop, sender)
+    }
+
+    /// Tags of the entries lookups see
+    fn stored_tags<M: LpmBackend>(store: &LpmStore<M>) -> HashMap<NormalizedCidr, TagId> {
+        store
+            .list_stored()
+            .unwrap()
+            .into_iter()
+            .map(|(cidr, stored)| (cidr, stored.tag_id))
+            .collect()
+    }
+
+    fn cidrs(prefix: &str, count: u8) -> Vec<NormalizedCidr> {
+        (0..count)
+            .map(|i| format!("{prefix}.{i}/32").parse().unwrap())
+            .collect()
+    }
+
+    fn reject_set(entry: &StoredEntry) -> Result<(), CompositeError> {
+        if entry.tag_id == SET_TAG {
+            return Err(CompositeError::new(ErrorCode::Einvalid, "in a set"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn longest_match_prefers_most_specific_prefix() {
+        let wide: NormalizedCidr = "10.0.0.0/8".parse().unwrap();
+        let narrow: NormalizedCidr = "10.1.0.0/16".parse().unwrap();
+        let host: NormalizedCidr = "2001:db8::1/128".parse().unwrap();
+        let items = HashMap::from([
+            (wide, stored(SET_TAG)),
+            (narrow, stored(MANUAL_TAG)),
+            (host, stored(MANUAL_TAG)),
+        ]);
+
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert_eq!(longest_match(&items, ip("10.1.2.3")).unwrap().0, narrow);
+        assert_eq!(longest_match(&items, ip("10.2.0.1")).unwrap().0, wide);
+        assert_eq!(longest_match(&items, ip("2001:db8::1")).unwrap().0, host);
+        assert!(longest_match(&items, ip("2001:db8::2")).is_none());
+        assert!(longest_match(&items, ip("192.0.2.1")).is_none());
+    }
+
+    #[test]
+    fn longest_match_handles_default_route() {
+        let any: NormalizedCidr = "0.0.0.0/0".parse().unwrap();
+        let items = HashMap::from([(any, stored(MANUAL_TAG))]);
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+        assert_eq!(longest_match(&items, ip).unwrap().0, any);
+    }
+
+    #[test]
+    fn covering_match_needs_the_whole_range() {
+        let wide: NormalizedCidr = "10.0.0.0/8".parse().unwrap();
+        let host: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let items = HashMap::from([(wide, stored(MANUAL_TAG)), (host, stored(MANUAL_TAG))]);
+
+        let cidr = |s: &str| s.parse::<NormalizedCidr>().unwrap();
+        let any = |_: &StoredEntry| true;
+        assert_eq!(covering_match(&items, cidr("10.1.0.0/16"), any), Some(wide));
+        assert_eq!(covering_match(&items, wide, any), Some(wide));
+        assert_eq!(covering_match(&items, host, any), Some(host));
+        // Only part of the range is covered
+        assert!(covering_match(&items, cidr("192.0.2.0/24"), any).is_none());
+        assert!(covering_match(&items, cidr("2001:db8::/32"), any).is_none());
+        // Entries refused by the filter cover nothing
+        let expiring = StoredEntry {
+            expiration: 1,
+            ..stored(MANUAL_TAG)
+        };
+        let items = HashMap::from([(wide, stored(MANUAL_TAG)), (host, expiring)]);
+        let permanent = |stored: &StoredEntry| stored.expiration == 0;
+        assert!(covering_match(&items, host, permanent).is_none());
+        assert_eq!(
+            covering_match(&items, cidr("10.0.0.1/32"), permanent),
+            Some(wide)
+        );
+    }
+
+    #[test]
+    fn check_capacity_tells_full_map_apart() {
+        let existing: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let new: NormalizedCidr = "192.0.2.2/32".parse().unwrap();
+        let items = HashMap::from([(existing, stored(MANUAL_TAG))]);
+
+        assert!(check_capacity(&items, 2, 0, new).is_ok());
+        // Replacing or rejecting a duplicate does not need room
+        assert!(check_capacity(&items, 1, 0, existing).is_ok());
+        let err = check_capacity(&items, 1, 0, new).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Efull);
+        assert_eq!(
+            err.message,
+            "couic underlying ebpf map is full: max 1 entries"
+        );
+    }
+
+    #[test]
+    fn purge_expired_keeps_entries_failing_map_removal() {
+        let expired: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let stuck: NormalizedCidr = "192.0.2.2/32".parse().unwrap();
+        let entry = StoredEntry {
+            creation: 0,
+            tag_id: MANUAL_TAG,
+            expiration: 100,
+            source: UNKNOWN_SOURCE,
+            propagate: false,
+        };
+        let mut items = HashMap::from([(expired, entry), (stuck, entry)]);
+
+        assert!(purge_expired(&mut items, 99, |_| true).is_empty());
+        let removed = purge_expired(&mut items, 100, |cidr| *cidr == expired);
+        assert_eq!(removed, vec![(expired, entry)]);
+        assert_eq!(items.len(), 1);
+        assert!(items.contains_key(&stuck));
+    }
+
+    #[test]
+    fn drop_applies_again_after_ignore_expiry() {
+        let drop_range: NormalizedCidr = "10.0.0.0/8".parse().unwrap();
+        let temporary: NormalizedCidr = "10.0.0.5/32".parse().unwrap();
+        let permanent: NormalizedCidr = "10.0.0.6/32".parse().unwrap();
+        let drop_items = HashMap::from([(drop_range, stored(MANUAL_TAG))]);
+        let mut ignore_items = HashMap::from([
+            (
+                temporary,
+                StoredEntry {
+                    creation: 0,
+                    tag_id: MANUAL_TAG,
+                    expiration: 100,
+                    source: UNKNOWN_SOURCE,
+                    propagate: false,
+                },
+            ),
+            (permanent, stored(MANUAL_TAG)),
+        ]);
+
+        // The XDP program looks the ignore maps up before the drop ones
+        let verdict = |ignore: &HashMap<NormalizedCidr, StoredEntry>, ip: &str| {
+            let ip: IpAddr = ip.parse().unwrap();
+            let to_entry = |(cidr, _): (NormalizedCidr, StoredEntry)| common::Entry {
+                creation: 0,
+                cidr,
+                tag: None,
+                expiration: common::Expiration::never(),
+                source: None,
+            };
+            common::Classification::new(
+                ip,
+                longest_match(ignore, ip).map(to_entry),
+                longest_match(&drop_items, ip).map(to_entry),
+            )
+            .decision
+        };
+        assert_eq!(verdict(&ignore_items, "10.0.0.5"), common::Decision::Ignore);
+
+        let removed = purge_expired(&mut ignore_items, 100, |_| true);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(verdict(&ignore_items, "10.0.0.5"), common::Decision::Drop);
+        assert_eq!(verdict(&ignore_items, "10.0.0.6"), common::Decision::Ignore);
+    }
+
+    #[test]
+    fn apply_batch_is_seen_whole_while_the_map_catches_up() {
+        let map = RecordingMap::default();
+        let store = recording_store(&map, 100);
+        let stale = cidrs("198.51.100", 4);
+        for cidr in &stale {
+            store.add_stored(*cidr, stored(SET_TAG)).unwrap();
+        }
+        let reloaded = cidrs("192.0.2", 10);
+        let changes: Vec<_> = stale
+            .iter()
+            .map(|cidr| (*cidr, None))
+            .chain(reloaded.iter().map(|cidr| (*cidr, Some(stored(SET_TAG)))))
+            .collect();
+        let manual: NormalizedCidr = "203.0.113.1/32".parse().unwrap();
+
+        // Lookups never see the batch partly applied, while the eBPF map is
+        // written chunk by chunk, API calls getting in between
+        let mut written = Vec::new();
+        let results = store.apply_batch_in_chunks(changes.clone(), 3, || {
+            let tags = stored_tags(&store);
+            assert!(reloaded.iter().all(|cidr| tags.get(cidr) == Some(&SET_TAG)));
+            assert!(stale.iter().all(|cidr| !tags.contains_key(cidr)));
+            written.push(map.tags().len());
+            if written.len() == 1 {
+                store.add_stored(manual, stored(MANUAL_TAG)).unwrap();
+            }
+        });
+
+        assert_eq!(written, vec![1, 3, 6, 9, 11]);
+        assert_eq!(results.len(), changes.len());
+        let (removed, added) = results.split_at(stale.len());
+        assert!(
+            removed
+                .iter()
+                .all(|(_, result)| matches!(result, Ok(Some(_))))
+        );
+        assert!(added.iter().all(|(_, result)| matches!(result, Ok(None))));
+        assert_eq!(map.tags(), stored_tags(&store));
+    }
+
+    #[test]
+    fn apply_batch_keeps_changes_made_between_chunks() {
+        let map = RecordingMap::default();
+        let store = recording_store(&map, 100);
+        let replaced: NormalizedCidr = "198.51.100.1/32".parse().unwrap();
+        store.add_stored(replaced, stored(SET_TAG)).unwrap();
+        let reloaded: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+
+        // Added again by the API once the reload removed it, before its
+        // chunk is written
+        let results = store.apply_batch_in_chunks(
+            vec![(reloaded, Some(stored(SET_TAG))), (replaced, None)],
+            1,
+            || {
+                let _ = store.add_stored(replaced, stored(MANUAL_TAG));
+            },
+        );
+
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        let expected = HashMap::from([(reloaded, SET_TAG), (replaced, MANUAL_TAG)]);
+        assert_eq!(stored_tags(&store), expected);
+        assert_eq!(map.tags(), expected);
+    }
+
+    #[test]
+    fn apply_batch_rolls_back_failed_writes() {
+        let map = RecordingMap::default();
+        let store = recording_store(&map, 100);
+        let updated: NormalizedCidr = "198.51.100.1/32".parse().unwrap();
+        store.add_stored(updated, stored(MANUAL_TAG)).unwrap();
+        let failing: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let added: NormalizedCidr = "192.0.2.2/32".parse().unwrap();
+        map.failing.lock().unwrap().extend([updated, failing]);
+
+        let results = store.apply_batch(vec![
+            (updated, Some(stored(SET_TAG))),
+            (failing, Some(stored(SET_TAG))),
+            (added, Some(stored(SET_TAG))),
+        ]);
+
+        let failed: Vec<NormalizedCidr> = results
+            .iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(cidr, _)| *cidr)
+            .collect();
+        assert_eq!(failed, vec![updated, failing]);
+        let expected = HashMap::from([(updated, MANUAL_TAG), (added, SET_TAG)]);
+        assert_eq!(stored_tags(&store), expected);
+        assert_eq!(map.tags(), expected);
+    }
+
+    #[test]
+    fn reservation_keeps_additions_out() {
+        let store = null_store(2);
+        let manual: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let reloaded: NormalizedCidr = "192.0.2.2/32".parse().unwrap();
+        let added: NormalizedCidr = "192.0.2.3/32".parse().unwrap();
+        store.add_stored(manual, stored(MANUAL_TAG)).unwrap();
+
+        let reservation = store
+            .reserve(|items, available| {
+                assert_eq!((items.len(), available), (1, 2));
+                Ok(1)
+            })
+            .unwrap();
+        let err = store.add_stored(added, stored(MANUAL_TAG)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Efull);
+        assert!(err.message.contains("1 of them reserved"));
+        assert_eq!(store.check_add(added).unwrap_err().code, ErrorCode::Efull);
+        // The reload takes the room set aside for it
+        for (_, result) in store.apply_batch(vec![(reloaded, Some(stored(SET_TAG)))]) {
+            assert_eq!(result.unwrap(), None);
+        }
+        drop(reservation);
+        assert_eq!(store.count(), 2);
+    }
+
+    #[test]
+    fn dropped_reservation_gives_room_back() {
+        let store = null_store(2);
+        let added: NormalizedCidr = "192.0.2.3/32".parse().unwrap();
+        let reservation = store.reserve(|_, _| Ok(2)).unwrap();
+        assert!(store.check_add(added).is_err());
+        drop(reservation);
+        store.add_stored(added, stored(MANUAL_TAG)).unwrap();
+    }
+
+    #[test]
+    fn remove_item_if_removes_accepted_entry() {
+        let cidr: NormalizedCidr = "192.0.2.0/24".parse().unwrap();
+        let mut items = HashMap::from([(cidr, stored(MANUAL_TAG))]);
+
+        let removed = remove_item_if(&mut items, cidr, reject_set, || Ok(())).unwrap();
+        assert_eq!(removed.tag_id, MANUAL_TAG);
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn remove_item_if_keeps_rejected_entry() {
+        let cidr: NormalizedCidr = "192.0.2.0/24".parse().unwrap();
+        let mut items = HashMap::from([(cidr, stored(SET_TAG))]);
+
+        let err = remove_item_if(&mut items, cidr, reject_set, || {
+            panic!("eBPF removal must not run for a rejected entry")
+        })
+        .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einvalid);
+        assert!(items.contains_key(&cidr));
+    }
+
+    #[test]
+    fn remove_item_if_keeps_entry_when_removal_fails() {
+        let cidr: NormalizedCidr = "192.0.2.0/24".parse().unwrap();
+        let mut items = HashMap::from([(cidr, stored(MANUAL_TAG))]);
+
+        let result = remove_item_if(&mut items, cidr, reject_set, || {
+            Err(CompositeError::new(ErrorCode::Einternal, "ebpf"))
+        });
+        assert!(result.is_err());
+        assert!(items.contains_key(&cidr));
+    }
+
+    #[test]
+    fn remove_item_if_missing_entry_is_not_found() {
+        let cidr: NormalizedCidr = "192.0.2.0/24".parse().unwrap();
+        let mut items = HashMap::new();
+
+        let err = remove_item_if(&mut items, cidr, reject_set, || Ok(())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Enotfound);
+    }
+
+    #[test]
+    fn remove_stored_if_is_not_raced_by_set_updates() {
+        let cidr: NormalizedCidr = "192.0.2.0/24".parse().unwrap();
+        let store = Arc::new(null_store(16));
+        store.add_stored(cidr, stored(MANUAL_TAG)).unwrap();
+        let (checking, checked) = std::sync::mpsc::channel();
+
+        // A set reload turns the entry into a set entry while the removal
+        // is checking it
+        let writer = {
+            let store = Arc::clone(&store);
+            thread::spawn(move || {
+                checked.recv().unwrap();
+                for (_, result) in store.apply_batch(vec![(cidr, Some(stored(SET_TAG)))]) {
+                    result.unwrap();
+                }
+            })
+        };
+
+        let removed = store
+            .remove_stored_if(cidr, |entry| {
+                checking.send(()).unwrap();
+                thread::sleep(Duration::from_millis(50));
+                reject_set(entry)
+            })
+            .unwrap();
+        writer.join().unwrap();
+
+        // The manual entry checked is the one removed, the set entry being
+        // added once the removal is done
+        assert_eq!(removed.tag_id, MANUAL_TAG);
+        assert_eq!(store.get_stored(cidr).unwrap().tag_id, SET_TAG);
+    }
+
+    #[test]
+    fn lpm_keys_refuse_the_other_family() {
+        let v4: NormalizedCidr = "10.0.0.0/8".parse().unwrap();
+        let v6: NormalizedCidr = "2001:db8::/32".parse().unwrap();
+        assert!(lpm_key_v4(&v4).is_ok());
+        assert!(lpm_key_v6(&v6).is_ok());
+        assert_eq!(lpm_key_v4(&v6).unwrap_err().code, ErrorCode::Einvalid);
+        assert_eq!(lpm_key_v6(&v4).unwrap_err().code, ErrorCode::Einvalid);
+    }
+
+    #[test]
+    fn backend_error_keeps_only_invalid_refusals() {
+        let v6: NormalizedCidr = "2001:db8::/32".parse().unwrap();
+        let refused = backend_error(lpm_key_v4(&v6).unwrap_err(), "inserting");
+        assert_eq!(refused.code, ErrorCode::Einvalid);
+
+        let kernel = CompositeError::new(ErrorCode::Einternal, "ebpf insert error: E2BIG");
+        let unexpected = backend_error(kernel, "inserting");
+        assert_eq!(unexpected.code, ErrorCode::Einternal);
+        assert!(unexpected.message.contains("E2BIG"));
+    }
+}
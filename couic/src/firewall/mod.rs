@@ -1,5 +1,12 @@
-mod lpm;
+pub mod backend;
+mod expired;
+mod hotplug;
+mod iface;
+pub(crate) mod lpm;
 mod peer;
 pub mod reporting;
 pub mod service;
+mod signature;
+mod source;
 mod tag;
+mod watch;
@@ -1,19 +1,19 @@
 use std::collections::HashSet;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::{Receiver, Sender, TrySendError, bounded};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError, bounded};
 use tracing::{error, info, warn};
 
 use client::{ApiVersion, CouicClient, CouicError, RemoteConfig};
 
-use crate::config::Config;
+use crate::config::{Config, Peering};
 
-use common::{Action, Entry, PeerJob, RawEntry, Tag};
+use common::{Action, Entry, PeerJob, RawEntry, Tag, feature};
 
-const PEERING_INTERVAL: Duration = Duration::from_millis(200);
 const MAX_BACKOFF: Duration = Duration::from_secs(60);
 const MAX_BUFFER_SIZE: usize = 1 << 14; // Max number of pending jobs
+const PEER_USER_AGENT: &str = concat!("couic-peer/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Debug, thiserror::Error)]
 pub enum PeerServiceError {
@@ -27,11 +27,74 @@ pub struct PeerService {
     sender: Sender<PeerJob>,
 }
 
+/// How jobs are grouped into the requests sent to peers
+#[derive(Debug, Clone, Copy)]
+struct Batching {
+    size: usize,
+    max_age: Duration,
+    compression: bool,
+}
+
+/// Client of a peer, with whether it decompresses request bodies
+struct Peer {
+    client: CouicClient,
+    /// Unknown until asked to the peer, and again after a failed batch, as
+    /// the peer may have been upgraded or downgraded
+    gzip: Option<bool>,
+}
+
+impl Peer {
+    /// Whether the peer advertises gzip request bodies. Asked once to its
+    /// version endpoint, older peers not serving it being sent plain bodies.
+    fn accepts_gzip(&mut self) -> bool {
+        if let Some(gzip) = self.gzip {
+            return gzip;
+        }
+        match self.client.server_version() {
+            Ok(version) => {
+                let gzip = version.supports(feature::GZIP_REQUESTS);
+                if !gzip {
+                    info!(
+                        "Peer {} does not accept compressed batches, sending them uncompressed",
+                        self.client.info()
+                    );
+                }
+                self.gzip = Some(gzip);
+                gzip
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to get the features of peer {}, sending the batch uncompressed: {e}",
+                    self.client.info()
+                );
+                false
+            }
+        }
+    }
+}
+
+impl From<&Peering> for Batching {
+    fn from(peering: &Peering) -> Self {
+        Self {
+            size: peering.batch_size,
+            max_age: Duration::from_millis(peering.batch_max_age_ms),
+            compression: peering.compression,
+        }
+    }
+}
+
 impl PeerService {
     pub fn new(config: &Config) -> Result<Self, PeerServiceError> {
+        let peering = config
+            .peering
+            .as_ref()
+            .ok_or_else(|| PeerServiceError::Config("Missing peering configuration".into()))?;
         let (sender, receiver) = bounded::<PeerJob>(MAX_BUFFER_SIZE);
-        let clients = Self::initialize_clients(config)?;
-        Self::spawn_worker(clients, receiver);
+        let peers = Self::initialize_clients(peering)?
+            .into_iter()
+            .map(|client| Peer { client, gzip: None })
+            .collect();
+        Self::spawn_worker(peers, receiver, Batching::from(peering));
         Ok(Self { sender })
     }
 
@@ -65,79 +128,39 @@ impl PeerService {
         }
     }
 
-    fn initialize_clients(config: &Config) -> Result<Vec<CouicClient>, PeerServiceError> {
-        if let Some(peering) = &config.peering {
-            peering
-                .peers
-                .iter()
-                .map(|peer| {
-                    let rc = RemoteConfig {
-                        host: peer.host.clone(),
-                        token: peer.token,
-                        port: peer.port,
-                        tls: peer.tls,
-                    };
-                    CouicClient::builder()
-                        .version(ApiVersion::V1)
-                        .build_remote(&rc)
-                        .map_err(PeerServiceError::Couic)
-                })
-                .collect()
-        } else {
-            Err(PeerServiceError::Config(
-                "Missing peering configuration".into(),
-            ))
-        }
+    fn initialize_clients(peering: &Peering) -> Result<Vec<CouicClient>, PeerServiceError> {
+        peering
+            .peers
+            .iter()
+            .map(|peer| {
+                let rc = RemoteConfig::new(peer.host.clone(), peer.port, peer.token, peer.tls);
+                CouicClient::builder()
+                    .version(ApiVersion::V1)
+                    .user_agent(PEER_USER_AGENT)
+                    .build_remote(&rc)
+                    .map_err(PeerServiceError::Couic)
+            })
+            .collect()
     }
 
-    fn spawn_worker(mut clients: Vec<CouicClient>, receiver: Receiver<PeerJob>) {
+    fn spawn_worker(mut peers: Vec<Peer>, receiver: Receiver<PeerJob>, batching: Batching) {
         thread::spawn(move || {
             let mut buffer_set = HashSet::with_capacity(4096);
             let mut backoff_delay = Duration::from_secs(1);
 
-            loop {
-                // Wait for a new job, with timeout
-                match receiver.recv_timeout(PEERING_INTERVAL) {
-                    Ok(job) => {
-                        buffer_set.insert(job);
-                    }
-                    Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
-                        // no job this interval
-                    }
-                    Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
-                        warn!("Peer job channel disconnected; worker exiting");
-                        break;
-                    }
-                }
-
-                // Drain any additional queued jobs
-                buffer_set.extend(receiver.try_iter());
+            // Wait for the first job of a batch
+            while let Ok(job) = receiver.recv() {
+                buffer_set.insert(job);
+                let disconnected = Self::coalesce(&receiver, &mut buffer_set, batching);
                 let buffer: Vec<PeerJob> = buffer_set.drain().collect();
-
-                if buffer.is_empty() {
-                    continue;
-                }
-
-                let mut all_success = true;
-
-                // Send all jobs to every configured client
-                for client in &mut clients {
-                    match client.peer().drop(&buffer) {
-                        Ok(_) => info!(
-                            "Peering sync successful with {} ({} jobs)",
-                            client.info(),
-                            buffer.len()
-                        ),
-                        Err(e) => {
-                            error!("Failed to peer with {}: {e}", client.info());
-                            all_success = false;
-                        }
-                    }
-                }
+                let all_success = Self::send_batch(&mut peers, &buffer, batching.compression);
 
                 if buffer_set.capacity() > 4096 {
                     buffer_set.shrink_to(4096);
                 }
+                if disconnected {
+                    break;
+                }
 
                 // Exponential backoff if any client failed
                 if all_success {
@@ -148,6 +171,149 @@ impl PeerService {
                     backoff_delay = (backoff_delay * 2).min(MAX_BACKOFF);
                 }
             }
+            warn!("Peer job channel disconnected; worker exiting");
         });
     }
+
+    /// Add jobs to a batch holding its first one, until it is full or that
+    /// job has waited long enough, returning whether the channel is
+    /// disconnected
+    fn coalesce(
+        receiver: &Receiver<PeerJob>,
+        batch: &mut HashSet<PeerJob>,
+        batching: Batching,
+    ) -> bool {
+        let deadline = Instant::now() + batching.max_age;
+        while batch.len() < batching.size {
+            match receiver.recv_deadline(deadline) {
+                Ok(job) => {
+                    batch.insert(job);
+                }
+                Err(RecvTimeoutError::Timeout) => return false,
+                Err(RecvTimeoutError::Disconnected) => return true,
+            }
+        }
+        false
+    }
+
+    /// Send a batch of jobs to every configured peer, returning whether all
+    /// of them received it. It is only compressed for the peers advertising
+    /// gzip request bodies.
+    fn send_batch(peers: &mut [Peer], buffer: &[PeerJob], compression: bool) -> bool {
+        let mut all_success = true;
+        for peer in peers {
+            let sent = if compression && peer.accepts_gzip() {
+                peer.client.peer().drop_compressed(buffer)
+            } else {
+                peer.client.peer().drop(buffer)
+            };
+            let client = &peer.client;
+            match sent {
+                Ok(results) => {
+                    // Rejected jobs are not retried: resending them
+                    // would fail the same way on the peer
+                    for result in results.iter().filter(|r| !r.applied) {
+                        warn!(
+                            "Peer {} rejected {:?} {}: {}",
+                            client.info(),
+                            result.action,
+                            result.cidr,
+                            result
+                                .error
+                                .as_ref()
+                                .map_or("unknown error", |e| e.message.as_str())
+                        );
+                    }
+                    info!(
+                        "Peering sync successful with {} ({} jobs)",
+                        client.info(),
+                        buffer.len()
+                    );
+                }
+                Err(e) => {
+                    error!("Failed to peer with {}: {e}", client.info());
+                    peer.gzip = None;
+                    all_success = false;
+                }
+            }
+        }
+        all_success
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use common::Expiration;
+
+    fn job(cidr: &str) -> PeerJob {
+        PeerJob {
+            action: Action::Add,
+            entry: RawEntry {
+                cidr: cidr.parse().unwrap(),
+                tag: None,
+                expiration: Expiration::never(),
+                metadata: None,
+            },
+        }
+    }
+
+    const fn batching(size: usize, max_age: Duration) -> Batching {
+        Batching {
+            size,
+            max_age,
+            compression: false,
+        }
+    }
+
+    #[test]
+    fn coalesce_stops_once_the_batch_is_full() {
+        let (sender, receiver) = bounded(16);
+        for i in 1..=5 {
+            sender.send(job(&format!("192.0.2.{i}/32"))).unwrap();
+        }
+        let mut batch = HashSet::from([job("192.0.2.0/32")]);
+
+        let started = Instant::now();
+        let disconnected =
+            PeerService::coalesce(&receiver, &mut batch, batching(3, Duration::from_secs(30)));
+        assert!(!disconnected);
+        assert_eq!(batch.len(), 3);
+        assert!(started.elapsed() < Duration::from_secs(30));
+        // The rest waits for the next batch
+        assert_eq!(receiver.len(), 3);
+    }
+
+    #[test]
+    fn coalesce_stops_once_the_first_job_is_old_enough() {
+        let (sender, receiver) = bounded(16);
+        sender.send(job("192.0.2.1/32")).unwrap();
+        // Counted once
+        sender.send(job("192.0.2.1/32")).unwrap();
+        let mut batch = HashSet::from([job("192.0.2.0/32")]);
+
+        let max_age = Duration::from_millis(50);
+        let started = Instant::now();
+        let disconnected = PeerService::coalesce(&receiver, &mut batch, batching(100, max_age));
+        assert!(!disconnected);
+        assert_eq!(batch.len(), 2);
+        assert!(started.elapsed() >= max_age);
+    }
+
+    #[test]
+    fn coalesce_reports_a_disconnected_channel() {
+        let (sender, receiver) = bounded(16);
+        sender.send(job("192.0.2.1/32")).unwrap();
+        drop(sender);
+        let mut batch = HashSet::from([job("192.0.2.0/32")]);
+
+        let disconnected = PeerService::coalesce(
+            &receiver,
+            &mut batch,
+            batching(100, Duration::from_secs(30)),
+        );
+        assert!(disconnected);
+        assert_eq!(batch.len(), 2);
+    }
 }
@@ -1,9 +1,13 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::thread;
 use std::time::Duration;
 
 use crossbeam_channel::{Receiver, Sender, TrySendError, bounded};
 use reqwest::blocking::Client;
-use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::header::{
+    ACCEPT, AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue, InvalidHeaderValue, USER_AGENT,
+};
 use tracing::{error, info, warn};
 
 use crate::config;
@@ -12,7 +16,7 @@ use common::Report;
 const CLIENT_TIMEOUT: u64 = 2;
 const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
 const MAX_BACKOFF: Duration = Duration::from_secs(60);
-pub const MAX_BUFFER_SIZE: usize = 1 << 12; // 4096 pending reports max
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1 << 12; // 4096 pending reports
 
 #[derive(Debug, thiserror::Error)]
 pub enum ReportingError {
@@ -20,6 +24,8 @@ pub enum ReportingError {
     Http(#[from] reqwest::Error),
     #[error("Serialization error: {0}")]
     Serde(#[from] serde_json::Error),
+    #[error("Invalid secret: {0}")]
+    Secret(#[from] InvalidHeaderValue),
     #[error("HTTP status error: {status} - Body: {body}")]
     HttpStatus {
         status: reqwest::StatusCode,
@@ -34,7 +40,7 @@ pub struct ReportingClient {
 }
 
 impl ReportingClient {
-    fn new(config: config::Reporting) -> Result<Self, ReportingError> {
+    fn new(target: config::ReportingTarget) -> Result<Self, ReportingError> {
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
@@ -46,6 +52,11 @@ impl ReportingClient {
                 env!("CARGO_PKG_VERSION")
             )),
         );
+        if let Some(secret) = &target.secret {
+            let mut value = HeaderValue::from_str(&format!("Bearer {secret}"))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
 
         let client = Client::builder()
             .timeout(Duration::from_secs(CLIENT_TIMEOUT))
@@ -53,7 +64,7 @@ impl ReportingClient {
             .build()?;
 
         Ok(Self {
-            webhook: config.webhook,
+            webhook: target.url,
             client,
         })
     }
@@ -72,56 +83,106 @@ impl ReportingClient {
     }
 }
 
+/// Consumer side of the reporting queue, shared with the worker so that the
+/// oldest pending report can be evicted when the queue is full
 #[derive(Debug)]
-pub struct ReportingService {
+struct ReportQueue {
+    target: String,
+    receiver: Receiver<Report>,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl ReportQueue {
+    /// Moves pending reports into `buffer`, trimming its oldest reports so
+    /// that reports kept across failed deliveries never exceed the capacity
+    fn drain_into(&self, buffer: &mut Vec<Report>) {
+        buffer.extend(self.receiver.try_iter());
+        let excess = buffer.len().saturating_sub(self.capacity);
+        if excess > 0 {
+            buffer.drain(..excess);
+            self.record_dropped(excess as u64);
+        }
+    }
+
+    fn record_dropped(&self, count: u64) {
+        let previous = self.dropped.fetch_add(count, Ordering::Relaxed);
+        let total = previous.saturating_add(count);
+        let capacity = self.capacity as u64;
+        // Warn on the first drop, then once per queue worth of drops
+        if previous == 0 || previous / capacity != total / capacity {
+            warn!(
+                "Reporting queue of {} full (>{} pending), {total} oldest reports dropped so far",
+                self.target, self.capacity
+            );
+        }
+    }
+}
+
+/// Queue and worker delivering reports to one target, so that a slow or
+/// unreachable target holds back no other
+#[derive(Debug, Clone)]
+struct TargetQueue {
     sender: Sender<Report>,
+    queue: Arc<ReportQueue>,
 }
 
-impl ReportingService {
-    pub fn new(config: config::Reporting) -> Result<Self, ReportingError> {
-        let (sender, receiver) = bounded::<Report>(MAX_BUFFER_SIZE);
-        let service = Self { sender };
-        service.spawn_worker(config, receiver)?;
-        Ok(service)
+impl TargetQueue {
+    fn new(target: String, capacity: usize) -> Self {
+        let (sender, receiver) = bounded::<Report>(capacity);
+        let queue = Arc::new(ReportQueue {
+            target,
+            receiver,
+            capacity,
+            dropped: AtomicU64::new(0),
+        });
+        Self { sender, queue }
     }
 
-    /// Adds a new report to be processed asynchronously
-    pub fn add_report(&self, report: Report) {
-        if let Err(err) = self.sender.try_send(report) {
-            match err {
-                TrySendError::Full(_) => warn!(
-                    "Reporting channel full (>{MAX_BUFFER_SIZE} pending). \
-                     Dropping new report to prevent memory exhaustion."
-                ),
-                TrySendError::Disconnected(_) => {
-                    error!("Reporting channel disconnected; unable to send report");
+    /// Queues a report, evicting the oldest pending report when full
+    fn push(&self, report: Report) {
+        let mut report = report;
+        loop {
+            match self.sender.try_send(report) {
+                Ok(()) => return,
+                Err(TrySendError::Full(rejected)) => {
+                    if self.queue.receiver.try_recv().is_ok() {
+                        self.queue.record_dropped(1);
+                    }
+                    report = rejected;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    error!(
+                        "Reporting channel of {} disconnected; unable to send report",
+                        self.queue.target
+                    );
+                    return;
                 }
             }
         }
     }
 
-    fn spawn_worker(
-        &self,
-        config: config::Reporting,
-        receiver: Receiver<Report>,
-    ) -> Result<(), ReportingError> {
-        let reporting_client = ReportingClient::new(config)?;
+    fn spawn_worker(&self, reporting_client: ReportingClient) {
+        let queue = Arc::clone(&self.queue);
 
         thread::spawn(move || {
-            let mut buffer = Vec::with_capacity(4096);
+            let mut buffer = Vec::with_capacity(DEFAULT_QUEUE_CAPACITY);
             let mut backoff_delay = Duration::from_secs(1);
 
             loop {
-                match receiver.recv_timeout(FLUSH_INTERVAL) {
+                match queue.receiver.recv_timeout(FLUSH_INTERVAL) {
                     Ok(report) => buffer.push(report),
                     Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
                     Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
-                        error!("Reporting channel disconnected; worker exiting");
+                        error!(
+                            "Reporting channel of {} disconnected; worker exiting",
+                            reporting_client.webhook
+                        );
                         break;
                     }
                 }
 
-                buffer.extend(receiver.try_iter());
+                queue.drain_into(&mut buffer);
 
                 if buffer.is_empty() {
                     continue;
@@ -135,20 +196,132 @@ impl ReportingService {
                             reporting_client.webhook
                         );
                         buffer.clear();
-                        if buffer.capacity() > 4096 {
-                            buffer.shrink_to(4096);
+                        if buffer.capacity() > DEFAULT_QUEUE_CAPACITY {
+                            buffer.shrink_to(DEFAULT_QUEUE_CAPACITY);
                         }
                         backoff_delay = Duration::from_secs(1);
                     }
                     Err(err) => {
-                        error!("Failed to send reports: {err}");
+                        error!(
+                            "Failed to send reports to {}: {err}",
+                            reporting_client.webhook
+                        );
                         thread::sleep(backoff_delay);
                         backoff_delay = (backoff_delay * 2).min(MAX_BACKOFF);
                     }
                 }
             }
         });
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportingService {
+    targets: Vec<TargetQueue>,
+}
+
+impl ReportingService {
+    pub fn new(config: config::Reporting) -> Result<Self, ReportingError> {
+        let mut targets = Vec::new();
+        for target in config.all_targets() {
+            let target_queue = TargetQueue::new(target.url.clone(), config.queue_capacity);
+            // Clients are all built before any worker starts
+            targets.push((target_queue, ReportingClient::new(target)?));
+        }
+        let targets = targets
+            .into_iter()
+            .map(|(target_queue, reporting_client)| {
+                target_queue.spawn_worker(reporting_client);
+                target_queue
+            })
+            .collect();
+        Ok(Self { targets })
+    }
+
+    /// Adds a new report to be processed asynchronously by every target,
+    /// evicting the oldest pending report of a target whose queue is full
+    pub fn add_report(&self, report: Report) {
+        if let Some((last, others)) = self.targets.split_last() {
+            for target in others {
+                target.push(report.clone());
+            }
+            last.push(report);
+        }
+    }
+
+    /// Number of reports dropped because a queue was full, over all targets
+    pub fn dropped(&self) -> u64 {
+        self.targets
+            .iter()
+            .map(|target| target.queue.dropped.load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use common::{Action, Entry, Expiration, Policy};
+    use std::str::FromStr;
+
+    fn report(creation: u64) -> Report {
+        Report {
+            action: Action::Add,
+            policy: Policy::Drop,
+            entry: Entry {
+                creation,
+                cidr: common::NormalizedCidr::from_str("192.0.2.1/32").unwrap(),
+                tag: None,
+                expiration: Expiration::never(),
+                source: None,
+            },
+            metadata: None,
+        }
+    }
+
+    fn service(capacities: &[usize]) -> ReportingService {
+        let targets = capacities
+            .iter()
+            .enumerate()
+            .map(|(i, &capacity)| TargetQueue::new(format!("http://target{i}"), capacity))
+            .collect();
+        ReportingService { targets }
+    }
+
+    #[test]
+    fn full_queue_drops_oldest_reports() {
+        let service = service(&[2]);
+        for creation in 1..=3 {
+            service.add_report(report(creation));
+        }
+        assert_eq!(service.dropped(), 1);
+
+        // Reports kept across a failed delivery count against the capacity
+        let mut buffer = vec![report(0)];
+        service.targets[0].queue.drain_into(&mut buffer);
+        let kept: Vec<u64> = buffer.iter().map(|r| r.entry.creation).collect();
+        assert_eq!(kept, vec![2, 3]);
+        assert_eq!(service.dropped(), 2);
+    }
+
+    #[test]
+    fn reports_fan_out_to_independent_targets() {
+        let service = service(&[1, 3]);
+        for creation in 1..=3 {
+            service.add_report(report(creation));
+        }
+        // Only the smaller queue dropped reports
+        assert_eq!(service.targets[0].queue.dropped.load(Ordering::Relaxed), 2);
+        assert_eq!(service.targets[1].queue.dropped.load(Ordering::Relaxed), 0);
+        assert_eq!(service.dropped(), 2);
 
-        Ok(())
+        let creations = |target: &TargetQueue| -> Vec<u64> {
+            let mut buffer = Vec::new();
+            target.queue.drain_into(&mut buffer);
+            buffer.iter().map(|r| r.entry.creation).collect()
+        };
+        assert_eq!(creations(&service.targets[0]), vec![3]);
+        assert_eq!(creations(&service.targets[1]), vec![1, 2, 3]);
     }
 }
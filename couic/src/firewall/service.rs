@@ -1,31 +1,43 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::{self, BufRead, BufReader};
+use std::io;
+use std::net::IpAddr;
 use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use ipnet::IpNet;
 
-use aya::{
-    Ebpf, EbpfError, include_bytes_aligned,
-    maps::{LpmTrie, MapData, MapError, PerCpuArray, PerCpuHashMap as LruHashMap},
-    programs::{ProgramError, Xdp, XdpFlags},
-};
+use aya::{EbpfError, maps::MapError, programs::ProgramError};
 use crossbeam_channel::{Receiver, Sender, unbounded};
+use nix::time::{ClockId, clock_gettime};
 use tracing::{debug, error, info, warn};
 
-use super::lpm::{LpmMap, LpmStore, LpmStoreError, StoredEntry};
+use super::backend::{EbpfBackend, FirewallBackend};
+use super::expired::ExpiredLog;
+use super::iface::IfaceError;
+use super::lpm::{ExpiredEntry, LpmStore, LpmStoreError, Reservation, StoredEntry};
 use super::peer::{PeerService, PeerServiceError};
 use super::reporting::{ReportingError, ReportingService};
-use super::tag::{TagId, TagRegistry};
-use crate::config::{Config, OperationMode};
+use super::signature::SetVerifier;
+use super::source::{SourceId, SourceRegistry};
+use super::tag::{PersistedTags, TagId, TagRegistry};
+use super::watch::watch_sets;
+use crate::config::{Config, MinPrefix};
 use crate::error::CompositeError;
 use crate::security::{SEC_FILE_PERM, SecurityService};
+use common::cidr::parse_cidr;
+use common::set::validate_set_entries_size;
+use common::signature::signature_path;
+use common::tag::matches_pattern;
 use common::{
-    Action, Entry, ErrorCode, Expiration, MAX_SET_FILE_SIZE, MAX_SET_NAME_LENGTH, Metadata,
-    NormalizedCidr, PktStats, Policy, Report, SET_EXTENSION, Set, SetName, SetSummary, Stats,
-    TagStats,
+    Action, ChangedSet, Classification, CompactedMap, Compaction, DISABLED_SET_EXTENSION, Entry,
+    EntryHits, EntryHitsKey, EntrySource, ErrorCode, ErrorDetail, Expiration, Lockdown,
+    MAX_SET_FILE_SIZE, MAX_SET_NAME_LENGTH, MapsUsage, Metadata, NormalizedCidr, Policy,
+    RecentlyExpired, ReplaceSummary, Report, SET_EXTENSION, Set, SetHeader, SetName, SetPatch,
+    SetPatchResult, SetSummary, SetsReload, SetsReloadStatus, SetsStatus, SetsSummary, SkippedSet,
+    SourceKind, Stats, TagStats, render_set_file,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -48,8 +60,23 @@ pub enum FirewallServiceError {
     Reporting(#[from] ReportingError),
     #[error("Program not found: {0}")]
     ProgramNotFound(String),
+    #[error("Interface error: {0}")]
+    Iface(#[from] IfaceError),
+    #[error("Sets watch error: {0}")]
+    Watch(#[from] notify::Error),
 }
 
+/// File in the working directory keeping tag IDs across restarts
+const TAGS_FILE: &str = "tags.json";
+/// Delay between two checks for tag changes to persist
+const TAGS_PERSIST_INTERVAL: Duration = Duration::from_secs(5);
+/// Metadata of the removal reports of expired entries
+const EXPIRY_METADATA_KIND: &str = "expiration";
+const EXPIRY_METADATA_DETAIL: &str = "expired";
+
+/// Set file modification times, keyed by set name
+type SetMtimes = HashMap<String, SystemTime>;
+
 #[derive(Default)]
 struct SetCounter {
     updated: usize,
@@ -58,24 +85,55 @@ struct SetCounter {
 }
 
 pub struct FirewallService {
-    _ebpf: Ebpf,
+    backend: Box<dyn FirewallBackend>,
     drop_v4: LpmStore,
     drop_v6: LpmStore,
     ignore_v4: LpmStore,
     ignore_v6: LpmStore,
-    stats: PerCpuArray<MapData, PktStats>,
-    drop_stats_per_tag: LruHashMap<MapData, u64, PktStats>,
-    ignore_stats_per_tag: LruHashMap<MapData, u64, PktStats>,
+    monitor_v4: LpmStore,
+    monitor_v6: LpmStore,
     peer_service: Option<PeerService>,
     reporting_service: Option<ReportingService>,
     tag_registry: TagRegistry,
+    /// Sources of the entries, looked up by the ID kept in the stores
+    source_registry: SourceRegistry,
     #[allow(dead_code)]
-    tag_release_sender: Sender<TagId>,
+    expiry_sender: Sender<ExpiredEntry>,
+    /// Last entries removed once expired
+    expired_log: ExpiredLog,
+    /// Modification times of the set files as of their last successful reload
+    loaded_sets: RwLock<HashMap<Policy, SetMtimes>>,
+    /// Changes applied by the last successful reload of each policy
+    last_reloads: RwLock<HashMap<Policy, SetsReload>>,
+    /// Serializes set file changes, so a `If-Match` check and the write it
+    /// guards see the same file
+    set_writes: Mutex<()>,
+    /// Serializes set reloads and policy replacements, which both diff the
+    /// stores against a target state before applying it
+    reconciling: Mutex<()>,
+    /// Set when `sets.public_key` is configured
+    set_verifier: Option<SetVerifier>,
+    /// Whether every packet not matched by an ignore entry is dropped, off
+    /// on startup
+    lockdown: Mutex<bool>,
+    /// Creation time in nanoseconds, telling entry revisions of successive
+    /// runs apart
+    started_at: u128,
     config: Config,
 }
 
 impl FirewallService {
     pub fn new(config: Config) -> Result<Self, FirewallServiceError> {
+        let backend = EbpfBackend::load(&config)?;
+        Self::with_backend(config, Box::new(backend))
+    }
+
+    /// Service on top of `backend`, whose maps are filled from the sets of
+    /// the working directory
+    pub fn with_backend(
+        config: Config,
+        mut backend: Box<dyn FirewallBackend>,
+    ) -> Result<Self, FirewallServiceError> {
         let peer_service = if let Some(peering) = &config.peering {
             if peering.enabled {
                 Some(PeerService::new(&config.clone())?)
@@ -95,121 +153,410 @@ impl FirewallService {
             None
         };
 
-        let mut ebpf = Ebpf::load(include_bytes_aligned!(concat!(env!("OUT_DIR"), "/couic")))?;
-
-        let program: &mut Xdp = ebpf
-            .program_mut("couic")
-            .ok_or_else(|| FirewallServiceError::ProgramNotFound("couic".to_string()))?
-            .try_into()?;
-        program.load()?;
-
-        let xdp_flags = match config.operation_mode {
-            OperationMode::Generic => XdpFlags::SKB_MODE,
-            OperationMode::Native => XdpFlags::DRV_MODE,
-            OperationMode::Offloaded => XdpFlags::HW_MODE,
+        let set_verifier = config
+            .sets
+            .public_key
+            .as_deref()
+            .map(load_set_verifier)
+            .transpose()?;
+
+        let tags_path = config
+            .firewall
+            .persist_tags
+            .then(|| Path::new(&config.working_dir).join(TAGS_FILE));
+        let tag_registry = tags_path
+            .as_deref()
+            .map_or_else(TagRegistry::new, Self::load_tag_registry);
+        let (expiry_sender, expiry_receiver) = unbounded::<ExpiredEntry>();
+
+        let mut store = |policy, ipv4| -> Result<LpmStore, FirewallServiceError> {
+            let (map, max_entries) = backend.take_lpm_map(policy, ipv4)?;
+            Ok(LpmStore::new(
+                map,
+                max_entries,
+                policy,
+                expiry_sender.clone(),
+                config.firewall.cleanup,
+            ))
         };
-        for iface in &config.ifaces {
-            program.attach(iface, xdp_flags)?;
-            info!(
-                "XDP program attached to interface: {iface} (mode: {:?})",
-                config.operation_mode
-            );
-        }
-
-        let tag_registry = TagRegistry::new();
-        let (tag_release_sender, tag_release_receiver) = unbounded::<TagId>();
-
-        let drop_v4 = LpmTrie::try_from(ebpf.take_map("couic_ipv4_drop").ok_or_else(|| {
-            FirewallServiceError::ProgramNotFound("couic_ipv4_drop".to_string())
-        })?)?;
-        let drop_v4 = LpmStore::new(LpmMap::V4(drop_v4), tag_release_sender.clone())?;
-        let drop_v6 = LpmTrie::try_from(ebpf.take_map("couic_ipv6_drop").ok_or_else(|| {
-            FirewallServiceError::ProgramNotFound("couic_ipv6_drop".to_string())
-        })?)?;
-        let drop_v6 = LpmStore::new(LpmMap::V6(drop_v6), tag_release_sender.clone())?;
-        let ignore_v4 =
-            LpmTrie::try_from(ebpf.take_map("couic_ipv4_ignore").ok_or_else(|| {
-                FirewallServiceError::ProgramNotFound("couic_ipv4_ignore".to_string())
-            })?)?;
-        let ignore_v4 = LpmStore::new(LpmMap::V4(ignore_v4), tag_release_sender.clone())?;
-        let ignore_v6 =
-            LpmTrie::try_from(ebpf.take_map("couic_ipv6_ignore").ok_or_else(|| {
-                FirewallServiceError::ProgramNotFound("couic_ipv6_ignore".to_string())
-            })?)?;
-        let ignore_v6 = LpmStore::new(LpmMap::V6(ignore_v6), tag_release_sender.clone())?;
-        let stats =
-            PerCpuArray::try_from(ebpf.take_map("couic_stats").ok_or_else(|| {
-                FirewallServiceError::ProgramNotFound("couic_stats".to_string())
-            })?)?;
-        let drop_stats_per_tag =
-            LruHashMap::try_from(ebpf.take_map("couic_drop_stats_per_tag").ok_or_else(|| {
-                FirewallServiceError::ProgramNotFound("couic_drop_stats_per_tag".to_string())
-            })?)?;
-        let ignore_stats_per_tag =
-            LruHashMap::try_from(ebpf.take_map("couic_ignore_stats_per_tag").ok_or_else(
-                || FirewallServiceError::ProgramNotFound("couic_ignore_stats_per_tag".to_string()),
-            )?)?;
-
-        // Launch tag release worker thread
-        Self::launch_tag_release_worker(tag_registry.clone(), tag_release_receiver);
+        let drop_v4 = store(Policy::Drop, true)?;
+        let drop_v6 = store(Policy::Drop, false)?;
+        let ignore_v4 = store(Policy::Ignore, true)?;
+        let ignore_v6 = store(Policy::Ignore, false)?;
+        let monitor_v4 = store(Policy::Monitor, true)?;
+        let monitor_v6 = store(Policy::Monitor, false)?;
+
+        // Launch expiry worker thread
+        let expired_log = ExpiredLog::new(config.firewall.recently_expired);
+        Self::launch_expiry_worker(
+            tag_registry.clone(),
+            reporting_service.clone(),
+            expired_log.clone(),
+            expiry_receiver,
+        );
 
         let service = Self {
-            _ebpf: ebpf,
+            backend,
             drop_v4,
             drop_v6,
             ignore_v4,
             ignore_v6,
-            stats,
-            drop_stats_per_tag,
-            ignore_stats_per_tag,
+            monitor_v4,
+            monitor_v6,
             peer_service,
             reporting_service,
             tag_registry,
-            tag_release_sender,
+            source_registry: SourceRegistry::new(),
+            expiry_sender,
+            expired_log,
+            loaded_sets: RwLock::new(HashMap::new()),
+            last_reloads: RwLock::new(HashMap::new()),
+            set_writes: Mutex::new(()),
+            reconciling: Mutex::new(()),
+            set_verifier,
+            lockdown: Mutex::new(false),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
             config,
         };
 
-        // Reload sets at startup
-        service.reload_sets()?;
+        // Reload sets at startup, drop sets refused since they were written
+        // being skipped rather than keeping the daemon from starting
+        service.reload_sets_with(DropGuards::SkipSet)?;
+
+        // Persist once sets have claimed their tags back, then on changes
+        if let Some(path) = tags_path {
+            Self::launch_tag_persist_worker(
+                service.tag_registry.clone(),
+                path,
+                service.config.user.clone(),
+                service.config.group.clone(),
+            );
+        }
 
         Ok(service)
     }
 
-    /// Background thread that processes tag releases from the cleanup threads
-    fn launch_tag_release_worker(tag_registry: TagRegistry, receiver: Receiver<TagId>) {
+    /// Restore tag IDs saved by a previous run, starting afresh when there
+    /// are none or they cannot be read
+    fn load_tag_registry(path: &Path) -> TagRegistry {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("No persisted tags at {}, starting afresh", path.display());
+                return TagRegistry::new();
+            }
+            Err(e) => {
+                warn!("Failed to read persisted tags {}: {e}", path.display());
+                return TagRegistry::new();
+            }
+        };
+        let restored = serde_json::from_str::<PersistedTags>(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|state| TagRegistry::restore(state).map_err(|e| e.to_string()));
+        match restored {
+            Ok(registry) => {
+                info!("Tag IDs restored from {}", path.display());
+                registry
+            }
+            Err(e) => {
+                warn!("Ignoring persisted tags {}: {e}", path.display());
+                TagRegistry::new()
+            }
+        }
+    }
+
+    /// Background thread writing the tag registry state whenever a tag was
+    /// created or removed since the last write
+    fn launch_tag_persist_worker(
+        tag_registry: TagRegistry,
+        path: std::path::PathBuf,
+        user: String,
+        group: String,
+    ) {
         thread::spawn(move || {
+            let mut saved_generation = None;
             loop {
-                if let Ok(tag_id) = receiver.recv() {
-                    // Release the first one
-                    if let Err(e) = tag_registry.release(tag_id) {
-                        error!("Failed to release tag {tag_id}: {e}");
+                match tag_registry.generation() {
+                    Ok(generation) if saved_generation != Some(generation) => {
+                        match Self::save_tags(&tag_registry, &path, &user, &group) {
+                            Ok(generation) => saved_generation = Some(generation),
+                            Err(e) => error!("Failed to persist tags: {e}"),
+                        }
                     }
+                    Ok(_) => {}
+                    Err(e) => error!("Failed to read tag registry: {e}"),
+                }
+                thread::sleep(TAGS_PERSIST_INTERVAL);
+            }
+        });
+    }
+
+    /// Atomically write the tag registry state, returning its generation
+    fn save_tags(
+        tag_registry: &TagRegistry,
+        path: &Path,
+        user: &str,
+        group: &str,
+    ) -> Result<u64, String> {
+        let (generation, state) = tag_registry.persisted().map_err(|e| e.to_string())?;
+        let content = serde_json::to_string(&state).map_err(|e| e.to_string())?;
+
+        let tmp_path = path.with_extension("couic.tmp");
+        fs::write(&tmp_path, content)
+            .map_err(|e| e.to_string())
+            .and_then(|()| {
+                SecurityService::set_owner_group_perms(&tmp_path, user, group, SEC_FILE_PERM)
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|()| fs::rename(&tmp_path, path).map_err(|e| e.to_string()))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp_path);
+            })?;
 
-                    // Batch drain any additional pending releases
-                    for tag_id in receiver.try_iter() {
-                        if let Err(e) = tag_registry.release(tag_id) {
-                            error!("Failed to release tag {tag_id}: {e}");
+        Ok(generation)
+    }
+
+    /// Background thread that logs and reports the entries expired by the
+    /// cleanup threads, then releases their tags
+    fn launch_expiry_worker(
+        tag_registry: TagRegistry,
+        reporting_service: Option<ReportingService>,
+        expired_log: ExpiredLog,
+        receiver: Receiver<ExpiredEntry>,
+    ) {
+        thread::spawn(move || {
+            let handle = |expired: ExpiredEntry| {
+                // Resolve the tag name before its release may free it
+                match expiry_report(&tag_registry, expired) {
+                    Ok(report) => {
+                        expired_log.record(RecentlyExpired {
+                            policy: report.policy,
+                            cidr: report.entry.cidr,
+                            tag: report.entry.tag.clone(),
+                            expired_at: expired.stored.expiration,
+                        });
+                        if expired.stored.propagate
+                            && let Some(reporting_service) = &reporting_service
+                        {
+                            reporting_service.add_report(report);
                         }
                     }
+                    Err(e) => error!("Failed to resolve expired entry {}: {e}", expired.cidr),
+                }
+                let tag_id = expired.stored.tag_id;
+                if let Err(e) = tag_registry.release(tag_id) {
+                    error!("Failed to release tag {tag_id}: {e}");
+                }
+            };
+            loop {
+                if let Ok(expired) = receiver.recv() {
+                    handle(expired);
+
+                    // Batch drain any additional pending expirations
+                    for expired in receiver.try_iter() {
+                        handle(expired);
+                    }
                 } else {
-                    warn!("Tag release channel disconnected; worker exiting");
+                    warn!("Expiry channel disconnected; worker exiting");
                     break;
                 }
             }
         });
     }
 
+    /// Background thread reloading the sets when their files change, with
+    /// `sets.watch` enabled
+    pub fn launch_sets_watcher(service: Arc<Self>) -> Result<(), FirewallServiceError> {
+        let watch = service.config.sets.watch;
+        if !watch.enabled {
+            return Ok(());
+        }
+        let sets_dir = Path::new(&service.config.working_dir).join("sets");
+        watch_sets(service, &sets_dir, Duration::from_millis(watch.debounce_ms))?;
+        Ok(())
+    }
+
+    /// Background thread that periodically logs XDP traffic volume per action
+    pub fn launch_stats_logger(service: Arc<Self>) {
+        let interval_secs = service.config.logging.stats_interval_secs;
+        if interval_secs == 0 {
+            return;
+        }
+
+        thread::spawn(move || {
+            let interval = Duration::from_secs(interval_secs);
+            let mut previous = service.get_stats().ok();
+
+            loop {
+                thread::sleep(interval);
+
+                let current = match service.get_stats() {
+                    Ok(stats) => stats,
+                    Err(e) => {
+                        error!("stats summary error: {e}");
+                        continue;
+                    }
+                };
+
+                if let Some(previous) = &previous {
+                    Self::log_stats_summary(previous, &current, interval_secs);
+                }
+                previous = Some(current);
+            }
+        });
+    }
+
+    fn log_stats_summary(previous: &Stats, current: &Stats, interval_secs: u64) {
+        let mut actions: Vec<_> = current.xdp.keys().collect();
+        actions.sort();
+
+        for action in actions {
+            let Some(now) = current.xdp.get(action) else {
+                continue;
+            };
+            let before = previous.xdp.get(action).copied().unwrap_or_default();
+            let rx_packets = now.rx_packets.saturating_sub(before.rx_packets);
+            let rx_bytes = now.rx_bytes.saturating_sub(before.rx_bytes);
+
+            info!(
+                action = %action,
+                interval_secs,
+                rx_packets,
+                rx_bytes,
+                pps = rx_packets.checked_div(interval_secs).unwrap_or_default(),
+                bps = rx_bytes
+                    .saturating_mul(8)
+                    .checked_div(interval_secs)
+                    .unwrap_or_default(),
+                drop_cidr_count = current.drop_cidr_count,
+                ignore_cidr_count = current.ignore_cidr_count,
+                "xdp traffic summary"
+            );
+        }
+    }
+
     fn get_lpm_store(&self, policy: Policy, is_ipv4: bool) -> &LpmStore {
         match (policy, is_ipv4) {
             (Policy::Drop, true) => &self.drop_v4,
             (Policy::Drop, false) => &self.drop_v6,
             (Policy::Ignore, true) => &self.ignore_v4,
             (Policy::Ignore, false) => &self.ignore_v6,
+            (Policy::Monitor, true) => &self.monitor_v4,
+            (Policy::Monitor, false) => &self.monitor_v6,
+        }
+    }
+
+    /// Shrink the userspace maps of every policy and family to their
+    /// entries, e.g. after a large removal
+    pub fn compact(&self) -> Result<Compaction, CompositeError> {
+        let mut compaction = Compaction::default();
+        for policy in [Policy::Ignore, Policy::Drop, Policy::Monitor] {
+            for (is_ipv4, family) in [(true, "ipv4"), (false, "ipv6")] {
+                let store = self.get_lpm_store(policy, is_ipv4);
+                let (capacity_before, capacity_after) = store.compact()?;
+                let entries = store.count();
+                info!(
+                    %policy,
+                    family,
+                    entries,
+                    capacity_before,
+                    capacity_after,
+                    "compacted userspace map"
+                );
+                compaction.maps.push(CompactedMap {
+                    policy,
+                    family: family.to_string(),
+                    entries,
+                    capacity_before,
+                    capacity_after,
+                });
+            }
+        }
+        Ok(compaction)
+    }
+
+    pub fn lockdown(&self) -> Result<Lockdown, CompositeError> {
+        let enabled = *self
+            .lockdown
+            .lock()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        Ok(Lockdown { enabled })
+    }
+
+    /// Drops every packet not matched by an ignore entry, or stops doing so.
+    /// Unless `force` is set, enabling it is refused while a range of
+    /// `firewall.management_cidrs` is not covered by an ignore entry that
+    /// never expires, or when none is configured.
+    pub fn set_lockdown(&self, enabled: bool, force: bool) -> Result<Lockdown, CompositeError> {
+        let mut state = self
+            .lockdown
+            .lock()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        if enabled && !force {
+            self.check_management_ignored()?;
+        }
+        self.backend.set_lockdown(enabled)?;
+        *state = enabled;
+        if enabled {
+            warn!("LOCKDOWN ENABLED: every packet not matched by an ignore entry is dropped");
+        } else {
+            warn!("Lockdown disabled: packets are filtered by the drop entries again");
+        }
+        Ok(Lockdown { enabled })
+    }
+
+    /// Refuses to lock down while a management range is not covered by an
+    /// ignore entry that never expires, as it would be cut off once the entry
+    /// expires
+    fn check_management_ignored(&self) -> Result<(), CompositeError> {
+        let ranges = &self.config.firewall.management_cidrs;
+        if ranges.is_empty() {
+            return Err(CompositeError::new(
+                ErrorCode::Econflict,
+                "no management range configured: list the ranges Couic is managed from in firewall.management_cidrs and ignore them, or force the lockdown",
+            ));
+        }
+        let mut ce = CompositeError::new(
+            ErrorCode::Econflict,
+            "lockdown would cut off management ranges: ignore them first, or force the lockdown",
+        );
+        for range in ranges {
+            let cidr = NormalizedCidr::new(*range);
+            let store = self.get_lpm_store(Policy::Ignore, cidr.is_v4());
+            if store
+                .covering(cidr, |stored| stored.expiration == 0)?
+                .is_some()
+            {
+                continue;
+            }
+            let message = if store.covering(cidr, |_| true)?.is_some() {
+                format!(
+                    "{cidr} is only ignored by an expiring entry, run `couicctl ignore add {cidr}`"
+                )
+            } else {
+                format!("{cidr} is not ignored, run `couicctl ignore add {cidr}`")
+            };
+            ce.add_detail(&cidr.to_string(), ErrorCode::Econflict, &message);
+        }
+        if ce.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ce)
         }
     }
 
     /// Convert Entry to `StoredEntry` by acquiring a tag from the registry
-    fn entry_to_stored(&self, entry: &Entry) -> Result<StoredEntry, CompositeError> {
+    /// Stored form of `entry`, whose expiry is reported only when it is added
+    /// locally with `propagate`, a peer entry being reported by its own node
+    fn entry_to_stored(
+        &self,
+        entry: &Entry,
+        propagate: bool,
+    ) -> Result<StoredEntry, CompositeError> {
+        let source = self.source_id(entry)?;
         let tag_str = entry.tag.as_deref().unwrap_or("");
         let tag_id = self.tag_registry.acquire(tag_str).map_err(|e| {
             CompositeError::new(ErrorCode::Einternal, &format!("Tag acquisition error: {e}"))
@@ -219,9 +566,24 @@ impl FirewallService {
             creation: entry.creation,
             tag_id,
             expiration: entry.expiration.as_timestamp(),
+            source,
+            propagate: propagate
+                && entry
+                    .source
+                    .as_ref()
+                    .is_none_or(|source| source.kind != SourceKind::Peer),
         })
     }
 
+    /// ID of the source of `entry`, entries tagged with a set name always
+    /// coming from that set
+    fn source_id(&self, entry: &Entry) -> Result<SourceId, CompositeError> {
+        let set = entry.in_set().then(EntrySource::set);
+        self.source_registry
+            .acquire(set.as_ref().or(entry.source.as_ref()))
+            .map_err(|e| CompositeError::new(ErrorCode::Einternal, &format!("Source error: {e}")))
+    }
+
     /// Convert `StoredEntry` to Entry by looking up the tag name from the registry
     fn stored_to_entry(
         &self,
@@ -235,12 +597,19 @@ impl FirewallService {
                 CompositeError::new(ErrorCode::Einternal, &format!("Tag lookup error: {e}"))
             })?
             .map(|s| s.to_string());
+        let source = self
+            .source_registry
+            .get_source(stored.source)
+            .map_err(|e| {
+                CompositeError::new(ErrorCode::Einternal, &format!("Source lookup error: {e}"))
+            })?;
 
         Ok(Entry {
             creation: stored.creation,
             cidr,
             tag: tag_name,
             expiration: Expiration::from_timestamp(stored.expiration),
+            source,
         })
     }
 
@@ -250,6 +619,22 @@ impl FirewallService {
         }
     }
 
+    pub const fn peering_enabled(&self) -> bool {
+        self.peer_service.is_some()
+    }
+
+    pub const fn reporting_enabled(&self) -> bool {
+        self.reporting_service.is_some()
+    }
+
+    /// Number of reports dropped by a full reporting queue, `None` when
+    /// reporting is disabled
+    pub fn reports_dropped(&self) -> Option<u64> {
+        self.reporting_service
+            .as_ref()
+            .map(ReportingService::dropped)
+    }
+
     /// Add a given entry to the specified policy list of the firewall.
     pub fn add_entry(
         &self,
@@ -257,11 +642,18 @@ impl FirewallService {
         entry: &Entry,
         metadata: Option<Metadata>,
         propagate: bool,
+        force: bool,
     ) -> Result<(), CompositeError> {
+        check_allowed_tags(&self.config.tags.allowed_patterns, [entry])?;
+        if policy == Policy::Drop && !force {
+            check_protected_cidrs(&self.config.firewall.protected_cidrs, [entry])?;
+            check_min_prefix(self.config.firewall.min_prefix, [entry])?;
+        }
+
         let lpm_store = self.get_lpm_store(policy, entry.cidr.is_v4());
 
         // Convert Entry to StoredEntry
-        let stored_entry = self.entry_to_stored(entry)?;
+        let stored_entry = self.entry_to_stored(entry, propagate)?;
 
         // Add to LPM store
         if let Err(e) = lpm_store.add_stored(entry.cidr, stored_entry) {
@@ -269,28 +661,40 @@ impl FirewallService {
             self.release_tag(stored_entry.tag_id);
             return Err(e);
         }
+        self.reset_entry_hits(policy, entry.cidr);
+        if policy == Policy::Drop
+            && let Some(caveat) = entry.cidr.source_caveat()
+        {
+            warn!(
+                "Drop entry {} only matches local traffic: {caveat}",
+                entry.cidr
+            );
+        }
 
         if propagate {
-            // Peer sync if enabled
-            if let Some(peer_service) = &self.peer_service {
-                peer_service.queue_job(entry, Action::Add);
-            }
-
-            // Reporting if enabled
-            if let Some(reporting_service) = &self.reporting_service {
-                let report = Report {
-                    action: Action::Add,
-                    policy,
-                    entry: entry.clone(),
-                    metadata,
-                };
-                reporting_service.add_report(report);
-            }
+            self.propagate_change(policy, entry.clone(), metadata, Action::Add);
         }
 
         Ok(())
     }
 
+    /// Run the checks of `add_entry` without adding the entry, so a client can
+    /// validate a change before applying it
+    pub fn check_entry(
+        &self,
+        policy: Policy,
+        entry: &Entry,
+        force: bool,
+    ) -> Result<(), CompositeError> {
+        check_allowed_tags(&self.config.tags.allowed_patterns, [entry])?;
+        if policy == Policy::Drop && !force {
+            check_protected_cidrs(&self.config.firewall.protected_cidrs, [entry])?;
+            check_min_prefix(self.config.firewall.min_prefix, [entry])?;
+        }
+        self.get_lpm_store(policy, entry.cidr.is_v4())
+            .check_add(entry.cidr)
+    }
+
     /// Get a given CIDR from the specified policy list of the firewall.
     pub fn get_entry(&self, policy: Policy, cidr: NormalizedCidr) -> Result<Entry, CompositeError> {
         let lpm_store = self.get_lpm_store(policy, cidr.is_v4());
@@ -299,6 +703,67 @@ impl FirewallService {
         self.stored_to_entry(cidr, stored)
     }
 
+    /// Packets matched by an entry since it was created
+    pub fn get_entry_hits(
+        &self,
+        policy: Policy,
+        cidr: NormalizedCidr,
+    ) -> Result<EntryHits, CompositeError> {
+        // Counters of CIDRs missing from the policy are meaningless
+        self.get_lpm_store(policy, cidr.is_v4()).get_stored(cidr)?;
+
+        let key = EntryHitsKey::new(policy, cidr);
+        let mut hits = EntryHits::default();
+        if let Some(counters) = self.backend.entry_hits(&key)? {
+            hits.rx_packets = counters.rx_packets;
+            hits.rx_bytes = counters.rx_bytes;
+            if counters.rx_packets > 0 {
+                hits.last_hit = monotonic_to_unix(counters.last_hit);
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Forget the counters left by a removed entry of the same CIDR, so a
+    /// created entry starts from zero
+    fn reset_entry_hits(&self, policy: Policy, cidr: NormalizedCidr) {
+        let key = EntryHitsKey::new(policy, cidr);
+        if let Err(e) = self.backend.remove_entry_hits(&key) {
+            debug!("Failed to reset hits of {cidr}: {e}");
+        }
+    }
+
+    /// Last entries removed once expired, the most recent first
+    pub fn recently_expired(&self) -> Vec<RecentlyExpired> {
+        self.expired_log.recent()
+    }
+
+    /// Decision the XDP program would take for packets from `ip`, with the
+    /// longest matching entry of each policy.
+    pub fn classify(&self, ip: IpAddr) -> Result<Classification, CompositeError> {
+        let lookup = |policy| {
+            self.get_lpm_store(policy, ip.is_ipv4())
+                .longest_match(ip)?
+                .map(|(cidr, stored)| self.stored_to_entry(cidr, stored))
+                .transpose()
+        };
+        Ok(Classification {
+            monitor: lookup(Policy::Monitor)?,
+            ..Classification::new(ip, lookup(Policy::Ignore)?, lookup(Policy::Drop)?)
+        })
+    }
+
+    /// ETag and last modification time of the entries of `policy`, to be read
+    /// before listing them. The ETag changes with every change of the entries,
+    /// expirations included, and on restart.
+    pub fn entries_revision(&self, policy: Policy) -> (String, SystemTime) {
+        let (changes_v4, modified_v4) = self.get_lpm_store(policy, true).revision();
+        let (changes_v6, modified_v6) = self.get_lpm_store(policy, false).revision();
+        let etag = format!("\"{:x}-{changes_v4:x}-{changes_v6:x}\"", self.started_at);
+        let modified = UNIX_EPOCH + Duration::from_secs(modified_v4.max(modified_v6));
+        (etag, modified)
+    }
+
     /// List all entries from the specified policy list of the firewall.
     pub fn list_entries(&self, policy: Policy) -> Result<Vec<Entry>, CompositeError> {
         let mut entries = Vec::new();
@@ -316,17 +781,85 @@ impl FirewallService {
         Ok(entries)
     }
 
+    /// Entries of `policy`, converted as they are iterated. Only the compact
+    /// stored entries are copied up front, so that the entries of a large
+    /// policy are never all held at once.
+    pub fn iter_entries(
+        self: Arc<Self>,
+        policy: Policy,
+    ) -> Result<impl Iterator<Item = Result<Entry, CompositeError>> + Send + 'static, CompositeError>
+    {
+        let mut stored = self.get_lpm_store(policy, true).list_stored()?;
+        stored.extend(self.get_lpm_store(policy, false).list_stored()?);
+        Ok(stored
+            .into_iter()
+            .map(move |(cidr, stored)| self.stored_to_entry(cidr, stored)))
+    }
+
     /// Remove a given entry from the specified policy list of the firewall.
+    /// During lockdown, ignore entries covering a management range are kept.
     pub fn remove_entry(
         &self,
         policy: Policy,
         cidr: NormalizedCidr,
         propagate: bool,
     ) -> Result<(), CompositeError> {
+        self.remove_entry_if(policy, cidr, propagate, false, |_| Ok(()))
+    }
+
+    /// Remove a given entry from the specified policy list of the firewall,
+    /// unless it is defined in a set. The set membership check and the removal
+    /// are done under the same lock, so the entry cannot be replaced by a set
+    /// entry in between. During lockdown, ignore entries covering a management
+    /// range are only removed when `force` is set.
+    pub fn remove_entry_if_not_in_set(
+        &self,
+        policy: Policy,
+        cidr: NormalizedCidr,
+        propagate: bool,
+        force: bool,
+    ) -> Result<(), CompositeError> {
+        self.remove_entry_if(policy, cidr, propagate, force, |stored| {
+            if self.stored_to_entry(cidr, *stored)?.in_set() {
+                let mut ce = CompositeError::new(
+                    ErrorCode::Einvalid,
+                    "Entry defined in a set cannot be removed",
+                );
+                ce.add_detail(
+                    "cidr",
+                    ErrorCode::Einvalid,
+                    &format!("cidr `{cidr}` is defined in a set"),
+                );
+                return Err(ce);
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_entry_if<F>(
+        &self,
+        policy: Policy,
+        cidr: NormalizedCidr,
+        propagate: bool,
+        force: bool,
+        check: F,
+    ) -> Result<(), CompositeError>
+    where
+        F: FnOnce(&StoredEntry) -> Result<(), CompositeError>,
+    {
+        // Held until the entry is removed, so that lockdown is not enabled
+        // in between
+        let lockdown = self
+            .lockdown
+            .lock()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        if policy == Policy::Ignore && *lockdown && !force {
+            check_management_kept(&self.config.firewall.management_cidrs, [&cidr])?;
+        }
         let lpm_store = self.get_lpm_store(policy, cidr.is_v4());
 
         // Remove from LPM store
-        let stored = lpm_store.remove_stored(cidr)?;
+        let stored = lpm_store.remove_stored_if(cidr, check)?;
 
         // Convert to Stored Entry
         let removed_entry = self.stored_to_entry(cidr, stored)?;
@@ -335,24 +868,231 @@ impl FirewallService {
         self.release_tag(stored.tag_id);
 
         if propagate {
-            // Peer sync if enabled
-            if let Some(peer_service) = &self.peer_service {
-                peer_service.queue_job(&removed_entry, Action::Remove);
+            // No metadata on removal
+            self.propagate_change(policy, removed_entry, None, Action::Remove);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the manual entries of a policy with exactly `entries`, only
+    /// applying the difference with the current state. Entries coming from
+    /// sets are left untouched, and `entries` whose CIDR is defined in a set
+    /// are skipped.
+    ///
+    /// Protected ranges, capacity and `max_created`, the number of entries
+    /// the caller may still create, are checked for both address families
+    /// before anything changes. The changes of each address family are then
+    /// applied as one batch, lookups seeing the old manual entries or the new
+    /// ones, never a mix. A change failing then does not stop the others: it
+    /// is reported in the `failed` entries of the summary, which tells the
+    /// state reached.
+    pub fn replace_policy(
+        &self,
+        policy: Policy,
+        entries: Vec<(Entry, Option<Metadata>)>,
+        propagate: bool,
+        force: bool,
+        max_created: Option<usize>,
+    ) -> Result<ReplaceSummary, CompositeError> {
+        let _guard = self.lock_reconciling()?;
+
+        let (target_v4, target_v6): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|(entry, _)| entry.cidr.is_v4());
+        let plan_v4 = self.plan_replace(policy, true, target_v4);
+        let plan_v6 = self.plan_replace(policy, false, target_v6);
+
+        let upserts = || {
+            plan_v4
+                .upserts
+                .iter()
+                .chain(&plan_v6.upserts)
+                .map(|(entry, _)| entry)
+        };
+        check_allowed_tags(&self.config.tags.allowed_patterns, upserts())?;
+        if policy == Policy::Drop && !force {
+            check_protected_cidrs(&self.config.firewall.protected_cidrs, upserts())?;
+            check_min_prefix(self.config.firewall.min_prefix, upserts())?;
+        }
+        for (is_ipv4, plan) in [(true, &plan_v4), (false, &plan_v6)] {
+            let max_entries = self.get_lpm_store(policy, is_ipv4).max_entries();
+            if plan.projected > max_entries {
+                return Err(CompositeError::new(
+                    ErrorCode::Efull,
+                    &format!(
+                        "{policy} entries exceed couic underlying ebpf map by {} entries: max {max_entries} entries",
+                        plan.projected - max_entries
+                    ),
+                ));
             }
+        }
 
-            // Reporting if enabled
-            if let Some(reporting_service) = &self.reporting_service {
-                let report = Report {
-                    action: Action::Remove,
-                    policy,
-                    entry: removed_entry,
-                    metadata: None, // No metadata on removal
-                };
-                reporting_service.add_report(report);
+        // Held until the plan is applied, so that lockdown is not enabled in
+        // between
+        let lockdown = self
+            .lockdown
+            .lock()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        if policy == Policy::Ignore && *lockdown && !force {
+            check_management_kept(
+                &self.config.firewall.management_cidrs,
+                plan_v4.removals.iter().chain(&plan_v6.removals),
+            )?;
+        }
+
+        let created = plan_v4.created + plan_v6.created;
+        if let Some(max_created) = max_created
+            && created > max_created
+        {
+            return Err(CompositeError::new(
+                ErrorCode::Equota,
+                &format!(
+                    "Replacing {policy} entries would create {created} entries, over the {max_created} left of the daily quota"
+                ),
+            ));
+        }
+
+        let mut summary = ReplaceSummary::default();
+        for (is_ipv4, plan) in [(true, plan_v4), (false, plan_v6)] {
+            self.apply_replace(policy, is_ipv4, plan, propagate, &mut summary);
+        }
+        drop(lockdown);
+        Ok(summary)
+    }
+
+    /// Changes turning the manual entries of a store into `target`
+    fn plan_replace(
+        &self,
+        policy: Policy,
+        is_ipv4: bool,
+        target: Vec<(Entry, Option<Metadata>)>,
+    ) -> ReplacePlan {
+        let store = self.get_lpm_store(policy, is_ipv4);
+        let (stored, sets) = self.stored_and_set_entries(store);
+        let manual: HashMap<NormalizedCidr, (String, u64)> = stored
+            .iter()
+            .filter(|(cidr, _)| !sets.contains_key(cidr))
+            .map(|(cidr, stored)| {
+                let tag = self
+                    .tag_registry
+                    .get_tag(stored.tag_id)
+                    .ok()
+                    .flatten()
+                    .map(|tag| tag.to_string())
+                    .unwrap_or_default();
+                (*cidr, (tag, stored.expiration))
+            })
+            .collect();
+        plan_replace(&manual, &sets, stored.len(), target)
+    }
+
+    /// Applies a plan, the failed changes being recorded in `summary`
+    fn apply_replace(
+        &self,
+        policy: Policy,
+        is_ipv4: bool,
+        plan: ReplacePlan,
+        propagate: bool,
+        summary: &mut ReplaceSummary,
+    ) {
+        let store = self.get_lpm_store(policy, is_ipv4);
+        summary.unchanged += plan.unchanged;
+        summary.skipped += plan.skipped;
+        let mut fail = |cidr: NormalizedCidr, e: CompositeError| {
+            error!("Failed to replace {policy} entry {cidr}: {e}");
+            summary.failed.insert(
+                cidr.to_string(),
+                ErrorDetail {
+                    code: e.0.code,
+                    message: e.0.message,
+                },
+            );
+        };
+
+        // Removals first, so the store never goes over its capacity
+        let mut changes: Vec<_> = plan.removals.into_iter().map(|cidr| (cidr, None)).collect();
+        let removals = changes.len();
+        let mut applied = Vec::with_capacity(plan.upserts.len());
+        for (entry, metadata) in plan.upserts {
+            match self.entry_to_stored(&entry, propagate) {
+                Ok(new_stored) => {
+                    changes.push((entry.cidr, Some(new_stored)));
+                    applied.push((new_stored, entry, metadata));
+                }
+                Err(e) => fail(entry.cidr, e),
             }
         }
 
-        Ok(())
+        // Applied as one batch, so lookups see the old manual entries or the
+        // new ones, never a mix. Results come in the order of the changes.
+        let mut results = store.apply_batch(changes).into_iter();
+        let mut removed = 0;
+        for (cidr, result) in results.by_ref().take(removals) {
+            match result {
+                Ok(Some(stored)) => {
+                    let entry = self.stored_to_entry(cidr, stored);
+                    self.release_tag(stored.tag_id);
+                    removed += 1;
+                    if propagate && let Ok(entry) = entry {
+                        self.propagate_change(policy, entry, None, Action::Remove);
+                    }
+                }
+                Ok(None) => {}
+                // Removed concurrently, which is what was asked for
+                Err(e) if e.code == ErrorCode::Enotfound => {}
+                Err(e) => fail(cidr, e),
+            }
+        }
+
+        let (mut created, mut updated) = (0, 0);
+        for ((cidr, result), (new_stored, entry, metadata)) in results.zip(applied) {
+            match result {
+                Ok(old_stored) => {
+                    if let Some(old_stored) = old_stored {
+                        self.release_tag(old_stored.tag_id);
+                        updated += 1;
+                    } else {
+                        self.reset_entry_hits(policy, cidr);
+                        created += 1;
+                    }
+                    if propagate {
+                        self.propagate_change(policy, entry, metadata, Action::Add);
+                    }
+                }
+                Err(e) => {
+                    self.release_tag(new_stored.tag_id);
+                    fail(cidr, e);
+                }
+            }
+        }
+        summary.removed += removed;
+        summary.created += created;
+        summary.updated += updated;
+    }
+
+    /// Forward a change to peers and reporting, when enabled
+    fn propagate_change(
+        &self,
+        policy: Policy,
+        entry: Entry,
+        metadata: Option<Metadata>,
+        action: Action,
+    ) {
+        // Peers only take drop entries, which monitor ones must not become
+        if let Some(peer_service) = &self.peer_service
+            && policy != Policy::Monitor
+        {
+            peer_service.queue_job(&entry, action.clone());
+        }
+        if let Some(reporting_service) = &self.reporting_service {
+            reporting_service.add_report(Report {
+                action,
+                policy,
+                entry,
+                metadata,
+            });
+        }
     }
 
     pub fn get_stats(&self) -> Result<Stats, CompositeError> {
@@ -366,165 +1106,448 @@ impl FirewallService {
 
         let mut xdp_stats = HashMap::with_capacity(LABELS.len());
 
-        for (i, vals) in self.stats.iter().enumerate() {
-            let label = LABELS.get(i).copied().unwrap_or("UNKNOWN");
-
-            match vals {
-                Ok(vals) => {
-                    let counter = vals.iter().fold(PktStats::default(), |mut acc, cpuvalue| {
-                        acc.rx_packets = acc.rx_packets.saturating_add(cpuvalue.rx_packets);
-                        acc.rx_bytes = acc.rx_bytes.saturating_add(cpuvalue.rx_bytes);
-                        acc
-                    });
-
-                    if i >= LABELS.len() {
-                        warn!("Unexpected stats index: {} (max: {})", i, LABELS.len() - 1);
-                        xdp_stats.insert(format!("UNKNOWN_{i}"), counter);
-                    } else {
-                        xdp_stats.insert(label.to_string(), counter);
-                    }
-                }
-                Err(e) => {
-                    error!("Error reading stats for {label}: {e}");
-                    xdp_stats.insert(label.to_string(), PktStats::default());
-                    return Err(CompositeError::new(
-                        ErrorCode::Einternal,
-                        &format!("Error reading stats for {label}: {e}"),
-                    ));
-                }
+        for (i, counter) in self.backend.xdp_stats()?.into_iter().enumerate() {
+            if let Some(label) = LABELS.get(i) {
+                xdp_stats.insert((*label).to_string(), counter);
+            } else {
+                warn!("Unexpected stats index: {} (max: {})", i, LABELS.len() - 1);
+                xdp_stats.insert(format!("UNKNOWN_{i}"), counter);
             }
         }
 
+        // Same order as the DROP_REASON_* indexes in the eBPF program
+        const DROP_REASONS: [&str; 4] = [
+            "matched_drop_v4",
+            "matched_drop_v6",
+            "malformed",
+            "lockdown",
+        ];
+
+        let drop_reasons = DROP_REASONS
+            .iter()
+            .zip(self.backend.drop_reasons()?)
+            .map(|(label, counter)| ((*label).to_string(), counter))
+            .collect();
+
         let stats = Stats {
             drop_cidr_count: self.drop_v4.count() + self.drop_v6.count(),
             ignore_cidr_count: self.ignore_v4.count() + self.ignore_v6.count(),
+            monitor_cidr_count: self.monitor_v4.count() + self.monitor_v6.count(),
             xdp: xdp_stats,
+            drop_reasons,
         };
 
         Ok(stats)
     }
 
-    fn get_stats_tags_from_map(
-        &self,
-        map: &LruHashMap<MapData, u64, PktStats>,
-    ) -> Result<TagStats, CompositeError> {
-        let mut tag_stats = HashMap::new();
-
-        for item in map.iter() {
-            match item {
-                Ok((tag_id, per_cpu_stats)) => {
-                    let total = per_cpu_stats.iter().fold(
-                        PktStats {
-                            rx_packets: 0,
-                            rx_bytes: 0,
-                        },
-                        |mut acc, s| {
-                            acc.rx_packets += s.rx_packets;
-                            acc.rx_bytes += s.rx_bytes;
-                            acc
-                        },
-                    );
+    pub fn get_stats_tags(&self, policy: Policy) -> Result<TagStats, CompositeError> {
+        let totals = self.backend.tag_stats(policy)?;
 
-                    match self.tag_registry.get_tag_display(tag_id) {
-                        Ok(Some(tag_name)) => {
-                            tag_stats.insert(tag_name, total);
-                        }
-                        Ok(None) => {
-                            debug!("Tag ID {tag_id} not found in registry");
-                        }
-                        Err(e) => {
-                            error!("Error getting tag name for ID {tag_id}: {e}");
-                            return Err(CompositeError::new(
-                                ErrorCode::Einternal,
-                                &format!("Tag acquisition error: {e}"),
-                            ));
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Error reading tag stats: {e}");
-                    return Err(CompositeError::new(
-                        ErrorCode::Einternal,
-                        &format!("Error reading tag stats: {e}"),
-                    ));
-                }
-            }
-        }
+        // Resolve all names at once rather than locking the registry per tag
+        let tag_stats = self.tag_registry.resolve_display(totals).map_err(|e| {
+            error!("Error getting tag names: {e}");
+            CompositeError::new(ErrorCode::Einternal, &format!("Tag acquisition error: {e}"))
+        })?;
 
         Ok(TagStats { tags: tag_stats })
     }
 
-    pub fn get_stats_tags(&self, policy: Policy) -> Result<TagStats, CompositeError> {
-        let map = match policy {
-            Policy::Drop => &self.drop_stats_per_tag,
-            Policy::Ignore => &self.ignore_stats_per_tag,
-        };
-        self.get_stats_tags_from_map(map)
+    /// Zeroes the XDP, drop reason, per tag and per entry counters, the
+    /// entries themselves being left untouched.
+    pub fn reset_stats(&self) -> Result<(), CompositeError> {
+        self.backend.reset_stats()
+    }
+
+    /// Reloads all sets from configuration directories. Drop sets overlapping
+    /// a protected range are refused unless `force` is set, as are, during
+    /// lockdown, ignore sets no longer covering a management range.
+    pub fn reload_sets(&self, force: bool) -> Result<Vec<SetsReload>, CompositeError> {
+        self.reload_sets_with(if force {
+            DropGuards::Force
+        } else {
+            DropGuards::Refuse
+        })
+    }
+
+    /// Reloads all sets after their files changed, drop sets refused being
+    /// skipped as at startup, so that one refused set does not keep the
+    /// others from reloading
+    pub fn reload_changed_sets(&self) -> Result<Vec<SetsReload>, CompositeError> {
+        self.reload_sets_with(DropGuards::SkipSet)
     }
 
-    /// Reloads all sets from configuration directories
-    pub fn reload_sets(&self) -> Result<(), CompositeError> {
+    /// Reloads the sets of every policy, a policy failing to reload keeping
+    /// its sets as loaded without stopping the next ones, except drop sets
+    /// that are not reloaded without the ignore sets. The first error is
+    /// returned once all policies were tried.
+    fn reload_sets_with(&self, guards: DropGuards) -> Result<Vec<SetsReload>, CompositeError> {
         let sets_ignore_dir = Path::new(&self.config.working_dir)
             .join("sets")
             .join("ignore");
         let sets_drop_dir = Path::new(&self.config.working_dir)
             .join("sets")
             .join("drop");
+        let sets_monitor_dir = Path::new(&self.config.working_dir)
+            .join("sets")
+            .join("monitor");
 
         // Reload ignore sets first (to avoid lockout)
+        let mut reloads = Vec::with_capacity(3);
+        let mut first_error = None;
         for (sets_dir, policy) in [
             (sets_ignore_dir, Policy::Ignore),
             (sets_drop_dir, Policy::Drop),
+            (sets_monitor_dir, Policy::Monitor),
         ] {
-            let sets_dir_str = sets_dir.to_str().ok_or_else(|| {
-                CompositeError::new(
-                    ErrorCode::Einternal,
-                    &format!("Failed to convert {} to string", sets_dir.display()),
-                )
-            })?;
-            self.reload_sets_from_dir(sets_dir_str, policy)?;
+            // Only the ignore sets are reloaded before, so new drop entries
+            // never come without the ignore entries they may rely on
+            if policy == Policy::Drop && first_error.is_some() {
+                continue;
+            }
+            let reload = sets_dir
+                .to_str()
+                .ok_or_else(|| {
+                    CompositeError::new(
+                        ErrorCode::Einternal,
+                        &format!("Failed to convert {} to string", sets_dir.display()),
+                    )
+                })
+                .and_then(|sets_dir| self.reload_sets_from_dir(sets_dir, policy, guards));
+            match reload {
+                Ok(reload) => reloads.push(reload),
+                Err(e) => {
+                    error!(policy = %policy, error = %e, "sets reload failed");
+                    first_error.get_or_insert(e);
+                }
+            }
         }
 
-        Ok(())
+        first_error.map_or(Ok(reloads), Err)
     }
 
     /// Reloads sets from a specific directory for a given policy
-    fn reload_sets_from_dir(&self, set_path: &str, policy: Policy) -> Result<(), CompositeError> {
+    fn reload_sets_from_dir(
+        &self,
+        set_path: &str,
+        policy: Policy,
+        guards: DropGuards,
+    ) -> Result<SetsReload, CompositeError> {
+        let _guard = self.lock_reconciling()?;
+        // Taken before reading the files, so a file modified during the
+        // reload is still reported as changed afterwards
+        let mut mtimes = set_mtimes(Path::new(set_path))?;
         let set_names = self.sets_names_from_dir(set_path)?;
 
         let mut target_set_v4 = HashMap::new();
         let mut target_set_v6 = HashMap::new();
+        let mut skipped = Vec::new();
 
         for set_name in &set_names {
-            self.entries_from_set(set_path, set_name, &mut target_set_v4, &mut target_set_v6)?;
+            let abs_path = Path::new(set_path).join(set_name);
+            let content = read_set_file(&abs_path)?;
+            if let Err(error) = self.verify_set(&abs_path, &content) {
+                warn!(set.path = %abs_path.display(), error = %error, "set file skipped");
+                // Keep what the set loaded before, and report it as changed
+                // until a file that verifies is reloaded
+                self.loaded_set_entries(policy, set_name, &mut target_set_v4, &mut target_set_v6);
+                let name = set_name.trim_end_matches(SET_EXTENSION).to_string();
+                mtimes.remove(&name);
+                skipped.push(SkippedSet {
+                    name,
+                    error: error.0,
+                });
+                continue;
+            }
+            let (mut set_v4, mut set_v6) = (HashMap::new(), HashMap::new());
+            self.entries_from_set(&content, set_name, &abs_path, &mut set_v4, &mut set_v6)?;
+            if policy == Policy::Drop
+                && guards == DropGuards::SkipSet
+                && let Err(error) = self.check_drop_guards(set_v4.values().chain(set_v6.values()))
+            {
+                warn!(set.path = %abs_path.display(), error = %error, "drop set refused, skipped");
+                let name = set_name.trim_end_matches(SET_EXTENSION).to_string();
+                mtimes.remove(&name);
+                skipped.push(SkippedSet {
+                    name,
+                    error: error.0,
+                });
+                continue;
+            }
+            target_set_v4.extend(set_v4);
+            target_set_v6.extend(set_v6);
+        }
+
+        if policy == Policy::Drop && guards == DropGuards::Refuse {
+            check_protected_cidrs(
+                &self.config.firewall.protected_cidrs,
+                target_set_v4.values().chain(target_set_v6.values()),
+            )?;
+            check_min_prefix(
+                self.config.firewall.min_prefix,
+                target_set_v4.values().chain(target_set_v6.values()),
+            )?;
         }
 
-        let counter_v4 = self.update_lpm_store(policy, true, target_set_v4)?;
-        let counter_v6 = self.update_lpm_store(policy, false, target_set_v6)?;
+        // Check both families before touching either, so a set that does not
+        // fit is never half-applied
+        let _reserved_v4 = self.reserve_lpm_store_capacity(policy, true, None, &target_set_v4)?;
+        let _reserved_v6 = self.reserve_lpm_store_capacity(policy, false, None, &target_set_v6)?;
+
+        // Held until the sets are applied, so that lockdown is not enabled in
+        // between
+        let lockdown = self
+            .lockdown
+            .lock()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        if policy == Policy::Ignore && *lockdown && guards != DropGuards::Force {
+            self.check_set_management_kept(None, &target_set_v4, &target_set_v6)?;
+        }
+        let counter_v4 = self.update_lpm_store(policy, true, None, target_set_v4)?;
+        let counter_v6 = self.update_lpm_store(policy, false, None, target_set_v6)?;
+        drop(lockdown);
 
+        let reload = SetsReload {
+            policy,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            created: counter_v4.created + counter_v6.created,
+            updated: counter_v4.updated + counter_v6.updated,
+            removed: counter_v4.removed + counter_v6.removed,
+            skipped,
+        };
         info!(
-            "sets reload: policy={}, updated={}, removed={}, created={}",
+            "sets reload: policy={}, updated={}, removed={}, created={}, skipped={}",
             policy,
-            counter_v4.updated + counter_v6.updated,
-            counter_v4.removed + counter_v6.removed,
-            counter_v4.created + counter_v6.created
+            reload.updated,
+            reload.removed,
+            reload.created,
+            reload.skipped.len()
         );
 
-        Ok(())
+        self.loaded_sets
+            .write()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?
+            .insert(policy, mtimes);
+        self.last_reloads
+            .write()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?
+            .insert(policy, reload.clone());
+
+        Ok(reload)
     }
 
-    fn update_lpm_store(
+    /// Refuses drop entries overlapping a protected range or broader than
+    /// the minimum prefix length
+    fn check_drop_guards<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a Entry> + Clone,
+    ) -> Result<(), CompositeError> {
+        check_protected_cidrs(&self.config.firewall.protected_cidrs, entries.clone())?;
+        check_min_prefix(self.config.firewall.min_prefix, entries)
+    }
+
+    /// Reloads a single set, the other sets of every policy being left as
+    /// loaded, so ignore sets stay in place while a drop set is reloaded. The
+    /// entries of a set whose file was deleted are removed. A drop set
+    /// overlapping a protected range is refused unless `force` is set, as is,
+    /// during lockdown, an ignore set no longer covering a management range.
+    pub fn reload_set(
         &self,
         policy: Policy,
-        is_ipv4: bool,
-        target_set: HashMap<NormalizedCidr, Entry>,
-    ) -> Result<SetCounter, CompositeError> {
-        let store = self.get_lpm_store(policy, is_ipv4);
+        name: &SetName,
+        force: bool,
+    ) -> Result<SetsReload, CompositeError> {
+        let _guard = self.lock_reconciling()?;
+        let abs_path = self.get_set_path(policy, name)?;
+        let set_name = format!("{name}{SET_EXTENSION}");
 
-        // Get all stored entries and filter to sets (tag ends with SET_EXTENSION)
-        let mut stored_map: HashMap<NormalizedCidr, StoredEntry> = store
-            .list_sets_stored()
-            .into_iter()
+        let mut target_set_v4 = HashMap::new();
+        let mut target_set_v6 = HashMap::new();
+        let mut skipped = Vec::new();
+        let mut mtime = None;
+
+        if abs_path.exists() {
+            self.check_set_file(&abs_path)?;
+            // Taken before reading the file, as for a full reload
+            let modified = fs::metadata(&abs_path)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|e| {
+                    CompositeError::new(
+                        ErrorCode::Einternal,
+                        &format!(
+                            "Failed to get metadata for file {}: {e}",
+                            abs_path.display()
+                        ),
+                    )
+                })?;
+            let content = read_set_file(&abs_path)?;
+            if let Err(error) = self.verify_set(&abs_path, &content) {
+                warn!(set.path = %abs_path.display(), error = %error, "set file skipped");
+                self.loaded_set_entries(policy, &set_name, &mut target_set_v4, &mut target_set_v6);
+                skipped.push(SkippedSet {
+                    name: name.to_string(),
+                    error: error.0,
+                });
+            } else {
+                self.entries_from_set(
+                    &content,
+                    &set_name,
+                    &abs_path,
+                    &mut target_set_v4,
+                    &mut target_set_v6,
+                )?;
+                mtime = Some(modified);
+            }
+        } else if !self.is_set_loaded(policy, name)? {
+            return Err(CompositeError::new(
+                ErrorCode::Enotfound,
+                &format!("Set '{name}' not found for policy '{policy}'"),
+            ));
+        }
+
+        // Entries listed by another set stay with it, so that reloading one
+        // set never takes over or removes the entries of another
+        for (is_ipv4, target) in [(true, &mut target_set_v4), (false, &mut target_set_v6)] {
+            let (_, sets) = self.stored_and_set_entries(self.get_lpm_store(policy, is_ipv4));
+            let mut own = sets.clone();
+            self.retain_set_entries(&mut own, &set_name);
+            target.retain(|cidr, _| own.contains_key(cidr) || !sets.contains_key(cidr));
+        }
+
+        if policy == Policy::Drop && !force {
+            check_protected_cidrs(
+                &self.config.firewall.protected_cidrs,
+                target_set_v4.values().chain(target_set_v6.values()),
+            )?;
+            check_min_prefix(
+                self.config.firewall.min_prefix,
+                target_set_v4.values().chain(target_set_v6.values()),
+            )?;
+        }
+
+        let _reserved_v4 =
+            self.reserve_lpm_store_capacity(policy, true, Some(&set_name), &target_set_v4)?;
+        let _reserved_v6 =
+            self.reserve_lpm_store_capacity(policy, false, Some(&set_name), &target_set_v6)?;
+
+        let lockdown = self
+            .lockdown
+            .lock()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        if policy == Policy::Ignore && *lockdown && !force {
+            self.check_set_management_kept(Some(&set_name), &target_set_v4, &target_set_v6)?;
+        }
+        let counter_v4 = self.update_lpm_store(policy, true, Some(&set_name), target_set_v4)?;
+        let counter_v6 = self.update_lpm_store(policy, false, Some(&set_name), target_set_v6)?;
+        drop(lockdown);
+
+        let reload = SetsReload {
+            policy,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            created: counter_v4.created + counter_v6.created,
+            updated: counter_v4.updated + counter_v6.updated,
+            removed: counter_v4.removed + counter_v6.removed,
+            skipped,
+        };
+        info!(
+            "set reload: policy={}, set={}, updated={}, removed={}, created={}, skipped={}",
+            policy,
+            name,
+            reload.updated,
+            reload.removed,
+            reload.created,
+            reload.skipped.len()
+        );
+
+        // Only this set is marked as loaded, the last reload of the policy
+        // keeps describing the last full reload
+        let mut loaded_sets = self
+            .loaded_sets
+            .write()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        let mtimes = loaded_sets.entry(policy).or_default();
+        match mtime {
+            Some(modified) => mtimes.insert(name.to_string(), modified),
+            None => mtimes.remove(name.as_str()),
+        };
+
+        Ok(reload)
+    }
+
+    /// Whether a set was loaded by the last reload, or has entries loaded
+    fn is_set_loaded(&self, policy: Policy, name: &SetName) -> Result<bool, CompositeError> {
+        let in_last_reload = self
+            .loaded_sets
+            .read()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?
+            .get(&policy)
+            .is_some_and(|mtimes| mtimes.contains_key(name.as_str()));
+        if in_last_reload {
+            return Ok(true);
+        }
+        let set_name = format!("{name}{SET_EXTENSION}");
+        let (mut v4, mut v6) = (HashMap::new(), HashMap::new());
+        self.loaded_set_entries(policy, &set_name, &mut v4, &mut v6);
+        Ok(!v4.is_empty() || !v6.is_empty())
+    }
+
+    /// Time and changes of the last reload of each policy
+    pub fn sets_status(&self) -> Result<SetsStatus, CompositeError> {
+        let last_reloads = self
+            .last_reloads
+            .read()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+        let reloads: Vec<SetsReload> = [Policy::Ignore, Policy::Drop, Policy::Monitor]
+            .iter()
+            .filter_map(|policy| last_reloads.get(policy).cloned())
+            .collect();
+        Ok(SetsStatus {
+            last_reload: reloads.iter().map(|reload| reload.time).max(),
+            reloads,
+        })
+    }
+
+    /// Compares the set files on disk with the ones loaded by the last reload
+    pub fn sets_reload_status(&self) -> Result<SetsReloadStatus, CompositeError> {
+        let loaded_sets = self
+            .loaded_sets
+            .read()
+            .map_err(|_| CompositeError::new(ErrorCode::Einternal, "Failed to acquire lock"))?;
+
+        let mut changed_sets = Vec::new();
+        for policy in [Policy::Ignore, Policy::Drop, Policy::Monitor] {
+            let on_disk = set_mtimes(&self.get_sets_dir(policy)?)?;
+            let loaded = loaded_sets.get(&policy).cloned().unwrap_or_default();
+            changed_sets.extend(
+                changed_set_names(&loaded, &on_disk)
+                    .into_iter()
+                    .map(|name| ChangedSet { policy, name }),
+            );
+        }
+
+        Ok(SetsReloadStatus {
+            reload_required: !changed_sets.is_empty(),
+            changed_sets,
+        })
+    }
+
+    /// Stored entries of a store, and the subset of them coming from sets
+    fn stored_and_set_entries(
+        &self,
+        store: &LpmStore,
+    ) -> (
+        HashMap<NormalizedCidr, StoredEntry>,
+        HashMap<NormalizedCidr, StoredEntry>,
+    ) {
+        let stored: HashMap<NormalizedCidr, StoredEntry> =
+            store.list_sets_stored().into_iter().collect();
+        let sets = stored
+            .iter()
             .filter(|(_, stored)| {
                 // Check if tag ends with SET_EXTENSION
                 self.tag_registry
@@ -533,14 +1556,100 @@ impl FirewallService {
                     .flatten()
                     .is_some_and(|tag| tag.ends_with(SET_EXTENSION))
             })
+            .map(|(cidr, stored)| (*cidr, *stored))
             .collect();
+        (stored, sets)
+    }
+
+    /// Stored entries of a store coming from `set_name`, a set file name
+    fn retain_set_entries(&self, sets: &mut HashMap<NormalizedCidr, StoredEntry>, set_name: &str) {
+        sets.retain(|_, stored| {
+            self.tag_registry
+                .get_tag(stored.tag_id)
+                .ok()
+                .flatten()
+                .as_deref()
+                == Some(set_name)
+        });
+    }
+
+    /// Checks that `target_set` fits in a store once it replaces the entries
+    /// of all sets, or of `set` only, setting aside the room it needs until
+    /// the returned reservation is dropped
+    fn reserve_lpm_store_capacity(
+        &self,
+        policy: Policy,
+        is_ipv4: bool,
+        set: Option<&str>,
+        target_set: &HashMap<NormalizedCidr, Entry>,
+    ) -> Result<Reservation<'_>, CompositeError> {
+        let store = self.get_lpm_store(policy, is_ipv4);
+        // Set entries only change through reloads, which are serialized
+        let (_, mut stored_sets) = self.stored_and_set_entries(store);
+        if let Some(set_name) = set {
+            self.retain_set_entries(&mut stored_sets, set_name);
+        }
+        store.reserve(|stored, available| {
+            check_set_capacity(policy, stored, &stored_sets, target_set, available)
+        })
+    }
+
+    /// Refuses, during lockdown, to reload the ignore sets, or `set` only,
+    /// when it would remove a set entry covering a management range
+    fn check_set_management_kept(
+        &self,
+        set: Option<&str>,
+        target_set_v4: &HashMap<NormalizedCidr, Entry>,
+        target_set_v6: &HashMap<NormalizedCidr, Entry>,
+    ) -> Result<(), CompositeError> {
+        let mut removals = Vec::new();
+        for (is_ipv4, target_set) in [(true, target_set_v4), (false, target_set_v6)] {
+            let (_, mut stored_sets) =
+                self.stored_and_set_entries(self.get_lpm_store(Policy::Ignore, is_ipv4));
+            if let Some(set_name) = set {
+                self.retain_set_entries(&mut stored_sets, set_name);
+            }
+            removals.extend(
+                stored_sets
+                    .into_keys()
+                    .filter(|cidr| !target_set.contains_key(cidr)),
+            );
+        }
+        check_management_kept(&self.config.firewall.management_cidrs, &removals)
+    }
+
+    /// Replaces the entries of all sets of a store, or of `set` only, with
+    /// `target_set`
+    fn update_lpm_store(
+        &self,
+        policy: Policy,
+        is_ipv4: bool,
+        set: Option<&str>,
+        target_set: HashMap<NormalizedCidr, Entry>,
+    ) -> Result<SetCounter, CompositeError> {
+        let store = self.get_lpm_store(policy, is_ipv4);
+
+        let (_, mut stored_map) = self.stored_and_set_entries(store);
+        if let Some(set_name) = set {
+            self.retain_set_entries(&mut stored_map, set_name);
+        }
 
         let mut counter = SetCounter::default();
 
-        // Process entries in target_set
+        // Remove entries that exist in stored but not in target first, so the
+        // store never goes over its capacity mid-reload
+        let mut changes: Vec<_> = stored_map
+            .keys()
+            .filter(|key| !target_set.contains_key(key))
+            .map(|key| (*key, None))
+            .collect();
+        let removals = changes.len();
+
+        // Add the entries of target_set missing from the store, and update
+        // the ones whose tag or expiration changed
+        let mut acquired: Vec<StoredEntry> = Vec::new();
         for (target_key, target_entry) in target_set {
             if let Some(existing_stored) = stored_map.get(&target_key) {
-                // Entry exists - check if update needed
                 let target_tag = target_entry.tag.as_deref().unwrap_or("");
 
                 // Get existing tag name to compare
@@ -552,60 +1661,68 @@ impl FirewallService {
                     .map(|s| s.to_string())
                     .unwrap_or_default();
 
-                if target_tag != existing_tag
-                    || target_entry.expiration.as_timestamp() != existing_stored.expiration
+                if target_tag == existing_tag
+                    && target_entry.expiration.as_timestamp() == existing_stored.expiration
                 {
-                    // Need to update - acquire new tag and update
-                    let new_stored = self.entry_to_stored(&target_entry)?;
-                    match store.update_stored(target_key, new_stored) {
-                        Ok(old_stored) => {
-                            // Release old tag
-                            self.release_tag(old_stored.tag_id);
-                            counter.updated += 1;
-                        }
-                        Err(e) => {
-                            // Release new tag on failure
-                            self.release_tag(new_stored.tag_id);
-                            return Err(e);
-                        }
-                    }
+                    continue;
                 }
-                // Mark as processed
-                stored_map.remove(&target_key);
-            } else {
-                // Entry doesn't exist - add it
-                let new_stored = self.entry_to_stored(&target_entry)?;
-                match store.add_or_update_stored(target_key, new_stored) {
-                    Ok(old_opt) => {
-                        // Release old tag if there was one
-                        if let Some(old_stored) = old_opt {
-                            self.release_tag(old_stored.tag_id);
-                        }
-                        counter.created += 1;
-                    }
-                    Err(e) => {
-                        // Release new tag on failure
+            }
+
+            let new_stored = match self.entry_to_stored(&target_entry, false) {
+                Ok(new_stored) => new_stored,
+                Err(e) => {
+                    for new_stored in &acquired {
                         self.release_tag(new_stored.tag_id);
-                        return Err(e);
                     }
+                    return Err(e);
                 }
-            }
+            };
+            acquired.push(new_stored);
+            changes.push((target_key, Some(new_stored)));
         }
 
-        // Remove entries that exist in stored but not in target
-        for (key, stored) in stored_map {
-            match store.remove_stored(key) {
-                Ok(_) => {
+        // Applied as one batch, so lookups never see the sets half reloaded.
+        // Results come in the order of the changes.
+        let mut results = store.apply_batch(changes).into_iter();
+        for (key, result) in results.by_ref().take(removals) {
+            match result {
+                Ok(Some(stored)) => {
                     // Release tag
                     self.release_tag(stored.tag_id);
                     counter.removed += 1;
                 }
+                Ok(None) => {}
                 Err(e) => {
                     error!("Failed to remove set entry {key}: {e}");
                 }
             }
         }
 
+        let mut first_error = None;
+        for ((key, result), new_stored) in results.zip(acquired) {
+            match result {
+                Ok(Some(old_stored)) => {
+                    // Release old tag
+                    self.release_tag(old_stored.tag_id);
+                    counter.updated += 1;
+                }
+                Ok(None) => {
+                    self.reset_entry_hits(policy, key);
+                    counter.created += 1;
+                }
+                Err(e) => {
+                    // Release new tag on failure
+                    self.release_tag(new_stored.tag_id);
+                    error!("Failed to apply set entry {key}: {e}");
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        if let Some(e) = first_error {
+            return Err(e);
+        }
+
         Ok(counter)
     }
 
@@ -634,36 +1751,7 @@ impl FirewallService {
                     .extension()
                     .is_some_and(|ext| ext == SET_EXTENSION.trim_start_matches('.'))
             {
-                // Check permissions
-                SecurityService::check_owner_group_perms(
-                    &path,
-                    &self.config.user,
-                    &self.config.group,
-                    SEC_FILE_PERM,
-                )
-                .map_err(|e| {
-                    CompositeError::new(
-                        ErrorCode::Einvalid,
-                        &format!("Set file {} has wrong permissions: {e}", path.display()),
-                    )
-                })?;
-
-                // Check file size
-                let metadata = fs::metadata(&path).map_err(|e| {
-                    CompositeError::new(
-                        ErrorCode::Einternal,
-                        &format!("Failed to get metadata for file {}: {}", path.display(), e),
-                    )
-                })?;
-                if metadata.len() > MAX_SET_FILE_SIZE {
-                    return Err(CompositeError::new(
-                        ErrorCode::Einvalid,
-                        &format!(
-                            "File {} exceeds the maximum allowed size of 5 MB",
-                            path.display()
-                        ),
-                    ));
-                }
+                self.check_set_file(&path)?;
                 if let Some(file_stem) = path.file_stem().and_then(|name| name.to_str()) {
                     if file_stem
                         .chars()
@@ -689,30 +1777,103 @@ impl FirewallService {
         Ok(result)
     }
 
-    /// Reads entries from a set file
-    fn entries_from_set(
-        &self,
-        path: &str,
-        set_name: &str,
-        v4: &mut HashMap<NormalizedCidr, Entry>,
-        v6: &mut HashMap<NormalizedCidr, Entry>,
-    ) -> Result<(), CompositeError> {
-        let abs_path = Path::new(path).join(set_name);
-        let file = fs::File::open(&abs_path).map_err(|e| {
+    /// Checks the permissions and size of a set file before it is loaded
+    fn check_set_file(&self, path: &Path) -> Result<(), CompositeError> {
+        SecurityService::check_owner_group_perms(
+            path,
+            &self.config.user,
+            &self.config.group,
+            SEC_FILE_PERM,
+        )
+        .map_err(|e| {
+            CompositeError::new(
+                ErrorCode::Einvalid,
+                &format!("Set file {} has wrong permissions: {e}", path.display()),
+            )
+        })?;
+
+        let metadata = fs::metadata(path).map_err(|e| {
             CompositeError::new(
                 ErrorCode::Einternal,
-                &format!("Failed to read file {path}: {e}"),
+                &format!("Failed to get metadata for file {}: {}", path.display(), e),
             )
         })?;
-        let reader = BufReader::new(file);
+        if metadata.len() > MAX_SET_FILE_SIZE {
+            return Err(CompositeError::new(
+                ErrorCode::Einvalid,
+                &format!(
+                    "File {} exceeds the maximum allowed size of 5 MB",
+                    path.display()
+                ),
+            ));
+        }
+        Ok(())
+    }
 
-        for line in reader.lines() {
-            let line = line.map_err(|e| {
-                CompositeError::new(
+    /// Checks the detached signature of a set file when a public key is
+    /// configured
+    fn verify_set(&self, path: &Path, content: &str) -> Result<(), CompositeError> {
+        let Some(verifier) = &self.set_verifier else {
+            return Ok(());
+        };
+        let sig_path = signature_path(path);
+        let signature = fs::read(&sig_path).map_err(|e| {
+            CompositeError::new(
+                ErrorCode::Einvalid,
+                &format!("Failed to read signature {}: {e}", sig_path.display()),
+            )
+        })?;
+        verifier
+            .verify(content.as_bytes(), &signature)
+            .map_err(|e| {
+                let mut ce = CompositeError::new(
                     ErrorCode::Einvalid,
-                    &format!("Failed to read file {path}: {e}"),
-                )
-            })?;
+                    &format!("Set file {} failed verification", path.display()),
+                );
+                ce.add_detail("signature", e.0.code, &e.0.message);
+                ce
+            })
+    }
+
+    /// Entries currently loaded from a set, kept as is when its file is
+    /// skipped
+    fn loaded_set_entries(
+        &self,
+        policy: Policy,
+        set_name: &str,
+        v4: &mut HashMap<NormalizedCidr, Entry>,
+        v6: &mut HashMap<NormalizedCidr, Entry>,
+    ) {
+        for (is_ipv4, target) in [(true, v4), (false, v6)] {
+            let (_, sets) = self.stored_and_set_entries(self.get_lpm_store(policy, is_ipv4));
+            for (cidr, stored) in sets {
+                let tag = self.tag_registry.get_tag(stored.tag_id).ok().flatten();
+                if tag.as_deref() == Some(set_name) {
+                    target.insert(
+                        cidr,
+                        Entry {
+                            creation: stored.creation,
+                            cidr,
+                            tag: Some(set_name.to_string()),
+                            expiration: Expiration::never(),
+                            source: Some(EntrySource::set()),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Parses the entries of a set file content
+    fn entries_from_set(
+        &self,
+        content: &str,
+        set_name: &str,
+        abs_path: &Path,
+        v4: &mut HashMap<NormalizedCidr, Entry>,
+        v6: &mut HashMap<NormalizedCidr, Entry>,
+    ) -> Result<(), CompositeError> {
+        for line in content.lines() {
             let line = line.trim();
 
             // Skip empty lines and comments
@@ -748,6 +1909,7 @@ impl FirewallService {
                 cidr,
                 tag: Some(set_name.to_string()),
                 expiration: Expiration::never(),
+                source: Some(EntrySource::set()),
             }),
             Err(_) => Err(CompositeError::new(
                 ErrorCode::Einvalid,
@@ -782,20 +1944,28 @@ impl FirewallService {
         Ok(sets_dir.join(format!("{name}{SET_EXTENSION}")))
     }
 
-    /// Writes entries to a set file atomically
+    /// Gets the path a set file is renamed to while the set is disabled
+    fn get_disabled_set_path(
+        &self,
+        policy: Policy,
+        name: &SetName,
+    ) -> Result<std::path::PathBuf, CompositeError> {
+        let sets_dir = self.get_sets_dir(policy)?;
+        Ok(sets_dir.join(format!("{name}{SET_EXTENSION}{DISABLED_SET_EXTENSION}")))
+    }
+
+    /// Writes the header and entries to a set file atomically, returning the
+    /// ETag of the new content
     fn write_set_file(
         &self,
         path: &std::path::Path,
+        header: &SetHeader,
         entries: &[IpNet],
-    ) -> Result<(), CompositeError> {
+    ) -> Result<String, CompositeError> {
         let tmp_path = path.with_extension("couic.tmp");
 
         // Write content to temp file
-        let content: String = entries
-            .iter()
-            .map(std::string::ToString::to_string)
-            .collect::<Vec<_>>()
-            .join("\n");
+        let content = render_set_file(header, entries);
         fs::write(&tmp_path, &content).map_err(|e| {
             CompositeError::new(
                 ErrorCode::Einternal,
@@ -829,7 +1999,7 @@ impl FirewallService {
             )
         })?;
 
-        Ok(())
+        Ok(content_etag(&content))
     }
 
     /// Lists all sets for a given policy
@@ -851,43 +2021,75 @@ impl FirewallService {
             })?;
 
             let path = entry.path();
-            if path.is_file()
-                && path
-                    .extension()
-                    .is_some_and(|ext| ext == SET_EXTENSION.trim_start_matches('.'))
-            {
-                let metadata = fs::metadata(&path).map_err(|e| {
-                    CompositeError::new(
-                        ErrorCode::Einternal,
-                        &format!("Failed to get file metadata: {e}"),
-                    )
-                })?;
+            let Some((name_str, disabled)) = set_file_name(&path) else {
+                continue;
+            };
+            if !path.is_file() {
+                continue;
+            }
+            let metadata = fs::metadata(&path).map_err(|e| {
+                CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Failed to get file metadata: {e}"),
+                )
+            })?;
 
-                if let Some(name_str) = path.file_stem().and_then(|n| n.to_str()) {
-                    // Only include valid set names
-                    if let Ok(name) = SetName::try_from(name_str) {
-                        // Count non-empty, non-comment lines
-                        let content = fs::read_to_string(&path).unwrap_or_default();
-                        let entry_count = content
-                            .lines()
-                            .filter(|l| !l.trim().is_empty() && !l.trim().starts_with('#'))
-                            .count();
-
-                        sets.push(SetSummary {
-                            name,
-                            entry_count,
-                            file_size: metadata.len(),
-                        });
-                    }
-                }
+            // Only include valid set names
+            if let Ok(name) = SetName::try_from(name_str) {
+                // Count non-empty, non-comment lines
+                let content = fs::read_to_string(&path).unwrap_or_default();
+                let entry_count = content
+                    .lines()
+                    .filter(|l| !l.trim().is_empty() && !l.trim().starts_with('#'))
+                    .count();
+
+                sets.push(SetSummary {
+                    name,
+                    header: SetHeader::parse(&content),
+                    entry_count,
+                    file_size: metadata.len(),
+                    disabled,
+                });
             }
         }
 
-        Ok(sets)
-    }
+        // Files copied into the directory are not bound by the limit
+        if sets.len() > self.config.sets.max_sets {
+            warn!(
+                "policy {policy} has {} sets, over the {} of sets.max_sets, which slows down reloads",
+                sets.len(),
+                self.config.sets.max_sets
+            );
+        }
+
+        Ok(sets)
+    }
+
+    /// Counts the entries of all the sets of a policy, those listed by
+    /// several sets once
+    pub fn sets_summary(&self, policy: Policy) -> Result<SetsSummary, CompositeError> {
+        let mut sets = Vec::new();
+        // Disabled sets load no entries
+        for summary in self.list_sets(policy)?.iter().filter(|set| !set.disabled) {
+            let (set, _) = self.get_set(policy, &summary.name)?;
+            sets.push(set.entries);
+        }
+        let mut summary = SetsSummary::new(sets.iter().map(Vec::as_slice));
+        let (store_v4, store_v6) = (
+            self.get_lpm_store(policy, true),
+            self.get_lpm_store(policy, false),
+        );
+        summary.maps = Some(MapsUsage {
+            v4: store_v4.count(),
+            max_v4: store_v4.max_entries(),
+            v6: store_v6.count(),
+            max_v6: store_v6.max_entries(),
+        });
+        Ok(summary)
+    }
 
-    /// Gets a specific set by name
-    pub fn get_set(&self, policy: Policy, name: &SetName) -> Result<Set, CompositeError> {
+    /// Gets a specific set by name, along with the ETag of its file
+    pub fn get_set(&self, policy: Policy, name: &SetName) -> Result<(Set, String), CompositeError> {
         let set_path = self.get_set_path(policy, name)?;
 
         if !set_path.exists() {
@@ -925,7 +2127,7 @@ impl FirewallService {
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
-            match line.parse::<IpNet>() {
+            match parse_cidr(line) {
                 Ok(cidr) => entries.push(cidr),
                 Err(e) => {
                     errors.add_detail(
@@ -941,20 +2143,27 @@ impl FirewallService {
             return Err(errors);
         }
 
-        Ok(Set {
-            name: name.clone(),
-            entries,
-        })
+        Ok((
+            Set {
+                name: name.clone(),
+                header: SetHeader::parse(&content),
+                entries,
+            },
+            content_etag(&content),
+        ))
     }
 
-    /// Creates a new set
+    /// Creates a new set, returning it along with the ETag of its file
     pub fn create_set(
         &self,
         policy: Policy,
         name: &SetName,
+        header: &SetHeader,
         entries: &[IpNet],
-    ) -> Result<Set, CompositeError> {
+    ) -> Result<(Set, String), CompositeError> {
+        self.check_unsigned_set_writes()?;
         let set_path = self.get_set_path(policy, name)?;
+        let _guard = self.lock_set_writes()?;
 
         // Check if set already exists
         if set_path.exists() {
@@ -963,23 +2172,84 @@ impl FirewallService {
                 &format!("Set '{name}' already exists for policy '{policy}'"),
             ));
         }
+        if self.get_disabled_set_path(policy, name)?.exists() {
+            return Err(CompositeError::new(
+                ErrorCode::Econflict,
+                &format!("Set '{name}' already exists for policy '{policy}', disabled"),
+            ));
+        }
+        if self.config.sets.unique_names {
+            self.check_unique_set_name(policy, name)?;
+        }
+        self.check_set_count(policy)?;
+
+        let header = SetHeader::default().merged(header.clone());
+        let etag = self.write_set_file(&set_path, &header, entries)?;
+
+        Ok((
+            Set {
+                name: name.clone(),
+                header,
+                entries: entries.to_vec(),
+            },
+            etag,
+        ))
+    }
 
-        self.write_set_file(&set_path, entries)?;
+    /// Refuses a new set once a policy has `sets.max_sets` sets, disabled
+    /// ones included since enabling them does not check the limit again
+    fn check_set_count(&self, policy: Policy) -> Result<(), CompositeError> {
+        let max_sets = self.config.sets.max_sets;
+        let count = set_file_count(&self.get_sets_dir(policy)?)?;
+        if count >= max_sets {
+            return Err(CompositeError::new(
+                ErrorCode::Econflict,
+                &format!(
+                    "Policy '{policy}' already has {count} sets, the maximum being {max_sets} (sets.max_sets)"
+                ),
+            ));
+        }
+        Ok(())
+    }
 
-        Ok(Set {
-            name: name.clone(),
-            entries: entries.to_vec(),
-        })
+    /// Refuses a set name already used by another policy, which would make
+    /// sets with contradicting entries look alike
+    fn check_unique_set_name(&self, policy: Policy, name: &SetName) -> Result<(), CompositeError> {
+        for other in [Policy::Drop, Policy::Ignore, Policy::Monitor] {
+            let exists = self.get_set_path(other, name)?.exists()
+                || self.get_disabled_set_path(other, name)?.exists();
+            if other != policy && exists {
+                let mut ce = CompositeError::new(
+                    ErrorCode::Econflict,
+                    &format!(
+                        "Set '{name}' already exists for policy '{other}', set names must be unique across policies"
+                    ),
+                );
+                ce.add_detail(
+                    "name",
+                    ErrorCode::Econflict,
+                    &format!("{name} is used by policy {other}"),
+                );
+                return Err(ce);
+            }
+        }
+        Ok(())
     }
 
-    /// Updates an existing set (replaces all entries)
+    /// Updates an existing set (replaces all entries). The header fields not
+    /// set in `header` are kept from the current file. With `if_match`, the
+    /// update only applies if the file still has this ETag.
     pub fn update_set(
         &self,
         policy: Policy,
         name: &SetName,
+        header: &SetHeader,
         entries: &[IpNet],
-    ) -> Result<Set, CompositeError> {
+        if_match: Option<&str>,
+    ) -> Result<(Set, String), CompositeError> {
+        self.check_unsigned_set_writes()?;
         let set_path = self.get_set_path(policy, name)?;
+        let _guard = self.lock_set_writes()?;
 
         // Check if set exists
         if !set_path.exists() {
@@ -989,32 +2259,1356 @@ impl FirewallService {
             ));
         }
 
-        self.write_set_file(&set_path, entries)?;
+        let current = fs::read_to_string(&set_path).map_err(|e| {
+            CompositeError::new(
+                ErrorCode::Einternal,
+                &format!("Failed to read set file: {e}"),
+            )
+        })?;
+        if let Some(expected) = if_match
+            && !etag_matches(expected, &content_etag(&current))
+        {
+            return Err(CompositeError::new(
+                ErrorCode::Eprecondition,
+                &format!("Set '{name}' was modified since it was read"),
+            ));
+        }
+
+        let header = SetHeader::parse(&current).merged(header.clone());
+        let etag = self.write_set_file(&set_path, &header, entries)?;
+
+        Ok((
+            Set {
+                name: name.clone(),
+                header,
+                entries: entries.to_vec(),
+            },
+            etag,
+        ))
+    }
 
-        Ok(Set {
-            name: name.clone(),
-            entries: entries.to_vec(),
+    fn lock_reconciling(&self) -> Result<std::sync::MutexGuard<'_, ()>, CompositeError> {
+        self.reconciling.lock().map_err(|_| {
+            CompositeError::new(ErrorCode::Einternal, "Failed to acquire reconciling lock")
         })
     }
 
+    /// Refuses to write set files when `sets.public_key` is configured, the
+    /// next reload skipping them as unsigned
+    fn check_unsigned_set_writes(&self) -> Result<(), CompositeError> {
+        if self.set_verifier.is_some() {
+            return Err(CompositeError::new(
+                ErrorCode::Eprecondition,
+                "Sets must be signed as sets.public_key is configured: write the set file and its signature, then reload the sets",
+            ));
+        }
+        Ok(())
+    }
+
+    fn lock_set_writes(&self) -> Result<std::sync::MutexGuard<'_, ()>, CompositeError> {
+        self.set_writes.lock().map_err(|_| {
+            CompositeError::new(ErrorCode::Einternal, "Failed to acquire set writes lock")
+        })
+    }
+
+    /// Adds and removes entries of an existing set, its header and other
+    /// entries being kept. With `if_match`, the patch only applies if the file
+    /// still has this ETag.
+    pub fn patch_set(
+        &self,
+        policy: Policy,
+        name: &SetName,
+        patch: &SetPatch,
+        if_match: Option<&str>,
+    ) -> Result<(SetPatchResult, String), CompositeError> {
+        self.check_unsigned_set_writes()?;
+        let set_path = self.get_set_path(policy, name)?;
+        let _guard = self.lock_set_writes()?;
+
+        let (mut set, etag) = self.get_set(policy, name)?;
+        if let Some(expected) = if_match
+            && !etag_matches(expected, &etag)
+        {
+            return Err(CompositeError::new(
+                ErrorCode::Eprecondition,
+                &format!("Set '{name}' was modified since it was read"),
+            ));
+        }
+
+        let (added, removed) = patch.apply(&mut set.entries);
+        let result = SetPatchResult {
+            name: name.clone(),
+            added,
+            removed,
+            entry_count: set.entries.len(),
+        };
+        // Leave the file untouched, so it does not show up as changed
+        if added == 0 && removed == 0 {
+            return Ok((result, etag));
+        }
+
+        let mut errors = CompositeError::new(ErrorCode::Einvalid, "Validation failed");
+        validate_set_entries_size(&set.entries, &mut errors);
+        if errors.has_errors() {
+            return Err(errors);
+        }
+
+        let etag = self.write_set_file(&set_path, &set.header, &set.entries)?;
+        Ok((result, etag))
+    }
+
     /// Deletes a set
     pub fn delete_set(&self, policy: Policy, name: &SetName) -> Result<(), CompositeError> {
         let set_path = self.get_set_path(policy, name)?;
+        let disabled_path = self.get_disabled_set_path(policy, name)?;
+        let _guard = self.lock_set_writes()?;
 
-        if !set_path.exists() {
+        let file_path = if set_path.exists() {
+            &set_path
+        } else if disabled_path.exists() {
+            &disabled_path
+        } else {
             return Err(CompositeError::new(
                 ErrorCode::Enotfound,
                 &format!("Set '{name}' not found for policy '{policy}'"),
             ));
-        }
+        };
 
-        fs::remove_file(&set_path).map_err(|e| {
+        fs::remove_file(file_path).map_err(|e| {
             CompositeError::new(
                 ErrorCode::Einternal,
                 &format!("Failed to delete set file: {e}"),
             )
         })?;
+        // A signature left behind would apply to a set recreated later
+        let sig_path = signature_path(&set_path);
+        if sig_path.exists() {
+            fs::remove_file(&sig_path).map_err(|e| {
+                CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Failed to delete set signature: {e}"),
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Disables a set by renaming its file, so that reloads skip it and
+    /// remove its entries while the file is kept, or enables it back. A set
+    /// already in the requested state is left as is.
+    pub fn set_disabled(
+        &self,
+        policy: Policy,
+        name: &SetName,
+        disabled: bool,
+    ) -> Result<(), CompositeError> {
+        let set_path = self.get_set_path(policy, name)?;
+        let disabled_path = self.get_disabled_set_path(policy, name)?;
+        let _guard = self.lock_set_writes()?;
+
+        let (from, to) = if disabled {
+            (&set_path, &disabled_path)
+        } else {
+            (&disabled_path, &set_path)
+        };
+        match (from.exists(), to.exists()) {
+            (false, true) => return Ok(()),
+            (false, false) => {
+                return Err(CompositeError::new(
+                    ErrorCode::Enotfound,
+                    &format!("Set '{name}' not found for policy '{policy}'"),
+                ));
+            }
+            (true, true) => {
+                return Err(CompositeError::new(
+                    ErrorCode::Econflict,
+                    &format!(
+                        "Set '{name}' of policy '{policy}' has both an enabled and a disabled file"
+                    ),
+                ));
+            }
+            (true, false) => {}
+        }
+        // The signature keeps the name of the enabled file, so it applies
+        // again once the set is enabled
+        fs::rename(from, to).map_err(|e| {
+            CompositeError::new(
+                ErrorCode::Einternal,
+                &format!("Failed to rename set file: {e}"),
+            )
+        })
+    }
+}
+
+/// Name of the set of a file, and whether the set is disabled, or `None` for
+/// other files
+fn set_file_name(path: &Path) -> Option<(&str, bool)> {
+    let file_name = path.file_name()?.to_str()?;
+    match file_name.strip_suffix(DISABLED_SET_EXTENSION) {
+        Some(enabled) => Some((enabled.strip_suffix(SET_EXTENSION)?, true)),
+        None => Some((file_name.strip_suffix(SET_EXTENSION)?, false)),
+    }
+}
+
+/// Number of set files in a directory, disabled ones included
+fn set_file_count(dir: &Path) -> Result<usize, CompositeError> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        CompositeError::new(
+            ErrorCode::Einternal,
+            &format!("Failed to read directory {}: {e}", dir.display()),
+        )
+    })?;
+
+    let mut count = 0_usize;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| {
+                CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Failed to access directory entry: {e}"),
+                )
+            })?
+            .path();
+        if path.is_file() && set_file_name(&path).is_some() {
+            count = count.saturating_add(1);
+        }
+    }
+    Ok(count)
+}
+
+/// Strong ETag of a content, e.g. a set file, a quoted FNV-1a hash. Stable
+/// across restarts, unlike the std hasher.
+pub(crate) fn content_etag(content: &str) -> String {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = content.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("\"{hash:016x}\"")
+}
+
+/// Whether an `If-Match` value, a list of ETags or `*`, matches `etag`
+fn etag_matches(if_match: &str, etag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
+
+/// Reads the modification times of the set files of a directory
+fn set_mtimes(dir: &Path) -> Result<SetMtimes, CompositeError> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        CompositeError::new(
+            ErrorCode::Einternal,
+            &format!("Failed to read directory {}: {e}", dir.display()),
+        )
+    })?;
+
+    let mut mtimes = HashMap::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| {
+                CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Failed to access directory entry: {e}"),
+                )
+            })?
+            .path();
+        if !path.is_file()
+            || !path
+                .extension()
+                .is_some_and(|ext| ext == SET_EXTENSION.trim_start_matches('.'))
+        {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| {
+                CompositeError::new(
+                    ErrorCode::Einternal,
+                    &format!("Failed to get metadata for file {}: {e}", path.display()),
+                )
+            })?;
+        mtimes.insert(name.to_string(), modified);
+    }
+    Ok(mtimes)
+}
+
+/// Reads the public key set files are verified against
+fn load_set_verifier(path: &str) -> Result<SetVerifier, CompositeError> {
+    let pem = fs::read_to_string(path).map_err(|e| {
+        CompositeError::new(
+            ErrorCode::Einternal,
+            &format!("Failed to read sets public key {path}: {e}"),
+        )
+    })?;
+    SetVerifier::from_pem(&pem).map_err(|e| {
+        CompositeError::new(
+            ErrorCode::Einvalid,
+            &format!("Invalid sets public key {path}: {e}"),
+        )
+    })
+}
+
+/// Reads a set file, whose size was checked when listing the directory
+fn read_set_file(path: &Path) -> Result<String, CompositeError> {
+    fs::read_to_string(path).map_err(|e| {
+        CompositeError::new(
+            ErrorCode::Einvalid,
+            &format!("Failed to read file {}: {e}", path.display()),
+        )
+    })
+}
+
+/// Names of the sets added, modified or removed between two snapshots
+fn changed_set_names(loaded: &SetMtimes, on_disk: &SetMtimes) -> Vec<String> {
+    let mut changed: Vec<String> = on_disk
+        .iter()
+        .filter(|(name, mtime)| loaded.get(*name) != Some(*mtime))
+        .map(|(name, _)| name.clone())
+        .chain(
+            loaded
+                .keys()
+                .filter(|name| !on_disk.contains_key(*name))
+                .cloned(),
+        )
+        .collect();
+    changed.sort_unstable();
+    changed
+}
+
+/// Changes replacing the manual entries of a store
+#[derive(Debug, Default)]
+struct ReplacePlan {
+    removals: Vec<NormalizedCidr>,
+    /// Entries to create, or to update as their tag or expiration changed
+    upserts: Vec<(Entry, Option<Metadata>)>,
+    unchanged: usize,
+    skipped: usize,
+    /// Number of `upserts` creating an entry
+    created: usize,
+    /// Number of stored entries once the plan is applied
+    projected: usize,
+}
+
+/// Unix timestamp of `monotonic_ns`, a `CLOCK_MONOTONIC` time as given by the
+/// XDP program
+fn monotonic_to_unix(monotonic_ns: u64) -> Option<u64> {
+    let now = clock_gettime(ClockId::CLOCK_MONOTONIC).ok()?;
+    let now_ns = u64::try_from(Duration::from(now).as_nanos()).ok()?;
+    let unix = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(shift_to_unix(monotonic_ns, now_ns, unix.as_secs()))
+}
+
+/// Unix timestamp of `then_ns`, given the monotonic time `now_ns` at the
+/// Unix timestamp `now_secs`
+const fn shift_to_unix(then_ns: u64, now_ns: u64, now_secs: u64) -> u64 {
+    now_secs.saturating_sub(now_ns.saturating_sub(then_ns) / 1_000_000_000)
+}
+
+/// Diff between the `manual` entries of a store, given as their tag and
+/// expiration, and `target`. CIDRs in `sets` are neither removed nor
+/// replaced; `stored` counts all entries of the store.
+fn plan_replace(
+    manual: &HashMap<NormalizedCidr, (String, u64)>,
+    sets: &HashMap<NormalizedCidr, StoredEntry>,
+    stored: usize,
+    target: Vec<(Entry, Option<Metadata>)>,
+) -> ReplacePlan {
+    let mut plan = ReplacePlan::default();
+    let mut kept = HashSet::with_capacity(target.len());
+
+    for (entry, metadata) in target {
+        if sets.contains_key(&entry.cidr) {
+            plan.skipped += 1;
+            continue;
+        }
+        kept.insert(entry.cidr);
+        match manual.get(&entry.cidr) {
+            Some((tag, expiration))
+                if tag.as_str() == entry.tag.as_deref().unwrap_or("")
+                    && *expiration == entry.expiration.as_timestamp() =>
+            {
+                plan.unchanged += 1;
+            }
+            Some(_) => plan.upserts.push((entry, metadata)),
+            None => {
+                plan.created += 1;
+                plan.upserts.push((entry, metadata));
+            }
+        }
+    }
+    plan.removals = manual
+        .keys()
+        .filter(|cidr| !kept.contains(*cidr))
+        .copied()
+        .collect();
+    plan.projected = (stored + plan.created).saturating_sub(plan.removals.len());
+    plan
+}
+
+/// Checks that `target_set` fits in a store holding `stored` once it
+/// replaces `stored_sets`, returning the number of entries it creates
+fn check_set_capacity(
+    policy: Policy,
+    stored: &HashMap<NormalizedCidr, StoredEntry>,
+    stored_sets: &HashMap<NormalizedCidr, StoredEntry>,
+    target_set: &HashMap<NormalizedCidr, Entry>,
+    max_entries: usize,
+) -> Result<usize, CompositeError> {
+    let removed = stored_sets
+        .keys()
+        .filter(|cidr| !target_set.contains_key(cidr))
+        .count();
+    let created: Vec<&Entry> = target_set
+        .iter()
+        .filter(|(cidr, _)| !stored.contains_key(cidr))
+        .map(|(_, entry)| entry)
+        .collect();
+
+    let projected = stored.len().saturating_sub(removed) + created.len();
+    if projected <= max_entries {
+        return Ok(created.len());
+    }
+    let overflow = projected - max_entries;
+
+    let mut sets: Vec<&str> = created
+        .iter()
+        .filter_map(|entry| entry.tag.as_deref())
+        .collect();
+    sets.sort_unstable();
+    sets.dedup();
+
+    let mut ce = CompositeError::new(
+        ErrorCode::Efull,
+        &format!(
+            "{policy} sets exceed couic underlying ebpf map by {overflow} entries: max {max_entries} entries"
+        ),
+    );
+    for set in sets {
+        ce.add_detail(
+            set,
+            ErrorCode::Efull,
+            &format!("set `{set}` does not fit, existing entries left untouched"),
+        );
+    }
+    Err(ce)
+}
+
+/// Refuses entries whose tag matches none of the allowed patterns, when
+/// there are any. Entries of sets are exempt.
+fn check_allowed_tags<'a>(
+    allowed_patterns: &[String],
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Result<(), CompositeError> {
+    if allowed_patterns.is_empty() {
+        return Ok(());
+    }
+    let mut ce = CompositeError::new(
+        ErrorCode::Einvalid,
+        &format!(
+            "entry tags must match one of the allowed patterns: {}",
+            allowed_patterns.join(", ")
+        ),
+    );
+    for entry in entries {
+        if entry.in_set() {
+            continue;
+        }
+        let message = match entry.tag.as_deref() {
+            Some(tag) if allowed_patterns.iter().any(|p| matches_pattern(tag, p)) => continue,
+            Some(tag) => format!("tag `{tag}` of {} is not allowed", entry.cidr),
+            None => format!("{} has no tag, one is required", entry.cidr),
+        };
+        ce.add_detail(&entry.cidr.to_string(), ErrorCode::Einvalid, &message);
+    }
+
+    if ce.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ce)
+    }
+}
+
+/// How a sets reload treats drop sets the guards refuse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DropGuards {
+    /// Refuse the whole reload
+    Refuse,
+    /// Skip the refused sets with a warning, so sets written before the
+    /// guards existed never keep the daemon from starting
+    SkipSet,
+    Force,
+}
 
+/// Refuses, during lockdown, to remove ignore entries covering a range of
+/// `firewall.management_cidrs`, which would cut it off
+fn check_management_kept<'a>(
+    ranges: &[IpNet],
+    removals: impl IntoIterator<Item = &'a NormalizedCidr>,
+) -> Result<(), CompositeError> {
+    let mut ce = CompositeError::new(
+        ErrorCode::Econflict,
+        "ignore entries covering management ranges are kept during lockdown, disable it first or force the removal",
+    );
+    for cidr in removals {
+        if let Some(range) = ranges
+            .iter()
+            .map(|range| NormalizedCidr::new(*range))
+            .find(|range| cidr.supernet_of(range))
+        {
+            ce.add_detail(
+                &cidr.to_string(),
+                ErrorCode::Econflict,
+                &format!("{cidr} keeps management range {range} reachable during lockdown"),
+            );
+        }
+    }
+    if ce.errors.is_empty() {
         Ok(())
+    } else {
+        Err(ce)
+    }
+}
+
+/// Refuses drop entries overlapping a protected range, either containing it
+/// or contained in it
+fn check_protected_cidrs<'a>(
+    protected: &[IpNet],
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Result<(), CompositeError> {
+    let mut ce = CompositeError::new(
+        ErrorCode::Econflict,
+        "drop entries overlap protected ranges, force the addition to override",
+    );
+    for entry in entries {
+        let cidr = entry.cidr;
+        let Some(range) = protected
+            .iter()
+            .find(|range| NormalizedCidr::new(**range).overlaps(&cidr))
+        else {
+            continue;
+        };
+        let message = entry.tag.as_deref().map_or_else(
+            || format!("{cidr} overlaps protected range {range}"),
+            |tag| format!("{cidr} from `{tag}` overlaps protected range {range}"),
+        );
+        ce.add_detail(&cidr.to_string(), ErrorCode::Econflict, &message);
+    }
+
+    if ce.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ce)
+    }
+}
+
+/// Refuses drop entries broader than the minimum prefix length of their
+/// family
+fn check_min_prefix<'a>(
+    min_prefix: MinPrefix,
+    entries: impl IntoIterator<Item = &'a Entry>,
+) -> Result<(), CompositeError> {
+    let mut ce = CompositeError::new(
+        ErrorCode::Einvalid,
+        "drop entries are broader than the minimum prefix length, force the addition to override",
+    );
+    for entry in entries {
+        let cidr = entry.cidr;
+        let min = if cidr.is_v4() {
+            min_prefix.v4
+        } else {
+            min_prefix.v6
+        };
+        if cidr.prefix_len() >= min {
+            continue;
+        }
+        let message = entry.tag.as_deref().map_or_else(
+            || format!("{cidr} is broader than /{min}"),
+            |tag| format!("{cidr} from `{tag}` is broader than /{min}"),
+        );
+        ce.add_detail(&cidr.to_string(), ErrorCode::Einvalid, &message);
+    }
+
+    if ce.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ce)
+    }
+}
+
+/// Removal report of an expired entry, its tag still being registered
+fn expiry_report(tag_registry: &TagRegistry, expired: ExpiredEntry) -> Result<Report, String> {
+    let tag = tag_registry
+        .get_tag(expired.stored.tag_id)
+        .map_err(|e| e.to_string())?
+        .map(|s| s.to_string());
+    Ok(Report {
+        action: Action::Remove,
+        policy: expired.policy,
+        entry: Entry {
+            creation: expired.stored.creation,
+            cidr: expired.cidr,
+            tag,
+            expiration: Expiration::from_timestamp(expired.stored.expiration),
+            source: None,
+        },
+        metadata: Some(Metadata {
+            kind: EXPIRY_METADATA_KIND.to_string(),
+            detail: EXPIRY_METADATA_DETAIL.to_string(),
+            extra: None,
+        }),
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::firewall::backend::MemoryBackend;
+    use crate::firewall::source::UNKNOWN_SOURCE;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+    use tempfile::TempDir;
+
+    fn stored(tag_id: TagId) -> StoredEntry {
+        StoredEntry {
+            creation: 0,
+            tag_id,
+            expiration: 0,
+            source: UNKNOWN_SOURCE,
+            propagate: false,
+        }
+    }
+
+    fn set_entry(cidr: &str, set: &str) -> (NormalizedCidr, Entry) {
+        let cidr: NormalizedCidr = cidr.parse().unwrap();
+        let entry = Entry {
+            creation: 0,
+            cidr,
+            tag: Some(set.to_string()),
+            expiration: Expiration::never(),
+            source: Some(EntrySource::set()),
+        };
+        (cidr, entry)
+    }
+
+    /// Working directory holding the sets directories of every policy
+    fn working_dir() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for policy in ["ignore", "drop", "monitor"] {
+            fs::create_dir_all(dir.path().join("sets").join(policy)).unwrap();
+        }
+        dir
+    }
+
+    /// Service reading its sets from `dir`, on an in-memory backend whose
+    /// maps hold `max_entries` entries each
+    fn service_with(
+        dir: &TempDir,
+        max_entries: usize,
+        configure: impl FnOnce(&mut Config),
+    ) -> Result<FirewallService, FirewallServiceError> {
+        let user = std::env::var("USER").unwrap_or_else(|_| "root".to_string());
+        let mut config = Config {
+            working_dir: dir.path().to_string_lossy().to_string(),
+            group: user.clone(),
+            user,
+            ..Default::default()
+        };
+        configure(&mut config);
+        FirewallService::with_backend(config, Box::new(MemoryBackend::new(max_entries)))
+    }
+
+    /// Service reading no sets, on `backend`
+    fn service_on(dir: &TempDir, backend: MemoryBackend) -> FirewallService {
+        let user = std::env::var("USER").unwrap_or_else(|_| "root".to_string());
+        let config = Config {
+            working_dir: dir.path().to_string_lossy().to_string(),
+            group: user.clone(),
+            user,
+            ..Default::default()
+        };
+        FirewallService::with_backend(config, Box::new(backend)).unwrap()
+    }
+
+    fn write_set(dir: &TempDir, policy: Policy, name: &str, content: &str) {
+        let path = dir
+            .path()
+            .join("sets")
+            .join(policy.to_string())
+            .join(format!("{name}{SET_EXTENSION}"));
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(SEC_FILE_PERM)).unwrap();
+    }
+
+    fn stored_cidrs(service: &FirewallService, policy: Policy) -> Vec<String> {
+        let mut cidrs: Vec<String> = [true, false]
+            .into_iter()
+            .flat_map(|ipv4| service.get_lpm_store(policy, ipv4).list_stored().unwrap())
+            .map(|(cidr, _)| cidr.to_string())
+            .collect();
+        cidrs.sort();
+        cidrs
+    }
+
+    #[test]
+    fn oversized_reload_leaves_the_store_untouched() {
+        let dir = working_dir();
+        write_set(&dir, Policy::Drop, "feed", "192.0.2.1/32\n192.0.2.2/32\n");
+        let service = service_with(&dir, 2, |_| {}).unwrap();
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["192.0.2.1/32", "192.0.2.2/32"]
+        );
+
+        write_set(
+            &dir,
+            Policy::Drop,
+            "feed",
+            "192.0.2.3/32\n192.0.2.4/32\n192.0.2.5/32\n",
+        );
+        let err = service.reload_sets(false).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Efull);
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["192.0.2.1/32", "192.0.2.2/32"]
+        );
+    }
+
+    #[test]
+    fn tampered_set_is_skipped_keeping_its_loaded_entries() {
+        use super::super::signature::tests::{CONTENT, OPENSSL_SIGNATURE, PUBLIC_KEY};
+
+        let dir = working_dir();
+        let public_key = dir.path().join("sets.pub");
+        fs::write(&public_key, PUBLIC_KEY).unwrap();
+        let set_path = dir.path().join("sets/ignore/feed.couic");
+        write_set(
+            &dir,
+            Policy::Ignore,
+            "feed",
+            std::str::from_utf8(CONTENT).unwrap(),
+        );
+        fs::write(signature_path(&set_path), OPENSSL_SIGNATURE).unwrap();
+        let service = service_with(&dir, 16, |config| {
+            config.sets.public_key = Some(public_key.to_string_lossy().to_string());
+        })
+        .unwrap();
+        assert_eq!(stored_cidrs(&service, Policy::Ignore), vec!["10.0.0.0/8"]);
+
+        write_set(&dir, Policy::Ignore, "feed", "10.0.0.0/8\n192.0.2.0/24\n");
+        let reloads = service.reload_sets(false).unwrap();
+        let ignore = reloads
+            .iter()
+            .find(|reload| reload.policy == Policy::Ignore)
+            .unwrap();
+        let skipped: Vec<&str> = ignore.skipped.iter().map(|set| set.name.as_str()).collect();
+        assert_eq!(skipped, vec!["feed"]);
+        assert_eq!(stored_cidrs(&service, Policy::Ignore), vec!["10.0.0.0/8"]);
+        assert!(service.sets_reload_status().unwrap().reload_required);
+    }
+
+    #[test]
+    fn signed_sets_refuse_unsigned_writes() {
+        use super::super::signature::tests::PUBLIC_KEY;
+
+        let dir = working_dir();
+        let public_key = dir.path().join("sets.pub");
+        fs::write(&public_key, PUBLIC_KEY).unwrap();
+        write_set(&dir, Policy::Drop, "feed", "192.0.2.0/24\n");
+        let service = service_with(&dir, 16, |config| {
+            config.sets.public_key = Some(public_key.to_string_lossy().to_string());
+        })
+        .unwrap();
+        let entries: Vec<IpNet> = vec!["198.51.100.0/24".parse().unwrap()];
+        let header = SetHeader::default();
+
+        let created = SetName::try_from("scanners").unwrap();
+        let err = service
+            .create_set(Policy::Drop, &created, &header, &entries)
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Eprecondition);
+        assert!(!dir.path().join("sets/drop/scanners.couic").exists());
+
+        let feed = SetName::try_from("feed").unwrap();
+        let err = service
+            .update_set(Policy::Drop, &feed, &header, &entries, None)
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Eprecondition);
+        let patch = SetPatch {
+            add: entries,
+            remove: Vec::new(),
+        };
+        let err = service
+            .patch_set(Policy::Drop, &feed, &patch, None)
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::Eprecondition);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("sets/drop/feed.couic")).unwrap(),
+            "192.0.2.0/24\n"
+        );
+    }
+
+    #[test]
+    fn startup_skips_drop_sets_overlapping_protected_ranges() {
+        let dir = working_dir();
+        write_set(&dir, Policy::Drop, "bogons", "127.0.0.0/8\n192.0.2.0/24\n");
+        write_set(&dir, Policy::Drop, "feed", "198.51.100.0/24\n");
+        let service = service_with(&dir, 16, |_| {}).unwrap();
+
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["198.51.100.0/24"]
+        );
+        let status = service.sets_status().unwrap();
+        let drop = status
+            .reloads
+            .iter()
+            .find(|reload| reload.policy == Policy::Drop)
+            .unwrap();
+        assert_eq!(drop.skipped.len(), 1);
+        assert_eq!(drop.skipped[0].name, "bogons");
+        assert_eq!(drop.skipped[0].error.code, ErrorCode::Econflict);
+        assert!(service.sets_reload_status().unwrap().reload_required);
+
+        // Explicit reloads still refuse the set
+        let err = service.reload_sets(false).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Econflict);
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["198.51.100.0/24"]
+        );
+    }
+
+    #[test]
+    fn startup_skips_drop_sets_broader_than_the_minimum_prefix() {
+        let dir = working_dir();
+        write_set(&dir, Policy::Drop, "bogons", "224.0.0.0/4\n240.0.0.0/4\n");
+        write_set(&dir, Policy::Drop, "feed", "198.51.100.0/24\n");
+        let service = service_with(&dir, 16, |_| {}).unwrap();
+
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["198.51.100.0/24"]
+        );
+        let status = service.sets_status().unwrap();
+        let skipped: Vec<(&str, ErrorCode)> = status
+            .reloads
+            .iter()
+            .flat_map(|reload| &reload.skipped)
+            .map(|skipped| (skipped.name.as_str(), skipped.error.code.clone()))
+            .collect();
+        assert_eq!(skipped, vec![("bogons", ErrorCode::Einvalid)]);
+
+        let err = service.reload_sets(false).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einvalid);
+        service.reload_sets(true).unwrap();
+        assert_eq!(stored_cidrs(&service, Policy::Drop).len(), 3);
+    }
+
+    #[test]
+    fn lockdown_keeps_ignore_sets_covering_management_ranges() {
+        let dir = working_dir();
+        write_set(&dir, Policy::Ignore, "admin", "192.0.2.0/24\n");
+        write_set(&dir, Policy::Ignore, "partners", "198.51.100.0/24\n");
+        let service = service_with(&dir, 16, |config| {
+            config.firewall.management_cidrs = vec!["192.0.2.10/32".parse().unwrap()];
+        })
+        .unwrap();
+        service.set_lockdown(true, false).unwrap();
+
+        // Other ignore sets still reload
+        write_set(&dir, Policy::Ignore, "partners", "203.0.113.0/24\n");
+        service.reload_sets(false).unwrap();
+        assert_eq!(
+            stored_cidrs(&service, Policy::Ignore),
+            vec!["192.0.2.0/24", "203.0.113.0/24"]
+        );
+
+        // Emptying or deleting the set covering the management range is
+        // refused, by full and single set reloads
+        let admin: SetName = "admin".parse().unwrap();
+        for content in [Some(""), None] {
+            let path = dir.path().join("sets/ignore/admin.couic");
+            match content {
+                Some(content) => write_set(&dir, Policy::Ignore, "admin", content),
+                None => fs::remove_file(&path).unwrap(),
+            }
+            let err = service.reload_sets(false).unwrap_err();
+            assert_eq!(err.code, ErrorCode::Econflict);
+            let err = service
+                .reload_set(Policy::Ignore, &admin, false)
+                .unwrap_err();
+            assert_eq!(err.code, ErrorCode::Econflict);
+            assert_eq!(
+                stored_cidrs(&service, Policy::Ignore),
+                vec!["192.0.2.0/24", "203.0.113.0/24"]
+            );
+        }
+
+        service.reload_sets(true).unwrap();
+        assert_eq!(
+            stored_cidrs(&service, Policy::Ignore),
+            vec!["203.0.113.0/24"]
+        );
+    }
+
+    #[test]
+    fn set_changes_are_reloaded_skipping_refused_drop_sets() {
+        let dir = working_dir();
+        let service = Arc::new(
+            service_with(&dir, 16, |config| {
+                config.sets.watch.enabled = true;
+                config.sets.watch.debounce_ms = 20;
+            })
+            .unwrap(),
+        );
+        FirewallService::launch_sets_watcher(service.clone()).unwrap();
+
+        // The drop set overlapping loopback is skipped, neither keeping the
+        // other drop set nor the monitor set from loading
+        write_set(&dir, Policy::Drop, "bogus", "127.0.0.1/32\n");
+        write_set(&dir, Policy::Drop, "feed", "198.51.100.0/24\n");
+        write_set(&dir, Policy::Monitor, "scanners", "203.0.113.0/24\n");
+        let start = Instant::now();
+        while stored_cidrs(&service, Policy::Drop).is_empty()
+            || stored_cidrs(&service, Policy::Monitor).is_empty()
+        {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "sets not reloaded after a file change"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["198.51.100.0/24"]
+        );
+        assert_eq!(
+            stored_cidrs(&service, Policy::Monitor),
+            vec!["203.0.113.0/24"]
+        );
+        let status = service.sets_status().unwrap();
+        let skipped: Vec<&str> = status
+            .reloads
+            .iter()
+            .flat_map(|reload| &reload.skipped)
+            .map(|skipped| skipped.name.as_str())
+            .collect();
+        assert_eq!(skipped, vec!["bogus"]);
+    }
+
+    #[test]
+    fn expiry_report_resolves_tag_and_marks_reason() {
+        let registry = TagRegistry::new();
+        let tag_id = registry.acquire("abuse").unwrap();
+        let expired = ExpiredEntry {
+            policy: Policy::Drop,
+            cidr: "192.0.2.0/24".parse().unwrap(),
+            stored: StoredEntry {
+                creation: 10,
+                tag_id,
+                expiration: 20,
+                source: UNKNOWN_SOURCE,
+                propagate: false,
+            },
+        };
+
+        let report = expiry_report(&registry, expired).unwrap();
+        assert_eq!(report.action, Action::Remove);
+        assert_eq!(report.policy, Policy::Drop);
+        assert_eq!(report.entry.cidr, expired.cidr);
+        assert_eq!(report.entry.tag.as_deref(), Some("abuse"));
+        assert_eq!(report.entry.expiration.as_timestamp(), 20);
+        let metadata = report.metadata.unwrap();
+        assert_eq!(metadata.kind, "expiration");
+        assert_eq!(metadata.detail, "expired");
+    }
+
+    #[test]
+    fn only_local_propagated_entries_report_their_expiry() {
+        let dir = working_dir();
+        let service = service_on(&dir, MemoryBackend::new(16));
+        let add = |cidr: &str, source: EntrySource, propagate: bool| {
+            let cidr: NormalizedCidr = cidr.parse().unwrap();
+            let entry = Entry {
+                creation: 0,
+                cidr,
+                tag: None,
+                expiration: Expiration::never(),
+                source: Some(source),
+            };
+            service
+                .add_entry(Policy::Drop, &entry, None, propagate, false)
+                .unwrap();
+            service
+                .get_lpm_store(Policy::Drop, true)
+                .get_stored(cidr)
+                .unwrap()
+                .propagate
+        };
+
+        assert!(add("192.0.2.1/32", EntrySource::manual("admin"), true));
+        // Kept local by the caller
+        assert!(!add("192.0.2.2/32", EntrySource::manual("admin"), false));
+        // Reported by the peer it comes from
+        assert!(!add("192.0.2.3/32", EntrySource::peer("node-b"), true));
+    }
+
+    #[test]
+    fn set_etag_follows_content() {
+        let etag = content_etag("# @description: scanners\n192.0.2.0/24");
+        assert_eq!(etag, content_etag("# @description: scanners\n192.0.2.0/24"));
+        assert_ne!(etag, content_etag("# @description: scanners\n192.0.2.0/25"));
+        assert_eq!(content_etag(""), "\"cbf29ce484222325\"");
+    }
+
+    #[test]
+    fn etag_matches_lists_and_wildcard() {
+        let etag = content_etag("192.0.2.0/24");
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"0\", {etag}"), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"0\"", &etag));
+    }
+
+    #[test]
+    fn changed_set_names_detects_added_modified_and_removed_sets() {
+        let t0 = UNIX_EPOCH + Duration::from_secs(1000);
+        let t1 = t0 + Duration::from_secs(1);
+        let loaded = HashMap::from([
+            ("kept".to_string(), t0),
+            ("modified".to_string(), t0),
+            ("removed".to_string(), t0),
+        ]);
+        let on_disk = HashMap::from([
+            ("kept".to_string(), t0),
+            ("modified".to_string(), t1),
+            ("added".to_string(), t1),
+        ]);
+
+        assert_eq!(
+            changed_set_names(&loaded, &on_disk),
+            vec!["added", "modified", "removed"]
+        );
+        assert!(changed_set_names(&loaded, &loaded).is_empty());
+    }
+
+    #[test]
+    fn set_mtimes_lists_set_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("blocklist.couic"), "192.0.2.0/24\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a set\n").unwrap();
+
+        let mtimes = set_mtimes(dir.path()).unwrap();
+        assert_eq!(mtimes.len(), 1);
+        assert!(mtimes.contains_key("blocklist"));
+    }
+
+    #[test]
+    fn set_file_name_tells_disabled_sets_apart() {
+        for (file, expected) in [
+            ("blocklist.couic", Some(("blocklist", false))),
+            ("blocklist.couic.disabled", Some(("blocklist", true))),
+            ("blocklist.couic.sig", None),
+            ("blocklist.couic.tmp", None),
+            ("notes.disabled", None),
+        ] {
+            assert_eq!(set_file_name(Path::new(file)), expected, "{file}");
+        }
+    }
+
+    #[test]
+    fn load_set_verifier_reports_unusable_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("sets.pub");
+        fs::write(
+            &key,
+            "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEAorpS1fIpwKdveyNGFfV3AX7hfrHIynSbvNFrFbIHG4o=\n-----END PUBLIC KEY-----\n",
+        )
+        .unwrap();
+        assert!(load_set_verifier(key.to_str().unwrap()).is_ok());
+
+        let missing = dir.path().join("missing.pub");
+        let err = load_set_verifier(missing.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einternal);
+
+        fs::write(&key, "not a key").unwrap();
+        let err = load_set_verifier(key.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Einvalid);
+    }
+
+    #[test]
+    fn check_set_capacity_accepts_fitting_reload() {
+        let old: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let stored_sets = HashMap::from([(old, stored(1))]);
+        let stored = stored_sets.clone();
+        // Replaces the only set entry, so the store stays at 1 entry
+        let target = HashMap::from([set_entry("192.0.2.2/32", "a.couic")]);
+
+        let created = check_set_capacity(Policy::Drop, &stored, &stored_sets, &target, 1).unwrap();
+        assert_eq!(created, 1);
+    }
+
+    #[test]
+    fn check_set_capacity_counts_overwritten_manual_entries_once() {
+        let manual: NormalizedCidr = "192.0.2.1/32".parse().unwrap();
+        let stored = HashMap::from([(manual, stored(1))]);
+        let target = HashMap::from([set_entry("192.0.2.1/32", "a.couic")]);
+
+        let created =
+            check_set_capacity(Policy::Drop, &stored, &HashMap::new(), &target, 1).unwrap();
+        assert_eq!(created, 0);
+    }
+
+    #[test]
+    fn check_set_capacity_rejects_oversized_reload() {
+        let manual: NormalizedCidr = "198.51.100.1/32".parse().unwrap();
+        let stored = HashMap::from([(manual, stored(1))]);
+        let target = HashMap::from([
+            set_entry("192.0.2.1/32", "a.couic"),
+            set_entry("192.0.2.2/32", "b.couic"),
+            set_entry("192.0.2.3/32", "b.couic"),
+        ]);
+
+        let err =
+            check_set_capacity(Policy::Drop, &stored, &HashMap::new(), &target, 2).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Efull);
+        assert!(err.message.contains("by 2 entries"));
+        let sets: Vec<&str> = err.errors.keys().map(String::as_str).collect();
+        assert!(sets.contains(&"a.couic"));
+        assert!(sets.contains(&"b.couic"));
+    }
+
+    #[test]
+    fn plan_replace_reconciles_manual_entries_only() {
+        let manual = |cidr: &str, tag: &str| -> (NormalizedCidr, (String, u64)) {
+            (cidr.parse().unwrap(), (tag.to_string(), 0))
+        };
+        let manual = HashMap::from([
+            manual("192.0.2.1/32", "feed"),
+            manual("192.0.2.2/32", "feed"),
+            manual("192.0.2.3/32", "old"),
+        ]);
+        let (set_cidr, _) = set_entry("198.51.100.0/24", "a.couic");
+        let sets = HashMap::from([(set_cidr, stored(1))]);
+        let target = [
+            set_entry("192.0.2.1/32", "feed"),
+            set_entry("192.0.2.3/32", "feed"),
+            set_entry("192.0.2.4/32", "feed"),
+            set_entry("198.51.100.0/24", "feed"),
+        ]
+        .into_iter()
+        .map(|(_, entry)| (entry, None))
+        .collect();
+
+        let plan = plan_replace(&manual, &sets, 4, target);
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.skipped, 1);
+        assert_eq!(plan.removals, vec!["192.0.2.2/32".parse().unwrap()]);
+        let upserts: Vec<String> = plan
+            .upserts
+            .iter()
+            .map(|(entry, _)| entry.cidr.to_string())
+            .collect();
+        assert_eq!(upserts, vec!["192.0.2.3/32", "192.0.2.4/32"]);
+        // One created, one removed
+        assert_eq!(plan.created, 1);
+        assert_eq!(plan.projected, 4);
+    }
+
+    fn manual_entries(cidrs: &[&str]) -> Vec<(Entry, Option<Metadata>)> {
+        cidrs
+            .iter()
+            .map(|cidr| {
+                let entry = Entry {
+                    source: None,
+                    ..set_entry(cidr, "feed").1
+                };
+                (entry, None)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replace_policy_reports_failed_changes_and_applies_the_others() {
+        let dir = working_dir();
+        let backend = MemoryBackend::new(8);
+        backend.fail_insert("192.0.2.2/32".parse().unwrap());
+        let service = service_on(&dir, backend);
+        let entries = manual_entries(&["192.0.2.9/32"]);
+        service
+            .replace_policy(Policy::Drop, entries, false, false, None)
+            .unwrap();
+
+        let entries = manual_entries(&["192.0.2.1/32", "192.0.2.2/32", "192.0.2.3/32"]);
+        let summary = service
+            .replace_policy(Policy::Drop, entries, false, false, None)
+            .unwrap();
+        assert_eq!(summary.created, 2);
+        assert_eq!(summary.removed, 1);
+        let failed: Vec<&str> = summary.failed.keys().map(String::as_str).collect();
+        assert_eq!(failed, vec!["192.0.2.2/32"]);
+        assert_eq!(summary.failed["192.0.2.2/32"].code, ErrorCode::Einternal);
+        assert_eq!(
+            stored_cidrs(&service, Policy::Drop),
+            vec!["192.0.2.1/32", "192.0.2.3/32"]
+        );
+    }
+
+    #[test]
+    fn replace_policy_is_never_seen_half_applied() {
+        let dir = working_dir();
+        let service = service_on(&dir, MemoryBackend::new(8192));
+        // More entries than a chunk, so the eBPF map is written in several
+        let cidrs = |net: u8| -> Vec<String> {
+            let mut cidrs: Vec<String> = (0..3000u16)
+                .map(|i| format!("10.{net}.{}.{}/32", i / 256, i % 256))
+                .collect();
+            cidrs.sort();
+            cidrs
+        };
+        let entries = |cidrs: &[String]| {
+            manual_entries(&cidrs.iter().map(String::as_str).collect::<Vec<_>>())
+        };
+        let (old, new) = (cidrs(1), cidrs(2));
+        service
+            .replace_policy(Policy::Drop, entries(&old), false, false, None)
+            .unwrap();
+
+        let done = AtomicBool::new(false);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                loop {
+                    let finished = done.load(Ordering::Relaxed);
+                    let stored = stored_cidrs(&service, Policy::Drop);
+                    assert!(stored == old || stored == new, "replace seen half applied");
+                    if finished {
+                        break;
+                    }
+                }
+            });
+            service
+                .replace_policy(Policy::Drop, entries(&new), false, false, None)
+                .unwrap();
+            done.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(stored_cidrs(&service, Policy::Drop), new);
+    }
+
+    #[test]
+    fn replace_policy_over_the_creations_left_changes_nothing() {
+        let dir = working_dir();
+        let service = service_with(&dir, 8, |_| {}).unwrap();
+        let entries = manual_entries(&["192.0.2.1/32"]);
+        service
+            .replace_policy(Policy::Drop, entries, false, false, None)
+            .unwrap();
+
+        // Keeping 192.0.2.1 creates two entries, one more than allowed
+        let target = ["192.0.2.1/32", "192.0.2.2/32", "2001:db8::1/128"];
+        let ce = service
+            .replace_policy(Policy::Drop, manual_entries(&target), false, false, Some(1))
+            .unwrap_err();
+        assert_eq!(ce.code, ErrorCode::Equota);
+        assert_eq!(stored_cidrs(&service, Policy::Drop), vec!["192.0.2.1/32"]);
+
+        let summary = service
+            .replace_policy(Policy::Drop, manual_entries(&target), false, false, Some(2))
+            .unwrap();
+        assert_eq!(summary.created, 2);
+        assert_eq!(summary.unchanged, 1);
+    }
+
+    #[test]
+    fn monotonic_times_are_shifted_to_unix() {
+        let now_secs = 1_700_000_000;
+        let now_ns = 5_000 * 1_000_000_000;
+        assert_eq!(shift_to_unix(now_ns, now_ns, now_secs), now_secs);
+        assert_eq!(
+            shift_to_unix(now_ns - 90 * 1_000_000_000, now_ns, now_secs),
+            now_secs - 90
+        );
+        // A hit recorded after the monotonic clock was read
+        assert_eq!(shift_to_unix(now_ns + 1, now_ns, now_secs), now_secs);
+    }
+
+    #[test]
+    fn plan_replace_with_no_entries_removes_all_manual_entries() {
+        let cidr: NormalizedCidr = "2001:db8::/32".parse().unwrap();
+        let manual = HashMap::from([(cidr, (String::new(), 0))]);
+        let plan = plan_replace(&manual, &HashMap::new(), 1, Vec::new());
+        assert_eq!(plan.removals, vec![cidr]);
+        assert_eq!(plan.projected, 0);
+    }
+
+    fn protected() -> Vec<IpNet> {
+        vec!["127.0.0.0/8".parse().unwrap(), "fe80::/10".parse().unwrap()]
+    }
+
+    #[test]
+    fn check_protected_cidrs_accepts_disjoint_entries() {
+        let (_, v4) = set_entry("192.0.2.0/24", "a.couic");
+        let (_, v6) = set_entry("2001:db8::/32", "a.couic");
+        check_protected_cidrs(&protected(), [&v4, &v6]).unwrap();
+    }
+
+    #[test]
+    fn check_protected_cidrs_rejects_contained_and_containing_entries() {
+        let (_, inside) = set_entry("127.0.0.1/32", "a.couic");
+        let (_, all) = set_entry("0.0.0.0/0", "b.couic");
+        let (_, v6) = set_entry("fe80::1/128", "c.couic");
+        let (_, fine) = set_entry("192.0.2.0/24", "d.couic");
+
+        let ce = check_protected_cidrs(&protected(), [&inside, &all, &v6, &fine]).unwrap_err();
+        assert_eq!(ce.code, ErrorCode::Econflict);
+        assert_eq!(ce.errors.len(), 3);
+        assert!(ce.errors["0.0.0.0/0"].message.contains("127.0.0.0/8"));
+        assert!(ce.errors["fe80::1/128"].message.contains("c.couic"));
+        assert!(!ce.errors.contains_key("192.0.2.0/24"));
+    }
+
+    #[test]
+    fn check_min_prefix_rejects_broad_entries_per_family() {
+        let min_prefix = MinPrefix { v4: 8, v6: 32 };
+        let (_, v4_edge) = set_entry("10.0.0.0/8", "a.couic");
+        let (_, v4_broad) = set_entry("16.0.0.0/4", "b.couic");
+        let (_, v6_edge) = set_entry("2001:db8::/32", "c.couic");
+        let (_, v6_broad) = set_entry("2000::/3", "d.couic");
+        check_min_prefix(min_prefix, [&v4_edge, &v6_edge]).unwrap();
+
+        let ce = check_min_prefix(min_prefix, [&v4_broad, &v6_broad, &v4_edge]).unwrap_err();
+        assert_eq!(ce.code, ErrorCode::Einvalid);
+        assert_eq!(ce.errors.len(), 2);
+        assert!(ce.errors["16.0.0.0/4"].message.contains("/8"));
+        assert!(ce.errors["2000::/3"].message.contains("d.couic"));
+        check_min_prefix(MinPrefix { v4: 0, v6: 0 }, [&v4_broad, &v6_broad]).unwrap();
+    }
+
+    #[test]
+    fn check_allowed_tags_rejects_unknown_and_missing_tags() {
+        let allowed = vec!["team/*".to_string(), "fail2ban".to_string()];
+        let (_, team) = set_entry("192.0.2.1/32", "team/web/scanner");
+        let (_, fail2ban) = set_entry("192.0.2.2/32", "fail2ban");
+        let (_, set) = set_entry("192.0.2.3/32", "feed.couic");
+        let (_, other) = set_entry("192.0.2.4/32", "teams");
+        let untagged = Entry {
+            tag: None,
+            ..set_entry("192.0.2.5/32", "").1
+        };
+        check_allowed_tags(&allowed, [&team, &fail2ban, &set]).unwrap();
+
+        let ce = check_allowed_tags(&allowed, [&team, &other, &untagged]).unwrap_err();
+        assert_eq!(ce.code, ErrorCode::Einvalid);
+        assert_eq!(ce.errors.len(), 2);
+        assert!(ce.errors["192.0.2.4/32"].message.contains("teams"));
+        assert!(ce.errors["192.0.2.5/32"].message.contains("no tag"));
+        check_allowed_tags(&[], [&other, &untagged]).unwrap();
     }
 }
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use common::EntrySource;
+
+/// ID of an interned source, 0 standing for an unknown source
+pub type SourceId = u32;
+
+pub const UNKNOWN_SOURCE: SourceId = 0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SourceRegistryError {
+    #[error("Source registry lock poisoned")]
+    LockPoisoned,
+    #[error("Source table is full")]
+    IdExhausted,
+}
+
+#[derive(Debug, Default)]
+struct SourceRegistryInner {
+    /// Source of ID `n` at index `n - 1`
+    by_id: Vec<EntrySource>,
+    by_source: HashMap<EntrySource, SourceId>,
+}
+
+/// Sources of the entries, interned so that stored entries stay `Copy`.
+/// There are only a few of them, a kind per client, so they are never
+/// released.
+#[derive(Debug, Clone, Default)]
+pub struct SourceRegistry {
+    inner: Arc<RwLock<SourceRegistryInner>>,
+}
+
+impl SourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn acquire(&self, source: Option<&EntrySource>) -> Result<SourceId, SourceRegistryError> {
+        let Some(source) = source else {
+            return Ok(UNKNOWN_SOURCE);
+        };
+        if let Some(&id) = self
+            .inner
+            .read()
+            .map_err(|_| SourceRegistryError::LockPoisoned)?
+            .by_source
+            .get(source)
+        {
+            return Ok(id);
+        }
+
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|_| SourceRegistryError::LockPoisoned)?;
+        // Interned concurrently since the read lock was released
+        if let Some(&id) = inner.by_source.get(source) {
+            return Ok(id);
+        }
+        let id = inner
+            .by_id
+            .len()
+            .checked_add(1)
+            .and_then(|id| SourceId::try_from(id).ok())
+            .ok_or(SourceRegistryError::IdExhausted)?;
+        inner.by_id.push(source.clone());
+        inner.by_source.insert(source.clone(), id);
+        Ok(id)
+    }
+
+    pub fn get_source(&self, id: SourceId) -> Result<Option<EntrySource>, SourceRegistryError> {
+        let Some(index) = id.checked_sub(1) else {
+            return Ok(None);
+        };
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| SourceRegistryError::LockPoisoned)?;
+        Ok(usize::try_from(index)
+            .ok()
+            .and_then(|index| inner.by_id.get(index))
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acquire_interns_sources() {
+        let registry = SourceRegistry::new();
+        let alice = registry
+            .acquire(Some(&EntrySource::manual("alice")))
+            .unwrap();
+        let peer = registry.acquire(Some(&EntrySource::peer("alice"))).unwrap();
+        assert_ne!(alice, UNKNOWN_SOURCE);
+        assert_ne!(alice, peer);
+        assert_eq!(
+            registry
+                .acquire(Some(&EntrySource::manual("alice")))
+                .unwrap(),
+            alice
+        );
+        assert_eq!(
+            registry.get_source(peer).unwrap(),
+            Some(EntrySource::peer("alice"))
+        );
+    }
+
+    #[test]
+    fn unknown_source() {
+        let registry = SourceRegistry::new();
+        assert_eq!(registry.acquire(None).unwrap(), UNKNOWN_SOURCE);
+        assert_eq!(registry.get_source(UNKNOWN_SOURCE).unwrap(), None);
+        assert_eq!(registry.get_source(42).unwrap(), None);
+    }
+}
@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 use common::RESERVED_TAG_NAME;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
 
-// IDs are monotonic and never reused
+// IDs are monotonic and never reused by another tag, even across restarts
+// when the registry is persisted
 pub type TagId = u64;
 
 #[derive(Debug, thiserror::Error)]
@@ -14,6 +17,16 @@ pub enum TagRegistryError {
     InvalidId,
     #[error("Tag table is full")]
     IdExhausted,
+    #[error("Invalid persisted tag state: {0}")]
+    InvalidState(String),
+}
+
+/// Tag IDs saved across restarts so a tag keeps its ID, and so its per-tag
+/// stats, once it is acquired again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistedTags {
+    pub next_id: TagId,
+    pub tags: HashMap<String, TagId>,
 }
 
 #[derive(Debug)]
@@ -27,6 +40,10 @@ struct TagRegistryInner {
     next_id: TagId,
     by_id: HashMap<TagId, TagEntry>,
     by_name: HashMap<Arc<str>, TagId>,
+    /// IDs restored from a previous run, handed back when their tag is acquired
+    reserved: HashMap<Arc<str>, TagId>,
+    /// Bumped whenever a tag is created or removed
+    generation: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -47,10 +64,79 @@ impl TagRegistry {
                 next_id: 1,
                 by_id: HashMap::new(),
                 by_name: HashMap::new(),
+                reserved: HashMap::new(),
+                generation: 0,
             })),
         }
     }
 
+    /// Registry reusing the IDs of a previous run. Restored tags have no
+    /// reference until acquired again, and new tags get IDs above all of them.
+    pub fn restore(state: PersistedTags) -> Result<Self, TagRegistryError> {
+        let mut reserved: HashMap<Arc<str>, TagId> = HashMap::with_capacity(state.tags.len());
+        let mut ids = HashSet::with_capacity(state.tags.len());
+        for (name, id) in state.tags {
+            if id == 0 || id >= state.next_id {
+                return Err(TagRegistryError::InvalidState(format!(
+                    "tag '{name}' has ID {id} outside of 1..{}",
+                    state.next_id
+                )));
+            }
+            if !ids.insert(id) {
+                return Err(TagRegistryError::InvalidState(format!(
+                    "ID {id} is used by several tags"
+                )));
+            }
+            reserved.insert(name.into(), id);
+        }
+
+        Ok(Self {
+            inner: Arc::new(RwLock::new(TagRegistryInner {
+                next_id: state.next_id.max(1),
+                by_id: HashMap::new(),
+                by_name: HashMap::new(),
+                reserved,
+                generation: 0,
+            })),
+        })
+    }
+
+    /// Counter changing whenever the state to persist changes
+    pub fn generation(&self) -> Result<u64, TagRegistryError> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| TagRegistryError::LockPoisoned)?;
+        Ok(inner.generation)
+    }
+
+    /// State to persist along with its generation: the live tags. Restored
+    /// IDs not acquired again by then have no entry left, so they are
+    /// released rather than saved forever, and the file stays bounded by
+    /// the live tags.
+    pub fn persisted(&self) -> Result<(u64, PersistedTags), TagRegistryError> {
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|_| TagRegistryError::LockPoisoned)?;
+        if !inner.reserved.is_empty() {
+            debug!("Releasing {} unclaimed tag IDs", inner.reserved.len());
+            inner.reserved.clear();
+        }
+        let tags = inner
+            .by_name
+            .iter()
+            .map(|(name, &id)| (name.to_string(), id))
+            .collect();
+        Ok((
+            inner.generation,
+            PersistedTags {
+                next_id: inner.next_id,
+                tags,
+            },
+        ))
+    }
+
     pub fn acquire(&self, tag: &str) -> Result<TagId, TagRegistryError> {
         let mut inner = self
             .inner
@@ -65,15 +151,21 @@ impl TagRegistry {
             return Ok(id);
         }
 
-        let id = inner.next_id;
-        if id == u64::MAX {
-            return Err(TagRegistryError::IdExhausted);
-        }
-        inner.next_id += 1;
+        let id = if let Some(id) = inner.reserved.remove(tag) {
+            id
+        } else {
+            let id = inner.next_id;
+            if id == u64::MAX {
+                return Err(TagRegistryError::IdExhausted);
+            }
+            inner.next_id += 1;
+            id
+        };
 
         let name: Arc<str> = tag.into();
         inner.by_name.insert(Arc::clone(&name), id);
         inner.by_id.insert(id, TagEntry { name, refcount: 1 });
+        inner.generation += 1;
 
         Ok(id)
     }
@@ -95,6 +187,7 @@ impl TagRegistry {
 
         if should_remove && let Some(entry) = inner.by_id.remove(&id) {
             inner.by_name.remove(&entry.name);
+            inner.generation += 1;
         }
 
         Ok(())
@@ -108,18 +201,37 @@ impl TagRegistry {
         Ok(inner.by_id.get(&id).map(|e| Arc::clone(&e.name)))
     }
 
-    pub fn get_tag_display(&self, id: TagId) -> Result<Option<String>, TagRegistryError> {
+    /// Keys each value by the display name of its tag under a single read
+    /// lock, so a stats scrape does not take the lock once per tag. Entries
+    /// with an unknown ID are dropped.
+    pub fn resolve_display<T>(
+        &self,
+        entries: impl IntoIterator<Item = (TagId, T)>,
+    ) -> Result<HashMap<String, T>, TagRegistryError> {
         let inner = self
             .inner
             .read()
             .map_err(|_| TagRegistryError::LockPoisoned)?;
-        Ok(inner.by_id.get(&id).map(|e| {
-            if e.name.is_empty() {
-                RESERVED_TAG_NAME.into()
-            } else {
-                e.name.as_ref().into()
-            }
-        }))
+        Ok(entries
+            .into_iter()
+            .filter_map(|(id, value)| {
+                let Some(e) = inner.by_id.get(&id) else {
+                    debug!("Tag ID {id} not found in registry");
+                    return None;
+                };
+                Some((display_name(&e.name), value))
+            })
+            .collect())
+    }
+}
+
+/// Name under which a tag is reported, the empty tag being shown as the
+/// reserved tag name.
+fn display_name(name: &str) -> String {
+    if name.is_empty() {
+        RESERVED_TAG_NAME.into()
+    } else {
+        name.into()
     }
 }
 
@@ -210,6 +322,94 @@ mod tests {
         assert_eq!(registry.get_tag(id).unwrap().unwrap().as_ref(), "");
     }
 
+    #[test]
+    fn resolve_display_keys_by_tag_name() {
+        let registry = TagRegistry::new();
+        let foo = registry.acquire("foo").unwrap();
+        let bar = registry.acquire("bar").unwrap();
+        let empty = registry.acquire("").unwrap();
+        registry.release(bar).unwrap();
+
+        let resolved = registry
+            .resolve_display([(foo, 1), (bar, 2), (empty, 3), (999, 4)])
+            .unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved["foo"], 1);
+        assert_eq!(resolved[RESERVED_TAG_NAME], 3);
+    }
+
+    fn persisted_state(tags: &[(&str, TagId)], next_id: TagId) -> PersistedTags {
+        PersistedTags {
+            next_id,
+            tags: tags
+                .iter()
+                .map(|&(name, id)| (name.to_string(), id))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn restore_hands_back_persisted_ids() {
+        let registry = TagRegistry::restore(persisted_state(&[("foo", 3), ("bar", 7)], 8)).unwrap();
+        // Restored tags are not live until acquired
+        assert!(registry.get_tag(3).unwrap().is_none());
+
+        assert_eq!(registry.acquire("bar").unwrap(), 7);
+        assert_eq!(registry.acquire("foo").unwrap(), 3);
+        assert_eq!(registry.acquire("baz").unwrap(), 8);
+        assert_eq!(registry.get_tag(7).unwrap().unwrap().as_ref(), "bar");
+    }
+
+    #[test]
+    fn restore_rejects_inconsistent_state() {
+        assert!(TagRegistry::restore(persisted_state(&[("foo", 0)], 2)).is_err());
+        assert!(TagRegistry::restore(persisted_state(&[("foo", 2)], 2)).is_err());
+        assert!(TagRegistry::restore(persisted_state(&[("foo", 1), ("bar", 1)], 2)).is_err());
+    }
+
+    #[test]
+    fn persisted_releases_unclaimed_ids_and_released_tags() {
+        let registry = TagRegistry::restore(persisted_state(&[("foo", 3), ("bar", 7)], 8)).unwrap();
+        let foo = registry.acquire("foo").unwrap();
+        let baz = registry.acquire("baz").unwrap();
+        registry.release(foo).unwrap();
+
+        let (_, state) = registry.persisted().unwrap();
+        assert_eq!(state, persisted_state(&[("baz", baz)], 9));
+        // IDs are still never reused by another tag
+        assert_eq!(registry.acquire("bar").unwrap(), 9);
+    }
+
+    #[test]
+    fn persisted_survives_roundtrip() {
+        let registry = TagRegistry::new();
+        registry.acquire("foo").unwrap();
+        registry.acquire("bar").unwrap();
+
+        let (_, state) = registry.persisted().unwrap();
+        let json = serde_json::to_string(&state).unwrap();
+        let restored = TagRegistry::restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restored.acquire("bar").unwrap(), 2);
+        assert_eq!(restored.acquire("foo").unwrap(), 1);
+        assert_eq!(restored.acquire("new").unwrap(), 3);
+    }
+
+    #[test]
+    fn generation_changes_when_tags_are_created_or_removed() {
+        let registry = TagRegistry::new();
+        let g0 = registry.generation().unwrap();
+        let id = registry.acquire("foo").unwrap();
+        let g1 = registry.generation().unwrap();
+        assert_ne!(g0, g1);
+
+        registry.acquire("foo").unwrap();
+        registry.release(id).unwrap();
+        assert_eq!(registry.generation().unwrap(), g1);
+
+        registry.release(id).unwrap();
+        assert_ne!(registry.generation().unwrap(), g1);
+    }
+
     #[test]
     fn thread_safe() {
         let registry = Arc::new(TagRegistry::new());
@@ -0,0 +1,190 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, bounded};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tracing::{error, info, warn};
+
+use super::service::FirewallService;
+use common::{DISABLED_SET_EXTENSION, Policy, SET_EXTENSION, SIGNATURE_EXTENSION, SetsReload};
+
+/// Changes keep postponing a reload for at most this many debounce delays,
+/// so files rewritten continuously are still reloaded now and then
+const MAX_DEBOUNCE_ROUNDS: u32 = 10;
+
+/// Watch the set directories of every policy under `sets_dir`, reloading all
+/// the sets once their files stopped changing for `debounce`
+pub fn watch_sets(
+    service: Arc<FirewallService>,
+    sets_dir: &Path,
+    debounce: Duration,
+) -> notify::Result<()> {
+    // A single pending change is kept, the reload picking up all of them
+    let (sender, changes) = bounded(1);
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) if is_set_change(&event) => {
+                let _ = sender.try_send(());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Sets watch error: {e}"),
+        })?;
+    for policy in [Policy::Ignore, Policy::Drop, Policy::Monitor] {
+        watcher.watch(
+            &sets_dir.join(policy.to_string()),
+            RecursiveMode::NonRecursive,
+        )?;
+    }
+    info!("Watching set files in {}", sets_dir.display());
+
+    thread::spawn(move || {
+        // Notifications stop once the watcher is dropped
+        let _watcher = watcher;
+        while wait_for_changes(&changes, debounce) {
+            reload_sets(&service);
+        }
+        warn!("Sets watch channel disconnected; watcher exiting");
+    });
+    Ok(())
+}
+
+/// Reload the sets as done at startup, logging the sets skipped
+fn reload_sets(service: &FirewallService) {
+    match service.reload_changed_sets() {
+        Ok(reloads) => {
+            let skipped: Vec<String> = reloads
+                .iter()
+                .flat_map(|reload| {
+                    reload
+                        .skipped
+                        .iter()
+                        .map(move |skipped| format!("{}/{}", reload.policy, skipped.name))
+                })
+                .collect();
+            if !skipped.is_empty() {
+                warn!(sets = ?skipped, "sets skipped after a file change");
+            }
+            let total = |count: fn(&SetsReload) -> usize| reloads.iter().map(count).sum::<usize>();
+            info!(
+                created = total(|reload| reload.created),
+                updated = total(|reload| reload.updated),
+                removed = total(|reload| reload.removed),
+                skipped = total(|reload| reload.skipped.len()),
+                "sets reloaded after a file change"
+            );
+        }
+        Err(e) => error!(error = %e, "failed to reload sets after a file change"),
+    }
+}
+
+/// Whether `event` changes a set file, its signature or its disabled copy.
+/// Reads of the files, as done by a reload, and temporary files written by a
+/// sync are left out.
+fn is_set_change(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) && event.paths.iter().any(|path| is_set_file(path))
+}
+
+fn is_set_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            [SET_EXTENSION, SIGNATURE_EXTENSION, DISABLED_SET_EXTENSION]
+                .iter()
+                .any(|extension| name.ends_with(extension))
+        })
+}
+
+/// Wait for a change, then until no other one came for `debounce`, or for
+/// `MAX_DEBOUNCE_ROUNDS` debounce delays overall. False once the watcher is
+/// gone.
+fn wait_for_changes(changes: &Receiver<()>, debounce: Duration) -> bool {
+    if changes.recv().is_err() {
+        return false;
+    }
+    let first = Instant::now();
+    let max_delay = debounce.saturating_mul(MAX_DEBOUNCE_ROUNDS);
+    loop {
+        let remaining = max_delay.saturating_sub(first.elapsed());
+        if remaining.is_zero() {
+            return true;
+        }
+        match changes.recv_timeout(debounce.min(remaining)) {
+            Ok(()) => {}
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(path.into())
+    }
+
+    #[test]
+    fn set_changes_are_detected() {
+        let create = EventKind::Create(CreateKind::File);
+        let modify = EventKind::Modify(ModifyKind::Any);
+        assert!(is_set_change(&event(create, "/sets/drop/feed.couic")));
+        assert!(is_set_change(&event(modify, "/sets/drop/feed.couic.sig")));
+        assert!(is_set_change(&event(
+            EventKind::Remove(notify::event::RemoveKind::File),
+            "/sets/drop/feed.couic.disabled"
+        )));
+        // Temporary file of a sync, renamed once written
+        assert!(!is_set_change(&event(modify, "/sets/drop/.feed.couic.tmp")));
+        // Read by a reload
+        assert!(!is_set_change(&event(
+            EventKind::Access(AccessKind::Any),
+            "/sets/drop/feed.couic"
+        )));
+    }
+
+    #[test]
+    fn changes_are_debounced() {
+        let (sender, changes) = bounded(1);
+        let debounce = Duration::from_millis(50);
+        let writer = thread::spawn(move || {
+            for _ in 0..3 {
+                let _ = sender.try_send(());
+                thread::sleep(Duration::from_millis(10));
+            }
+            sender
+        });
+        let start = Instant::now();
+        assert!(wait_for_changes(&changes, debounce));
+        // Returned once the last change is older than the debounce delay
+        assert!(start.elapsed() >= debounce);
+        assert!(changes.try_recv().is_err());
+
+        drop(writer.join().unwrap());
+        assert!(!wait_for_changes(&changes, debounce));
+    }
+
+    #[test]
+    fn continuous_changes_still_reload() {
+        let (sender, changes) = bounded(1);
+        let debounce = Duration::from_millis(20);
+        let writer = thread::spawn(move || {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(600) {
+                let _ = sender.try_send(());
+                thread::sleep(Duration::from_millis(5));
+            }
+        });
+        let start = Instant::now();
+        assert!(wait_for_changes(&changes, debounce));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,21 @@
+//! Couic firewall daemon, as a library for the `couic` binary. Its modules
+//! are not a stable API.
+#![allow(clippy::similar_names)]
+
+#[doc(hidden)]
+pub mod api;
+#[doc(hidden)]
+pub mod config;
+mod error;
+mod extractors;
+#[doc(hidden)]
+pub mod firewall;
+#[doc(hidden)]
+pub mod security;
+
+/// Internals driven by the benchmarks, without loading the eBPF program
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::firewall::lpm::{LpmStore, NullMap, StoredEntry};
+}
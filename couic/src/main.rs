@@ -1,24 +1,18 @@
 #![allow(clippy::similar_names)]
 use std::fs;
-use std::path::Path;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 
 use clap::{Arg, Command};
-use tokio::net::UnixListener;
-use tracing::error;
+use tokio::net::{TcpListener, UnixListener};
+use tracing::{error, info};
 
-use crate::config::Config;
-use crate::firewall::service::FirewallService;
-use api::rbac::RBACService;
-use security::{SEC_SOCKET_PERM, SecurityService};
-
-mod api;
-mod config;
-mod error;
-mod extractors;
-mod firewall;
-mod security;
+use couic::api::{self, rbac::RBACService};
+use couic::config::{self, Config};
+use couic::firewall::service::FirewallService;
+use couic::security::{SEC_SOCKET_PERM, SecurityService};
 
 pub const NAME: &str = "Couic";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -49,6 +43,10 @@ fn main() {
             std::process::exit(1);
         })
     });
+    cfg.validate().unwrap_or_else(|e| {
+        eprintln!("Error validating configuration: {e}");
+        std::process::exit(1);
+    });
 
     // Initialize directories before logging
     cfg.init_working_dir().unwrap_or_else(|e| {
@@ -57,7 +55,7 @@ fn main() {
     });
 
     // Setup logging
-    let _guard = cfg.init_logger().unwrap_or_else(|e| {
+    let (log_level, _stdout_guard, _file_guard) = cfg.init_logger().unwrap_or_else(|e| {
         eprintln!("Error initializing logging: {e}");
         std::process::exit(1);
     });
@@ -76,7 +74,7 @@ fn main() {
     };
 
     let firewall = match FirewallService::new(cfg.clone()) {
-        Ok(service) => service,
+        Ok(service) => Arc::new(service),
         Err(e) => {
             error!("Failed to instantiate firewall service: {e}");
             process::exit(1);
@@ -88,18 +86,47 @@ fn main() {
         process::exit(1);
     }
 
-    let app = api::create_router(firewall, rbac);
+    FirewallService::launch_stats_logger(Arc::clone(&firewall));
+    if let Err(e) = FirewallService::launch_sets_watcher(Arc::clone(&firewall)) {
+        error!("Failed to watch set files: {e}");
+        process::exit(1);
+    }
 
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap();
+    let state = api::AppState::new(firewall, rbac, log_level, PathBuf::from(&cfg.logging.dir));
+    let metrics_app = cfg
+        .metrics
+        .as_ref()
+        .map(|_| api::create_metrics_router(&state));
+    let app = api::create_router(state, cfg.server.compression, cfg.server.max_body_size);
+
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(threads) = cfg.runtime.worker_threads {
+        builder.worker_threads(threads);
+    }
+    if let Some(threads) = cfg.runtime.max_blocking_threads {
+        builder.max_blocking_threads(threads);
+    }
+    let rt = builder.build().unwrap();
 
-    rt.block_on(server(app, cfg.clone()));
+    rt.block_on(server(app, metrics_app, cfg.clone()));
     process::exit(1);
 }
 
-async fn server(app: axum::Router, cfg: config::Config) {
+async fn server(app: axum::Router, metrics_app: Option<axum::Router>, cfg: config::Config) {
+    if let (Some(metrics), Some(metrics_app)) = (&cfg.metrics, metrics_app) {
+        let addr = SocketAddr::new(metrics.addr, metrics.port);
+        let listener = TcpListener::bind(addr)
+            .await
+            .expect("Error creating metrics listener");
+        info!("serving metrics on http://{addr}/metrics");
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, metrics_app).await {
+                error!("metrics listener error: {e}");
+            }
+        });
+    }
+
     if Path::new(&cfg.server.socket).exists() {
         fs::remove_file(&cfg.server.socket).expect("Fail to remove couic socket file");
     }
@@ -116,5 +143,10 @@ async fn server(app: axum::Router, cfg: config::Config) {
         eprintln!("Failed to set socket permissions: {e}");
     }
 
-    axum::serve(uds, app).await.unwrap();
+    axum::serve(
+        uds,
+        app.into_make_service_with_connect_info::<api::SocketPeer>(),
+    )
+    .await
+    .unwrap();
 }
@@ -1,11 +1,12 @@
+use std::fmt;
 use std::fs;
 use std::os::unix::fs::MetadataExt;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
-use caps::{CapSet, Capability, drop, has_cap};
+use caps::{CapSet, Capability, CapsHashSet, drop};
 use nix::sys::prctl;
-use nix::unistd::{Group, User, chown};
+use nix::unistd::{Group, User, chown, geteuid};
 
 pub const SEC_FILE_PERM: u32 = 0o600;
 pub const SEC_DIR_PERM: u32 = 0o755;
@@ -25,10 +26,207 @@ pub enum SecurityError {
     OwnershipOrPermsIncorrect(String),
     #[error("Capability error: {0}")]
     CapabilityError(String),
+    #[error("Missing capabilities to load the eBPF XDP program: {0}")]
+    MissingCapabilities(MissingCapabilities),
     #[error("Privilege error: {0}")]
     PrivilegeError(String),
 }
 
+/// How the process got its privileges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Privileges {
+    Root,
+    /// Non-root user with ambient capabilities, as granted by systemd
+    AmbientCaps,
+    /// Non-root user, possibly with file capabilities
+    Unprivileged,
+}
+
+impl fmt::Display for Privileges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Root => write!(f, "running as root"),
+            Self::AmbientCaps => write!(f, "running with ambient capabilities"),
+            Self::Unprivileged => write!(f, "running as a non-root user"),
+        }
+    }
+}
+
+/// Why a required capability cannot be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingReason {
+    /// Not permitted and out of the bounding set, so no exec can grant it
+    NotInBoundingSet,
+    NotPermitted,
+    /// Permitted but not raised in the effective set
+    NotEffective,
+}
+
+impl fmt::Display for MissingReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInBoundingSet => write!(f, "not in the bounding set"),
+            Self::NotPermitted => write!(f, "not permitted"),
+            Self::NotEffective => write!(f, "permitted but not effective"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingCapability {
+    pub cap: Capability,
+    pub reason: MissingReason,
+}
+
+/// Every required capability the process lacks, with hints to grant them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingCapabilities {
+    pub missing: Vec<MissingCapability>,
+    /// Every capability couic needs, to be granted at once
+    pub required: Vec<Capability>,
+    pub privileges: Privileges,
+    /// Path of the couic binary, for `setcap`
+    pub binary: String,
+}
+
+impl MissingCapabilities {
+    pub fn hints(&self) -> Vec<String> {
+        let names = |separator: &str, lowercase: bool| {
+            self.required
+                .iter()
+                .map(|cap| {
+                    let name = cap.to_string();
+                    if lowercase { name.to_lowercase() } else { name }
+                })
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        let systemd = format!(
+            "set `AmbientCapabilities={caps}` and `CapabilityBoundingSet={caps}` in the systemd unit",
+            caps = names(" ", false)
+        );
+        let mut hints = Vec::new();
+        let has_reason =
+            |reason: MissingReason| self.missing.iter().any(|missing| missing.reason == reason);
+        if has_reason(MissingReason::NotInBoundingSet) {
+            hints.push(format!(
+                "the bounding set was restricted by the parent: {systemd}, or add them with `--cap-add` to the container"
+            ));
+        }
+        match self.privileges {
+            Privileges::Root => {
+                if has_reason(MissingReason::NotPermitted)
+                    || has_reason(MissingReason::NotEffective)
+                {
+                    hints.push(
+                        "root does not hold them: check the securebits and the user namespace of the process"
+                            .to_string(),
+                    );
+                }
+            }
+            Privileges::AmbientCaps => hints.push(systemd),
+            Privileges::Unprivileged => {
+                hints.push(format!(
+                    "grant them to the binary with `sudo setcap {}+ep {}`, or {systemd}",
+                    names(",", true),
+                    self.binary
+                ));
+            }
+        }
+        hints
+    }
+}
+
+impl fmt::Display for MissingCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let missing = self
+            .missing
+            .iter()
+            .map(|missing| format!("{} ({})", missing.cap, missing.reason))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "{missing}; {}", self.privileges)?;
+        for hint in self.hints() {
+            write!(f, "; hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Capability sets of the calling thread
+#[derive(Debug, Default)]
+struct ThreadCaps {
+    effective: CapsHashSet,
+    permitted: CapsHashSet,
+    bounding: CapsHashSet,
+    ambient: CapsHashSet,
+}
+
+impl ThreadCaps {
+    fn read() -> Result<Self, SecurityError> {
+        let read = |set: CapSet| {
+            caps::read(None, set).map_err(|e| {
+                SecurityError::CapabilityError(format!("Failed to read {set:?} capabilities: {e}"))
+            })
+        };
+        Ok(Self {
+            effective: read(CapSet::Effective)?,
+            permitted: read(CapSet::Permitted)?,
+            bounding: read(CapSet::Bounding)?,
+            ambient: read(CapSet::Ambient)?,
+        })
+    }
+
+    fn missing(&self, cap: Capability) -> Option<MissingReason> {
+        if self.effective.contains(&cap) {
+            None
+        } else if self.permitted.contains(&cap) {
+            Some(MissingReason::NotEffective)
+        } else if self.bounding.contains(&cap) {
+            Some(MissingReason::NotPermitted)
+        } else {
+            Some(MissingReason::NotInBoundingSet)
+        }
+    }
+}
+
+/// Capabilities needed to load the eBPF program and attach it. `CAP_BPF`,
+/// from Linux 5.8 on, is left out as `CAP_SYS_ADMIN` grants everything it does
+const REQUIRED_CAPABILITIES: [Capability; 2] =
+    [Capability::CAP_NET_ADMIN, Capability::CAP_SYS_ADMIN];
+
+fn diagnose(
+    required: Vec<Capability>,
+    thread: &ThreadCaps,
+    root: bool,
+    binary: String,
+) -> Option<MissingCapabilities> {
+    let missing: Vec<MissingCapability> = required
+        .iter()
+        .filter_map(|&cap| {
+            thread
+                .missing(cap)
+                .map(|reason| MissingCapability { cap, reason })
+        })
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    let privileges = if root {
+        Privileges::Root
+    } else if thread.ambient.is_empty() {
+        Privileges::Unprivileged
+    } else {
+        Privileges::AmbientCaps
+    };
+    Some(MissingCapabilities {
+        missing,
+        required,
+        privileges,
+        binary,
+    })
+}
+
 pub struct SecurityService;
 
 impl SecurityService {
@@ -93,29 +291,26 @@ impl SecurityService {
         Ok(())
     }
 
+    /// Check the capabilities needed at startup, reporting all the missing
+    /// ones at once
     pub fn check_required_capabilities() -> Result<(), SecurityError> {
-        let required_caps = [Capability::CAP_NET_ADMIN, Capability::CAP_SYS_ADMIN];
-        for &cap in &required_caps {
-            match has_cap(None, CapSet::Effective, cap) {
-                Ok(true) => {}
-                Ok(false) => {
-                    return Err(SecurityError::CapabilityError(format!(
-                        "Missing capability to load ebpf xdp program: {cap:?}"
-                    )));
-                }
-                Err(e) => {
-                    return Err(SecurityError::CapabilityError(format!(
-                        "Error checking capability {cap:?}: {e}"
-                    )));
-                }
-            }
+        let required = REQUIRED_CAPABILITIES.to_vec();
+        let binary = std::env::current_exe()
+            .map_or_else(|_| "couic".to_string(), |path| path.display().to_string());
+        match diagnose(required, &ThreadCaps::read()?, geteuid().is_root(), binary) {
+            Some(missing) => Err(SecurityError::MissingCapabilities(missing)),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     pub fn drop_all_caps_nonewprivs() -> Result<(), SecurityError> {
+        Self::drop_caps_nonewprivs_except(&[])
+    }
+
+    /// Drop the capabilities of the calling thread but `keep`
+    pub fn drop_caps_nonewprivs_except(keep: &[Capability]) -> Result<(), SecurityError> {
         let all_caps = caps::all();
-        for cap in all_caps {
+        for cap in all_caps.into_iter().filter(|cap| !keep.contains(cap)) {
             for set in &[CapSet::Effective, CapSet::Permitted, CapSet::Inheritable] {
                 if let Err(e) = drop(None, *set, cap) {
                     return Err(SecurityError::CapabilityError(format!(
@@ -382,4 +577,93 @@ mod tests {
                 .unwrap();
         }
     }
+
+    fn thread_caps(effective: &[Capability], permitted: &[Capability]) -> ThreadCaps {
+        ThreadCaps {
+            effective: effective.iter().copied().collect(),
+            permitted: permitted.iter().copied().collect(),
+            bounding: [Capability::CAP_NET_ADMIN, Capability::CAP_SYS_ADMIN]
+                .into_iter()
+                .collect(),
+            ambient: CapsHashSet::new(),
+        }
+    }
+
+    fn required() -> Vec<Capability> {
+        REQUIRED_CAPABILITIES.to_vec()
+    }
+
+    #[test]
+    fn test_diagnose_reports_every_missing_capability() {
+        let thread = thread_caps(&[], &[Capability::CAP_SYS_ADMIN]);
+        let report = diagnose(required(), &thread, false, "/usr/bin/couic".to_string()).unwrap();
+        assert_eq!(report.privileges, Privileges::Unprivileged);
+        assert_eq!(
+            report.missing,
+            vec![
+                MissingCapability {
+                    cap: Capability::CAP_NET_ADMIN,
+                    reason: MissingReason::NotPermitted
+                },
+                MissingCapability {
+                    cap: Capability::CAP_SYS_ADMIN,
+                    reason: MissingReason::NotEffective
+                },
+            ]
+        );
+        let message = report.to_string();
+        assert!(message.starts_with(
+            "CAP_NET_ADMIN (not permitted), CAP_SYS_ADMIN (permitted but not effective); \
+             running as a non-root user"
+        ));
+        assert!(message.contains("sudo setcap cap_net_admin,cap_sys_admin+ep /usr/bin/couic"));
+        assert!(!message.contains("--cap-add"));
+
+        let mut thread = thread_caps(&[], &[]);
+        thread.bounding.remove(&Capability::CAP_SYS_ADMIN);
+        let report = diagnose(required(), &thread, false, String::new()).unwrap();
+        assert_eq!(
+            report.missing.last(),
+            Some(&MissingCapability {
+                cap: Capability::CAP_SYS_ADMIN,
+                reason: MissingReason::NotInBoundingSet
+            })
+        );
+        assert!(report.to_string().contains("--cap-add"));
+    }
+
+    #[test]
+    fn test_diagnose_never_requires_bpf() {
+        let granted = [Capability::CAP_NET_ADMIN, Capability::CAP_SYS_ADMIN];
+        let thread = thread_caps(&granted, &granted);
+        assert!(diagnose(required(), &thread, false, String::new()).is_none());
+
+        // Nor reports or suggests it when CAP_SYS_ADMIN is missing
+        let thread = thread_caps(&[Capability::CAP_NET_ADMIN], &[Capability::CAP_NET_ADMIN]);
+        let report = diagnose(required(), &thread, false, String::new()).unwrap();
+        let missing: Vec<Capability> = report.missing.iter().map(|missing| missing.cap).collect();
+        assert_eq!(missing, vec![Capability::CAP_SYS_ADMIN]);
+        let message = report.to_string();
+        assert!(!message.to_lowercase().contains("cap_bpf"), "{message}");
+    }
+
+    #[test]
+    fn test_diagnose_privileges() {
+        let all = required();
+        assert!(diagnose(all.clone(), &thread_caps(&all, &all), false, String::new()).is_none());
+
+        let root = diagnose(all.clone(), &thread_caps(&[], &[]), true, String::new()).unwrap();
+        assert_eq!(root.privileges, Privileges::Root);
+        assert!(!root.to_string().contains("setcap"));
+
+        let mut ambient = thread_caps(&[Capability::CAP_NET_ADMIN], &[Capability::CAP_NET_ADMIN]);
+        ambient.ambient.insert(Capability::CAP_NET_ADMIN);
+        let report = diagnose(all, &ambient, false, String::new()).unwrap();
+        assert_eq!(report.privileges, Privileges::AmbientCaps);
+        assert!(
+            report
+                .to_string()
+                .contains("AmbientCapabilities=CAP_NET_ADMIN CAP_SYS_ADMIN`")
+        );
+    }
 }
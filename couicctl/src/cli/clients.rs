@@ -1,5 +1,6 @@
 use clap::{Args, Subcommand};
 use comfy_table::{Cell, ContentArrangement, Table, presets::UTF8_FULL};
+use serde::Serialize;
 
 use client::CouicClient;
 use common::{Client, ClientName, ClientRaw, Group};
@@ -39,6 +40,8 @@ enum ClientsSubCommand {
         name: ClientName,
         #[arg(long)]
         json: bool,
+        #[arg(long, help = "Display full tokens instead of a redacted prefix")]
+        show_tokens: bool,
     },
     #[command(about = "List clients")]
     List {
@@ -46,6 +49,12 @@ enum ClientsSubCommand {
         quiet: bool,
         #[arg(long, conflicts_with = "quiet")]
         json: bool,
+        #[arg(
+            long,
+            conflicts_with = "quiet",
+            help = "Display full tokens instead of a redacted prefix"
+        )]
+        show_tokens: bool,
     },
     #[command(about = "Remove client")]
     Delete { name: ClientName },
@@ -60,22 +69,37 @@ impl Command for ClientsCommand {
                     group: group.clone(),
                 };
                 let clt = client.clients().add(&client_request)?;
+                // The token is only useful if displayed in full on creation
+                let clt = ClientView::new(clt, true);
                 if *json {
                     println!("{}", serde_json::to_string_pretty(&clt)?);
                 } else {
                     print_client(clt);
                 }
             }
-            ClientsSubCommand::Inspect { name, json } => {
-                let clt = client.clients().get(name)?;
+            ClientsSubCommand::Inspect {
+                name,
+                json,
+                show_tokens,
+            } => {
+                let clt = ClientView::new(client.clients().get(name)?, *show_tokens);
                 if *json {
                     println!("{}", serde_json::to_string_pretty(&clt)?);
                 } else {
                     print_client(clt);
                 }
             }
-            ClientsSubCommand::List { quiet, json } => {
-                let clients = client.clients().list()?;
+            ClientsSubCommand::List {
+                quiet,
+                json,
+                show_tokens,
+            } => {
+                let clients: Vec<ClientView> = client
+                    .clients()
+                    .list()?
+                    .into_iter()
+                    .map(|c| ClientView::new(c, *show_tokens))
+                    .collect();
                 if *json {
                     println!("{}", serde_json::to_string_pretty(&clients)?);
                 } else {
@@ -90,7 +114,40 @@ impl Command for ClientsCommand {
     }
 }
 
-fn print_client(client: Client) {
+const REDACTED_TOKEN_PREFIX_LENGTH: usize = 8;
+
+/// Client as displayed by the CLI, with an optionally redacted token
+#[derive(Debug, Serialize)]
+struct ClientView {
+    name: ClientName,
+    group: Group,
+    token: String,
+}
+
+impl ClientView {
+    fn new(client: Client, show_token: bool) -> Self {
+        let token = client.token.to_string();
+        Self {
+            name: client.name,
+            group: client.group,
+            token: if show_token {
+                token
+            } else {
+                redact_token(&token)
+            },
+        }
+    }
+}
+
+/// Keep only the first characters of a token, e.g. `01115f88-****`
+fn redact_token(token: &str) -> String {
+    let prefix = token
+        .get(..REDACTED_TOKEN_PREFIX_LENGTH)
+        .unwrap_or_default();
+    format!("{prefix}-****")
+}
+
+fn print_client(client: ClientView) {
     let mut table = Table::new();
     table
         .load_preset(UTF8_FULL)
@@ -106,7 +163,7 @@ fn print_client(client: Client) {
     println!("{table}");
 }
 
-fn print_clients(clients: Vec<Client>, quiet: bool) {
+fn print_clients(clients: Vec<ClientView>, quiet: bool) {
     if quiet {
         for c in clients {
             println!("{}", c.name);
@@ -128,3 +185,42 @@ fn print_clients(clients: Vec<Client>, quiet: bool) {
         println!("{table}");
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn make_client() -> Client {
+        Client {
+            name: ClientName::try_from("superclient").unwrap(),
+            token: Uuid::parse_str("01115f88-fd3d-4fbd-b205-44c90e81dae5").unwrap(),
+            group: Group::ClientRw,
+        }
+    }
+
+    #[test]
+    fn test_redact_token() {
+        assert_eq!(
+            redact_token("01115f88-fd3d-4fbd-b205-44c90e81dae5"),
+            "01115f88-****"
+        );
+        assert_eq!(redact_token("short"), "-****");
+    }
+
+    #[test]
+    fn test_client_view_redacted_by_default() {
+        let view = ClientView::new(make_client(), false);
+        assert_eq!(view.token, "01115f88-****");
+
+        let json = serde_json::to_string(&view).unwrap();
+        assert!(!json.contains("44c90e81dae5"));
+    }
+
+    #[test]
+    fn test_client_view_show_token() {
+        let view = ClientView::new(make_client(), true);
+        assert_eq!(view.token, "01115f88-fd3d-4fbd-b205-44c90e81dae5");
+    }
+}
@@ -0,0 +1,153 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::os::unix::fs::FileTypeExt;
+
+use clap::{Args, Subcommand};
+
+use client::{CouicClient, CouicError};
+
+use super::CommandError;
+use crate::config::{Config, Mode, Overrides};
+
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_RESET: &str = "\x1b[0m";
+const HTTP_UNAUTHORIZED: u16 = 401;
+
+#[derive(Args, Debug)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    command: ConfigSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+#[command(about = "Inspect couicctl configuration")]
+enum ConfigSubCommand {
+    #[command(about = "Check configuration, daemon reachability and authentication")]
+    Check,
+}
+
+impl ConfigCommand {
+    /// Run the command from the configuration file, before any connection to the daemon
+    pub fn run(&self, path: &str, overrides: &Overrides) -> Result<(), CommandError> {
+        match self.command {
+            ConfigSubCommand::Check => check(path, overrides),
+        }
+    }
+}
+
+/// Whether the report is colored: only on a terminal, unless `NO_COLOR` is
+/// set to a non-empty value
+fn use_color(is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+    is_terminal && no_color.is_none_or(OsStr::is_empty)
+}
+
+fn report(ok: bool, label: &str, detail: &str) {
+    let (color, mark) = if ok {
+        (ANSI_GREEN, "✔")
+    } else {
+        (ANSI_RED, "✘")
+    };
+    if use_color(
+        io::stdout().is_terminal(),
+        std::env::var_os("NO_COLOR").as_deref(),
+    ) {
+        println!("{color}{mark}{ANSI_RESET} {label:<15} {detail}");
+    } else {
+        println!("{mark} {label:<15} {detail}");
+    }
+}
+
+fn check_failed() -> CommandError {
+    CommandError::Generic("configuration check failed".to_string())
+}
+
+fn check(path: &str, overrides: &Overrides) -> Result<(), CommandError> {
+    let config = match Config::load(path, overrides) {
+        Ok(config) => {
+            report(true, "Config", path);
+            config
+        }
+        Err(e) => {
+            report(false, "Config", &format!("{path}: {e}"));
+            return Err(check_failed());
+        }
+    };
+
+    match config.mode {
+        Mode::Local => {
+            let socket = config.socket.clone().unwrap_or_default();
+            report(true, "Mode", "local");
+            let is_socket =
+                fs::metadata(&socket).is_ok_and(|metadata| metadata.file_type().is_socket());
+            if is_socket {
+                report(true, "Socket", &socket);
+            } else {
+                report(false, "Socket", &format!("{socket} is not a Unix socket"));
+                return Err(check_failed());
+            }
+        }
+        Mode::Remote => {
+            report(true, "Mode", "remote");
+            let scheme = if config.tls.unwrap_or_default() {
+                "https"
+            } else {
+                "http"
+            };
+            report(
+                true,
+                "Host",
+                &format!(
+                    "{scheme}://{}:{}",
+                    config.host.clone().unwrap_or_default(),
+                    config.port.unwrap_or_default()
+                ),
+            );
+        }
+    }
+
+    match config.build_client(overrides.token) {
+        Ok(client) => check_daemon(&client),
+        Err(e) => {
+            report(false, "Connection", &e.to_string());
+            Err(check_failed())
+        }
+    }
+}
+
+fn check_daemon(client: &CouicClient) -> Result<(), CommandError> {
+    match client.health().get() {
+        Ok(health) => {
+            report(true, "Connection", &format!("couic {}", health.version));
+            report(
+                true,
+                "Authentication",
+                &format!("client {} (group {})", health.client, health.group),
+            );
+            Ok(())
+        }
+        Err(CouicError::ApiError { status, .. }) if status == HTTP_UNAUTHORIZED => {
+            report(true, "Connection", client.info().as_str());
+            report(false, "Authentication", "token rejected by the daemon");
+            Err(check_failed())
+        }
+        Err(e) => {
+            report(false, "Connection", &e.to_string());
+            Err(check_failed())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_use_color() {
+        assert!(use_color(true, None));
+        assert!(use_color(true, Some(OsStr::new(""))));
+        assert!(!use_color(true, Some(OsStr::new("1"))));
+        assert!(!use_color(false, None));
+    }
+}
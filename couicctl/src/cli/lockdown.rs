@@ -0,0 +1,64 @@
+use clap::{Args, Subcommand};
+
+use client::CouicClient;
+use common::Lockdown;
+
+use super::{Command, CommandError};
+
+#[derive(Args, Debug)]
+#[command(
+    about = "Drop every packet not matched by an ignore entry",
+    long_about = "Switch the daemon to lockdown during an incident: every packet not matched by an ignore entry is dropped, until lockdown is turned off or the daemon restarts. Requires an admin client"
+)]
+pub struct LockdownCommand {
+    #[command(subcommand)]
+    command: LockdownSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum LockdownSubCommand {
+    #[command(
+        about = "Enable lockdown",
+        long_about = "Enable lockdown. Refused while a range of firewall.management_cidrs is not covered by an ignore entry that never expires, or when none is configured, unless forced"
+    )]
+    On {
+        #[arg(long, help = "Enable it even though management ranges are not ignored")]
+        force: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    #[command(about = "Disable lockdown")]
+    Off {
+        #[arg(long)]
+        json: bool,
+    },
+    #[command(about = "Show whether lockdown is enabled")]
+    Status {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+impl Command for LockdownCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        let (lockdown, json) = match &self.command {
+            LockdownSubCommand::On { force, json } => (client.lockdown().set(true, *force)?, json),
+            LockdownSubCommand::Off { json } => (client.lockdown().set(false, false)?, json),
+            LockdownSubCommand::Status { json } => (client.lockdown().status()?, json),
+        };
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&lockdown)?);
+        } else {
+            print_lockdown(lockdown);
+        }
+        Ok(())
+    }
+}
+
+fn print_lockdown(lockdown: Lockdown) {
+    if lockdown.enabled {
+        println!("Lockdown: on, every packet not matched by an ignore entry is dropped");
+    } else {
+        println!("Lockdown: off");
+    }
+}
@@ -0,0 +1,86 @@
+use clap::Args;
+
+use client::CouicClient;
+use common::LogLevel;
+
+use super::{Command, CommandError};
+
+#[derive(Args, Debug)]
+#[command(
+    about = "Show or change the log level of the daemon",
+    long_about = "Show the log filter of the daemon, or change its level without a restart, e.g. to debug an incident. The level is kept until the next change, --reset or a restart, which restore the filter of RUST_LOG"
+)]
+pub struct LogLevelCommand {
+    #[arg(help = "New level: off, error, warn, info, debug or trace")]
+    level: Option<LogLevel>,
+    #[arg(
+        long,
+        conflicts_with = "level",
+        help = "Restore the filter the daemon started with"
+    )]
+    reset: bool,
+    #[arg(long)]
+    json: bool,
+}
+
+impl Command for LogLevelCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        let filter = match self.level {
+            Some(level) => client.logging().set_level(level)?,
+            None if self.reset => client.logging().reset_level()?,
+            None => client.logging().level()?,
+        };
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&filter)?);
+        } else {
+            println!("Log level: {}", filter.filter);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about = "Show the last lines of the daemon log",
+    long_about = "Show the last lines of the current log file of the daemon, and with --follow the lines appended to it until interrupted. Requires an admin client"
+)]
+pub struct LogsCommand {
+    #[arg(
+        long,
+        default_value_t = 100,
+        help = "Number of lines to show, up to 10000"
+    )]
+    tail: usize,
+    #[arg(short, long, help = "Keep showing the lines appended to the log")]
+    follow: bool,
+    #[arg(long, conflicts_with = "follow")]
+    json: bool,
+}
+
+impl LogsCommand {
+    /// Whether the command streams the log, which no request timeout should
+    /// cut
+    pub const fn follows(&self) -> bool {
+        self.follow
+    }
+}
+
+impl Command for LogsCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        if self.follow {
+            client
+                .logging()
+                .follow(self.tail, |line| println!("{line}"))?;
+            return Ok(());
+        }
+        let tail = client.logging().tail(self.tail)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&tail)?);
+        } else {
+            for line in &tail.lines {
+                println!("{line}");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use clap::{Args, Subcommand};
+
+use client::CouicClient;
+
+use super::table::{Column, TableOptions, print_table};
+use super::{Command, CommandError};
+
+const COMPACTION_COLUMNS: [Column; 5] = [
+    Column {
+        key: "policy",
+        header: "Policy",
+    },
+    Column {
+        key: "family",
+        header: "Family",
+    },
+    Column {
+        key: "entries",
+        header: "Entries",
+    },
+    Column {
+        key: "capacity_before",
+        header: "Capacity Before",
+    },
+    Column {
+        key: "capacity_after",
+        header: "Capacity After",
+    },
+];
+
+#[derive(Args, Debug)]
+pub struct MaintenanceCommand {
+    #[command(subcommand)]
+    command: MaintenanceSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+#[command(about = "Run maintenance tasks on the daemon")]
+enum MaintenanceSubCommand {
+    #[command(
+        about = "Release the memory of removed entries",
+        long_about = "Shrink the userspace maps of every policy to their entries now, rather than at the next periodic shrink, e.g. after a large import was removed or expired. Requires an admin client"
+    )]
+    Compact {
+        #[command(flatten)]
+        table: TableOptions,
+        #[arg(long, conflicts_with_all = ["no_headers", "columns"])]
+        json: bool,
+    },
+}
+
+impl Command for MaintenanceCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        match &self.command {
+            MaintenanceSubCommand::Compact { table, json } => {
+                let compaction = client.maintenance().compact()?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&compaction)?);
+                    return Ok(());
+                }
+                let rows = compaction
+                    .maps
+                    .into_iter()
+                    .map(|map| {
+                        vec![
+                            map.policy.to_string(),
+                            map.family,
+                            map.entries.to_string(),
+                            map.capacity_before.to_string(),
+                            map.capacity_after.to_string(),
+                        ]
+                    })
+                    .collect();
+                print_table(&COMPACTION_COLUMNS, rows, table)
+            }
+        }
+    }
+}
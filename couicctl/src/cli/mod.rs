@@ -3,14 +3,20 @@ use clap::{Parser, Subcommand};
 use client::{CouicClient, CouicError};
 
 use crate::VERSION;
-use crate::config::ConfigError;
+use crate::config::{ConfigError, Overrides};
 
 mod clients;
+mod config;
+mod lockdown;
+mod logging;
+mod maintenance;
 mod policy;
 mod sets;
 mod stats;
+mod table;
+mod version;
 
-use policy::{DropSubCommand, IgnoreSubCommand, PolicyCommand};
+use policy::{DropSubCommand, IgnoreSubCommand, MonitorSubCommand, PolicyCommand, TestCommand};
 
 #[derive(Parser, Debug)]
 #[command(name = "couicctl")]
@@ -25,6 +31,8 @@ pub struct Cli {
         default_value = "/etc/couic/couicctl.toml"
     )]
     pub config: String,
+    #[command(flatten)]
+    pub overrides: Overrides,
     #[arg(long, hide = true)]
     pub markdown_help: bool,
     #[command(subcommand)]
@@ -52,6 +60,36 @@ pub enum Commands {
     Sets(sets::SetsCommand),
     Drop(PolicyCommand<DropSubCommand>),
     Ignore(PolicyCommand<IgnoreSubCommand>),
+    Monitor(PolicyCommand<MonitorSubCommand>),
+    Test(TestCommand),
+    Config(config::ConfigCommand),
+    LogLevel(logging::LogLevelCommand),
+    Logs(logging::LogsCommand),
+    Maintenance(maintenance::MaintenanceCommand),
+    Lockdown(lockdown::LockdownCommand),
+    Version(version::VersionCommand),
+}
+
+impl Commands {
+    /// Whether the command may connect to the daemon, and so needs a token
+    #[must_use]
+    pub const fn connects(&self) -> bool {
+        match self {
+            Self::Sets(cmd) => cmd.connects(),
+            Self::Version(cmd) => cmd.connects(),
+            Self::Clients(_)
+            | Self::Stats(_)
+            | Self::Drop(_)
+            | Self::Ignore(_)
+            | Self::Monitor(_)
+            | Self::Test(_)
+            | Self::Config(_)
+            | Self::LogLevel(_)
+            | Self::Logs(_)
+            | Self::Maintenance(_)
+            | Self::Lockdown(_) => true,
+        }
+    }
 }
 
 pub fn execute(mut client: CouicClient, command: Commands) -> Result<(), CommandError> {
@@ -61,9 +99,54 @@ pub fn execute(mut client: CouicClient, command: Commands) -> Result<(), Command
         Commands::Sets(cmd) => cmd.execute(&mut client),
         Commands::Drop(cmd) => cmd.execute(&mut client),
         Commands::Ignore(cmd) => cmd.execute(&mut client),
+        Commands::Monitor(cmd) => cmd.execute(&mut client),
+        Commands::Test(cmd) => cmd.execute(&mut client),
+        // Run by `main` before connecting, the check reporting connection
+        // failures itself
+        Commands::Config(_) => Err(CommandError::Generic(
+            "configuration checks do not run on an established connection".to_string(),
+        )),
+        Commands::LogLevel(cmd) => cmd.execute(&mut client),
+        Commands::Logs(cmd) => cmd.execute(&mut client),
+        Commands::Maintenance(cmd) => cmd.execute(&mut client),
+        Commands::Lockdown(cmd) => cmd.execute(&mut client),
+        Commands::Version(cmd) => cmd.execute(&mut client),
     }
 }
 
 pub trait Command {
     fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError>;
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn connects(args: &[&str]) -> bool {
+        let cli = Cli::try_parse_from([&["couicctl"], args].concat()).unwrap();
+        cli.command.unwrap().connects()
+    }
+
+    #[test]
+    fn test_only_commands_reaching_the_daemon_connect() {
+        assert!(connects(&["drop", "list"]));
+        assert!(connects(&["version", "--remote"]));
+        assert!(!connects(&["version"]));
+        assert!(!connects(&[
+            "sets",
+            "sign",
+            "--key",
+            "key.pem",
+            "set.couic"
+        ]));
+        assert!(!connects(&["sets", "validate", "set.couic"]));
+        assert!(connects(&[
+            "sets",
+            "validate",
+            "set.couic",
+            "--policy",
+            "drop"
+        ]));
+    }
+}
@@ -1,17 +1,62 @@
+use std::fs;
+use std::io::{self, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use clap::{Args, Subcommand};
 use comfy_table::{Cell, ContentArrangement, Table, presets::UTF8_FULL};
 use humantime::parse_duration;
 
-use client::CouicClient;
-use common::{Entry, Expiration, NormalizedCidr, Policy, RawEntry, Tag};
+use client::{BatchSummary, CouicClient, CouicError, WriteOptions};
+use common::constants::{MAX_NAMESPACED_TAG_LENGTH, MAX_TAG_NAME_LENGTH, MAX_TAG_NAMESPACE_DEPTH};
+use common::{
+    CompositeError, Entry, EntrySource, Expiration, Metadata, NormalizedCidr, Policy, RawEntries,
+    RawEntry, RawEntryInput, ReplaceSummary, Tag, ValidateFrom, feature, tag,
+};
+use indicatif::{ProgressBar, ProgressStyle};
 
+use super::table::{Column, TableOptions, print_table};
 use super::{Command, CommandError};
+use crate::range::CidrInput;
+use crate::ripe::{OriginCache, PrefixOrigin};
 
 const SECONDS_PER_DAY: u64 = 86_400;
 const SECONDS_PER_HOUR: u64 = 3_600;
 const SECONDS_PER_MINUTE: u64 = 60;
+const DEFAULT_METADATA_KIND: &str = "manual";
+const STDIN_ENTRIES_FILE: &str = "-";
+const ENTRY_COLUMNS: [Column; 5] = [
+    Column {
+        key: "policy",
+        header: "Policy",
+    },
+    Column {
+        key: "cidr",
+        header: "CIDR",
+    },
+    Column {
+        key: "tag",
+        header: "Tag",
+    },
+    Column {
+        key: "expiration",
+        header: "Expiration",
+    },
+    Column {
+        key: "source",
+        header: "Source",
+    },
+];
+
+/// How `--dry-run` checks the entries of an addition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DryRun {
+    /// Validate locally, then have the daemon check them
+    Server,
+    /// Validate locally only
+    Local,
+}
 
 #[derive(Args, Debug)]
 pub struct PolicyCommand<T: Subcommand> {
@@ -29,6 +74,22 @@ trait PolicyAction {
     fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError>;
 }
 
+/// Checks an expiration delay when parsing arguments, zero meaning never
+fn parse_expiration(expiration: &str) -> Result<String, String> {
+    if expiration != "0" {
+        parse_duration(expiration).map_err(|e| e.to_string())?;
+    }
+    Ok(expiration.to_string())
+}
+
+fn tag_long_help() -> String {
+    format!(
+        "Tag for the entry. Valid characters are a-zA-Z0-9-_ and max length is {MAX_TAG_NAME_LENGTH} per name. \
+         Tags can be namespaced with '/', e.g. team/web/fail2ban, up to {MAX_TAG_NAMESPACE_DEPTH} \
+         levels and {MAX_NAMESPACED_TAG_LENGTH} characters in total"
+    )
+}
+
 fn calculate_expiration(expiration: &str) -> Result<u64, CommandError> {
     if expiration != "0" {
         let duration = parse_duration(expiration)
@@ -43,6 +104,21 @@ fn calculate_expiration(expiration: &str) -> Result<u64, CommandError> {
     Ok(0)
 }
 
+fn build_metadata(
+    kind: Option<&str>,
+    detail: Option<&str>,
+) -> Result<Option<Metadata>, CommandError> {
+    if kind.is_none() && detail.is_none() {
+        return Ok(None);
+    }
+    Metadata::new(
+        kind.unwrap_or(DEFAULT_METADATA_KIND),
+        detail.unwrap_or_default(),
+    )
+    .map(Some)
+    .map_err(|e| CommandError::Generic(format!("Invalid metadata: {e}")))
+}
+
 fn format_expiration(expiration: Expiration) -> String {
     if expiration.is_never() {
         "never".to_string()
@@ -81,41 +157,60 @@ fn format_expiration(expiration: Expiration) -> String {
 }
 
 fn matches_tag(tag: &str, patterns: &[String]) -> bool {
-    patterns.iter().any(|pattern| {
-        let starts_wild = pattern.starts_with('*');
-        let ends_wild = pattern.ends_with('*');
-
-        match (starts_wild, ends_wild) {
-            (true, true) => {
-                // *substring* or * or **
-                if pattern.len() <= 2 {
-                    // "*" or "**" matches everything
-                    true
-                } else {
-                    pattern
-                        .strip_prefix('*')
-                        .and_then(|p| p.strip_suffix('*'))
-                        .is_some_and(|inner| tag.contains(inner))
-                }
-            }
-            (true, false) => {
-                // *suffix
-                pattern
-                    .strip_prefix('*')
-                    .is_some_and(|suffix| !suffix.is_empty() && tag.ends_with(suffix))
-            }
-            (false, true) => {
-                // prefix*
-                pattern
-                    .strip_suffix('*')
-                    .is_some_and(|prefix| !prefix.is_empty() && tag.starts_with(prefix))
-            }
-            (false, false) => {
-                // exact match
-                tag == pattern
-            }
+    patterns
+        .iter()
+        .any(|pattern| tag::matches_pattern(tag, pattern))
+}
+
+/// Filters of entry lists on their expiration
+#[derive(Args, Debug)]
+pub struct ExpiryFilter {
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = "Only list entries expiring within DURATION (e.g., 30m, 1d), expired ones included"
+    )]
+    expiring_within: Option<Duration>,
+    #[arg(
+        long,
+        conflicts_with = "expiring_within",
+        help = "Only list expired entries not yet removed by the daemon"
+    )]
+    expired: bool,
+}
+
+impl ExpiryFilter {
+    const fn within(&self) -> Option<Duration> {
+        if self.expired {
+            Some(Duration::ZERO)
+        } else {
+            self.expiring_within
         }
-    })
+    }
+}
+
+/// Entries of `policy` matching the tags and expiry filters, the latter
+/// applied by the daemon when it supports it
+fn list_entries(
+    client: &CouicClient,
+    policy: Policy,
+    tags: Option<&str>,
+    expiry: &ExpiryFilter,
+) -> Result<Vec<Entry>, CommandError> {
+    let entries = match expiry.within() {
+        None => client.policy().list(policy)?,
+        Some(within) if client.server_version()?.supports(feature::EXPIRING_FILTER) => {
+            client.policy().list_expiring(policy, within)?
+        }
+        Some(within) => client
+            .policy()
+            .list(policy)?
+            .into_iter()
+            .filter(|entry| entry.expiration.expires_within(within))
+            .collect(),
+    };
+    Ok(filter_entries(entries, tags))
 }
 
 fn filter_entries(entries: Vec<Entry>, tags: Option<&str>) -> Vec<Entry> {
@@ -138,27 +233,356 @@ fn filter_entries(entries: Vec<Entry>, tags: Option<&str>) -> Vec<Entry> {
     }
 }
 
-fn print_entries(entries: Vec<Entry>, quiet: bool, policy: &str) {
-    if quiet {
-        for entry in entries {
-            println!("{}", entry.cidr);
+/// Entries of an addition, validated like the daemon does
+fn raw_entries(
+    cidr: &CidrInput,
+    tag: Option<&Tag>,
+    expiration: u64,
+    metadata: Option<&Metadata>,
+) -> Result<Vec<RawEntry>, CommandError> {
+    cidr.cidrs()
+        .iter()
+        .map(|cidr| {
+            RawEntry::validate_from(RawEntryInput {
+                cidr: cidr.to_string(),
+                tag: tag.map(ToString::to_string),
+                expiration,
+                metadata: metadata.cloned(),
+            })
+            .map_err(|e| invalid_entry(*cidr, &e))
+        })
+        .collect()
+}
+
+fn invalid_entry(cidr: NormalizedCidr, error: &CompositeError) -> CommandError {
+    CommandError::Generic(format!("Invalid entry {cidr}: {}", describe_error(error)))
+}
+
+/// Details of `error` on one line, or its message when it has none
+fn describe_error(error: &CompositeError) -> String {
+    let mut details: Vec<String> = error
+        .errors
+        .iter()
+        .map(|(field, detail)| format!("{field}: {}", detail.message))
+        .collect();
+    details.sort();
+    if details.is_empty() {
+        error.message.clone()
+    } else {
+        details.join(", ")
+    }
+}
+
+/// Entries of an addition, from the command line or from `--from-file`
+fn entries_to_add(
+    cidr: Option<&CidrInput>,
+    from_file: Option<&Path>,
+    tag: Option<&Tag>,
+    expiration: &str,
+    kind: Option<&str>,
+    detail: Option<&str>,
+) -> Result<Vec<RawEntry>, CommandError> {
+    if let Some(path) = from_file {
+        return Ok(read_entries_file(path)?.0);
+    }
+    let cidr =
+        cidr.ok_or_else(|| CommandError::Generic("A CIDR or --from-file is required".to_string()))?;
+    raw_entries(
+        cidr,
+        tag,
+        calculate_expiration(expiration)?,
+        build_metadata(kind, detail)?.as_ref(),
+    )
+}
+
+/// Warn about drops of link-local or multicast sources, which forwarded
+/// traffic never comes from
+fn warn_local_only(entries: &[RawEntry]) {
+    for entry in entries {
+        if let Some(caveat) = entry.cidr.source_caveat() {
+            eprintln!(
+                "Warning: {} only matches local traffic: {caveat}",
+                entry.cidr
+            );
         }
+    }
+}
+
+/// Options of an addition checked by the daemon with `--dry-run`
+fn server_dry_run(
+    client: &CouicClient,
+    options: WriteOptions,
+) -> Result<WriteOptions, CommandError> {
+    // An older daemon would add the entries instead of checking them
+    if !client.server_version()?.supports(feature::DRY_RUN) {
+        return Err(CommandError::Generic(
+            "The daemon cannot check entries without adding them, use --dry-run=local".to_string(),
+        ));
+    }
+    Ok(WriteOptions {
+        dry_run: true,
+        ..options
+    })
+}
+
+/// Add `entries`, or only check them with `dry_run`. When some of the
+/// entries of a range fail, the added and failed ones are reported before
+/// returning an error.
+fn add_entries(
+    client: &CouicClient,
+    policy: Policy,
+    entries: &[RawEntry],
+    options: WriteOptions,
+    dry_run: Option<DryRun>,
+    json: bool,
+) -> Result<Vec<Entry>, CommandError> {
+    let options = match dry_run {
+        Some(DryRun::Local) => {
+            return Ok(entries.iter().cloned().map(RawEntry::into_entry).collect());
+        }
+        Some(DryRun::Server) => server_dry_run(client, options)?,
+        None => options,
+    };
+    if let [entry] = entries {
+        return Ok(vec![
+            client.policy().add_with_options(policy, entry, options)?,
+        ]);
+    }
+    let summary = client.policy().add_batch(policy, entries, options, |_| {});
+    if summary.failed.is_empty() {
+        return Ok(summary.added);
+    }
+    print_batch(&summary, summary.added.len(), policy, dry_run, json)?;
+    Err(batch_failed(&summary, policy))
+}
+
+fn batch_failed(summary: &BatchSummary, policy: Policy) -> CommandError {
+    CommandError::Generic(format!(
+        "{} {policy} entries could not be added",
+        summary.failed.len().saturating_add(summary.skipped)
+    ))
+}
+
+/// Add the entries of `--from-file` with a progress bar, the entries refused
+/// by the daemon being reported at the end
+fn add_batch(
+    client: &CouicClient,
+    policy: Policy,
+    entries: &[RawEntry],
+    options: WriteOptions,
+    dry_run: Option<DryRun>,
+    json: bool,
+) -> Result<(), CommandError> {
+    let options = match dry_run {
+        Some(DryRun::Local) => {
+            // Entries of the file are validated when it is read
+            print_batch(
+                &BatchSummary::default(),
+                entries.len(),
+                policy,
+                dry_run,
+                json,
+            )?;
+            return Ok(());
+        }
+        Some(DryRun::Server) => server_dry_run(client, options)?,
+        None => options,
+    };
+
+    let bar = ProgressBar::new(u64::try_from(entries.len()).unwrap_or(u64::MAX));
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} entries, {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    bar.set_message("0 failed");
+    let summary = client
+        .policy()
+        .add_batch(policy, entries, options, |progress| {
+            bar.set_position(u64::try_from(progress.done).unwrap_or(u64::MAX));
+            bar.set_message(format!("{} failed", progress.failed));
+        });
+    bar.finish_and_clear();
+
+    print_batch(&summary, summary.added.len(), policy, dry_run, json)?;
+    if summary.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(batch_failed(&summary, policy))
+    }
+}
+
+fn print_batch(
+    summary: &BatchSummary,
+    added: usize,
+    policy: Policy,
+    dry_run: Option<DryRun>,
+    json: bool,
+) -> Result<(), CommandError> {
+    let failed: Vec<(String, String)> = summary
+        .failed
+        .iter()
+        .map(|(cidr, e)| {
+            let reason = if let CouicError::ApiError { error, .. } = e {
+                describe_error(error)
+            } else {
+                e.to_string()
+            };
+            (cidr.to_string(), reason)
+        })
+        .collect();
+    if json {
+        let failed: Vec<_> = failed
+            .iter()
+            .map(|(cidr, reason)| serde_json::json!({"cidr": cidr, "error": reason}))
+            .collect();
+        let report = serde_json::json!({
+            "added": added,
+            "failed": failed,
+            "skipped": summary.skipped,
+            "dry_run": dry_run.is_some(),
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    if dry_run.is_some() {
+        println!("Dry run, nothing was added. {added} {policy} entries would be added");
+    } else {
+        println!("Added {added} {policy} entries");
+    }
+    if !failed.is_empty() {
+        println!("{} entries failed:", failed.len());
+        for (cidr, reason) in &failed {
+            println!("  {cidr}: {reason}");
+        }
+    }
+    if summary.skipped > 0 {
+        println!(
+            "{} entries skipped, the daemon stopped answering",
+            summary.skipped
+        );
+    }
+    Ok(())
+}
+
+/// Read the entries of a replace or a batch addition from a JSON file, or from the standard
+/// input for `-`
+fn read_entries_file(path: &Path) -> Result<RawEntries, CommandError> {
+    let content = if path.as_os_str() == STDIN_ENTRIES_FILE {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| CommandError::Generic(format!("Failed to read entries: {e}")))?;
+        content
+    } else {
+        fs::read_to_string(path).map_err(|e| {
+            CommandError::Generic(format!("Failed to read file {}: {}", path.display(), e))
+        })?
+    };
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn replace_entries(
+    client: &CouicClient,
+    policy: Policy,
+    from_file: &Path,
+    options: WriteOptions,
+    json: bool,
+) -> Result<(), CommandError> {
+    let entries = read_entries_file(from_file)?;
+    let summary = client.policy().replace(policy, &entries, options)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
     } else {
-        let mut table = Table::new();
-        table
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic)
-            .set_header(vec!["Policy", "CIDR", "Tag", "Expiration"]);
-
-        for entry in entries {
-            table.add_row(vec![
-                Cell::new(policy),
-                Cell::new(entry.cidr),
-                Cell::new(entry.tag.unwrap_or_else(|| "-".to_string())),
-                Cell::new(format_expiration(entry.expiration)),
-            ]);
+        print_replaced(&summary, policy);
+    }
+    if summary.failed.is_empty() {
+        Ok(())
+    } else {
+        Err(CommandError::Generic(format!(
+            "{} {policy} entries could not be replaced",
+            summary.failed.len()
+        )))
+    }
+}
+
+fn print_replaced(summary: &ReplaceSummary, policy: Policy) {
+    println!(
+        "Replaced {policy} entries: {} created, {} updated, {} removed, {} unchanged",
+        summary.created, summary.updated, summary.removed, summary.unchanged
+    );
+    if summary.skipped > 0 {
+        println!(
+            "{} entries left untouched, already loaded from sets",
+            summary.skipped
+        );
+    }
+    if !summary.failed.is_empty() {
+        println!("{} entries failed:", summary.failed.len());
+        let mut failed: Vec<_> = summary.failed.iter().collect();
+        failed.sort_by(|a, b| a.0.cmp(b.0));
+        for (cidr, detail) in failed {
+            println!("  {cidr}: {}", detail.message);
+        }
+    }
+}
+
+fn print_added(
+    mut entries: Vec<Entry>,
+    json: bool,
+    policy: &str,
+    dry_run: bool,
+) -> Result<(), CommandError> {
+    if dry_run && !json {
+        println!("Dry run, nothing was added. Entries that would be added:");
+    }
+    if entries.len() == 1
+        && let Some(entry) = entries.pop()
+    {
+        if json {
+            println!("{}", serde_json::to_string_pretty(&entry)?);
+        } else {
+            print_entry(entry, policy);
         }
-        println!("{table}");
+    } else if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        print_entries(entries, &TableOptions::default(), policy)?;
+    }
+    Ok(())
+}
+
+/// Source of an entry, `-` when the server does not record it
+fn format_source(source: Option<&EntrySource>) -> String {
+    source.map_or_else(|| "-".to_string(), ToString::to_string)
+}
+
+fn print_entries(
+    entries: Vec<Entry>,
+    options: &TableOptions,
+    policy: &str,
+) -> Result<(), CommandError> {
+    let rows = entries
+        .into_iter()
+        .map(|entry| {
+            vec![
+                policy.to_string(),
+                entry.cidr.to_string(),
+                entry.tag.unwrap_or_else(|| "-".to_string()),
+                format_expiration(entry.expiration),
+                format_source(entry.source.as_ref()),
+            ]
+        })
+        .collect();
+    print_table(&ENTRY_COLUMNS, rows, options)
+}
+
+/// `--quiet` is the same as `--no-headers --columns cidr`
+fn list_options(quiet: bool, table: &TableOptions) -> TableOptions {
+    if quiet {
+        TableOptions::quiet()
+    } else {
+        table.clone()
     }
 }
 
@@ -167,62 +591,366 @@ fn print_entry(entry: Entry, policy: &str) {
     table
         .load_preset(UTF8_FULL)
         .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(vec!["Policy", "CIDR", "Tag", "Expiration"]);
+        .set_header(vec!["Policy", "CIDR", "Tag", "Expiration", "Source"]);
 
     table.add_row(vec![
         Cell::new(policy),
         Cell::new(entry.cidr),
         Cell::new(entry.tag.unwrap_or_else(|| "-".to_string())),
         Cell::new(format_expiration(entry.expiration)),
+        Cell::new(format_source(entry.source.as_ref())),
     ]);
 
     println!("{table}");
 }
 
+/// Origin AS of an entry, looked up with `--enrich`
+enum Origin {
+    Announced(PrefixOrigin),
+    NotAnnounced,
+    Unknown,
+}
+
+impl Origin {
+    /// Look up the origin of `entry` from `RIPEstat`, a failed lookup only
+    /// leaving it unknown
+    fn lookup(entry: &Entry) -> Self {
+        let mut cache = OriginCache::load();
+        let origin = match cache.lookup(&entry.cidr.inner()) {
+            Ok(Some(origin)) => Self::Announced(origin),
+            Ok(None) => Self::NotAnnounced,
+            Err(e) => {
+                eprintln!(
+                    "Warning: failed to look up the origin of {}: {e}",
+                    entry.cidr
+                );
+                return Self::Unknown;
+            }
+        };
+        cache.save();
+        origin
+    }
+
+    fn columns(&self) -> [String; 2] {
+        match self {
+            Self::Announced(origin) => [format!("AS{}", origin.asn), origin.holder.clone()],
+            Self::NotAnnounced => ["-".to_string(), "not announced".to_string()],
+            Self::Unknown => ["unknown".to_string(), "unknown".to_string()],
+        }
+    }
+
+    fn into_prefix_origin(self) -> Option<PrefixOrigin> {
+        match self {
+            Self::Announced(origin) => Some(origin),
+            Self::NotAnnounced | Self::Unknown => None,
+        }
+    }
+}
+
+fn format_last_hit(last_hit: Option<u64>) -> String {
+    last_hit
+        .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+        .map_or_else(
+            || "never".to_string(),
+            |time| humantime::format_rfc3339_seconds(time).to_string(),
+        )
+}
+
+fn inspect_entry(
+    client: &CouicClient,
+    policy: Policy,
+    cidr: NormalizedCidr,
+    enrich: bool,
+    stats: bool,
+    json: bool,
+) -> Result<(), CommandError> {
+    let entry = client.policy().get(policy, &cidr.to_string())?;
+    let hits = if stats {
+        Some(client.policy().hits(policy, &cidr.to_string())?)
+    } else {
+        None
+    };
+    let origin = enrich.then(|| Origin::lookup(&entry));
+
+    if json {
+        let mut inspected = serde_json::to_value(&entry)?;
+        if let Some(object) = inspected.as_object_mut() {
+            if let Some(origin) = origin {
+                object.insert(
+                    "origin".to_string(),
+                    serde_json::to_value(origin.into_prefix_origin())?,
+                );
+            }
+            if let Some(hits) = hits {
+                object.insert("hits".to_string(), serde_json::to_value(hits)?);
+            }
+        }
+        println!("{}", serde_json::to_string_pretty(&inspected)?);
+        return Ok(());
+    }
+    if origin.is_none() && hits.is_none() {
+        print_entry(entry, &policy.to_string());
+        return Ok(());
+    }
+
+    let mut header = vec!["Policy", "CIDR", "Tag", "Expiration", "Source"];
+    let mut row = vec![
+        Cell::new(policy),
+        Cell::new(entry.cidr),
+        Cell::new(entry.tag.unwrap_or_else(|| "-".to_string())),
+        Cell::new(format_expiration(entry.expiration)),
+        Cell::new(format_source(entry.source.as_ref())),
+    ];
+    if let Some(origin) = origin {
+        let [asn, network] = origin.columns();
+        header.extend(["ASN", "Network"]);
+        row.extend([Cell::new(asn), Cell::new(network)]);
+    }
+    if let Some(hits) = hits {
+        header.extend(["Packets", "Bytes", "Last Hit"]);
+        row.extend([
+            Cell::new(hits.rx_packets),
+            Cell::new(hits.rx_bytes),
+            Cell::new(format_last_hit(hits.last_hit)),
+        ]);
+    }
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header);
+    table.add_row(row);
+    println!("{table}");
+    Ok(())
+}
+
+/// Adds a manual monitor entry to the drop list, then removes it from the
+/// monitor list so it is not counted twice
+fn promote_entry(
+    client: &CouicClient,
+    cidr: NormalizedCidr,
+    options: WriteOptions,
+) -> Result<Entry, CommandError> {
+    let entry = client.policy().get(Policy::Monitor, &cidr.to_string())?;
+    if entry.in_set() {
+        return Err(CommandError::Generic(format!(
+            "{cidr} is loaded from set `{}`, move the set file to the drop sets instead",
+            entry.tag.as_deref().unwrap_or_default()
+        )));
+    }
+    let tag = entry
+        .tag
+        .as_deref()
+        .filter(|tag| !tag.is_empty())
+        .map(Tag::try_from)
+        .transpose()
+        .map_err(|e| CommandError::Generic(e.to_string()))?;
+    let promoted = client.policy().add_with_options(
+        Policy::Drop,
+        &RawEntry {
+            cidr,
+            tag,
+            expiration: entry.expiration,
+            metadata: None,
+        },
+        options,
+    )?;
+    let options = WriteOptions {
+        force: false,
+        ..options
+    };
+    if let Err(e) = client
+        .policy()
+        .delete_with_options(Policy::Monitor, &cidr.to_string(), options)
+    {
+        // Undo the addition, so the entry is not left in both policies
+        let state = match client.policy().delete_with_options(
+            Policy::Drop,
+            &cidr.to_string(),
+            options,
+        ) {
+            Ok(()) => "the drop entry was removed again, the entry is only monitored".to_string(),
+            Err(undo) => format!(
+                "removing the drop entry back failed too ({undo}), the entry is now in both the drop and monitor policies"
+            ),
+        };
+        return Err(CommandError::Generic(format!(
+            "Failed to remove {cidr} from the monitor policy ({e}): {state}"
+        )));
+    }
+    Ok(promoted)
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about = "Show whether an address would be dropped, ignored or passed",
+    long_about = "Show whether packets from an address would be dropped, ignored or passed, with the longest matching entry of each policy. Ignore entries take precedence over drop entries"
+)]
+pub struct TestCommand {
+    #[arg(help = "Address to test, e.g., 192.0.2.1 or 2001:db8::1")]
+    ip: IpAddr,
+    #[arg(long)]
+    json: bool,
+}
+
+impl Command for TestCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        let classification = client.policy().classify(self.ip)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&classification)?);
+        } else {
+            print!("{classification}");
+        }
+        Ok(())
+    }
+}
+
 #[derive(Subcommand, Debug)]
 #[command(about = "Control drop policy")]
 pub enum DropSubCommand {
     #[command(about = "Add entry to drop list")]
     Add {
-        #[arg(help = "CIDR block to add to the drop list, e.g., 192.168.0.0/24")]
-        cidr: NormalizedCidr,
+        #[arg(
+            required_unless_present = "from_file",
+            help = "CIDR block or address range to add to the drop list, e.g., 192.168.0.0/24 or 10.0.0.5-10.0.0.20",
+            long_help = "CIDR block or inclusive address range to add to the drop list, e.g., 192.168.0.0/24 or 10.0.0.5-10.0.0.20. Ranges are converted into the minimal set of covering CIDRs"
+        )]
+        cidr: Option<CidrInput>,
+        #[arg(
+            long,
+            conflicts_with_all = ["cidr", "tag", "expiration", "kind", "detail"],
+            help = "JSON file of entries to add, or - for the standard input",
+            long_help = "JSON array of entries to add with a progress bar, as accepted by the add endpoint, e.g. [{\"cidr\": \"192.0.2.0/24\", \"tag\": \"abuse\", \"expiration\": 0}]. Entries refused by the daemon are reported at the end, without stopping the others. Use - to read the standard input"
+        )]
+        from_file: Option<PathBuf>,
         #[arg(
             short,
             long,
             default_value = "couicctl",
             help = "Tag for the entry, e.g., my_tag",
-            long_help = "Tag for the entry. Valid characters are a-zA-Z0-9-_ and max length is 64"
+            long_help = tag_long_help()
         )]
         tag: Option<Tag>,
         #[arg(
             short = 'e',
             long,
             default_value = "0",
-            help = "Expiration time in minutes",
-            long_help = "Expiration time in minutes. The default value is zero, which means the entry never expires; otherwise, the expiration is set in minutes in the future."
+            value_parser = parse_expiration,
+            help = "Expiration delay (e.g., 30m, 1h, 1d)",
+            long_help = "Expiration delay from now, with a unit (e.g., 30m, 1h, 1d). The default value is zero, which means the entry never expires."
         )]
         expiration: String,
+        #[arg(
+            long,
+            help = "Kind of the entry, e.g., abuse",
+            long_help = "Kind of the entry, forwarded as metadata to reports. Defaults to 'manual' when --detail is set"
+        )]
+        kind: Option<String>,
+        #[arg(
+            long,
+            help = "Reason for the entry, e.g., 'ticket #42'",
+            long_help = "Reason for the entry, forwarded as metadata to reports"
+        )]
+        detail: Option<String>,
+        #[arg(
+            long,
+            help = "Allow an entry overlapping a protected range or too broad",
+            long_help = "Allow an entry overlapping a range protected by the daemon configuration (loopback and link-local by default), or broader than its minimum prefix length (/8 for IPv4 and /32 for IPv6 by default)"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "server",
+            value_name = "CHECK",
+            help = "Validate the entries and print them without adding them",
+            long_help = "Validate the entries and print them without adding anything. With 'server', the default, the daemon also checks protected ranges, duplicates and capacity; with 'local', entries are only validated by couicctl"
+        )]
+        dry_run: Option<DryRun>,
         #[arg(long)]
         json: bool,
     },
     #[command(about = "Remove entry from drop list")]
-    Delete { cidr: NormalizedCidr },
+    Delete {
+        cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+    },
+    #[command(
+        about = "Replace the manual entries of the drop list",
+        long_about = "Replace the manual entries of the drop list with the entries of a file, in one request. Entries missing from the file are removed, entries loaded from sets are left untouched"
+    )]
+    Replace {
+        #[arg(
+            long,
+            help = "JSON file of entries, or - for the standard input",
+            long_help = "JSON array of entries, as accepted by the add endpoint, e.g. [{\"cidr\": \"192.0.2.0/24\", \"tag\": \"abuse\", \"expiration\": 0}]. Use - to read the standard input"
+        )]
+        from_file: PathBuf,
+        #[arg(
+            long,
+            help = "Allow entries overlapping a protected range or too broad",
+            long_help = "Allow entries overlapping a range protected by the daemon configuration (loopback and link-local by default), or broader than its minimum prefix length (/8 for IPv4 and /32 for IPv6 by default)"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(long)]
+        json: bool,
+    },
     #[command(about = "List entries in drop list")]
     List {
-        #[arg(short, long)]
+        #[arg(
+            short,
+            long,
+            conflicts_with = "columns",
+            help = "Print CIDRs only, one per line"
+        )]
         quiet: bool,
+        #[command(flatten)]
+        table: TableOptions,
         #[arg(
             short = 't',
             long = "tags",
-            help = "Filter entries by tags. Supports wildcards (*). Multiple tags can be specified, separated by commas. Quote wildcards to prevent shell expansion (e.g., -t '*')."
+            help = "Filter entries by tags. Supports wildcards (*) and namespaces (e.g., team/web/*). Multiple tags can be specified, separated by commas. Quote wildcards to prevent shell expansion (e.g., -t '*')."
         )]
         tags: Option<String>,
-        #[arg(long, conflicts_with = "quiet")]
+        #[command(flatten)]
+        expiry: ExpiryFilter,
+        #[arg(long, conflicts_with_all = ["quiet", "no_headers", "columns"])]
         json: bool,
     },
     #[command(about = "Inspect entry in drop list")]
     Inspect {
         cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Show the origin AS and network name of the entry",
+            long_help = "Show the origin AS and network name of the entry, looked up from RIPE NCC RIPEstat and cached for a day"
+        )]
+        enrich: bool,
+        #[arg(
+            long,
+            help = "Show the packets matched by the entry",
+            long_help = "Show the packets and bytes matched by the entry since it was created, and when it was last hit"
+        )]
+        stats: bool,
         #[arg(long)]
         json: bool,
     },
@@ -233,41 +961,84 @@ impl PolicyAction for DropSubCommand {
         match self {
             Self::Add {
                 cidr,
+                from_file,
                 tag,
                 expiration,
+                kind,
+                detail,
+                force,
+                no_propagate,
+                dry_run,
                 json,
             } => {
-                let exp = calculate_expiration(expiration)?;
-                let entry = RawEntry {
-                    cidr: *cidr,
-                    tag: tag.clone(),
-                    expiration: Expiration::from_timestamp(exp),
-                    metadata: None,
+                let entries = entries_to_add(
+                    cidr.as_ref(),
+                    from_file.as_deref(),
+                    tag.as_ref(),
+                    expiration,
+                    kind.as_deref(),
+                    detail.as_deref(),
+                )?;
+                warn_local_only(&entries);
+                let options = WriteOptions {
+                    force: *force,
+                    propagate: !*no_propagate,
+                    ..WriteOptions::default()
                 };
-                let entry = client.policy().add(Policy::Drop, &entry)?;
-                if *json {
-                    println!("{}", serde_json::to_string_pretty(&entry)?);
+                if from_file.is_some() {
+                    add_batch(client, Policy::Drop, &entries, options, *dry_run, *json)?;
                 } else {
-                    print_entry(entry, "drop");
+                    let added =
+                        add_entries(client, Policy::Drop, &entries, options, *dry_run, *json)?;
+                    print_added(added, *json, "drop", dry_run.is_some())?;
                 }
             }
-            Self::Delete { cidr } => {
-                client.policy().delete(Policy::Drop, &cidr.to_string())?;
+            Self::Delete { cidr, no_propagate } => {
+                client.policy().delete_with_options(
+                    Policy::Drop,
+                    &cidr.to_string(),
+                    WriteOptions {
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                )?;
             }
-            Self::Inspect { cidr, json } => {
-                let entry = client.policy().get(Policy::Drop, &cidr.to_string())?;
-                if *json {
-                    println!("{}", serde_json::to_string_pretty(&entry)?);
-                } else {
-                    print_entry(entry, "drop");
-                }
+            Self::Replace {
+                from_file,
+                force,
+                no_propagate,
+                json,
+            } => {
+                replace_entries(
+                    client,
+                    Policy::Drop,
+                    from_file,
+                    WriteOptions {
+                        force: *force,
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                    *json,
+                )?;
             }
-            Self::List { quiet, tags, json } => {
-                let entries = filter_entries(client.policy().list(Policy::Drop)?, tags.as_deref());
+            Self::Inspect {
+                cidr,
+                enrich,
+                stats,
+                json,
+            } => inspect_entry(client, Policy::Drop, *cidr, *enrich, *stats, *json)?,
+            Self::List {
+                quiet,
+                table,
+                tags,
+                expiry,
+                json,
+            } => {
+                let entries = list_entries(client, Policy::Drop, tags.as_deref(), expiry)?;
                 if *json {
                     println!("{}", serde_json::to_string_pretty(&entries)?);
                 } else {
-                    print_entries(entries, *quiet, "drop");
+                    print_entries(entries, &list_options(*quiet, table), "drop")?;
                 }
             }
         }
@@ -280,44 +1051,146 @@ impl PolicyAction for DropSubCommand {
 pub enum IgnoreSubCommand {
     #[command(about = "Add entry to ignore list")]
     Add {
-        #[arg(help = "CIDR block to add to the ignore list, e.g., 192.168.0.0/24")]
-        cidr: NormalizedCidr,
+        #[arg(
+            required_unless_present = "from_file",
+            help = "CIDR block or address range to add to the ignore list, e.g., 192.168.0.0/24 or 10.0.0.5-10.0.0.20",
+            long_help = "CIDR block or inclusive address range to add to the ignore list, e.g., 192.168.0.0/24 or 10.0.0.5-10.0.0.20. Ranges are converted into the minimal set of covering CIDRs"
+        )]
+        cidr: Option<CidrInput>,
+        #[arg(
+            long,
+            conflicts_with_all = ["cidr", "tag", "expiration", "kind", "detail"],
+            help = "JSON file of entries to add, or - for the standard input",
+            long_help = "JSON array of entries to add with a progress bar, as accepted by the add endpoint, e.g. [{\"cidr\": \"192.0.2.0/24\", \"tag\": \"abuse\", \"expiration\": 0}]. Entries refused by the daemon are reported at the end, without stopping the others. Use - to read the standard input"
+        )]
+        from_file: Option<PathBuf>,
         #[arg(
             short,
             long,
             help = "Tag for the entry, e.g., my_tag",
-            long_help = "Tag for the entry. Valid characters are a-zA-Z0-9-_ and max length is 64"
+            long_help = tag_long_help()
         )]
         tag: Option<Tag>,
         #[arg(
             short = 'e',
             long,
             default_value = "0",
-            help = "Expiration time in minutes",
-            long_help = "Expiration time in minutes. The default value is zero, which means the entry never expires; otherwise, the expiration is set in minutes in the future."
+            value_parser = parse_expiration,
+            help = "Expiration delay (e.g., 30m, 1h, 1d)",
+            long_help = "Expiration delay from now, with a unit (e.g., 30m, 1h, 1d). The default value is zero, which means the entry never expires."
         )]
         expiration: String,
+        #[arg(
+            long,
+            help = "Kind of the entry, e.g., abuse",
+            long_help = "Kind of the entry, forwarded as metadata to reports. Defaults to 'manual' when --detail is set"
+        )]
+        kind: Option<String>,
+        #[arg(
+            long,
+            help = "Reason for the entry, e.g., 'ticket #42'",
+            long_help = "Reason for the entry, forwarded as metadata to reports"
+        )]
+        detail: Option<String>,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "server",
+            value_name = "CHECK",
+            help = "Validate the entries and print them without adding them",
+            long_help = "Validate the entries and print them without adding anything. With 'server', the default, the daemon also checks protected ranges, duplicates and capacity; with 'local', entries are only validated by couicctl"
+        )]
+        dry_run: Option<DryRun>,
         #[arg(long)]
         json: bool,
     },
     #[command(about = "Remove entry from ignore list")]
-    Delete { cidr: NormalizedCidr },
+    Delete {
+        cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Remove an entry kept for a management range during lockdown",
+            long_help = "Remove an ignore entry covering a range of firewall.management_cidrs while lockdown is enabled, which may cut off that range"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+    },
+    #[command(
+        about = "Replace the manual entries of the ignore list",
+        long_about = "Replace the manual entries of the ignore list with the entries of a file, in one request. Entries missing from the file are removed, entries loaded from sets are left untouched"
+    )]
+    Replace {
+        #[arg(
+            long,
+            help = "JSON file of entries, or - for the standard input",
+            long_help = "JSON array of entries, as accepted by the add endpoint, e.g. [{\"cidr\": \"192.0.2.0/24\", \"tag\": \"abuse\", \"expiration\": 0}]. Use - to read the standard input"
+        )]
+        from_file: PathBuf,
+        #[arg(
+            long,
+            help = "Remove entries kept for management ranges during lockdown",
+            long_help = "Remove ignore entries covering a range of firewall.management_cidrs while lockdown is enabled, which may cut off that range"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(long)]
+        json: bool,
+    },
     #[command(about = "List entries in ignore list")]
     List {
-        #[arg(short, long)]
+        #[arg(
+            short,
+            long,
+            conflicts_with = "columns",
+            help = "Print CIDRs only, one per line"
+        )]
         quiet: bool,
+        #[command(flatten)]
+        table: TableOptions,
         #[arg(
             short = 't',
             long = "tags",
-            help = "Filter entries by tags. Supports wildcards (*). Multiple tags can be specified, separated by commas. Quote wildcards to prevent shell expansion (e.g., -t '*')."
+            help = "Filter entries by tags. Supports wildcards (*) and namespaces (e.g., team/web/*). Multiple tags can be specified, separated by commas. Quote wildcards to prevent shell expansion (e.g., -t '*')."
         )]
         tags: Option<String>,
-        #[arg(long, conflicts_with = "quiet")]
+        #[command(flatten)]
+        expiry: ExpiryFilter,
+        #[arg(long, conflicts_with_all = ["quiet", "no_headers", "columns"])]
         json: bool,
     },
     #[command(about = "Inspect entry in ignore list")]
     Inspect {
         cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Show the origin AS and network name of the entry",
+            long_help = "Show the origin AS and network name of the entry, looked up from RIPE NCC RIPEstat and cached for a day"
+        )]
+        enrich: bool,
+        #[arg(
+            long,
+            help = "Show the packets matched by the entry",
+            long_help = "Show the packets and bytes matched by the entry since it was created, and when it was last hit"
+        )]
+        stats: bool,
         #[arg(long)]
         json: bool,
     },
@@ -328,42 +1201,347 @@ impl PolicyAction for IgnoreSubCommand {
         match self {
             Self::Add {
                 cidr,
+                from_file,
                 tag,
                 expiration,
+                kind,
+                detail,
+                no_propagate,
+                dry_run,
                 json,
             } => {
-                let exp = calculate_expiration(expiration)?;
-                let entry = RawEntry {
-                    cidr: *cidr,
-                    tag: tag.clone(),
-                    expiration: Expiration::from_timestamp(exp),
-                    metadata: None,
+                let entries = entries_to_add(
+                    cidr.as_ref(),
+                    from_file.as_deref(),
+                    tag.as_ref(),
+                    expiration,
+                    kind.as_deref(),
+                    detail.as_deref(),
+                )?;
+                let options = WriteOptions {
+                    propagate: !*no_propagate,
+                    ..WriteOptions::default()
                 };
-                let entry = client.policy().add(Policy::Ignore, &entry)?;
-                if *json {
-                    println!("{}", serde_json::to_string_pretty(&entry)?);
+                if from_file.is_some() {
+                    add_batch(client, Policy::Ignore, &entries, options, *dry_run, *json)?;
                 } else {
-                    print_entry(entry, "ignore");
+                    let added =
+                        add_entries(client, Policy::Ignore, &entries, options, *dry_run, *json)?;
+                    print_added(added, *json, "ignore", dry_run.is_some())?;
                 }
             }
-            Self::Delete { cidr } => {
-                client.policy().delete(Policy::Ignore, &cidr.to_string())?;
+            Self::Delete {
+                cidr,
+                force,
+                no_propagate,
+            } => {
+                client.policy().delete_with_options(
+                    Policy::Ignore,
+                    &cidr.to_string(),
+                    WriteOptions {
+                        force: *force,
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                )?;
+            }
+            Self::Replace {
+                from_file,
+                force,
+                no_propagate,
+                json,
+            } => {
+                replace_entries(
+                    client,
+                    Policy::Ignore,
+                    from_file,
+                    WriteOptions {
+                        force: *force,
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                    *json,
+                )?;
             }
-            Self::Inspect { cidr, json } => {
-                let entry = client.policy().get(Policy::Ignore, &cidr.to_string())?;
+            Self::Inspect {
+                cidr,
+                enrich,
+                stats,
+                json,
+            } => inspect_entry(client, Policy::Ignore, *cidr, *enrich, *stats, *json)?,
+            Self::List {
+                quiet,
+                table,
+                tags,
+                expiry,
+                json,
+            } => {
+                let entries = list_entries(client, Policy::Ignore, tags.as_deref(), expiry)?;
                 if *json {
-                    println!("{}", serde_json::to_string_pretty(&entry)?);
+                    println!("{}", serde_json::to_string_pretty(&entries)?);
+                } else {
+                    print_entries(entries, &list_options(*quiet, table), "ignore")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+#[command(about = "Control monitor policy, counting would-be drops without dropping")]
+pub enum MonitorSubCommand {
+    #[command(about = "Add entry to monitor list")]
+    Add {
+        #[arg(
+            required_unless_present = "from_file",
+            help = "CIDR block or address range to add to the monitor list, e.g., 192.168.0.0/24 or 10.0.0.5-10.0.0.20",
+            long_help = "CIDR block or inclusive address range to add to the monitor list, e.g., 192.168.0.0/24 or 10.0.0.5-10.0.0.20. Ranges are converted into the minimal set of covering CIDRs"
+        )]
+        cidr: Option<CidrInput>,
+        #[arg(
+            long,
+            conflicts_with_all = ["cidr", "tag", "expiration", "kind", "detail"],
+            help = "JSON file of entries to add, or - for the standard input",
+            long_help = "JSON array of entries to add with a progress bar, as accepted by the add endpoint, e.g. [{\"cidr\": \"192.0.2.0/24\", \"tag\": \"abuse\", \"expiration\": 0}]. Entries refused by the daemon are reported at the end, without stopping the others. Use - to read the standard input"
+        )]
+        from_file: Option<PathBuf>,
+        #[arg(
+            short,
+            long,
+            help = "Tag for the entry, e.g., my_tag",
+            long_help = tag_long_help()
+        )]
+        tag: Option<Tag>,
+        #[arg(
+            short = 'e',
+            long,
+            default_value = "0",
+            value_parser = parse_expiration,
+            help = "Expiration delay (e.g., 30m, 1h, 1d)",
+            long_help = "Expiration delay from now, with a unit (e.g., 30m, 1h, 1d). The default value is zero, which means the entry never expires."
+        )]
+        expiration: String,
+        #[arg(
+            long,
+            help = "Kind of the entry, e.g., abuse",
+            long_help = "Kind of the entry, forwarded as metadata to reports. Defaults to 'manual' when --detail is set"
+        )]
+        kind: Option<String>,
+        #[arg(
+            long,
+            help = "Reason for the entry, e.g., 'ticket #42'",
+            long_help = "Reason for the entry, forwarded as metadata to reports"
+        )]
+        detail: Option<String>,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "server",
+            value_name = "CHECK",
+            help = "Validate the entries and print them without adding them",
+            long_help = "Validate the entries and print them without adding anything. With 'server', the default, the daemon also checks protected ranges, duplicates and capacity; with 'local', entries are only validated by couicctl"
+        )]
+        dry_run: Option<DryRun>,
+        #[arg(long)]
+        json: bool,
+    },
+    #[command(about = "Remove entry from monitor list")]
+    Delete {
+        cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+    },
+    #[command(
+        about = "Replace the manual entries of the monitor list",
+        long_about = "Replace the manual entries of the monitor list with the entries of a file, in one request. Entries missing from the file are removed, entries loaded from sets are left untouched"
+    )]
+    Replace {
+        #[arg(
+            long,
+            help = "JSON file of entries, or - for the standard input",
+            long_help = "JSON array of entries, as accepted by the add endpoint, e.g. [{\"cidr\": \"192.0.2.0/24\", \"tag\": \"abuse\", \"expiration\": 0}]. Use - to read the standard input"
+        )]
+        from_file: PathBuf,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    #[command(about = "List entries in monitor list")]
+    List {
+        #[arg(
+            short,
+            long,
+            conflicts_with = "columns",
+            help = "Print CIDRs only, one per line"
+        )]
+        quiet: bool,
+        #[command(flatten)]
+        table: TableOptions,
+        #[arg(
+            short = 't',
+            long = "tags",
+            help = "Filter entries by tags. Supports wildcards (*) and namespaces (e.g., team/web/*). Multiple tags can be specified, separated by commas. Quote wildcards to prevent shell expansion (e.g., -t '*')."
+        )]
+        tags: Option<String>,
+        #[command(flatten)]
+        expiry: ExpiryFilter,
+        #[arg(long, conflicts_with_all = ["quiet", "no_headers", "columns"])]
+        json: bool,
+    },
+    #[command(
+        about = "Move an entry from the monitor list to the drop list",
+        long_about = "Move an entry from the monitor list to the drop list, keeping its tag and expiration. Entries loaded from sets are promoted by moving their set file instead"
+    )]
+    Promote {
+        cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Allow an entry overlapping a protected range or too broad",
+            long_help = "Allow an entry overlapping a range protected by the daemon configuration (loopback and link-local by default), or broader than its minimum prefix length (/8 for IPv4 and /32 for IPv6 by default)"
+        )]
+        force: bool,
+        #[arg(
+            long,
+            help = "Do not forward the change to peers and reporting",
+            long_help = "Do not forward the change to peers and reporting, e.g. when they are managed out-of-band"
+        )]
+        no_propagate: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    #[command(about = "Inspect entry in monitor list")]
+    Inspect {
+        cidr: NormalizedCidr,
+        #[arg(
+            long,
+            help = "Show the origin AS and network name of the entry",
+            long_help = "Show the origin AS and network name of the entry, looked up from RIPE NCC RIPEstat and cached for a day"
+        )]
+        enrich: bool,
+        #[arg(
+            long,
+            help = "Show the packets matched by the entry",
+            long_help = "Show the packets and bytes matched by the entry since it was created, and when it was last hit"
+        )]
+        stats: bool,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+impl PolicyAction for MonitorSubCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        match self {
+            Self::Add {
+                cidr,
+                from_file,
+                tag,
+                expiration,
+                kind,
+                detail,
+                no_propagate,
+                dry_run,
+                json,
+            } => {
+                let entries = entries_to_add(
+                    cidr.as_ref(),
+                    from_file.as_deref(),
+                    tag.as_ref(),
+                    expiration,
+                    kind.as_deref(),
+                    detail.as_deref(),
+                )?;
+                let options = WriteOptions {
+                    propagate: !*no_propagate,
+                    ..WriteOptions::default()
+                };
+                if from_file.is_some() {
+                    add_batch(client, Policy::Monitor, &entries, options, *dry_run, *json)?;
                 } else {
-                    print_entry(entry, "ignore");
+                    let added =
+                        add_entries(client, Policy::Monitor, &entries, options, *dry_run, *json)?;
+                    print_added(added, *json, "monitor", dry_run.is_some())?;
                 }
             }
-            Self::List { quiet, tags, json } => {
-                let entries =
-                    filter_entries(client.policy().list(Policy::Ignore)?, tags.as_deref());
+            Self::Delete { cidr, no_propagate } => {
+                client.policy().delete_with_options(
+                    Policy::Monitor,
+                    &cidr.to_string(),
+                    WriteOptions {
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                )?;
+            }
+            Self::Replace {
+                from_file,
+                no_propagate,
+                json,
+            } => {
+                replace_entries(
+                    client,
+                    Policy::Monitor,
+                    from_file,
+                    WriteOptions {
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                    *json,
+                )?;
+            }
+            Self::Promote {
+                cidr,
+                force,
+                no_propagate,
+                json,
+            } => {
+                let entry = promote_entry(
+                    client,
+                    *cidr,
+                    WriteOptions {
+                        force: *force,
+                        propagate: !*no_propagate,
+                        ..WriteOptions::default()
+                    },
+                )?;
+                print_added(vec![entry], *json, "drop", false)?;
+            }
+            Self::Inspect {
+                cidr,
+                enrich,
+                stats,
+                json,
+            } => inspect_entry(client, Policy::Monitor, *cidr, *enrich, *stats, *json)?,
+            Self::List {
+                quiet,
+                table,
+                tags,
+                expiry,
+                json,
+            } => {
+                let entries = list_entries(client, Policy::Monitor, tags.as_deref(), expiry)?;
                 if *json {
                     println!("{}", serde_json::to_string_pretty(&entries)?);
                 } else {
-                    print_entries(entries, *quiet, "ignore");
+                    print_entries(entries, &list_options(*quiet, table), "monitor")?;
                 }
             }
         }
@@ -372,8 +1550,11 @@ impl PolicyAction for IgnoreSubCommand {
 }
 
 #[cfg(test)]
+#[allow(clippy::unwrap_used)]
 mod tests {
     use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
 
     #[test]
     fn test_matches_tag_exact_match() {
@@ -491,4 +1672,46 @@ mod tests {
         assert!(!matches_tag("fail2ban-sshd", &[]));
         assert!(!matches_tag("anything", &[]));
     }
+
+    #[test]
+    fn test_build_metadata() {
+        assert!(build_metadata(None, None).unwrap().is_none());
+
+        let metadata = build_metadata(None, Some("ticket #42")).unwrap().unwrap();
+        assert_eq!(metadata.kind, "manual");
+        assert_eq!(metadata.detail, "ticket #42");
+
+        let metadata = build_metadata(Some("abuse"), None).unwrap().unwrap();
+        assert_eq!(metadata.kind, "abuse");
+        assert_eq!(metadata.detail, "");
+
+        let long = "a".repeat(common::MAX_METADATA_KIND_LENGTH + 1);
+        assert!(build_metadata(Some(&long), None).is_err());
+    }
+
+    #[test]
+    fn test_matches_tag_namespace() {
+        let patterns = vec!["team/web/*".to_string()];
+        assert!(matches_tag("team/web/fail2ban", &patterns));
+        assert!(matches_tag("team/web", &patterns));
+        assert!(!matches_tag("team/website", &patterns));
+        assert!(!matches_tag("team/db/fail2ban", &patterns));
+    }
+
+    #[test]
+    fn test_documented_expirations_parse() {
+        for args in [
+            ["couicctl", "ignore", "add", "192.0.2.10/32", "-e", "1h"],
+            ["couicctl", "drop", "add", "1.1.1.1/32", "-e", "1m"],
+            ["couicctl", "drop", "add", "1.2.3.4/32", "-e", "30s"],
+            ["couicctl", "monitor", "add", "198.51.100.0/24", "-e", "0"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_ok(), "{args:?}");
+        }
+        // A bare number has no unit
+        assert!(
+            Cli::try_parse_from(["couicctl", "ignore", "add", "192.0.2.10/32", "-e", "60"])
+                .is_err()
+        );
+    }
 }
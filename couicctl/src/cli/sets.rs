@@ -1,15 +1,23 @@
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 
 use clap::{Args, Subcommand};
 use ipnet::IpNet;
+use serde::Serialize;
 
 use client::CouicClient;
-use common::{Policy, Set, SetName};
+use common::signature::signature_path;
+use common::{MapsUsage, Policy, Set, SetHeader, SetName, SetPatch, SetsReload, render_set_file};
 
 use super::{Command, CommandError};
+use crate::config::{Config, Overrides};
+use crate::import::{self, ImportFormat, Validation};
 use crate::ripe;
+use crate::sign::SetSigner;
+
+const STDOUT_SET_FILE: &str = "-";
 
 #[derive(Args, Debug)]
 pub struct SetsCommand {
@@ -22,19 +30,24 @@ pub struct SetsCommand {
 enum SetsSubCommand {
     #[command(about = "List sets for a policy")]
     List {
-        #[arg(help = "Policy (drop or ignore)")]
+        #[arg(help = "Policy (drop, ignore or monitor)")]
+        policy: Policy,
+    },
+    #[command(about = "Count the entries of all the sets of a policy, overlaps once")]
+    Summary {
+        #[arg(help = "Policy (drop, ignore or monitor)")]
         policy: Policy,
     },
     #[command(about = "Inspect a specific set")]
     Inspect {
-        #[arg(help = "Policy (drop or ignore)")]
+        #[arg(help = "Policy (drop, ignore or monitor)")]
         policy: Policy,
         #[arg(help = "Set name")]
         name: SetName,
     },
     #[command(about = "Create a new set")]
     Create {
-        #[arg(help = "Policy (drop or ignore)")]
+        #[arg(help = "Policy (drop, ignore or monitor)")]
         policy: Policy,
         #[arg(help = "Set name")]
         name: SetName,
@@ -55,47 +68,233 @@ enum SetsSubCommand {
         #[arg(
             long,
             help = "Import CIDRs from file (one per line, # for comments)",
+            long_help = "Import CIDRs from file (one per line, # for comments). Plain addresses, `ipset save` output and fail2ban iptables rules, status or log lines are also understood, see --format. The description and source of a set file header, as written by 'sets export', are kept unless given as options",
             conflicts_with_all = ["entries", "from_asn"],
             required_unless_present_any = ["entries", "from_asn"]
         )]
         from_file: Option<PathBuf>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            requires = "from_file",
+            help = "Layout of the imported file"
+        )]
+        format: ImportFormat,
+        #[arg(long, help = "Description written in the set file header")]
+        description: Option<String>,
+        #[arg(
+            long,
+            help = "Origin of the entries written in the set file header, e.g. a feed URL"
+        )]
+        source: Option<String>,
     },
     #[command(about = "Update a set (replaces all entries)")]
     Update {
-        #[arg(help = "Policy (drop or ignore)")]
+        #[arg(help = "Policy (drop, ignore or monitor)")]
         policy: Policy,
         #[arg(help = "Set name")]
         name: SetName,
         #[arg(help = "CIDR entries", num_args = 1..)]
         entries: Vec<IpNet>,
+        #[arg(
+            long,
+            value_name = "ETAG",
+            help = "Only update the set if it still has this ETag, as shown by 'sets inspect'",
+            long_help = "Only update the set if it still has this ETag, as shown by 'sets inspect'. The update is refused if the set was modified since, so concurrent changes are not overwritten. Without it, the ETag of the set is fetched right before the update"
+        )]
+        if_match: Option<String>,
+        #[arg(
+            long,
+            help = "Replace the description of the set header, kept when omitted, an empty value clearing it"
+        )]
+        description: Option<String>,
+        #[arg(
+            long,
+            help = "Replace the source of the set header, kept when omitted, an empty value clearing it"
+        )]
+        source: Option<String>,
+    },
+    #[command(about = "Add entries to a set, keeping the others")]
+    AddEntry {
+        #[arg(help = "Policy (drop, ignore or monitor)")]
+        policy: Policy,
+        #[arg(help = "Set name")]
+        name: SetName,
+        #[arg(help = "CIDR entries", num_args = 1.., required = true)]
+        entries: Vec<IpNet>,
+    },
+    #[command(about = "Remove entries from a set, keeping the others")]
+    RemoveEntry {
+        #[arg(help = "Policy (drop, ignore or monitor)")]
+        policy: Policy,
+        #[arg(help = "Set name")]
+        name: SetName,
+        #[arg(help = "CIDR entries", num_args = 1.., required = true)]
+        entries: Vec<IpNet>,
+    },
+    #[command(about = "Export a set in the set file format, to back it up or edit it")]
+    Export {
+        #[arg(help = "Policy (drop, ignore or monitor)")]
+        policy: Policy,
+        #[arg(help = "Set name")]
+        name: SetName,
+        #[arg(
+            long,
+            short,
+            value_name = "FILE",
+            help = "File to write, the standard output when omitted or `-`",
+            long_help = "File to write, the standard output when omitted or `-`. It can be imported again with 'sets create --from-file'"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Only write the entries, without the description and source header"
+        )]
+        no_header: bool,
     },
     #[command(about = "Delete a set")]
     Delete {
-        #[arg(help = "Policy (drop or ignore)")]
+        #[arg(help = "Policy (drop, ignore or monitor)")]
         policy: Policy,
         #[arg(help = "Set name")]
         name: SetName,
     },
-    #[command(about = "Reload sets into eBPF maps")]
-    Reload,
+    #[command(
+        about = "Disable a set, keeping its file",
+        long_about = "Disable a set: its entries are removed on the next reload while its file is kept, so it can be enabled back later"
+    )]
+    Disable {
+        #[arg(help = "Policy (drop, ignore or monitor)")]
+        policy: Policy,
+        #[arg(help = "Set name")]
+        name: SetName,
+    },
+    #[command(about = "Enable a disabled set, its entries being loaded on the next reload")]
+    Enable {
+        #[arg(help = "Policy (drop, ignore or monitor)")]
+        policy: Policy,
+        #[arg(help = "Set name")]
+        name: SetName,
+    },
+    #[command(about = "Reload sets into eBPF maps, or a single set")]
+    Reload {
+        #[arg(
+            help = "Policy of the set to reload, all sets being reloaded if omitted",
+            requires = "name"
+        )]
+        policy: Option<Policy>,
+        #[arg(help = "Name of the set to reload")]
+        name: Option<SetName>,
+        #[arg(
+            long,
+            help = "Allow drop sets overlapping a protected range or too broad, and cutting off management ranges during lockdown",
+            long_help = "Allow drop sets overlapping a range protected by the daemon configuration (loopback and link-local by default), or broader than its minimum prefix length (/8 for IPv4 and /32 for IPv6 by default). During lockdown, also allow removing ignore set entries covering a range of firewall.management_cidrs, which may cut off that range"
+        )]
+        force: bool,
+        #[arg(long, help = "Print the entries changed per policy as JSON")]
+        json: bool,
+    },
+    #[command(about = "Show the last reload and whether set files differ from the loaded sets")]
+    Status,
+    #[command(about = "Sign set files with an Ed25519 private key")]
+    Sign {
+        #[arg(
+            long,
+            help = "Ed25519 private key (PKCS#8 PEM)",
+            long_help = "Ed25519 private key (PKCS#8 PEM), e.g. generated with `openssl genpkey -algorithm ed25519`. The daemon checks the signatures against the matching public key set as `sets.public_key`"
+        )]
+        key: PathBuf,
+        #[arg(
+            help = "Set files to sign, each signature being written next to it with a .sig extension",
+            num_args = 1..,
+            required = true
+        )]
+        files: Vec<PathBuf>,
+    },
+    #[command(
+        about = "Check a file before creating a set from it",
+        long_about = "Check a file before creating a set from it with 'sets create --from-file': report the lines that cannot be read, the networks listed twice and those within a broader one, and count the IPv4 and IPv6 networks. With --policy, also estimate whether the entries fit in the maps of the policy when the daemon can be reached. Fails if a line cannot be read"
+    )]
+    Validate {
+        #[arg(help = "File to check")]
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t, help = "Layout of the file")]
+        format: ImportFormat,
+        #[arg(
+            long,
+            help = "Policy of the set, to check that its entries fit in the maps of the daemon"
+        )]
+        policy: Option<Policy>,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+impl SetsCommand {
+    /// Whether the command may connect to the daemon, signing never doing so
+    /// and validating only to check the capacity of a policy
+    pub const fn connects(&self) -> bool {
+        !matches!(
+            &self.command,
+            SetsSubCommand::Sign { .. } | SetsSubCommand::Validate { policy: None, .. }
+        )
+    }
+
+    /// Run the commands working on local files only, before the configuration
+    /// is loaded, returning `None` for the others. Validating a file only
+    /// connects to the daemon to check its capacity, which may fail.
+    pub fn run_local(&self, path: &str, overrides: &Overrides) -> Option<Result<(), CommandError>> {
+        if let SetsSubCommand::Sign { key, files } = &self.command {
+            return Some(sign_sets(key, files));
+        }
+        let SetsSubCommand::Validate { policy, .. } = &self.command else {
+            return None;
+        };
+        let client = policy.map(|_| {
+            Config::load(path, overrides)
+                .map_err(CommandError::from)
+                .and_then(|config| Ok(config.build_client(overrides.token)?))
+        });
+        let client = match client {
+            Some(Ok(client)) => Some(client),
+            Some(Err(e)) => {
+                eprintln!("Capacity not checked, daemon unavailable: {e}");
+                None
+            }
+            None => None,
+        };
+        Some(self.validate(client.as_ref()))
+    }
+
+    /// Runs `sets validate`, doing nothing for the other commands
+    fn validate(&self, client: Option<&CouicClient>) -> Result<(), CommandError> {
+        if let SetsSubCommand::Validate {
+            file,
+            format,
+            policy,
+            json,
+        } = &self.command
+        {
+            validate_file(client, file, *format, *policy, *json)?;
+        }
+        Ok(())
+    }
 }
 
 impl Command for SetsCommand {
     fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
         match &self.command {
-            SetsSubCommand::List { policy } => {
-                let sets = client.sets().list(*policy)?;
-                if sets.is_empty() {
-                    println!("No sets found for policy '{policy}'");
-                } else {
-                    for set in sets {
-                        println!("{set}");
-                    }
-                }
+            SetsSubCommand::List { policy } => list_sets(client, *policy)?,
+            SetsSubCommand::Summary { policy } => {
+                print!("{}", client.sets().summary(*policy)?);
             }
             SetsSubCommand::Inspect { policy, name } => {
-                let set = client.sets().get(*policy, name)?;
-                println!("{set}");
+                let (set, etag) = client.sets().get_with_etag(*policy, name)?;
+                print!("{set}");
+                if let Some(etag) = etag {
+                    println!("ETag: {etag}");
+                }
             }
             SetsSubCommand::Create {
                 policy,
@@ -103,25 +302,19 @@ impl Command for SetsCommand {
                 entries,
                 from_asn,
                 from_file,
+                format,
+                description,
+                source,
             } => {
-                let final_entries = if let Some(asn) = from_asn {
-                    println!("Fetching prefixes for ASN: {asn}");
-                    let prefixes = ripe::fetch_asn_prefixes(asn)?;
-                    let count = prefixes.len();
-                    println!("Retrieved {count} prefixes from RIPE NCC RIPEstat");
-                    prefixes
-                } else if let Some(path) = from_file {
-                    println!("Reading CIDRs from file: {}", path.display());
-                    let prefixes = read_cidrs_from_file(path)?;
-                    let count = prefixes.len();
-                    println!("Loaded {count} CIDRs from file");
-                    prefixes
-                } else {
-                    entries.clone()
-                };
+                let (final_entries, file_header) =
+                    resolve_entries(entries, from_asn.as_deref(), from_file.as_ref(), *format)?;
 
                 let set = Set {
                     name: name.clone(),
+                    header: file_header.merged(SetHeader {
+                        description: description.clone(),
+                        source: source.clone(),
+                    }),
                     entries: final_entries,
                 };
                 let created = client.sets().create(*policy, &set)?;
@@ -130,87 +323,444 @@ impl Command for SetsCommand {
                     created.name,
                     created.entries.len()
                 );
-                println!("Note: Run 'couicctl sets reload' to apply changes");
+                print_reload_note(client);
             }
             SetsSubCommand::Update {
                 policy,
                 name,
                 entries,
+                if_match,
+                description,
+                source,
             } => {
                 let set = Set {
                     name: name.clone(),
+                    header: SetHeader {
+                        description: description.clone(),
+                        source: source.clone(),
+                    },
                     entries: entries.clone(),
                 };
-                let updated = client.sets().update(*policy, name, &set)?;
-                println!(
-                    "Set '{}' updated with {} entries",
-                    updated.name,
-                    updated.entries.len()
-                );
-                println!("Note: Run 'couicctl sets reload' to apply changes");
+                update_set(client, *policy, &set, if_match.as_deref())?;
             }
+            SetsSubCommand::AddEntry {
+                policy,
+                name,
+                entries,
+            } => patch_set(client, *policy, name, entries, &[])?,
+            SetsSubCommand::RemoveEntry {
+                policy,
+                name,
+                entries,
+            } => patch_set(client, *policy, name, &[], entries)?,
+            SetsSubCommand::Export {
+                policy,
+                name,
+                output,
+                no_header,
+            } => export_set(client, *policy, name, output.as_ref(), *no_header)?,
             SetsSubCommand::Delete { policy, name } => {
                 client.sets().delete(*policy, name)?;
                 println!("Set '{name}' deleted");
-                println!("Note: Run 'couicctl sets reload' to apply changes");
-            }
-            SetsSubCommand::Reload => {
-                client.sets().reload()?;
-                println!("Sets reloaded successfully");
+                print_reload_note(client);
             }
+            SetsSubCommand::Disable { policy, name } => set_disabled(client, *policy, name, true)?,
+            SetsSubCommand::Enable { policy, name } => set_disabled(client, *policy, name, false)?,
+            SetsSubCommand::Reload {
+                policy,
+                name,
+                force,
+                json,
+            } => reload_sets(client, policy.zip(name.as_ref()), *force, *json)?,
+            SetsSubCommand::Status => print_status(client)?,
+            SetsSubCommand::Sign { key, files } => sign_sets(key, files)?,
+            SetsSubCommand::Validate { .. } => self.validate(Some(client))?,
         }
         Ok(())
     }
 }
 
-fn read_cidrs_from_file(path: &PathBuf) -> Result<Vec<IpNet>, CommandError> {
-    let file = fs::File::open(path).map_err(|e| {
-        CommandError::Generic(format!("Failed to open file {}: {}", path.display(), e))
-    })?;
-    let reader = BufReader::new(file);
+/// Prints the sets of a policy, disabled ones included
+fn list_sets(client: &CouicClient, policy: Policy) -> Result<(), CommandError> {
+    let sets = client.sets().list(policy)?;
+    if sets.is_empty() {
+        println!("No sets found for policy '{policy}'");
+    } else {
+        for set in sets {
+            println!("{set}");
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the entries of a set, only if it still has the entity tag of
+/// `if_match`, or else the one it had when fetched just before, so that a
+/// concurrent change is never overwritten
+fn update_set(
+    client: &CouicClient,
+    policy: Policy,
+    set: &Set,
+    if_match: Option<&str>,
+) -> Result<(), CommandError> {
+    let if_match = match if_match {
+        Some(etag) => Some(etag.to_string()),
+        None => client.sets().get_with_etag(policy, &set.name)?.1,
+    };
+    let (updated, etag) = match if_match {
+        Some(etag) => client
+            .sets()
+            .update_if_match(policy, &set.name, set, &etag)?,
+        // Daemons without entity tags on sets
+        None => (client.sets().update(policy, &set.name, set)?, None),
+    };
+    println!(
+        "Set '{}' updated with {} entries",
+        updated.name,
+        updated.entries.len()
+    );
+    if let Some(etag) = etag {
+        println!("ETag: {etag}");
+    }
+    print_reload_note(client);
+    Ok(())
+}
 
-    let mut cidrs = Vec::new();
-    let mut errors = Vec::new();
+/// Adds and removes entries of a set, only reminding to reload when it changed
+fn patch_set(
+    client: &CouicClient,
+    policy: Policy,
+    name: &SetName,
+    add: &[IpNet],
+    remove: &[IpNet],
+) -> Result<(), CommandError> {
+    let patch = SetPatch {
+        add: add.to_vec(),
+        remove: remove.to_vec(),
+    };
+    let result = client.sets().patch(policy, name, &patch)?;
+    println!("{result}");
+    if result.added > 0 || result.removed > 0 {
+        print_reload_note(client);
+    }
+    Ok(())
+}
 
-    for (line_num, line) in reader.lines().enumerate() {
-        let line = line.map_err(|e| {
-            CommandError::Generic(format!(
-                "Failed to read line {}: {}",
-                line_num.saturating_add(1),
-                e
-            ))
-        })?;
-        let line = line.trim();
+/// Writes a set as its file content, to `output` or the standard output
+fn export_set(
+    client: &CouicClient,
+    policy: Policy,
+    name: &SetName,
+    output: Option<&PathBuf>,
+    no_header: bool,
+) -> Result<(), CommandError> {
+    let set = client.sets().get(policy, name)?;
+    let header = if no_header {
+        SetHeader::default()
+    } else {
+        set.header
+    };
+    let mut content = render_set_file(&header, &set.entries);
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    match output.filter(|path| path.as_os_str() != STDOUT_SET_FILE) {
+        Some(path) => {
+            fs::write(path, content).map_err(|e| {
+                CommandError::Generic(format!("Failed to write file {}: {e}", path.display()))
+            })?;
+            println!(
+                "Set '{name}' exported to {} with {} entries",
+                path.display(),
+                set.entries.len()
+            );
+        }
+        None => io::stdout()
+            .write_all(content.as_bytes())
+            .map_err(|e| CommandError::Generic(format!("Failed to write set: {e}")))?,
+    }
+    Ok(())
+}
 
-        // Skip empty lines and comments
-        if line.is_empty() || line.starts_with('#') {
-            continue;
+/// Entries of a new set, given on the command line or imported, with the
+/// header of an imported set file
+fn resolve_entries(
+    entries: &[IpNet],
+    from_asn: Option<&str>,
+    from_file: Option<&PathBuf>,
+    format: ImportFormat,
+) -> Result<(Vec<IpNet>, SetHeader), CommandError> {
+    if let Some(asn) = from_asn {
+        println!("Fetching prefixes for ASN: {asn}");
+        let prefixes = ripe::fetch_asn_prefixes(asn)?;
+        let count = prefixes.len();
+        println!("Retrieved {count} prefixes from RIPE NCC RIPEstat");
+        Ok((prefixes, SetHeader::default()))
+    } else if let Some(path) = from_file {
+        println!("Reading CIDRs from file: {}", path.display());
+        let (prefixes, header) = read_cidrs_from_file(path, format)?;
+        let count = prefixes.len();
+        println!("Loaded {count} CIDRs from file");
+        Ok((prefixes, header))
+    } else {
+        Ok((entries.to_vec(), SetHeader::default()))
+    }
+}
+
+/// Prints the last reload of each policy, then the set files changed since
+fn print_status(client: &CouicClient) -> Result<(), CommandError> {
+    let status = client.sets().status()?;
+    match status.last_reload {
+        Some(time) => {
+            println!("Last reload: {}", format_timestamp(time));
+            for reload in &status.reloads {
+                print_reload(reload);
+            }
         }
+        None => println!("Sets were never loaded"),
+    }
+    print!("{}", client.sets().reload_status()?);
+    Ok(())
+}
+
+/// Reloads all sets, or the single set given as its policy and name
+fn reload_sets(
+    client: &CouicClient,
+    set: Option<(Policy, &SetName)>,
+    force: bool,
+    json: bool,
+) -> Result<(), CommandError> {
+    let reloads = if let Some((policy, name)) = set {
+        client.sets().reload_set(policy, name, force)?
+    } else {
+        client.sets().reload_with_force(force)?
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reloads)?);
+        return Ok(());
+    }
+    match set {
+        Some((_, name)) => println!("Set '{name}' reloaded successfully"),
+        None => println!("Sets reloaded successfully"),
+    }
+    for reload in &reloads {
+        print_reload(reload);
+    }
+    Ok(())
+}
 
-        match line.parse::<IpNet>() {
-            Ok(cidr) => cidrs.push(cidr),
-            Err(e) => errors.push(format!(
-                "line {}: {} ({})",
-                line_num.saturating_add(1),
-                line,
-                e
-            )),
+/// Prints the changes of a reload, and why sets were skipped
+fn print_reload(reload: &SetsReload) {
+    println!("\t{reload}");
+    for skipped in &reload.skipped {
+        let reasons: Vec<&str> = skipped
+            .error
+            .errors
+            .values()
+            .map(|detail| detail.message.as_str())
+            .collect();
+        if reasons.is_empty() {
+            println!("\t\tskipped '{}': {}", skipped.name, skipped.error);
+        } else {
+            println!(
+                "\t\tskipped '{}': {} ({})",
+                skipped.name,
+                skipped.error,
+                reasons.join(", ")
+            );
         }
     }
+}
+
+/// Report of `sets validate`, with the usage of the maps of the policy when
+/// the daemon reports it
+#[derive(Serialize)]
+struct FileValidation {
+    #[serde(flatten)]
+    validation: Validation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maps: Option<MapsUsage>,
+    /// Whether the entries fit in the maps even if none is loaded yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fits: Option<bool>,
+}
 
-    if !errors.is_empty() {
+/// Checks a file as `sets create --from-file` reads it, and whether its
+/// entries fit in the maps of `policy`
+fn validate_file(
+    client: Option<&CouicClient>,
+    file: &PathBuf,
+    format: ImportFormat,
+    policy: Option<Policy>,
+    json: bool,
+) -> Result<(), CommandError> {
+    let content = fs::read_to_string(file).map_err(|e| {
+        CommandError::Generic(format!("Failed to read file {}: {}", file.display(), e))
+    })?;
+    let validation = import::validate(&content, format);
+    let maps = match (client, policy) {
+        (Some(client), Some(policy)) => match client.sets().summary(policy) {
+            Ok(summary) => {
+                if summary.maps.is_none() {
+                    eprintln!("Capacity not checked, the daemon does not report it");
+                }
+                summary.maps
+            }
+            Err(e) => {
+                eprintln!("Capacity not checked, daemon unavailable: {e}");
+                None
+            }
+        },
+        _ => None,
+    };
+    let fits = maps.map(|maps| {
+        maps.v4.saturating_add(validation.v4) <= maps.max_v4
+            && maps.v6.saturating_add(validation.v6) <= maps.max_v6
+    });
+    let invalid = validation.invalid.len();
+    let report = FileValidation {
+        validation,
+        maps,
+        fits,
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_validation(&report, file, policy);
+    }
+    if invalid > 0 {
         return Err(CommandError::Generic(format!(
-            "Failed to parse {} CIDR(s):\n{}",
-            errors.len(),
-            errors.join("\n")
+            "{invalid} invalid line(s) in {}",
+            file.display()
         )));
     }
+    Ok(())
+}
+
+fn print_validation(report: &FileValidation, file: &Path, policy: Option<Policy>) {
+    let validation = &report.validation;
+    println!(
+        "{}: {} networks ({} IPv4, {} IPv6)",
+        file.display(),
+        validation.v4.saturating_add(validation.v6),
+        validation.v4,
+        validation.v6
+    );
+    for (title, lines) in [
+        ("Invalid lines", &validation.invalid),
+        ("Duplicates", &validation.duplicates),
+        ("Overlaps", &validation.overlaps),
+    ] {
+        if !lines.is_empty() {
+            println!("{title}: {}", lines.len());
+            for line in lines {
+                println!("\t{line}");
+            }
+        }
+    }
+    if let (Some(maps), Some(fits), Some(policy)) = (report.maps, report.fits, policy) {
+        println!(
+            "{policy} maps: {}/{} IPv4, {}/{} IPv6 entries",
+            maps.v4, maps.max_v4, maps.v6, maps.max_v6
+        );
+        if fits {
+            println!("The entries fit in the {policy} maps");
+        } else {
+            println!(
+                "The entries may not fit in the {policy} maps, unless most of them are already loaded"
+            );
+        }
+    }
+}
+
+/// Writes the detached signature of each set file next to it
+fn sign_sets(key: &PathBuf, files: &[PathBuf]) -> Result<(), CommandError> {
+    let pem = fs::read_to_string(key)
+        .map_err(|e| CommandError::Generic(format!("Failed to read key {}: {e}", key.display())))?;
+    let signer = SetSigner::from_pem(&pem)
+        .map_err(|e| CommandError::Generic(format!("Invalid key {}: {e}", key.display())))?;
+    for file in files {
+        let content = fs::read(file).map_err(|e| {
+            CommandError::Generic(format!("Failed to read set file {}: {e}", file.display()))
+        })?;
+        let sig_path = signature_path(file);
+        fs::write(&sig_path, signer.sign(&content)).map_err(|e| {
+            CommandError::Generic(format!(
+                "Failed to write signature {}: {e}",
+                sig_path.display()
+            ))
+        })?;
+        println!("Signed {} into {}", file.display(), sig_path.display());
+    }
+    Ok(())
+}
+
+/// Formats a Unix timestamp as an RFC 3339 date, in UTC
+fn format_timestamp(time: u64) -> String {
+    UNIX_EPOCH
+        .checked_add(Duration::from_secs(time))
+        .map_or_else(
+            || time.to_string(),
+            |time| humantime::format_rfc3339_seconds(time).to_string(),
+        )
+}
+
+/// Reminds to reload the sets, unless the daemon reports them up to date
+fn print_reload_note(client: &CouicClient) {
+    let reload_required = match client.sets().reload_status() {
+        Ok(status) => status.reload_required,
+        Err(_) => true,
+    };
+    if reload_required {
+        println!("Note: Run 'couicctl sets reload' to apply changes");
+    }
+}
+
+/// Disables or enables a set, the change applying on the next reload
+fn set_disabled(
+    client: &CouicClient,
+    policy: Policy,
+    name: &SetName,
+    disabled: bool,
+) -> Result<(), CommandError> {
+    let activation = if disabled {
+        client.sets().disable(policy, name)?
+    } else {
+        client.sets().enable(policy, name)?
+    };
+    let state = if activation.disabled {
+        "disabled"
+    } else {
+        "enabled"
+    };
+    println!("Set '{}' {state}", activation.name);
+    if activation.reload_required {
+        println!("Note: Run 'couicctl sets reload' to apply changes");
+    }
+    Ok(())
+}
+
+fn read_cidrs_from_file(
+    path: &PathBuf,
+    format: ImportFormat,
+) -> Result<(Vec<IpNet>, SetHeader), CommandError> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        CommandError::Generic(format!("Failed to read file {}: {}", path.display(), e))
+    })?;
+
+    let import = import::parse(&content, format);
+    if !import.skipped.is_empty() {
+        eprintln!(
+            "Skipped {} line(s) that could not be interpreted:\n{}",
+            import.skipped.len(),
+            import.skipped.join("\n")
+        );
+    }
 
-    if cidrs.is_empty() {
+    if import.cidrs.is_empty() {
         return Err(CommandError::Generic(
             "No valid CIDRs found in file".to_string(),
         ));
     }
 
-    Ok(cidrs)
+    Ok((import.cidrs, SetHeader::parse(&content)))
 }
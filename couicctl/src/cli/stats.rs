@@ -2,16 +2,34 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use clap::Args;
-use comfy_table::{Cell, ContentArrangement, Table, presets::UTF8_FULL};
 
 use client::CouicClient;
-use common::{Policy, Stats, TagStats};
+use common::{PktRates, Policy, Stats, TagStats};
 
+use super::table::{Column, TableOptions, print_table};
 use super::{Command, CommandError};
 
 const ANSI_CLEAR_SCREEN: &str = "\x1B[2J\x1B[1;1H";
 const ANSI_HIGHLIGHT: &str = "\x1b[47;30m";
 const ANSI_RESET: &str = "\x1b[0m";
+const TAG_STATS_COLUMNS: [Column; 4] = [
+    Column {
+        key: "tag",
+        header: "Tag",
+    },
+    Column {
+        key: "policy",
+        header: "Policy",
+    },
+    Column {
+        key: "rx_packets",
+        header: "RX Packets",
+    },
+    Column {
+        key: "rx_bytes",
+        header: "RX Bytes",
+    },
+];
 
 #[derive(Args, Debug)]
 pub struct StatsCommand {
@@ -31,14 +49,50 @@ enum StatsSubCommand {
     },
     #[command(about = "Display drop statistics per tag")]
     Drop {
-        #[arg(long)]
+        #[command(flatten)]
+        table: TableOptions,
+        #[arg(long, conflicts_with_all = ["no_headers", "columns"])]
         json: bool,
     },
     #[command(about = "Display ignore statistics per tag")]
     Ignore {
+        #[command(flatten)]
+        table: TableOptions,
+        #[arg(long, conflicts_with_all = ["no_headers", "columns"])]
+        json: bool,
+    },
+    #[command(
+        about = "Display monitor statistics per tag",
+        long_about = "Display monitor statistics per tag: the packets and bytes monitor entries would have dropped, ignored sources excepted"
+    )]
+    Monitor {
+        #[command(flatten)]
+        table: TableOptions,
+        #[arg(long, conflicts_with_all = ["no_headers", "columns"])]
+        json: bool,
+    },
+    #[command(about = "Display traffic rates per action and per tag")]
+    Delta {
+        #[arg(
+            short,
+            long,
+            value_name = "SECS",
+            help = "Sampling window in seconds (default: since the previous call, or 1s)"
+        )]
+        window: Option<u64>,
         #[arg(long)]
         json: bool,
     },
+    #[command(about = "Display metrics in Prometheus text format")]
+    Metrics {
+        #[arg(long, required = true)]
+        prometheus: bool,
+    },
+    #[command(
+        about = "Reset the packet and byte counters to zero",
+        long_about = "Reset the packet and byte counters to zero: XDP actions, drop reasons, per tag and per entry counters. Entries are left untouched. Requires the admin group"
+    )]
+    Reset,
 }
 
 impl Command for StatsCommand {
@@ -76,32 +130,55 @@ impl Command for StatsCommand {
                     thread::sleep(Duration::from_secs(1));
                 }
             }
-            StatsSubCommand::Drop { json: true } => {
+            StatsSubCommand::Drop { json: true, .. } => {
                 let tag_stats = client.stats().tag(Policy::Drop)?;
                 println!("{}", serde_json::to_string_pretty(&tag_stats)?);
             }
-            StatsSubCommand::Drop { json: false } => {
+            StatsSubCommand::Drop { json: false, table } => {
                 let tag_stats = client.stats().tag(Policy::Drop)?;
-                print_tag_stats(&tag_stats, "drop");
+                print_tag_stats(&tag_stats, "drop", table)?;
             }
-            StatsSubCommand::Ignore { json: true } => {
+            StatsSubCommand::Ignore { json: true, .. } => {
                 let tag_stats = client.stats().tag(Policy::Ignore)?;
                 println!("{}", serde_json::to_string_pretty(&tag_stats)?);
             }
-            StatsSubCommand::Ignore { json: false } => {
+            StatsSubCommand::Ignore { json: false, table } => {
                 let tag_stats = client.stats().tag(Policy::Ignore)?;
-                print_tag_stats(&tag_stats, "ignore");
+                print_tag_stats(&tag_stats, "ignore", table)?;
+            }
+            StatsSubCommand::Monitor { json: true, .. } => {
+                let tag_stats = client.stats().tag(Policy::Monitor)?;
+                println!("{}", serde_json::to_string_pretty(&tag_stats)?);
+            }
+            StatsSubCommand::Monitor { json: false, table } => {
+                let tag_stats = client.stats().tag(Policy::Monitor)?;
+                print_tag_stats(&tag_stats, "monitor", table)?;
+            }
+            StatsSubCommand::Delta { window, json } => {
+                let delta = client.stats().delta(*window)?;
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&delta)?);
+                } else {
+                    print!("{delta}");
+                }
+            }
+            StatsSubCommand::Metrics { .. } => {
+                print!("{}", client.stats().metrics_prometheus()?);
+            }
+            StatsSubCommand::Reset => {
+                client.stats().reset()?;
+                println!("Stats reset");
             }
         }
         Ok(())
     }
 }
 
-#[allow(clippy::cast_precision_loss)]
 fn display_live_stats(current_stats: &Stats, prev_stats: Option<&Stats>, elapsed: f64) {
     println!("Every {elapsed:.1}s: stats");
     println!("Drop CIDR Count: {}", current_stats.drop_cidr_count);
     println!("Ignore CIDR Count: {}", current_stats.ignore_cidr_count);
+    println!("Monitor CIDR Count: {}", current_stats.monitor_cidr_count);
     println!("XDP Stats:");
 
     let mut actions: Vec<_> = current_stats.xdp.keys().collect();
@@ -113,27 +190,17 @@ fn display_live_stats(current_stats: &Stats, prev_stats: Option<&Stats>, elapsed
 
             let prev_stat = prev_stats.and_then(|p| p.xdp.get(action));
 
-            let (packets_rate, bytes_rate, highlight_packets, highlight_bytes) =
-                prev_stat.map_or((0.0, 0.0, false, false), |prev| {
-                    let packet_delta = current_stat.rx_packets.saturating_sub(prev.rx_packets);
-                    let byte_delta = current_stat.rx_bytes.saturating_sub(prev.rx_bytes);
-                    let pps = packet_delta as f64 / elapsed;
-                    let bps = byte_delta as f64 / elapsed;
-                    (
-                        pps,
-                        bps,
-                        current_stat.rx_packets != prev.rx_packets,
-                        current_stat.rx_bytes != prev.rx_bytes,
-                    )
-                });
+            let rates = prev_stat.map_or_else(PktRates::default, |prev| {
+                PktRates::between(prev, current_stat, elapsed)
+            });
 
             println!(
                 "    RX Packets: {}",
-                format_rate_highlight(packets_rate, "pps", highlight_packets)
+                format_rate_highlight(rates.pps, "pps", rates.pps > 0.0)
             );
             println!(
                 "    RX Bytes: {}",
-                format_rate_highlight(bytes_rate, "bps", highlight_bytes)
+                format_rate_highlight(rates.bps, "bps", rates.bps > 0.0)
             );
         }
     }
@@ -157,31 +224,31 @@ fn format_rate_highlight(value: f64, unit: &str, highlight: bool) -> String {
     }
 }
 
-fn print_tag_stats(tag_stats: &TagStats, policy: &str) {
-    if tag_stats.tags.is_empty() {
+fn print_tag_stats(
+    tag_stats: &TagStats,
+    policy: &str,
+    options: &TableOptions,
+) -> Result<(), CommandError> {
+    if tag_stats.tags.is_empty() && !options.no_headers {
         println!("No tag statistics available.");
-        return;
+        return Ok(());
     }
 
-    let mut table = Table::new();
-    table
-        .load_preset(UTF8_FULL)
-        .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(vec!["Tag", "Policy", "RX Packets", "RX Bytes"]);
-
     let mut tag_names: Vec<_> = tag_stats.tags.keys().collect();
     tag_names.sort();
 
-    for tag_name in tag_names {
-        if let Some(stats) = tag_stats.tags.get(tag_name) {
-            table.add_row(vec![
-                Cell::new(tag_name),
-                Cell::new(policy),
-                Cell::new(stats.rx_packets),
-                Cell::new(stats.rx_bytes),
-            ]);
-        }
-    }
-
-    println!("{table}");
+    let rows = tag_names
+        .into_iter()
+        .filter_map(|tag_name| {
+            tag_stats.tags.get(tag_name).map(|stats| {
+                vec![
+                    tag_name.clone(),
+                    policy.to_string(),
+                    stats.rx_packets.to_string(),
+                    stats.rx_bytes.to_string(),
+                ]
+            })
+        })
+        .collect();
+    print_table(&TAG_STATS_COLUMNS, rows, options)
 }
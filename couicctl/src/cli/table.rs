@@ -0,0 +1,130 @@
+use clap::Args;
+use comfy_table::{Cell, ContentArrangement, Table, presets::UTF8_FULL};
+
+use super::CommandError;
+
+/// Column of a table output, selected by its `key` with `--columns`
+pub struct Column {
+    pub key: &'static str,
+    pub header: &'static str,
+}
+
+#[derive(Args, Debug, Default, Clone)]
+pub struct TableOptions {
+    #[arg(
+        long,
+        help = "Print rows tab-separated, without headers or borders",
+        long_help = "Print rows tab-separated, without headers or borders, for use in scripts"
+    )]
+    pub no_headers: bool,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "COLUMNS",
+        help = "Columns to print, in order, e.g. cidr,tag"
+    )]
+    pub columns: Vec<String>,
+}
+
+impl TableOptions {
+    /// Output of `--quiet`: one CIDR per line
+    pub fn quiet() -> Self {
+        Self {
+            no_headers: true,
+            columns: vec!["cidr".to_string()],
+        }
+    }
+}
+
+/// Print `rows`, holding a value per column of `columns`, restricted to the
+/// selected columns
+pub fn print_table(
+    columns: &[Column],
+    rows: Vec<Vec<String>>,
+    options: &TableOptions,
+) -> Result<(), CommandError> {
+    let selected = select_columns(columns, &options.columns)?;
+    let pick = |row: &[String]| -> Vec<String> {
+        selected
+            .iter()
+            .filter_map(|&i| row.get(i).cloned())
+            .collect()
+    };
+
+    if options.no_headers {
+        for row in rows {
+            println!("{}", pick(&row).join("\t"));
+        }
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(
+            selected
+                .iter()
+                .filter_map(|&i| columns.get(i))
+                .map(|column| column.header),
+        );
+    for row in rows {
+        table.add_row(pick(&row).into_iter().map(Cell::new));
+    }
+    println!("{table}");
+    Ok(())
+}
+
+/// Indexes of the columns named by `names`, case insensitively, all columns
+/// when none is named
+fn select_columns(columns: &[Column], names: &[String]) -> Result<Vec<usize>, CommandError> {
+    if names.is_empty() {
+        return Ok((0..columns.len()).collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            columns
+                .iter()
+                .position(|column| column.key.eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| {
+                    let available: Vec<&str> = columns.iter().map(|column| column.key).collect();
+                    CommandError::Generic(format!(
+                        "Unknown column {name} (available: {})",
+                        available.join(", ")
+                    ))
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: [Column; 3] = [
+        Column {
+            key: "cidr",
+            header: "CIDR",
+        },
+        Column {
+            key: "tag",
+            header: "Tag",
+        },
+        Column {
+            key: "expiration",
+            header: "Expiration",
+        },
+    ];
+
+    #[test]
+    fn test_select_columns() {
+        assert_eq!(select_columns(&COLUMNS, &[]).unwrap(), vec![0, 1, 2]);
+        let names = vec!["Tag".to_string(), " cidr".to_string()];
+        assert_eq!(select_columns(&COLUMNS, &names).unwrap(), vec![1, 0]);
+
+        let err = select_columns(&COLUMNS, &["policy".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("available: cidr, tag, expiration"));
+    }
+}
@@ -0,0 +1,97 @@
+use clap::Args;
+use serde::Serialize;
+
+use client::{CouicClient, CouicError};
+use common::ServerVersion;
+
+use super::{Command, CommandError};
+use crate::VERSION;
+use crate::config::{Config, Overrides};
+
+const HTTP_NOT_FOUND: u16 = 404;
+
+#[derive(Args, Debug)]
+#[command(
+    about = "Show the version of couicctl, and of the daemon with --remote",
+    long_about = "Show the version of couicctl. With --remote, also show the version, API versions and features of the daemon, to diagnose a version skew. Only the version of couicctl is shown when the daemon is unreachable or too old to report its version"
+)]
+pub struct VersionCommand {
+    #[arg(long, help = "Also show the version of the connected daemon")]
+    remote: bool,
+    #[arg(long)]
+    json: bool,
+}
+
+/// Versions printed by `--json`, `server` being unset when unknown
+#[derive(Serialize)]
+struct Versions {
+    client: &'static str,
+    server: Option<ServerVersion>,
+}
+
+impl VersionCommand {
+    /// Whether the command connects to the daemon, only done with `--remote`
+    pub const fn connects(&self) -> bool {
+        self.remote
+    }
+
+    /// Run the command before any connection to the daemon, which is only
+    /// made with `--remote` and may fail
+    pub fn run(&self, path: &str, overrides: &Overrides) -> Result<(), CommandError> {
+        if !self.remote {
+            return self.print(None);
+        }
+        let client = Config::load(path, overrides)
+            .map_err(CommandError::from)
+            .and_then(|config| Ok(config.build_client(overrides.token)?));
+        match client {
+            Ok(client) => self.execute_remote(&client),
+            Err(e) => {
+                eprintln!("Daemon unavailable: {e}");
+                self.print(None)
+            }
+        }
+    }
+
+    fn execute_remote(&self, client: &CouicClient) -> Result<(), CommandError> {
+        match client.server_version() {
+            Ok(server) => self.print(Some(server)),
+            Err(CouicError::ApiError { status, .. }) if status == HTTP_NOT_FOUND => {
+                eprintln!("The daemon does not report its version");
+                self.print(None)
+            }
+            Err(e) => {
+                eprintln!("Daemon unavailable: {e}");
+                self.print(None)
+            }
+        }
+    }
+
+    fn print(&self, server: Option<ServerVersion>) -> Result<(), CommandError> {
+        if self.json {
+            let versions = Versions {
+                client: VERSION,
+                server,
+            };
+            println!("{}", serde_json::to_string_pretty(&versions)?);
+            return Ok(());
+        }
+        println!("couicctl {VERSION}");
+        if let Some(server) = server {
+            println!("{} {}", server.name, server.version);
+            println!("API versions: {}", server.api_versions.join(", "));
+            println!("Features: {}", server.features.join(", "));
+        }
+        Ok(())
+    }
+}
+
+impl Command for VersionCommand {
+    fn execute(&self, client: &mut CouicClient) -> Result<(), CommandError> {
+        if self.remote {
+            self.execute_remote(client)
+        } else {
+            self.print(None)
+        }
+    }
+}
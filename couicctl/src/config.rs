@@ -1,10 +1,31 @@
 use std::fs;
-use std::io;
+use std::io::{self, Read};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 use std::path::Path;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use client::{ApiVersion, CouicClient, CouicError, LocalConfig, RemoteConfig, TIMEOUT};
+use common::{ClientName, DEFAULT_CLIENT_NAME};
+
+/// Identifies couicctl in the daemon logs, ahead of the client library
+const USER_AGENT: &str = concat!("couicctl/", env!("CARGO_PKG_VERSION"));
+/// Config file section holding the named profiles
+const PROFILES_KEY: &str = "profiles";
+/// `token_file` value reading the token from the standard input
+const STDIN_TOKEN_FILE: &str = "-";
+/// Permission bits a token file must not have: any access by group or others
+const TOKEN_FILE_FORBIDDEN_MODE: u32 = 0o077;
+/// Sockets probed in order in local mode when none is configured, the
+/// packaged daemon listening on the first one
+const DEFAULT_SOCKETS: [&str; 3] = [
+    "/var/run/couic/couic.sock",
+    "/run/couic/couic.sock",
+    "/var/run/couic.sock",
+];
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("IO error: {0}")]
@@ -15,7 +36,7 @@ pub enum ConfigError {
     Validation(String),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
     Local,
@@ -36,27 +57,257 @@ pub struct Config {
     pub tls: Option<bool>,
     #[serde(default)]
     pub token: Option<Uuid>,
+    /// File holding the token, `-` for the standard input
+    #[serde(default)]
+    pub token_file: Option<String>,
+    /// Request timeout in seconds, `0` disabling it
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// PEM client certificate for a daemon requiring mutual TLS
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    /// PEM private key of `client_cert`
+    #[serde(default)]
+    pub client_key: Option<String>,
+}
+
+/// Connection settings given on the command line or through the environment.
+///
+/// Precedence is flags > environment variables > config file.
+#[derive(Debug, Default, clap::Args)]
+pub struct Overrides {
+    /// Profile of the config file to use, from its `[profiles.<name>]` section
+    #[arg(long, global = true, env = "COUICCTL_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Connection mode, overrides `mode`
+    #[arg(long, global = true, env = "COUICCTL_MODE", value_enum)]
+    pub mode: Option<Mode>,
+    /// Unix socket of the local daemon, overrides `socket`
+    #[arg(long, global = true, env = "COUICCTL_SOCKET", value_name = "PATH")]
+    pub socket: Option<String>,
+    /// Client token, overrides `token` (and `client_file` in local mode).
+    /// `COUIC_TOKEN` is also read when `COUICCTL_TOKEN` is not set
+    #[arg(
+        long,
+        global = true,
+        env = "COUICCTL_TOKEN",
+        value_name = "UUID",
+        hide_env_values = true,
+        value_parser = parse_token,
+        conflicts_with = "token_file"
+    )]
+    pub token: Option<Uuid>,
+    /// File holding the client token, `-` for the standard input, overrides
+    /// `token_file`
+    #[arg(long, global = true, env = "COUICCTL_TOKEN_FILE", value_name = "PATH")]
+    pub token_file: Option<String>,
+    /// Remote daemon host, overrides `host`
+    #[arg(long, global = true, env = "COUICCTL_HOST")]
+    pub host: Option<String>,
+    /// Remote daemon port, overrides `port`
+    #[arg(long, global = true, env = "COUICCTL_PORT")]
+    pub port: Option<u16>,
+    /// Use TLS for the remote daemon, overrides `tls`
+    #[arg(long, global = true, env = "COUICCTL_TLS", value_name = "BOOL")]
+    pub tls: Option<bool>,
+    /// Request timeout in seconds, 0 for none, overrides `timeout` (default: 5)
+    #[arg(long, global = true, env = "COUICCTL_TIMEOUT", value_name = "SECS")]
+    pub timeout: Option<u64>,
+    /// PEM client certificate for mutual TLS, overrides `client_cert`
+    #[arg(long, global = true, env = "COUICCTL_CLIENT_CERT", value_name = "PATH")]
+    pub client_cert: Option<String>,
+    /// PEM private key of the client certificate, overrides `client_key`
+    #[arg(long, global = true, env = "COUICCTL_CLIENT_KEY", value_name = "PATH")]
+    pub client_key: Option<String>,
+}
+
+/// Environment variable also holding the client token, below
+/// `COUICCTL_TOKEN`
+pub const TOKEN_ENV_ALIAS: &str = "COUIC_TOKEN";
+
+impl Overrides {
+    /// Takes the token from `alias`, the value of `COUIC_TOKEN`, unless a
+    /// token or a token file is already given
+    pub fn token_alias(&mut self, alias: Option<&str>) -> Result<(), ConfigError> {
+        if self.token.is_none()
+            && self.token_file.is_none()
+            && let Some(value) = alias
+        {
+            let token = parse_token(value)
+                .map_err(|e| ConfigError::Validation(format!("invalid {TOKEN_ENV_ALIAS}: {e}")))?;
+            self.token = Some(token);
+        }
+        Ok(())
+    }
+}
+
+fn parse_token(s: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(s).map_err(|e| e.to_string())
 }
 
 impl Config {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+    pub fn load<P: AsRef<Path>>(path: P, overrides: &Overrides) -> Result<Self, ConfigError> {
         let content = fs::read_to_string(&path).map_err(ConfigError::Io)?;
 
-        let config: Self = toml::from_str(&content).map_err(ConfigError::Parse)?;
+        let mut config = Self::parse(&content, overrides.profile.as_deref())?;
+        config.apply_overrides(overrides);
 
         config.validate()?;
+        if matches!(config.mode, Mode::Local) && config.socket.is_none() {
+            config.socket = Some(discover_socket(&DEFAULT_SOCKETS)?);
+        }
+        if let Some(path) = &config.token_file {
+            config.token = Some(read_token_file(path)?);
+        }
         Ok(config)
     }
 
-    fn validate(&self) -> Result<(), ConfigError> {
+    /// Parse a config file, the keys of the selected profile replacing the
+    /// top-level ones, which only apply when no profile is selected or
+    /// missing from the profile
+    fn parse(content: &str, profile: Option<&str>) -> Result<Self, ConfigError> {
+        let mut table: toml::Table = toml::from_str(content).map_err(ConfigError::Parse)?;
+        let profiles = match table.remove(PROFILES_KEY) {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => {
+                return Err(ConfigError::Validation(format!(
+                    "{PROFILES_KEY} must be a table of profiles"
+                )));
+            }
+            None => toml::Table::new(),
+        };
+
+        if let Some(name) = profile {
+            match profiles.get(name) {
+                Some(toml::Value::Table(values)) => {
+                    table.extend(values.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+                Some(_) => {
+                    return Err(ConfigError::Validation(format!(
+                        "profile {name} must be a table"
+                    )));
+                }
+                None => {
+                    let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                    return Err(ConfigError::Validation(format!(
+                        "unknown profile {name} (available: {})",
+                        if available.is_empty() {
+                            "none".to_string()
+                        } else {
+                            available.join(", ")
+                        }
+                    )));
+                }
+            }
+        }
+
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(ConfigError::Parse)
+    }
+
+    /// Build a client for the configured daemon. In local mode, `token`, or
+    /// the token read from `token_file`, replaces the client file.
+    pub fn build_client(self, token: Option<Uuid>) -> Result<CouicClient, CouicError> {
+        let timeout = self.request_timeout();
         match self.mode {
             Mode::Local => {
-                if self.socket.is_none() {
-                    return Err(ConfigError::Validation(
-                        "socket is required for local mode".to_string(),
-                    ));
+                let socket = self.socket.unwrap_or_default();
+                let token = token.or_else(|| self.token_file.and(self.token));
+                let lc = match token {
+                    Some(token) => LocalConfig::from_token(socket, token),
+                    // A file named after the default client, wherever it is,
+                    // must be the one of that client and not a link to another
+                    None if Path::new(&self.client_file).file_stem()
+                        == Some(DEFAULT_CLIENT_NAME.as_ref()) =>
+                    {
+                        let lc = LocalConfig::from_file(socket, self.client_file);
+                        match ClientName::try_from(DEFAULT_CLIENT_NAME) {
+                            Ok(name) => lc.expect_client(name),
+                            Err(_) => lc,
+                        }
+                    }
+                    None => LocalConfig::from_file(socket, self.client_file),
+                };
+                CouicClient::builder()
+                    .version(ApiVersion::V1)
+                    .user_agent(USER_AGENT)
+                    .timeout(timeout)
+                    .build_local(lc)
+            }
+            Mode::Remote => {
+                let mut cc = RemoteConfig::new(
+                    self.host.unwrap_or_default(),
+                    self.port.unwrap_or_default(),
+                    self.token.unwrap_or_default(),
+                    self.tls.unwrap_or_default(),
+                );
+                // Set together, as checked by `validate`
+                if let (Some(cert), Some(key)) = (self.client_cert, self.client_key) {
+                    cc = cc.client_identity(cert, key);
                 }
+                CouicClient::builder()
+                    .version(ApiVersion::V1)
+                    .user_agent(USER_AGENT)
+                    .timeout(timeout)
+                    .build_remote(&cc)
             }
+        }
+    }
+
+    /// Client request timeout, the library default unless configured, `0`
+    /// disabling it
+    fn request_timeout(&self) -> Option<Duration> {
+        self.timeout.map_or(Some(TIMEOUT), |secs| {
+            (secs > 0).then(|| Duration::from_secs(secs))
+        })
+    }
+
+    fn apply_overrides(&mut self, overrides: &Overrides) {
+        if let Some(mode) = overrides.mode {
+            self.mode = mode;
+        }
+        if let Some(socket) = &overrides.socket {
+            self.socket = Some(socket.clone());
+        }
+        if let Some(token_file) = &overrides.token_file {
+            self.token_file = Some(token_file.clone());
+            self.token = None;
+        }
+        if let Some(token) = overrides.token {
+            self.token = Some(token);
+            self.token_file = None;
+        }
+        if let Some(host) = &overrides.host {
+            self.host = Some(host.clone());
+        }
+        if let Some(port) = overrides.port {
+            self.port = Some(port);
+        }
+        if let Some(tls) = overrides.tls {
+            self.tls = Some(tls);
+        }
+        if let Some(timeout) = overrides.timeout {
+            self.timeout = Some(timeout);
+        }
+        if let Some(client_cert) = &overrides.client_cert {
+            self.client_cert = Some(client_cert.clone());
+        }
+        if let Some(client_key) = &overrides.client_key {
+            self.client_key = Some(client_key.clone());
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.token.is_some() && self.token_file.is_some() {
+            return Err(ConfigError::Validation(
+                "token and token_file are mutually exclusive".to_string(),
+            ));
+        }
+
+        match self.mode {
+            // Without a socket, the default ones are probed
+            Mode::Local => {}
             Mode::Remote => {
                 if self.host.is_none() {
                     return Err(ConfigError::Validation(
@@ -68,9 +319,358 @@ impl Config {
                         "port is required for remote mode".to_string(),
                     ));
                 }
+                if self.client_cert.is_some() != self.client_key.is_some() {
+                    return Err(ConfigError::Validation(
+                        "client_cert and client_key must be set together".to_string(),
+                    ));
+                }
+                if self.client_cert.is_some() && self.tls != Some(true) {
+                    return Err(ConfigError::Validation(
+                        "client_cert requires tls = true".to_string(),
+                    ));
+                }
             }
         }
 
         Ok(())
     }
 }
+
+/// First of `candidates` that is a Unix socket, for a local mode config
+/// without `socket`
+fn discover_socket(candidates: &[&str]) -> Result<String, ConfigError> {
+    candidates
+        .iter()
+        .find(|path| fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()))
+        .map(ToString::to_string)
+        .ok_or_else(|| {
+            ConfigError::Validation(format!(
+                "socket is not configured and no daemon socket was found (tried {}), set socket or --socket",
+                candidates.join(", ")
+            ))
+        })
+}
+
+/// Read a token from a file, or from the standard input for `-`
+fn read_token_file(path: &str) -> Result<Uuid, ConfigError> {
+    let content = if path == STDIN_TOKEN_FILE {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        check_token_file_perms(Path::new(path))?;
+        fs::read_to_string(path).map_err(|e| {
+            ConfigError::Validation(format!("failed to read token file {path}: {e}"))
+        })?
+    };
+    Uuid::parse_str(content.trim())
+        .map_err(|e| ConfigError::Validation(format!("invalid token in {path}: {e}")))
+}
+
+/// Like the client files of the daemon, a token file must only be accessible
+/// by its owner
+fn check_token_file_perms(path: &Path) -> Result<(), ConfigError> {
+    let metadata = fs::metadata(path).map_err(|e| {
+        ConfigError::Validation(format!("failed to read token file {}: {e}", path.display()))
+    })?;
+    let mode = metadata.permissions().mode();
+    if mode & TOKEN_FILE_FORBIDDEN_MODE != 0 {
+        return Err(ConfigError::Validation(format!(
+            "token file {} must not be accessible by group or others (mode {:o}, expected 600)",
+            path.display(),
+            mode & 0o777
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LOCAL_CONFIG: &str = r#"
+mode = "local"
+socket = "/var/run/couic/couic.sock"
+client_file = "/var/lib/couic/rbac/clients/couicctl.toml"
+"#;
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let mut config: Config = toml::from_str(LOCAL_CONFIG).unwrap();
+        let overrides = Overrides {
+            socket: Some("/tmp/couic.sock".to_string()),
+            ..Overrides::default()
+        };
+        config.apply_overrides(&overrides);
+        assert_eq!(config.socket.as_deref(), Some("/tmp/couic.sock"));
+        assert!(matches!(config.mode, Mode::Local));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_overrides_switch_to_remote() {
+        let mut config: Config = toml::from_str(LOCAL_CONFIG).unwrap();
+        let overrides = Overrides {
+            mode: Some(Mode::Remote),
+            host: Some("couic.example.org".to_string()),
+            ..Overrides::default()
+        };
+        config.apply_overrides(&overrides);
+        // port is still missing
+        assert!(config.validate().is_err());
+
+        config.apply_overrides(&Overrides {
+            port: Some(8080),
+            tls: Some(true),
+            ..Overrides::default()
+        });
+        assert!(config.validate().is_ok());
+        assert_eq!(config.host.as_deref(), Some("couic.example.org"));
+        assert_eq!(config.tls, Some(true));
+    }
+
+    #[test]
+    fn test_client_cert_requires_key_and_tls() {
+        let mut config = Config::parse(PROFILES_CONFIG, Some("edge1")).unwrap();
+        config.apply_overrides(&Overrides {
+            client_cert: Some("/etc/couic/client.pem".to_string()),
+            ..Overrides::default()
+        });
+        assert!(config.validate().is_err());
+
+        config.apply_overrides(&Overrides {
+            client_key: Some("/etc/couic/client.key".to_string()),
+            ..Overrides::default()
+        });
+        assert!(config.validate().is_ok());
+
+        config.tls = Some(false);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_no_overrides_keeps_file_values() {
+        let mut config: Config = toml::from_str(LOCAL_CONFIG).unwrap();
+        config.apply_overrides(&Overrides::default());
+        assert_eq!(config.socket.as_deref(), Some("/var/run/couic/couic.sock"));
+        assert!(config.token.is_none());
+    }
+
+    const PROFILES_CONFIG: &str = r#"
+mode = "local"
+socket = "/var/run/couic/couic.sock"
+client_file = "/var/lib/couic/rbac/clients/couicctl.toml"
+
+[profiles.edge1]
+mode = "remote"
+host = "edge1.example.org"
+port = 8443
+tls = true
+token = "6b1b7e1a-9a3c-4c56-9f43-1d2b5a0f4e11"
+
+[profiles.lab]
+socket = "/tmp/couic.sock"
+"#;
+
+    #[test]
+    fn test_parse_without_profile_uses_top_level() {
+        let config = Config::parse(PROFILES_CONFIG, None).unwrap();
+        assert!(matches!(config.mode, Mode::Local));
+        assert_eq!(config.socket.as_deref(), Some("/var/run/couic/couic.sock"));
+        assert!(config.host.is_none());
+    }
+
+    #[test]
+    fn test_parse_profile_overlays_top_level() {
+        let config = Config::parse(PROFILES_CONFIG, Some("edge1")).unwrap();
+        assert!(matches!(config.mode, Mode::Remote));
+        assert_eq!(config.host.as_deref(), Some("edge1.example.org"));
+        assert_eq!(config.port, Some(8443));
+        assert!(config.validate().is_ok());
+
+        let config = Config::parse(PROFILES_CONFIG, Some("lab")).unwrap();
+        assert!(matches!(config.mode, Mode::Local));
+        assert_eq!(config.socket.as_deref(), Some("/tmp/couic.sock"));
+        assert_eq!(
+            config.client_file,
+            "/var/lib/couic/rbac/clients/couicctl.toml"
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_profile() {
+        let err = Config::parse(PROFILES_CONFIG, Some("edge2")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Validation error: unknown profile edge2 (available: edge1, lab)"
+        );
+        let err = Config::parse(LOCAL_CONFIG, Some("edge1")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Validation error: unknown profile edge1 (available: none)"
+        );
+    }
+
+    fn token_file(dir: &TempDir, name: &str, content: &str, mode: u32) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_read_token_file() {
+        let dir = TempDir::new().unwrap();
+        let path = token_file(
+            &dir,
+            "valid",
+            "6b1b7e1a-9a3c-4c56-9f43-1d2b5a0f4e11\n",
+            0o600,
+        );
+        assert_eq!(
+            read_token_file(&path).unwrap(),
+            Uuid::parse_str("6b1b7e1a-9a3c-4c56-9f43-1d2b5a0f4e11").unwrap()
+        );
+
+        let path = token_file(&dir, "open", "6b1b7e1a-9a3c-4c56-9f43-1d2b5a0f4e11", 0o644);
+        let err = read_token_file(&path).unwrap_err();
+        assert!(err.to_string().contains("must not be accessible"));
+
+        let path = token_file(&dir, "invalid", "not-a-uuid", 0o400);
+        let err = read_token_file(&path).unwrap_err();
+        assert!(err.to_string().contains("invalid token"));
+    }
+
+    #[test]
+    fn test_token_alias() {
+        let token = "6b1b7e1a-9a3c-4c56-9f43-1d2b5a0f4e11";
+        let mut overrides = Overrides::default();
+        overrides.token_alias(Some(token)).unwrap();
+        assert_eq!(overrides.token, Some(Uuid::parse_str(token).unwrap()));
+
+        // COUICCTL_TOKEN or a token file take precedence
+        let other = Uuid::new_v4();
+        let mut overrides = Overrides {
+            token: Some(other),
+            ..Overrides::default()
+        };
+        overrides.token_alias(Some(token)).unwrap();
+        assert_eq!(overrides.token, Some(other));
+        let mut overrides = Overrides {
+            token_file: Some("/run/secrets/couic".to_string()),
+            ..Overrides::default()
+        };
+        overrides.token_alias(Some(token)).unwrap();
+        assert!(overrides.token.is_none());
+
+        let err = Overrides::default()
+            .token_alias(Some("not-a-uuid"))
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid COUIC_TOKEN"));
+    }
+
+    #[test]
+    fn test_token_and_token_file_are_exclusive() {
+        let mut config = Config::parse(PROFILES_CONFIG, Some("edge1")).unwrap();
+        config.token_file = Some("/etc/couic/token".to_string());
+        assert!(config.validate().is_err());
+
+        // An override replaces both sources of the config file
+        config.apply_overrides(&Overrides {
+            token_file: Some("/run/secrets/couic".to_string()),
+            ..Overrides::default()
+        });
+        assert!(config.validate().is_ok());
+        assert!(config.token.is_none());
+    }
+
+    #[test]
+    fn test_discover_socket_skips_missing_and_regular_files() {
+        let dir = TempDir::new().unwrap();
+        let dir = dir.path();
+        let file = dir.join("file.sock");
+        fs::write(&file, "").unwrap();
+        let socket = dir.join("couic.sock");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+
+        let missing = dir.join("missing.sock");
+        let candidates = [
+            missing.to_str().unwrap(),
+            file.to_str().unwrap(),
+            socket.to_str().unwrap(),
+        ];
+        assert_eq!(
+            discover_socket(&candidates).unwrap(),
+            socket.to_string_lossy()
+        );
+
+        let err = discover_socket(&candidates[..2]).unwrap_err();
+        assert!(err.to_string().contains(&format!(
+            "tried {}, {}",
+            missing.display(),
+            file.display()
+        )));
+    }
+
+    #[test]
+    fn test_local_mode_without_socket_is_valid() {
+        let mut config: Config = toml::from_str(LOCAL_CONFIG).unwrap();
+        config.socket = None;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_client_file_named_after_the_default_client() {
+        let dir = TempDir::new().unwrap();
+        let socket = dir.path().join("couic.sock");
+        fs::write(&socket, "").unwrap();
+        let ops = dir.path().join("ops.toml");
+        fs::write(
+            &ops,
+            format!("token = \"{}\"\ngroup = \"admin\"\n", Uuid::new_v4()),
+        )
+        .unwrap();
+        let build = |client_file: &Path| {
+            let mut config: Config = toml::from_str(LOCAL_CONFIG).unwrap();
+            config.socket = Some(socket.to_string_lossy().into_owned());
+            config.client_file = client_file.to_string_lossy().into_owned();
+            config.build_client(None)
+        };
+
+        // Outside the default path, a link named after the default client
+        // must still lead to its file
+        let link = dir.path().join(format!("{DEFAULT_CLIENT_NAME}.toml"));
+        std::os::unix::fs::symlink(&ops, &link).unwrap();
+        let err = build(&link).err().unwrap();
+        assert!(
+            err.to_string()
+                .contains("is the one of client ops, expected couicctl")
+        );
+
+        // Other client files are loaded as named
+        assert!(build(&ops).is_ok());
+        fs::remove_file(&link).unwrap();
+        fs::copy(&ops, &link).unwrap();
+        assert!(build(&link).is_ok());
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let mut config: Config = toml::from_str(LOCAL_CONFIG).unwrap();
+        assert_eq!(config.request_timeout(), Some(TIMEOUT));
+
+        config.apply_overrides(&Overrides {
+            timeout: Some(120),
+            ..Overrides::default()
+        });
+        assert_eq!(config.request_timeout(), Some(Duration::from_secs(120)));
+
+        config.apply_overrides(&Overrides {
+            timeout: Some(0),
+            ..Overrides::default()
+        });
+        assert_eq!(config.request_timeout(), None);
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+/// Layout of a file imported into a set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// Detect the layout of each line
+    #[default]
+    Auto,
+    /// One CIDR or address per line
+    Cidr,
+    /// `ipset save` output
+    Ipset,
+    /// fail2ban iptables rules, `fail2ban-client status` or log ban lines
+    Fail2ban,
+}
+
+/// Networks read from a file, and the lines that could not be interpreted
+#[derive(Debug, Default)]
+pub struct Import {
+    pub cidrs: Vec<IpNet>,
+    pub skipped: Vec<String>,
+}
+
+/// Extracts networks from `content`, normalized to their network address,
+/// sorted and without duplicates.
+///
+/// Plain addresses become /32 or /128. A fail2ban unban line removes the
+/// address banned by the lines before it.
+#[must_use]
+pub fn parse(content: &str, format: ImportFormat) -> Import {
+    let mut import = Import::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line, format) {
+            Some(Parsed::Networks(cidrs)) => {
+                import.cidrs.extend(cidrs.iter().map(IpNet::trunc));
+            }
+            Some(Parsed::Unban(cidr)) => {
+                let cidr = cidr.trunc();
+                import.cidrs.retain(|banned| *banned != cidr);
+            }
+            None => import
+                .skipped
+                .push(format!("line {}: {line}", index.saturating_add(1))),
+        }
+    }
+    import.cidrs.sort_unstable();
+    import.cidrs.dedup();
+    import
+}
+
+/// Report of `sets validate` on a file, lines being numbered from 1
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Validation {
+    /// Distinct networks, normalized to their network address
+    pub v4: usize,
+    pub v6: usize,
+    pub invalid: Vec<String>,
+    /// Networks listed again, with the line listing them first
+    pub duplicates: Vec<String>,
+    /// Networks within a broader one of the file, which already covers them
+    pub overlaps: Vec<String>,
+}
+
+/// Checks each line of `content` as `parse` reads it, reporting what `parse`
+/// silently drops or merges
+#[must_use]
+pub fn validate(content: &str, format: ImportFormat) -> Validation {
+    let mut validation = Validation::default();
+    let mut first_lines: HashMap<IpNet, usize> = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let number = index.saturating_add(1);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let cidrs = match parse_line(line, format) {
+            Some(Parsed::Networks(cidrs)) => cidrs,
+            // Unbans list no network of their own
+            Some(Parsed::Unban(_)) => continue,
+            None => {
+                validation.invalid.push(format!("line {number}: {line}"));
+                continue;
+            }
+        };
+        for cidr in cidrs.iter().map(IpNet::trunc) {
+            if let Some(first) = first_lines.get(&cidr) {
+                validation
+                    .duplicates
+                    .push(format!("line {number}: {cidr} already on line {first}"));
+            } else {
+                first_lines.insert(cidr, number);
+            }
+        }
+    }
+
+    let networks: HashSet<IpNet> = first_lines.keys().copied().collect();
+    let mut overlaps: Vec<(usize, String)> = first_lines
+        .iter()
+        .filter_map(|(cidr, number)| {
+            let broader = broadest_supernet(cidr, &networks)?;
+            let broader_line = first_lines.get(&broader)?;
+            Some((
+                *number,
+                format!("line {number}: {cidr} within {broader} of line {broader_line}"),
+            ))
+        })
+        .collect();
+    overlaps.sort_unstable();
+    validation.overlaps = overlaps.into_iter().map(|(_, overlap)| overlap).collect();
+    validation.v4 = networks.iter().filter(|cidr| cidr.addr().is_ipv4()).count();
+    validation.v6 = networks.len().saturating_sub(validation.v4);
+    validation
+}
+
+/// Broadest network of `networks` strictly containing `cidr`
+fn broadest_supernet(cidr: &IpNet, networks: &HashSet<IpNet>) -> Option<IpNet> {
+    let mut broadest = None;
+    let mut current = *cidr;
+    while let Some(supernet) = current.supernet() {
+        if networks.contains(&supernet) {
+            broadest = Some(supernet);
+        }
+        current = supernet;
+    }
+    broadest
+}
+
+/// What a line of an imported file carries
+enum Parsed {
+    /// Networks listed by the line, none for headers and the like
+    Networks(Vec<IpNet>),
+    /// Network unbanned by a fail2ban log line
+    Unban(IpNet),
+}
+
+/// Networks of a line, or `None` when the line cannot be interpreted
+fn parse_line(line: &str, format: ImportFormat) -> Option<Parsed> {
+    match format {
+        ImportFormat::Cidr => parse_cidr_line(line).map(Parsed::Networks),
+        ImportFormat::Ipset => parse_ipset_line(line).map(Parsed::Networks),
+        ImportFormat::Fail2ban => parse_fail2ban_line(line),
+        ImportFormat::Auto => parse_cidr_line(line)
+            .or_else(|| parse_ipset_line(line))
+            .map(Parsed::Networks)
+            .or_else(|| parse_fail2ban_line(line)),
+    }
+}
+
+fn parse_address(token: &str) -> Option<IpNet> {
+    token
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| token.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn parse_cidr_line(line: &str) -> Option<Vec<IpNet>> {
+    parse_address(line).map(|cidr| vec![cidr])
+}
+
+/// `create <set> <type> ...` headers and `add <set> <entry> [options]` lines,
+/// the entry possibly carrying extra members, e.g. `192.0.2.0/24,tcp:80`
+fn parse_ipset_line(line: &str) -> Option<Vec<IpNet>> {
+    let mut tokens = line.split_whitespace();
+    match tokens.next()? {
+        "create" => Some(Vec::new()),
+        "add" => {
+            let entry = tokens.nth(1)?;
+            parse_address(entry.split(',').next()?).map(|cidr| vec![cidr])
+        }
+        _ => None,
+    }
+}
+
+/// iptables rules from `iptables-save` or `iptables -S`, the banned IP list
+/// of `fail2ban-client status <jail>`, and fail2ban log lines. Only the
+/// sources rejected or dropped by the `f2b-*` chains of fail2ban are bans,
+/// other rules being ignored.
+fn parse_fail2ban_line(line: &str) -> Option<Parsed> {
+    if line.starts_with('*') || line.starts_with(':') || line == "COMMIT" {
+        return Some(Parsed::Networks(Vec::new()));
+    }
+
+    if let Some((_, list)) = line.split_once("Banned IP list:") {
+        return list
+            .split_whitespace()
+            .map(parse_address)
+            .collect::<Option<_>>()
+            .map(Parsed::Networks);
+    }
+
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let rule = match tokens.as_slice() {
+        ["iptables" | "ip6tables", rule @ ..] => rule,
+        rule @ [first, ..] if matches!(*first, "-A" | "-I") => rule,
+        _ => &[],
+    };
+    if !rule.is_empty() {
+        return parse_fail2ban_rule(rule).map(Parsed::Networks);
+    }
+
+    if line.contains("fail2ban.") {
+        // Only bans and unbans carry an address, other log lines are noise
+        return Some(
+            tokens
+                .windows(2)
+                .find_map(|pair| match pair {
+                    ["Ban", address] => {
+                        parse_address(address).map(|cidr| Parsed::Networks(vec![cidr]))
+                    }
+                    ["Unban", address] => parse_address(address).map(Parsed::Unban),
+                    _ => None,
+                })
+                .unwrap_or(Parsed::Networks(Vec::new())),
+        );
+    }
+
+    None
+}
+
+/// Source banned by an iptables rule, none for the rules of other chains,
+/// other targets (jumps, `RETURN`, `ACCEPT`...) and rules without source
+fn parse_fail2ban_rule(tokens: &[&str]) -> Option<Vec<IpNet>> {
+    let after = |flags: &[&str]| {
+        tokens.windows(2).find_map(|pair| match pair {
+            [flag, value] if flags.contains(flag) => Some(*value),
+            _ => None,
+        })
+    };
+    let is_ban = after(&["-A", "--append", "-I", "--insert"])
+        .is_some_and(|chain| chain.starts_with("f2b-"))
+        && after(&["-j", "--jump"]).is_some_and(|target| matches!(target, "REJECT" | "DROP"));
+    if !is_ban {
+        return Some(Vec::new());
+    }
+
+    let mut previous = "";
+    let mut rest = tokens.iter();
+    while let Some(&token) = rest.next() {
+        if matches!(token, "-s" | "--source") {
+            // A negated source bans everything else
+            if previous == "!" {
+                return None;
+            }
+            return parse_address(rest.next()?).map(|cidr| vec![cidr]);
+        }
+        previous = token;
+    }
+    Some(Vec::new())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn to_strings(cidrs: &[IpNet]) -> Vec<String> {
+        cidrs.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_parse_plain_cidrs_and_addresses() {
+        let import = parse(
+            "# feed\n192.0.2.0/24\n198.51.100.7\n2001:db8::1\n\n192.0.2.5/24\n",
+            ImportFormat::Cidr,
+        );
+        assert_eq!(
+            to_strings(&import.cidrs),
+            ["192.0.2.0/24", "198.51.100.7/32", "2001:db8::1/128"]
+        );
+        assert_eq!(import.skipped, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_ipset_save() {
+        let content = "\
+create blocklist hash:net family inet hashsize 1024 maxelem 65536
+add blocklist 192.0.2.0/24
+add blocklist 198.51.100.7 timeout 3600
+add blocklist 203.0.113.0/24,tcp:80
+add blocklist not-an-address
+";
+        let import = parse(content, ImportFormat::Ipset);
+        assert_eq!(
+            to_strings(&import.cidrs),
+            ["192.0.2.0/24", "198.51.100.7/32", "203.0.113.0/24"]
+        );
+        assert_eq!(import.skipped, ["line 5: add blocklist not-an-address"]);
+    }
+
+    #[test]
+    fn test_parse_fail2ban_iptables_rules() {
+        let content = "\
+*filter
+:f2b-sshd - [0:0]
+-A INPUT -p tcp -m multiport --dports 22 -j f2b-sshd
+-A f2b-sshd -s 192.0.2.1/32 -j REJECT --reject-with icmp-port-unreachable
+-A f2b-sshd -s 198.51.100.7 -j DROP
+-A f2b-sshd ! -s 203.0.113.0/24 -j DROP
+-A f2b-sshd -j RETURN
+COMMIT
+";
+        let import = parse(content, ImportFormat::Fail2ban);
+        assert_eq!(
+            to_strings(&import.cidrs),
+            ["192.0.2.1/32", "198.51.100.7/32"]
+        );
+        assert_eq!(
+            import.skipped,
+            ["line 6: -A f2b-sshd ! -s 203.0.113.0/24 -j DROP"]
+        );
+    }
+
+    #[test]
+    fn test_parse_fail2ban_ignores_other_rules() {
+        let content = "\
+-A INPUT -s 192.0.2.1/32 -j ACCEPT
+-A INPUT -s 192.0.2.2/32 -j DROP
+-A f2b-sshd -s 192.0.2.3/32 -j ACCEPT
+-A f2b-sshd -s 192.0.2.4/32 -j RETURN
+iptables -A DOCKER -s 192.0.2.5/32 -j REJECT
+iptables -I f2b-nginx -s 198.51.100.7/32 -j REJECT
+";
+        let import = parse(content, ImportFormat::Fail2ban);
+        assert_eq!(to_strings(&import.cidrs), ["198.51.100.7/32"]);
+        assert_eq!(import.skipped, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_fail2ban_log_honors_unbans() {
+        let content = "\
+2024-05-01 10:00:00,000 fail2ban.actions [42]: NOTICE  [sshd] Ban 192.0.2.1
+2024-05-01 10:00:01,000 fail2ban.actions [42]: NOTICE  [sshd] Ban 192.0.2.2
+2024-05-01 10:10:00,000 fail2ban.actions [42]: NOTICE  [sshd] Unban 192.0.2.1
+2024-05-01 10:20:00,000 fail2ban.actions [42]: NOTICE  [sshd] Unban 192.0.2.2
+2024-05-01 10:30:00,000 fail2ban.actions [42]: NOTICE  [sshd] Ban 192.0.2.2
+";
+        let import = parse(content, ImportFormat::Fail2ban);
+        assert_eq!(to_strings(&import.cidrs), ["192.0.2.2/32"]);
+
+        let validation = validate(content, ImportFormat::Fail2ban);
+        assert_eq!(validation.invalid, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_fail2ban_status_and_log() {
+        let content = "\
+Status for the jail: sshd
+   `- Banned IP list:\t192.0.2.1 2001:db8::7
+2024-05-01 10:00:00,000 fail2ban.filter  [42]: INFO    [sshd] Found 198.51.100.9
+2024-05-01 10:00:01,000 fail2ban.actions [42]: NOTICE  [sshd] Ban 198.51.100.9
+2024-05-01 11:00:01,000 fail2ban.actions [42]: NOTICE  [sshd] Unban 203.0.113.4
+";
+        let import = parse(content, ImportFormat::Fail2ban);
+        assert_eq!(
+            to_strings(&import.cidrs),
+            ["192.0.2.1/32", "198.51.100.9/32", "2001:db8::7/128"]
+        );
+        assert_eq!(import.skipped, ["line 1: Status for the jail: sshd"]);
+    }
+
+    #[test]
+    fn test_parse_auto_mixes_formats() {
+        let content = "\
+192.0.2.0/24
+add blocklist 198.51.100.0/24
+-A f2b-sshd -s 203.0.113.1/32 -j DROP
+garbage line
+";
+        let import = parse(content, ImportFormat::Auto);
+        assert_eq!(
+            to_strings(&import.cidrs),
+            ["192.0.2.0/24", "198.51.100.0/24", "203.0.113.1/32"]
+        );
+        assert_eq!(import.skipped, ["line 4: garbage line"]);
+    }
+
+    #[test]
+    fn test_validate_reports_invalid_lines_duplicates_and_overlaps() {
+        let content = "\
+# feed
+192.0.2.0/24
+192.0.2.5/24
+192.0.2.128/25
+198.51.100.7
+garbage line
+10.0.0.0/8
+10.1.2.0/24
+2001:db8::/32
+198.51.100.7/32
+";
+        let validation = validate(content, ImportFormat::Cidr);
+        assert_eq!(validation.v4, 5);
+        assert_eq!(validation.v6, 1);
+        assert_eq!(validation.invalid, ["line 6: garbage line"]);
+        assert_eq!(
+            validation.duplicates,
+            [
+                "line 3: 192.0.2.0/24 already on line 2",
+                "line 10: 198.51.100.7/32 already on line 5"
+            ]
+        );
+        assert_eq!(
+            validation.overlaps,
+            [
+                "line 4: 192.0.2.128/25 within 192.0.2.0/24 of line 2",
+                "line 8: 10.1.2.0/24 within 10.0.0.0/8 of line 7"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_strict_format_rejects_other_layouts() {
+        let import = parse("add blocklist 192.0.2.0/24\n", ImportFormat::Cidr);
+        assert_eq!(import.cidrs, Vec::<IpNet>::new());
+        assert_eq!(import.skipped.len(), 1);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod cli;
 pub mod config;
+pub mod import;
+pub mod range;
 pub mod ripe;
+pub mod sign;
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
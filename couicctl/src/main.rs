@@ -1,54 +1,74 @@
 mod cli;
 mod config;
+mod import;
+mod range;
 mod ripe;
+mod sign;
 
 use clap::{CommandFactory, Parser};
 
-use client::{ApiVersion, CouicClient, LocalConfig, RemoteConfig};
-
 use crate::cli::CommandError;
-use crate::config::Mode;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn main() -> Result<(), CommandError> {
-    let cli = cli::Cli::parse();
-
+    let mut cli = cli::Cli::parse();
     if cli.markdown_help {
         clap_markdown::print_help_markdown::<cli::Cli>();
         std::process::exit(0);
     }
 
-    let config = config::Config::load(&cli.config)?;
+    // A malformed alias only fails the commands needing the token
+    if cli.command.as_ref().is_none_or(cli::Commands::connects) {
+        cli.overrides
+            .token_alias(std::env::var(config::TOKEN_ENV_ALIAS).ok().as_deref())?;
+    }
 
-    // Create client based on mode
-    let client = match config.mode {
-        Mode::Local => {
-            let lc = LocalConfig::from_file(config.socket.unwrap_or_default(), config.client_file);
-            CouicClient::builder()
-                .version(ApiVersion::V1)
-                .build_local(lc)?
+    // Configuration checks report connection failures themselves
+    if let Some(cli::Commands::Config(cmd)) = &cli.command {
+        if let Err(e) = cmd.run(&cli.config, &cli.overrides) {
+            eprintln!("Error executing command: {e}");
+            std::process::exit(1);
         }
-        Mode::Remote => {
-            let cc = RemoteConfig {
-                token: config.token.unwrap_or_default(),
-                host: config.host.unwrap_or_default(),
-                port: config.port.unwrap_or_default(),
-                tls: config.tls.unwrap_or_default(),
-            };
-            CouicClient::builder()
-                .version(ApiVersion::V1)
-                .build_remote(&cc)?
+        return Ok(());
+    }
+
+    // The version is shown even when the daemon is unreachable
+    if let Some(cli::Commands::Version(cmd)) = &cli.command {
+        if let Err(e) = cmd.run(&cli.config, &cli.overrides) {
+            eprintln!("Error executing command: {e}");
+            std::process::exit(1);
         }
-    };
+        return Ok(());
+    }
+
+    // Signing and validating set files need neither a configuration nor the
+    // daemon
+    if let Some(cli::Commands::Sets(cmd)) = &cli.command
+        && let Some(result) = cmd.run_local(&cli.config, &cli.overrides)
+    {
+        if let Err(e) = result {
+            eprintln!("Error executing command: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut config = config::Config::load(&cli.config, &cli.overrides)?;
+    // Following the log lasts until interrupted
+    if let Some(cli::Commands::Logs(cmd)) = &cli.command
+        && cmd.follows()
+    {
+        config.timeout = Some(0);
+    }
+    let client = config.build_client(cli.overrides.token)?;
 
     if let Some(command) = cli.command {
         if let Err(e) = cli::execute(client, command) {
             eprintln!("Error executing command: {e}");
             std::process::exit(1);
-        } else {
-            Ok(())
         }
+        Ok(())
     } else {
         cli::Cli::command()
             .print_help()
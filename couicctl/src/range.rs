@@ -0,0 +1,153 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ipnet::{IpNet, Ipv4Subnets, Ipv6Subnets};
+
+use common::NormalizedCidr;
+use common::cidr::check_unscoped;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RangeError {
+    #[error("Invalid CIDR: {0}")]
+    InvalidCidr(String),
+    #[error("Invalid address in range: {0}")]
+    InvalidAddress(String),
+    #[error("Range mixes IPv4 and IPv6 addresses: {0}")]
+    MixedFamily(String),
+    #[error("Range start is greater than range end: {0}")]
+    Reversed(String),
+}
+
+/// A CIDR or a dash range (e.g. `10.0.0.5-10.0.0.20`) given on the command line
+#[derive(Debug, Clone)]
+pub struct CidrInput(Vec<NormalizedCidr>);
+
+impl CidrInput {
+    #[must_use]
+    pub fn cidrs(&self) -> &[NormalizedCidr] {
+        &self.0
+    }
+}
+
+impl FromStr for CidrInput {
+    type Err = RangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((start, end)) = s.split_once('-') {
+            let cidrs = range_to_cidrs(start.trim(), end.trim())?
+                .into_iter()
+                .map(NormalizedCidr::from)
+                .collect();
+            return Ok(Self(cidrs));
+        }
+
+        s.parse::<NormalizedCidr>()
+            .map(|cidr| Self(vec![cidr]))
+            .map_err(|e| RangeError::InvalidCidr(e.to_string()))
+    }
+}
+
+/// Convert an inclusive address range into the minimal list of covering CIDRs
+pub fn range_to_cidrs(start: &str, end: &str) -> Result<Vec<IpNet>, RangeError> {
+    for addr in [start, end] {
+        check_unscoped(addr).map_err(|e| RangeError::InvalidAddress(e.to_string()))?;
+    }
+    let start_addr = start
+        .parse::<IpAddr>()
+        .map_err(|_| RangeError::InvalidAddress(start.to_string()))?;
+    let end_addr = end
+        .parse::<IpAddr>()
+        .map_err(|_| RangeError::InvalidAddress(end.to_string()))?;
+
+    let same_family = start_addr.is_ipv4() == end_addr.is_ipv4();
+    if same_family && start_addr > end_addr {
+        return Err(RangeError::Reversed(format!("{start}-{end}")));
+    }
+
+    match (start_addr, end_addr) {
+        (IpAddr::V4(s), IpAddr::V4(e)) => Ok(Ipv4Subnets::new(s, e, 0).map(IpNet::V4).collect()),
+        (IpAddr::V6(s), IpAddr::V6(e)) => Ok(Ipv6Subnets::new(s, e, 0).map(IpNet::V6).collect()),
+        _ => Err(RangeError::MixedFamily(format!("{start}-{end}"))),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn to_strings(cidrs: &[NormalizedCidr]) -> Vec<String> {
+        cidrs.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_cidr_input_single_cidr() {
+        let input: CidrInput = "192.168.1.0/24".parse().unwrap();
+        assert_eq!(to_strings(input.cidrs()), ["192.168.1.0/24"]);
+    }
+
+    #[test]
+    fn test_cidr_input_ipv4_range() {
+        let input: CidrInput = "10.0.0.5-10.0.0.20".parse().unwrap();
+        assert_eq!(
+            to_strings(input.cidrs()),
+            [
+                "10.0.0.5/32",
+                "10.0.0.6/31",
+                "10.0.0.8/29",
+                "10.0.0.16/30",
+                "10.0.0.20/32"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cidr_input_aligned_range() {
+        let input: CidrInput = "10.0.0.0 - 10.0.0.255".parse().unwrap();
+        assert_eq!(to_strings(input.cidrs()), ["10.0.0.0/24"]);
+
+        let input: CidrInput = "10.0.0.1-10.0.0.1".parse().unwrap();
+        assert_eq!(to_strings(input.cidrs()), ["10.0.0.1/32"]);
+    }
+
+    #[test]
+    fn test_cidr_input_ipv6_range() {
+        let input: CidrInput = "2001:db8::-2001:db8::ffff".parse().unwrap();
+        assert_eq!(to_strings(input.cidrs()), ["2001:db8::/112"]);
+    }
+
+    #[test]
+    fn test_cidr_input_invalid() {
+        assert!(matches!(
+            "10.0.0.20-10.0.0.5".parse::<CidrInput>(),
+            Err(RangeError::Reversed(_))
+        ));
+        assert!(matches!(
+            "10.0.0.1-2001:db8::1".parse::<CidrInput>(),
+            Err(RangeError::MixedFamily(_))
+        ));
+        assert!(matches!(
+            "2001:db8::1-10.0.0.1".parse::<CidrInput>(),
+            Err(RangeError::MixedFamily(_))
+        ));
+        assert!(matches!(
+            "10.0.0.1-nope".parse::<CidrInput>(),
+            Err(RangeError::InvalidAddress(_))
+        ));
+        assert!(matches!(
+            "not-a-cidr/99".parse::<CidrInput>(),
+            Err(RangeError::InvalidAddress(_))
+        ));
+        assert!(matches!(
+            "nope".parse::<CidrInput>(),
+            Err(RangeError::InvalidCidr(_))
+        ));
+        let err = "fe80::1%eth0/64".parse::<CidrInput>().unwrap_err();
+        assert!(err.to_string().contains("scoped IPv6 address"));
+        let err = "fe80::1%eth0-fe80::9%eth0"
+            .parse::<CidrInput>()
+            .unwrap_err();
+        assert!(err.to_string().contains("scoped IPv6 address"));
+    }
+}
@@ -1,10 +1,19 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use ipnet::IpNet;
 use reqwest::blocking::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 const TIMEOUT: Duration = Duration::from_secs(30);
+/// Timeout of origin lookups, which only annotate an output
+const ORIGIN_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a cached prefix origin is trusted
+const ORIGIN_CACHE_TTL: Duration = Duration::from_hours(24);
+const ORIGIN_CACHE_FILE: &str = "couicctl/prefix-origins.json";
 const USER_AGENT_VALUE: &str = concat!(
     env!("CARGO_PKG_NAME"),
     "/",
@@ -39,6 +48,116 @@ struct Prefix {
     prefix: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct OverviewResponse {
+    data: OverviewData,
+}
+
+#[derive(Deserialize, Debug)]
+struct OverviewData {
+    #[serde(default)]
+    asns: Vec<PrefixOrigin>,
+}
+
+/// Origin AS of an announced prefix and the name of its holder
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PrefixOrigin {
+    pub asn: u32,
+    pub holder: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CachedOrigin {
+    fetched: u64,
+    origin: Option<PrefixOrigin>,
+}
+
+/// Prefix origins looked up from `RIPEstat`, persisted in the user cache
+/// directory so that repeated inspections do not query the API again
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct OriginCache {
+    origins: HashMap<String, CachedOrigin>,
+}
+
+impl OriginCache {
+    /// Load the cache, starting empty when it is missing or unreadable
+    #[must_use]
+    pub fn load() -> Self {
+        cache_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Best effort: a cache that cannot be written is only a missed shortcut
+    pub fn save(&self) {
+        let Some(path) = cache_path() else {
+            return;
+        };
+        if let Some(parent) = path.parent()
+            && fs::create_dir_all(parent).is_err()
+        {
+            return;
+        }
+        if let Ok(content) = serde_json::to_string(self) {
+            let _ = fs::write(path, content);
+        }
+    }
+
+    /// Origin of `prefix`, from the cache when fresh, `Err` when the API
+    /// could not be queried
+    pub fn lookup(&mut self, prefix: &IpNet) -> Result<Option<PrefixOrigin>, RipeError> {
+        let now = unix_now();
+        let key = prefix.to_string();
+        if let Some(cached) = self.fresh(&key, now) {
+            return Ok(cached.origin.clone());
+        }
+        let origin = fetch_prefix_origin(prefix)?;
+        self.origins.insert(
+            key,
+            CachedOrigin {
+                fetched: now,
+                origin: origin.clone(),
+            },
+        );
+        Ok(origin)
+    }
+
+    fn fresh(&self, key: &str, now: u64) -> Option<&CachedOrigin> {
+        self.origins
+            .get(key)
+            .filter(|cached| now.saturating_sub(cached.fetched) < ORIGIN_CACHE_TTL.as_secs())
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(dir.join(ORIGIN_CACHE_FILE))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Fetch the origin AS of a prefix from RIPE NCC `RIPEstat` API, `None` when
+/// the prefix is not announced
+pub fn fetch_prefix_origin(prefix: &IpNet) -> Result<Option<PrefixOrigin>, RipeError> {
+    let url = format!("https://stat.ripe.net/data/prefix-overview/data.json?resource={prefix}");
+
+    let client = Client::builder()
+        .timeout(ORIGIN_TIMEOUT)
+        .user_agent(USER_AGENT_VALUE)
+        .build()?;
+
+    let response: OverviewResponse = client.get(&url).send()?.error_for_status()?.json()?;
+    Ok(response.data.asns.into_iter().next())
+}
+
 /// Fetch announced prefixes for a given ASN from RIPE NCC `RIPEstat` API
 ///
 /// Accepts ASN in format "200373" or "AS200373"
@@ -83,3 +202,45 @@ pub fn fetch_asn_prefixes(asn: &str) -> Result<Vec<IpNet>, RipeError> {
 
     Ok(cidrs)
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overview_first_origin() {
+        let body = r#"{"data": {"asns": [{"asn": 13335, "holder": "CLOUDFLARENET - Cloudflare, Inc."}], "announced": true}}"#;
+        let response: OverviewResponse = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            response.data.asns.first(),
+            Some(&PrefixOrigin {
+                asn: 13335,
+                holder: "CLOUDFLARENET - Cloudflare, Inc.".to_string(),
+            })
+        );
+
+        let response: OverviewResponse =
+            serde_json::from_str(r#"{"data": {"announced": false}}"#).unwrap();
+        assert_eq!(response.data.asns, Vec::new());
+    }
+
+    #[test]
+    fn test_origin_cache_expires() {
+        let mut cache = OriginCache::default();
+        cache.origins.insert(
+            "192.0.2.0/24".to_string(),
+            CachedOrigin {
+                fetched: 1_000,
+                origin: None,
+            },
+        );
+        assert!(cache.fresh("192.0.2.0/24", 1_000).is_some());
+        assert!(
+            cache
+                .fresh("192.0.2.0/24", 1_000 + ORIGIN_CACHE_TTL.as_secs())
+                .is_none()
+        );
+        assert!(cache.fresh("198.51.100.0/24", 1_000).is_none());
+    }
+}
@@ -15,8 +15,11 @@ cd /tmp && hurl --variable expiration_ts=2547914495 \
     /mnt/tests/integration/clients.hurl \
     /mnt/tests/integration/drop.hurl \
     /mnt/tests/integration/ignore.hurl \
+    /mnt/tests/integration/monitor.hurl \
     /mnt/tests/integration/stats.hurl \
-    /mnt/tests/integration/sets.hurl
+    /mnt/tests/integration/health.hurl \
+    /mnt/tests/integration/sets.hurl \
+    /mnt/tests/integration/classify.hurl
 
 # New edge case and validation tests
 cd /tmp && hurl --variable expiration_ts=2547914495 \
@@ -26,7 +29,9 @@ cd /tmp && hurl --variable expiration_ts=2547914495 \
     /mnt/tests/integration/metadata.hurl \
     /mnt/tests/integration/malformed_requests.hurl \
     /mnt/tests/integration/cross_policy.hurl \
-    /mnt/tests/integration/http_methods.hurl
+    /mnt/tests/integration/http_methods.hurl \
+    /mnt/tests/integration/idempotency.hurl \
+    /mnt/tests/integration/replace.hurl
 
 # Temp dirs for sets
 SETS_DIR="/tmp/sets/ignore"